@@ -32,10 +32,24 @@ pub struct PriceData {
     pub prices: Vec<AssetOptionalPrice>,
 }
 
+/// How `get_price_data` misbehaves, to test the failure paths of its callers.
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(crate = "near_sdk::serde")]
+pub enum FailureMode {
+    /// The call panics.
+    Panic,
+    /// Every asset is reported without a price.
+    MissingPrice,
+}
+
 #[near_bindgen]
 #[derive(BorshSerialize, BorshDeserialize, PanicOnDefault)]
 pub struct Contract {
-    prices: HashMap<AssetId, Price>
+    prices: HashMap<AssetId, Price>,
+    /// Reported timestamps lag the block by this much, to serve stale prices.
+    delay_sec: u32,
+    recency_duration_sec: u32,
+    failure_mode: Option<FailureMode>,
 }
 
 #[near_bindgen]
@@ -44,6 +58,9 @@ impl Contract {
     pub fn new() -> Self {
         Self {
             prices: HashMap::new(),
+            delay_sec: 0,
+            recency_duration_sec: 90,
+            failure_mode: None,
         }
     }
 
@@ -51,25 +68,44 @@ impl Contract {
         self.prices.insert(asset_id, price);
     }
 
+    pub fn remove_price_data(&mut self, asset_id: AssetId) {
+        self.prices.remove(&asset_id);
+    }
+
+    pub fn set_delay_sec(&mut self, delay_sec: u32) {
+        self.delay_sec = delay_sec;
+    }
+
+    pub fn set_recency_duration_sec(&mut self, recency_duration_sec: u32) {
+        self.recency_duration_sec = recency_duration_sec;
+    }
+
+    pub fn set_failure_mode(&mut self, failure_mode: Option<FailureMode>) {
+        self.failure_mode = failure_mode;
+    }
+
     pub fn get_price_data(&self, asset_ids: Option<Vec<AssetId>>) -> PriceData {
-        // let asset_ids = asset_ids.unwrap_or(vec![]);
+        if self.failure_mode == Some(FailureMode::Panic) {
+            env::panic(b"Price oracle failure");
+        }
+        let missing_price = self.failure_mode == Some(FailureMode::MissingPrice);
         PriceData {
-            timestamp: env::block_timestamp(),
-            recency_duration_sec: 90,
+            timestamp: env::block_timestamp().saturating_sub(self.delay_sec as u64 * 1_000_000_000),
+            recency_duration_sec: self.recency_duration_sec,
             prices: {
                 let mut res = vec![];
                 if let Some(asset_ids) = asset_ids {
                     for asset_id in asset_ids {
                         res.push(AssetOptionalPrice{
                             asset_id: asset_id.clone(),
-                            price: self.prices.get(&asset_id).cloned(),
+                            price: self.prices.get(&asset_id).cloned().filter(|_| !missing_price),
                         });
                     }
                 } else {
                     for (asset_id, price) in self.prices.iter() {
                         res.push(AssetOptionalPrice{
                             asset_id: asset_id.clone(),
-                            price: Some(price.clone()),
+                            price: Some(price.clone()).filter(|_| !missing_price),
                         });
                     }
                 }
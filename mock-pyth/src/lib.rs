@@ -3,7 +3,7 @@ use std::collections::HashMap;
 use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
 use near_sdk::json_types::{I64, U64};
 use near_sdk::serde::{Deserialize, Serialize};
-use near_sdk::{near_bindgen, PanicOnDefault};
+use near_sdk::{env, near_bindgen, PanicOnDefault};
 
 #[derive(BorshDeserialize, BorshSerialize, Debug, Deserialize, Serialize, Clone)]
 #[serde(crate = "near_sdk::serde")]
@@ -69,10 +69,23 @@ impl near_sdk::serde::Serialize for PriceIdentifier {
     }
 }
 
+/// How `get_price` misbehaves, to test the failure paths of its callers.
+#[derive(BorshDeserialize, BorshSerialize, Debug, Deserialize, Serialize, Clone, PartialEq)]
+#[serde(crate = "near_sdk::serde")]
+pub enum FailureMode {
+    /// The call panics.
+    Panic,
+    /// No price is returned.
+    MissingPrice,
+}
+
 #[near_bindgen]
 #[derive(BorshSerialize, BorshDeserialize, PanicOnDefault)]
 pub struct Contract {
     price_info: HashMap<PriceIdentifier, PythPrice>,
+    /// Returned publish times lag the stored ones by this much, to serve stale prices.
+    delay_sec: u32,
+    failure_mode: Option<FailureMode>,
 }
 
 #[near_bindgen]
@@ -80,10 +93,20 @@ impl Contract {
     #[init]
     pub fn new() -> Self {
         Self {
-            price_info: HashMap::new()
+            price_info: HashMap::new(),
+            delay_sec: 0,
+            failure_mode: None,
         }
     }
 
+    pub fn set_delay_sec(&mut self, delay_sec: u32) {
+        self.delay_sec = delay_sec;
+    }
+
+    pub fn set_failure_mode(&mut self, failure_mode: Option<FailureMode>) {
+        self.failure_mode = failure_mode;
+    }
+
     pub fn set_price(&mut self, price_identifier: PriceIdentifier, pyth_price: PythPrice) {
         self.price_info.insert(price_identifier, pyth_price);
     }
@@ -93,6 +116,13 @@ impl Contract {
     }
 
     pub fn get_price(&self, price_identifier: PriceIdentifier) -> Option<PythPrice> {
-        self.price_info.get(&price_identifier).cloned()
+        match self.failure_mode {
+            Some(FailureMode::Panic) => env::panic(b"Pyth failure"),
+            Some(FailureMode::MissingPrice) => None,
+            None => self.price_info.get(&price_identifier).cloned().map(|mut price| {
+                price.publish_time -= self.delay_sec as i64;
+                price
+            }),
+        }
     }
 }
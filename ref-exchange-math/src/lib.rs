@@ -0,0 +1,38 @@
+//! Swap math of the Ref exchange pools, shared by the contract and off-chain routers
+//! so quotes match what the contract executes. Amounts of stable, rated and degen pools
+//! are in comparable decimal (c_amounts), see the contract for the conversion.
+#![no_std]
+
+extern crate alloc;
+
+use uint::construct_uint;
+
+pub mod simple;
+pub mod stable;
+pub mod rated;
+pub mod degen;
+pub mod range;
+
+pub type Balance = u128;
+/// Nano seconds.
+pub type Timestamp = u64;
+
+/// Fee divisor, allowing to provide fee in bps.
+pub const FEE_DIVISOR: u32 = 10_000;
+
+/// Precision of rated and degen pool rates, matching their comparable decimal of 24.
+pub const PRECISION: u128 = 1_000_000_000_000_000_000_000_000;
+
+construct_uint! {
+    /// 256-bit unsigned integer.
+    pub struct U256(4);
+}
+
+construct_uint! {
+    /// 384-bit unsigned integer.
+    pub struct U384(6);
+}
+
+pub fn u128_ratio(a: u128, num: u128, denom: u128) -> u128 {
+    (U256::from(a) * U256::from(num) / U256::from(denom)).as_u128()
+}
@@ -0,0 +1,70 @@
+//! Math of range pools, constant product pools whose liquidity is concentrated between
+//! a lower and an upper price. Within the range the real reserves trade like a simple pool
+//! over virtual reserves `x + L / sqrt(p_upper)` and `y + L * sqrt(p_lower)`, where L is the
+//! liquidity. Prices are raw token1 per raw token0 in PRECISION, square roots of prices
+//! are in PRECISION as well.
+use crate::{simple, Balance, PRECISION, U256, U384};
+
+/// Square root of a price in PRECISION, in PRECISION.
+pub fn sqrt_price(price: u128) -> u128 {
+    (U256::from(price) * U256::from(PRECISION)).integer_sqrt().as_u128()
+}
+
+/// Liquidity L of the reserves, solving `(x + L / sqrt_upper) * (y + L * sqrt_lower) = L^2`.
+/// sqrt_lower must be below sqrt_upper.
+pub fn get_liquidity(amounts: &[Balance], sqrt_lower: u128, sqrt_upper: u128) -> Balance {
+    let (x, y) = (U384::from(amounts[0]), U384::from(amounts[1]));
+    let (sa, sb, s) = (U384::from(sqrt_lower), U384::from(sqrt_upper), U384::from(PRECISION));
+    let b = x * sa / s + y * s / sb;
+    let discriminant = b * b + U384::from(4u8) * x * y * (sb - sa) / sb;
+    (sb * (b + discriminant.integer_sqrt()) / (U384::from(2u8) * (sb - sa))).as_u128()
+}
+
+/// Virtual reserves the real `amounts` trade on for the liquidity.
+pub fn virtual_amounts(amounts: &[Balance], liquidity: Balance, sqrt_lower: u128, sqrt_upper: u128) -> [Balance; 2] {
+    [
+        amounts[0] + (U256::from(liquidity) * U256::from(PRECISION) / U256::from(sqrt_upper)).as_u128(),
+        amounts[1] + (U256::from(liquidity) * U256::from(sqrt_lower) / U256::from(PRECISION)).as_u128(),
+    ]
+}
+
+/// Amount out of token_out for amount_in of token_in with the total fee in bps, None if it
+/// would take more than the real reserve of token_out, pushing the price out of the range.
+/// Real reserves must not be both empty and amount_in must be positive.
+pub fn get_amount_out(
+    amounts: &[Balance],
+    token_in: usize,
+    amount_in: Balance,
+    sqrt_lower: u128,
+    sqrt_upper: u128,
+    total_fee: u32,
+) -> Option<Balance> {
+    let token_out = 1 - token_in;
+    let liquidity = get_liquidity(amounts, sqrt_lower, sqrt_upper);
+    let virtuals = virtual_amounts(amounts, liquidity, sqrt_lower, sqrt_upper);
+    let amount_out = simple::get_amount_out(virtuals[token_in], virtuals[token_out], amount_in, total_fee);
+    if amount_out < amounts[token_out] {
+        Some(amount_out)
+    } else {
+        None
+    }
+}
+
+/// Amount in of token_in needed for amount_out of token_out with the total fee in bps, rounded up,
+/// None if amount_out is not below the real reserve of token_out.
+pub fn get_amount_in(
+    amounts: &[Balance],
+    token_in: usize,
+    amount_out: Balance,
+    sqrt_lower: u128,
+    sqrt_upper: u128,
+    total_fee: u32,
+) -> Option<Balance> {
+    let token_out = 1 - token_in;
+    if amount_out >= amounts[token_out] {
+        return None;
+    }
+    let liquidity = get_liquidity(amounts, sqrt_lower, sqrt_upper);
+    let virtuals = virtual_amounts(amounts, liquidity, sqrt_lower, sqrt_upper);
+    Some(simple::get_amount_in(virtuals[token_in], virtuals[token_out], amount_out, total_fee))
+}
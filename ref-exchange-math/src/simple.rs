@@ -0,0 +1,31 @@
+//! Constant product math of simple pools.
+use crate::{Balance, FEE_DIVISOR, U256};
+
+/// Amount out for amount_in with the total fee in bps taken from the input.
+/// Reserves and amount_in must be positive.
+pub fn get_amount_out(in_balance: Balance, out_balance: Balance, amount_in: Balance, total_fee: u32) -> Balance {
+    let amount_with_fee = U256::from(amount_in) * U256::from(FEE_DIVISOR - total_fee);
+    (amount_with_fee * U256::from(out_balance) / (U256::from(FEE_DIVISOR) * U256::from(in_balance) + amount_with_fee))
+        .as_u128()
+}
+
+/// Amount in needed for amount_out with the total fee in bps, rounded up.
+/// Reserves must be positive and amount_out below out_balance.
+pub fn get_amount_in(in_balance: Balance, out_balance: Balance, amount_out: Balance, total_fee: u32) -> Balance {
+    let numerator = U256::from(in_balance) * U256::from(amount_out) * U256::from(FEE_DIVISOR);
+    let denominator = (U256::from(out_balance) - U256::from(amount_out)) * U256::from(FEE_DIVISOR - total_fee);
+    (numerator / denominator + U256::one()).as_u128()
+}
+
+/// Part of `amount` to swap into the other token so the rest and the swap output match
+/// the pool ratio after the swap, for adding `amount` of a single token as liquidity.
+/// in_balance must be positive.
+pub fn get_zap_swap_amount(in_balance: Balance, amount: Balance, total_fee: u32) -> Balance {
+    // Solves (amount - s) / (in_balance + s) = out / (out_balance - out) for the swap of s.
+    let reserve = U256::from(in_balance);
+    let fee_divisor = U256::from(FEE_DIVISOR);
+    let after_fee = U256::from(FEE_DIVISOR - total_fee);
+    let b = reserve * (fee_divisor + after_fee);
+    let discriminant = b * b + U256::from(4u8) * after_fee * fee_divisor * U256::from(amount) * reserve;
+    ((discriminant.integer_sqrt() - b) / (U256::from(2u8) * after_fee)).as_u128()
+}
@@ -1,9 +1,9 @@
 ///! Calculator to maintain the invariant on adding/removing liquidity and on swapping.
 ///! Large part of the code was taken from https://github.com/saber-hq/stable-swap/blob/master/stable-swap-math/src/curve.rs
-use near_sdk::{Balance, Timestamp};
+use alloc::vec;
+use alloc::vec::Vec;
 
-use crate::admin_fee::AdminFees;
-use crate::utils::{FEE_DIVISOR, U256, u128_ratio};
+use crate::{u128_ratio, Balance, Timestamp, FEE_DIVISOR, U256};
 
 /// Minimum ramp duration, in nano sec.
 pub const MIN_RAMP_DURATION: Timestamp = 86400 * 1_000_000_000;
@@ -21,10 +21,10 @@ pub struct Fees {
 }
 
 impl Fees {
-    pub fn new(total_fee: u32, fees: &AdminFees) -> Self {
+    pub fn new(total_fee: u32, admin_fee_bps: u32) -> Self {
         Self {
             trade_fee: total_fee,
-            admin_fee: fees.admin_fee_bps,
+            admin_fee: admin_fee_bps,
         }
     }
 
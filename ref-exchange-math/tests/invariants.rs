@@ -0,0 +1,350 @@
+//! Deterministic invariant fuzzing of the pool math. Random swap, add and remove sequences
+//! from fixed seeds check that no operation hands out more than the pool gives up, that the
+//! invariant per share never drops and that share supply matches the shares of the LPs.
+use rand::{Rng, SeedableRng};
+use rand_pcg::Pcg32;
+use ref_exchange_math::{degen, range, rated, simple, stable, U256, PRECISION};
+
+const SEEDS: [u64; 8] = [1, 7, 42, 1_000, 65_537, 123_456_789, 987_654_321, 18_446_744_073];
+const STEPS: usize = 200;
+const LPS: usize = 3;
+/// Rated and degen math converts amounts to and from rates, flooring each time,
+/// which may cost D a few thousand units.
+const RATE_ROUNDING_TOLERANCE: u128 = 10_000;
+
+#[derive(Clone, Copy, Debug)]
+enum Curve {
+    Stable,
+    Rated,
+    Degen,
+}
+
+#[derive(Clone, Debug)]
+struct CurvePool {
+    curve: Curve,
+    amp: u128,
+    rates: Vec<u128>,
+    total_fee: u32,
+    admin_fee: u32,
+    c_amounts: Vec<u128>,
+    shares: Vec<u128>,
+    share_supply: u128,
+}
+
+/// Runs $body with the calculator and fees of the pool's curve bound to $swap and $fees.
+macro_rules! with_curve {
+    ($pool: expr, |$swap: ident, $fees: ident| $body: expr) => {
+        match $pool.curve {
+            Curve::Stable => {
+                let $swap = stable::StableSwap::new($pool.amp, $pool.amp, 0, 0, 0);
+                let $fees = stable::Fees::new($pool.total_fee, $pool.admin_fee);
+                $body
+            }
+            Curve::Rated => {
+                let $swap = rated::RatedSwap::new($pool.amp, $pool.amp, 0, 0, 0, &$pool.rates);
+                let $fees = rated::Fees::new($pool.total_fee, $pool.admin_fee);
+                $body
+            }
+            Curve::Degen => {
+                let $swap = degen::DegenSwap::new($pool.amp, $pool.amp, 0, 0, 0, &$pool.rates);
+                let $fees = degen::Fees::new($pool.total_fee, $pool.admin_fee);
+                $body
+            }
+        }
+    };
+}
+
+impl CurvePool {
+    fn new(rng: &mut Pcg32, curve: Curve) -> Self {
+        let n_coins = rng.gen_range(2..=3);
+        let decimals = match curve {
+            Curve::Stable => 18,
+            Curve::Rated | Curve::Degen => 24,
+        };
+        let rates = (0..n_coins)
+            .map(|_| match curve {
+                Curve::Stable => PRECISION,
+                Curve::Rated | Curve::Degen => PRECISION / 2 + rng.gen_range(0..=3 * PRECISION / 2),
+            })
+            .collect();
+        let c_amounts = (0..n_coins)
+            .map(|_| rng.gen_range(1_000..1_000_000_000u128) * 10u128.pow(decimals))
+            .collect();
+        let mut pool = CurvePool {
+            curve,
+            amp: rng.gen_range(1..=2_000),
+            rates,
+            total_fee: rng.gen_range(0..=100),
+            admin_fee: if rng.gen_bool(0.5) { 0 } else { 2_000 },
+            c_amounts,
+            shares: vec![0; LPS],
+            share_supply: 0,
+        };
+        pool.share_supply = pool.d(&pool.c_amounts);
+        pool.shares[0] = pool.share_supply;
+        pool
+    }
+
+    fn d(&self, c_amounts: &Vec<u128>) -> u128 {
+        let d = match self.curve {
+            Curve::Stable => stable::StableSwap::new(self.amp, self.amp, 0, 0, 0)
+                .compute_d(c_amounts)
+                .map(|d| d.as_u128()),
+            Curve::Rated => rated::RatedSwap::new(self.amp, self.amp, 0, 0, 0, &self.rates)
+                .compute_d_with_rates(c_amounts)
+                .map(|d| d.as_u128()),
+            Curve::Degen => degen::DegenSwap::new(self.amp, self.amp, 0, 0, 0, &self.rates)
+                .compute_d_with_degens(c_amounts)
+                .map(|d| d.as_u128()),
+        };
+        d.expect("D failed to converge")
+    }
+
+    fn rounding_tolerance(&self) -> u128 {
+        match self.curve {
+            // D is floored
+            Curve::Stable => 1,
+            Curve::Rated | Curve::Degen => RATE_ROUNDING_TOLERANCE,
+        }
+    }
+
+    fn assert_invariant_per_share_kept(&self, d_before: u128, supply_before: u128, op: &str) {
+        let d_after = self.d(&self.c_amounts);
+        assert!(
+            U256::from(d_after + self.rounding_tolerance()) * U256::from(supply_before)
+                >= U256::from(d_before) * U256::from(self.share_supply),
+            "{} lowered D per share: {} / {} -> {} / {} in {:?}",
+            op, d_before, supply_before, d_after, self.share_supply, self
+        );
+        assert_eq!(self.shares.iter().sum::<u128>(), self.share_supply, "{} broke share supply", op);
+        assert!(self.c_amounts.iter().all(|amount| *amount > 0), "{} drained a reserve", op);
+    }
+
+    fn swap(&self, token_in: usize, amount_in: u128, token_out: usize) -> Option<(u128, u128, u128, u128)> {
+        with_curve!(self, |swap, fees| swap
+            .swap_to(token_in, amount_in, token_out, &self.c_amounts, &fees)
+            .map(|result| (result.new_source_amount, result.new_destination_amount, result.amount_swapped, result.admin_fee)))
+    }
+
+    fn step_swap(&mut self, rng: &mut Pcg32) {
+        let n_coins = self.c_amounts.len();
+        let token_in = rng.gen_range(0..n_coins);
+        let token_out = (token_in + rng.gen_range(1..n_coins)) % n_coins;
+        let amount_in = self.c_amounts[token_in] / 10_000 * rng.gen_range(1..=1_000);
+        let (d_before, supply_before) = (self.d(&self.c_amounts), self.share_supply);
+        let (new_source, new_destination, amount_out, admin_fee) = match self.swap(token_in, amount_in, token_out) {
+            Some(result) => result,
+            None => return,
+        };
+        assert!(new_source <= self.c_amounts[token_in] + amount_in, "swap booked more than received");
+        assert!(
+            self.c_amounts[token_out] - new_destination >= amount_out + admin_fee,
+            "swap paid out more than the reserve gave up"
+        );
+        self.c_amounts[token_in] = new_source;
+        self.c_amounts[token_out] = new_destination;
+        self.assert_invariant_per_share_kept(d_before, supply_before, "swap");
+
+        if amount_out > 0 {
+            if let Some((_, _, amount_back, _)) = self.swap(token_out, amount_out, token_in) {
+                assert!(
+                    amount_back <= amount_in + self.rounding_tolerance(),
+                    "round trip returned {} for {}", amount_back, amount_in
+                );
+            }
+        }
+    }
+
+    fn step_add_liquidity(&mut self, rng: &mut Pcg32) {
+        let lp = rng.gen_range(0..LPS);
+        let amounts: Vec<u128> = self
+            .c_amounts
+            .iter()
+            .map(|amount| if rng.gen_bool(0.8) { amount / 10_000 * rng.gen_range(0..=2_000) } else { 0 })
+            .collect();
+        let (d_before, supply_before) = (self.d(&self.c_amounts), self.share_supply);
+        let mint = match with_curve!(self, |swap, fees| swap
+            .compute_lp_amount_for_deposit(&amounts, &self.c_amounts, self.share_supply, &fees))
+        {
+            Some((mint, _)) => mint,
+            None => {
+                assert!(amounts.iter().all(|amount| *amount == 0), "deposit of {:?} rejected", amounts);
+                return;
+            }
+        };
+        for (amount, deposit) in self.c_amounts.iter_mut().zip(amounts.iter()) {
+            *amount += deposit;
+        }
+        self.shares[lp] += mint;
+        self.share_supply += mint;
+        self.assert_invariant_per_share_kept(d_before, supply_before, "add liquidity");
+    }
+
+    fn step_remove_liquidity(&mut self, rng: &mut Pcg32) {
+        let lp = rng.gen_range(0..LPS);
+        let burn = self.shares[lp] / 100 * rng.gen_range(0..=50);
+        if burn == 0 {
+            return;
+        }
+        let (d_before, supply_before) = (self.d(&self.c_amounts), self.share_supply);
+        for amount in self.c_amounts.iter_mut() {
+            *amount -= (U256::from(*amount) * U256::from(burn) / U256::from(supply_before)).as_u128();
+        }
+        self.shares[lp] -= burn;
+        self.share_supply -= burn;
+        self.assert_invariant_per_share_kept(d_before, supply_before, "remove liquidity");
+    }
+
+    fn step_remove_liquidity_by_tokens(&mut self, rng: &mut Pcg32) {
+        let lp = rng.gen_range(0..LPS);
+        let amounts: Vec<u128> = self
+            .c_amounts
+            .iter()
+            .map(|amount| if rng.gen_bool(0.7) { amount / 10_000 * rng.gen_range(0..=1_000) } else { 0 })
+            .collect();
+        let (d_before, supply_before) = (self.d(&self.c_amounts), self.share_supply);
+        let burn = match with_curve!(self, |swap, fees| swap
+            .compute_lp_amount_for_withdraw(&amounts, &self.c_amounts, self.share_supply, &fees))
+        {
+            Some((burn, _)) => burn,
+            None => {
+                assert!(amounts.iter().all(|amount| *amount == 0), "withdrawal of {:?} rejected", amounts);
+                return;
+            }
+        };
+        assert!(burn > 0, "withdrawal of {:?} burnt no shares", amounts);
+        if burn > self.shares[lp] {
+            return;
+        }
+        for (amount, withdrawal) in self.c_amounts.iter_mut().zip(amounts.iter()) {
+            *amount -= withdrawal;
+        }
+        self.shares[lp] -= burn;
+        self.share_supply -= burn;
+        self.assert_invariant_per_share_kept(d_before, supply_before, "remove liquidity by tokens");
+    }
+}
+
+fn run_curve(curve: Curve) {
+    for seed in SEEDS.iter() {
+        let mut rng = Pcg32::seed_from_u64(*seed);
+        let mut pool = CurvePool::new(&mut rng, curve);
+        for _ in 0..STEPS {
+            match rng.gen_range(0..4) {
+                0 => pool.step_swap(&mut rng),
+                1 => pool.step_add_liquidity(&mut rng),
+                2 => pool.step_remove_liquidity(&mut rng),
+                _ => pool.step_remove_liquidity_by_tokens(&mut rng),
+            }
+        }
+    }
+}
+
+#[test]
+fn test_stable_invariants() {
+    run_curve(Curve::Stable);
+}
+
+#[test]
+fn test_rated_invariants() {
+    run_curve(Curve::Rated);
+}
+
+#[test]
+fn test_degen_invariants() {
+    run_curve(Curve::Degen);
+}
+
+#[test]
+fn test_simple_invariants() {
+    for seed in SEEDS.iter() {
+        let mut rng = Pcg32::seed_from_u64(*seed);
+        let total_fee = rng.gen_range(0..=100);
+        let mut amounts = [
+            rng.gen_range(1_000..1_000_000_000u128) * 10u128.pow(24),
+            rng.gen_range(1_000..1_000_000_000u128) * 10u128.pow(18),
+        ];
+        for _ in 0..STEPS {
+            let token_in = rng.gen_range(0..2);
+            let token_out = 1 - token_in;
+            let amount_in = amounts[token_in] / 10_000 * rng.gen_range(1..=1_000);
+            let k_before = U256::from(amounts[0]) * U256::from(amounts[1]);
+            let amount_out = simple::get_amount_out(amounts[token_in], amounts[token_out], amount_in, total_fee);
+            assert!(amount_out < amounts[token_out], "swap drained the pool");
+            let amount_needed = simple::get_amount_in(amounts[token_in], amounts[token_out], amount_out, total_fee);
+            assert!(amount_needed <= amount_in + 1, "{} needed for what {} bought", amount_needed, amount_in);
+            assert!(
+                simple::get_amount_out(amounts[token_in], amounts[token_out], amount_needed, total_fee) >= amount_out,
+                "amount in of {} falls short of {}", amount_needed, amount_out
+            );
+            amounts[token_in] += amount_in;
+            amounts[token_out] -= amount_out;
+            assert!(U256::from(amounts[0]) * U256::from(amounts[1]) >= k_before, "swap lowered k");
+            let amount_back = simple::get_amount_out(amounts[token_out], amounts[token_in], amount_out, total_fee);
+            assert!(amount_back <= amount_in, "round trip returned {} for {}", amount_back, amount_in);
+        }
+    }
+}
+
+#[test]
+fn test_simple_zap() {
+    for seed in SEEDS.iter() {
+        let mut rng = Pcg32::seed_from_u64(*seed);
+        let total_fee = rng.gen_range(0..=100);
+        let amounts = [
+            rng.gen_range(1_000..1_000_000_000u128) * 10u128.pow(24),
+            rng.gen_range(1_000..1_000_000_000u128) * 10u128.pow(18),
+        ];
+        for _ in 0..STEPS {
+            let token_in = rng.gen_range(0..2);
+            let token_out = 1 - token_in;
+            let amount = amounts[token_in] / 100_000 * rng.gen_range(1..=100_000);
+            let swap_amount = simple::get_zap_swap_amount(amounts[token_in], amount, total_fee);
+            assert!(swap_amount < amount, "zap swaps {} of {}", swap_amount, amount);
+            let amount_out = simple::get_amount_out(amounts[token_in], amounts[token_out], swap_amount, total_fee);
+            // What is left of the input and the output are in the ratio of the pool after the swap.
+            let left = U256::from(amount - swap_amount) * U256::from(amounts[token_out] - amount_out);
+            let right = U256::from(amount_out) * U256::from(amounts[token_in] + swap_amount);
+            let diff = if left > right { left - right } else { right - left };
+            assert!(diff <= right / U256::from(1_000_000u64) + U256::from(amounts[token_in]), "zap of {} leaves {} vs {}", amount, left, right);
+        }
+    }
+}
+
+#[test]
+fn test_range_invariants() {
+    for seed in SEEDS.iter() {
+        let mut rng = Pcg32::seed_from_u64(*seed);
+        let total_fee = rng.gen_range(0..=100);
+        // Raw price of 1e18-decimal token1 per 1e24-decimal token0 around 1e-6, in a band of up to 10%.
+        let price_lower = rng.gen_range(900_000..1_000_000u128) * 10u128.pow(12);
+        let price_upper = price_lower + price_lower / 1_000 * rng.gen_range(1..=100);
+        let (sqrt_lower, sqrt_upper) = (range::sqrt_price(price_lower), range::sqrt_price(price_upper));
+        let mut amounts = [
+            rng.gen_range(1_000..1_000_000_000u128) * 10u128.pow(24),
+            rng.gen_range(1_000..1_000_000_000u128) * 10u128.pow(18),
+        ];
+        for _ in 0..STEPS {
+            let token_in = rng.gen_range(0..2);
+            let token_out = 1 - token_in;
+            let amount_in = amounts[token_in] / 10_000 * rng.gen_range(1..=1_000);
+            let liquidity = range::get_liquidity(&amounts, sqrt_lower, sqrt_upper);
+            let amount_out = match range::get_amount_out(&amounts, token_in, amount_in, sqrt_lower, sqrt_upper, total_fee) {
+                Some(amount_out) => amount_out,
+                None => continue,
+            };
+            let amount_needed = range::get_amount_in(&amounts, token_in, amount_out, sqrt_lower, sqrt_upper, total_fee).unwrap();
+            assert!(amount_needed <= amount_in + 1, "{} needed for what {} bought", amount_needed, amount_in);
+            amounts[token_in] += amount_in;
+            amounts[token_out] -= amount_out;
+            assert!(amounts[token_out] > 0, "swap drained the pool");
+            assert!(range::get_liquidity(&amounts, sqrt_lower, sqrt_upper) >= liquidity, "swap lowered liquidity");
+            let virtuals = range::virtual_amounts(&amounts, liquidity, sqrt_lower, sqrt_upper);
+            let price = U256::from(virtuals[1]) * U256::from(PRECISION) / U256::from(virtuals[0]);
+            assert!(price >= U256::from(price_lower) && price <= U256::from(price_upper), "price left the range");
+            if let Some(amount_back) = range::get_amount_out(&amounts, token_out, amount_out, sqrt_lower, sqrt_upper, total_fee) {
+                assert!(amount_back <= amount_in, "round trip returned {} for {}", amount_back, amount_in);
+            }
+        }
+    }
+}
@@ -29,6 +29,19 @@ const ACC_ID_AS_KEY_STORAGE: StorageUsage = ACC_ID_STORAGE + 4;
 const KEY_PREFIX_ACC: StorageUsage = 64;
 /// As a near_sdk::collection key, 1 byte for prefiex
 const ACC_ID_AS_CLT_KEY_STORAGE: StorageUsage = ACC_ID_AS_KEY_STORAGE + 1;
+/// Longest memo a deposit or withdrawal may carry, in bytes.
+pub const MAX_MEMO_LEN: usize = 256;
+
+pub fn assert_memo_valid(memo: &str) {
+    assert!(!memo.is_empty() && memo.len() <= MAX_MEMO_LEN, "Memo must be 1 to {} bytes", MAX_MEMO_LEN);
+}
+
+/// Entries of the account in the replica export index, paid once out of a new account's
+/// registration deposit. Accounts registered before the index are grandfathered.
+/// a Vector slot, 1 byte prefix + U64_STORAGE index as key and the account id as value,
+/// + a LookupMap position, the account id as collection key and U64_STORAGE as value.
+pub const REPLICA_INDEX_STORAGE: StorageUsage =
+    1 + U64_STORAGE + ACC_ID_AS_KEY_STORAGE + ACC_ID_AS_CLT_KEY_STORAGE + U64_STORAGE;
 
 // ACC_ID: the Contract accounts map key length
 // + VAccount enum: 1 byte
@@ -116,7 +129,7 @@ impl Account {
     /// Deposit amount to the balance of given token,
     /// if given token not register and not enough storage, deposit fails 
     pub(crate) fn deposit_with_storage_check(&mut self, token: &AccountId, amount: Balance) -> bool { 
-        if let Some(balance) = self.tokens.get(token) {
+        let deposited = if let Some(balance) = self.tokens.get(token) {
             // token has been registered, just add without storage check, 
             let new_balance = balance + amount;
             self.tokens.insert(token, &new_balance);
@@ -134,7 +147,11 @@ impl Account {
                 self.tokens.remove(token);
                 false
             }
+        };
+        if deposited {
+            update_token_ledger(token, |ledger| ledger.inner_balances += amount as i128);
         }
+        deposited
     }
 
     /// Deposit amount to the balance of given token.
@@ -148,6 +165,7 @@ impl Account {
             } else {
                 self.tokens.insert(token, &amount);
             }
+            update_token_ledger(token, |ledger| ledger.inner_balances += amount as i128);
         }
     }
 
@@ -165,6 +183,7 @@ impl Account {
             } else {
                 env::panic(ERR21_TOKEN_NOT_REG.as_bytes());
             }
+            update_token_ledger(token, |ledger| ledger.inner_balances -= amount as i128);
         }
     }
 
@@ -203,6 +222,11 @@ impl Account {
         INIT_ACCOUNT_STORAGE as Balance * env::storage_byte_cost()
     }
 
+    /// Returns what a new account pays once on registration for its replica export index entries.
+    pub fn registration_fee() -> Balance {
+        REPLICA_INDEX_STORAGE as Balance * env::storage_byte_cost()
+    }
+
     /// Registers given token and set balance to 0.
     pub(crate) fn register(&mut self, token_ids: &Vec<ValidAccountId>) {
         for token_id in token_ids {
@@ -333,6 +357,7 @@ impl Contract {
         let sender_id = env::predecessor_account_id();
         let mut account = self.internal_unwrap_account(&sender_id);
         for token_id in token_ids {
+            assert_no_in_flight(&sender_id, Some(token_id.as_ref()));
             account.unregister(token_id.as_ref());
         }
         self.internal_save_account(&sender_id, account);
@@ -345,7 +370,7 @@ impl Contract {
         account.get_balance(&token_id).is_some()
     }
 
-    /// Withdraws given token from the deposits of given user.
+    /// Withdraws given token from the deposits of given user, less the token's withdraw fee if any.
     /// a zero amount means to withdraw all in user's inner account.
     /// Optional unregister will try to remove record of this token from AccountDeposit for given user.
     /// Unregister will fail if the left over balance is non 0.
@@ -357,35 +382,42 @@ impl Contract {
         unregister: Option<bool>,
         skip_unwrap_near: Option<bool>
     ) -> Promise {
-        assert_one_yocto();
-        self.assert_contract_running();
-        let token_id: AccountId = token_id.into();
-        // feature frozenlist
-        self.assert_no_frozen_tokens(&[token_id.clone()]);
-        let sender_id = env::predecessor_account_id();
-        let mut account = self.internal_unwrap_account(&sender_id);
-        
-        // get full amount if amount param is 0
-        let mut amount: u128 = amount.into();
-        if amount == 0 {
-            amount = account.get_balance(&token_id).expect(ERR21_TOKEN_NOT_REG);
-        }
-        assert!(amount > 0, "{}", ERR29_ILLEGAL_WITHDRAW_AMOUNT);
-        
-        // Note: subtraction and deregistration will be reverted if the promise fails.
-        account.withdraw(&token_id, amount);
-        if unregister == Some(true) {
-            account.unregister(&token_id);
-        }
-        self.internal_save_account(&sender_id, account);
-        self.internal_send_tokens(&sender_id, &token_id, amount, skip_unwrap_near)
+        self.internal_withdraw(token_id, amount, unregister, skip_unwrap_near, None).1
     }
 
+    /// Same as `withdraw`, the memo is echoed in a withdraw event for reconciliation.
+    #[payable]
+    pub fn withdraw_with_memo(
+        &mut self,
+        token_id: ValidAccountId,
+        amount: U128,
+        unregister: Option<bool>,
+        skip_unwrap_near: Option<bool>,
+        memo: String,
+    ) -> Promise {
+        assert_memo_valid(&memo);
+        let token: AccountId = token_id.clone().into();
+        let (amount, promise) = self.internal_withdraw(token_id, amount, unregister, skip_unwrap_near, None);
+        event::Event::Withdraw {
+            account_id: &env::predecessor_account_id(),
+            token_id: &token,
+            amount: U128(amount),
+            memo: &memo,
+        }.emit();
+        promise
+    }
+
+    /// `token_id` is None for withdrawals started before multiple wrapped NEAR tokens were supported,
+    /// `fee` for withdrawals started before withdraw fees were held until the transfer resolves.
+    /// `receiver_id` is the wallet paid when it isn't the sender, who gets a failed transfer back.
     #[private]
     pub fn exchange_callback_post_withdraw_near(
         &mut self,
         sender_id: AccountId,
         amount: U128,
+        token_id: Option<AccountId>,
+        fee: Option<U128>,
+        receiver_id: Option<AccountId>,
     ) -> U128 {
         assert_eq!(
             env::promise_results_count(),
@@ -393,19 +425,32 @@ impl Contract {
             "{}",
             ERR25_CALLBACK_POST_WITHDRAW_INVALID
         );
+        let token_id = token_id.unwrap_or_else(|| self.wnear_id.clone().unwrap());
+        // The withdraw fee was held out of the amount sent, see `internal_send_tokens`.
+        let fee = fee.map(|fee| fee.0).unwrap_or(0);
+        let held = amount.0 + fee;
+        release_in_flight(&sender_id, &token_id);
+        settle_pending_withdrawal(&sender_id, &token_id, held);
+        let succeeded = matches!(env::promise_result(0), PromiseResult::Successful(_));
+        update_token_ledger(&token_id, |ledger| {
+            ledger.pending_withdrawals = ledger.pending_withdrawals.saturating_sub(held);
+            if succeeded {
+                ledger.total -= amount.0 as i128;
+            }
+        });
         match env::promise_result(0) {
             PromiseResult::NotReady => unreachable!(),
             PromiseResult::Successful(_) => {
-                Promise::new(sender_id).transfer(amount.into());
+                self.internal_credit_withdraw_fee(&token_id, fee);
+                Promise::new(receiver_id.unwrap_or(sender_id)).transfer(amount.into());
                 amount
             },
             PromiseResult::Failed => {
-                // This reverts the changes from withdraw function.
-                // If account doesn't exit, deposits to the owner's account as lostfound.
+                // This reverts the changes from withdraw function, the withdraw fee included.
+                // If account doesn't exit, holds it for the account to reclaim, or as lostfound once too many are held.
                 let mut failed = false;
-                let token_id = self.wnear_id.as_ref().unwrap().clone();
                 if let Some(mut account) = self.internal_get_account(&sender_id) {
-                    if account.deposit_with_storage_check(&token_id, amount.0) {
+                    if account.deposit_with_storage_check(&token_id, held) {
                         // cause storage already checked, here can directly save
                         self.accounts.insert(&sender_id, &account.into());
                     } else {
@@ -414,7 +459,7 @@ impl Contract {
                         // so, here we can just leave it without insert, won't cause storage collection inconsistency.
                         env::log(
                             format!(
-                                "Account {} has not enough storage. Depositing to owner.",
+                                "Account {} has not enough storage. Holding it for reclaim.",
                                 sender_id
                             )
                             .as_bytes(),
@@ -424,7 +469,7 @@ impl Contract {
                 } else {
                     env::log(
                         format!(
-                            "Account {} is not registered. Depositing to owner.",
+                            "Account {} is not registered. Holding it for reclaim.",
                             sender_id
                         )
                         .as_bytes(),
@@ -432,19 +477,22 @@ impl Contract {
                     failed = true;
                 }
                 if failed {
-                    self.internal_lostfound(&token_id, amount.0);
+                    self.internal_hold_unclaimed_withdrawal(&sender_id, &token_id, held);
                 }
                 0.into()
             }
         }
     }
         
+    /// `receiver_id` is the wallet paid when it isn't the sender, who gets a failed transfer back.
     #[private]
     pub fn exchange_callback_post_withdraw(
         &mut self,
         token_id: AccountId,
         sender_id: AccountId,
         amount: U128,
+        fee: Option<U128>,
+        receiver_id: Option<AccountId>,
     ) -> U128 {
         assert_eq!(
             env::promise_results_count(),
@@ -452,15 +500,33 @@ impl Contract {
             "{}",
             ERR25_CALLBACK_POST_WITHDRAW_INVALID
         );
+        // The withdraw fee was held out of the amount sent, see `internal_send_tokens`.
+        let fee = fee.map(|fee| fee.0).unwrap_or(0);
+        let held = amount.0 + fee;
+        release_in_flight(&sender_id, &token_id);
+        settle_pending_withdrawal(&sender_id, &token_id, held);
+        let succeeded = matches!(env::promise_result(0), PromiseResult::Successful(_));
+        update_token_ledger(&token_id, |ledger| {
+            ledger.pending_withdrawals = ledger.pending_withdrawals.saturating_sub(held);
+            if succeeded {
+                ledger.total -= amount.0 as i128;
+            }
+        });
         match env::promise_result(0) {
             PromiseResult::NotReady => unreachable!(),
-            PromiseResult::Successful(_) => amount,
+            PromiseResult::Successful(_) => {
+                self.internal_credit_withdraw_fee(&token_id, fee);
+                amount
+            }
             PromiseResult::Failed => {
-                // This reverts the changes from withdraw function.
-                // If account doesn't exit, deposits to the owner's account as lostfound.
+                if let Some(receiver_id) = receiver_id {
+                    log!("Transfer to {} failed, returning it to {}", receiver_id, sender_id);
+                }
+                // This reverts the changes from withdraw function, the withdraw fee included.
+                // If account doesn't exit, holds it for the account to reclaim, or as lostfound once too many are held.
                 let mut failed = false;
                 if let Some(mut account) = self.internal_get_account(&sender_id) {
-                    if account.deposit_with_storage_check(&token_id, amount.0) {
+                    if account.deposit_with_storage_check(&token_id, held) {
                         // cause storage already checked, here can directly save
                         self.accounts.insert(&sender_id, &account.into());
                     } else {
@@ -469,7 +535,7 @@ impl Contract {
                         // so, here we can just leave it without insert, won't cause storage collection inconsistency.
                         env::log(
                             format!(
-                                "Account {} has not enough storage. Depositing to owner.",
+                                "Account {} has not enough storage. Holding it for reclaim.",
                                 sender_id
                             )
                             .as_bytes(),
@@ -479,7 +545,7 @@ impl Contract {
                 } else {
                     env::log(
                         format!(
-                            "Account {} is not registered. Depositing to owner.",
+                            "Account {} is not registered. Holding it for reclaim.",
                             sender_id
                         )
                         .as_bytes(),
@@ -487,7 +553,7 @@ impl Contract {
                     failed = true;
                 }
                 if failed {
-                    self.internal_lostfound(&token_id, amount.0);
+                    self.internal_hold_unclaimed_withdrawal(&sender_id, &token_id, held);
                 }
                 0.into()
             }
@@ -499,8 +565,20 @@ impl Contract {
 
     /// Checks that account has enough storage to be stored and saves it into collection.
     /// This should be only place to directly use `self.accounts`.
-    pub(crate) fn internal_save_account(&mut self, account_id: &AccountId, account: Account) {
+    /// A new account pays its registration fee out of its NEAR here.
+    pub(crate) fn internal_save_account(&mut self, account_id: &AccountId, mut account: Account) {
+        let registered = self.accounts.contains_key(account_id);
+        if !registered {
+            account.near_amount = account.near_amount
+                .checked_sub(Account::registration_fee())
+                .expect(ERR11_INSUFFICIENT_STORAGE);
+        }
+        let storage_usage = account.storage_usage();
+        self.internal_top_up_storage(account_id, &mut account, storage_usage);
         account.assert_storage_usage();
+        if index_replica_account(account_id) {
+            bump_state_version();
+        }
         self.accounts.insert(&account_id, &account.into());
     }
 
@@ -525,7 +603,7 @@ impl Contract {
     /// storage withdraw
     pub(crate) fn internal_storage_withdraw(&mut self, account_id: &AccountId, amount: Balance) -> u128 {
         let mut account = self.internal_unwrap_account(&account_id);
-        let available = account.storage_available();
+        let available = self.internal_storage_available(account_id, &account);
         assert!(available > 0, "{}", ERR15_NO_STORAGE_CAN_WITHDRAW);
         let mut withdraw_amount = amount;
         if amount == 0 {
@@ -583,8 +661,51 @@ impl Contract {
             .unwrap_or(0)
     }
 
+    /// Withdraws from the caller's deposit, see `withdraw`, to the receiver if given.
+    /// Returns the amount withdrawn.
+    pub(crate) fn internal_withdraw(
+        &mut self,
+        token_id: ValidAccountId,
+        amount: U128,
+        unregister: Option<bool>,
+        skip_unwrap_near: Option<bool>,
+        receiver_id: Option<&AccountId>,
+    ) -> (Balance, Promise) {
+        assert_one_yocto();
+        self.assert_contract_running();
+        let token_id: AccountId = token_id.into();
+        // feature frozenlist
+        self.assert_no_frozen_tokens(&[token_id.clone()]);
+        let sender_id = env::predecessor_account_id();
+        let mut account = self.internal_unwrap_account(&sender_id);
+        
+        // get full amount if amount param is 0
+        let mut amount: u128 = amount.into();
+        if amount == 0 {
+            amount = account.get_balance(&token_id).expect(ERR21_TOKEN_NOT_REG);
+        }
+        assert!(amount > 0, "{}", ERR29_ILLEGAL_WITHDRAW_AMOUNT);
+        if unregister == Some(true) {
+            // a pending refund of this token would otherwise end up in lostfound
+            assert_no_in_flight(&sender_id, Some(&token_id));
+        }
+        
+        self.internal_record_limited_withdrawal(&sender_id, &token_id, amount);
+
+        // Note: subtraction and deregistration will be reverted if the promise fails.
+        account.withdraw(&token_id, amount);
+        if unregister == Some(true) {
+            account.unregister(&token_id);
+        }
+        self.internal_save_account(&sender_id, account);
+        let amount_sent = amount - withdraw_fee_of(&token_id, amount);
+        (amount_sent, self.internal_send_tokens_to(&sender_id, receiver_id.unwrap_or(&sender_id), &token_id, amount, skip_unwrap_near))
+    }
+
     /// Sends given amount to given user and if it fails, returns it back to user's balance.
     /// Tokens must already be subtracted from internal balance.
+    /// The token's withdraw fee is held out of the amount until the transfer resolves,
+    /// it goes to the fee recipient if the transfer succeeds and back with the amount otherwise.
     pub(crate) fn internal_send_tokens(
         &self,
         sender_id: &AccountId,
@@ -592,12 +713,26 @@ impl Contract {
         amount: Balance,
         skip_unwrap_near: Option<bool>,
     ) -> Promise {
-        let is_wnear_id = if let Some(wnear_id) = self.wnear_id.as_ref() {
-            token_id == wnear_id
-        } else {
-            false
-        };
-        if is_wnear_id && !skip_unwrap_near.unwrap_or(true) {
+        self.internal_send_tokens_to(sender_id, sender_id, token_id, amount, skip_unwrap_near)
+    }
+
+    /// Same as `internal_send_tokens` with the tokens sent to another wallet. The in-flight marker
+    /// and a failed transfer stay with the sender, whose balance they were taken from.
+    pub(crate) fn internal_send_tokens_to(
+        &self,
+        sender_id: &AccountId,
+        receiver_id: &AccountId,
+        token_id: &AccountId,
+        amount: Balance,
+        skip_unwrap_near: Option<bool>,
+    ) -> Promise {
+        let receiver = if receiver_id != sender_id { Some(receiver_id.clone()) } else { None };
+        acquire_in_flight(sender_id, token_id);
+        update_token_ledger(token_id, |ledger| ledger.pending_withdrawals += amount);
+        add_pending_withdrawal(sender_id, token_id, amount);
+        let fee = withdraw_fee_of(token_id, amount);
+        let amount = amount - fee;
+        if self.is_wrapped_near(token_id) && !skip_unwrap_near.unwrap_or(true) {
             ext_wrap_near::near_withdraw(
                 U128(amount),
                 token_id,
@@ -607,13 +742,16 @@ impl Contract {
             .then(ext_self::exchange_callback_post_withdraw_near(
                 sender_id.clone(),
                 U128(amount),
+                Some(token_id.clone()),
+                Some(U128(fee)),
+                receiver,
                 &env::current_account_id(),
                 0,
                 GAS_FOR_RESOLVE_TRANSFER,
             ))
         } else {
             ext_fungible_token::ft_transfer(
-                sender_id.clone(),
+                receiver_id.clone(),
                 U128(amount),
                 None,
                 token_id,
@@ -624,6 +762,8 @@ impl Contract {
                 token_id.clone(),
                 sender_id.clone(),
                 U128(amount),
+                Some(U128(fee)),
+                receiver,
                 &env::current_account_id(),
                 0,
                 GAS_FOR_RESOLVE_TRANSFER,
@@ -638,6 +778,11 @@ impl Contract {
         amount: Balance,
         msg: String
     ) -> Promise {
+        acquire_in_flight(sender_id, token_id);
+        update_token_ledger(token_id, |ledger| ledger.pending_withdrawals += amount);
+        add_pending_withdrawal(sender_id, token_id, amount);
+        let fee = withdraw_fee_of(token_id, amount);
+        let amount = amount - fee;
         ext_fungible_token::ft_transfer_call(
             sender_id.clone(),
             U128(amount),
@@ -651,6 +796,8 @@ impl Contract {
             token_id.clone(),
             sender_id.clone(),
             U128(amount),
+            Some(U128(fee)),
+            None,
             &env::current_account_id(),
             0,
             GAS_FOR_RESOLVE_TRANSFER,
@@ -0,0 +1,87 @@
+use crate::*;
+use crate::utils::to_nano;
+use near_sdk::json_types::U64;
+
+/// Defaults an account applies to its own swaps.
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone, Default)]
+#[serde(crate = "near_sdk::serde")]
+#[cfg_attr(not(target_arch = "wasm32"), derive(Debug, PartialEq))]
+pub struct AccountPreferences {
+    /// Tolerance of route quotes that leave out tolerance_bps.
+    pub max_slippage_bps: Option<u32>,
+    /// Deadline of route quotes that leave out deadline, counted from their quoted_at.
+    pub deadline_sec: Option<u32>,
+    /// Withdraw the final output of swap chains sent by the account to its wallet.
+    pub auto_withdraw: bool,
+}
+
+pub fn read_account_preferences_from_storage() -> LookupMap<AccountId, AccountPreferences> {
+    if let Some(content) = env::storage_read(ACCOUNT_PREFERENCES.as_bytes()) {
+        LookupMap::try_from_slice(&content).expect("deserialize account preferences failed.")
+    } else {
+        LookupMap::new(StorageKey::AccountPreferences)
+    }
+}
+
+pub fn write_account_preferences_to_storage(account_preferences: LookupMap<AccountId, AccountPreferences>) {
+    env::storage_write(
+        ACCOUNT_PREFERENCES.as_bytes(),
+        &account_preferences.try_to_vec().unwrap(),
+    );
+}
+
+impl Contract {
+    /// Fills the fields the route quote leaves out from the trader's preferences.
+    pub(crate) fn internal_apply_quote_preferences(&self, trader_id: &AccountId, route_quote: &RouteQuote) -> RouteQuote {
+        let mut route_quote = route_quote.clone();
+        if route_quote.tolerance_bps.is_some() && route_quote.deadline.is_some() {
+            return route_quote;
+        }
+        let preferences = read_account_preferences_from_storage().get(trader_id).unwrap_or_default();
+        if route_quote.tolerance_bps.is_none() {
+            route_quote.tolerance_bps = preferences.max_slippage_bps;
+        }
+        if route_quote.deadline.is_none() {
+            route_quote.deadline = route_quote.quoted_at
+                .zip(preferences.deadline_sec)
+                .map(|(quoted_at, deadline_sec)| U64(quoted_at.0 + to_nano(deadline_sec)));
+        }
+        route_quote
+    }
+
+    /// Sends the output of a finished swap chain to the trader if it opted in.
+    pub(crate) fn internal_auto_withdraw(&mut self, trader_id: &AccountId, actions: &[Action], result: &ActionResult) {
+        if !read_account_preferences_from_storage().get(trader_id).map(|preferences| preferences.auto_withdraw).unwrap_or(false) {
+            return;
+        }
+        self.internal_send_swap_output(trader_id, trader_id, actions, result, None);
+    }
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Set the caller's swap defaults, None to clear them.
+    /// Attached deposit covers the record, the rest is refunded.
+    #[payable]
+    pub fn set_account_preferences(&mut self, preferences: Option<AccountPreferences>) {
+        let prev_storage = env::storage_usage();
+        let account_id = env::predecessor_account_id();
+        let mut account_preferences = read_account_preferences_from_storage();
+        if let Some(preferences) = preferences {
+            assert!(
+                preferences.max_slippage_bps.map(|bps| bps <= utils::FEE_DIVISOR).unwrap_or(true),
+                "Invalid max_slippage_bps"
+            );
+            assert!(preferences.deadline_sec.map(|sec| sec > 0).unwrap_or(true), "Invalid deadline_sec");
+            account_preferences.insert(&account_id, &preferences);
+        } else {
+            account_preferences.remove(&account_id);
+        }
+        write_account_preferences_to_storage(account_preferences);
+        self.internal_check_storage(prev_storage);
+    }
+
+    pub fn get_account_preferences(&self, account_id: ValidAccountId) -> Option<AccountPreferences> {
+        read_account_preferences_from_storage().get(account_id.as_ref())
+    }
+}
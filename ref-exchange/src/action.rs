@@ -1,6 +1,7 @@
 use crate::errors::{ERR41_WRONG_ACTION_RESULT, ERR77_INVALID_ACTION_TYPE};
+use crate::utils::{FEE_DIVISOR, U256};
 use near_sdk::serde::{Deserialize, Serialize};
-use near_sdk::{env, json_types::U128, AccountId, Balance};
+use near_sdk::{env, json_types::{U128, U64}, AccountId, Balance};
 use std::collections::HashSet;
 
 /// Single swap action.
@@ -94,6 +95,14 @@ impl Action {
         }
     }
 
+    /// Whether the action takes its amount from the previous step.
+    pub fn is_chained(&self) -> bool {
+        match self {
+            Action::Swap(swap_action) => swap_action.amount_in.is_none(),
+            Action::SwapByOutput(swap_by_output_action) => swap_by_output_action.amount_out.is_none(),
+        }
+    }
+
     pub fn get_amount_out(&self) -> Option<U128> {
         match self {
             Action::Swap(_) => unimplemented!(),
@@ -104,6 +113,55 @@ impl Action {
     }
 }
 
+/// Amounts a quote gave for each step of a swap route, checked as the route executes
+/// so a failing route tells which hop fell short.
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(crate = "near_sdk::serde")]
+pub struct RouteQuote {
+    /// Quoted amount out of each swap action, in action order.
+    pub amounts_out: Vec<U128>,
+    /// How far below its quote a step may come out, in bps.
+    /// Defaults to the trader's max_slippage_bps preference.
+    pub tolerance_bps: Option<u32>,
+    /// Block timestamp the quote was taken at.
+    pub quoted_at: Option<U64>,
+    /// Timestamp after which the route is rejected.
+    /// Defaults to quoted_at plus the trader's deadline_sec preference.
+    pub deadline: Option<U64>,
+}
+
+impl RouteQuote {
+    pub fn assert_valid(&self, actions: &[Action]) {
+        let tolerance_bps = self.tolerance_bps.expect("No route quote tolerance");
+        assert!(tolerance_bps <= FEE_DIVISOR, "Invalid route quote tolerance");
+        if let Some(deadline) = self.deadline {
+            assert!(env::block_timestamp() <= deadline.0, "Route quote deadline {} passed", deadline.0);
+        }
+        assert_eq!(self.amounts_out.len(), actions.len(), "Route quote doesn't match actions");
+        assert!(matches!(actions[0], Action::Swap(_)), "Route quote only applies to swap actions");
+    }
+
+    /// Panics if the amount out of the given step is below its quote by more than the tolerance.
+    pub fn assert_step(&self, step: usize, action: &Action, amount_out: Balance) {
+        let quoted = self.amounts_out[step].0;
+        let tolerance_bps = self.tolerance_bps.unwrap_or(0);
+        let shortfall = quoted.saturating_sub(amount_out);
+        assert!(
+            shortfall == 0
+                || U256::from(shortfall) * U256::from(FEE_DIVISOR)
+                    <= U256::from(quoted) * U256::from(tolerance_bps),
+            "Route step {} in pool {} ({} -> {}) returned {}, quoted {} with {} bps tolerance",
+            step,
+            action.get_pool_id(),
+            action.get_token_in(),
+            action.get_token_out(),
+            amount_out,
+            quoted,
+            tolerance_bps
+        );
+    }
+}
+
 /// Result from action execution.
 #[derive(Serialize, Deserialize)]
 #[serde(crate = "near_sdk::serde")]
@@ -143,6 +201,17 @@ pub fn get_tokens_in_actions(actions: &[Action]) -> HashSet<AccountId> {
     tokens
 }
 
+/// Hops of the longest route, a route being an action and the chained actions following it.
+pub fn get_longest_route_hops(actions: &[Action]) -> usize {
+    let mut longest = 0;
+    let mut hops = 0;
+    for action in actions {
+        hops = if action.is_chained() { hops + 1 } else { 1 };
+        longest = std::cmp::max(longest, hops);
+    }
+    longest
+}
+
 pub fn assert_all_same_action_type(actions: &[Action]) {
     if !actions.is_empty() {
         let all_same_action_type = match &actions[0] {
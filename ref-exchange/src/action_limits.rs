@@ -0,0 +1,67 @@
+use crate::*;
+
+/// Caps on a single batch of actions, so oversized batches fail upfront
+/// instead of running out of gas halfway.
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone)]
+#[serde(crate = "near_sdk::serde")]
+#[cfg_attr(not(target_arch = "wasm32"), derive(Debug, PartialEq))]
+pub struct ActionLimits {
+    pub max_actions: u32,
+    /// Most hops of a route, see `get_longest_route_hops`.
+    pub max_route_hops: u32,
+}
+
+impl ActionLimits {
+    pub fn assert_within(&self, actions: &[Action]) {
+        assert!(
+            actions.len() <= self.max_actions as usize,
+            "{} actions exceed the limit of {}", actions.len(), self.max_actions
+        );
+        let hops = get_longest_route_hops(actions);
+        assert!(
+            hops <= self.max_route_hops as usize,
+            "Route of {} hops exceeds the limit of {}", hops, self.max_route_hops
+        );
+    }
+}
+
+pub fn read_action_limits_from_storage() -> Option<ActionLimits> {
+    env::storage_read(ACTION_LIMITS.as_bytes())
+        .map(|content| ActionLimits::try_from_slice(&content).expect("deserialize action limits failed."))
+}
+
+pub fn write_action_limits_to_storage(action_limits: Option<ActionLimits>) {
+    match action_limits {
+        Some(action_limits) => {
+            env::storage_write(ACTION_LIMITS.as_bytes(), &action_limits.try_to_vec().unwrap());
+        }
+        None => {
+            env::storage_remove(ACTION_LIMITS.as_bytes());
+        }
+    }
+}
+
+pub fn assert_within_action_limits(actions: &[Action]) {
+    if let Some(action_limits) = read_action_limits_from_storage() {
+        action_limits.assert_within(actions);
+    }
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Set the caps on action batches, None to lift them.
+    #[payable]
+    pub fn set_action_limits(&mut self, action_limits: Option<ActionLimits>) {
+        assert_one_yocto();
+        self.assert_owner();
+        audit_privileged_action("set_action_limits");
+        if let Some(action_limits) = action_limits.as_ref() {
+            assert!(action_limits.max_actions > 0 && action_limits.max_route_hops > 0, "Invalid action limits");
+        }
+        write_action_limits_to_storage(action_limits);
+    }
+
+    pub fn get_action_limits(&self) -> Option<ActionLimits> {
+        read_action_limits_from_storage()
+    }
+}
@@ -8,6 +8,7 @@ construct_uint! {
 }
 
 /// Maintain information about fees.
+#[derive(Clone)]
 pub struct AdminFees {
     /// Basis points of the admin fee in total fee.
     pub admin_fee_bps: u32,
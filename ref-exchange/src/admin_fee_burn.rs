@@ -0,0 +1,77 @@
+use crate::*;
+use crate::utils::{u128_dec_format, u128_ratio, FEE_DIVISOR};
+
+/// Admin fee burning policy of a stable kind pool.
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone)]
+#[serde(crate = "near_sdk::serde")]
+#[cfg_attr(not(target_arch = "wasm32"), derive(Debug))]
+pub struct AdminFeeBurn {
+    /// Part of the admin fee shares minted to the exchange by a swap that is burnt, in bps.
+    pub burn_bps: u32,
+    /// Shares burnt so far.
+    #[serde(with = "u128_dec_format")]
+    pub burnt_shares: Balance,
+}
+
+pub fn read_admin_fee_burns_from_storage() -> LookupMap<u64, AdminFeeBurn> {
+    if let Some(content) = env::storage_read(ADMIN_FEE_BURNS.as_bytes()) {
+        LookupMap::try_from_slice(&content).expect("deserialize admin fee burns failed.")
+    } else {
+        LookupMap::new(StorageKey::AdminFeeBurns)
+    }
+}
+
+pub fn write_admin_fee_burns_to_storage(admin_fee_burns: LookupMap<u64, AdminFeeBurn>) {
+    env::storage_write(
+        ADMIN_FEE_BURNS.as_bytes(),
+        &admin_fee_burns.try_to_vec().unwrap(),
+    );
+}
+
+impl Contract {
+    /// Burns the configured part of the admin fee shares the exchange got from a swap,
+    /// given its share balance before the swap, so they accrue to the remaining LPs.
+    pub(crate) fn internal_burn_admin_fee_shares(&self, pool_id: u64, pool: &mut Pool, prev_exchange_shares: Balance) {
+        let mut admin_fee_burns = read_admin_fee_burns_from_storage();
+        let mut admin_fee_burn = match admin_fee_burns.get(&pool_id) {
+            Some(admin_fee_burn) => admin_fee_burn,
+            None => return,
+        };
+        let exchange_id = env::current_account_id();
+        let minted = pool.share_balances(&exchange_id) - prev_exchange_shares;
+        let burnt = u128_ratio(minted, admin_fee_burn.burn_bps as u128, FEE_DIVISOR as u128);
+        if burnt == 0 {
+            return;
+        }
+        pool.share_burn(&exchange_id, burnt);
+        admin_fee_burn.burnt_shares += burnt;
+        admin_fee_burns.insert(&pool_id, &admin_fee_burn);
+        write_admin_fee_burns_to_storage(admin_fee_burns);
+    }
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Set the part of a stable, rated or degen pool's swap admin fees, in bps, burnt instead
+    /// of kept by the exchange. Zero turns burning off.
+    #[payable]
+    pub fn set_pool_admin_fee_burn_bps(&mut self, pool_id: u64, burn_bps: u32) {
+        assert_one_yocto();
+        self.assert_owner();
+        audit_privileged_action("set_pool_admin_fee_burn_bps");
+        let pool = self.internal_get_pool(pool_id);
+        assert!(
+            !matches!(pool, Pool::SimplePool(_) | Pool::RangePool(_)),
+            "Admin fee burning is for stable kind pools"
+        );
+        assert!(burn_bps <= FEE_DIVISOR, "{}", ERR101_ILLEGAL_FEE);
+        let mut admin_fee_burns = read_admin_fee_burns_from_storage();
+        let burnt_shares = admin_fee_burns.get(&pool_id).map(|admin_fee_burn| admin_fee_burn.burnt_shares).unwrap_or(0);
+        admin_fee_burns.insert(&pool_id, &AdminFeeBurn { burn_bps, burnt_shares });
+        write_admin_fee_burns_to_storage(admin_fee_burns);
+    }
+
+    pub fn get_pool_admin_fee_burn(&self, pool_id: u64) -> Option<AdminFeeBurn> {
+        read_admin_fee_burns_from_storage().get(&pool_id)
+    }
+}
@@ -0,0 +1,122 @@
+use crate::*;
+
+/// How a pool's swap admin fees reach the exchange.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(crate = "near_sdk::serde")]
+#[cfg_attr(not(target_arch = "wasm32"), derive(Debug))]
+pub enum AdminFeeMode {
+    /// Minted as LP shares to the exchange account.
+    Shares,
+    /// Minted shares are redeemed right away and the tokens kept aside for the owner.
+    Tokens,
+}
+
+pub fn read_admin_fee_tokens_from_storage() -> LookupMap<u64, Vec<Balance>> {
+    if let Some(content) = env::storage_read(ADMIN_FEE_TOKENS.as_bytes()) {
+        LookupMap::try_from_slice(&content).expect("deserialize admin fee tokens failed.")
+    } else {
+        LookupMap::new(StorageKey::AdminFeeTokens)
+    }
+}
+
+pub fn write_admin_fee_tokens_to_storage(admin_fee_tokens: LookupMap<u64, Vec<Balance>>) {
+    env::storage_write(
+        ADMIN_FEE_TOKENS.as_bytes(),
+        &admin_fee_tokens.try_to_vec().unwrap(),
+    );
+}
+
+impl Contract {
+    /// For pools in token mode, redeems the admin fee shares the exchange got from a swap,
+    /// given its share balance before the swap.
+    pub(crate) fn internal_accrue_admin_fee_tokens(&self, pool_id: u64, pool: &mut Pool, prev_exchange_shares: Balance) {
+        let exchange_id = env::current_account_id();
+        let minted = pool.share_balances(&exchange_id) - prev_exchange_shares;
+        if minted == 0 {
+            return;
+        }
+        let mut admin_fee_tokens = read_admin_fee_tokens_from_storage();
+        let mut accrued = match admin_fee_tokens.get(&pool_id) {
+            Some(accrued) => accrued,
+            None => return,
+        };
+        self.internal_settle_lp_fees(pool_id, pool, &[&exchange_id]);
+        let amounts = pool.remove_liquidity(&exchange_id, minted, vec![0; accrued.len()], false);
+        for (i, amount) in amounts.into_iter().enumerate() {
+            accrued[i] += amount;
+        }
+        admin_fee_tokens.insert(&pool_id, &accrued);
+        write_admin_fee_tokens_to_storage(admin_fee_tokens);
+    }
+
+    fn internal_withdraw_admin_fee_tokens(&mut self, pool_id: u64, tokens: &[AccountId], accrued: &[Balance]) {
+        let owner_id = self.owner_id.clone();
+        let mut deposits = self.internal_unwrap_account(&owner_id);
+        for (token_id, amount) in tokens.iter().zip(accrued.iter()) {
+            deposits.deposit(token_id, *amount);
+        }
+        self.internal_save_account(&owner_id, deposits);
+        log!("Admin fee tokens {:?} of pool {} withdrawn to {}", accrued, pool_id, owner_id);
+    }
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Switch how the pool's swap admin fees accrue. Leaving token mode moves the tokens kept so far
+    /// to the owner's inner account.
+    #[payable]
+    pub fn set_pool_admin_fee_mode(&mut self, pool_id: u64, mode: AdminFeeMode) {
+        assert_one_yocto();
+        self.assert_owner();
+        audit_privileged_action("set_pool_admin_fee_mode");
+        let pool = self.internal_get_pool(pool_id);
+        let mut admin_fee_tokens = read_admin_fee_tokens_from_storage();
+        match mode {
+            AdminFeeMode::Tokens => {
+                if admin_fee_tokens.get(&pool_id).is_none() {
+                    admin_fee_tokens.insert(&pool_id, &vec![0; pool.tokens().len()]);
+                }
+                write_admin_fee_tokens_to_storage(admin_fee_tokens);
+            }
+            AdminFeeMode::Shares => {
+                let accrued = admin_fee_tokens.remove(&pool_id);
+                write_admin_fee_tokens_to_storage(admin_fee_tokens);
+                if let Some(accrued) = accrued {
+                    self.internal_withdraw_admin_fee_tokens(pool_id, pool.tokens(), &accrued);
+                }
+            }
+        }
+    }
+
+    /// Move the admin fee tokens kept aside for a pool in token mode to the owner's inner account.
+    #[payable]
+    pub fn claim_admin_fee_tokens(&mut self, pool_id: u64) -> Vec<U128> {
+        assert_one_yocto();
+        assert!(self.is_owner_or_guardians(), "{}", ERR100_NOT_ALLOWED);
+        audit_privileged_action("claim_admin_fee_tokens");
+        self.assert_contract_running();
+        let pool = self.internal_get_pool(pool_id);
+        let mut admin_fee_tokens = read_admin_fee_tokens_from_storage();
+        let accrued = admin_fee_tokens.get(&pool_id).expect("Pool not in token mode");
+        admin_fee_tokens.insert(&pool_id, &vec![0; accrued.len()]);
+        write_admin_fee_tokens_to_storage(admin_fee_tokens);
+        self.internal_withdraw_admin_fee_tokens(pool_id, pool.tokens(), &accrued);
+        accrued.into_iter().map(|amount| amount.into()).collect()
+    }
+
+    pub fn get_pool_admin_fee_mode(&self, pool_id: u64) -> AdminFeeMode {
+        if read_admin_fee_tokens_from_storage().get(&pool_id).is_some() {
+            AdminFeeMode::Tokens
+        } else {
+            AdminFeeMode::Shares
+        }
+    }
+
+    /// Returns the admin fee tokens kept aside for a pool in token mode.
+    pub fn get_pool_admin_fee_tokens(&self, pool_id: u64) -> Vec<U128> {
+        read_admin_fee_tokens_from_storage()
+            .get(&pool_id)
+            .map(|accrued| accrued.into_iter().map(|amount| amount.into()).collect())
+            .unwrap_or_default()
+    }
+}
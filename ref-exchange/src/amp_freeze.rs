@@ -0,0 +1,47 @@
+use crate::*;
+use near_sdk::collections::LookupSet;
+
+pub fn read_frozen_amp_pools_from_storage() -> LookupSet<u64> {
+    if let Some(content) = env::storage_read(FROZEN_AMP_POOLS.as_bytes()) {
+        LookupSet::try_from_slice(&content).expect("deserialize frozen amp pools failed.")
+    } else {
+        LookupSet::new(StorageKey::FrozenAmpPools)
+    }
+}
+
+pub fn write_frozen_amp_pools_to_storage(frozen_amp_pools: LookupSet<u64>) {
+    env::storage_write(
+        FROZEN_AMP_POOLS.as_bytes(),
+        &frozen_amp_pools.try_to_vec().unwrap(),
+    );
+}
+
+pub fn assert_amp_not_frozen(pool_id: u64) {
+    assert!(!read_frozen_amp_pools_from_storage().contains(&pool_id), "Amp of pool {} is frozen", pool_id);
+}
+
+/// Rejects new ramps of the pool until the owner calls `resume_ramp_amp`.
+pub fn freeze_amp(pool_id: u64) {
+    let mut frozen_amp_pools = read_frozen_amp_pools_from_storage();
+    frozen_amp_pools.insert(&pool_id);
+    write_frozen_amp_pools_to_storage(frozen_amp_pools);
+    log!("Amp of pool {} frozen by {}", pool_id, env::predecessor_account_id());
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Allow ramping the amp of a pool frozen by `stable_swap_stop_ramp_amp` again.
+    #[payable]
+    pub fn resume_ramp_amp(&mut self, pool_id: u64) {
+        assert_one_yocto();
+        self.assert_owner();
+        audit_privileged_action("resume_ramp_amp");
+        let mut frozen_amp_pools = read_frozen_amp_pools_from_storage();
+        frozen_amp_pools.remove(&pool_id);
+        write_frozen_amp_pools_to_storage(frozen_amp_pools);
+    }
+
+    pub fn is_amp_frozen(&self, pool_id: u64) -> bool {
+        read_frozen_amp_pools_from_storage().contains(&pool_id)
+    }
+}
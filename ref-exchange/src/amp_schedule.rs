@@ -0,0 +1,154 @@
+use crate::*;
+use crate::stable_swap::assert_amp_ramp_step;
+use crate::utils::u64_dec_format;
+use near_sdk::Timestamp;
+
+pub const MAX_AMP_SCHEDULE_STEPS: usize = 10;
+
+/// One leg of a scheduled amp ramp, reaching `target_amp` at `target_time`.
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone)]
+#[serde(crate = "near_sdk::serde")]
+#[cfg_attr(not(target_arch = "wasm32"), derive(Debug))]
+pub struct AmpRampStep {
+    pub target_amp: u64,
+    #[serde(with = "u64_dec_format")]
+    pub target_time: Timestamp,
+}
+
+pub fn read_amp_schedule_from_storage() -> LookupMap<u64, Vec<AmpRampStep>> {
+    if let Some(content) = env::storage_read(AMP_SCHEDULE.as_bytes()) {
+        LookupMap::try_from_slice(&content).expect("deserialize amp schedule failed.")
+    } else {
+        LookupMap::new(StorageKey::AmpSchedule)
+    }
+}
+
+pub fn write_amp_schedule_to_storage(amp_schedule: LookupMap<u64, Vec<AmpRampStep>>) {
+    env::storage_write(
+        AMP_SCHEDULE.as_bytes(),
+        &amp_schedule.try_to_vec().unwrap(),
+    );
+}
+
+/// Moves the ramp onto the next scheduled step each time the current one has finished.
+/// Each step starts exactly where the previous one stopped, so the amp curve doesn't depend on when this runs.
+fn advance_ramp(
+    steps: &[AmpRampStep],
+    current_time: Timestamp,
+    init_amp_factor: &mut u128,
+    target_amp_factor: &mut u128,
+    init_amp_time: &mut Timestamp,
+    stop_amp_time: &mut Timestamp,
+) {
+    while current_time >= *stop_amp_time {
+        match steps.iter().find(|step| step.target_time > *stop_amp_time) {
+            Some(step) => {
+                *init_amp_factor = *target_amp_factor;
+                *init_amp_time = *stop_amp_time;
+                *target_amp_factor = step.target_amp as u128;
+                *stop_amp_time = step.target_time;
+            }
+            None => break,
+        }
+    }
+}
+
+macro_rules! advance_pool_ramp {
+    ($pool: expr, $steps: expr) => {
+        advance_ramp(
+            $steps,
+            env::block_timestamp(),
+            &mut $pool.init_amp_factor,
+            &mut $pool.target_amp_factor,
+            &mut $pool.init_amp_time,
+            &mut $pool.stop_amp_time,
+        )
+    };
+}
+
+impl Contract {
+    /// Loads the pool with any due amp schedule steps applied.
+    pub fn internal_get_pool(&self, pool_id: u64) -> Pool {
+        let mut pool = self.pools.get(pool_id).expect(ERR85_NO_POOL);
+        if !matches!(pool, Pool::SimplePool(_) | Pool::RangePool(_)) {
+            if let Some(steps) = read_amp_schedule_from_storage().get(&pool_id) {
+                match &mut pool {
+                    Pool::StableSwapPool(p) => advance_pool_ramp!(p, &steps),
+                    Pool::RatedSwapPool(p) => advance_pool_ramp!(p, &steps),
+                    Pool::DegenSwapPool(p) => advance_pool_ramp!(p, &steps),
+                    Pool::SimplePool(_) | Pool::RangePool(_) => {}
+                }
+            }
+        }
+        pool
+    }
+
+    pub(crate) fn internal_save_pool(&mut self, pool_id: u64, pool: &Pool) {
+        self.pools.replace(pool_id, pool);
+    }
+
+    pub(crate) fn internal_clear_amp_schedule(&mut self, pool_id: u64) {
+        let mut amp_schedule = read_amp_schedule_from_storage();
+        if amp_schedule.remove(&pool_id).is_some() {
+            write_amp_schedule_to_storage(amp_schedule);
+        }
+    }
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Ramp amp through a list of steps, the first one starts now, each following one starts
+    /// when the previous one reaches its target. Every step obeys the single ramp limits.
+    /// Replaces any previous schedule, a manual ramp or stop cancels it.
+    #[payable]
+    pub fn stable_swap_schedule_ramp_amp(&mut self, pool_id: u64, steps: Vec<AmpRampStep>) {
+        assert_one_yocto();
+        assert!(self.is_owner_or_guardians(), "{}", ERR100_NOT_ALLOWED);
+        audit_privileged_action("stable_swap_schedule_ramp_amp");
+        assert!(!steps.is_empty() && steps.len() <= MAX_AMP_SCHEDULE_STEPS, "Invalid steps");
+        assert_amp_not_frozen(pool_id);
+        let mut pool = self.internal_get_pool(pool_id);
+        let first = &steps[0];
+        match &mut pool {
+            Pool::StableSwapPool(p) => p.ramp_amplification(first.target_amp as u128, first.target_time),
+            Pool::RatedSwapPool(p) => p.ramp_amplification(first.target_amp as u128, first.target_time),
+            Pool::DegenSwapPool(p) => p.ramp_amplification(first.target_amp as u128, first.target_time),
+            _ => env::panic(ERR88_NOT_STABLE_POOL.as_bytes()),
+        }
+        for pair in steps.windows(2) {
+            assert_amp_ramp_step(
+                pair[0].target_amp as u128,
+                pair[0].target_time,
+                pair[1].target_amp as u128,
+                pair[1].target_time,
+            );
+        }
+        self.pools.replace(pool_id, &pool);
+        let mut amp_schedule = read_amp_schedule_from_storage();
+        amp_schedule.insert(&pool_id, &steps);
+        write_amp_schedule_to_storage(amp_schedule);
+    }
+
+    /// Persists due schedule steps into the pool, and drops the schedule once its last step has started.
+    pub fn sync_amp_schedule(&mut self, pool_id: u64) {
+        let pool = self.internal_get_pool(pool_id);
+        let stop_amp_time = match &pool {
+            Pool::StableSwapPool(p) => p.stop_amp_time,
+            Pool::RatedSwapPool(p) => p.stop_amp_time,
+            Pool::DegenSwapPool(p) => p.stop_amp_time,
+            _ => env::panic(ERR88_NOT_STABLE_POOL.as_bytes()),
+        };
+        self.pools.replace(pool_id, &pool);
+        let finished = read_amp_schedule_from_storage()
+            .get(&pool_id)
+            .map(|steps| steps.iter().all(|step| step.target_time <= stop_amp_time))
+            .unwrap_or(false);
+        if finished {
+            self.internal_clear_amp_schedule(pool_id);
+        }
+    }
+
+    pub fn get_amp_schedule(&self, pool_id: u64) -> Vec<AmpRampStep> {
+        read_amp_schedule_from_storage().get(&pool_id).unwrap_or_default()
+    }
+}
@@ -0,0 +1,104 @@
+use crate::*;
+use crate::utils::u64_dec_format;
+use near_sdk::Timestamp;
+
+/// Entries kept, older ones are dropped as new ones come in.
+pub const MAX_AUDIT_LOG_LEN: u64 = 500;
+/// Recorded call arguments are cut to this many bytes.
+pub const MAX_AUDIT_PARAMS_LEN: usize = 1024;
+
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone)]
+#[serde(crate = "near_sdk::serde")]
+#[cfg_attr(not(target_arch = "wasm32"), derive(Debug))]
+pub struct AuditEntry {
+    pub actor_id: AccountId,
+    pub action: String,
+    /// JSON arguments of the call.
+    pub params: String,
+    #[serde(with = "u64_dec_format")]
+    pub timestamp: Timestamp,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+#[cfg_attr(not(target_arch = "wasm32"), derive(Debug))]
+pub struct AuditLogEntry {
+    pub index: u64,
+    pub entry: AuditEntry,
+}
+
+pub fn read_audit_log_from_storage() -> LookupMap<u64, AuditEntry> {
+    if let Some(content) = env::storage_read(AUDIT_LOG.as_bytes()) {
+        LookupMap::try_from_slice(&content).expect("deserialize audit log failed.")
+    } else {
+        LookupMap::new(StorageKey::AuditLog)
+    }
+}
+
+pub fn write_audit_log_to_storage(audit_log: LookupMap<u64, AuditEntry>) {
+    env::storage_write(
+        AUDIT_LOG.as_bytes(),
+        &audit_log.try_to_vec().unwrap(),
+    );
+}
+
+pub fn read_audit_log_len_from_storage() -> u64 {
+    if let Some(content) = env::storage_read(AUDIT_LOG_LEN.as_bytes()) {
+        u64::try_from_slice(&content).expect("deserialize audit log len failed.")
+    } else {
+        0
+    }
+}
+
+pub fn write_audit_log_len_to_storage(len: u64) {
+    env::storage_write(
+        AUDIT_LOG_LEN.as_bytes(),
+        &len.try_to_vec().unwrap(),
+    );
+}
+
+/// Records a privileged call by the predecessor with the call's arguments,
+/// to be invoked right after the permission check.
+pub(crate) fn audit_privileged_action(action: &str) {
+    let mut params = String::from_utf8_lossy(&env::input().unwrap_or_default()).into_owned();
+    if params.len() > MAX_AUDIT_PARAMS_LEN {
+        let mut end = MAX_AUDIT_PARAMS_LEN;
+        while !params.is_char_boundary(end) {
+            end -= 1;
+        }
+        params.truncate(end);
+    }
+    let index = read_audit_log_len_from_storage();
+    let mut audit_log = read_audit_log_from_storage();
+    audit_log.insert(&index, &AuditEntry {
+        actor_id: env::predecessor_account_id(),
+        action: action.to_string(),
+        params,
+        timestamp: env::block_timestamp(),
+    });
+    if index >= MAX_AUDIT_LOG_LEN {
+        audit_log.remove(&(index - MAX_AUDIT_LOG_LEN));
+    }
+    write_audit_log_to_storage(audit_log);
+    write_audit_log_len_to_storage(index + 1);
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Total number of privileged actions recorded so far, the next entry's index.
+    pub fn get_audit_log_len(&self) -> u64 {
+        read_audit_log_len_from_storage()
+    }
+
+    /// Returns the retained entries from `from_index` on, by default starting from the oldest retained one.
+    pub fn get_audit_log(&self, from_index: Option<u64>, limit: Option<u64>) -> Vec<AuditLogEntry> {
+        let len = read_audit_log_len_from_storage();
+        let first_index = len.saturating_sub(MAX_AUDIT_LOG_LEN);
+        let from_index = std::cmp::max(from_index.unwrap_or(first_index), first_index);
+        let to_index = std::cmp::min(from_index.saturating_add(limit.unwrap_or(MAX_AUDIT_LOG_LEN)), len);
+        let audit_log = read_audit_log_from_storage();
+        (from_index..to_index)
+            .filter_map(|index| audit_log.get(&index).map(|entry| AuditLogEntry { index, entry }))
+            .collect()
+    }
+}
@@ -43,6 +43,7 @@ impl Contract {
     pub fn extend_client_echo_token_id_whitelist(&mut self, token_ids: Vec<ValidAccountId>) {
         assert_one_yocto();
         assert!(self.is_owner_or_guardians(), "{}", ERR100_NOT_ALLOWED);
+        audit_privileged_action("extend_client_echo_token_id_whitelist");
         let mut client_echo_token_id_whitelist = read_ce_tw_from_storage();
         for token_id in token_ids {
             let is_success = client_echo_token_id_whitelist.insert(token_id.as_ref());
@@ -55,6 +56,7 @@ impl Contract {
     pub fn remove_client_echo_token_id_whitelist(&mut self, token_ids: Vec<ValidAccountId>) {
         assert_one_yocto();
         self.assert_owner();
+        audit_privileged_action("remove_client_echo_token_id_whitelist");
         let mut client_echo_token_id_whitelist = read_ce_tw_from_storage();
         for token_id in token_ids {
             let is_success = client_echo_token_id_whitelist.remove(token_id.as_ref());
@@ -72,6 +74,7 @@ impl Contract {
     pub fn extend_client_echo_sender_id_whitelist(&mut self, sender_ids: Vec<ValidAccountId>) {
         assert_one_yocto();
         assert!(self.is_owner_or_guardians(), "{}", ERR100_NOT_ALLOWED);
+        audit_privileged_action("extend_client_echo_sender_id_whitelist");
         let mut client_echo_sender_id_whitelist = read_ce_sw_from_storage();
         for sender_id in sender_ids {
             let is_success = client_echo_sender_id_whitelist.insert(sender_id.as_ref());
@@ -84,6 +87,7 @@ impl Contract {
     pub fn remove_client_echo_sender_id_whitelist(&mut self, sender_ids: Vec<ValidAccountId>) {
         assert_one_yocto();
         self.assert_owner();
+        audit_privileged_action("remove_client_echo_sender_id_whitelist");
         let mut client_echo_sender_id_whitelist = read_ce_sw_from_storage();
         for sender_id in sender_ids {
             let is_success = client_echo_sender_id_whitelist.remove(sender_id.as_ref());
@@ -10,4 +10,418 @@ pub const POOL_LIMIT: &str = "pl";
 
 // Key for client echo limit
 pub const CLIENT_ECHO_TOKEN_ID_WHITELIST: &str = "ce_tw";
-pub const CLIENT_ECHO_SENDER_ID_WHITELIST: &str = "ce_sw";
\ No newline at end of file
+pub const CLIENT_ECHO_SENDER_ID_WHITELIST: &str = "ce_sw";
+
+// Key for archived pools
+pub const ARCHIVED_POOLS: &str = "ap";
+
+// Key for per pool twap record retention
+pub const TWAP_RECORD_LIMIT: &str = "twap_rl";
+
+// Key for per pool volume stats retention
+pub const VOLUME_STATS_RETENTION: &str = "vol_rt";
+
+// Key for pending cross-contract operations
+pub const IN_FLIGHT_OPERATIONS: &str = "iff";
+
+// Key for mft_transfer_call receiver policy
+pub const MFT_RECEIVER_POLICY: &str = "mft_rp";
+pub const MFT_RECEIVER_LIST: &str = "mft_rl";
+pub const MFT_RECEIVER_MIN_GAS: &str = "mft_rg";
+
+// Key for pool metadata
+pub const POOL_METADATA: &str = "pmd";
+
+// Key for scheduled amp ramps
+pub const AMP_SCHEDULE: &str = "amp_s";
+
+// Key for degen price bands
+pub const DEGEN_PRICE_BANDS: &str = "degen_pb";
+
+// Key for stable pool maker rebates
+pub const MAKER_REBATE_CONFIGS: &str = "mrb_c";
+pub const MAKER_REBATE_ESCROWS: &str = "mrb_e";
+
+// Key for the wash trade filter
+pub const WASH_TRADE_WINDOW: &str = "wt_w";
+pub const RECENT_SWAPS: &str = "wt_r";
+
+// Key for claimable simple pool LP fees
+pub const POOL_FEE_GROWTH: &str = "lpf_g";
+pub const LP_FEE_POSITIONS: &str = "lpf_p";
+
+// Key for LP share locks
+pub const SHARE_LOCKS: &str = "slk";
+pub const ACCOUNT_SHARE_LOCKS: &str = "slk_a";
+pub const NEXT_SHARE_LOCK_ID: &str = "slk_n";
+
+// Key for LP share streams
+pub const SHARE_STREAMS: &str = "sst";
+pub const ACCOUNT_SHARE_STREAMS: &str = "sst_a";
+pub const NEXT_SHARE_STREAM_ID: &str = "sst_n";
+
+// Key for swap fee rebates
+pub const FEE_REBATE_CONFIG: &str = "frb_c";
+pub const FEE_REBATE_POT: &str = "frb_p";
+pub const PENDING_FEE_REBATES: &str = "frb_r";
+pub const PENDING_FEE_REBATE_TOTALS: &str = "frb_t";
+
+// Key for opt-in trade histories
+pub const TRADE_HISTORIES: &str = "th";
+
+// Key for pools taking admin fees as tokens
+pub const ADMIN_FEE_TOKENS: &str = "aft";
+
+// Key for the token accounting ledger
+pub const TOKEN_LEDGERS: &str = "ldg";
+
+// Key for the privileged action audit log
+pub const AUDIT_LOG: &str = "al";
+pub const AUDIT_LOG_LEN: &str = "al_n";
+
+// Key for the Sputnik DAO allowed to act as owner
+pub const DAO_ID: &str = "dao";
+
+// Key for LP votes on pool parameters
+pub const LP_PROPOSALS: &str = "lpv";
+pub const POOL_LP_PROPOSALS: &str = "lpv_p";
+pub const LP_VOTES: &str = "lpv_a";
+pub const NEXT_LP_PROPOSAL_ID: &str = "lpv_n";
+
+// Key for degen token listings under guardian review
+pub const DEGEN_LISTINGS: &str = "dgl";
+
+// Key for the activation delay of new referrals
+pub const REFERRAL_ACTIVATIONS: &str = "rfa";
+pub const REFERRAL_ACTIVATION_DELAY: &str = "rfa_d";
+
+// Key for multi token deposit plans in progress
+pub const DEPOSIT_PLANS: &str = "dpl";
+
+// Key for per pool swap screening
+pub const SWAP_SCREEN_CONFIGS: &str = "ssc";
+pub const SWAP_SCREEN_LIST: &str = "ssl";
+
+// Key for pools with an LP allow-list
+pub const PERMISSIONED_POOLS: &str = "pp";
+pub const POOL_LP_ALLOWLIST: &str = "pp_l";
+
+// Key for protocol-owned liquidity
+pub const POL_TREASURY: &str = "pol_t";
+pub const POL_POSITIONS: &str = "pol";
+
+// Key for pool ids by kind, fee and token set, and the pool count cap
+pub const POOL_REGISTRY: &str = "preg";
+pub const MAX_POOL_COUNT: &str = "pmax";
+pub const DUPLICATE_POOLS_ALLOWED: &str = "pdup";
+
+// Key for fallback oracle sources of degen tokens
+pub const DEGEN_FALLBACKS: &str = "degen_fb";
+
+// Key for staleness and change limits of rated tokens
+pub const RATE_GUARDS: &str = "rate_g";
+
+// Key for exchange-wide statistics counters
+pub const EXCHANGE_COUNTERS: &str = "xst";
+pub const EXCHANGE_TOKEN_STATS: &str = "xst_t";
+pub const EXCHANGE_TRADERS: &str = "xst_a";
+
+// Key for share price checkpoints of pools
+pub const LP_CHECKPOINTS: &str = "lpck";
+
+// Key for recent swap volume of pools
+pub const POOL_VOLUME_WINDOWS: &str = "pvw";
+
+// Key for wrapped NEAR tokens unwrapped besides wnear_id
+pub const EXTRA_WNEAR_IDS: &str = "wnear_x";
+
+// Key for swap defaults of accounts
+pub const ACCOUNT_PREFERENCES: &str = "acc_pref";
+
+// Key for pools that pay no referral fee
+pub const REFERRAL_OPT_OUT_POOLS: &str = "ref_oo";
+
+// Key for per pool caps on a single liquidity removal
+pub const WITHDRAWAL_CAPS: &str = "wd_cap";
+
+// Key for fresh price rules of degen pools
+pub const DEGEN_FRESH_PRICE_RULES: &str = "degen_fr";
+
+// Key for incentive escrows of pools and the positions of their LPs
+pub const LP_INCENTIVES: &str = "lpi";
+pub const LP_INCENTIVE_POSITIONS: &str = "lpi_p";
+
+// Key for launch auctions of new pools
+pub const LAUNCH_AUCTIONS: &str = "la";
+
+// Key for accounts denied deposits and swaps
+pub const DENIED_ACCOUNTS: &str = "deny";
+
+// Key for TWAP orders
+pub const TWAP_ORDERS: &str = "twap";
+pub const NEXT_TWAP_ORDER_ID: &str = "twap_n";
+
+// Key for the latest swap of pools
+pub const LAST_TRADES: &str = "ltr";
+
+// Key for admin fee reports per epoch
+pub const EPOCH_FEES: &str = "ep_fee";
+
+// Key for token prices cached for views and the keepers pushing them
+pub const CACHED_PRICES: &str = "pc";
+pub const PRICE_KEEPERS: &str = "pc_k";
+
+// Key for the safe mode snapshot root and the amounts claimed against it
+pub const SAFE_MODE_SNAPSHOT: &str = "sm_root";
+pub const SAFE_MODE_CLAIMS: &str = "sm_c";
+
+// Key for caps on action batches
+pub const ACTION_LIMITS: &str = "act_lim";
+
+// Key for temporary fee reductions of pools
+pub const FEE_HOLIDAYS: &str = "fee_hol";
+
+// Key for degen pool TVL limit utilization thresholds and the levels pools reached
+pub const DEGEN_TVL_ALERT_THRESHOLDS: &str = "dtvl_t";
+pub const DEGEN_TVL_ALERT_LEVELS: &str = "dtvl_l";
+
+// Key for accounts covering storage with their inner wNEAR and the wNEAR taken for it
+pub const STORAGE_TOP_UP_ACCOUNTS: &str = "st_tu";
+pub const STORAGE_TOP_UP_WNEAR: &str = "st_tu_w";
+pub const STORAGE_TOP_UP_DEPOSITS: &str = "st_tu_d";
+
+// Key for share checkpoints of pools
+pub const SHARE_CHECKPOINTS: &str = "sc";
+pub const NEXT_SHARE_CHECKPOINT_ID: &str = "sc_n";
+
+// Key for nonces of signed off-chain orders
+pub const ORDER_NONCE_FLOORS: &str = "on_f";
+pub const USED_ORDER_NONCES: &str = "on_u";
+
+// Key for the insurance fund fed by admin fees
+pub const INSURANCE_FEE_BPS: &str = "if_bps";
+pub const INSURANCE_ACCRUED: &str = "if_a";
+pub const INSURANCE_WITHDRAWALS: &str = "if_w";
+
+// Key for degen pool TVL limits following the market cap of a pool token
+pub const DYNAMIC_TVL_LIMITS: &str = "dtl";
+
+// Key for permissioned pools whose shares only move to allow-listed accounts
+pub const TRANSFER_RESTRICTED_POOLS: &str = "trp";
+
+// Key for swap volume attributed to referrals
+pub const REFERRAL_VOLUME_PERIOD: &str = "rv_p";
+pub const REFERRAL_VOLUMES: &str = "rv_v";
+
+// Key for stable kind pools by token pair
+pub const STABLE_PAIR_POOLS: &str = "spp";
+
+// Key for failed withdrawals that could not be credited back
+pub const UNCLAIMED_WITHDRAWALS: &str = "uw";
+pub const UNCLAIMED_WITHDRAWAL_TOTALS: &str = "uw_t";
+
+// Key for the part of stable kind pools' admin fees burnt for the LPs
+pub const ADMIN_FEE_BURNS: &str = "afb";
+
+// Key for per pool caps on the output reserve a single swap can take
+pub const SWAP_CAPS: &str = "sw_cap";
+
+// Key for creator rights over permissionless pools
+pub const CREATOR_FEE_BOUNDS: &str = "cfb";
+pub const POOL_DEPRECATION_REQUESTS: &str = "pdr";
+
+// Key for accounts letting anyone compound their rewards into pool shares
+pub const AUTO_COMPOUND_ACCOUNTS: &str = "aca";
+
+// Keys for the full account and pool export to off-chain replicas
+pub const STATE_VERSION: &str = "st_ver";
+pub const REPLICA_ACCOUNT_IDS: &str = "rp_acc";
+pub const REPLICA_ACCOUNT_POSITIONS: &str = "rp_acc_p";
+
+// Key for exit fees taken from withdrawals of bridged tokens
+pub const WITHDRAW_FEES: &str = "wd_fee";
+
+// Keys for the LP referral program
+pub const LP_REFERRAL_FEE_BPS: &str = "lp_ref_bps";
+pub const LP_REFERRERS: &str = "lp_ref";
+pub const LP_REFERRAL_REWARDS: &str = "lp_ref_rw";
+
+// Key for pools whose amp was frozen by an emergency stop
+pub const FROZEN_AMP_POOLS: &str = "amp_frz";
+
+// Key for the canonical token of each symbol
+pub const TOKEN_ALIASES: &str = "tk_alias";
+
+// Keys for the lock bonus fee tier
+pub const POOL_LOCK_BONUS: &str = "lk_bonus";
+pub const LOCK_BONUS_POSITIONS: &str = "lk_bonus_p";
+pub const LOCK_BONUS_EXPIRIES: &str = "lk_bonus_e";
+
+// Keys for share weighted raffles
+pub const SHARE_RAFFLES: &str = "sh_raffle";
+pub const NEXT_SHARE_RAFFLE_ID: &str = "sh_raffle_id";
+
+// Key for rated pool rate divergence breakers
+pub const RATE_DIVERGENCE_BREAKERS: &str = "rate_div";
+
+// Key for pool admin delegated to projects
+pub const POOL_DELEGATIONS: &str = "pool_dlg";
+
+// Keys for per account pending withdrawal and locked amounts
+pub const PENDING_WITHDRAWALS: &str = "pend_wd";
+pub const LOCKED_DEPOSITS: &str = "lk_dep";
+
+// Key for self-imposed daily withdrawal limits of accounts
+pub const WITHDRAWAL_LIMITS: &str = "wd_limit";
+
+// Keys for the index of pools that aren't archived
+pub const ACTIVE_POOL_IDS: &str = "act_pools";
+pub const ACTIVE_POOL_INDEX_LEN: &str = "act_pools_len";
+
+// Key for the token_in escrowed by TWAP orders per token
+pub const TWAP_ESCROW_TOTALS: &str = "twap_esc";
+
+// Keys for the entry counts of lookup collections
+pub const COLLECTION_LENS: &str = "coll_lens";
+pub const REPLICA_ACCOUNT_COUNT: &str = "rp_acc_cnt";
+
+// Key for the ids of share locks that exist, i.e. their NFTs
+pub const SHARE_LOCK_IDS: &str = "slk_i";
+
+// Key for the flag marking a test network deployment, set at init
+pub const TEST_NETWORK: &str = "test_net";
+
+/// Every key above, inspected by `inspect_storage`.
+pub const CUSTOM_KEYS: &[&str] = &[
+    RATE_STORAGE_KEY,
+    DEGEN_STORAGE_KEY,
+    DEGEN_ORACLE_CONFIG_STORAGE_KEY,
+    POOL_LIMIT,
+    CLIENT_ECHO_TOKEN_ID_WHITELIST,
+    CLIENT_ECHO_SENDER_ID_WHITELIST,
+    ARCHIVED_POOLS,
+    TWAP_RECORD_LIMIT,
+    VOLUME_STATS_RETENTION,
+    IN_FLIGHT_OPERATIONS,
+    MFT_RECEIVER_POLICY,
+    MFT_RECEIVER_LIST,
+    MFT_RECEIVER_MIN_GAS,
+    POOL_METADATA,
+    AMP_SCHEDULE,
+    DEGEN_PRICE_BANDS,
+    MAKER_REBATE_CONFIGS,
+    MAKER_REBATE_ESCROWS,
+    WASH_TRADE_WINDOW,
+    RECENT_SWAPS,
+    POOL_FEE_GROWTH,
+    LP_FEE_POSITIONS,
+    SHARE_LOCKS,
+    ACCOUNT_SHARE_LOCKS,
+    NEXT_SHARE_LOCK_ID,
+    SHARE_STREAMS,
+    ACCOUNT_SHARE_STREAMS,
+    NEXT_SHARE_STREAM_ID,
+    FEE_REBATE_CONFIG,
+    FEE_REBATE_POT,
+    PENDING_FEE_REBATES,
+    PENDING_FEE_REBATE_TOTALS,
+    TRADE_HISTORIES,
+    ADMIN_FEE_TOKENS,
+    TOKEN_LEDGERS,
+    AUDIT_LOG,
+    AUDIT_LOG_LEN,
+    DAO_ID,
+    LP_PROPOSALS,
+    POOL_LP_PROPOSALS,
+    LP_VOTES,
+    NEXT_LP_PROPOSAL_ID,
+    DEGEN_LISTINGS,
+    REFERRAL_ACTIVATIONS,
+    REFERRAL_ACTIVATION_DELAY,
+    DEPOSIT_PLANS,
+    SWAP_SCREEN_CONFIGS,
+    SWAP_SCREEN_LIST,
+    PERMISSIONED_POOLS,
+    POOL_LP_ALLOWLIST,
+    POL_TREASURY,
+    POL_POSITIONS,
+    POOL_REGISTRY,
+    MAX_POOL_COUNT,
+    DUPLICATE_POOLS_ALLOWED,
+    DEGEN_FALLBACKS,
+    RATE_GUARDS,
+    EXCHANGE_COUNTERS,
+    EXCHANGE_TOKEN_STATS,
+    EXCHANGE_TRADERS,
+    LP_CHECKPOINTS,
+    POOL_VOLUME_WINDOWS,
+    EXTRA_WNEAR_IDS,
+    ACCOUNT_PREFERENCES,
+    REFERRAL_OPT_OUT_POOLS,
+    WITHDRAWAL_CAPS,
+    DEGEN_FRESH_PRICE_RULES,
+    LP_INCENTIVES,
+    LP_INCENTIVE_POSITIONS,
+    LAUNCH_AUCTIONS,
+    DENIED_ACCOUNTS,
+    TWAP_ORDERS,
+    NEXT_TWAP_ORDER_ID,
+    LAST_TRADES,
+    EPOCH_FEES,
+    CACHED_PRICES,
+    PRICE_KEEPERS,
+    SAFE_MODE_SNAPSHOT,
+    SAFE_MODE_CLAIMS,
+    ACTION_LIMITS,
+    FEE_HOLIDAYS,
+    DEGEN_TVL_ALERT_THRESHOLDS,
+    DEGEN_TVL_ALERT_LEVELS,
+    STORAGE_TOP_UP_ACCOUNTS,
+    STORAGE_TOP_UP_WNEAR,
+    STORAGE_TOP_UP_DEPOSITS,
+    SHARE_CHECKPOINTS,
+    NEXT_SHARE_CHECKPOINT_ID,
+    ORDER_NONCE_FLOORS,
+    USED_ORDER_NONCES,
+    INSURANCE_FEE_BPS,
+    INSURANCE_ACCRUED,
+    INSURANCE_WITHDRAWALS,
+    DYNAMIC_TVL_LIMITS,
+    TRANSFER_RESTRICTED_POOLS,
+    REFERRAL_VOLUME_PERIOD,
+    REFERRAL_VOLUMES,
+    STABLE_PAIR_POOLS,
+    UNCLAIMED_WITHDRAWALS,
+    UNCLAIMED_WITHDRAWAL_TOTALS,
+    ADMIN_FEE_BURNS,
+    SWAP_CAPS,
+    CREATOR_FEE_BOUNDS,
+    POOL_DEPRECATION_REQUESTS,
+    AUTO_COMPOUND_ACCOUNTS,
+    STATE_VERSION,
+    REPLICA_ACCOUNT_IDS,
+    REPLICA_ACCOUNT_POSITIONS,
+    WITHDRAW_FEES,
+    LP_REFERRAL_FEE_BPS,
+    LP_REFERRERS,
+    LP_REFERRAL_REWARDS,
+    FROZEN_AMP_POOLS,
+    TOKEN_ALIASES,
+    POOL_LOCK_BONUS,
+    LOCK_BONUS_POSITIONS,
+    LOCK_BONUS_EXPIRIES,
+    SHARE_RAFFLES,
+    NEXT_SHARE_RAFFLE_ID,
+    RATE_DIVERGENCE_BREAKERS,
+    POOL_DELEGATIONS,
+    PENDING_WITHDRAWALS,
+    LOCKED_DEPOSITS,
+    WITHDRAWAL_LIMITS,
+    ACTIVE_POOL_IDS,
+    ACTIVE_POOL_INDEX_LEN,
+    TWAP_ESCROW_TOTALS,
+    COLLECTION_LENS,
+    REPLICA_ACCOUNT_COUNT,
+    SHARE_LOCK_IDS,
+    TEST_NETWORK,
+];
@@ -0,0 +1,74 @@
+use crate::*;
+
+/// Owner operations a Sputnik DAO proposal can carry out through `dao_execute`.
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(crate = "near_sdk::serde")]
+#[cfg_attr(not(target_arch = "wasm32"), derive(Debug))]
+pub enum DaoAction {
+    SetOwner { owner_id: ValidAccountId },
+    ExtendGuardians { guardians: Vec<ValidAccountId> },
+    RemoveGuardians { guardians: Vec<ValidAccountId> },
+    ChangeState { state: RunningState },
+    ExtendWhitelistedTokens { tokens: Vec<ValidAccountId> },
+    RemoveWhitelistedTokens { tokens: Vec<ValidAccountId> },
+    ModifyAdminFee { admin_fee_bps: u32 },
+    ModifyTotalFee { pool_id: u64, total_fee: u32 },
+}
+
+pub fn read_dao_id_from_storage() -> Option<AccountId> {
+    env::storage_read(DAO_ID.as_bytes())
+        .map(|content| AccountId::try_from_slice(&content).expect("deserialize dao id failed."))
+}
+
+pub fn write_dao_id_to_storage(dao_id: Option<AccountId>) {
+    match dao_id {
+        Some(dao_id) => {
+            env::storage_write(DAO_ID.as_bytes(), &dao_id.try_to_vec().unwrap());
+        }
+        None => {
+            env::storage_remove(DAO_ID.as_bytes());
+        }
+    }
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Set or clear the Sputnik DAO account allowed to call `dao_execute`.
+    #[payable]
+    pub fn set_dao_id(&mut self, dao_id: Option<ValidAccountId>) {
+        assert_one_yocto();
+        self.assert_owner();
+        audit_privileged_action("set_dao_id");
+        let dao_id: Option<AccountId> = dao_id.map(|dao_id| dao_id.into());
+        log!("Modify dao_id from {:?} to {:?}", read_dao_id_from_storage(), dao_id);
+        write_dao_id_to_storage(dao_id);
+    }
+
+    pub fn get_dao_id(&self) -> Option<AccountId> {
+        read_dao_id_from_storage()
+    }
+
+    /// Entry point for FunctionCall proposals of the configured DAO, acting with owner rights.
+    /// `proposal_id` only identifies the proposal in logs and the audit log.
+    #[payable]
+    pub fn dao_execute(&mut self, proposal_id: u64, action: DaoAction) {
+        assert_one_yocto();
+        assert_eq!(
+            Some(env::predecessor_account_id()),
+            read_dao_id_from_storage(),
+            "{}", ERR100_NOT_ALLOWED
+        );
+        audit_privileged_action("dao_execute");
+        log!("Execute proposal {} of {}: {:?}", proposal_id, env::predecessor_account_id(), action);
+        match action {
+            DaoAction::SetOwner { owner_id } => self.internal_set_owner(owner_id),
+            DaoAction::ExtendGuardians { guardians } => self.internal_extend_guardians(guardians),
+            DaoAction::RemoveGuardians { guardians } => self.internal_remove_guardians(guardians),
+            DaoAction::ChangeState { state } => self.internal_change_state(state),
+            DaoAction::ExtendWhitelistedTokens { tokens } => self.internal_extend_whitelisted_tokens(tokens),
+            DaoAction::RemoveWhitelistedTokens { tokens } => self.internal_remove_whitelisted_tokens(tokens),
+            DaoAction::ModifyAdminFee { admin_fee_bps } => self.internal_modify_admin_fee(admin_fee_bps),
+            DaoAction::ModifyTotalFee { pool_id, total_fee } => self.internal_modify_total_fee(pool_id, total_fee),
+        }
+    }
+}
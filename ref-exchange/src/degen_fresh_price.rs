@@ -0,0 +1,108 @@
+use crate::*;
+use crate::utils::{to_nano, u128_ratio, FEE_DIVISOR};
+
+/// Swaps into a degen pool taking more than `min_swap_bps` of the token_in reserve
+/// need every pool token price published within the last `max_price_age_sec`,
+/// which is expected to be tighter than the oracle expiry.
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone)]
+#[serde(crate = "near_sdk::serde")]
+#[cfg_attr(not(target_arch = "wasm32"), derive(Debug))]
+pub struct DegenFreshPriceRule {
+    pub min_swap_bps: u32,
+    pub max_price_age_sec: u32,
+}
+
+impl DegenFreshPriceRule {
+    /// Returns a token whose price is too old if the swap is large enough for the rule.
+    pub fn stale_token<'a>(&self, token_ids: &'a [AccountId], updated_at: &[u64], amount_in: Balance, reserve_in: Balance) -> Option<&'a AccountId> {
+        if amount_in <= u128_ratio(reserve_in, self.min_swap_bps as u128, FEE_DIVISOR as u128) {
+            return None;
+        }
+        token_ids.iter()
+            .zip(updated_at.iter())
+            .find(|(_, updated_at)| env::block_timestamp() > *updated_at + to_nano(self.max_price_age_sec))
+            .map(|(token_id, _)| token_id)
+    }
+
+    /// Panics if the swap is large enough for the rule and any of the prices is too old.
+    pub fn assert_fresh(&self, token_ids: &[AccountId], updated_at: &[u64], amount_in: Balance, reserve_in: Balance) {
+        if let Some(token_id) = self.stale_token(token_ids, updated_at, amount_in, reserve_in) {
+            env::panic(format!("Degen price of {} too old for a swap this large", token_id).as_bytes());
+        }
+    }
+}
+
+pub fn read_degen_fresh_price_rules_from_storage() -> LookupMap<u64, DegenFreshPriceRule> {
+    if let Some(content) = env::storage_read(DEGEN_FRESH_PRICE_RULES.as_bytes()) {
+        LookupMap::try_from_slice(&content).expect("deserialize degen fresh price rules failed.")
+    } else {
+        LookupMap::new(StorageKey::DegenFreshPriceRules)
+    }
+}
+
+pub fn write_degen_fresh_price_rules_to_storage(degen_fresh_price_rules: LookupMap<u64, DegenFreshPriceRule>) {
+    env::storage_write(
+        DEGEN_FRESH_PRICE_RULES.as_bytes(),
+        &degen_fresh_price_rules.try_to_vec().unwrap(),
+    );
+}
+
+fn degen_updated_at(pool: &DegenSwapPool) -> Vec<u64> {
+    pool.token_account_ids.iter()
+        .map(|token_id| global_get_degen(token_id).price_info().map(|price_info| price_info.degen_updated_at).unwrap_or(0))
+        .collect()
+}
+
+impl Contract {
+    /// Applies the pool's fresh price rule, if any, to a swap about to go through it.
+    pub(crate) fn assert_degen_swap_price_fresh(&self, pool_id: u64, pool: &Pool, token_in: &AccountId, amount_in: Balance) {
+        let pool = match pool {
+            Pool::DegenSwapPool(p) => p,
+            _ => return,
+        };
+        if let Some(rule) = read_degen_fresh_price_rules_from_storage().get(&pool_id) {
+            let in_idx = pool.token_account_ids.iter().position(|id| id == token_in).expect(ERR63_MISSING_TOKEN);
+            rule.assert_fresh(&pool.token_account_ids, &degen_updated_at(pool), amount_in, pool.get_amounts()[in_idx]);
+        }
+    }
+
+    /// Whether the pool's fresh price rule, if any, lets the swap through.
+    pub(crate) fn is_degen_swap_price_fresh(&self, pool_id: u64, pool: &Pool, token_in: &AccountId, amount_in: Balance) -> bool {
+        let pool = match pool {
+            Pool::DegenSwapPool(p) => p,
+            _ => return true,
+        };
+        match read_degen_fresh_price_rules_from_storage().get(&pool_id) {
+            Some(rule) => {
+                let in_idx = pool.token_account_ids.iter().position(|id| id == token_in).expect(ERR63_MISSING_TOKEN);
+                rule.stale_token(&pool.token_account_ids, &degen_updated_at(pool), amount_in, pool.get_amounts()[in_idx]).is_none()
+            }
+            None => true,
+        }
+    }
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Set the fresh price rule of a degen pool, None to remove it.
+    #[payable]
+    pub fn set_degen_fresh_price_rule(&mut self, pool_id: u64, rule: Option<DegenFreshPriceRule>) {
+        assert_one_yocto();
+        assert!(self.is_owner_or_guardians(), "{}", ERR100_NOT_ALLOWED);
+        audit_privileged_action("set_degen_fresh_price_rule");
+        assert!(matches!(self.internal_get_pool(pool_id), Pool::DegenSwapPool(_)), "Not a degen pool");
+        let mut degen_fresh_price_rules = read_degen_fresh_price_rules_from_storage();
+        if let Some(rule) = rule {
+            assert!(rule.min_swap_bps <= FEE_DIVISOR, "Invalid min_swap_bps");
+            assert!(rule.max_price_age_sec > 0, "Invalid max_price_age_sec");
+            degen_fresh_price_rules.insert(&pool_id, &rule);
+        } else {
+            degen_fresh_price_rules.remove(&pool_id);
+        }
+        write_degen_fresh_price_rules_to_storage(degen_fresh_price_rules);
+    }
+
+    pub fn get_degen_fresh_price_rule(&self, pool_id: u64) -> Option<DegenFreshPriceRule> {
+        read_degen_fresh_price_rules_from_storage().get(&pool_id)
+    }
+}
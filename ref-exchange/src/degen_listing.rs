@@ -0,0 +1,130 @@
+use crate::*;
+use crate::utils::{to_nano, u128_dec_format, u64_dec_format};
+use degen_swap::degen::{global_register_degen, read_degens_from_storage, DegenType};
+use near_sdk::Timestamp;
+
+/// NEAR bond attached to a listing proposal, returned on approval or expiry, slashed to the owner on rejection.
+pub const DEGEN_LISTING_BOND: Balance = 10_000_000_000_000_000_000_000_000;
+/// Guardians review a listing within this window, after it the proposer can take the bond back.
+pub const DEGEN_LISTING_REVIEW_SEC: u32 = 3 * 24 * 3600;
+
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone)]
+#[serde(crate = "near_sdk::serde")]
+#[cfg_attr(not(target_arch = "wasm32"), derive(Debug))]
+pub struct DegenListing {
+    pub proposer_id: AccountId,
+    pub degen_type: DegenType,
+    #[serde(with = "u128_dec_format")]
+    pub bond: Balance,
+    #[serde(with = "u64_dec_format")]
+    pub review_end_time: Timestamp,
+}
+
+pub fn read_degen_listings_from_storage() -> UnorderedMap<AccountId, DegenListing> {
+    if let Some(content) = env::storage_read(DEGEN_LISTINGS.as_bytes()) {
+        UnorderedMap::try_from_slice(&content).expect("deserialize degen listings failed.")
+    } else {
+        UnorderedMap::new(StorageKey::DegenListings)
+    }
+}
+
+pub fn write_degen_listings_to_storage(degen_listings: UnorderedMap<AccountId, DegenListing>) {
+    env::storage_write(
+        DEGEN_LISTINGS.as_bytes(),
+        &degen_listings.try_to_vec().unwrap(),
+    );
+}
+
+impl Contract {
+    fn internal_take_degen_listing(&mut self, token_id: &AccountId) -> DegenListing {
+        let mut degen_listings = read_degen_listings_from_storage();
+        let listing = degen_listings.remove(token_id).expect("Degen listing not found");
+        write_degen_listings_to_storage(degen_listings);
+        listing
+    }
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Propose a new degen token with its oracle config for guardian review.
+    /// Attached deposit must cover DEGEN_LISTING_BOND, the rest is refunded.
+    #[payable]
+    pub fn propose_degen_token(&mut self, token_id: ValidAccountId, degen_type: DegenType) {
+        self.assert_contract_running();
+        let token_id: AccountId = token_id.into();
+        let proposer_id = env::predecessor_account_id();
+        assert!(!read_degens_from_storage().contains_key(&token_id), "Degen token {} already exist", token_id);
+        let mut degen_listings = read_degen_listings_from_storage();
+        assert!(degen_listings.get(&token_id).is_none(), "Degen listing already exist");
+        let attached_deposit = env::attached_deposit();
+        assert!(attached_deposit >= DEGEN_LISTING_BOND, "Insufficient listing bond: {}", DEGEN_LISTING_BOND);
+        degen_listings.insert(&token_id, &DegenListing {
+            proposer_id: proposer_id.clone(),
+            degen_type,
+            bond: DEGEN_LISTING_BOND,
+            review_end_time: env::block_timestamp() + to_nano(DEGEN_LISTING_REVIEW_SEC),
+        });
+        write_degen_listings_to_storage(degen_listings);
+        log!("{} proposes degen token {}", proposer_id, token_id);
+        if attached_deposit > DEGEN_LISTING_BOND {
+            Promise::new(proposer_id).transfer(attached_deposit - DEGEN_LISTING_BOND);
+        }
+    }
+
+    /// Register the proposed degen token and return the bond to the proposer.
+    #[payable]
+    pub fn approve_degen_listing(&mut self, token_id: ValidAccountId) {
+        assert_one_yocto();
+        assert!(self.is_owner_or_guardians(), "{}", ERR100_NOT_ALLOWED);
+        audit_privileged_action("approve_degen_listing");
+        let token_id: AccountId = token_id.into();
+        let listing = self.internal_take_degen_listing(&token_id);
+        assert!(env::block_timestamp() < listing.review_end_time, "Degen listing review expired");
+        if global_register_degen(&token_id, listing.degen_type.clone()) {
+            log!("New {:?} typed degen token {} registered by {}", listing.degen_type, token_id, env::predecessor_account_id());
+        } else {
+            env::panic(format!("Degen token {} already exist", token_id).as_bytes());
+        }
+        Promise::new(listing.proposer_id).transfer(listing.bond);
+    }
+
+    /// Drop the proposed degen token, slashing the bond to the owner.
+    #[payable]
+    pub fn reject_degen_listing(&mut self, token_id: ValidAccountId) {
+        assert_one_yocto();
+        assert!(self.is_owner_or_guardians(), "{}", ERR100_NOT_ALLOWED);
+        audit_privileged_action("reject_degen_listing");
+        let token_id: AccountId = token_id.into();
+        let listing = self.internal_take_degen_listing(&token_id);
+        assert!(env::block_timestamp() < listing.review_end_time, "Degen listing review expired");
+        log!("Degen listing {} of {} rejected by {}", token_id, listing.proposer_id, env::predecessor_account_id());
+        Promise::new(self.owner_id.clone()).transfer(listing.bond);
+    }
+
+    /// Take back the bond of a listing left unreviewed past its window.
+    pub fn withdraw_expired_degen_listing(&mut self, token_id: ValidAccountId) {
+        let token_id: AccountId = token_id.into();
+        let listing = self.internal_take_degen_listing(&token_id);
+        assert!(env::block_timestamp() >= listing.review_end_time, "Degen listing under review");
+        log!("Degen listing {} of {} expired", token_id, listing.proposer_id);
+        Promise::new(listing.proposer_id).transfer(listing.bond);
+    }
+
+    pub fn get_degen_listing(&self, token_id: ValidAccountId) -> Option<DegenListing> {
+        read_degen_listings_from_storage().get(token_id.as_ref())
+    }
+
+    pub fn get_degen_listings(&self, from_index: Option<u64>, limit: Option<u64>) -> HashMap<AccountId, DegenListing> {
+        let degen_listings = read_degen_listings_from_storage();
+        let keys = degen_listings.keys_as_vector();
+        let from_index = from_index.unwrap_or(0);
+        let limit = limit.unwrap_or(keys.len());
+        (from_index..std::cmp::min(keys.len(), from_index + limit))
+            .map(|index| {
+                let key = keys.get(index).unwrap();
+                let listing = degen_listings.get(&key).unwrap();
+                (key, listing)
+            })
+            .collect()
+    }
+}
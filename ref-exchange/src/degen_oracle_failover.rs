@@ -0,0 +1,123 @@
+use crate::*;
+use near_sdk::json_types::U64;
+
+/// Second price source of a degen token, its price replaces the primary one while
+/// the primary is stale or unavailable.
+#[derive(BorshSerialize, BorshDeserialize, Clone)]
+pub struct DegenFallback {
+    pub degen: Degen,
+    /// Whether the token currently trades on the fallback price.
+    pub is_active: bool,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+#[cfg_attr(not(target_arch = "wasm32"), derive(Debug))]
+pub struct DegenFallbackInfo {
+    pub degen_type: String,
+    pub degen_price: Option<U128>,
+    pub last_update_ts: Option<U64>,
+    pub is_active: bool,
+}
+
+pub fn read_degen_fallbacks_from_storage() -> HashMap<AccountId, DegenFallback> {
+    if let Some(content) = env::storage_read(DEGEN_FALLBACKS.as_bytes()) {
+        HashMap::try_from_slice(&content).expect("deserialize degen fallbacks failed.")
+    } else {
+        HashMap::new()
+    }
+}
+
+pub fn write_degen_fallbacks_to_storage(degen_fallbacks: HashMap<AccountId, DegenFallback>) {
+    env::storage_write(
+        DEGEN_FALLBACKS.as_bytes(),
+        &degen_fallbacks.try_to_vec().unwrap(),
+    );
+}
+
+/// Requests a new price from the primary source of the token and, if any, from its fallback.
+pub fn sync_degen_prices(token_id: &AccountId) {
+    global_get_degen(token_id).sync_token_price(token_id);
+    if let Some(fallback) = read_degen_fallbacks_from_storage().get(token_id) {
+        fallback.degen.async_update().then(ext_self::update_degen_fallback_price_callback(
+            token_id.clone(),
+            &env::current_account_id(),
+            NO_DEPOSIT,
+            GAS_FOR_BASIC_OP,
+        ));
+    }
+}
+
+/// Hands the token back to its primary source once that one delivers again.
+pub fn on_degen_primary_price_updated(token_id: &AccountId) {
+    let mut degen_fallbacks = read_degen_fallbacks_from_storage();
+    if let Some(fallback) = degen_fallbacks.get_mut(token_id) {
+        if fallback.is_active {
+            fallback.is_active = false;
+            write_degen_fallbacks_to_storage(degen_fallbacks);
+            event::Event::DegenOracleRecovered { token_id }.emit();
+        }
+    }
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Set the fallback price source of a degen token, None to remove it.
+    #[payable]
+    pub fn set_degen_fallback_oracle(&mut self, token_id: ValidAccountId, degen_type: Option<DegenType>) {
+        assert_one_yocto();
+        assert!(self.is_owner_or_guardians(), "{}", ERR100_NOT_ALLOWED);
+        audit_privileged_action("set_degen_fallback_oracle");
+        let token_id: AccountId = token_id.into();
+        assert!(read_degens_from_storage().contains_key(&token_id), "{} is not degen token", token_id);
+        let mut degen_fallbacks = read_degen_fallbacks_from_storage();
+        if let Some(degen_type) = degen_type {
+            log!("Degen {} fallback oracle set to {:?}", token_id, degen_type);
+            degen_fallbacks.insert(token_id.clone(), DegenFallback {
+                degen: Degen::new(token_id, degen_type),
+                is_active: false,
+            });
+        } else {
+            assert!(degen_fallbacks.remove(&token_id).is_some(), "No fallback oracle for {}", token_id);
+        }
+        write_degen_fallbacks_to_storage(degen_fallbacks);
+    }
+
+    /// The async return of the fallback source update. Fails the token over to the fallback
+    /// price while its primary price is stale or missing.
+    #[private]
+    pub fn update_degen_fallback_price_callback(&mut self, token_id: AccountId) {
+        if let Some(cross_call_result) = near_sdk::promise_result_as_success() {
+            let mut degen_fallbacks = read_degen_fallbacks_from_storage();
+            let fallback = match degen_fallbacks.get_mut(&token_id) {
+                Some(fallback) => fallback,
+                None => return,
+            };
+            let new_degen = fallback.degen.set_price(&cross_call_result);
+            let mut degen = global_get_degen(&token_id);
+            if fallback.is_active || !degen.has_valid_price() {
+                degen.set_price_info(fallback.degen.get_price_info().clone());
+                global_set_degen(&token_id, &degen);
+                if !fallback.is_active {
+                    fallback.is_active = true;
+                    event::Event::DegenOracleFailover {
+                        token_id: &token_id,
+                        degen_type: fallback.degen.get_type(),
+                        price: U128(new_degen),
+                    }.emit();
+                }
+                log!("Token {} got new degen {} from fallback oracle.", token_id, new_degen);
+            }
+            write_degen_fallbacks_to_storage(degen_fallbacks);
+        }
+    }
+
+    pub fn get_degen_fallback_oracle(&self, token_id: ValidAccountId) -> Option<DegenFallbackInfo> {
+        read_degen_fallbacks_from_storage().get(token_id.as_ref()).map(|fallback| DegenFallbackInfo {
+            degen_type: fallback.degen.get_type(),
+            degen_price: fallback.degen.price_info().map(|price_info| price_info.stored_degen.into()),
+            last_update_ts: fallback.degen.price_info().map(|price_info| price_info.degen_updated_at.into()),
+            is_active: fallback.is_active,
+        })
+    }
+}
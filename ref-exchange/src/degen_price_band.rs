@@ -0,0 +1,97 @@
+use crate::*;
+use crate::utils::u128_dec_format;
+
+/// Absolute bounds for a degen token price, in the same precision as the stored degen price.
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone)]
+#[serde(crate = "near_sdk::serde")]
+#[cfg_attr(not(target_arch = "wasm32"), derive(Debug))]
+pub struct DegenPriceBand {
+    #[serde(with = "u128_dec_format")]
+    pub min_price: u128,
+    #[serde(with = "u128_dec_format")]
+    pub max_price: u128,
+}
+
+impl DegenPriceBand {
+    pub fn contains(&self, price: u128) -> bool {
+        price >= self.min_price && price <= self.max_price
+    }
+}
+
+pub fn read_degen_price_bands_from_storage() -> HashMap<AccountId, DegenPriceBand> {
+    if let Some(content) = env::storage_read(DEGEN_PRICE_BANDS.as_bytes()) {
+        HashMap::try_from_slice(&content).expect("deserialize degen price bands failed.")
+    } else {
+        HashMap::new()
+    }
+}
+
+pub fn write_degen_price_bands_to_storage(degen_price_bands: HashMap<AccountId, DegenPriceBand>) {
+    env::storage_write(
+        DEGEN_PRICE_BANDS.as_bytes(),
+        &degen_price_bands.try_to_vec().unwrap(),
+    );
+}
+
+/// Returns the given degen tokens whose price falls outside their band.
+pub fn degen_tokens_out_of_band(token_ids: &[AccountId], prices: &[u128]) -> Vec<AccountId> {
+    let degen_price_bands = read_degen_price_bands_from_storage();
+    if degen_price_bands.is_empty() {
+        return vec![];
+    }
+    token_ids.iter().zip(prices.iter())
+        .filter(|(token_id, price)| degen_price_bands.get(*token_id).map(|band| !band.contains(**price)).unwrap_or(false))
+        .map(|(token_id, _)| token_id.clone())
+        .collect()
+}
+
+/// Panics if any of the given degen prices falls outside its token's band.
+/// Pools holding such a token are suspended until the price comes back within the band.
+pub fn assert_degen_prices_in_band(token_ids: &[AccountId], prices: &[u128]) {
+    let out_of_band = degen_tokens_out_of_band(token_ids, prices);
+    assert!(out_of_band.is_empty(), "Degen price of {} out of band", out_of_band[0]);
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Set the price band of a degen token.
+    #[payable]
+    pub fn set_degen_price_band(&mut self, token_id: ValidAccountId, min_price: U128, max_price: U128) {
+        assert_one_yocto();
+        assert!(self.is_owner_or_guardians(), "{}", ERR100_NOT_ALLOWED);
+        audit_privileged_action("set_degen_price_band");
+        assert!(min_price.0 > 0 && min_price.0 <= max_price.0, "Invalid price band");
+        global_get_degen(token_id.as_ref());
+        let mut degen_price_bands = read_degen_price_bands_from_storage();
+        degen_price_bands.insert(token_id.clone().into(), DegenPriceBand {
+            min_price: min_price.0,
+            max_price: max_price.0,
+        });
+        write_degen_price_bands_to_storage(degen_price_bands);
+        log!("Degen {} price band set to [{}, {}]", token_id, min_price.0, max_price.0);
+    }
+
+    #[payable]
+    pub fn remove_degen_price_band(&mut self, token_id: ValidAccountId) {
+        assert_one_yocto();
+        self.assert_owner();
+        audit_privileged_action("remove_degen_price_band");
+        let mut degen_price_bands = read_degen_price_bands_from_storage();
+        assert!(degen_price_bands.remove(token_id.as_ref()).is_some(), "Invalid token_id");
+        write_degen_price_bands_to_storage(degen_price_bands);
+    }
+
+    pub fn get_degen_price_bands(&self) -> HashMap<AccountId, DegenPriceBand> {
+        read_degen_price_bands_from_storage()
+    }
+
+    /// Whether the stored price of the given degen token is usable, i.e. not expired and within its band.
+    pub fn is_degen_price_usable(&self, token_id: ValidAccountId) -> bool {
+        let degen = global_get_degen(token_id.as_ref());
+        degen.is_price_valid()
+            && read_degen_price_bands_from_storage()
+                .get(token_id.as_ref())
+                .map(|band| band.contains(degen.get_price_info().stored_degen))
+                .unwrap_or(true)
+    }
+}
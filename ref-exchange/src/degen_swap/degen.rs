@@ -50,7 +50,7 @@ pub enum Degen {
     PythOracle(PythOracleDegen),
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Debug, Clone)]
 #[serde(crate = "near_sdk::serde")]
 pub enum DegenType {
     PriceOracle {
@@ -96,6 +96,27 @@ impl Degen {
             Degen::PythOracle(_) => "PythOracle".to_string(),
         }
     }
+
+    /// The stored price, None before the first update.
+    pub fn price_info(&self) -> Option<&PriceInfo> {
+        match self {
+            Degen::PriceOracle(d) => d.price_info.as_ref(),
+            Degen::PythOracle(d) => d.price_info.as_ref(),
+        }
+    }
+
+    /// Overrides the stored price, used to fail over to another source.
+    pub fn set_price_info(&mut self, price_info: PriceInfo) {
+        match self {
+            Degen::PriceOracle(d) => d.price_info = Some(price_info),
+            Degen::PythOracle(d) => d.price_info = Some(price_info),
+        }
+    }
+
+    /// Whether a price is stored and not expired.
+    pub fn has_valid_price(&self) -> bool {
+        self.price_info().is_some() && self.is_price_valid()
+    }
 }
 
 impl DegenTrait for Degen {
@@ -5,7 +5,7 @@ use near_sdk::{env, AccountId, Balance, Timestamp};
 
 use crate::admin_fee::AdminFees;
 use crate::errors::*;
-use crate::degen_swap::math::{
+use ref_exchange_math::degen::{
     Fees, DegenSwap, SwapResult, MAX_AMP, MAX_AMP_CHANGE, MIN_AMP, MIN_RAMP_DURATION,
 };
 use crate::utils::{add_to_collection, u128_ratio, SwapVolume, FEE_DIVISOR, U256};
@@ -15,7 +15,6 @@ pub use self::degen::*;
 pub use self::price_oracle::*;
 pub use self::pyth_oracle::*;
 
-mod math;
 pub mod degen;
 mod price_oracle;
 mod pyth_oracle;
@@ -104,6 +103,7 @@ impl DegenSwapPool {
         for token_id in &self.token_account_ids {
             assert!(is_global_degen_price_valid(token_id) == true, "{}", ERR129_DEGENS_EXPIRED);
         }
+        crate::assert_degen_prices_in_band(&self.token_account_ids, &self.get_degens());
     }
 
     pub fn get_amounts(&self) ->Vec<u128> {
@@ -117,7 +117,7 @@ impl DegenSwapPool {
         amounts
     }
 
-    fn amounts_to_c_amounts(&self, amounts: &Vec<u128>) ->Vec<u128> {
+    pub fn amounts_to_c_amounts(&self, amounts: &Vec<u128>) ->Vec<u128> {
         let mut c_amounts = amounts.clone();
         for (index, value) in self.token_decimals.iter().enumerate() {
             let factor = 10_u128
@@ -242,7 +242,7 @@ impl DegenSwapPool {
                     &c_amounts,
                     &self.c_amounts,
                     self.shares_total_supply,
-                    &Fees::new(self.total_fee, &fees),
+                    &Fees::new(self.total_fee, fees.admin_fee_bps),
                 )
                 .expect(ERR67_LPSHARE_CALC_ERR)
         }
@@ -396,7 +396,7 @@ impl DegenSwapPool {
         }
 
         let invariant = self.get_invariant_with_degens(degens.as_ref().unwrap_or(&self.get_degens()));
-        let trade_fee = Fees::new(self.total_fee, &fees);
+        let trade_fee = Fees::new(self.total_fee, fees.admin_fee_bps);
 
         let (burn_shares, _) = invariant
             .compute_lp_amount_for_withdraw(
@@ -434,7 +434,7 @@ impl DegenSwapPool {
         self.assert_degens_valid();
 
         let invariant = self.get_invariant_with_degens(&self.get_degens());
-        let trade_fee = Fees::new(self.total_fee, &fees);
+        let trade_fee = Fees::new(self.total_fee, fees.admin_fee_bps);
 
         let (burn_shares, fee_part) = invariant
             .compute_lp_amount_for_withdraw(
@@ -504,6 +504,28 @@ impl DegenSwapPool {
         burn_shares
     }
 
+    /// Quote of a swap, None where prices expired or left the band, the swap fails to compute or would leave the out reserve below MIN_RESERVE.
+    pub fn try_quote_swap(&self, token_in: &AccountId, amount_in: Balance, token_out: &AccountId, fees: &AdminFees) -> Option<Balance> {
+        if !self.token_account_ids.iter().all(is_global_degen_price_valid)
+            || !crate::degen_tokens_out_of_band(&self.token_account_ids, &self.get_degens()).is_empty() {
+            return None;
+        }
+        let in_idx = self.token_index(token_in);
+        let out_idx = self.token_index(token_out);
+        let result = self.get_invariant_with_degens(&self.get_degens())
+            .swap_to(
+                in_idx,
+                self.amount_to_c_amount(amount_in, in_idx),
+                out_idx,
+                &self.c_amounts,
+                &Fees::new(self.total_fee, fees.admin_fee_bps),
+            )?;
+        if result.new_destination_amount < MIN_RESERVE {
+            return None;
+        }
+        Some(self.c_amount_to_amount(result.amount_swapped, out_idx))
+    }
+
     /// Returns number of tokens in outcome, given amount.
     /// Tokens are provided as indexes into token list for given pool.
     /// All tokens are comparable tokens
@@ -535,7 +557,7 @@ impl DegenSwapPool {
                 c_amount_in,
                 token_out,
                 &self.c_amounts,
-                &Fees::new(self.total_fee, &fees),
+                &Fees::new(self.total_fee, fees.admin_fee_bps),
             )
             .expect(ERR70_SWAP_OUT_CALC_ERR)
 
@@ -0,0 +1,110 @@
+use crate::*;
+use crate::degen_swap::degen::is_global_degen_price_valid;
+use crate::utils::{FEE_DIVISOR, U256};
+
+/// Most utilization thresholds that can be configured.
+pub const MAX_DEGEN_TVL_ALERT_THRESHOLDS: usize = 5;
+
+/// Utilization thresholds of degen pool TVL limits in bps, ascending.
+pub fn read_degen_tvl_alert_thresholds_from_storage() -> Vec<u32> {
+    if let Some(content) = env::storage_read(DEGEN_TVL_ALERT_THRESHOLDS.as_bytes()) {
+        Vec::try_from_slice(&content).expect("deserialize degen tvl alert thresholds failed.")
+    } else {
+        vec![]
+    }
+}
+
+pub fn write_degen_tvl_alert_thresholds_to_storage(thresholds: Vec<u32>) {
+    env::storage_write(
+        DEGEN_TVL_ALERT_THRESHOLDS.as_bytes(),
+        &thresholds.try_to_vec().unwrap(),
+    );
+}
+
+/// Highest threshold each degen pool was last seen above.
+pub fn read_degen_tvl_alert_levels_from_storage() -> LookupMap<u64, u32> {
+    if let Some(content) = env::storage_read(DEGEN_TVL_ALERT_LEVELS.as_bytes()) {
+        LookupMap::try_from_slice(&content).expect("deserialize degen tvl alert levels failed.")
+    } else {
+        LookupMap::new(StorageKey::DegenTvlAlertLevels)
+    }
+}
+
+pub fn write_degen_tvl_alert_levels_to_storage(levels: LookupMap<u64, u32>) {
+    env::storage_write(
+        DEGEN_TVL_ALERT_LEVELS.as_bytes(),
+        &levels.try_to_vec().unwrap(),
+    );
+}
+
+impl Contract {
+    /// Emits an event when the TVL of a degen pool with a limit moved across a threshold
+    /// since it was last checked. Skipped while a price of the pool is invalid.
+    pub(crate) fn internal_check_degen_tvl_utilization(&self, pool_id: u64, pool: &Pool) {
+        let p = match pool {
+            Pool::DegenSwapPool(p) => p,
+            _ => return,
+        };
+        let thresholds = read_degen_tvl_alert_thresholds_from_storage();
+        if thresholds.is_empty() || !p.token_account_ids.iter().all(is_global_degen_price_valid) {
+            return;
+        }
+        let tvl_limit = match read_pool_limit_from_storage().get(&pool_id) {
+            Some(pool_limit) => pool_limit.get_degen_pool_limit().tvl_limit,
+            None => return,
+        };
+        if tvl_limit == 0 {
+            return;
+        }
+        let tvl = p.get_tvl();
+        let utilization_bps = std::cmp::min(
+            U256::from(tvl) * U256::from(FEE_DIVISOR) / U256::from(tvl_limit),
+            U256::from(u32::MAX),
+        ).as_u32();
+        let level = thresholds.iter().rev().find(|threshold| utilization_bps >= **threshold).cloned().unwrap_or(0);
+        let mut levels = read_degen_tvl_alert_levels_from_storage();
+        if levels.get(&pool_id).unwrap_or(0) == level {
+            return;
+        }
+        if level == 0 {
+            levels.remove(&pool_id);
+        } else {
+            levels.insert(&pool_id, &level);
+        }
+        write_degen_tvl_alert_levels_to_storage(levels);
+        event::Event::DegenTvlUtilization {
+            pool_id,
+            utilization_bps,
+            threshold_bps: level,
+            tvl: U128(tvl),
+            tvl_limit: U128(tvl_limit),
+        }.emit();
+    }
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Set the TVL limit utilization thresholds in bps that degen pools emit events at, empty to stop them.
+    #[payable]
+    pub fn set_degen_tvl_alert_thresholds(&mut self, thresholds_bps: Vec<u32>) {
+        assert_one_yocto();
+        assert!(self.is_owner_or_guardians(), "{}", ERR100_NOT_ALLOWED);
+        audit_privileged_action("set_degen_tvl_alert_thresholds");
+        assert!(thresholds_bps.len() <= MAX_DEGEN_TVL_ALERT_THRESHOLDS, "Too many thresholds");
+        assert!(
+            thresholds_bps.iter().all(|threshold| *threshold > 0 && *threshold <= FEE_DIVISOR)
+                && thresholds_bps.windows(2).all(|pair| pair[0] < pair[1]),
+            "Thresholds must be ascending within (0, {}]", FEE_DIVISOR
+        );
+        write_degen_tvl_alert_thresholds_to_storage(thresholds_bps);
+    }
+
+    pub fn get_degen_tvl_alert_thresholds(&self) -> Vec<u32> {
+        read_degen_tvl_alert_thresholds_from_storage()
+    }
+
+    /// Highest threshold the pool was last seen above, 0 for none.
+    pub fn get_degen_tvl_alert_level(&self, pool_id: u64) -> u32 {
+        read_degen_tvl_alert_levels_from_storage().get(&pool_id).unwrap_or(0)
+    }
+}
@@ -0,0 +1,67 @@
+use crate::*;
+
+/// Accounts blocked from depositing and swapping, e.g. sanctioned or linked to an exploit.
+/// They can still withdraw their balances to themselves.
+pub fn read_denied_accounts_from_storage() -> UnorderedSet<AccountId> {
+    if let Some(content) = env::storage_read(DENIED_ACCOUNTS.as_bytes()) {
+        UnorderedSet::try_from_slice(&content).expect("deserialize denied accounts failed.")
+    } else {
+        UnorderedSet::new(StorageKey::DeniedAccounts)
+    }
+}
+
+pub fn write_denied_accounts_to_storage(denied_accounts: UnorderedSet<AccountId>) {
+    env::storage_write(
+        DENIED_ACCOUNTS.as_bytes(),
+        &denied_accounts.try_to_vec().unwrap(),
+    );
+}
+
+pub fn assert_account_not_denied(account_id: &AccountId) {
+    assert!(!read_denied_accounts_from_storage().contains(account_id), "Account {} denied", account_id);
+}
+
+#[near_bindgen]
+impl Contract {
+    #[payable]
+    pub fn extend_denied_accounts(&mut self, account_ids: Vec<ValidAccountId>) {
+        assert_one_yocto();
+        assert!(self.is_owner_or_guardians(), "{}", ERR100_NOT_ALLOWED);
+        audit_privileged_action("extend_denied_accounts");
+        let account_ids: Vec<AccountId> = account_ids.into_iter().map(|account_id| account_id.into()).collect();
+        let mut denied_accounts = read_denied_accounts_from_storage();
+        for account_id in account_ids.iter() {
+            denied_accounts.insert(account_id);
+        }
+        write_denied_accounts_to_storage(denied_accounts);
+        event::Event::DenyListUpdate { account_ids: &account_ids, denied: true }.emit();
+    }
+
+    #[payable]
+    pub fn remove_denied_accounts(&mut self, account_ids: Vec<ValidAccountId>) {
+        assert_one_yocto();
+        assert!(self.is_owner_or_guardians(), "{}", ERR100_NOT_ALLOWED);
+        audit_privileged_action("remove_denied_accounts");
+        let account_ids: Vec<AccountId> = account_ids.into_iter().map(|account_id| account_id.into()).collect();
+        let mut denied_accounts = read_denied_accounts_from_storage();
+        for account_id in account_ids.iter() {
+            assert!(denied_accounts.remove(account_id), "Account {} not denied", account_id);
+        }
+        write_denied_accounts_to_storage(denied_accounts);
+        event::Event::DenyListUpdate { account_ids: &account_ids, denied: false }.emit();
+    }
+
+    pub fn is_account_denied(&self, account_id: ValidAccountId) -> bool {
+        read_denied_accounts_from_storage().contains(account_id.as_ref())
+    }
+
+    pub fn get_denied_accounts(&self, from_index: Option<u64>, limit: Option<u64>) -> Vec<AccountId> {
+        let denied_accounts = read_denied_accounts_from_storage();
+        let values = denied_accounts.as_vector();
+        let from_index = from_index.unwrap_or(0);
+        let limit = limit.unwrap_or(values.len());
+        (from_index..std::cmp::min(values.len(), from_index + limit))
+            .map(|index| values.get(index).unwrap())
+            .collect()
+    }
+}
@@ -0,0 +1,155 @@
+use crate::*;
+
+/// Where an account's balance of a token currently sits, see `get_deposits_detail`.
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+#[cfg_attr(not(target_arch = "wasm32"), derive(Debug, PartialEq))]
+pub struct TokenDepositDetail {
+    /// Inner balance, free to swap or withdraw.
+    pub available: U128,
+    /// Sent out by withdrawals whose transfer hasn't resolved yet, credited back if it fails.
+    pub pending_withdrawal: U128,
+    /// Escrowed for the account: open TWAP orders, unsold tokens and proceeds of its launch auctions,
+    /// LP incentives it funds that LPs haven't claimed, and failed withdrawals held for reclaim.
+    pub locked: U128,
+    pub total: U128,
+}
+
+/// Where an account's shares of a pool currently sit, see `get_deposits_detail`.
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+#[cfg_attr(not(target_arch = "wasm32"), derive(Debug, PartialEq))]
+pub struct ShareDepositDetail {
+    /// Shares that can be moved or removed right now.
+    pub available: U128,
+    /// Held by share locks, outgoing streams, votes or protocol-owned liquidity.
+    pub locked: U128,
+    /// Used in farming or burrowland through shadow actions.
+    pub shadowed: U128,
+    pub total: U128,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+#[cfg_attr(not(target_arch = "wasm32"), derive(Debug))]
+pub struct DepositsDetail {
+    pub tokens: HashMap<AccountId, TokenDepositDetail>,
+    /// Pools the account has shadowed or locked shares in.
+    pub shares: HashMap<u64, ShareDepositDetail>,
+}
+
+fn read_account_token_amounts(key: &str, storage_key: StorageKey) -> LookupMap<AccountId, HashMap<AccountId, Balance>> {
+    if let Some(content) = env::storage_read(key.as_bytes()) {
+        LookupMap::try_from_slice(&content).expect("deserialize account token amounts failed.")
+    } else {
+        LookupMap::new(storage_key)
+    }
+}
+
+/// Adds `amount` to the account's entry for the token when `increase`, subtracts it otherwise.
+/// Entries from before the tracking existed are missing, so subtraction saturates.
+fn update_account_token_amount(key: &str, storage_key: StorageKey, account_id: &AccountId, token_id: &AccountId, amount: Balance, increase: bool) {
+    let mut amounts = read_account_token_amounts(key, storage_key);
+    let mut account_amounts = amounts.get(account_id).unwrap_or_default();
+    let prev = account_amounts.get(token_id).copied().unwrap_or(0);
+    let new = if increase { prev + amount } else { prev.saturating_sub(amount) };
+    if new == 0 {
+        account_amounts.remove(token_id);
+    } else {
+        account_amounts.insert(token_id.clone(), new);
+    }
+    if account_amounts.is_empty() {
+        amounts.remove(account_id);
+    } else {
+        amounts.insert(account_id, &account_amounts);
+    }
+    env::storage_write(key.as_bytes(), &amounts.try_to_vec().unwrap());
+}
+
+pub fn read_pending_withdrawals_from_storage() -> LookupMap<AccountId, HashMap<AccountId, Balance>> {
+    read_account_token_amounts(PENDING_WITHDRAWALS, StorageKey::PendingWithdrawals)
+}
+
+pub fn read_locked_deposits_from_storage() -> LookupMap<AccountId, HashMap<AccountId, Balance>> {
+    read_account_token_amounts(LOCKED_DEPOSITS, StorageKey::LockedDeposits)
+}
+
+/// Records tokens sent out of the account until the transfer callback settles them.
+pub fn add_pending_withdrawal(account_id: &AccountId, token_id: &AccountId, amount: Balance) {
+    update_account_token_amount(PENDING_WITHDRAWALS, StorageKey::PendingWithdrawals, account_id, token_id, amount, true);
+}
+
+pub fn settle_pending_withdrawal(account_id: &AccountId, token_id: &AccountId, amount: Balance) {
+    update_account_token_amount(PENDING_WITHDRAWALS, StorageKey::PendingWithdrawals, account_id, token_id, amount, false);
+}
+
+/// Records tokens escrowed for the account outside its inner balance.
+pub fn add_locked_deposit(account_id: &AccountId, token_id: &AccountId, amount: Balance) {
+    update_account_token_amount(LOCKED_DEPOSITS, StorageKey::LockedDeposits, account_id, token_id, amount, true);
+}
+
+pub fn release_locked_deposit(account_id: &AccountId, token_id: &AccountId, amount: Balance) {
+    update_account_token_amount(LOCKED_DEPOSITS, StorageKey::LockedDeposits, account_id, token_id, amount, false);
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Deposits of the account split into available, pending withdrawal and locked amounts
+    /// per token, and its shares split into available, locked and shadowed amounts per pool.
+    pub fn get_deposits_detail(&self, account_id: ValidAccountId) -> DepositsDetail {
+        let account_id: AccountId = account_id.into();
+        let account = self.internal_get_account(&account_id);
+        let pending = read_pending_withdrawals_from_storage().get(&account_id).unwrap_or_default();
+        let locked = read_locked_deposits_from_storage().get(&account_id).unwrap_or_default();
+
+        let mut token_ids: Vec<AccountId> = account.as_ref().map(|account| account.get_tokens()).unwrap_or_default();
+        token_ids.extend(pending.keys().chain(locked.keys()).cloned());
+        token_ids.sort();
+        token_ids.dedup();
+        let tokens = token_ids
+            .into_iter()
+            .map(|token_id| {
+                let available = account.as_ref().and_then(|account| account.get_balance(&token_id)).unwrap_or(0);
+                let pending_withdrawal = pending.get(&token_id).copied().unwrap_or(0);
+                let locked = locked.get(&token_id).copied().unwrap_or(0);
+                (token_id, TokenDepositDetail {
+                    available: U128(available),
+                    pending_withdrawal: U128(pending_withdrawal),
+                    locked: U128(locked),
+                    total: U128(available + pending_withdrawal + locked),
+                })
+            })
+            .collect();
+
+        let share_locks = read_share_locks_from_storage();
+        let mut pool_ids: Vec<u64> = read_account_share_locks_from_storage()
+            .get(&account_id)
+            .unwrap_or_default()
+            .iter()
+            .filter_map(|lock_id| share_locks.get(lock_id).map(|lock| lock.pool_id))
+            .collect();
+        if let Some(account) = account.as_ref() {
+            pool_ids.extend(account.shadow_records.keys());
+        }
+        pool_ids.sort_unstable();
+        pool_ids.dedup();
+        let shares = pool_ids
+            .into_iter()
+            .filter_map(|pool_id| {
+                let total = self.pools.get(pool_id)?.share_balances(&account_id);
+                let shadowed = account.as_ref()
+                    .and_then(|account| account.get_shadow_record(pool_id))
+                    .map(|record| std::cmp::min(std::cmp::max(record.shadow_in_farm, record.shadow_in_burrow), total))
+                    .unwrap_or(0);
+                let locked = std::cmp::min(self.internal_locked_shares(&account_id, pool_id), total);
+                Some((pool_id, ShareDepositDetail {
+                    available: U128(total - std::cmp::max(shadowed, locked)),
+                    locked: U128(locked),
+                    shadowed: U128(shadowed),
+                    total: U128(total),
+                }))
+            })
+            .collect();
+        DepositsDetail { tokens, shares }
+    }
+}
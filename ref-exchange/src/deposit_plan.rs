@@ -0,0 +1,151 @@
+use crate::*;
+
+/// What to do once all the deposits of a plan arrived, given in the msg of the first deposit.
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct DepositPlanInfo {
+    /// Deposits to wait for after the first one, token and amount.
+    pub deposits: HashMap<AccountId, U128>,
+    pub referral_id: Option<ValidAccountId>,
+    /// Swaps run on the inner account first.
+    #[serde(default)]
+    pub actions: Vec<Action>,
+    /// Liquidity then added from the inner account.
+    #[serde(default)]
+    pub add_liquidity_infos: Vec<AddLiquidityInfo>,
+}
+
+/// An account's plan in progress. Deposits land in the inner account as they come,
+/// so an unfinished plan can simply be cancelled.
+#[derive(BorshSerialize, BorshDeserialize)]
+pub struct DepositPlan {
+    pub remaining: HashMap<AccountId, Balance>,
+    /// `DepositPlanInfo` as JSON.
+    pub info: String,
+    /// Taken from the account's storage deposit for keeping the plan, given back when it's dropped.
+    pub storage_cost: Balance,
+}
+
+pub fn read_deposit_plans_from_storage() -> LookupMap<AccountId, DepositPlan> {
+    if let Some(content) = env::storage_read(DEPOSIT_PLANS.as_bytes()) {
+        LookupMap::try_from_slice(&content).expect("deserialize deposit plans failed.")
+    } else {
+        LookupMap::new(StorageKey::DepositPlans)
+    }
+}
+
+pub fn write_deposit_plans_to_storage(deposit_plans: LookupMap<AccountId, DepositPlan>) {
+    env::storage_write(
+        DEPOSIT_PLANS.as_bytes(),
+        &deposit_plans.try_to_vec().unwrap(),
+    );
+}
+
+impl Contract {
+    /// Deposits the first token of a plan and either runs it or keeps it waiting for the rest.
+    pub(crate) fn internal_start_deposit_plan(&mut self, sender_id: &AccountId, token_in: &AccountId, amount: Balance, info: DepositPlanInfo) {
+        let mut deposit_plans = read_deposit_plans_from_storage();
+        assert!(deposit_plans.get(sender_id).is_none(), "Deposit plan in progress");
+        assert!(!info.deposits.contains_key(token_in), "Deposit plan expects token {} again", token_in);
+        self.assert_no_frozen_tokens(std::slice::from_ref(token_in));
+        self.internal_deposit(sender_id, token_in, amount);
+        if info.deposits.is_empty() {
+            self.internal_execute_deposit_plan(sender_id, info);
+            return;
+        }
+        let prev_storage = env::storage_usage();
+        deposit_plans.insert(sender_id, &DepositPlan {
+            remaining: info.deposits.iter().map(|(token_id, amount)| (token_id.clone(), amount.0)).collect(),
+            info: near_sdk::serde_json::to_string(&info).unwrap(),
+            storage_cost: 0,
+        });
+        let mut account = self.internal_unwrap_account(sender_id);
+        let storage_cost = self.internal_charge_account_storage(sender_id, &mut account, prev_storage);
+        self.internal_save_account(sender_id, account);
+        let mut plan = deposit_plans.get(sender_id).unwrap();
+        plan.storage_cost = storage_cost;
+        deposit_plans.insert(sender_id, &plan);
+        write_deposit_plans_to_storage(deposit_plans);
+        log!("Deposit plan of {} waits for {:?}", sender_id, plan.remaining);
+    }
+
+    /// Deposits a further token of the sender's plan and runs the plan once nothing is missing.
+    pub(crate) fn internal_continue_deposit_plan(&mut self, sender_id: &AccountId, token_in: &AccountId, amount: Balance) {
+        let mut deposit_plans = read_deposit_plans_from_storage();
+        let mut plan = deposit_plans.get(sender_id).expect("No deposit plan in progress");
+        let expected = plan.remaining.get(token_in).copied().expect("Token not expected by deposit plan");
+        self.assert_no_frozen_tokens(std::slice::from_ref(token_in));
+        self.internal_deposit(sender_id, token_in, amount);
+        if amount < expected {
+            plan.remaining.insert(token_in.clone(), expected - amount);
+        } else {
+            plan.remaining.remove(token_in);
+        }
+        if !plan.remaining.is_empty() {
+            deposit_plans.insert(sender_id, &plan);
+            write_deposit_plans_to_storage(deposit_plans);
+            return;
+        }
+        deposit_plans.remove(sender_id);
+        write_deposit_plans_to_storage(deposit_plans);
+        let mut account = self.internal_unwrap_account(sender_id);
+        account.near_amount += plan.storage_cost;
+        self.internal_save_account(sender_id, account);
+        let info: DepositPlanInfo = near_sdk::serde_json::from_str(&plan.info).unwrap();
+        self.internal_execute_deposit_plan(sender_id, info);
+    }
+
+    /// Runs the swaps then adds the liquidity of a complete plan, all on the inner account.
+    /// Storage taken by new LP registrations is paid from the account's storage deposit.
+    fn internal_execute_deposit_plan(&mut self, sender_id: &AccountId, info: DepositPlanInfo) {
+        let prev_storage = env::storage_usage();
+        if !info.actions.is_empty() {
+            let referral_info = self.internal_get_referral_info(info.referral_id.map(|rid| rid.into()), sender_id);
+            let mut account = self.internal_unwrap_account(sender_id);
+            self.internal_execute_actions(sender_id, &mut account, &referral_info, &info.actions, ActionResult::None, None);
+            self.internal_save_account(sender_id, account);
+        }
+        for add_liquidity_info in info.add_liquidity_infos {
+            let pool_id = add_liquidity_info.pool_id;
+            self.internal_update_unit_share_cumulative_info(pool_id);
+            match self.internal_get_pool(pool_id) {
+                Pool::SimplePool(_) | Pool::RangePool(_) => {
+                    self.internal_add_liquidity(pool_id, sender_id, add_liquidity_info.amounts, add_liquidity_info.min_amounts);
+                }
+                _ => {
+                    let min_shares = add_liquidity_info.min_shares.expect("Need input min_shares");
+                    self.internal_add_stable_liquidity(pool_id, sender_id, add_liquidity_info.amounts, min_shares);
+                }
+            }
+        }
+        if env::storage_usage() > prev_storage {
+            let mut account = self.internal_unwrap_account(sender_id);
+            self.internal_charge_account_storage(sender_id, &mut account, prev_storage);
+            self.internal_save_account(sender_id, account);
+        }
+        log!("Deposit plan of {} executed", sender_id);
+    }
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Drop the caller's unfinished deposit plan. Tokens deposited so far stay in the inner account.
+    #[payable]
+    pub fn cancel_deposit_plan(&mut self) {
+        assert_one_yocto();
+        let sender_id = env::predecessor_account_id();
+        let mut deposit_plans = read_deposit_plans_from_storage();
+        let plan = deposit_plans.remove(&sender_id).expect("No deposit plan in progress");
+        write_deposit_plans_to_storage(deposit_plans);
+        let mut account = self.internal_unwrap_account(&sender_id);
+        account.near_amount += plan.storage_cost;
+        self.internal_save_account(&sender_id, account);
+    }
+
+    /// Returns the deposits the account's plan still waits for.
+    pub fn get_deposit_plan(&self, account_id: ValidAccountId) -> Option<HashMap<AccountId, U128>> {
+        read_deposit_plans_from_storage().get(account_id.as_ref()).map(|plan| {
+            plan.remaining.into_iter().map(|(token_id, amount)| (token_id, amount.into())).collect()
+        })
+    }
+}
@@ -0,0 +1,98 @@
+//! Test-only mode running each mutating pool operation first through its prediction view
+//! and then for real as the predecessor, failing as soon as the two results differ.
+
+use crate::*;
+
+fn assert_predicted<T: PartialEq + std::fmt::Debug>(operation: &str, predicted: T, executed: T) {
+    assert_eq!(predicted, executed, "Prediction of {} diverged from execution", operation);
+}
+
+impl Contract {
+    fn internal_deposits_of(&self, account_id: &AccountId, tokens: &[AccountId]) -> Vec<Balance> {
+        tokens.iter().map(|token_id| self.internal_get_deposit(account_id, token_id)).collect()
+    }
+
+    /// `predict_swap_actions` on the predecessor's deposits against `execute_actions`, plus
+    /// `get_return` for a single swap.
+    pub(crate) fn diff_execute_actions(&mut self, actions: Vec<Action>) -> U128 {
+        let sender_id = env::predecessor_account_id();
+        let tokens: Vec<AccountId> = get_tokens_in_actions(&actions).into_iter().collect();
+        let deposits = tokens
+            .iter()
+            .map(|token_id| (token_id.clone(), U128(self.internal_get_deposit(&sender_id, token_id))))
+            .collect();
+        let predicted = self.predict_swap_actions(deposits, actions.clone());
+        let quoted = match actions.as_slice() {
+            [Action::Swap(swap_action)] => Some(self.get_return(
+                swap_action.pool_id,
+                swap_action.token_in.clone().try_into().unwrap(),
+                swap_action.amount_in.expect("No amount_in"),
+                swap_action.token_out.clone().try_into().unwrap(),
+            )),
+            _ => None,
+        };
+        let result = self.execute_actions(actions, None);
+        for token_id in tokens.iter() {
+            assert_predicted(
+                &format!("actions on {} deposit", token_id),
+                predicted.get(token_id).map(|amount| amount.0).unwrap_or(0),
+                self.internal_get_deposit(&sender_id, token_id),
+            );
+        }
+        if let Some(quoted) = quoted {
+            assert_predicted("get_return", quoted.0, result.to_amount());
+        }
+        U128(result.to_amount())
+    }
+
+    pub(crate) fn diff_add_liquidity(&mut self, pool_id: u64, amounts: Vec<U128>) -> U128 {
+        let sender_id = env::predecessor_account_id();
+        let tokens = self.internal_get_pool(pool_id).tokens().to_vec();
+        let predicted = self.predict_add_simple_liquidity(pool_id, &amounts);
+        let before = self.internal_deposits_of(&sender_id, &tokens);
+        let shares = self.add_liquidity(pool_id, amounts, None);
+        let spent: Vec<U128> = before
+            .iter()
+            .zip(self.internal_deposits_of(&sender_id, &tokens))
+            .map(|(before, after)| U128(before - after))
+            .collect();
+        assert_predicted("add_liquidity shares", predicted.mint_shares, shares);
+        assert_predicted("add_liquidity amounts", predicted.need_amounts, spent);
+        shares
+    }
+
+    pub(crate) fn diff_add_stable_liquidity(&mut self, pool_id: u64, amounts: Vec<U128>) -> U128 {
+        let mut predicted = vec![("predict_add_stable_liquidity", self.predict_add_stable_liquidity(pool_id, &amounts))];
+        match self.internal_get_pool(pool_id) {
+            Pool::RatedSwapPool(_) => predicted.push(("predict_add_rated_liquidity", self.predict_add_rated_liquidity(pool_id, &amounts, &None))),
+            Pool::DegenSwapPool(_) => predicted.push(("predict_add_degen_liquidity", self.predict_add_degen_liquidity(pool_id, &amounts, &None))),
+            _ => {}
+        }
+        let shares = self.add_stable_liquidity(pool_id, amounts, U128(1));
+        for (operation, predicted) in predicted {
+            assert_predicted(operation, predicted, shares);
+        }
+        shares
+    }
+
+    pub(crate) fn diff_remove_liquidity(&mut self, pool_id: u64, shares: U128) -> Vec<U128> {
+        let predicted = self.predict_remove_liquidity(pool_id, shares);
+        let amounts = self.remove_liquidity(pool_id, shares, vec![U128(0); predicted.len()]);
+        assert_predicted("remove_liquidity", predicted, amounts.clone());
+        amounts
+    }
+
+    pub(crate) fn diff_remove_liquidity_by_tokens(&mut self, pool_id: u64, amounts: Vec<U128>) -> U128 {
+        let mut predicted = vec![("predict_remove_liquidity_by_tokens", self.predict_remove_liquidity_by_tokens(pool_id, &amounts))];
+        match self.internal_get_pool(pool_id) {
+            Pool::RatedSwapPool(_) => predicted.push(("predict_remove_rated_liquidity_by_tokens", self.predict_remove_rated_liquidity_by_tokens(pool_id, &amounts, &None))),
+            Pool::DegenSwapPool(_) => predicted.push(("predict_remove_degen_liquidity_by_tokens", self.predict_remove_degen_liquidity_by_tokens(pool_id, &amounts, &None))),
+            _ => {}
+        }
+        let burnt = self.remove_liquidity_by_tokens(pool_id, amounts, U128(u128::MAX));
+        for (operation, predicted) in predicted {
+            assert_predicted(operation, predicted, burnt);
+        }
+        burnt
+    }
+}
@@ -17,9 +17,11 @@ impl Contract {
         self.assert_contract_running();
         let account_id = env::predecessor_account_id();
         let prev_storage = env::storage_usage();
-        let mut pool = self.pools.get(pool_id).expect(ERR85_NO_POOL);
+        let mut pool = self.internal_get_pool(pool_id);
         let donation_amount = amount.map(|v| v.0).unwrap_or(pool.share_balances(&account_id));
         assert!(donation_amount > 0, "Invalid amount");
+        self.assert_shares_unlocked(&account_id, pool_id, pool.share_balances(&account_id), donation_amount);
+        self.internal_settle_lp_fees(pool_id, &pool, &[&account_id, &env::current_account_id()]);
         pool.share_transfer(&account_id, &env::current_account_id(), donation_amount);
         if unregister == Some(true) {
             pool.share_unregister(&account_id);
@@ -0,0 +1,153 @@
+use crate::*;
+use crate::degen_swap::degen::{global_get_degen, is_global_degen_price_valid, DegenTrait};
+use crate::utils::{u128_dec_format, u64_dec_format, u128_ratio, FEE_DIVISOR};
+use near_contract_standards::fungible_token::core_impl::ext_fungible_token;
+use near_sdk::Timestamp;
+
+/// TVL limit of a degen pool kept at a part of the market cap of one of its tokens,
+/// valued with its degen price, within fixed bounds.
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone)]
+#[serde(crate = "near_sdk::serde")]
+#[cfg_attr(not(target_arch = "wasm32"), derive(Debug))]
+pub struct DynamicTvlLimit {
+    pub token_id: AccountId,
+    /// Part of the token's market cap in bps the pool TVL may reach.
+    pub cap_bps: u32,
+    #[serde(with = "u128_dec_format")]
+    pub min_tvl_limit: Balance,
+    #[serde(with = "u128_dec_format")]
+    pub max_tvl_limit: Balance,
+    /// Last refresh, 0 before the first one.
+    #[serde(with = "u64_dec_format")]
+    pub updated_at: Timestamp,
+}
+
+impl DynamicTvlLimit {
+    /// TVL limit for the token's total supply at the given degen price.
+    pub fn tvl_limit(&self, total_supply: Balance, price: Balance, decimals: u8) -> Balance {
+        let market_cap = u128_ratio(total_supply, price, PRECISION) / 10u128.pow(decimals as u32);
+        let limit = u128_ratio(market_cap, self.cap_bps as u128, FEE_DIVISOR as u128);
+        std::cmp::min(std::cmp::max(limit, self.min_tvl_limit), self.max_tvl_limit)
+    }
+}
+
+pub fn read_dynamic_tvl_limits_from_storage() -> LookupMap<u64, DynamicTvlLimit> {
+    if let Some(content) = env::storage_read(DYNAMIC_TVL_LIMITS.as_bytes()) {
+        LookupMap::try_from_slice(&content).expect("deserialize dynamic tvl limits failed.")
+    } else {
+        LookupMap::new(StorageKey::DynamicTvlLimits)
+    }
+}
+
+pub fn write_dynamic_tvl_limits_to_storage(dynamic_tvl_limits: LookupMap<u64, DynamicTvlLimit>) {
+    env::storage_write(
+        DYNAMIC_TVL_LIMITS.as_bytes(),
+        &dynamic_tvl_limits.try_to_vec().unwrap(),
+    );
+}
+
+impl Contract {
+    /// Sets the pool's TVL limit from the token's total supply, if its degen price is valid.
+    pub(crate) fn internal_apply_dynamic_tvl_limit(&mut self, pool_id: u64, total_supply: Balance) {
+        let mut dynamic_tvl_limits = read_dynamic_tvl_limits_from_storage();
+        let mut dynamic_tvl_limit = match dynamic_tvl_limits.get(&pool_id) {
+            Some(dynamic_tvl_limit) => dynamic_tvl_limit,
+            None => return,
+        };
+        let token_id = &dynamic_tvl_limit.token_id;
+        if !is_global_degen_price_valid(token_id) {
+            log!("Dynamic TVL limit of pool {} not refreshed, {} price is invalid", pool_id, token_id);
+            return;
+        }
+        let decimals = match self.internal_get_pool(pool_id) {
+            Pool::DegenSwapPool(p) => p.token_decimals[p.token_account_ids.iter().position(|id| id == token_id).unwrap()],
+            _ => unreachable!(),
+        };
+        let price = global_get_degen(token_id).get_price_info().stored_degen;
+        let tvl_limit = dynamic_tvl_limit.tvl_limit(total_supply, price, decimals);
+        let mut pool_limit = read_pool_limit_from_storage();
+        pool_limit.insert(&pool_id, &VPoolLimitInfo::DegenPoolLimit(DegenPoolLimitInfo { tvl_limit }.into()));
+        write_pool_limit_to_storage(pool_limit);
+        dynamic_tvl_limit.updated_at = env::block_timestamp();
+        dynamic_tvl_limits.insert(&pool_id, &dynamic_tvl_limit);
+        write_dynamic_tvl_limits_to_storage(dynamic_tvl_limits);
+        log!("TVL limit of pool {} set to {} for {} supply of {}", pool_id, tvl_limit, total_supply, token_id);
+    }
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Let the degen pool's TVL limit follow `cap_bps` of the market cap of one of its tokens,
+    /// within [min_tvl_limit, max_tvl_limit], from the next refresh on.
+    #[payable]
+    pub fn set_dynamic_tvl_limit(
+        &mut self,
+        pool_id: u64,
+        token_id: ValidAccountId,
+        cap_bps: u32,
+        min_tvl_limit: U128,
+        max_tvl_limit: U128,
+    ) {
+        assert_one_yocto();
+        self.assert_owner();
+        audit_privileged_action("set_dynamic_tvl_limit");
+        let pool = self.internal_get_pool(pool_id);
+        assert!(pool.kind() == "DEGEN_SWAP");
+        assert!(pool.tokens().contains(token_id.as_ref()), "{}", ERR53_TOKEN_NOT_IN_LIST);
+        assert!(cap_bps > 0 && cap_bps <= FEE_DIVISOR, "Invalid cap bps");
+        assert!(min_tvl_limit.0 <= max_tvl_limit.0, "Invalid TVL limit bounds");
+        let mut dynamic_tvl_limits = read_dynamic_tvl_limits_from_storage();
+        dynamic_tvl_limits.insert(&pool_id, &DynamicTvlLimit {
+            token_id: token_id.into(),
+            cap_bps,
+            min_tvl_limit: min_tvl_limit.0,
+            max_tvl_limit: max_tvl_limit.0,
+            updated_at: 0,
+        });
+        write_dynamic_tvl_limits_to_storage(dynamic_tvl_limits);
+    }
+
+    /// Stop following the market cap, the last TVL limit stays in place.
+    #[payable]
+    pub fn remove_dynamic_tvl_limit(&mut self, pool_id: u64) {
+        assert_one_yocto();
+        self.assert_owner();
+        audit_privileged_action("remove_dynamic_tvl_limit");
+        let mut dynamic_tvl_limits = read_dynamic_tvl_limits_from_storage();
+        assert!(dynamic_tvl_limits.remove(&pool_id).is_some(), "No dynamic TVL limit");
+        write_dynamic_tvl_limits_to_storage(dynamic_tvl_limits);
+    }
+
+    /// Anyone can recompute the pool's TVL limit from the token's current total supply.
+    /// Refresh the token's degen price first, the limit is only updated while it is valid.
+    pub fn refresh_dynamic_tvl_limit(&self, pool_id: u64) -> Promise {
+        let dynamic_tvl_limit = read_dynamic_tvl_limits_from_storage().get(&pool_id).expect("No dynamic TVL limit");
+        ext_fungible_token::ft_total_supply(
+            &dynamic_tvl_limit.token_id,
+            NO_DEPOSIT,
+            GAS_FOR_BASIC_OP,
+        )
+        .then(ext_self::refresh_dynamic_tvl_limit_callback(
+            pool_id,
+            &env::current_account_id(),
+            NO_DEPOSIT,
+            GAS_FOR_BASIC_OP,
+        ))
+    }
+
+    /// The async return of refresh_dynamic_tvl_limit.
+    #[private]
+    pub fn refresh_dynamic_tvl_limit_callback(&mut self, pool_id: u64) {
+        match near_sdk::promise_result_as_success() {
+            Some(result) => {
+                let total_supply = near_sdk::serde_json::from_slice::<U128>(&result).expect(ERR126_FAILED_TO_PARSE_RESULT);
+                self.internal_apply_dynamic_tvl_limit(pool_id, total_supply.0);
+            }
+            None => log!("Dynamic TVL limit of pool {} not refreshed, {}", pool_id, ERR124_CROSS_CALL_FAILED),
+        }
+    }
+
+    pub fn get_dynamic_tvl_limit(&self, pool_id: u64) -> Option<DynamicTvlLimit> {
+        read_dynamic_tvl_limits_from_storage().get(&pool_id)
+    }
+}
@@ -0,0 +1,97 @@
+use crate::*;
+use crate::utils::{u128_dec_format, u128_ratio};
+
+/// Admin fees a pool's swaps accrued to the exchange during one epoch.
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone)]
+#[serde(crate = "near_sdk::serde")]
+#[cfg_attr(not(target_arch = "wasm32"), derive(Debug))]
+pub struct EpochPoolFees {
+    pub pool_id: u64,
+    pub token_account_ids: Vec<AccountId>,
+    /// LP shares minted to the exchange as admin fees.
+    #[serde(with = "u128_dec_format")]
+    pub shares: Balance,
+    /// Token amounts the minted shares were worth when minted, in pool token order.
+    pub amounts: Vec<U128>,
+    pub swap_count: u64,
+}
+
+pub fn read_epoch_fees_from_storage() -> LookupMap<u64, Vec<EpochPoolFees>> {
+    if let Some(content) = env::storage_read(EPOCH_FEES.as_bytes()) {
+        LookupMap::try_from_slice(&content).expect("deserialize epoch fees failed.")
+    } else {
+        LookupMap::new(StorageKey::EpochFees)
+    }
+}
+
+pub fn write_epoch_fees_to_storage(epoch_fees: LookupMap<u64, Vec<EpochPoolFees>>) {
+    env::storage_write(
+        EPOCH_FEES.as_bytes(),
+        &epoch_fees.try_to_vec().unwrap(),
+    );
+}
+
+impl Contract {
+    /// Adds the admin fee shares a swap minted to the exchange, given its share balance before
+    /// the swap, to the pool's report of the current epoch.
+    pub(crate) fn internal_record_epoch_admin_fees(&mut self, pool_id: u64, pool: &Pool, prev_exchange_shares: Balance) {
+        let minted = pool.share_balances(&env::current_account_id()) - prev_exchange_shares;
+        if minted == 0 {
+            return;
+        }
+        let total_shares = pool.share_total_balance();
+        let epoch = env::epoch_height();
+        let mut epoch_fees = read_epoch_fees_from_storage();
+        let mut report = epoch_fees.get(&epoch).unwrap_or_default();
+        let index = match report.iter().position(|fees| fees.pool_id == pool_id) {
+            Some(index) => index,
+            None => {
+                report.push(EpochPoolFees {
+                    pool_id,
+                    token_account_ids: pool.tokens().to_vec(),
+                    shares: 0,
+                    amounts: vec![U128(0); pool.tokens().len()],
+                    swap_count: 0,
+                });
+                report.len() - 1
+            }
+        };
+        let fees = &mut report[index];
+        fees.shares += minted;
+        for (accrued, amount) in fees.amounts.iter_mut().zip(pool.get_amounts()) {
+            accrued.0 += u128_ratio(amount, minted, total_shares);
+        }
+        fees.swap_count += 1;
+        epoch_fees.insert(&epoch, &report);
+        write_epoch_fees_to_storage(epoch_fees);
+    }
+}
+
+/// Drops the pool's entry from the report of the given epoch, and the report once it's empty.
+pub(crate) fn remove_epoch_pool_fees(epoch: u64, pool_id: u64) -> bool {
+    let mut epoch_fees = read_epoch_fees_from_storage();
+    let mut report = match epoch_fees.get(&epoch) {
+        Some(report) => report,
+        None => return false,
+    };
+    let prev_len = report.len();
+    report.retain(|fees| fees.pool_id != pool_id);
+    if report.len() == prev_len {
+        return false;
+    }
+    if report.is_empty() {
+        epoch_fees.remove(&epoch);
+    } else {
+        epoch_fees.insert(&epoch, &report);
+    }
+    write_epoch_fees_to_storage(epoch_fees);
+    true
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Admin fees accrued from swaps in the given epoch, per pool in order of their first fee.
+    pub fn get_epoch_fee_report(&self, epoch: u64) -> Vec<EpochPoolFees> {
+        read_epoch_fees_from_storage().get(&epoch).unwrap_or_default()
+    }
+}
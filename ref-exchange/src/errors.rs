@@ -13,6 +13,7 @@ pub const ERR19_NONZERO_LP_SHARES: &str = "E19: Nonzero LP shares";
 
 // Accounts.
 
+pub const ERR20_OPERATION_IN_FLIGHT: &str = "E20: conflicting operation in flight";
 pub const ERR21_TOKEN_NOT_REG: &str = "E21: token not registered";
 pub const ERR22_NOT_ENOUGH_TOKENS: &str = "E22: not enough tokens in deposit";
 // pub const ERR23_NOT_ENOUGH_NEAR: &str = "E23: not enough NEAR in deposit";
@@ -78,6 +79,8 @@ pub const ERR90_FEE_TOO_LARGE: &str = "E90: fee too large";
 pub const ERR91_NOT_ENOUGH_SHARES: &str = "E91: not enough shares";
 pub const ERR92_TOKEN_DUPLICATES: &str = "E92: token duplicated";
 pub const ERR89_WRONG_AMOUNT_COUNT: &str = "E89: wrong amount count";
+pub const ERR93_INVALID_PRICE_RANGE: &str = "E93: invalid price range";
+pub const ERR94_OUT_OF_PRICE_RANGE: &str = "E94: swap moves price out of range";
 
 
 // owner
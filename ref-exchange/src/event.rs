@@ -20,6 +20,71 @@ pub enum Event<'a> {
         account_id: &'a AccountId,
         token_id: &'a AccountId,
         amount: U128,
+    },
+    PolDeploy {
+        account_id: &'a AccountId,
+        pool_id: u64,
+        shares: U128,
+        amounts: Vec<U128>,
+    },
+    PolWithdraw {
+        account_id: &'a AccountId,
+        pool_id: u64,
+        shares: U128,
+        amounts: Vec<U128>,
+    },
+    DegenOracleFailover {
+        token_id: &'a AccountId,
+        degen_type: String,
+        price: U128,
+    },
+    DegenOracleRecovered {
+        token_id: &'a AccountId,
+    },
+    Deposit {
+        account_id: &'a AccountId,
+        token_id: &'a AccountId,
+        amount: U128,
+        memo: &'a str,
+    },
+    Withdraw {
+        account_id: &'a AccountId,
+        token_id: &'a AccountId,
+        amount: U128,
+        memo: &'a str,
+    },
+    DenyListUpdate {
+        account_ids: &'a [AccountId],
+        denied: bool,
+    },
+    DegenTvlUtilization {
+        pool_id: u64,
+        utilization_bps: u32,
+        /// Highest threshold reached, 0 once back below all of them.
+        threshold_bps: u32,
+        tvl: U128,
+        tvl_limit: U128,
+    },
+    ComplianceShareTransfer {
+        pool_id: u64,
+        sender_id: &'a AccountId,
+        receiver_id: &'a AccountId,
+        amount: U128,
+    },
+    UnclaimedWithdrawalHeld {
+        account_id: &'a AccountId,
+        token_id: &'a AccountId,
+        amount: U128,
+    },
+    UnclaimedWithdrawalClaimed {
+        account_id: &'a AccountId,
+        token_id: &'a AccountId,
+        amount: U128,
+    },
+    UnclaimedWithdrawalSwept {
+        account_id: &'a AccountId,
+        token_id: &'a AccountId,
+        amount: U128,
     }
 }
 
@@ -0,0 +1,147 @@
+use crate::*;
+use crate::utils::{u128_dec_format, u128_ratio, FEE_DIVISOR};
+use near_sdk::collections::LookupSet;
+use near_sdk::json_types::U64;
+
+#[derive(BorshSerialize, BorshDeserialize, Default)]
+pub struct ExchangeCounters {
+    pub total_swaps: u64,
+    pub unique_accounts: u64,
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone, Default)]
+#[serde(crate = "near_sdk::serde")]
+#[cfg_attr(not(target_arch = "wasm32"), derive(Debug))]
+pub struct TokenStats {
+    /// Amount swapped in and out of pools.
+    #[serde(with = "u128_dec_format")]
+    pub volume: Balance,
+    /// Pool fees charged on the amount swapped in, admin and referral shares included.
+    #[serde(with = "u128_dec_format")]
+    pub fees: Balance,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+#[cfg_attr(not(target_arch = "wasm32"), derive(Debug))]
+pub struct ExchangeStats {
+    pub total_swaps: U64,
+    /// Distinct accounts that executed swap actions with storage left for their first-trade flag.
+    pub unique_accounts: U64,
+    pub tokens: HashMap<AccountId, TokenStats>,
+}
+
+pub fn read_exchange_counters_from_storage() -> ExchangeCounters {
+    if let Some(content) = env::storage_read(EXCHANGE_COUNTERS.as_bytes()) {
+        ExchangeCounters::try_from_slice(&content).expect("deserialize exchange counters failed.")
+    } else {
+        ExchangeCounters::default()
+    }
+}
+
+pub fn write_exchange_counters_to_storage(exchange_counters: ExchangeCounters) {
+    env::storage_write(
+        EXCHANGE_COUNTERS.as_bytes(),
+        &exchange_counters.try_to_vec().unwrap(),
+    );
+}
+
+pub fn read_exchange_token_stats_from_storage() -> UnorderedMap<AccountId, TokenStats> {
+    if let Some(content) = env::storage_read(EXCHANGE_TOKEN_STATS.as_bytes()) {
+        UnorderedMap::try_from_slice(&content).expect("deserialize exchange token stats failed.")
+    } else {
+        UnorderedMap::new(StorageKey::ExchangeTokenStats)
+    }
+}
+
+pub fn write_exchange_token_stats_to_storage(exchange_token_stats: UnorderedMap<AccountId, TokenStats>) {
+    env::storage_write(
+        EXCHANGE_TOKEN_STATS.as_bytes(),
+        &exchange_token_stats.try_to_vec().unwrap(),
+    );
+}
+
+pub fn read_exchange_traders_from_storage() -> LookupSet<AccountId> {
+    if let Some(content) = env::storage_read(EXCHANGE_TRADERS.as_bytes()) {
+        LookupSet::try_from_slice(&content).expect("deserialize exchange traders failed.")
+    } else {
+        LookupSet::new(StorageKey::ExchangeTraders)
+    }
+}
+
+pub fn write_exchange_traders_to_storage(exchange_traders: LookupSet<AccountId>) {
+    env::storage_write(
+        EXCHANGE_TRADERS.as_bytes(),
+        &exchange_traders.try_to_vec().unwrap(),
+    );
+}
+
+impl Contract {
+    /// Counts a swap through the pool.
+    pub(crate) fn internal_record_swap_stats(
+        &mut self,
+        pool: &Pool,
+        token_in: &AccountId,
+        amount_in: Balance,
+        token_out: &AccountId,
+        amount_out: Balance,
+    ) {
+        let mut exchange_counters = read_exchange_counters_from_storage();
+        exchange_counters.total_swaps += 1;
+        write_exchange_counters_to_storage(exchange_counters);
+
+        let mut exchange_token_stats = read_exchange_token_stats_from_storage();
+        let mut stats_in = exchange_token_stats.get(token_in).unwrap_or_default();
+        stats_in.volume += amount_in;
+        stats_in.fees += u128_ratio(amount_in, pool.get_fee() as u128, FEE_DIVISOR as u128);
+        exchange_token_stats.insert(token_in, &stats_in);
+        let mut stats_out = exchange_token_stats.get(token_out).unwrap_or_default();
+        stats_out.volume += amount_out;
+        exchange_token_stats.insert(token_out, &stats_out);
+        write_exchange_token_stats_to_storage(exchange_token_stats);
+    }
+
+    /// Counts the trader the first time it swaps. The first-trade flag is paid from the account's
+    /// storage balance, a trader that can't cover it yet is counted on a later swap.
+    pub(crate) fn internal_record_stats_trader(&mut self, trader_id: &AccountId, account: &mut Account) {
+        let mut exchange_traders = read_exchange_traders_from_storage();
+        if exchange_traders.contains(trader_id) {
+            return;
+        }
+        let prev_storage = env::storage_usage();
+        exchange_traders.insert(trader_id);
+        let storage_cost = (env::storage_usage() - prev_storage) as Balance * env::storage_byte_cost();
+        if account.storage_available() < storage_cost {
+            exchange_traders.remove(trader_id);
+            return;
+        }
+        account.near_amount -= storage_cost;
+        write_exchange_traders_to_storage(exchange_traders);
+        let mut exchange_counters = read_exchange_counters_from_storage();
+        exchange_counters.unique_accounts += 1;
+        write_exchange_counters_to_storage(exchange_counters);
+    }
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Exchange-wide counters since they were introduced, with per token stats paged.
+    pub fn get_exchange_stats(&self, from_index: Option<u64>, limit: Option<u64>) -> ExchangeStats {
+        let exchange_counters = read_exchange_counters_from_storage();
+        let exchange_token_stats = read_exchange_token_stats_from_storage();
+        let keys = exchange_token_stats.keys_as_vector();
+        let from_index = from_index.unwrap_or(0);
+        let limit = limit.unwrap_or(keys.len());
+        ExchangeStats {
+            total_swaps: exchange_counters.total_swaps.into(),
+            unique_accounts: exchange_counters.unique_accounts.into(),
+            tokens: (from_index..std::cmp::min(keys.len(), from_index + limit))
+                .map(|index| {
+                    let key = keys.get(index).unwrap();
+                    let stats = exchange_token_stats.get(&key).unwrap();
+                    (key, stats)
+                })
+                .collect(),
+        }
+    }
+}
@@ -0,0 +1,96 @@
+use crate::*;
+use crate::admin_fee::AdminFees;
+use crate::utils::{u128_ratio, FEE_DIVISOR};
+
+/// The spot price used for price impact is quoted with this fraction of the amount in.
+pub const PRICE_IMPACT_PROBE_DIVISOR: u128 = 10_000;
+
+/// Where the value of a swap goes, all fees in token_out.
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+#[cfg_attr(not(target_arch = "wasm32"), derive(Debug))]
+pub struct ReturnBreakdown {
+    /// Amount out had the pool charged no fee.
+    pub amount_out_before_fees: U128,
+    /// Part of the pool fee kept by LPs.
+    pub lp_fee: U128,
+    /// Part of the pool fee taken by the exchange.
+    pub admin_fee: U128,
+    /// Part of the admin fee going to the referral.
+    pub referral_fee: U128,
+    /// Surcharge of a stable pool swap worsening the pool balance.
+    pub imbalance_fee: U128,
+    /// Rebate of a stable pool swap improving the pool balance.
+    pub imbalance_rebate: U128,
+    /// Loss against the spot price before fees, in bps. None when amount_in is too small to quote the spot price.
+    pub price_impact_bps: Option<u32>,
+    pub amount_out: U128,
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Like `get_return`, with the fees of the swap itemized.
+    /// The referral only counts if it is active and registered in the pool, as at swap time.
+    pub fn get_return_with_breakdown(
+        &self,
+        pool_id: u64,
+        token_in: ValidAccountId,
+        amount_in: U128,
+        token_out: ValidAccountId,
+        referral_id: Option<ValidAccountId>,
+    ) -> ReturnBreakdown {
+        let mut pool = self.internal_get_swap_pool(pool_id);
+        // The exchange never trades, so no referral counts as a self referral here.
+        let referral_info = self.internal_get_referral_info(referral_id.map(|rid| rid.into()), &env::current_account_id());
+        let admin_fees = self.internal_admin_fees(pool_id, &referral_info);
+        let admin_fee_bps = admin_fees.admin_fee_bps;
+        let referral_fee_bps = admin_fees.referral_info.as_ref()
+            .filter(|(rid, _)| pool.share_has_registered(rid))
+            .map(|(_, fee_bps)| *fee_bps)
+            .unwrap_or(0);
+
+        let mut no_fee_pool = self.internal_get_pool(pool_id);
+        no_fee_pool.modify_total_fee(0);
+        let amount_out_before_fees = no_fee_pool.swap(token_in.as_ref(), amount_in.0, token_out.as_ref(), 0, AdminFees::zero(), true);
+        let probe_in = amount_in.0 / PRICE_IMPACT_PROBE_DIVISOR;
+        let price_impact_bps = if probe_in > 0 {
+            let mut probe_pool = self.internal_get_pool(pool_id);
+            probe_pool.modify_total_fee(0);
+            let spot_out = probe_pool.swap(token_in.as_ref(), probe_in, token_out.as_ref(), 0, AdminFees::zero(), true)
+                * PRICE_IMPACT_PROBE_DIVISOR;
+            Some(if spot_out > amount_out_before_fees {
+                u128_ratio(spot_out - amount_out_before_fees, FEE_DIVISOR as u128, spot_out) as u32
+            } else {
+                0
+            })
+        } else {
+            None
+        };
+
+        let prev_imbalance = stable_pool_imbalance(&pool);
+        let max_amount_out = self.internal_max_swap_out(pool_id, &pool, token_out.as_ref());
+        let swap_out = pool.swap(
+            token_in.as_ref(),
+            amount_in.0,
+            token_out.as_ref(),
+            0,
+            admin_fees,
+            true,
+        );
+        let amount_out = self.internal_apply_maker_rebate(pool_id, prev_imbalance, &pool, token_out.as_ref(), swap_out, true);
+        assert_within_swap_cap(max_amount_out, swap_out);
+
+        let total_fee = amount_out_before_fees.saturating_sub(swap_out);
+        let admin_fee = u128_ratio(total_fee, admin_fee_bps as u128, FEE_DIVISOR as u128);
+        ReturnBreakdown {
+            amount_out_before_fees: amount_out_before_fees.into(),
+            lp_fee: (total_fee - admin_fee).into(),
+            admin_fee: admin_fee.into(),
+            referral_fee: u128_ratio(admin_fee, referral_fee_bps as u128, FEE_DIVISOR as u128).into(),
+            imbalance_fee: swap_out.saturating_sub(amount_out).into(),
+            imbalance_rebate: amount_out.saturating_sub(swap_out).into(),
+            price_impact_bps,
+            amount_out: amount_out.into(),
+        }
+    }
+}
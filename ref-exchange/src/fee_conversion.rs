@@ -0,0 +1,70 @@
+use crate::*;
+use crate::utils::{u128_ratio, FEE_DIVISOR};
+
+impl Contract {
+    /// Redeems the exchange's fee shares of the pool, plus the admin fee tokens kept aside in token mode,
+    /// and swaps them inside the same pool into `target_token`. Returns the amount of `target_token` got.
+    /// Shares beyond the pool's withdrawal cap stay for a later conversion.
+    fn internal_convert_pool_admin_fees(&mut self, pool_id: u64, target_token: &AccountId) -> Balance {
+        let ex_id = env::current_account_id();
+        let mut pool = self.internal_get_pool(pool_id);
+        let tokens = pool.tokens().to_vec();
+        assert!(tokens.contains(target_token), "{}", ERR63_MISSING_TOKEN);
+        let mut amounts = vec![0; tokens.len()];
+        let mut shares = pool.share_balances(&ex_id);
+        if let Some(max_withdrawal_bps) = read_withdrawal_caps_from_storage().get(&pool_id) {
+            shares = std::cmp::min(shares, u128_ratio(pool.share_total_balance(), max_withdrawal_bps as u128, FEE_DIVISOR as u128));
+        }
+        if shares > 0 {
+            let reserves = pool.get_amounts();
+            self.internal_settle_lp_fees(pool_id, &pool, &[&ex_id]);
+            amounts = pool.remove_liquidity(&ex_id, shares, vec![0; tokens.len()], false);
+            self.assert_within_withdrawal_cap(pool_id, &reserves, &amounts);
+            self.pools.replace(pool_id, &pool);
+        }
+        let mut admin_fee_tokens = read_admin_fee_tokens_from_storage();
+        if let Some(accrued) = admin_fee_tokens.get(&pool_id) {
+            for (i, amount) in accrued.iter().enumerate() {
+                amounts[i] += amount;
+            }
+            admin_fee_tokens.insert(&pool_id, &vec![0; tokens.len()]);
+            write_admin_fee_tokens_to_storage(admin_fee_tokens);
+        }
+        let mut amount_out = 0;
+        for (token_id, amount) in tokens.iter().zip(amounts) {
+            if token_id == target_token {
+                amount_out += amount;
+            } else if amount > 0 {
+                amount_out += self.internal_pool_swap(pool_id, token_id, amount, target_token, 0, &None);
+            }
+        }
+        log!("Admin fees of pool {} converted into {} {}", pool_id, amount_out, target_token);
+        amount_out
+    }
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Convert the admin fees accumulated in the given pools into `target_token` and credit them to
+    /// the owner's inner account. Every pool must contain `target_token`, other fee tokens are swapped
+    /// into it through the pool they come from. `min_out` bounds the total received.
+    /// Only owner or guardians can call.
+    #[payable]
+    pub fn convert_admin_fees(&mut self, pool_ids: Vec<u64>, target_token: ValidAccountId, min_out: U128) -> U128 {
+        assert_one_yocto();
+        assert!(self.is_owner_or_guardians(), "{}", ERR100_NOT_ALLOWED);
+        audit_privileged_action("convert_admin_fees");
+        self.assert_contract_running();
+        let target_token: AccountId = target_token.into();
+        let mut amount_out = 0;
+        for pool_id in pool_ids {
+            amount_out += self.internal_convert_pool_admin_fees(pool_id, &target_token);
+        }
+        assert!(amount_out >= min_out.0, "{}", ERR68_SLIPPAGE);
+        let owner_id = self.owner_id.clone();
+        let mut deposits = self.internal_unwrap_account(&owner_id);
+        deposits.deposit(&target_token, amount_out);
+        self.internal_save_account(&owner_id, deposits);
+        amount_out.into()
+    }
+}
@@ -0,0 +1,108 @@
+use crate::*;
+use crate::utils::u64_dec_format;
+use near_sdk::json_types::U64;
+use near_sdk::Timestamp;
+
+/// Temporary total fee of a pool between start and end. It only applies to swaps and their
+/// quotes, the pool keeps its own fee, so fee changes during the holiday take effect right away.
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone)]
+#[serde(crate = "near_sdk::serde")]
+#[cfg_attr(not(target_arch = "wasm32"), derive(Debug))]
+pub struct FeeHoliday {
+    pub fee: u32,
+    #[serde(with = "u64_dec_format")]
+    pub start: Timestamp,
+    #[serde(with = "u64_dec_format")]
+    pub end: Timestamp,
+}
+
+impl FeeHoliday {
+    pub fn is_active(&self) -> bool {
+        env::block_timestamp() >= self.start && env::block_timestamp() < self.end
+    }
+
+    pub fn is_expired(&self) -> bool {
+        env::block_timestamp() >= self.end
+    }
+}
+
+pub fn read_fee_holidays_from_storage() -> LookupMap<u64, FeeHoliday> {
+    if let Some(content) = env::storage_read(FEE_HOLIDAYS.as_bytes()) {
+        LookupMap::try_from_slice(&content).expect("deserialize fee holidays failed.")
+    } else {
+        LookupMap::new(StorageKey::FeeHolidays)
+    }
+}
+
+pub fn write_fee_holidays_to_storage(fee_holidays: LookupMap<u64, FeeHoliday>) {
+    env::storage_write(
+        FEE_HOLIDAYS.as_bytes(),
+        &fee_holidays.try_to_vec().unwrap(),
+    );
+}
+
+impl Contract {
+    /// Loads the pool with its fee holiday applied, to quote swaps. Never save a pool loaded this way.
+    pub(crate) fn internal_get_swap_pool(&self, pool_id: u64) -> Pool {
+        let mut pool = self.internal_get_pool(pool_id);
+        if let Some(fee_holiday) = read_fee_holidays_from_storage().get(&pool_id) {
+            apply_fee_holiday(&fee_holiday, &mut pool);
+        }
+        pool
+    }
+
+    /// Applies the pool's fee holiday to a pool about to swap, deleting the holiday once it's over.
+    /// Returns the pool's own fee, to be restored before the pool is saved.
+    pub(crate) fn internal_apply_fee_holiday(&mut self, pool_id: u64, pool: &mut Pool) -> u32 {
+        let pool_fee = pool.get_fee();
+        let mut fee_holidays = read_fee_holidays_from_storage();
+        if let Some(fee_holiday) = fee_holidays.get(&pool_id) {
+            if fee_holiday.is_expired() {
+                fee_holidays.remove(&pool_id);
+                write_fee_holidays_to_storage(fee_holidays);
+            } else {
+                apply_fee_holiday(&fee_holiday, pool);
+            }
+        }
+        pool_fee
+    }
+}
+
+/// The holiday never raises the fee, e.g. after the pool's own fee was lowered below it.
+fn apply_fee_holiday(fee_holiday: &FeeHoliday, pool: &mut Pool) {
+    if fee_holiday.is_active() && fee_holiday.fee < pool.get_fee() {
+        pool.modify_total_fee(fee_holiday.fee);
+    }
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Schedule a reduced total fee for the pool's swaps between start and end, replacing any earlier holiday.
+    #[payable]
+    pub fn schedule_fee_holiday(&mut self, pool_id: u64, fee: u32, start: U64, end: U64) {
+        assert_one_yocto();
+        self.assert_owner();
+        audit_privileged_action("schedule_fee_holiday");
+        assert!(start.0 < end.0 && end.0 > env::block_timestamp(), "Invalid fee holiday window");
+        let pool_fee = self.internal_get_pool(pool_id).get_fee();
+        assert!(fee < pool_fee, "Fee holiday must reduce the fee of {}", pool_fee);
+        let mut fee_holidays = read_fee_holidays_from_storage();
+        fee_holidays.insert(&pool_id, &FeeHoliday { fee, start: start.0, end: end.0 });
+        write_fee_holidays_to_storage(fee_holidays);
+    }
+
+    /// Drop the pool's holiday, its swaps pay the pool's own fee again right away.
+    #[payable]
+    pub fn cancel_fee_holiday(&mut self, pool_id: u64) {
+        assert_one_yocto();
+        self.assert_owner();
+        audit_privileged_action("cancel_fee_holiday");
+        let mut fee_holidays = read_fee_holidays_from_storage();
+        fee_holidays.remove(&pool_id).expect("No fee holiday");
+        write_fee_holidays_to_storage(fee_holidays);
+    }
+
+    pub fn get_fee_holiday(&self, pool_id: u64) -> Option<FeeHoliday> {
+        read_fee_holidays_from_storage().get(&pool_id)
+    }
+}
@@ -0,0 +1,232 @@
+use crate::*;
+use crate::utils::{FEE_DIVISOR, U256};
+
+/// Precision of the reward token rates.
+pub const FEE_REBATE_RATE_PRECISION: u128 = 1_000_000_000_000_000_000_000_000;
+
+/// Trade mining program: `rebate_bps` of each swap's fee goes back to the trader in `reward_token`,
+/// valued with `token_rates`, the amount of reward token per unit of the swapped in token in FEE_REBATE_RATE_PRECISION.
+/// Swaps of tokens without a rate earn nothing.
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone)]
+#[serde(crate = "near_sdk::serde")]
+#[cfg_attr(not(target_arch = "wasm32"), derive(Debug))]
+pub struct FeeRebateConfig {
+    pub reward_token: AccountId,
+    pub rebate_bps: u32,
+    pub token_rates: HashMap<AccountId, U128>,
+}
+
+pub fn read_fee_rebate_config_from_storage() -> Option<FeeRebateConfig> {
+    env::storage_read(FEE_REBATE_CONFIG.as_bytes())
+        .map(|content| FeeRebateConfig::try_from_slice(&content).expect("deserialize fee rebate config failed."))
+}
+
+pub fn write_fee_rebate_config_to_storage(config: Option<FeeRebateConfig>) {
+    match config {
+        Some(config) => {
+            env::storage_write(FEE_REBATE_CONFIG.as_bytes(), &config.try_to_vec().unwrap());
+        }
+        None => {
+            env::storage_remove(FEE_REBATE_CONFIG.as_bytes());
+        }
+    }
+}
+
+pub fn read_fee_rebate_pot_from_storage() -> Balance {
+    if let Some(content) = env::storage_read(FEE_REBATE_POT.as_bytes()) {
+        Balance::try_from_slice(&content).expect("deserialize fee rebate pot failed.")
+    } else {
+        0
+    }
+}
+
+pub fn write_fee_rebate_pot_to_storage(pot: Balance) {
+    env::storage_write(
+        FEE_REBATE_POT.as_bytes(),
+        &pot.try_to_vec().unwrap(),
+    );
+}
+
+pub fn read_pending_fee_rebates_from_storage() -> LookupMap<AccountId, HashMap<AccountId, Balance>> {
+    if let Some(content) = env::storage_read(PENDING_FEE_REBATES.as_bytes()) {
+        LookupMap::try_from_slice(&content).expect("deserialize pending fee rebates failed.")
+    } else {
+        LookupMap::new(StorageKey::PendingFeeRebates)
+    }
+}
+
+pub fn write_pending_fee_rebates_to_storage(pending_fee_rebates: LookupMap<AccountId, HashMap<AccountId, Balance>>) {
+    env::storage_write(
+        PENDING_FEE_REBATES.as_bytes(),
+        &pending_fee_rebates.try_to_vec().unwrap(),
+    );
+}
+
+/// Earned and not yet claimed rebates of all accounts, per reward token.
+pub fn read_pending_fee_rebate_totals_from_storage() -> HashMap<AccountId, Balance> {
+    if let Some(content) = env::storage_read(PENDING_FEE_REBATE_TOTALS.as_bytes()) {
+        HashMap::try_from_slice(&content).expect("deserialize pending fee rebate totals failed.")
+    } else {
+        HashMap::new()
+    }
+}
+
+pub fn write_pending_fee_rebate_totals_to_storage(pending_fee_rebate_totals: HashMap<AccountId, Balance>) {
+    env::storage_write(
+        PENDING_FEE_REBATE_TOTALS.as_bytes(),
+        &pending_fee_rebate_totals.try_to_vec().unwrap(),
+    );
+}
+
+impl Contract {
+    /// Books the rebate of a swap for `trader_id`, paid out of the pot while it lasts.
+    /// A new pending entry is charged to the storage balance of the trader's `account`,
+    /// a trader that can't cover it, e.g. swapping instantly, earns nothing.
+    pub(crate) fn internal_accrue_fee_rebate(
+        &mut self,
+        trader_id: &AccountId,
+        account: &mut Account,
+        pool_id: u64,
+        token_in: &AccountId,
+        amount_in: Balance,
+    ) {
+        let config = match read_fee_rebate_config_from_storage() {
+            Some(config) => config,
+            None => return,
+        };
+        let rate = match config.token_rates.get(token_in) {
+            Some(rate) => rate.0,
+            None => return,
+        };
+        let pot = read_fee_rebate_pot_from_storage();
+        if pot == 0 {
+            return;
+        }
+        let fee = self.internal_get_pool(pool_id).get_fee();
+        let rebate = (U256::from(amount_in)
+            * U256::from(fee)
+            * U256::from(config.rebate_bps)
+            * U256::from(rate)
+            / U256::from(FEE_DIVISOR)
+            / U256::from(FEE_DIVISOR)
+            / U256::from(FEE_REBATE_RATE_PRECISION))
+            .as_u128()
+            .min(pot);
+        if rebate == 0 {
+            return;
+        }
+        let mut pending_fee_rebates = read_pending_fee_rebates_from_storage();
+        let prev_pending = pending_fee_rebates.get(trader_id);
+        let mut pending = prev_pending.clone().unwrap_or_default();
+        *pending.entry(config.reward_token.clone()).or_insert(0) += rebate;
+        let prev_storage = env::storage_usage();
+        pending_fee_rebates.insert(trader_id, &pending);
+        let storage_cost = env::storage_usage().saturating_sub(prev_storage) as Balance * env::storage_byte_cost();
+        if account.storage_available() < storage_cost {
+            match prev_pending {
+                Some(prev_pending) => pending_fee_rebates.insert(trader_id, &prev_pending),
+                None => pending_fee_rebates.remove(trader_id),
+            };
+            log!("Fee rebate of {} not booked, not enough storage", trader_id);
+            return;
+        }
+        account.near_amount -= storage_cost;
+        write_pending_fee_rebates_to_storage(pending_fee_rebates);
+        write_fee_rebate_pot_to_storage(pot - rebate);
+        let mut pending_totals = read_pending_fee_rebate_totals_from_storage();
+        *pending_totals.entry(config.reward_token.clone()).or_insert(0) += rebate;
+        write_pending_fee_rebate_totals_to_storage(pending_totals);
+        log!("Fee rebate {} {} to {}", rebate, config.reward_token, trader_id);
+    }
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Set or clear the trade mining program. The reward token can't change while the pot holds funds.
+    #[payable]
+    pub fn set_fee_rebate_config(&mut self, config: Option<FeeRebateConfig>) {
+        assert_one_yocto();
+        self.assert_owner();
+        audit_privileged_action("set_fee_rebate_config");
+        let pot = read_fee_rebate_pot_from_storage();
+        if pot > 0 {
+            let reward_token = read_fee_rebate_config_from_storage().map(|c| c.reward_token);
+            assert!(
+                reward_token == config.as_ref().map(|c| c.reward_token.clone()),
+                "Fee rebate pot not empty"
+            );
+        }
+        if let Some(config) = config.as_ref() {
+            assert!(config.rebate_bps <= FEE_DIVISOR, "{}", ERR101_ILLEGAL_FEE);
+        }
+        write_fee_rebate_config_to_storage(config);
+    }
+
+    /// Move reward tokens from the owner's inner account into the rebate pot.
+    #[payable]
+    pub fn fund_fee_rebate_pot(&mut self, amount: U128) {
+        assert_one_yocto();
+        self.assert_owner();
+        audit_privileged_action("fund_fee_rebate_pot");
+        let config = read_fee_rebate_config_from_storage().expect("Fee rebate not configured");
+        let mut owner_account = self.internal_unwrap_account(&self.owner_id);
+        owner_account.withdraw(&config.reward_token, amount.0);
+        self.internal_save_account(&self.owner_id.clone(), owner_account);
+        write_fee_rebate_pot_to_storage(read_fee_rebate_pot_from_storage() + amount.0);
+    }
+
+    /// Move reward tokens from the rebate pot back to the owner's inner account.
+    #[payable]
+    pub fn withdraw_fee_rebate_pot(&mut self, amount: U128) {
+        assert_one_yocto();
+        self.assert_owner();
+        audit_privileged_action("withdraw_fee_rebate_pot");
+        let config = read_fee_rebate_config_from_storage().expect("Fee rebate not configured");
+        let pot = read_fee_rebate_pot_from_storage();
+        assert!(amount.0 <= pot, "Not enough in fee rebate pot");
+        write_fee_rebate_pot_to_storage(pot - amount.0);
+        let mut owner_account = self.internal_unwrap_account(&self.owner_id);
+        owner_account.deposit(&config.reward_token, amount.0);
+        self.internal_save_account(&self.owner_id.clone(), owner_account);
+    }
+
+    /// Move the caller's earned rebates into its inner account.
+    #[payable]
+    pub fn claim_fee_rebates(&mut self) -> HashMap<AccountId, U128> {
+        assert_one_yocto();
+        self.assert_contract_running();
+        let sender_id = env::predecessor_account_id();
+        let mut pending_fee_rebates = read_pending_fee_rebates_from_storage();
+        let prev_storage = env::storage_usage();
+        let pending = pending_fee_rebates.remove(&sender_id).unwrap_or_default();
+        write_pending_fee_rebates_to_storage(pending_fee_rebates);
+        let mut account = self.internal_unwrap_account(&sender_id);
+        // the entry was charged to the account's storage balance when booked
+        account.near_amount += prev_storage.saturating_sub(env::storage_usage()) as Balance * env::storage_byte_cost();
+        let mut pending_totals = read_pending_fee_rebate_totals_from_storage();
+        for (token_id, amount) in pending.iter() {
+            account.deposit(token_id, *amount);
+            *pending_totals.get_mut(token_id).unwrap() -= amount;
+        }
+        write_pending_fee_rebate_totals_to_storage(pending_totals);
+        self.internal_save_account(&sender_id, account);
+        pending.into_iter().map(|(token_id, amount)| (token_id, amount.into())).collect()
+    }
+
+    pub fn get_fee_rebate_config(&self) -> Option<FeeRebateConfig> {
+        read_fee_rebate_config_from_storage()
+    }
+
+    pub fn get_fee_rebate_pot(&self) -> U128 {
+        read_fee_rebate_pot_from_storage().into()
+    }
+
+    pub fn get_pending_fee_rebates(&self, account_id: ValidAccountId) -> HashMap<AccountId, U128> {
+        read_pending_fee_rebates_from_storage()
+            .get(account_id.as_ref())
+            .unwrap_or_default()
+            .into_iter()
+            .map(|(token_id, amount)| (token_id, amount.into()))
+            .collect()
+    }
+}
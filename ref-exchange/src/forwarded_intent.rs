@@ -0,0 +1,54 @@
+use crate::*;
+
+/// Batched intent a smart-contract wallet forwards for its user through one ft_transfer_call.
+/// The wallet only sends the tokens, the transferred amount and whatever the actions
+/// produce belong to the beneficiary.
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct ForwardedIntent {
+    pub beneficiary_id: ValidAccountId,
+    pub referral_id: Option<ValidAccountId>,
+    /// Swaps run on the transferred amount, none to simply deposit it for the beneficiary.
+    #[serde(default)]
+    pub actions: Vec<Action>,
+    /// Send the outcome to the beneficiary's wallet instead of its inner account.
+    #[serde(default)]
+    pub withdraw: bool,
+    pub skip_unwrap_near: Option<bool>,
+}
+
+impl Contract {
+    /// Runs an intent forwarded by `sender_id` with the tokens it transferred.
+    pub(crate) fn internal_execute_forwarded_intent(
+        &mut self,
+        sender_id: &AccountId,
+        token_in: AccountId,
+        amount_in: Balance,
+        intent: ForwardedIntent,
+    ) {
+        let beneficiary_id: AccountId = intent.beneficiary_id.into();
+        assert_account_not_denied(&beneficiary_id);
+        assert!(!intent.withdraw || !intent.actions.is_empty(), "Nothing to withdraw without actions");
+        log!("Intent forwarded by {} for {}", sender_id, beneficiary_id);
+        let out_amounts = if intent.actions.is_empty() {
+            self.assert_no_frozen_tokens(std::slice::from_ref(&token_in));
+            vec![(token_in, amount_in)]
+        } else {
+            self.internal_direct_actions(
+                &beneficiary_id,
+                token_in,
+                amount_in,
+                intent.referral_id.map(|x| x.to_string()),
+                &intent.actions,
+                None,
+            )
+        };
+        for (token_out, amount_out) in out_amounts {
+            if intent.withdraw {
+                self.internal_send_tokens(&beneficiary_id, &token_out, amount_out, intent.skip_unwrap_near);
+            } else {
+                self.internal_deposit(&beneficiary_id, &token_out, amount_out);
+            }
+        }
+    }
+}
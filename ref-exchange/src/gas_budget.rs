@@ -0,0 +1,52 @@
+use crate::*;
+use crate::utils::{GAS_FOR_BASIC_OP, GAS_FOR_FT_TRANSFER, GAS_FOR_RESOLVE_TRANSFER};
+use near_sdk::json_types::U64;
+use near_sdk::Gas;
+
+/// Gas assumed for an action without a hint, enough for a swap in any pool kind.
+pub const DEFAULT_ACTION_GAS: Gas = 10_000_000_000_000;
+/// Gas of an output withdraw and its callback, the NEAR unwrap takes the same as a token transfer.
+pub const GAS_FOR_WITHDRAW_WITH_CALLBACK: Gas = GAS_FOR_FT_TRANSFER + GAS_FOR_RESOLVE_TRANSFER;
+
+impl Contract {
+    /// Gas the trader's actions need: each action's hint or DEFAULT_ACTION_GAS, the account
+    /// handling around them, and the withdraw of the output if the trader auto withdraws it.
+    pub(crate) fn internal_actions_gas_budget(&self, trader_id: &AccountId, actions: &[Action], gas_hints: &[Option<U64>]) -> Gas {
+        assert_eq!(gas_hints.len(), actions.len(), "Gas hints must match actions");
+        let actions_gas: Gas = gas_hints.iter().map(|hint| hint.map(|gas| gas.0).unwrap_or(DEFAULT_ACTION_GAS)).sum();
+        let auto_withdraw = read_account_preferences_from_storage()
+            .get(trader_id)
+            .map(|preferences| preferences.auto_withdraw)
+            .unwrap_or(false);
+        let withdraw_gas = if auto_withdraw && matches!(actions.last(), Some(Action::Swap(_))) {
+            GAS_FOR_WITHDRAW_WITH_CALLBACK
+        } else {
+            0
+        };
+        actions_gas + GAS_FOR_BASIC_OP + withdraw_gas
+    }
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Same as `execute_actions`, failing before any state change unless the gas left covers
+    /// the whole plan, see `get_actions_gas_budget`. `gas_hints` holds the expected gas of
+    /// each action, None for the default.
+    #[payable]
+    pub fn execute_actions_with_gas_hints(
+        &mut self,
+        actions: Vec<Action>,
+        referral_id: Option<ValidAccountId>,
+        gas_hints: Vec<Option<U64>>,
+    ) -> ActionResult {
+        let budget = self.internal_actions_gas_budget(&env::predecessor_account_id(), &actions, &gas_hints);
+        let gas_left = env::prepaid_gas() - env::used_gas();
+        assert!(gas_left >= budget, "Not enough gas for the actions: {} needed, {} left", budget, gas_left);
+        self.internal_execute_sender_actions(actions, referral_id, None, None, None)
+    }
+
+    /// Gas `execute_actions_with_gas_hints` requires to be left for the account's actions.
+    pub fn get_actions_gas_budget(&self, account_id: ValidAccountId, actions: Vec<Action>, gas_hints: Vec<Option<U64>>) -> U64 {
+        self.internal_actions_gas_budget(account_id.as_ref(), &actions, &gas_hints).into()
+    }
+}
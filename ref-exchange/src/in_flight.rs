@@ -0,0 +1,94 @@
+use crate::*;
+
+/// Pending cross-contract operations per account, keyed by the resource they touch
+/// (a token id for withdrawals, `shadow:<pool_id>` for shadow actions) with a pending count.
+pub fn read_in_flight_from_storage() -> LookupMap<AccountId, HashMap<String, u32>> {
+    if let Some(content) = env::storage_read(IN_FLIGHT_OPERATIONS.as_bytes()) {
+        LookupMap::try_from_slice(&content).expect("deserialize in flight operations failed.")
+    } else {
+        LookupMap::new(StorageKey::InFlightOperations)
+    }
+}
+
+pub fn write_in_flight_to_storage(in_flight: LookupMap<AccountId, HashMap<String, u32>>) {
+    env::storage_write(
+        IN_FLIGHT_OPERATIONS.as_bytes(),
+        &in_flight.try_to_vec().unwrap(),
+    );
+}
+
+pub fn shadow_in_flight_key(pool_id: u64) -> String {
+    format!("shadow:{}", pool_id)
+}
+
+/// Marks an operation on `resource` as pending until its callback releases it.
+pub fn acquire_in_flight(account_id: &AccountId, resource: &str) {
+    let mut in_flight = read_in_flight_from_storage();
+    let mut operations = in_flight.get(account_id).unwrap_or_default();
+    *operations.entry(resource.to_string()).or_insert(0) += 1;
+    in_flight.insert(account_id, &operations);
+    write_in_flight_to_storage(in_flight);
+}
+
+/// Releases one pending operation on `resource`.
+/// Callbacks scheduled before the guard existed have nothing to release, so a missing entry is ignored.
+pub fn release_in_flight(account_id: &AccountId, resource: &str) {
+    let mut in_flight = read_in_flight_from_storage();
+    if let Some(mut operations) = in_flight.get(account_id) {
+        if let Some(count) = operations.get_mut(resource) {
+            *count -= 1;
+            if *count == 0 {
+                operations.remove(resource);
+            }
+        }
+        if operations.is_empty() {
+            in_flight.remove(account_id);
+        } else {
+            in_flight.insert(account_id, &operations);
+        }
+        write_in_flight_to_storage(in_flight);
+    }
+}
+
+/// Panics if the account has a pending operation on `resource`, or on anything when `resource` is None.
+pub fn assert_no_in_flight(account_id: &AccountId, resource: Option<&str>) {
+    if let Some(operations) = read_in_flight_from_storage().get(account_id) {
+        let conflict = match resource {
+            Some(resource) => operations.contains_key(resource),
+            None => !operations.is_empty(),
+        };
+        assert!(!conflict, "{}", ERR20_OPERATION_IN_FLIGHT);
+    }
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Clears the account's pending operations on `resource`, or all of them when it's None, for
+    /// markers left behind by a callback that never ran, e.g. out of gas. Only once it's sure the callback
+    /// can't run anymore, else its release is ignored and a later operation loses its guard.
+    /// Returns the number of pending operations cleared.
+    #[payable]
+    pub fn clear_in_flight_operations(&mut self, account_id: ValidAccountId, resource: Option<String>) -> u32 {
+        assert_one_yocto();
+        assert!(self.is_owner_or_guardians(), "{}", ERR100_NOT_ALLOWED);
+        audit_privileged_action("clear_in_flight_operations");
+        let mut in_flight = read_in_flight_from_storage();
+        let mut operations = in_flight.get(account_id.as_ref()).unwrap_or_default();
+        let cleared = match resource {
+            Some(resource) => operations.remove(&resource).unwrap_or(0),
+            None => operations.drain().map(|(_, count)| count).sum(),
+        };
+        if operations.is_empty() {
+            in_flight.remove(account_id.as_ref());
+        } else {
+            in_flight.insert(account_id.as_ref(), &operations);
+        }
+        write_in_flight_to_storage(in_flight);
+        cleared
+    }
+
+    /// Returns the pending cross-contract operations of the given account.
+    pub fn get_in_flight_operations(&self, account_id: ValidAccountId) -> HashMap<String, u32> {
+        read_in_flight_from_storage().get(account_id.as_ref()).unwrap_or_default()
+    }
+}
@@ -0,0 +1,211 @@
+use crate::*;
+use crate::dao_adapter::read_dao_id_from_storage;
+use crate::utils::{u128_dec_format, u64_dec_format, u128_ratio, FEE_DIVISOR};
+use near_sdk::Timestamp;
+
+/// Pseudo account holding the insurance shares in each pool. No one can sign as it,
+/// so its shares only leave through a DAO withdrawal.
+pub const INSURANCE_FUND_ID: &str = "@insurance";
+
+/// Highest part of the admin fees, in bps, that can be diverted into the insurance fund.
+pub const MAX_INSURANCE_FEE_BPS: u32 = 5000;
+
+/// Delay between the DAO requesting an insurance withdrawal and executing it.
+pub const INSURANCE_WITHDRAW_TIMELOCK: Timestamp = 7 * 24 * 3600 * 1_000_000_000;
+
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone)]
+#[serde(crate = "near_sdk::serde")]
+#[cfg_attr(not(target_arch = "wasm32"), derive(Debug))]
+pub struct InsuranceWithdrawal {
+    #[serde(with = "u128_dec_format")]
+    pub shares: Balance,
+    /// Inner account the removed liquidity goes to.
+    pub receiver_id: AccountId,
+    #[serde(with = "u64_dec_format")]
+    pub unlock_time: Timestamp,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+#[cfg_attr(not(target_arch = "wasm32"), derive(Debug))]
+pub struct InsuranceFundInfo {
+    /// Shares the fund holds in the pool now, grown by LP fees for simple pools.
+    pub shares: U128,
+    /// Shares diverted from admin fees so far.
+    pub accrued_shares: U128,
+    /// Token amounts the shares are worth now.
+    pub amounts: Vec<U128>,
+    pub pending_withdrawal: Option<InsuranceWithdrawal>,
+}
+
+pub fn read_insurance_fee_bps_from_storage() -> u32 {
+    if let Some(content) = env::storage_read(INSURANCE_FEE_BPS.as_bytes()) {
+        u32::try_from_slice(&content).expect("deserialize insurance fee bps failed.")
+    } else {
+        0
+    }
+}
+
+pub fn write_insurance_fee_bps_to_storage(insurance_fee_bps: u32) {
+    env::storage_write(
+        INSURANCE_FEE_BPS.as_bytes(),
+        &insurance_fee_bps.try_to_vec().unwrap(),
+    );
+}
+
+pub fn read_insurance_accrued_from_storage() -> LookupMap<u64, Balance> {
+    if let Some(content) = env::storage_read(INSURANCE_ACCRUED.as_bytes()) {
+        LookupMap::try_from_slice(&content).expect("deserialize insurance accrued failed.")
+    } else {
+        LookupMap::new(StorageKey::InsuranceAccrued)
+    }
+}
+
+pub fn write_insurance_accrued_to_storage(accrued: LookupMap<u64, Balance>) {
+    env::storage_write(
+        INSURANCE_ACCRUED.as_bytes(),
+        &accrued.try_to_vec().unwrap(),
+    );
+}
+
+pub fn read_insurance_withdrawals_from_storage() -> LookupMap<u64, InsuranceWithdrawal> {
+    if let Some(content) = env::storage_read(INSURANCE_WITHDRAWALS.as_bytes()) {
+        LookupMap::try_from_slice(&content).expect("deserialize insurance withdrawals failed.")
+    } else {
+        LookupMap::new(StorageKey::InsuranceWithdrawals)
+    }
+}
+
+pub fn write_insurance_withdrawals_to_storage(withdrawals: LookupMap<u64, InsuranceWithdrawal>) {
+    env::storage_write(
+        INSURANCE_WITHDRAWALS.as_bytes(),
+        &withdrawals.try_to_vec().unwrap(),
+    );
+}
+
+fn assert_dao() {
+    assert_eq!(
+        Some(env::predecessor_account_id()),
+        read_dao_id_from_storage(),
+        "{}", ERR100_NOT_ALLOWED
+    );
+}
+
+impl Contract {
+    /// Moves the configured part of the admin fee shares the exchange got from a swap,
+    /// given its share balance before the swap, to the insurance fund.
+    pub(crate) fn internal_divert_insurance_shares(&self, pool_id: u64, pool: &mut Pool, prev_exchange_shares: Balance) {
+        let insurance_fee_bps = read_insurance_fee_bps_from_storage();
+        if insurance_fee_bps == 0 {
+            return;
+        }
+        let exchange_id = env::current_account_id();
+        let minted = pool.share_balances(&exchange_id) - prev_exchange_shares;
+        let diverted = u128_ratio(minted, insurance_fee_bps as u128, FEE_DIVISOR as u128);
+        if diverted == 0 {
+            return;
+        }
+        let insurance_id = INSURANCE_FUND_ID.to_string();
+        self.internal_settle_lp_fees(pool_id, pool, &[&exchange_id, &insurance_id]);
+        if !pool.share_has_registered(&insurance_id) {
+            pool.share_register(&insurance_id);
+        }
+        pool.share_transfer(&exchange_id, &insurance_id, diverted);
+        let mut accrued = read_insurance_accrued_from_storage();
+        accrued.insert(&pool_id, &(accrued.get(&pool_id).unwrap_or(0) + diverted));
+        write_insurance_accrued_to_storage(accrued);
+    }
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Set the part of swap admin fees, in bps, diverted into the insurance fund.
+    #[payable]
+    pub fn set_insurance_fee_bps(&mut self, insurance_fee_bps: u32) {
+        assert_one_yocto();
+        self.assert_owner();
+        audit_privileged_action("set_insurance_fee_bps");
+        assert!(insurance_fee_bps <= MAX_INSURANCE_FEE_BPS, "Insurance fee bps over {}", MAX_INSURANCE_FEE_BPS);
+        write_insurance_fee_bps_to_storage(insurance_fee_bps);
+    }
+
+    /// Start the timelock for paying `shares` of the pool's insurance fund out to the receiver,
+    /// replacing any pending request. DAO only.
+    #[payable]
+    pub fn request_insurance_withdrawal(&mut self, pool_id: u64, shares: U128, receiver_id: ValidAccountId) {
+        assert_one_yocto();
+        assert_dao();
+        audit_privileged_action("request_insurance_withdrawal");
+        let pool = self.internal_get_pool(pool_id);
+        assert!(shares.0 > 0 && shares.0 <= pool.share_balances(&INSURANCE_FUND_ID.to_string()), "Invalid shares");
+        let mut withdrawals = read_insurance_withdrawals_from_storage();
+        withdrawals.insert(&pool_id, &InsuranceWithdrawal {
+            shares: shares.0,
+            receiver_id: receiver_id.into(),
+            unlock_time: env::block_timestamp() + INSURANCE_WITHDRAW_TIMELOCK,
+        });
+        write_insurance_withdrawals_to_storage(withdrawals);
+    }
+
+    /// Drop a pending insurance withdrawal, callable by the DAO, the owner or guardians.
+    #[payable]
+    pub fn cancel_insurance_withdrawal(&mut self, pool_id: u64) {
+        assert_one_yocto();
+        assert!(
+            self.is_owner_or_guardians() || read_dao_id_from_storage() == Some(env::predecessor_account_id()),
+            "{}", ERR100_NOT_ALLOWED
+        );
+        audit_privileged_action("cancel_insurance_withdrawal");
+        let mut withdrawals = read_insurance_withdrawals_from_storage();
+        withdrawals.remove(&pool_id).expect("No pending insurance withdrawal");
+        write_insurance_withdrawals_to_storage(withdrawals);
+    }
+
+    /// Remove the liquidity of an unlocked insurance withdrawal into the receiver's inner account. DAO only.
+    #[payable]
+    pub fn execute_insurance_withdrawal(&mut self, pool_id: u64, min_amounts: Vec<U128>) -> Vec<U128> {
+        assert_one_yocto();
+        assert_dao();
+        audit_privileged_action("execute_insurance_withdrawal");
+        self.assert_contract_running();
+        let mut withdrawals = read_insurance_withdrawals_from_storage();
+        let withdrawal = withdrawals.remove(&pool_id).expect("No pending insurance withdrawal");
+        assert!(env::block_timestamp() >= withdrawal.unlock_time, "Insurance withdrawal still timelocked");
+        write_insurance_withdrawals_to_storage(withdrawals);
+        let insurance_id = INSURANCE_FUND_ID.to_string();
+        let mut pool = self.internal_get_pool(pool_id);
+        self.internal_settle_lp_fees(pool_id, &pool, &[&insurance_id]);
+        let amounts = pool.remove_liquidity(
+            &insurance_id,
+            withdrawal.shares,
+            min_amounts.into_iter().map(|amount| amount.into()).collect(),
+            false,
+        );
+        self.pools.replace(pool_id, &pool);
+        let mut deposits = self.internal_unwrap_account(&withdrawal.receiver_id);
+        for (token_id, amount) in pool.tokens().iter().zip(amounts.iter()) {
+            deposits.deposit(token_id, *amount);
+        }
+        self.internal_save_account(&withdrawal.receiver_id, deposits);
+        log!("Insurance of pool {} paid {:?} to {}", pool_id, amounts, withdrawal.receiver_id);
+        amounts.into_iter().map(|amount| amount.into()).collect()
+    }
+
+    pub fn get_insurance_fee_bps(&self) -> u32 {
+        read_insurance_fee_bps_from_storage()
+    }
+
+    pub fn get_insurance_fund(&self, pool_id: u64) -> InsuranceFundInfo {
+        let pool = self.internal_get_pool(pool_id);
+        let shares = pool.share_balances(&INSURANCE_FUND_ID.to_string());
+        let total_shares = pool.share_total_balance();
+        InsuranceFundInfo {
+            shares: U128(shares),
+            accrued_shares: U128(read_insurance_accrued_from_storage().get(&pool_id).unwrap_or(0)),
+            amounts: pool.get_amounts().into_iter().map(|amount| {
+                U128(if total_shares == 0 { 0 } else { u128_ratio(amount, shares, total_shares) })
+            }).collect(),
+            pending_withdrawal: read_insurance_withdrawals_from_storage().get(&pool_id),
+        }
+    }
+}
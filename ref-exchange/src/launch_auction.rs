@@ -0,0 +1,263 @@
+use crate::*;
+use crate::utils::{to_nano, u128_dec_format, u128_ratio, u64_dec_format, U256};
+use near_sdk::Timestamp;
+
+/// Precision of launch auction prices, quote token units per sale token unit.
+pub const LAUNCH_PRICE_PRECISION: u128 = 1_000_000_000_000_000_000_000_000;
+pub const MAX_LAUNCH_AUCTION_SEC: u32 = 7 * 24 * 3600;
+
+/// Descending price sale of `sale_token` for the other token of a new simple pool.
+/// The price falls linearly from `start_price` to `end_price`, and each bid fills at the current one.
+/// Swaps and liquidity are closed until it's settled, seeding the pool at the last fill price
+/// from the raised quote tokens and the sale tokens reserved for it.
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone)]
+#[serde(crate = "near_sdk::serde")]
+#[cfg_attr(not(target_arch = "wasm32"), derive(Debug))]
+pub struct LaunchAuction {
+    pub creator_id: AccountId,
+    pub sale_token: AccountId,
+    pub quote_token: AccountId,
+    #[serde(with = "u128_dec_format")]
+    pub start_price: u128,
+    #[serde(with = "u128_dec_format")]
+    pub end_price: u128,
+    #[serde(with = "u64_dec_format")]
+    pub start: Timestamp,
+    #[serde(with = "u64_dec_format")]
+    pub end: Timestamp,
+    /// Sale tokens escrowed and not sold yet.
+    #[serde(with = "u128_dec_format")]
+    pub unsold: Balance,
+    /// Sale tokens escrowed apart from the sale to seed the pool, so it opens with liquidity
+    /// even once everything is sold.
+    #[serde(with = "u128_dec_format")]
+    pub seed_reserve: Balance,
+    /// Quote tokens paid by bidders.
+    #[serde(with = "u128_dec_format")]
+    pub raised: Balance,
+    /// Price of the last bid, 0 before the first.
+    #[serde(with = "u128_dec_format")]
+    pub last_price: u128,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+#[cfg_attr(not(target_arch = "wasm32"), derive(Debug))]
+pub struct LaunchAuctionParams {
+    pub sale_token: ValidAccountId,
+    /// Taken from the creator's inner account.
+    pub sale_amount: U128,
+    /// Sale tokens taken from the creator's inner account on top of `sale_amount` and kept to seed the pool.
+    pub seed_amount: U128,
+    /// In LAUNCH_PRICE_PRECISION.
+    pub start_price: U128,
+    pub end_price: U128,
+    pub duration_sec: u32,
+}
+
+impl LaunchAuction {
+    pub fn current_price(&self) -> u128 {
+        let now = std::cmp::min(std::cmp::max(env::block_timestamp(), self.start), self.end);
+        self.start_price - u128_ratio(
+            self.start_price - self.end_price,
+            (now - self.start) as u128,
+            (self.end - self.start) as u128,
+        )
+    }
+
+    pub fn is_over(&self) -> bool {
+        env::block_timestamp() >= self.end || self.unsold == 0
+    }
+}
+
+pub fn read_launch_auctions_from_storage() -> LookupMap<u64, LaunchAuction> {
+    if let Some(content) = env::storage_read(LAUNCH_AUCTIONS.as_bytes()) {
+        LookupMap::try_from_slice(&content).expect("deserialize launch auctions failed.")
+    } else {
+        LookupMap::new(StorageKey::LaunchAuctions)
+    }
+}
+
+pub fn write_launch_auctions_to_storage(launch_auctions: LookupMap<u64, LaunchAuction>) {
+    env::storage_write(
+        LAUNCH_AUCTIONS.as_bytes(),
+        &launch_auctions.try_to_vec().unwrap(),
+    );
+}
+
+impl Contract {
+    pub(crate) fn assert_pool_launched(&self, pool_id: u64) {
+        assert!(read_launch_auctions_from_storage().get(&pool_id).is_none(), "Pool in launch auction");
+    }
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Creates a simple pool that opens with a launch auction of one of its tokens for the other.
+    /// The caller must have the other token registered.
+    #[payable]
+    pub fn add_simple_pool_with_launch_auction(&mut self, tokens: Vec<ValidAccountId>, fee: u32, auction: LaunchAuctionParams) -> u64 {
+        self.assert_contract_running();
+        check_token_duplicates(&tokens);
+        let LaunchAuctionParams { sale_token, sale_amount, seed_amount, start_price, end_price, duration_sec } = auction;
+        assert!(sale_amount.0 > 0 && seed_amount.0 > 0, "{}", ERR31_ZERO_AMOUNT);
+        assert!(end_price.0 > 0 && end_price.0 <= start_price.0, "Invalid auction prices");
+        assert!(duration_sec > 0 && duration_sec <= MAX_LAUNCH_AUCTION_SEC, "Invalid auction duration");
+        let prev_storage = env::storage_usage();
+        let creator_id = env::predecessor_account_id();
+        let pool_id = self.internal_push_pool(Pool::SimplePool(SimplePool::new(
+            self.pools.len() as u32,
+            tokens,
+            fee,
+        )));
+        let sale_token: AccountId = sale_token.into();
+        let quote_token = self.internal_get_pool(pool_id).tokens().iter()
+            .find(|token_id| **token_id != sale_token)
+            .cloned()
+            .expect(ERR63_MISSING_TOKEN);
+        assert!(self.internal_get_pool(pool_id).tokens().contains(&sale_token), "{}", ERR63_MISSING_TOKEN);
+
+        let mut account = self.internal_unwrap_account(&creator_id);
+        account.get_balance(&quote_token).expect(ERR21_TOKEN_NOT_REG);
+        account.withdraw(&sale_token, sale_amount.0 + seed_amount.0);
+        self.internal_save_account(&creator_id, account);
+        add_locked_deposit(&creator_id, &sale_token, sale_amount.0 + seed_amount.0);
+        let now = env::block_timestamp();
+        let mut launch_auctions = read_launch_auctions_from_storage();
+        launch_auctions.insert(&pool_id, &LaunchAuction {
+            creator_id,
+            sale_token,
+            quote_token,
+            start_price: start_price.0,
+            end_price: end_price.0,
+            start: now,
+            end: now + to_nano(duration_sec),
+            unsold: sale_amount.0,
+            seed_reserve: seed_amount.0,
+            raised: 0,
+            last_price: 0,
+        });
+        write_launch_auctions_to_storage(launch_auctions);
+        self.internal_record_pool_creator(pool_id);
+        self.internal_register_pool(pool_id);
+        self.internal_check_storage(prev_storage);
+        pool_id
+    }
+
+    /// Buy sale tokens of the pool's launch auction at the current price with up to `amount_in`
+    /// quote tokens of the caller's inner account. Returns the sale tokens bought.
+    #[payable]
+    pub fn bid_launch_auction(&mut self, pool_id: u64, amount_in: U128, min_amount_out: U128) -> U128 {
+        assert_one_yocto();
+        self.assert_contract_running();
+        let sender_id = env::predecessor_account_id();
+        assert_account_not_denied(&sender_id);
+        let mut launch_auctions = read_launch_auctions_from_storage();
+        let mut auction = launch_auctions.get(&pool_id).expect("No launch auction");
+        assert!(!auction.is_over(), "Launch auction over");
+        let price = auction.current_price();
+        let mut amount_in = amount_in.0;
+        let mut amount_out = u128_ratio(amount_in, LAUNCH_PRICE_PRECISION, price);
+        if amount_out > auction.unsold {
+            amount_out = auction.unsold;
+            // rounded up, in favor of the creator
+            amount_in = ((U256::from(amount_out) * U256::from(price) + U256::from(LAUNCH_PRICE_PRECISION - 1))
+                / U256::from(LAUNCH_PRICE_PRECISION)).as_u128();
+        }
+        assert!(amount_out > 0, "{}", ERR31_ZERO_AMOUNT);
+        assert!(amount_out >= min_amount_out.0, "{}", ERR68_SLIPPAGE);
+
+        // the quote tokens end up with the creator
+        self.internal_record_limited_withdrawal(&sender_id, &auction.quote_token, amount_in);
+        let mut account = self.internal_unwrap_account(&sender_id);
+        account.withdraw(&auction.quote_token, amount_in);
+        account.deposit(&auction.sale_token, amount_out);
+        self.internal_save_account(&sender_id, account);
+        let prev_storage = env::storage_usage();
+        auction.unsold -= amount_out;
+        auction.raised += amount_in;
+        release_locked_deposit(&auction.creator_id, &auction.sale_token, amount_out);
+        add_locked_deposit(&auction.creator_id, &auction.quote_token, amount_in);
+        // the first bid records the creator's locked proceeds
+        self.internal_charge_caller_storage(&sender_id, prev_storage);
+        auction.last_price = price;
+        launch_auctions.insert(&pool_id, &auction);
+        write_launch_auctions_to_storage(launch_auctions);
+        log!("{} bought {} {} for {} {} in launch auction of pool {}",
+            sender_id, amount_out, auction.sale_token, amount_in, auction.quote_token, pool_id);
+        amount_out.into()
+    }
+
+    /// End a finished launch auction and open the pool. The raised quote tokens and the sale tokens
+    /// matching them at the last fill price, out of the seed reserve and then the unsold ones, seed
+    /// the pool as the creator's liquidity. The rest goes back to the creator's inner account.
+    /// The creator can settle once the auction is over, anyone once its end passed.
+    /// If the creator is gone or unregistered either token, the pool opens empty and the tokens
+    /// are held for the creator to reclaim. Attached deposit covers the creator's LP record.
+    #[payable]
+    pub fn settle_launch_auction(&mut self, pool_id: u64) -> U128 {
+        self.assert_contract_running();
+        let prev_storage = env::storage_usage();
+        let mut launch_auctions = read_launch_auctions_from_storage();
+        let auction = launch_auctions.remove(&pool_id).expect("No launch auction");
+        write_launch_auctions_to_storage(launch_auctions);
+        assert!(auction.is_over(), "Launch auction not over");
+        assert!(
+            env::predecessor_account_id() == auction.creator_id || env::block_timestamp() >= auction.end,
+            "{}", ERR100_NOT_ALLOWED
+        );
+
+        let sale_available = auction.seed_reserve + auction.unsold;
+        release_locked_deposit(&auction.creator_id, &auction.sale_token, sale_available);
+        release_locked_deposit(&auction.creator_id, &auction.quote_token, auction.raised);
+        let creator_account = self.internal_get_account(&auction.creator_id).filter(|account| {
+            account.get_balance(&auction.sale_token).is_some() && account.get_balance(&auction.quote_token).is_some()
+        });
+        let mut account = match creator_account {
+            Some(account) => account,
+            None => {
+                // the pool opens empty, the creator can reclaim the tokens once registered again
+                log!("Creator {} of pool {} can't take the auction tokens, holding them for reclaim", auction.creator_id, pool_id);
+                self.internal_hold_unclaimed_withdrawal(&auction.creator_id, &auction.sale_token, sale_available);
+                if auction.raised > 0 {
+                    self.internal_hold_unclaimed_withdrawal(&auction.creator_id, &auction.quote_token, auction.raised);
+                }
+                self.internal_check_storage(prev_storage);
+                return U128(0);
+            }
+        };
+        account.deposit(&auction.sale_token, sale_available);
+        account.deposit(&auction.quote_token, auction.raised);
+        self.internal_save_account(&auction.creator_id, account);
+        let shares = if auction.raised > 0 {
+            let mut sale_seed = u128_ratio(auction.raised, LAUNCH_PRICE_PRECISION, auction.last_price);
+            let mut quote_seed = auction.raised;
+            if sale_seed > sale_available {
+                sale_seed = sale_available;
+                quote_seed = u128_ratio(sale_seed, auction.last_price, LAUNCH_PRICE_PRECISION);
+            }
+            if sale_seed > 0 && quote_seed > 0 {
+                let amounts = self.internal_get_pool(pool_id).tokens().iter()
+                    .map(|token_id| U128(if *token_id == auction.sale_token { sale_seed } else { quote_seed }))
+                    .collect();
+                self.internal_add_liquidity(pool_id, &auction.creator_id, amounts, None)
+            } else {
+                0
+            }
+        } else {
+            0
+        };
+        self.internal_check_storage(prev_storage);
+        log!("Launch auction of pool {} settled at price {}", pool_id, auction.last_price);
+        shares.into()
+    }
+
+    pub fn get_launch_auction(&self, pool_id: u64) -> Option<LaunchAuction> {
+        read_launch_auctions_from_storage().get(&pool_id)
+    }
+
+    /// Current price of the pool's launch auction, None without one.
+    pub fn get_launch_auction_price(&self, pool_id: u64) -> Option<U128> {
+        read_launch_auctions_from_storage().get(&pool_id).map(|auction| auction.current_price().into())
+    }
+}
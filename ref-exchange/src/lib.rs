@@ -18,12 +18,13 @@ use near_sdk::{
 use utils::{NO_DEPOSIT, GAS_FOR_BASIC_OP};
 
 use crate::account_deposit::*;
-pub use crate::action::{SwapAction, SwapByOutputAction, Action, ActionResult, get_tokens_in_actions, assert_all_same_action_type};
+pub use crate::action::{SwapAction, SwapByOutputAction, Action, ActionResult, RouteQuote, get_tokens_in_actions, assert_all_same_action_type, get_longest_route_hops};
 use crate::errors::*;
 use crate::admin_fee::AdminFees;
 use crate::pool::Pool;
 use crate::simple_pool::SimplePool;
 use crate::stable_swap::StableSwapPool;
+use crate::range_pool::RangePool;
 use crate::rated_swap::{RatedSwapPool, rate::{RateTrait, global_get_rate, global_set_rate}};
 use crate::utils::{check_token_duplicates, pair_rated_price_to_vec_u8, TokenCache};
 pub use crate::custom_keys::*;
@@ -35,6 +36,87 @@ pub use crate::oracle::*;
 pub use crate::degen_swap::*;
 pub use crate::pool_limit_info::*;
 pub use crate::client_echo_limit::*;
+pub use crate::pool_snapshot::*;
+pub use crate::pool_archive::*;
+pub use crate::in_flight::*;
+pub use crate::mft_receiver_policy::*;
+pub use crate::pool_metadata::*;
+pub use crate::amp_schedule::*;
+pub use crate::degen_price_band::*;
+pub use crate::maker_rebate::*;
+pub use crate::wash_trade::*;
+pub use crate::lp_fees::*;
+pub use crate::share_lock::*;
+pub use crate::lp_position_nft::*;
+pub use crate::share_stream::*;
+pub use crate::fee_rebate::*;
+pub use crate::trade_history::*;
+pub use crate::admin_fee_mode::*;
+pub use crate::token_ledger::*;
+pub use crate::audit_log::*;
+pub use crate::dao_adapter::*;
+pub use crate::lp_voting::*;
+pub use crate::degen_listing::*;
+pub use crate::referral_registry::*;
+pub use crate::fee_breakdown::*;
+pub use crate::deposit_plan::*;
+pub use crate::swap_screen::*;
+pub use crate::permissioned_pool::*;
+pub use crate::pol_manager::*;
+pub use crate::pool_registry::*;
+pub use crate::degen_oracle_failover::*;
+pub use crate::rate_guard::*;
+pub use crate::rate_batch::*;
+pub use crate::exchange_stats::*;
+pub use crate::lp_performance::*;
+pub use crate::pool_health::*;
+pub use crate::wrapped_near::*;
+pub use crate::account_preferences::*;
+pub use crate::referral_opt_out::*;
+pub use crate::withdrawal_cap::*;
+pub use crate::degen_fresh_price::*;
+pub use crate::lp_incentives::*;
+pub use crate::launch_auction::*;
+pub use crate::deny_list::*;
+pub use crate::twap_order::*;
+pub use crate::market_price::*;
+pub use crate::epoch_fees::*;
+pub use crate::forwarded_intent::*;
+pub use crate::price_cache::*;
+pub use crate::safe_mode::*;
+pub use crate::action_limits::*;
+pub use crate::partial_actions::*;
+pub use crate::fee_holiday::*;
+pub use crate::degen_tvl_alert::*;
+pub use crate::storage_top_up::*;
+pub use crate::share_checkpoint::*;
+pub use crate::storage_inspector::*;
+pub use crate::order_nonce::*;
+pub use crate::insurance_fund::*;
+pub use crate::dynamic_tvl_limit::*;
+pub use crate::referral_volume::*;
+pub use crate::stable_router::*;
+pub use crate::unclaimed_withdrawal::*;
+pub use crate::admin_fee_burn::*;
+pub use crate::swap_cap::*;
+pub use crate::pool_creator::*;
+pub use crate::reward_compound::*;
+pub use crate::replica_export::*;
+pub use crate::stable_zap::*;
+pub use crate::withdraw_fee::*;
+pub use crate::lp_referral::*;
+pub use crate::amp_freeze::*;
+pub use crate::lp_round_trip::*;
+pub use crate::gas_budget::*;
+pub use crate::token_aliases::*;
+pub use crate::lock_bonus::*;
+pub use crate::pool_params::*;
+pub use crate::share_raffle::*;
+pub use crate::rate_divergence::*;
+pub use crate::pool_delegation::*;
+pub use crate::deposit_detail::*;
+pub use crate::withdrawal_limit::*;
+pub use crate::views_v2::*;
 
 mod account_deposit;
 mod action;
@@ -48,6 +130,7 @@ mod simple_pool;
 mod stable_swap;
 mod rated_swap;
 mod degen_swap;
+mod range_pool;
 mod oracle;
 mod storage_impl;
 mod token_receiver;
@@ -60,6 +143,92 @@ mod pool_limit_info;
 mod client_echo_limit;
 mod donation;
 mod event;
+mod pool_snapshot;
+mod pool_archive;
+mod in_flight;
+mod mft_receiver_policy;
+mod pool_metadata;
+mod amp_schedule;
+mod degen_price_band;
+mod maker_rebate;
+mod wash_trade;
+mod lp_fees;
+mod share_lock;
+mod lp_position_nft;
+mod share_stream;
+mod fee_rebate;
+mod trade_history;
+mod admin_fee_mode;
+mod token_ledger;
+mod audit_log;
+mod dao_adapter;
+mod lp_voting;
+mod degen_listing;
+mod fee_conversion;
+mod referral_registry;
+mod fee_breakdown;
+mod deposit_plan;
+mod swap_screen;
+mod permissioned_pool;
+mod pol_manager;
+mod pool_registry;
+mod degen_oracle_failover;
+mod rate_guard;
+mod rate_batch;
+mod exchange_stats;
+mod lp_performance;
+mod pool_health;
+mod wrapped_near;
+mod account_preferences;
+mod referral_opt_out;
+mod withdrawal_cap;
+mod degen_fresh_price;
+mod lp_incentives;
+mod launch_auction;
+mod deny_list;
+mod twap_order;
+mod market_price;
+mod epoch_fees;
+mod forwarded_intent;
+mod price_cache;
+mod safe_mode;
+mod action_limits;
+mod partial_actions;
+mod swap_receiver;
+mod fee_holiday;
+mod degen_tvl_alert;
+mod storage_top_up;
+mod share_checkpoint;
+mod storage_inspector;
+mod order_nonce;
+mod insurance_fund;
+mod dynamic_tvl_limit;
+mod referral_volume;
+mod stable_router;
+mod unclaimed_withdrawal;
+mod admin_fee_burn;
+mod near_deposit;
+mod swap_cap;
+mod pool_creator;
+mod reward_compound;
+mod replica_export;
+mod stable_zap;
+mod withdraw_fee;
+mod lp_referral;
+mod amp_freeze;
+mod lp_round_trip;
+mod gas_budget;
+mod token_aliases;
+mod lock_bonus;
+mod pool_params;
+mod share_raffle;
+mod rate_divergence;
+mod pool_delegation;
+mod deposit_detail;
+mod withdrawal_limit;
+mod views_v2;
+#[cfg(test)]
+mod differential;
 
 near_sdk::setup_alloc!();
 
@@ -78,6 +247,97 @@ pub(crate) enum StorageKey {
     PoolLimit,
     ClientEchoTokenIdWhitelistItem,
     ClientEchoSenderIdWhitelistItem,
+    ArchivedPools,
+    TwapRecordLimit,
+    InFlightOperations,
+    MftReceiverList,
+    MftReceiverMinGas,
+    PoolMetadata,
+    AmpSchedule,
+    MakerRebateConfigs,
+    MakerRebateEscrows,
+    RecentSwaps,
+    PoolFeeGrowth,
+    LpFeePositions,
+    ShareLocks,
+    AccountShareLocks,
+    ShareStreams,
+    AccountShareStreams,
+    PendingFeeRebates,
+    TradeHistories,
+    AdminFeeTokens,
+    TokenLedgers,
+    AuditLog,
+    LpProposals,
+    PoolLpProposals,
+    LpVotes,
+    DegenListings,
+    ReferralActivations,
+    DepositPlans,
+    SwapScreenConfigs,
+    SwapScreenList,
+    PermissionedPools,
+    PoolLpAllowlist,
+    PolPositions,
+    PoolRegistry,
+    ExchangeTokenStats,
+    ExchangeTraders,
+    LpCheckpoints,
+    PoolVolumeWindows,
+    AccountPreferences,
+    ReferralOptOutPools,
+    WithdrawalCaps,
+    DegenFreshPriceRules,
+    LpIncentives,
+    LpIncentivePositions,
+    LaunchAuctions,
+    DeniedAccounts,
+    TwapOrders,
+    LastTrades,
+    EpochFees,
+    CachedPrices,
+    PriceKeepers,
+    SafeModeClaims,
+    FeeHolidays,
+    DegenTvlAlertLevels,
+    StorageTopUpAccounts,
+    StorageTopUpWnear,
+    ShareCheckpoints,
+    ShareCheckpointRecords {checkpoint_id: u64},
+    OrderNonceFloors,
+    UsedOrderNonces,
+    InsuranceAccrued,
+    InsuranceWithdrawals,
+    DynamicTvlLimits,
+    TransferRestrictedPools,
+    ReferralVolumes,
+    StablePairPools,
+    UnclaimedWithdrawals,
+    UnclaimedWithdrawalTotals,
+    AdminFeeBurns,
+    SwapCaps,
+    PoolDeprecationRequests,
+    AutoCompoundAccounts,
+    ReplicaAccountIds,
+    ReplicaAccountPositions,
+    LpReferrers,
+    LpReferralRewards,
+    FrozenAmpPools,
+    PoolLockBonus,
+    LockBonusPositions,
+    ShareRaffles,
+    ShareRaffleEntrants {raffle_id: u64},
+    PoolDelegations,
+    PendingWithdrawals,
+    LockedDeposits,
+    WithdrawalLimits,
+    ActivePoolIds,
+    VolumeStatsRetention,
+    LockBonusExpiries,
+    StorageTopUpDeposits,
+    TwapEscrowTotals,
+    CollectionLens,
+    ShareLockIds,
 }
 
 #[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Eq, PartialEq, Clone)]
@@ -100,6 +360,9 @@ impl fmt::Display for RunningState {
 pub trait SelfCallbacks {
     fn update_token_rate_callback(&mut self, token_id: AccountId);
     fn update_degen_token_price_callback(&mut self, token_id: AccountId);
+    fn update_degen_fallback_price_callback(&mut self, token_id: AccountId);
+    fn batch_update_token_rates_callback(&mut self, token_ids: Vec<AccountId>);
+    fn refresh_dynamic_tvl_limit_callback(&mut self, pool_id: u64);
 }
 
 #[near_bindgen]
@@ -137,8 +400,13 @@ pub struct Contract {
 
 #[near_bindgen]
 impl Contract {
+    /// `test_network` marks a deployment on a test network, enabling test only methods like
+    /// `import_pool_snapshot`.
     #[init]
-    pub fn new(owner_id: ValidAccountId, boost_farm_id: ValidAccountId, burrowland_id: ValidAccountId, exchange_fee: u32, referral_fee: u32) -> Self {
+    pub fn new(owner_id: ValidAccountId, boost_farm_id: ValidAccountId, burrowland_id: ValidAccountId, exchange_fee: u32, referral_fee: u32, test_network: Option<bool>) -> Self {
+        if test_network.unwrap_or(false) {
+            write_test_network_to_storage(true);
+        }
         Self {
             owner_id: owner_id.as_ref().clone(),
             boost_farm_id: boost_farm_id.as_ref().clone(),
@@ -171,6 +439,29 @@ impl Contract {
         )))
     }
 
+    /// Adds new "Range Pool" of two tokens whose liquidity is concentrated between
+    /// the given prices of token1 per token0 in raw amounts, in precision 1e24, the upper
+    /// at least MIN_PRICE_RANGE_BPS above the lower.
+    /// Attached NEAR should be enough to cover the added storage.
+    #[payable]
+    pub fn add_range_pool(
+        &mut self,
+        tokens: Vec<ValidAccountId>,
+        fee: u32,
+        price_lower: U128,
+        price_upper: U128,
+    ) -> u64 {
+        self.assert_contract_running();
+        check_token_duplicates(&tokens);
+        self.internal_add_pool(Pool::RangePool(RangePool::new(
+            self.pools.len() as u32,
+            tokens,
+            fee,
+            price_lower.0,
+            price_upper.0,
+        )))
+    }
+
     /// Adds new "Stable Pool" with given tokens, decimals, fee and amp.
     /// It is limited to owner or guardians, cause a complex and correct config is needed.
     /// tokens: pool tokens in this stable swap.
@@ -186,6 +477,7 @@ impl Contract {
         amp_factor: u64,
     ) -> u64 {
         assert!(self.is_owner_or_guardians(), "{}", ERR100_NOT_ALLOWED);
+        audit_privileged_action("add_stable_swap_pool");
         check_token_duplicates(&tokens);
         self.internal_add_pool(Pool::StableSwapPool(StableSwapPool::new(
             self.pools.len() as u32,
@@ -196,6 +488,64 @@ impl Contract {
         )))
     }
 
+    /// Creates a simple pool and seeds it with the caller's inner balances in one call,
+    /// so the initial price is set by the creator.
+    /// Attached NEAR should cover storage of the pool and the creator's LP record.
+    /// Returns the pool id and minted shares.
+    #[payable]
+    pub fn add_simple_pool_with_liquidity(
+        &mut self,
+        tokens: Vec<ValidAccountId>,
+        fee: u32,
+        amounts: Vec<U128>,
+    ) -> (u64, U128) {
+        self.assert_contract_running();
+        check_token_duplicates(&tokens);
+        let prev_storage = env::storage_usage();
+        let sender_id = env::predecessor_account_id();
+        let pool_id = self.internal_push_pool(Pool::SimplePool(SimplePool::new(
+            self.pools.len() as u32,
+            tokens,
+            fee,
+        )));
+        let shares = self.internal_add_liquidity(pool_id, &sender_id, amounts, None);
+        self.internal_record_pool_creator(pool_id);
+        self.internal_register_pool(pool_id);
+        self.internal_check_storage(prev_storage);
+        (pool_id, U128(shares))
+    }
+
+    /// Stable pool version of `add_simple_pool_with_liquidity`, limited to owner or guardians.
+    #[payable]
+    pub fn add_stable_swap_pool_with_liquidity(
+        &mut self,
+        tokens: Vec<ValidAccountId>,
+        decimals: Vec<u8>,
+        fee: u32,
+        amp_factor: u64,
+        amounts: Vec<U128>,
+        min_shares: U128,
+    ) -> (u64, U128) {
+        self.assert_contract_running();
+        assert!(self.is_owner_or_guardians(), "{}", ERR100_NOT_ALLOWED);
+        audit_privileged_action("add_stable_swap_pool_with_liquidity");
+        check_token_duplicates(&tokens);
+        let prev_storage = env::storage_usage();
+        let sender_id = env::predecessor_account_id();
+        let pool_id = self.internal_push_pool(Pool::StableSwapPool(StableSwapPool::new(
+            self.pools.len() as u32,
+            tokens,
+            decimals,
+            amp_factor as u128,
+            fee,
+        )));
+        let shares = self.internal_add_stable_liquidity(pool_id, &sender_id, amounts, min_shares);
+        self.internal_record_pool_creator(pool_id);
+        self.internal_register_pool(pool_id);
+        self.internal_check_storage(prev_storage);
+        (pool_id, U128(shares))
+    }
+
     ///
     #[payable]
     pub fn add_rated_swap_pool(
@@ -206,6 +556,7 @@ impl Contract {
         amp_factor: u64,
     ) -> u64 {
         assert!(self.is_owner_or_guardians(), "{}", ERR100_NOT_ALLOWED);
+        audit_privileged_action("add_rated_swap_pool");
         check_token_duplicates(&tokens);
         self.internal_add_pool(Pool::RatedSwapPool(RatedSwapPool::new(
             self.pools.len() as u32,
@@ -225,6 +576,7 @@ impl Contract {
         amp_factor: u64,
     ) -> u64 {
         assert!(self.is_owner_or_guardians(), "{}", ERR100_NOT_ALLOWED);
+        audit_privileged_action("add_degen_swap_pool");
         check_token_duplicates(&tokens);
         self.internal_add_pool(Pool::DegenSwapPool(DegenSwapPool::new(
             self.pools.len() as u32,
@@ -262,22 +614,23 @@ impl Contract {
         }
 
         let mut virtual_account: Account = Account::new(&String::from(VIRTUAL_ACC));
-        let referral_info :Option<(AccountId, u32)> = referral_id
-            .as_ref().and_then(|rid| self.referrals.get(rid.as_ref()))
-            .map(|fee| (referral_id.unwrap().into(), fee));
+        let referral_info = self.internal_get_referral_info(referral_id.map(|rid| rid.into()), &sender_id);
         for (use_token, use_amount) in use_tokens.iter() {
             account.withdraw(use_token, use_amount.0);
             virtual_account.deposit(use_token, use_amount.0);
         }
         let _ = self.internal_execute_actions(
+            &sender_id,
             &mut virtual_account,
             &referral_info,
             &actions,
             ActionResult::None,
+            None,
         );
         let mut result = HashMap::new();
         for (token, amount) in virtual_account.tokens.to_vec() {
             if amount > 0 {
+                virtual_account.withdraw(&token, amount);
                 account.deposit(&token, amount);
                 result.insert(token, amount.into());
             }
@@ -299,33 +652,28 @@ impl Contract {
         actions: Vec<Action>,
         referral_id: Option<ValidAccountId>,
     ) -> ActionResult {
-        self.assert_contract_running();
-        assert_ne!(actions.len(), 0, "{}", ERR72_AT_LEAST_ONE_SWAP);
-        let sender_id = env::predecessor_account_id();
-        let mut account = self.internal_unwrap_account(&sender_id);
-        // Validate that all tokens are whitelisted if no deposit (e.g. trade with access key).
-        if env::attached_deposit() == 0 {
-            for action in &actions {
-                for token in action.tokens() {
-                    assert!(
-                        account.get_balance(&token).is_some() 
-                            || self.is_whitelisted_token(&token),
-                        "{}",
-                        // [AUDIT_05]
-                        ERR27_DEPOSIT_NEEDED
-                    );
-                }
-            }
-        }
+        self.internal_execute_sender_actions(actions, referral_id, None, None, None)
+    }
 
-        let referral_info :Option<(AccountId, u32)> = referral_id
-            .as_ref().and_then(|rid| self.referrals.get(rid.as_ref()))
-            .map(|fee| (referral_id.unwrap().into(), fee));
-        
-        let result =
-            self.internal_execute_actions(&mut account, &referral_info, &actions, ActionResult::None);
-        self.internal_save_account(&sender_id, account);
-        result
+    /// Execute set of swap actions between pools, checking each step against the given quote.
+    /// Fails on the first step coming out below its quoted amount by more than the tolerance.
+    #[payable]
+    pub fn swap_with_quote(
+        &mut self,
+        actions: Vec<SwapAction>,
+        referral_id: Option<ValidAccountId>,
+        route_quote: RouteQuote,
+    ) -> U128 {
+        U128(
+            self.internal_execute_sender_actions(
+                actions.into_iter().map(Action::Swap).collect(),
+                referral_id,
+                Some(&route_quote),
+                None,
+                None,
+            )
+            .to_amount(),
+        )
     }
 
     /// Execute set of swap actions between pools.
@@ -378,31 +726,7 @@ impl Contract {
         self.internal_update_unit_share_cumulative_info(pool_id);
         let prev_storage = env::storage_usage();
         let sender_id = env::predecessor_account_id();
-        let mut amounts: Vec<u128> = amounts.into_iter().map(|amount| amount.into()).collect();
-        let mut pool = self.pools.get(pool_id).expect(ERR85_NO_POOL);
-        // feature frozenlist
-        self.assert_no_frozen_tokens(pool.tokens());
-        // Add amounts given to liquidity first. It will return the balanced amounts.
-        let shares = pool.add_liquidity(
-            &sender_id,
-            &mut amounts,
-            false
-        );
-        if let Some(min_amounts) = min_amounts {
-            // Check that all amounts are above request min amounts in case of front running that changes the exchange rate.
-            for (amount, min_amount) in amounts.iter().zip(min_amounts.iter()) {
-                assert!(amount >= &min_amount.0, "{}", ERR86_MIN_AMOUNT);
-            }
-        }
-        // [AUDITION_AMENDMENT] 2.3.7 Code Optimization (I)
-        let mut deposits = self.internal_unwrap_account(&sender_id);
-        let tokens = pool.tokens();
-        // Subtract updated amounts from deposits. This will fail if there is not enough funds for any of the tokens.
-        for i in 0..tokens.len() {
-            deposits.withdraw(&tokens[i], amounts[i]);
-        }
-        self.internal_save_account(&sender_id, deposits);
-        self.pools.replace(pool_id, &pool);
+        let shares = self.internal_add_liquidity(pool_id, &sender_id, amounts, min_amounts);
         self.internal_check_storage(prev_storage);
         U128(shares)
     }
@@ -427,28 +751,7 @@ impl Contract {
         self.internal_update_unit_share_cumulative_info(pool_id);
         let prev_storage = env::storage_usage();
         let sender_id = env::predecessor_account_id();
-        let amounts: Vec<u128> = amounts.into_iter().map(|amount| amount.into()).collect();
-        let mut pool = self.pools.get(pool_id).expect(ERR85_NO_POOL);
-        // feature frozenlist
-        self.assert_no_frozen_tokens(pool.tokens());
-        // Add amounts given to liquidity first. It will return the balanced amounts.
-        let mint_shares = pool.add_stable_liquidity(
-            &sender_id,
-            &amounts,
-            min_shares.into(),
-            AdminFees::new(self.admin_fee_bps),
-            false
-        );
-        pool.assert_tvl_not_exceed_limit(pool_id);
-        // [AUDITION_AMENDMENT] 2.3.7 Code Optimization (I)
-        let mut deposits = self.internal_unwrap_account(&sender_id);
-        let tokens = pool.tokens();
-        // Subtract amounts from deposits. This will fail if there is not enough funds for any of the tokens.
-        for i in 0..tokens.len() {
-            deposits.withdraw(&tokens[i], amounts[i]);
-        }
-        self.internal_save_account(&sender_id, deposits);
-        self.pools.replace(pool_id, &pool);
+        let mint_shares = self.internal_add_stable_liquidity(pool_id, &sender_id, amounts, min_shares);
         self.internal_check_storage(prev_storage);
         mint_shares.into()
     }
@@ -470,13 +773,18 @@ impl Contract {
         self.assert_contract_running();
         self.internal_update_unit_share_cumulative_info(pool_id);
         let sender_id = env::predecessor_account_id();
-        let mut pool = self.pools.get(pool_id).expect(ERR85_NO_POOL);
+        let mut pool = self.internal_get_pool(pool_id);
         let mut deposits = self.internal_unwrap_account(&sender_id);
         if let Some(record) = deposits.get_shadow_record(pool_id) {
             assert!(shares.0 <= record.free_shares(pool.share_balances(&sender_id)), "Not enough free shares");
         }
+        self.assert_shares_unlocked(&sender_id, pool_id, pool.share_balances(&sender_id), shares.0);
         // feature frozenlist
         self.assert_no_frozen_tokens(pool.tokens());
+        let prev_storage = env::storage_usage();
+        self.internal_settle_lp_fees(pool_id, &pool, &[&sender_id]);
+        self.internal_charge_account_storage(&sender_id, &mut deposits, prev_storage);
+        let reserves = pool.get_amounts();
         let amounts = pool.remove_liquidity(
             &sender_id,
             shares.into(),
@@ -486,6 +794,9 @@ impl Contract {
                 .collect(),
             false
         );
+        self.assert_within_withdrawal_cap(pool_id, &reserves, &amounts);
+        self.internal_check_degen_tvl_utilization(pool_id, &pool);
+        self.internal_track_rate_divergence(pool_id, &pool);
         self.pools.replace(pool_id, &pool);
         let tokens = pool.tokens();
         for i in 0..tokens.len() {
@@ -513,15 +824,20 @@ impl Contract {
         self.assert_contract_running();
         self.internal_update_unit_share_cumulative_info(pool_id);
         let sender_id = env::predecessor_account_id();
-        let mut pool = self.pools.get(pool_id).expect(ERR85_NO_POOL);
+        let mut pool = self.internal_get_pool(pool_id);
         let mut deposits = self.internal_unwrap_account(&sender_id);
+        let total_shares = pool.share_balances(&sender_id);
         let free_shares = if let Some(record) = deposits.get_shadow_record(pool_id) {
-            record.free_shares(pool.share_balances(&sender_id))
+            record.free_shares(total_shares)
         } else {
-            pool.share_balances(&sender_id)
+            total_shares
         };
         // feature frozenlist
         self.assert_no_frozen_tokens(pool.tokens());
+        let reserves = pool.get_amounts();
+        let prev_storage = env::storage_usage();
+        self.internal_record_share_checkpoints(pool_id, &pool, &[&sender_id, &env::current_account_id()]);
+        self.internal_charge_account_storage(&sender_id, &mut deposits, prev_storage);
         let burn_shares = pool.remove_liquidity_by_tokens(
             &sender_id,
             amounts
@@ -534,6 +850,14 @@ impl Contract {
             false
         );
         assert!(burn_shares <= free_shares, "Not enough free shares");
+        self.assert_shares_unlocked(&sender_id, pool_id, total_shares, burn_shares);
+        self.assert_within_withdrawal_cap(
+            pool_id,
+            &reserves,
+            &amounts.iter().map(|amount| amount.0).collect::<Vec<_>>(),
+        );
+        self.internal_check_degen_tvl_utilization(pool_id, &pool);
+        self.internal_track_rate_divergence(pool_id, &pool);
         self.pools.replace(pool_id, &pool);
         let tokens = pool.tokens();
         for i in 0..tokens.len() {
@@ -548,6 +872,10 @@ impl Contract {
         let caller = env::predecessor_account_id();
         let token_id: AccountId = token_id.into();
         if let Some(rate) = global_get_rate(&token_id) {
+            if rate.is_static() {
+                log!("Caller {} invokes token {} rait async-update but its rate is static.", caller, token_id);
+                return PromiseOrValue::Value(true);
+            }
             log!("Caller {} invokes token {} rait async-update.", caller, token_id);
             rate.async_update().then(ext_self::update_token_rate_callback(
                 token_id,
@@ -583,36 +911,22 @@ impl Contract {
             };
             pair_rated_price_to_vec_u8(cross_call_result1, cross_call_result2)
         };
-        if let Some(mut rate) = global_get_rate(&token_id) {
-            let new_rate = rate.set(&cross_call_result);
-            global_set_rate(&token_id, &rate);
-            log!(
-                "Token {} got new rate {} from cross-contract call.",
-                token_id, new_rate
-            );
-        }
+        self.internal_apply_token_rate(&token_id, &cross_call_result);
     }
 
     /// anyone can trigger an update for some degen token
     pub fn update_degen_token_price(& self, token_id: ValidAccountId) {
         let caller = env::predecessor_account_id();
         let token_id: AccountId = token_id.into();
-        let degen = global_get_degen(&token_id);
         log!("Caller {} invokes token {} rait async-update.", caller, token_id);
-        degen.sync_token_price(&token_id);
+        sync_degen_prices(&token_id);
     }
 
     /// the async return of update_degen_token_price
     #[private]
     pub fn update_degen_token_price_callback(&mut self, token_id: AccountId) {
         if let Some(cross_call_result) = near_sdk::promise_result_as_success() {
-            let mut degen = global_get_degen(&token_id);
-            let new_degen = degen.set_price(&cross_call_result);
-            global_set_degen(&token_id, &degen);
-            log!(
-                "Token {} got new degen {} from cross-contract call.",
-                token_id, new_degen
-            );
+            self.internal_apply_degen_price(&token_id, &cross_call_result);
         }
     }
 }
@@ -662,39 +976,219 @@ impl Contract {
         storage_cost
     }
 
+    /// Charges the storage used since `prev_storage` to the account's storage balance, topping it up
+    /// from wNEAR first if the account opted in. Fails if the account can't cover it.
+    fn internal_charge_account_storage(&mut self, account_id: &AccountId, account: &mut Account, prev_storage: StorageUsage) -> Balance {
+        let storage_cost = env::storage_usage()
+            .checked_sub(prev_storage)
+            .unwrap_or_default() as Balance
+            * env::storage_byte_cost();
+        if storage_cost > 0 {
+            self.internal_top_up_storage(account_id, account, storage_cost);
+            account.near_amount = account.near_amount.checked_sub(storage_cost).expect(ERR11_INSUFFICIENT_STORAGE);
+        }
+        storage_cost
+    }
+
+    /// Charges the storage used since `prev_storage` to the storage balance of the account if it
+    /// is the caller, e.g. for the share checkpoint and LP fee records of its share transfers.
+    /// In callbacks the contract keeps covering it.
+    pub(crate) fn internal_charge_caller_storage(&mut self, account_id: &AccountId, prev_storage: StorageUsage) {
+        if *account_id != env::predecessor_account_id() || env::storage_usage() <= prev_storage {
+            return;
+        }
+        let mut account = self.internal_unwrap_account(account_id);
+        self.internal_charge_account_storage(account_id, &mut account, prev_storage);
+        self.internal_save_account(account_id, account);
+    }
+
     /// Adds given pool to the list and returns it's id.
     /// If there is not enough attached balance to cover storage, fails.
     /// If too much attached - refunds it back.
-    fn internal_add_pool(&mut self, mut pool: Pool) -> u64 {
+    fn internal_add_pool(&mut self, pool: Pool) -> u64 {
         let prev_storage = env::storage_usage();
+        let id = self.internal_push_pool(pool);
+        self.internal_record_pool_creator(id);
+        self.internal_register_pool(id);
+        self.internal_check_storage(prev_storage);
+        id
+    }
+
+    /// Pushes the pool without charging storage, caller is responsible for the storage check.
+    fn internal_push_pool(&mut self, mut pool: Pool) -> u64 {
+        self.assert_pool_creatable(&pool);
         let id = self.pools.len() as u64;
         // exchange share was registered at creation time
         pool.share_register(&env::current_account_id());
         self.pools.push(&pool);
-        self.internal_check_storage(prev_storage);
+        bump_state_version();
         id
     }
 
+    /// Records the caller as the creator of a new pool.
+    fn internal_record_pool_creator(&mut self, pool_id: u64) {
+        self.internal_set_pool_metadata(pool_id, PoolMetadata {
+            creator: Some(env::predecessor_account_id()),
+            name: None,
+            project_url_hash: None,
+            category: None,
+        });
+    }
+
+    /// Adds liquidity to a simple pool from the sender's inner balances, returns minted shares.
+    /// Storage is not checked here.
+    fn internal_add_liquidity(
+        &mut self,
+        pool_id: u64,
+        sender_id: &AccountId,
+        amounts: Vec<U128>,
+        min_amounts: Option<Vec<U128>>,
+    ) -> Balance {
+        let mut amounts: Vec<u128> = amounts.into_iter().map(|amount| amount.into()).collect();
+        self.assert_pool_not_archived(pool_id);
+        self.assert_pool_launched(pool_id);
+        self.assert_lp_allowed(pool_id, sender_id);
+        assert_account_not_denied(sender_id);
+        let mut pool = self.internal_get_pool(pool_id);
+        // feature frozenlist
+        self.assert_no_frozen_tokens(pool.tokens());
+        self.internal_settle_lp_fees(pool_id, &pool, &[sender_id]);
+        // Add amounts given to liquidity first. It will return the balanced amounts.
+        let shares = pool.add_liquidity(
+            sender_id,
+            &mut amounts,
+            false
+        );
+        if let Some(min_amounts) = min_amounts {
+            // Check that all amounts are above request min amounts in case of front running that changes the exchange rate.
+            for (amount, min_amount) in amounts.iter().zip(min_amounts.iter()) {
+                assert!(amount >= &min_amount.0, "{}", ERR86_MIN_AMOUNT);
+            }
+        }
+        // [AUDITION_AMENDMENT] 2.3.7 Code Optimization (I)
+        let mut deposits = self.internal_unwrap_account(sender_id);
+        let tokens = pool.tokens();
+        // Subtract updated amounts from deposits. This will fail if there is not enough funds for any of the tokens.
+        for i in 0..tokens.len() {
+            deposits.withdraw(&tokens[i], amounts[i]);
+        }
+        self.internal_save_account(sender_id, deposits);
+        self.pools.replace(pool_id, &pool);
+        shares
+    }
+
+    /// Adds liquidity to a stable-like pool from the sender's inner balances, returns minted shares.
+    /// Storage is not checked here.
+    fn internal_add_stable_liquidity(
+        &mut self,
+        pool_id: u64,
+        sender_id: &AccountId,
+        amounts: Vec<U128>,
+        min_shares: U128,
+    ) -> Balance {
+        let amounts: Vec<u128> = amounts.into_iter().map(|amount| amount.into()).collect();
+        self.assert_pool_not_archived(pool_id);
+        self.assert_pool_launched(pool_id);
+        self.assert_lp_allowed(pool_id, sender_id);
+        assert_account_not_denied(sender_id);
+        let mut pool = self.internal_get_pool(pool_id);
+        // feature frozenlist
+        self.assert_no_frozen_tokens(pool.tokens());
+        self.internal_record_share_checkpoints(pool_id, &pool, &[sender_id, &env::current_account_id()]);
+        // Add amounts given to liquidity first. It will return the balanced amounts.
+        let mint_shares = pool.add_stable_liquidity(
+            sender_id,
+            &amounts,
+            min_shares.into(),
+            AdminFees::new(self.admin_fee_bps),
+            false
+        );
+        pool.assert_tvl_not_exceed_limit(pool_id);
+        self.internal_check_degen_tvl_utilization(pool_id, &pool);
+        // [AUDITION_AMENDMENT] 2.3.7 Code Optimization (I)
+        let mut deposits = self.internal_unwrap_account(sender_id);
+        let tokens = pool.tokens();
+        // Subtract amounts from deposits. This will fail if there is not enough funds for any of the tokens.
+        for i in 0..tokens.len() {
+            deposits.withdraw(&tokens[i], amounts[i]);
+        }
+        self.internal_save_account(sender_id, deposits);
+        self.internal_track_rate_divergence(pool_id, &pool);
+        self.pools.replace(pool_id, &pool);
+        mint_shares
+    }
+
     fn get_degen_tokens_in_actions(&self, actions: &[Action]) -> HashSet<AccountId> {
         let mut degen_tokens = HashSet::new();
         actions.iter().for_each(|action| {
-            if let Pool::DegenSwapPool(p) = self.pools.get(action.get_pool_id()).expect(ERR85_NO_POOL) {
+            if let Pool::DegenSwapPool(p) = self.internal_get_pool(action.get_pool_id()) {
                 degen_tokens.extend(p.tokens().iter().cloned());
             }
         });
         degen_tokens
     }
 
+    /// Executes actions of the predecessor on its inner account, see `execute_actions`.
+    /// The output of a swap chain goes to the receiver if given.
+    fn internal_execute_sender_actions(
+        &mut self,
+        actions: Vec<Action>,
+        referral_id: Option<ValidAccountId>,
+        route_quote: Option<&RouteQuote>,
+        receiver_id: Option<&AccountId>,
+        skip_unwrap_near: Option<bool>,
+    ) -> ActionResult {
+        self.assert_contract_running();
+        assert_ne!(actions.len(), 0, "{}", ERR72_AT_LEAST_ONE_SWAP);
+        let sender_id = env::predecessor_account_id();
+        let mut account = self.internal_unwrap_account(&sender_id);
+        // Validate that all tokens are whitelisted if no deposit (e.g. trade with access key).
+        if env::attached_deposit() == 0 {
+            for action in &actions {
+                for token in action.tokens() {
+                    assert!(
+                        account.get_balance(&token).is_some() 
+                            || self.is_whitelisted_token(&token),
+                        "{}",
+                        // [AUDIT_05]
+                        ERR27_DEPOSIT_NEEDED
+                    );
+                }
+            }
+        }
+
+        let referral_info = self.internal_get_referral_info(referral_id.map(|rid| rid.into()), &sender_id);
+        
+        let result =
+            self.internal_execute_actions(&sender_id, &mut account, &referral_info, &actions, ActionResult::None, route_quote);
+        self.internal_save_account(&sender_id, account);
+        if let Some(receiver_id) = receiver_id {
+            self.internal_send_swap_output(&sender_id, receiver_id, &actions, &result, skip_unwrap_near);
+        } else {
+            self.internal_auto_withdraw(&sender_id, &actions, &result);
+        }
+        result
+    }
+
     /// Execute sequence of actions on given account. Modifies passed account.
+    /// If a route quote is given, each step is checked against it.
     /// Returns result of the last action.
     fn internal_execute_actions(
         &mut self,
+        trader_id: &AccountId,
         account: &mut Account,
         referral_info: &Option<(AccountId, u32)>,
         actions: &[Action],
         prev_result: ActionResult,
+        route_quote: Option<&RouteQuote>,
     ) -> ActionResult {
+        assert_account_not_denied(trader_id);
         assert_all_same_action_type(actions);
+        assert_within_action_limits(actions);
+        let route_quote = route_quote.map(|route_quote| self.internal_apply_quote_preferences(trader_id, route_quote));
+        if let Some(route_quote) = route_quote.as_ref() {
+            route_quote.assert_valid(actions);
+        }
         // fronzen token feature
         // [AUDITION_AMENDMENT] 2.3.8 Code Optimization (II)
         self.assert_no_frozen_tokens(
@@ -707,8 +1201,11 @@ impl Contract {
         let mut result = prev_result;
         match actions[0] {
             Action::Swap(_) => {
-                for action in actions {
-                    result = self.internal_execute_action(account, referral_info, action, result);
+                for (step, action) in actions.iter().enumerate() {
+                    result = self.internal_execute_action(trader_id, account, referral_info, action, result);
+                    if let Some(route_quote) = route_quote.as_ref() {
+                        route_quote.assert_step(step, action, result.to_amount());
+                    }
                 }
             }
             Action::SwapByOutput(_) => {
@@ -720,7 +1217,7 @@ impl Contract {
                     } else {
                         assert!(prev_action.unwrap().get_token_in() == action.get_token_out());
                     }
-                    result = self.internal_execute_action(account, referral_info, action, result);
+                    result = self.internal_execute_action(trader_id, account, referral_info, action, result);
                     prev_action = Some(action);
                 }
                 self.finalize_prev_swap_chain(account, prev_action, &result);
@@ -728,8 +1225,7 @@ impl Contract {
         }
         let degen_tokens = self.get_degen_tokens_in_actions(actions);
         for token_id in degen_tokens {
-            let degen = global_get_degen(&token_id);
-            degen.sync_token_price(&token_id);
+            sync_degen_prices(&token_id);
         }
         result
     }
@@ -743,13 +1239,16 @@ impl Contract {
     /// Executes single action on given account. Modifies passed account. Returns a result based on type of action.
     fn internal_execute_action(
         &mut self,
+        trader_id: &AccountId,
         account: &mut Account,
         referral_info: &Option<(AccountId, u32)>,
         action: &Action,
         prev_result: ActionResult,
     ) -> ActionResult {
+        self.internal_record_stats_trader(trader_id, account);
         match action {
             Action::Swap(swap_action) => {
+                self.assert_swap_screen_passed(swap_action.pool_id, trader_id);
                 let amount_in = swap_action
                     .amount_in
                     .map(|value| value.0)
@@ -763,15 +1262,33 @@ impl Contract {
                     swap_action.min_amount_out.0,
                     referral_info,
                 );
-                account.deposit(&swap_action.token_out, amount_out);
-                // [AUDIT_02]
-                ActionResult::Amount(U128(amount_out))
-            }
-            Action::SwapByOutput(swap_by_output_action) => {
-                let amount_out = swap_by_output_action
-                    .amount_out
-                    .map(|value| value.0)
-                    .unwrap_or_else(|| prev_result.to_amount());
+                self.internal_net_wash_trade(
+                    trader_id,
+                    swap_action.pool_id,
+                    &swap_action.token_in,
+                    amount_in,
+                    &swap_action.token_out,
+                    amount_out,
+                );
+                self.internal_accrue_fee_rebate(trader_id, account, swap_action.pool_id, &swap_action.token_in, amount_in);
+                self.internal_record_trade(
+                    trader_id,
+                    swap_action.pool_id,
+                    &swap_action.token_in,
+                    amount_in,
+                    &swap_action.token_out,
+                    amount_out,
+                );
+                account.deposit(&swap_action.token_out, amount_out);
+                // [AUDIT_02]
+                ActionResult::Amount(U128(amount_out))
+            }
+            Action::SwapByOutput(swap_by_output_action) => {
+                self.assert_swap_screen_passed(swap_by_output_action.pool_id, trader_id);
+                let amount_out = swap_by_output_action
+                    .amount_out
+                    .map(|value| value.0)
+                    .unwrap_or_else(|| prev_result.to_amount());
                 let amount_in = self.internal_pool_swap_by_output(
                     swap_by_output_action.pool_id,
                     &swap_by_output_action.token_in,
@@ -780,6 +1297,23 @@ impl Contract {
                     swap_by_output_action.max_amount_in.map(|v| v.0),
                     referral_info,
                 );
+                self.internal_net_wash_trade(
+                    trader_id,
+                    swap_by_output_action.pool_id,
+                    &swap_by_output_action.token_in,
+                    amount_in,
+                    &swap_by_output_action.token_out,
+                    amount_out,
+                );
+                self.internal_accrue_fee_rebate(trader_id, account, swap_by_output_action.pool_id, &swap_by_output_action.token_in, amount_in);
+                self.internal_record_trade(
+                    trader_id,
+                    swap_by_output_action.pool_id,
+                    &swap_by_output_action.token_in,
+                    amount_in,
+                    &swap_by_output_action.token_out,
+                    amount_out,
+                );
                 ActionResult::Amount(U128(amount_in))
             }
         }
@@ -796,20 +1330,41 @@ impl Contract {
         min_amount_out: u128,
         referral_info: &Option<(AccountId, u32)>,
     ) -> u128 {
+        self.assert_pool_not_archived(pool_id);
+        self.assert_pool_launched(pool_id);
+        self.assert_pool_not_rate_guarded(pool_id);
         self.internal_update_unit_share_cumulative_info(pool_id);
-        let mut pool = self.pools.get(pool_id).expect(ERR85_NO_POOL);
+        let mut pool = self.internal_get_pool(pool_id);
+        let pool_fee = self.internal_apply_fee_holiday(pool_id, &mut pool);
+        self.assert_degen_swap_price_fresh(pool_id, &pool, token_in, amount_in);
+        let admin_fees = self.internal_admin_fees(pool_id, referral_info);
+        self.internal_settle_admin_fee_receivers(pool_id, &pool, &admin_fees.referral_info);
+        let attributed_referral = admin_fees.referral_info.clone();
+        let prev_imbalance = stable_pool_imbalance(&pool);
+        let prev_exchange_shares = pool.share_balances(&env::current_account_id());
+        let max_amount_out = self.internal_max_swap_out(pool_id, &pool, token_out);
         let amount_out = pool.swap(
             token_in,
             amount_in,
             token_out,
             min_amount_out,
-            AdminFees {
-                admin_fee_bps: self.admin_fee_bps,
-                exchange_id: env::current_account_id(),
-                referral_info: referral_info.clone(),
-            },
+            admin_fees.clone(),
             false
         );
+        assert_within_swap_cap(max_amount_out, amount_out);
+        self.internal_collect_lp_fee(pool_id, &mut pool, token_in, amount_in, &admin_fees);
+        let amount_out = self.internal_apply_maker_rebate(pool_id, prev_imbalance, &pool, token_out, amount_out, false);
+        assert!(amount_out >= min_amount_out, "{}", ERR68_SLIPPAGE);
+        self.internal_record_swap_stats(&pool, token_in, amount_in, token_out, amount_out);
+        self.internal_record_pool_volume(pool_id, &pool, token_in, amount_in, token_out, amount_out);
+        self.internal_record_last_trade(pool_id, token_in, amount_in, token_out, amount_out);
+        self.internal_record_referral_volume(&attributed_referral, token_in, amount_in);
+        self.internal_record_epoch_admin_fees(pool_id, &pool, prev_exchange_shares);
+        self.internal_divert_insurance_shares(pool_id, &mut pool, prev_exchange_shares);
+        self.internal_burn_admin_fee_shares(pool_id, &mut pool, prev_exchange_shares);
+        self.internal_accrue_admin_fee_tokens(pool_id, &mut pool, prev_exchange_shares);
+        self.internal_track_rate_divergence(pool_id, &pool);
+        pool.modify_total_fee(pool_fee);
         self.pools.replace(pool_id, &pool);
         amount_out
     }
@@ -825,20 +1380,36 @@ impl Contract {
         max_amount_in: Option<u128>,
         referral_info: &Option<(AccountId, u32)>,
     ) -> u128 {
+        self.assert_pool_not_archived(pool_id);
+        self.assert_pool_launched(pool_id);
+        self.assert_pool_not_rate_guarded(pool_id);
         self.internal_update_unit_share_cumulative_info(pool_id);
-        let mut pool = self.pools.get(pool_id).expect(ERR85_NO_POOL);
+        let mut pool = self.internal_get_pool(pool_id);
+        let pool_fee = self.internal_apply_fee_holiday(pool_id, &mut pool);
+        let admin_fees = self.internal_admin_fees(pool_id, referral_info);
+        self.internal_settle_admin_fee_receivers(pool_id, &pool, &admin_fees.referral_info);
+        let attributed_referral = admin_fees.referral_info.clone();
+        let prev_exchange_shares = pool.share_balances(&env::current_account_id());
+        assert_within_swap_cap(self.internal_max_swap_out(pool_id, &pool, token_out), amount_out);
         let amount_in = pool.swap_by_output(
             token_in,
             amount_out,
             token_out,
             max_amount_in,
-            AdminFees {
-                admin_fee_bps: self.admin_fee_bps,
-                exchange_id: env::current_account_id(),
-                referral_info: referral_info.clone(),
-            },
+            admin_fees.clone(),
             false
         );
+        self.internal_collect_lp_fee(pool_id, &mut pool, token_in, amount_in, &admin_fees);
+        self.internal_record_swap_stats(&pool, token_in, amount_in, token_out, amount_out);
+        self.internal_record_pool_volume(pool_id, &pool, token_in, amount_in, token_out, amount_out);
+        self.internal_record_last_trade(pool_id, token_in, amount_in, token_out, amount_out);
+        self.internal_record_referral_volume(&attributed_referral, token_in, amount_in);
+        self.internal_record_epoch_admin_fees(pool_id, &pool, prev_exchange_shares);
+        self.internal_divert_insurance_shares(pool_id, &mut pool, prev_exchange_shares);
+        self.internal_burn_admin_fee_shares(pool_id, &mut pool, prev_exchange_shares);
+        self.internal_accrue_admin_fee_tokens(pool_id, &mut pool, prev_exchange_shares);
+        self.internal_track_rate_divergence(pool_id, &pool);
+        pool.modify_total_fee(pool_fee);
         self.pools.replace(pool_id, &pool);
         amount_in
     }
@@ -855,6 +1426,7 @@ impl Contract {
         prev_result: ActionResult,
     ) {
         assert_all_same_action_type(actions);
+        assert_within_action_limits(actions);
         self.assert_no_frozen_tokens(
             &get_tokens_in_actions(actions)
             .into_iter()
@@ -948,19 +1520,23 @@ impl Contract {
         min_amount_out: u128,
         referral_info: &Option<(AccountId, u32)>,
     ) -> u128 {
-        let mut pool = pool_cache.remove(&pool_id).unwrap_or(self.pools.get(pool_id).expect(ERR85_NO_POOL));
+        self.assert_pool_not_archived(pool_id);
+        self.assert_pool_launched(pool_id);
+        self.assert_pool_not_rate_guarded(pool_id);
+        let mut pool = pool_cache.remove(&pool_id).unwrap_or_else(|| self.internal_get_swap_pool(pool_id));
+        let prev_imbalance = stable_pool_imbalance(&pool);
+        let max_amount_out = self.internal_max_swap_out(pool_id, &pool, token_out);
         let amount_out = pool.swap(
             token_in,
             amount_in,
             token_out,
             min_amount_out,
-            AdminFees {
-                admin_fee_bps: self.admin_fee_bps,
-                exchange_id: env::current_account_id(),
-                referral_info: referral_info.clone(),
-            },
+            self.internal_admin_fees(pool_id, referral_info),
             true
         );
+        assert_within_swap_cap(max_amount_out, amount_out);
+        let amount_out = self.internal_apply_maker_rebate(pool_id, prev_imbalance, &pool, token_out, amount_out, true);
+        assert!(amount_out >= min_amount_out, "{}", ERR68_SLIPPAGE);
         pool_cache.insert(pool_id, pool);
         amount_out
     }
@@ -975,17 +1551,17 @@ impl Contract {
         max_amount_in: Option<u128>,
         referral_info: &Option<(AccountId, u32)>,
     ) -> u128 {
-        let mut pool = pool_cache.remove(&pool_id).unwrap_or(self.pools.get(pool_id).expect(ERR85_NO_POOL));
+        self.assert_pool_not_archived(pool_id);
+        self.assert_pool_launched(pool_id);
+        self.assert_pool_not_rate_guarded(pool_id);
+        let mut pool = pool_cache.remove(&pool_id).unwrap_or_else(|| self.internal_get_swap_pool(pool_id));
+        assert_within_swap_cap(self.internal_max_swap_out(pool_id, &pool, token_out), amount_out);
         let amount_in = pool.swap_by_output(
             token_in,
             amount_out,
             token_out,
             max_amount_in,
-            AdminFees {
-                admin_fee_bps: self.admin_fee_bps,
-                exchange_id: env::current_account_id(),
-                referral_info: referral_info.clone(),
-            },
+            self.internal_admin_fees(pool_id, referral_info),
             false
         );
         pool_cache.insert(pool_id, pool);
@@ -1008,7 +1584,7 @@ mod tests {
     fn setup_contract() -> (VMContextBuilder, Contract) {
         let mut context = VMContextBuilder::new();
         testing_env!(context.predecessor_account_id(accounts(0)).build());
-        let contract = Contract::new(accounts(0), "boost_farm".to_string().try_into().unwrap(), "burrowland".to_string().try_into().unwrap(), 2000, 0);
+        let contract = Contract::new(accounts(0), "boost_farm".to_string().try_into().unwrap(), "burrowland".to_string().try_into().unwrap(), 2000, 0, None);
         (context, contract)
     }
 
@@ -1058,7 +1634,7 @@ mod tests {
         contract.extend_whitelisted_tokens(tokens.clone());
         testing_env!(context
             .predecessor_account_id(account_id.clone())
-            .attached_deposit(env::storage_byte_cost() * 300)
+            .attached_deposit(env::storage_byte_cost() * 1300)
             .build());
         let pool_id = contract.add_simple_pool(tokens, 25);
         testing_env!(context
@@ -1194,6 +1770,58 @@ mod tests {
         assert_eq!(contract.get_deposit(accounts(3), accounts(1)).0, 0);
     }
 
+    #[test]
+    fn test_range_pool() {
+        let one = 10u128.pow(24);
+        let (mut context, mut contract) = setup_contract();
+        testing_env!(context.predecessor_account_id(accounts(0)).attached_deposit(1).build());
+        contract.extend_whitelisted_tokens(vec![accounts(1), accounts(2)]);
+        testing_env!(context
+            .predecessor_account_id(accounts(3))
+            .attached_deposit(to_yocto("1"))
+            .build());
+        let pool_id = contract.add_range_pool(
+            vec![accounts(1), accounts(2)],
+            25,
+            U128(one * 95 / 100),
+            U128(one * 105 / 100),
+        );
+        deposit_tokens(
+            &mut context,
+            &mut contract,
+            accounts(3),
+            vec![(accounts(1), to_yocto("100")), (accounts(2), to_yocto("100"))],
+        );
+        testing_env!(context
+            .predecessor_account_id(accounts(3))
+            .attached_deposit(to_yocto("0.0007"))
+            .build());
+        contract.add_liquidity(pool_id, vec![U128(to_yocto("10")), U128(to_yocto("10"))], None);
+        assert_eq!(contract.get_pool_total_shares(pool_id).0, crate::utils::INIT_SHARES_SUPPLY);
+
+        // deeper than a simple pool of the same reserves, which would return less than 0.91.
+        let expected_out = contract.get_return(pool_id, accounts(1), U128(one), accounts(2));
+        assert!(expected_out.0 > one * 95 / 100 && expected_out.0 < one);
+        testing_env!(context.predecessor_account_id(accounts(3)).attached_deposit(1).build());
+        let amount_out = swap(&mut contract, pool_id, accounts(1), one, accounts(2));
+        assert_eq!(amount_out, expected_out.0);
+        assert_eq!(contract.get_deposit(accounts(3), accounts(1)).0, to_yocto("89"));
+        assert_eq!(contract.get_deposit(accounts(3), accounts(2)).0, to_yocto("90") + amount_out);
+
+        let amounts = contract.remove_liquidity(
+            pool_id,
+            contract.get_pool_shares(pool_id, accounts(3)),
+            vec![U128(1), U128(1)],
+        );
+        assert_eq!(contract.get_pool_shares(pool_id, accounts(3)).0, 0);
+        assert!(amounts[0].0 > to_yocto("10") && amounts[1].0 <= to_yocto("10") - amount_out);
+        assert_eq!(contract.get_deposit(accounts(3), accounts(1)).0, to_yocto("89") + amounts[0].0);
+        assert_eq!(
+            contract.get_deposit(accounts(3), accounts(2)).0,
+            to_yocto("90") + amount_out + amounts[1].0
+        );
+    }
+
     /// Test liquidity management.
     #[test]
     fn test_liquidity() {
@@ -1440,7 +2068,7 @@ mod tests {
 
         testing_env!(context
             .predecessor_account_id(acc.clone())
-            .attached_deposit(env::storage_byte_cost() * 500)
+            .attached_deposit(env::storage_byte_cost() * 1500)
             .build());
         let pool_id = contract.add_simple_pool(vec![token1.clone(), token2.clone()], 25);
         testing_env!(context
@@ -1934,7 +2562,7 @@ mod tests {
         assert_eq!(0, contract.get_user_whitelisted_tokens(accounts(3)).len());
         testing_env!(context
             .predecessor_account_id(accounts(0))
-            .attached_deposit(env::storage_byte_cost() * 334)
+            .attached_deposit(env::storage_byte_cost() * 1334)
             .build());
         let pool_id = contract.add_stable_swap_pool(tokens, vec![18, 18], 25, 240);
         println!("{:?}", contract.version());
@@ -2014,7 +2642,7 @@ mod tests {
         testing_env!(context.predecessor_account_id(accounts(0)).block_timestamp(2*86400 * 1_000_000_000).attached_deposit(1).build());
         contract.stable_swap_ramp_amp(0,250, (3*86400 * 1_000_000_000).into());
         testing_env!(context.predecessor_account_id(accounts(0)).attached_deposit(1).build());
-        contract.stable_swap_stop_ramp_amp(0);
+        contract.stable_swap_stop_ramp_amp(0, None);
     }
 
 
@@ -2032,7 +2660,7 @@ mod tests {
         assert_eq!(0, contract.get_user_whitelisted_tokens(accounts(3)).len());
         testing_env!(context
             .predecessor_account_id(accounts(0))
-            .attached_deposit(env::storage_byte_cost() * 389) // required storage depends on contract_id length
+            .attached_deposit(env::storage_byte_cost() * 1389) // required storage depends on contract_id length
             .build());
         let pool_id = contract.add_rated_swap_pool(tokens, vec![18, 18], 25, 240);
         println!("{:?}", contract.version());
@@ -2122,7 +2750,7 @@ mod tests {
         testing_env!(context.predecessor_account_id(accounts(0)).block_timestamp(2*86400 * 1_000_000_000).attached_deposit(1).build());
         contract.stable_swap_ramp_amp(0,250, (3*86400 * 1_000_000_000).into());
         testing_env!(context.predecessor_account_id(accounts(0)).attached_deposit(1).build());
-        contract.stable_swap_stop_ramp_amp(0);
+        contract.stable_swap_stop_ramp_amp(0, None);
     }
 
     #[test]
@@ -2161,12 +2789,12 @@ mod tests {
         );
         testing_env!(context
             .predecessor_account_id(accounts(0))
-            .attached_deposit(env::storage_byte_cost() * 335)
+            .attached_deposit(env::storage_byte_cost() * 1335)
             .build());
         contract.add_stable_swap_pool(vec![accounts(4), accounts(5)], vec![18, 18], 25, 240);
         testing_env!(context
             .predecessor_account_id(accounts(0))
-            .attached_deposit(env::storage_byte_cost() * 389) // required storage depends on contract_id length
+            .attached_deposit(env::storage_byte_cost() * 1389) // required storage depends on contract_id length
             .build());
         contract.add_rated_swap_pool(vec![accounts(4), accounts(5)], vec![18, 18], 25, 240);
 
@@ -2189,7 +2817,7 @@ mod tests {
         assert_eq!(0, contract.get_user_whitelisted_tokens(accounts(3)).len());
         testing_env!(context
             .predecessor_account_id(accounts(0))
-            .attached_deposit(env::storage_byte_cost() * 334)
+            .attached_deposit(env::storage_byte_cost() * 1334)
             .build());
         let pool_id = contract.add_stable_swap_pool(tokens, vec![18, 18], 25, 240);
         
@@ -2227,4 +2855,4205 @@ mod tests {
         assert_eq!(RECORD_COUNT_LIMIT, contract.get_pool_twap_info_view(pool_id).unwrap().records.len());
         assert!(contract.get_unit_share_twap_token_amounts(pool_id).is_some());
     }
+
+    fn setup_test_network_contract() -> (VMContextBuilder, Contract) {
+        let mut context = VMContextBuilder::new();
+        testing_env!(context.predecessor_account_id(accounts(0)).build());
+        let contract = Contract::new(accounts(0), "boost_farm".to_string().try_into().unwrap(), "burrowland".to_string().try_into().unwrap(), 2000, 0, Some(true));
+        (context, contract)
+    }
+
+    #[test]
+    fn test_pool_snapshot() {
+        let (mut context, mut contract) = setup_test_network_contract();
+        let pool_id = create_pool_with_liquidity(
+            &mut context,
+            &mut contract,
+            accounts(3),
+            vec![(accounts(1), to_yocto("5")), (accounts(2), to_yocto("10"))],
+        );
+        deposit_tokens(&mut context, &mut contract, accounts(3), vec![(accounts(1), to_yocto("1"))]);
+        testing_env!(context.predecessor_account_id(accounts(3)).attached_deposit(1).build());
+        swap(&mut contract, pool_id, accounts(1), to_yocto("1"), accounts(2));
+
+        let mut json = String::new();
+        loop {
+            let chunk = contract.get_pool_snapshot(pool_id, Some(json.len() as u64), Some(64));
+            json.push_str(&chunk.data);
+            if json.len() as u64 == chunk.total_len.0 {
+                break;
+            }
+        }
+        let snapshot: PoolSnapshot = near_sdk::serde_json::from_str(&json).unwrap();
+
+        testing_env!(context
+            .predecessor_account_id(accounts(0))
+            .attached_deposit(to_yocto("1"))
+            .build());
+        let new_pool_id = contract.import_pool_snapshot(snapshot);
+        let origin = contract.get_pool(pool_id);
+        let imported = contract.get_pool(new_pool_id);
+        assert_eq!(imported.pool_kind, origin.pool_kind);
+        assert_eq!(imported.amounts, origin.amounts);
+        assert_eq!(imported.total_fee, origin.total_fee);
+        assert_eq!(imported.shares_total_supply, origin.shares_total_supply);
+        assert_eq!(contract.get_pool_shares(new_pool_id, accounts(0)), origin.shares_total_supply);
+        assert_eq!(
+            contract.get_return(new_pool_id, accounts(1), to_yocto("1").into(), accounts(2)),
+            contract.get_return(pool_id, accounts(1), to_yocto("1").into(), accounts(2))
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Snapshot import is disabled on mainnet")]
+    fn test_pool_snapshot_import_mainnet() {
+        let (mut context, mut contract) = setup_contract();
+        let pool_id = create_pool_with_liquidity(
+            &mut context,
+            &mut contract,
+            accounts(3),
+            vec![(accounts(1), to_yocto("5")), (accounts(2), to_yocto("10"))],
+        );
+        let chunk = contract.get_pool_snapshot(pool_id, None, None);
+        let snapshot: PoolSnapshot = near_sdk::serde_json::from_str(&chunk.data).unwrap();
+        testing_env!(context
+            .current_account_id("exchange.testnet".to_string().try_into().unwrap())
+            .predecessor_account_id(accounts(0))
+            .attached_deposit(to_yocto("1"))
+            .build());
+        contract.import_pool_snapshot(snapshot);
+    }
+
+    #[test]
+    fn test_archive_pool() {
+        let (mut context, mut contract) = setup_contract();
+        let pool_id = create_pool_with_liquidity(
+            &mut context,
+            &mut contract,
+            accounts(3),
+            vec![(accounts(1), to_yocto("5")), (accounts(2), to_yocto("10"))],
+        );
+        let active_pool_id = create_pool_with_liquidity(
+            &mut context,
+            &mut contract,
+            accounts(3),
+            vec![(accounts(1), to_yocto("5")), (accounts(4), to_yocto("10"))],
+        );
+        testing_env!(context.predecessor_account_id(accounts(3)).attached_deposit(1).build());
+        contract.remove_liquidity(pool_id, contract.get_pool_shares(pool_id, accounts(3)), vec![1.into(), 1.into()]);
+
+        testing_env!(context.predecessor_account_id(accounts(0)).attached_deposit(1).build());
+        contract.deprecate_pool(pool_id);
+        contract.archive_pool(pool_id);
+        assert!(contract.is_pool_archived(pool_id));
+        assert!(contract.get_archived_pools(None, None).contains_key(&pool_id));
+        let active_pools = contract.get_active_pools(0, 10);
+        assert_eq!(active_pools.len(), 1);
+        assert!(active_pools.contains_key(&active_pool_id));
+        // pages follow pool ids
+        assert!(contract.get_active_pools(0, 1).contains_key(&active_pool_id));
+        assert!(contract.get_active_pools(active_pool_id + 1, 10).is_empty());
+        assert_eq!(contract.get_pool(pool_id).shares_total_supply.0, 0);
+    }
+
+    #[test]
+    #[should_panic(expected = "Pool not deprecated")]
+    fn test_archive_pool_not_deprecated() {
+        let (mut context, mut contract) = setup_contract();
+        let pool_id = create_pool_with_liquidity(
+            &mut context,
+            &mut contract,
+            accounts(3),
+            vec![(accounts(1), to_yocto("5")), (accounts(2), to_yocto("10"))],
+        );
+        testing_env!(context.predecessor_account_id(accounts(3)).attached_deposit(1).build());
+        contract.remove_liquidity(pool_id, contract.get_pool_shares(pool_id, accounts(3)), vec![1.into(), 1.into()]);
+        testing_env!(context.predecessor_account_id(accounts(0)).attached_deposit(1).build());
+        contract.archive_pool(pool_id);
+    }
+
+    #[test]
+    #[should_panic(expected = "Pool archived")]
+    fn test_archived_pool_not_swappable() {
+        let (mut context, mut contract) = setup_contract();
+        let pool_id = create_pool_with_liquidity(
+            &mut context,
+            &mut contract,
+            accounts(3),
+            vec![(accounts(1), to_yocto("5")), (accounts(2), to_yocto("10"))],
+        );
+        testing_env!(context.predecessor_account_id(accounts(3)).attached_deposit(1).build());
+        contract.remove_liquidity(pool_id, contract.get_pool_shares(pool_id, accounts(3)), vec![1.into(), 1.into()]);
+        testing_env!(context.predecessor_account_id(accounts(0)).attached_deposit(1).build());
+        contract.deprecate_pool(pool_id);
+        contract.archive_pool(pool_id);
+
+        testing_env!(context.predecessor_account_id(accounts(3)).attached_deposit(1).build());
+        swap(&mut contract, pool_id, accounts(1), to_yocto("1"), accounts(2));
+    }
+
+    #[test]
+    fn test_token_withdraw_fee() {
+        let (mut context, mut contract) = setup_contract();
+        deposit_tokens(&mut context, &mut contract, accounts(3), vec![(accounts(1), to_yocto("5"))]);
+        deposit_tokens(&mut context, &mut contract, accounts(4), vec![]);
+        testing_env!(context.predecessor_account_id(accounts(0)).attached_deposit(1).build());
+        contract.set_token_withdraw_fee(accounts(1), Some(WithdrawFee { fee_bps: 50, recipient_id: accounts(4).into() }));
+        assert_eq!(contract.get_token_withdraw_fees().get(accounts(1).as_ref()).unwrap().fee_bps, 50);
+
+        testing_env!(context.predecessor_account_id(accounts(3)).attached_deposit(1).build());
+        contract.withdraw_with_memo(accounts(1), U128(to_yocto("1")), None, None, "exit".to_string());
+        assert_eq!(contract.get_deposit(accounts(3), accounts(1)).0, to_yocto("4"));
+        assert_eq!(contract.get_in_flight_operations(accounts(3)).get(accounts(1).as_ref()), Some(&1));
+        // the fee is held until the transfer resolves
+        assert_eq!(contract.get_deposit(accounts(4), accounts(1)).0, 0);
+
+        // a failed transfer refunds the fee with the amount
+        fail_withdraw_callback(&mut context, &mut contract, accounts(3), to_yocto("0.995"), Some(to_yocto("0.005")));
+        assert_eq!(contract.get_deposit(accounts(3), accounts(1)).0, to_yocto("5"));
+        assert_eq!(contract.get_deposit(accounts(4), accounts(1)).0, 0);
+
+        // swap outputs sent to a wallet pay it too
+        let pool_id = create_pool_with_liquidity(
+            &mut context,
+            &mut contract,
+            accounts(3),
+            vec![(accounts(2), to_yocto("5")), (accounts(1), to_yocto("1"))],
+        );
+        deposit_tokens(&mut context, &mut contract, accounts(3), vec![(accounts(2), to_yocto("1"))]);
+        testing_env!(context.predecessor_account_id(accounts(3)).attached_deposit(1).build(), Default::default(), Default::default(), Default::default(), vec![]);
+        let amount_out = contract.swap_to(
+            vec![SwapAction {
+                pool_id,
+                token_in: accounts(2).into(),
+                amount_in: Some(U128(to_yocto("1"))),
+                token_out: accounts(1).into(),
+                min_amount_out: U128(1),
+            }],
+            None,
+            accounts(5),
+            None,
+        ).0;
+        assert_eq!(contract.get_in_flight_operations(accounts(3)).get(accounts(1).as_ref()), Some(&1));
+        let fee = amount_out * 50 / 10_000;
+
+        testing_env!(
+            context.predecessor_account_id(env::current_account_id().try_into().unwrap()).build(),
+            Default::default(),
+            Default::default(),
+            Default::default(),
+            vec![PromiseResult::Successful(vec![])]
+        );
+        contract.exchange_callback_post_withdraw(accounts(1).into(), accounts(3).into(), U128(amount_out - fee), Some(U128(fee)), Some(accounts(5).into()));
+        assert_eq!(contract.get_deposit(accounts(4), accounts(1)).0, fee);
+    }
+
+    #[test]
+    #[should_panic(expected = "Withdraw fee must be within (0, 100] bps")]
+    fn test_token_withdraw_fee_too_large() {
+        let (mut context, mut contract) = setup_contract();
+        testing_env!(context.predecessor_account_id(accounts(0)).attached_deposit(1).build());
+        contract.set_token_withdraw_fee(accounts(1), Some(WithdrawFee { fee_bps: 101, recipient_id: accounts(0).into() }));
+    }
+
+    #[test]
+    fn test_daily_withdrawal_limit() {
+        let (mut context, mut contract) = setup_contract();
+        let day = crate::utils::to_nano(WITHDRAWAL_LIMIT_DAY_SEC);
+        deposit_tokens(&mut context, &mut contract, accounts(3), vec![(accounts(1), to_yocto("5"))]);
+        testing_env!(context.predecessor_account_id(accounts(3)).attached_deposit(to_yocto("0.01")).build());
+        contract.set_daily_withdrawal_limit(accounts(1).into(), Some(U128(to_yocto("2"))));
+        testing_env!(context.attached_deposit(1).build());
+        contract.withdraw(accounts(1), U128(to_yocto("1")), None, None);
+
+        // raising the limit waits for the delay, lowering it applies at once
+        contract.set_daily_withdrawal_limit(accounts(1).into(), Some(U128(to_yocto("3"))));
+        let limit = contract.get_daily_withdrawal_limits(accounts(3)).remove(accounts(1).as_ref()).unwrap();
+        assert_eq!((limit.daily_limit.0, limit.withdrawn_today.0), (to_yocto("2"), to_yocto("1")));
+        assert_eq!(limit.pending_change.unwrap().daily_limit, Some(U128(to_yocto("3"))));
+        contract.set_daily_withdrawal_limit(accounts(1).into(), Some(U128(to_yocto("1.5"))));
+        let limit = contract.get_daily_withdrawal_limits(accounts(3)).remove(accounts(1).as_ref()).unwrap();
+        assert_eq!(limit.daily_limit.0, to_yocto("1.5"));
+        assert!(limit.pending_change.is_none());
+
+        contract.set_daily_withdrawal_limit(accounts(1).into(), None);
+        testing_env!(context.block_timestamp(day).build());
+        assert_eq!(contract.get_daily_withdrawal_limits(accounts(3)).get(accounts(1).as_ref()).unwrap().withdrawn_today.0, 0);
+        contract.withdraw(accounts(1), U128(to_yocto("1.5")), None, None);
+        testing_env!(context.block_timestamp(crate::utils::to_nano(WITHDRAWAL_LIMIT_CHANGE_DELAY_SEC)).build());
+        assert!(contract.get_daily_withdrawal_limits(accounts(3)).is_empty());
+        contract.withdraw(accounts(1), U128(to_yocto("2")), None, None);
+    }
+
+    #[test]
+    #[should_panic(expected = "Daily withdrawal limit of 2000000000000000000000000 bob exceeded")]
+    fn test_daily_withdrawal_limit_exceeded() {
+        let (mut context, mut contract) = setup_contract();
+        deposit_tokens(&mut context, &mut contract, accounts(3), vec![(accounts(1), to_yocto("5"))]);
+        testing_env!(context.predecessor_account_id(accounts(3)).attached_deposit(to_yocto("0.01")).build());
+        contract.set_daily_withdrawal_limit(accounts(1).into(), Some(U128(to_yocto("2"))));
+        testing_env!(context.attached_deposit(1).build());
+        contract.withdraw(accounts(1), U128(to_yocto("1")), None, None);
+        contract.withdraw(accounts(1), U128(to_yocto("1.5")), None, None);
+    }
+
+    #[test]
+    fn test_daily_withdrawal_limit_mft_transfer() {
+        let (mut context, mut contract) = setup_contract();
+        deposit_tokens(&mut context, &mut contract, accounts(4), vec![(accounts(1), 1)]);
+        deposit_tokens(&mut context, &mut contract, accounts(3), vec![(accounts(1), to_yocto("5")), (accounts(2), to_yocto("5"))]);
+        testing_env!(context.predecessor_account_id(accounts(3)).attached_deposit(to_yocto("0.01")).build());
+        contract.set_daily_withdrawal_limit(accounts(1).into(), Some(U128(to_yocto("2"))));
+        testing_env!(context.attached_deposit(1).build());
+        contract.mft_transfer(accounts(1).into(), accounts(4), U128(to_yocto("1.5")), None);
+        let limit = contract.get_daily_withdrawal_limits(accounts(3)).remove(accounts(1).as_ref()).unwrap();
+        assert_eq!(limit.withdrawn_today.0, to_yocto("1.5"));
+
+        // a cap next to others is a raise from nothing, it waits for the delay
+        contract.set_daily_withdrawal_limit(accounts(2).into(), Some(U128(to_yocto("1"))));
+        let limit = contract.get_daily_withdrawal_limits(accounts(3)).remove(accounts(2).as_ref()).unwrap();
+        assert_eq!(limit.daily_limit.0, 0);
+        assert_eq!(limit.pending_change.unwrap().daily_limit, Some(U128(to_yocto("1"))));
+    }
+
+    #[test]
+    #[should_panic(expected = "No daily withdrawal limit of charlie, needed while others are set")]
+    fn test_daily_withdrawal_limit_unlimited_token() {
+        let (mut context, mut contract) = setup_contract();
+        deposit_tokens(&mut context, &mut contract, accounts(4), vec![(accounts(2), 1)]);
+        deposit_tokens(&mut context, &mut contract, accounts(3), vec![(accounts(1), to_yocto("5")), (accounts(2), to_yocto("5"))]);
+        testing_env!(context.predecessor_account_id(accounts(3)).attached_deposit(to_yocto("0.01")).build());
+        contract.set_daily_withdrawal_limit(accounts(1).into(), Some(U128(to_yocto("2"))));
+        testing_env!(context.attached_deposit(1).build());
+        contract.mft_transfer(accounts(2).into(), accounts(4), U128(to_yocto("1")), None);
+    }
+
+    #[test]
+    fn test_in_flight_withdraw() {
+        let (mut context, mut contract) = setup_contract();
+        deposit_tokens(&mut context, &mut contract, accounts(3), vec![(accounts(1), to_yocto("5"))]);
+        testing_env!(context.predecessor_account_id(accounts(3)).attached_deposit(1).build());
+        contract.withdraw(accounts(1), U128(to_yocto("1")), None, None);
+        assert_eq!(contract.get_in_flight_operations(accounts(3)).get(accounts(1).as_ref()), Some(&1));
+
+        testing_env!(
+            context.predecessor_account_id(env::current_account_id().try_into().unwrap()).build(),
+            Default::default(),
+            Default::default(),
+            Default::default(),
+            vec![PromiseResult::Failed]
+        );
+        contract.exchange_callback_post_withdraw(accounts(1).into(), accounts(3).into(), U128(to_yocto("1")), None, None);
+        assert!(contract.get_in_flight_operations(accounts(3)).is_empty());
+        assert_eq!(contract.get_deposit(accounts(3), accounts(1)).0, to_yocto("5"));
+
+        testing_env!(context.predecessor_account_id(accounts(3)).attached_deposit(1).build(), Default::default(), Default::default(), Default::default(), vec![]);
+        contract.withdraw(accounts(1), U128(0), Some(true), None);
+        assert!(contract.get_deposits(accounts(3)).is_empty());
+    }
+
+    #[test]
+    fn test_clear_in_flight_operations() {
+        let (mut context, mut contract) = setup_contract();
+        deposit_tokens(&mut context, &mut contract, accounts(3), vec![(accounts(1), to_yocto("5")), (accounts(2), to_yocto("5"))]);
+        testing_env!(context.predecessor_account_id(accounts(3)).attached_deposit(1).build());
+        contract.withdraw(accounts(1), U128(to_yocto("1")), None, None);
+        contract.withdraw(accounts(2), U128(to_yocto("1")), None, None);
+        contract.withdraw(accounts(2), U128(to_yocto("1")), None, None);
+
+        testing_env!(context.predecessor_account_id(accounts(0)).attached_deposit(1).build());
+        assert_eq!(contract.clear_in_flight_operations(accounts(3), Some(accounts(2).to_string())), 2);
+        let operations = contract.get_in_flight_operations(accounts(3));
+        assert_eq!(operations.len(), 1);
+        assert_eq!(operations.get(accounts(1).as_ref()), Some(&1));
+        assert_eq!(contract.clear_in_flight_operations(accounts(3), None), 1);
+        assert!(contract.get_in_flight_operations(accounts(3)).is_empty());
+
+        testing_env!(context.predecessor_account_id(accounts(3)).attached_deposit(1).build());
+        contract.withdraw(accounts(1), U128(0), Some(true), None);
+        assert!(contract.get_deposits(accounts(3)).get(accounts(1).as_ref()).is_none());
+    }
+
+    #[test]
+    #[should_panic(expected = "E100: no permission to invoke this")]
+    fn test_clear_in_flight_operations_not_allowed() {
+        let (mut context, mut contract) = setup_contract();
+        testing_env!(context.predecessor_account_id(accounts(3)).attached_deposit(1).build());
+        contract.clear_in_flight_operations(accounts(3), None);
+    }
+
+    fn fail_withdraw_callback(context: &mut VMContextBuilder, contract: &mut Contract, sender_id: ValidAccountId, amount: Balance, fee: Option<Balance>) {
+        testing_env!(
+            context.predecessor_account_id(env::current_account_id().try_into().unwrap()).build(),
+            Default::default(),
+            Default::default(),
+            Default::default(),
+            vec![PromiseResult::Failed]
+        );
+        contract.exchange_callback_post_withdraw(accounts(1).into(), sender_id.into(), U128(amount), fee.map(U128), None);
+    }
+
+    #[test]
+    fn test_unclaimed_withdrawal() {
+        let (mut context, mut contract) = setup_contract();
+        fail_withdraw_callback(&mut context, &mut contract, accounts(4), to_yocto("1"), None);
+        fail_withdraw_callback(&mut context, &mut contract, accounts(4), to_yocto("2"), None);
+        fail_withdraw_callback(&mut context, &mut contract, accounts(5), to_yocto("3"), None);
+        assert_eq!(contract.get_unclaimed_withdrawal(accounts(4), accounts(1)).unwrap().amount, to_yocto("3"));
+        let unclaimed_withdrawals = contract.get_unclaimed_withdrawals(None, None);
+        assert_eq!(unclaimed_withdrawals.len(), 2);
+        assert_eq!(unclaimed_withdrawals[1].account_id, accounts(5).to_string());
+        assert_eq!(contract.get_deposit(accounts(0), accounts(1)).0, 0);
+
+        testing_env!(context.predecessor_account_id(accounts(4)).attached_deposit(to_yocto("1")).build(), Default::default(), Default::default(), Default::default(), vec![]);
+        contract.storage_deposit(None, None);
+        testing_env!(context.predecessor_account_id(accounts(4)).attached_deposit(1).build());
+        assert_eq!(contract.claim_unclaimed_withdrawal(accounts(1)).0, to_yocto("3"));
+        assert_eq!(contract.get_deposit(accounts(4), accounts(1)).0, to_yocto("3"));
+        assert!(contract.get_unclaimed_withdrawal(accounts(4), accounts(1)).is_none());
+
+        testing_env!(context
+            .predecessor_account_id(accounts(0))
+            .block_timestamp(UNCLAIMED_WITHDRAWAL_GRACE_SEC as u64 * 1_000_000_000)
+            .attached_deposit(1)
+            .build());
+        contract.sweep_unclaimed_withdrawals(vec![(accounts(5), accounts(1))]);
+        assert_eq!(contract.get_deposit(accounts(0), accounts(1)).0, to_yocto("3"));
+        assert!(contract.get_unclaimed_withdrawals(None, None).is_empty());
+        assert!(near_sdk::test_utils::get_logs().iter().any(|log| log.contains("unclaimed_withdrawal_swept")));
+    }
+
+    #[test]
+    #[should_panic(expected = "still in grace period")]
+    fn test_unclaimed_withdrawal_sweep_in_grace() {
+        let (mut context, mut contract) = setup_contract();
+        fail_withdraw_callback(&mut context, &mut contract, accounts(4), to_yocto("1"), None);
+        testing_env!(context
+            .predecessor_account_id(accounts(0))
+            .block_timestamp(UNCLAIMED_WITHDRAWAL_GRACE_SEC as u64 * 1_000_000_000 - 1)
+            .attached_deposit(1)
+            .build(), Default::default(), Default::default(), Default::default(), vec![]);
+        contract.sweep_unclaimed_withdrawals(vec![(accounts(4), accounts(1))]);
+    }
+
+    #[test]
+    #[should_panic(expected = "E20: conflicting operation in flight")]
+    fn test_in_flight_blocks_unregister() {
+        let (mut context, mut contract) = setup_contract();
+        deposit_tokens(&mut context, &mut contract, accounts(3), vec![(accounts(1), to_yocto("5"))]);
+        testing_env!(context.predecessor_account_id(accounts(3)).attached_deposit(1).build());
+        contract.withdraw(accounts(1), U128(to_yocto("5")), None, None);
+        contract.unregister_tokens(vec![accounts(1)]);
+    }
+
+    #[test]
+    #[should_panic(expected = "Receiver not allowed")]
+    fn test_mft_receiver_allowlist() {
+        let (mut context, mut contract) = setup_contract();
+        let pool_id = create_pool_with_liquidity(
+            &mut context,
+            &mut contract,
+            accounts(3),
+            vec![(accounts(1), to_yocto("5")), (accounts(2), to_yocto("10"))],
+        );
+        testing_env!(context.predecessor_account_id(accounts(0)).attached_deposit(1).build());
+        contract.set_mft_receiver_policy(MftReceiverListMode::Allowlist, near_sdk::json_types::U64(DEFAULT_MFT_RECEIVER_MIN_GAS));
+        contract.extend_mft_receiver_list(vec![accounts(4)]);
+        assert_eq!(contract.get_mft_receiver_list(), vec![accounts(4).to_string()]);
+
+        testing_env!(context.predecessor_account_id(accounts(3)).attached_deposit(1).build());
+        contract.mft_transfer_call(format!(":{}", pool_id), accounts(5), U128(1_000), None, "".to_string());
+    }
+
+    #[test]
+    fn test_pool_metadata() {
+        let (mut context, mut contract) = setup_contract();
+        let pool_id = create_pool_with_liquidity(
+            &mut context,
+            &mut contract,
+            accounts(3),
+            vec![(accounts(1), to_yocto("5")), (accounts(2), to_yocto("10"))],
+        );
+        assert_eq!(contract.get_pool_metadata(pool_id).unwrap().creator, Some(accounts(3).to_string()));
+
+        testing_env!(context.predecessor_account_id(accounts(3)).attached_deposit(to_yocto("0.01")).build());
+        contract.set_pool_metadata(pool_id, Some("USDC/NEAR".to_string()), Some("ab".repeat(32)), Some("stable".to_string()));
+        let metadata = contract.get_pool_metadata(pool_id).unwrap();
+        assert_eq!(metadata.creator, Some(accounts(3).to_string()));
+        assert_eq!(metadata.name, Some("USDC/NEAR".to_string()));
+        assert_eq!(metadata.category, Some("stable".to_string()));
+
+        testing_env!(context.predecessor_account_id(accounts(0)).attached_deposit(to_yocto("0.01")).build());
+        contract.set_pool_metadata(pool_id, None, None, None);
+        assert!(contract.get_pool_metadata_batch(vec![pool_id])[&pool_id].name.is_none());
+    }
+
+    #[test]
+    #[should_panic(expected = "E100: no permission to invoke this")]
+    fn test_pool_metadata_not_creator() {
+        let (mut context, mut contract) = setup_contract();
+        let pool_id = create_pool_with_liquidity(
+            &mut context,
+            &mut contract,
+            accounts(3),
+            vec![(accounts(1), to_yocto("5")), (accounts(2), to_yocto("10"))],
+        );
+        testing_env!(context.predecessor_account_id(accounts(4)).attached_deposit(to_yocto("0.01")).build());
+        contract.set_pool_metadata(pool_id, Some("USDC/NEAR".to_string()), None, None);
+    }
+
+    #[test]
+    fn test_transfer_pool_creator() {
+        let (mut context, mut contract) = setup_contract();
+        let pool_id = create_pool_with_liquidity(
+            &mut context,
+            &mut contract,
+            accounts(3),
+            vec![(accounts(1), to_yocto("5")), (accounts(2), to_yocto("10"))],
+        );
+        testing_env!(context.predecessor_account_id(accounts(3)).attached_deposit(to_yocto("0.01")).build());
+        contract.transfer_pool_creator(pool_id, accounts(4));
+        assert_eq!(contract.get_pool_metadata(pool_id).unwrap().creator, Some(accounts(4).to_string()));
+
+        testing_env!(context.predecessor_account_id(accounts(0)).attached_deposit(1).build());
+        contract.set_creator_fee_bounds(Some(CreatorFeeBounds { min_fee: 10, max_fee: 100 }));
+        testing_env!(context.predecessor_account_id(accounts(4)).attached_deposit(1).build());
+        contract.modify_total_fee_by_creator(pool_id, 50);
+        assert_eq!(contract.get_pool_fee(pool_id), 50);
+
+        testing_env!(context.predecessor_account_id(accounts(4)).attached_deposit(to_yocto("0.01")).build());
+        contract.request_pool_deprecation(pool_id);
+        assert!(contract.get_pool_deprecation_requests(None, None).contains_key(&pool_id));
+        testing_env!(context.predecessor_account_id(accounts(4)).attached_deposit(1).build());
+        contract.cancel_pool_deprecation(pool_id);
+        assert!(contract.get_pool_deprecation_requests(None, None).is_empty());
+    }
+
+    #[test]
+    #[should_panic(expected = "E62: illegal fee")]
+    fn test_modify_total_fee_by_creator_out_of_bounds() {
+        let (mut context, mut contract) = setup_contract();
+        let pool_id = create_pool_with_liquidity(
+            &mut context,
+            &mut contract,
+            accounts(3),
+            vec![(accounts(1), to_yocto("5")), (accounts(2), to_yocto("10"))],
+        );
+        testing_env!(context.predecessor_account_id(accounts(0)).attached_deposit(1).build());
+        contract.set_creator_fee_bounds(Some(CreatorFeeBounds { min_fee: 10, max_fee: 100 }));
+        testing_env!(context.predecessor_account_id(accounts(3)).attached_deposit(1).build());
+        contract.modify_total_fee_by_creator(pool_id, 101);
+    }
+
+    #[test]
+    fn test_compound_rewards() {
+        let (mut context, mut contract) = setup_contract();
+        let pool_id = create_pool_with_liquidity(
+            &mut context,
+            &mut contract,
+            accounts(3),
+            vec![(accounts(1), to_yocto("5")), (accounts(2), to_yocto("10"))],
+        );
+        deposit_tokens(&mut context, &mut contract, accounts(4), vec![(accounts(1), to_yocto("1"))]);
+        testing_env!(context
+            .predecessor_account_id(accounts(4))
+            .block_timestamp(crate::utils::to_nano(1000))
+            .attached_deposit(to_yocto("0.01"))
+            .build());
+        contract.create_lp_incentive(pool_id, accounts(1), U128(to_yocto("1")), 100);
+
+        testing_env!(context.block_timestamp(crate::utils::to_nano(1050)).build());
+        assert!(contract.get_reward_auto_compound(accounts(3)).is_none());
+        testing_env!(context.predecessor_account_id(accounts(3)).attached_deposit(to_yocto("0.01")).build());
+        contract.set_reward_auto_compound(true, Some(1000));
+        assert_eq!(contract.get_reward_auto_compound(accounts(3)), Some(1000));
+        testing_env!(context.predecessor_account_id(accounts(0)).attached_deposit(to_yocto("0.01")).build());
+        contract.push_cached_prices(vec![
+            TokenPrice { token_id: accounts(1), multiplier: U128(2), decimals: 0 },
+            TokenPrice { token_id: accounts(2), multiplier: U128(1), decimals: 0 },
+        ]);
+
+        let shares = contract.get_pool_shares(pool_id, accounts(3)).0;
+        let balances = (contract.get_deposit(accounts(3), accounts(1)).0, contract.get_deposit(accounts(3), accounts(2)).0);
+        testing_env!(context.predecessor_account_id(accounts(4)).attached_deposit(0).build());
+        let minted = contract.compound_rewards(accounts(3), pool_id).0;
+        assert!(minted > 0);
+        assert_eq!(contract.get_pool_shares(pool_id, accounts(3)).0, shares + minted);
+        assert_eq!(contract.get_claimable_lp_incentive(pool_id, accounts(3)).0, 0);
+        assert_eq!(contract.get_lp_incentive(pool_id).unwrap().claimed, to_yocto("0.5"));
+        // only rounding dust of the swap is left over
+        assert!(contract.get_deposit(accounts(3), accounts(1)).0 - balances.0 < 1_000);
+        assert!(contract.get_deposit(accounts(3), accounts(2)).0 - balances.1 < 1_000);
+    }
+
+    #[test]
+    fn test_compound_rewards_off_cached_prices() {
+        let (mut context, mut contract) = setup_contract();
+        let pool_id = create_pool_with_liquidity(
+            &mut context,
+            &mut contract,
+            accounts(3),
+            vec![(accounts(1), to_yocto("5")), (accounts(2), to_yocto("10"))],
+        );
+        deposit_tokens(&mut context, &mut contract, accounts(4), vec![(accounts(1), to_yocto("1"))]);
+        testing_env!(context
+            .predecessor_account_id(accounts(4))
+            .block_timestamp(crate::utils::to_nano(1000))
+            .attached_deposit(to_yocto("0.01"))
+            .build());
+        contract.create_lp_incentive(pool_id, accounts(1), U128(to_yocto("1")), 100);
+        // the pool trades at 2, the cached prices say 3
+        testing_env!(context.predecessor_account_id(accounts(0)).attached_deposit(to_yocto("0.01")).build());
+        contract.push_cached_prices(vec![
+            TokenPrice { token_id: accounts(1), multiplier: U128(3), decimals: 0 },
+            TokenPrice { token_id: accounts(2), multiplier: U128(1), decimals: 0 },
+        ]);
+
+        testing_env!(context
+            .predecessor_account_id(accounts(3))
+            .block_timestamp(crate::utils::to_nano(1050))
+            .attached_deposit(0)
+            .build());
+        let balance = contract.get_deposit(accounts(3), accounts(1)).0;
+        assert_eq!(contract.compound_rewards(accounts(3), pool_id).0, 0);
+        assert_eq!(contract.get_deposit(accounts(3), accounts(1)).0, balance + to_yocto("0.5"));
+    }
+
+    #[test]
+    #[should_panic(expected = "Account danny denied")]
+    fn test_compound_rewards_denied() {
+        let (mut context, mut contract) = setup_contract();
+        let pool_id = create_pool_with_liquidity(
+            &mut context,
+            &mut contract,
+            accounts(3),
+            vec![(accounts(1), to_yocto("5")), (accounts(2), to_yocto("10"))],
+        );
+        deposit_tokens(&mut context, &mut contract, accounts(4), vec![(accounts(1), to_yocto("1"))]);
+        testing_env!(context
+            .predecessor_account_id(accounts(4))
+            .block_timestamp(crate::utils::to_nano(1000))
+            .attached_deposit(to_yocto("0.01"))
+            .build());
+        contract.create_lp_incentive(pool_id, accounts(1), U128(to_yocto("1")), 100);
+        testing_env!(context.predecessor_account_id(accounts(0)).attached_deposit(1).build());
+        contract.extend_denied_accounts(vec![accounts(3)]);
+
+        testing_env!(context.predecessor_account_id(accounts(3)).block_timestamp(crate::utils::to_nano(1050)).attached_deposit(0).build());
+        contract.compound_rewards(accounts(3), pool_id);
+    }
+
+    #[test]
+    #[should_panic(expected = "E100: no permission to invoke this")]
+    fn test_compound_rewards_not_opted_in() {
+        let (mut context, mut contract) = setup_contract();
+        let pool_id = create_pool_with_liquidity(
+            &mut context,
+            &mut contract,
+            accounts(3),
+            vec![(accounts(1), to_yocto("5")), (accounts(2), to_yocto("10"))],
+        );
+        testing_env!(context.predecessor_account_id(accounts(4)).build());
+        contract.compound_rewards(accounts(3), pool_id);
+    }
+
+    #[test]
+    fn test_replica_export() {
+        let (mut context, mut contract) = setup_contract();
+        let pool_id = create_pool_with_liquidity(
+            &mut context,
+            &mut contract,
+            accounts(3),
+            vec![(accounts(1), to_yocto("5")), (accounts(2), to_yocto("10"))],
+        );
+        deposit_tokens(&mut context, &mut contract, accounts(4), vec![(accounts(1), to_yocto("1")), (accounts(2), to_yocto("1"))]);
+
+        let page = contract.get_replica_accounts(None, 1);
+        assert_eq!(page.items[0].account_id, accounts(3).to_string());
+        let cursor = page.next_cursor.unwrap();
+        let page = contract.get_replica_accounts(Some(cursor), 10);
+        assert_eq!(page.items[0].account_id, accounts(4).to_string());
+        assert_eq!(page.items[0].deposits.get(&accounts(1).to_string()).unwrap().0, to_yocto("1"));
+        assert!(page.next_cursor.is_none());
+
+        let pools = contract.get_replica_pools(None, 10);
+        assert_eq!(pools.items.len(), 1);
+        assert_eq!(pools.items[0].pool_id, pool_id);
+        assert_eq!(pools.state_version, page.state_version);
+
+        // balance changes are picked up by block height, registrations bump the version
+        testing_env!(context.predecessor_account_id(accounts(4)).attached_deposit(1).build());
+        swap(&mut contract, pool_id, accounts(1), 1_000, accounts(2));
+        assert_eq!(contract.get_replica_pools(None, 10).state_version, page.state_version);
+        deposit_tokens(&mut context, &mut contract, accounts(5), vec![]);
+        assert!(contract.get_replica_pools(None, 10).state_version.0 > page.state_version.0);
+    }
+
+    #[test]
+    fn test_add_simple_pool_with_liquidity() {
+        let (mut context, mut contract) = setup_contract();
+        deposit_tokens(
+            &mut context,
+            &mut contract,
+            accounts(3),
+            vec![(accounts(1), to_yocto("10")), (accounts(2), to_yocto("20"))],
+        );
+        testing_env!(context.predecessor_account_id(accounts(0)).attached_deposit(1).build());
+        contract.extend_whitelisted_tokens(vec![accounts(1), accounts(2)]);
+
+        testing_env!(context.predecessor_account_id(accounts(3)).attached_deposit(to_yocto("0.02")).build());
+        let (pool_id, shares) = contract.add_simple_pool_with_liquidity(
+            vec![accounts(1), accounts(2)],
+            25,
+            vec![U128(to_yocto("5")), U128(to_yocto("10"))],
+        );
+        assert_eq!(shares.0, crate::utils::INIT_SHARES_SUPPLY);
+        assert_eq!(contract.get_pool_shares(pool_id, accounts(3)), shares);
+        assert_eq!(contract.get_pool(pool_id).amounts, vec![U128(to_yocto("5")), U128(to_yocto("10"))]);
+        assert_eq!(contract.get_deposit(accounts(3), accounts(1)).0, to_yocto("5"));
+        assert_eq!(contract.get_deposit(accounts(3), accounts(2)).0, to_yocto("10"));
+        assert_eq!(contract.get_pool_metadata(pool_id).unwrap().creator, Some(accounts(3).to_string()));
+    }
+
+    #[test]
+    fn test_amp_schedule() {
+        let (mut context, mut contract) = setup_contract();
+        let day = 86400 * 1_000_000_000u64;
+        testing_env!(context
+            .predecessor_account_id(accounts(0))
+            .attached_deposit(env::storage_byte_cost() * 1334)
+            .build());
+        let pool_id = contract.add_stable_swap_pool(vec![accounts(1), accounts(2)], vec![18, 18], 25, 240);
+        testing_env!(context.block_timestamp(day).attached_deposit(1).build());
+        contract.stable_swap_schedule_ramp_amp(pool_id, vec![
+            AmpRampStep { target_amp: 480, target_time: 2 * day },
+            AmpRampStep { target_amp: 960, target_time: 3 * day },
+        ]);
+        assert_eq!(contract.get_amp_schedule(pool_id).len(), 2);
+        testing_env!(context.block_timestamp(2 * day).build());
+        assert_eq!(contract.get_pool(pool_id).amp, 480);
+        testing_env!(context.block_timestamp(2 * day + day / 2).build());
+        assert_eq!(contract.get_pool(pool_id).amp, 720);
+        contract.sync_amp_schedule(pool_id);
+        assert!(contract.get_amp_schedule(pool_id).is_empty());
+        testing_env!(context.block_timestamp(4 * day).build());
+        assert_eq!(contract.get_pool(pool_id).amp, 960);
+    }
+
+    #[test]
+    #[should_panic(expected = "E84: amp factor change is too large")]
+    fn test_amp_schedule_invalid_step() {
+        let (mut context, mut contract) = setup_contract();
+        let day = 86400 * 1_000_000_000u64;
+        testing_env!(context
+            .predecessor_account_id(accounts(0))
+            .attached_deposit(env::storage_byte_cost() * 1334)
+            .build());
+        let pool_id = contract.add_stable_swap_pool(vec![accounts(1), accounts(2)], vec![18, 18], 25, 240);
+        testing_env!(context.block_timestamp(day).attached_deposit(1).build());
+        contract.stable_swap_schedule_ramp_amp(pool_id, vec![
+            AmpRampStep { target_amp: 480, target_time: 2 * day },
+            AmpRampStep { target_amp: 9600, target_time: 3 * day },
+        ]);
+    }
+
+    #[test]
+    fn test_stop_ramp_amp() {
+        let (mut context, mut contract) = setup_contract();
+        let day = 86400 * 1_000_000_000u64;
+        testing_env!(context
+            .predecessor_account_id(accounts(0))
+            .attached_deposit(env::storage_byte_cost() * 1334)
+            .build());
+        let pool_id = contract.add_stable_swap_pool(vec![accounts(1), accounts(2)], vec![18, 18], 25, 240);
+        testing_env!(context.block_timestamp(day).attached_deposit(1).build());
+        contract.extend_guardians(vec![accounts(5)]);
+        contract.stable_swap_ramp_amp(pool_id, 480, (3 * day).into());
+
+        testing_env!(context.predecessor_account_id(accounts(5)).block_timestamp(2 * day).build());
+        contract.stable_swap_stop_ramp_amp(pool_id, Some(true));
+        assert!(contract.is_amp_frozen(pool_id));
+        testing_env!(context.block_timestamp(3 * day).build());
+        assert_eq!(contract.get_pool(pool_id).amp, 360);
+
+        testing_env!(context.predecessor_account_id(accounts(0)).build());
+        contract.resume_ramp_amp(pool_id);
+        assert!(!contract.is_amp_frozen(pool_id));
+        contract.stable_swap_ramp_amp(pool_id, 240, (4 * day).into());
+        testing_env!(context.block_timestamp(4 * day).build());
+        assert_eq!(contract.get_pool(pool_id).amp, 240);
+    }
+
+    #[test]
+    #[should_panic(expected = "Amp of pool 0 is frozen")]
+    fn test_ramp_frozen_amp() {
+        let (mut context, mut contract) = setup_contract();
+        let day = 86400 * 1_000_000_000u64;
+        testing_env!(context
+            .predecessor_account_id(accounts(0))
+            .attached_deposit(env::storage_byte_cost() * 1334)
+            .build());
+        let pool_id = contract.add_stable_swap_pool(vec![accounts(1), accounts(2)], vec![18, 18], 25, 240);
+        testing_env!(context.block_timestamp(day).attached_deposit(1).build());
+        contract.stable_swap_stop_ramp_amp(pool_id, Some(true));
+        contract.stable_swap_ramp_amp(pool_id, 480, (3 * day).into());
+    }
+
+    #[test]
+    #[should_panic(expected = "Degen price of band_a.near out of band")]
+    fn test_degen_price_band() {
+        let (mut context, mut contract) = setup_contract();
+        let token: ValidAccountId = "band_a.near".try_into().unwrap();
+        register_degen_tokens(&mut context, &mut contract, vec![(token.clone(), 150)]);
+        contract.set_degen_price_band(token.clone(), U128(100), U128(200));
+        assert_eq!(contract.get_degen_price_bands()[token.as_ref()].max_price, 200);
+        assert_degen_prices_in_band(&[token.to_string(), accounts(2).to_string()], &[150, 1]);
+        assert_degen_prices_in_band(&[token.to_string(), accounts(2).to_string()], &[201, 1]);
+    }
+
+    #[test]
+    fn test_maker_rebate() {
+        let (mut context, mut contract) = setup_contract();
+        let token_amounts = vec![(accounts(1), to_yocto("5")), (accounts(2), to_yocto("5"))];
+        testing_env!(context.predecessor_account_id(accounts(0)).attached_deposit(1).build());
+        contract.extend_whitelisted_tokens(vec![accounts(1), accounts(2)]);
+        testing_env!(context
+            .predecessor_account_id(accounts(0))
+            .attached_deposit(env::storage_byte_cost() * 1334)
+            .build());
+        let pool_id = contract.add_stable_swap_pool(vec![accounts(1), accounts(2)], vec![18, 18], 25, 240);
+        testing_env!(context
+            .predecessor_account_id(accounts(3))
+            .attached_deposit(to_yocto("0.03"))
+            .build());
+        contract.storage_deposit(None, None);
+        deposit_tokens(&mut context, &mut contract, accounts(3), token_amounts);
+        deposit_tokens(&mut context, &mut contract, accounts(0), vec![(accounts(1), to_yocto("0.1"))]);
+        testing_env!(context
+            .predecessor_account_id(accounts(3))
+            .attached_deposit(to_yocto("0.0007"))
+            .build());
+        contract.add_stable_liquidity(pool_id, vec![to_yocto("4").into(), to_yocto("4").into()], U128(1));
+
+        testing_env!(context.predecessor_account_id(accounts(0)).attached_deposit(1).build());
+        contract.set_pool_maker_rebate(pool_id, Some(MakerRebateConfig { surcharge_bps: 100, rebate_bps: 100 }));
+
+        // quotes include the surcharge and rebate the swap applies
+        let quote = contract.get_return(pool_id, accounts(1), to_yocto("1").into(), accounts(2)).0;
+        testing_env!(context.predecessor_account_id(accounts(3)).attached_deposit(1).build());
+        let amount_out = swap(&mut contract, pool_id, accounts(1), to_yocto("1"), accounts(2));
+        assert_eq!(amount_out, quote);
+        let surcharge = contract.get_pool_maker_rebate_escrow(pool_id)[1].0;
+        assert_eq!(surcharge, (amount_out + surcharge) / 100);
+        assert_eq!(contract.get_stable_pool(pool_id).c_amounts[1].0, to_yocto("4") - amount_out - surcharge);
+
+        // nothing in the escrow to pay the rebate in, it is skipped
+        let quote = contract.get_return(pool_id, accounts(2), to_yocto("0.25").into(), accounts(1)).0;
+        testing_env!(context.predecessor_account_id(accounts(3)).attached_deposit(1).build());
+        let amount_out = swap(&mut contract, pool_id, accounts(2), to_yocto("0.25"), accounts(1));
+        assert_eq!(amount_out, quote);
+        assert_eq!(contract.get_pool_maker_rebate_escrow(pool_id)[0].0, 0);
+
+        testing_env!(context.predecessor_account_id(accounts(0)).attached_deposit(1).build());
+        contract.fund_pool_maker_rebate(pool_id, accounts(1), U128(to_yocto("0.1")));
+        let quote = contract.get_return(pool_id, accounts(2), to_yocto("0.25").into(), accounts(1)).0;
+        testing_env!(context.predecessor_account_id(accounts(3)).attached_deposit(1).build());
+        let amount_out = swap(&mut contract, pool_id, accounts(2), to_yocto("0.25"), accounts(1));
+        assert_eq!(amount_out, quote);
+        let rebate = to_yocto("0.1") - contract.get_pool_maker_rebate_escrow(pool_id)[0].0;
+        assert_eq!(rebate, (amount_out - rebate) / 100);
+    }
+
+    #[test]
+    fn test_wash_trade_netted() {
+        let (mut context, mut contract) = setup_contract();
+        let pool_id = create_pool_with_liquidity(
+            &mut context,
+            &mut contract,
+            accounts(3),
+            vec![(accounts(1), to_yocto("5")), (accounts(2), to_yocto("10"))],
+        );
+        let other_pool_id = create_pool_with_liquidity(
+            &mut context,
+            &mut contract,
+            accounts(3),
+            vec![(accounts(1), to_yocto("5")), (accounts(4), to_yocto("5"))],
+        );
+        deposit_tokens(&mut context, &mut contract, accounts(3), vec![(accounts(1), to_yocto("2"))]);
+        testing_env!(context.predecessor_account_id(accounts(0)).attached_deposit(1).build());
+        contract.set_wash_trade_window(600);
+        testing_env!(context.predecessor_account_id(accounts(3)).attached_deposit(1).build());
+        let amount_out = swap(&mut contract, pool_id, accounts(1), to_yocto("1"), accounts(2));
+        let volumes = contract.get_pool_volumes(pool_id);
+        assert_eq!(volumes[0].input.0, to_yocto("1"));
+        assert_eq!(volumes[0].output.0, amount_out);
+        // a swap in another pool in between doesn't hide the round trip
+        testing_env!(context.predecessor_account_id(accounts(3)).attached_deposit(1).build());
+        swap(&mut contract, other_pool_id, accounts(1), to_yocto("1"), accounts(4));
+
+        testing_env!(context.predecessor_account_id(accounts(3)).attached_deposit(1).build());
+        swap(&mut contract, pool_id, accounts(2), amount_out, accounts(1));
+        let volumes = contract.get_pool_volumes(pool_id);
+        assert_eq!(volumes[0].input.0, 0);
+        assert_eq!(volumes[0].output.0, 0);
+        assert_eq!(volumes[1].input.0, 0);
+        assert_eq!(volumes[1].output.0, 0);
+
+        // outside the window the reverse swap counts as volume
+        testing_env!(context.predecessor_account_id(accounts(3)).attached_deposit(1).build());
+        let amount_out = swap(&mut contract, pool_id, accounts(1), to_yocto("0.5"), accounts(2));
+        testing_env!(context
+            .predecessor_account_id(accounts(3))
+            .block_timestamp(crate::utils::to_nano(601))
+            .attached_deposit(1)
+            .build());
+        swap(&mut contract, pool_id, accounts(2), amount_out, accounts(1));
+        let volumes = contract.get_pool_volumes(pool_id);
+        assert_eq!(volumes[0].input.0, to_yocto("0.5"));
+        assert_eq!(volumes[1].input.0, amount_out);
+
+        // a swap by output reversing it is netted too
+        testing_env!(context.predecessor_account_id(accounts(3)).attached_deposit(1).build());
+        contract.swap_by_output(
+            vec![SwapByOutputAction {
+                pool_id,
+                token_in: accounts(1).into(),
+                amount_out: Some(U128(amount_out)),
+                token_out: accounts(2).into(),
+                max_amount_in: None,
+            }],
+            None,
+        );
+        let volumes = contract.get_pool_volumes(pool_id);
+        assert!(volumes[1].input.0 < amount_out);
+
+        testing_env!(context.block_timestamp(crate::utils::to_nano(1202)).attached_deposit(0).build());
+        assert_eq!(contract.prune_recent_swaps(vec![(accounts(3), pool_id)]), 1);
+        assert!(read_recent_swaps_from_storage().get(&(accounts(3).into(), pool_id)).is_none());
+    }
+
+    #[test]
+    fn test_claim_fees() {
+        let (mut context, mut contract) = setup_contract();
+        let pool_id = create_pool_with_liquidity(
+            &mut context,
+            &mut contract,
+            accounts(3),
+            vec![(accounts(1), to_yocto("5")), (accounts(2), to_yocto("10"))],
+        );
+        testing_env!(context.predecessor_account_id(accounts(0)).attached_deposit(1).build());
+        contract.enable_pool_fee_claims(pool_id);
+        let shares = contract.get_pool_shares(pool_id, accounts(3)).0;
+        let amounts = contract.get_pool(pool_id).amounts;
+
+        deposit_tokens(&mut context, &mut contract, accounts(3), vec![(accounts(1), to_yocto("1"))]);
+        testing_env!(context.predecessor_account_id(accounts(3)).attached_deposit(1).build());
+        let amount_out = swap(&mut contract, pool_id, accounts(1), to_yocto("1"), accounts(2));
+        let unclaimed = contract.get_pool_unclaimed_fees(pool_id);
+        assert!(unclaimed[0].0 > 0);
+        assert_eq!(unclaimed[1].0, 0);
+        // the LP part of the fee does not stay in the pool
+        let pool_amounts = contract.get_pool(pool_id).amounts;
+        assert_eq!(pool_amounts[0].0, amounts[0].0 + to_yocto("1") - unclaimed[0].0);
+        assert_eq!(pool_amounts[1].0, amounts[1].0 - amount_out);
+
+        let claimable = contract.get_claimable_fees(pool_id, accounts(3));
+        assert!(claimable[0].0 > 0 && claimable[0].0 <= unclaimed[0].0);
+        let prev_deposit = contract.get_deposit(accounts(3), accounts(1)).0;
+        testing_env!(context.predecessor_account_id(accounts(3)).attached_deposit(1).build());
+        let claimed = contract.claim_fees(pool_id);
+        assert_eq!(claimed, claimable);
+        assert_eq!(contract.get_deposit(accounts(3), accounts(1)).0, prev_deposit + claimed[0].0);
+        assert_eq!(contract.get_pool_shares(pool_id, accounts(3)).0, shares);
+        assert_eq!(contract.get_claimable_fees(pool_id, accounts(3))[0].0, 0);
+        assert_eq!(contract.get_pool_unclaimed_fees(pool_id)[0].0, unclaimed[0].0 - claimed[0].0);
+    }
+
+    #[test]
+    fn test_lp_referral_rewards() {
+        let (mut context, mut contract) = setup_contract();
+        let pool_id = create_pool_with_liquidity(
+            &mut context,
+            &mut contract,
+            accounts(3),
+            vec![(accounts(1), to_yocto("5")), (accounts(2), to_yocto("10"))],
+        );
+        testing_env!(context.predecessor_account_id(accounts(0)).attached_deposit(1).build());
+        contract.enable_pool_fee_claims(pool_id);
+        contract.insert_referral(accounts(4), 2000);
+        contract.set_lp_referral_fee_bps(2000);
+        deposit_tokens(&mut context, &mut contract, accounts(4), vec![(accounts(1), 1)]);
+
+        deposit_tokens(&mut context, &mut contract, accounts(5), vec![(accounts(1), to_yocto("5")), (accounts(2), to_yocto("10"))]);
+        testing_env!(context.predecessor_account_id(accounts(5)).attached_deposit(to_yocto("0.01")).build());
+        contract.add_liquidity_with_referral(pool_id, vec![U128(to_yocto("5")), U128(to_yocto("10"))], None, accounts(4));
+        assert_eq!(contract.get_lp_referrer(pool_id, accounts(5)), Some(accounts(4).into()));
+        // the creator was not referred
+        assert_eq!(contract.get_lp_referrer(pool_id, accounts(3)), None);
+        // the rate is fixed when the referrer is recorded
+        testing_env!(context.predecessor_account_id(accounts(0)).attached_deposit(1).build());
+        contract.set_lp_referral_fee_bps(0);
+
+        deposit_tokens(&mut context, &mut contract, accounts(3), vec![(accounts(1), to_yocto("1"))]);
+        testing_env!(context.predecessor_account_id(accounts(3)).attached_deposit(1).build());
+        swap(&mut contract, pool_id, accounts(1), to_yocto("1"), accounts(2));
+
+        let exchange_id: ValidAccountId = env::current_account_id().try_into().unwrap();
+        let exchange_claimable = contract.get_claimable_fees(pool_id, exchange_id.clone());
+        let claimable = contract.get_claimable_fees(pool_id, accounts(5));
+        testing_env!(context.predecessor_account_id(accounts(5)).attached_deposit(1).build());
+        // the referred LP keeps all of its fees, the cut comes out of the exchange's ones
+        let claimed = contract.claim_fees(pool_id);
+        assert_eq!(claimed, claimable);
+        let rewards = contract.get_lp_referral_rewards(pool_id, accounts(4));
+        assert!(rewards[0].0 > 0);
+        assert_eq!(rewards[0].0, std::cmp::min(claimed[0].0 * 2000 / 10000, exchange_claimable[0].0));
+        assert_eq!(contract.get_claimable_fees(pool_id, exchange_id)[0].0, exchange_claimable[0].0 - rewards[0].0);
+
+        let unclaimed = contract.get_pool_unclaimed_fees(pool_id);
+        testing_env!(context.predecessor_account_id(accounts(4)).attached_deposit(1).build());
+        assert_eq!(contract.claim_lp_referral_rewards(pool_id), rewards);
+        assert_eq!(contract.get_deposit(accounts(4), accounts(1)).0, 1 + rewards[0].0);
+        assert_eq!(contract.get_pool_unclaimed_fees(pool_id)[0].0, unclaimed[0].0 - rewards[0].0);
+        assert_eq!(contract.get_lp_referral_rewards(pool_id, accounts(4))[0].0, 0);
+    }
+
+    #[test]
+    fn test_lock_bonus() {
+        let (mut context, mut contract) = setup_contract();
+        let pool_id = create_pool_with_liquidity(
+            &mut context,
+            &mut contract,
+            accounts(3),
+            vec![(accounts(1), to_yocto("5")), (accounts(2), to_yocto("10"))],
+        );
+        let day = 86400 * 1_000_000_000u64;
+        let week = 7 * day;
+        testing_env!(context.predecessor_account_id(accounts(0)).attached_deposit(1).build());
+        contract.enable_pool_fee_claims(pool_id);
+        contract.set_pool_lock_bonus(pool_id, 2000);
+        let shares = contract.get_pool_shares(pool_id, accounts(3)).0;
+        testing_env!(context.predecessor_account_id(accounts(3)).attached_deposit(to_yocto("0.01")).build());
+        let lock_id = contract.lock_shares(pool_id, U128(shares / 2), near_sdk::json_types::U64(week + day), None);
+        assert_eq!(contract.get_pool_lock_bonus(pool_id).unwrap().locked_weight.0, shares / 2);
+        // a lock released within the current expiry bucket takes no part
+        let short_lock_id = contract.lock_shares(pool_id, U128(shares / 4), near_sdk::json_types::U64(day), None);
+        assert!(contract.get_claimable_lock_bonus(short_lock_id).is_empty());
+        assert_eq!(contract.get_pool_lock_bonus(pool_id).unwrap().locked_weight.0, shares / 2);
+
+        deposit_tokens(&mut context, &mut contract, accounts(3), vec![(accounts(1), to_yocto("2"))]);
+        testing_env!(context.predecessor_account_id(accounts(3)).attached_deposit(1).build());
+        swap(&mut contract, pool_id, accounts(1), to_yocto("1"), accounts(2));
+        let bonus = contract.get_claimable_lock_bonus(lock_id);
+        assert!(bonus[0].0 > 0);
+        // the bonus is a fifth of the LP fee, the rest is shared among all shares
+        let claimable = contract.get_claimable_fees(pool_id, accounts(3));
+        assert!(claimable[0].0 > bonus[0].0 * 3);
+        let prev_deposit = contract.get_deposit(accounts(3), accounts(1)).0;
+        assert_eq!(contract.claim_lock_bonus(lock_id), bonus);
+        assert_eq!(contract.get_deposit(accounts(3), accounts(1)).0, prev_deposit + bonus[0].0);
+
+        // the lock stops earning from the start of its expiry bucket
+        testing_env!(context.predecessor_account_id(accounts(3)).block_timestamp(week).attached_deposit(1).build());
+        swap(&mut contract, pool_id, accounts(1), to_yocto("1"), accounts(2));
+        assert_eq!(contract.get_claimable_lock_bonus(lock_id)[0].0, 0);
+        assert_eq!(contract.get_pool_lock_bonus(pool_id).unwrap().locked_weight.0, 0);
+        contract.claim_lock_bonus(lock_id);
+        assert!(contract.get_claimable_lock_bonus(lock_id).is_empty());
+    }
+
+    #[test]
+    fn test_lock_bonus_weight() {
+        let (mut context, mut contract) = setup_contract();
+        let pool_id = create_pool_with_liquidity(
+            &mut context,
+            &mut contract,
+            accounts(3),
+            vec![(accounts(1), to_yocto("5")), (accounts(2), to_yocto("10"))],
+        );
+        let week = 7 * 86400 * 1_000_000_000u64;
+        testing_env!(context.predecessor_account_id(accounts(0)).attached_deposit(1).build());
+        contract.enable_pool_fee_claims(pool_id);
+        contract.set_pool_lock_bonus(pool_id, 2000);
+        let shares = contract.get_pool_shares(pool_id, accounts(3)).0;
+        testing_env!(context.predecessor_account_id(accounts(3)).attached_deposit(to_yocto("0.01")).build());
+        // dust locks take no part
+        let dust_lock_id = contract.lock_shares(pool_id, U128(1), near_sdk::json_types::U64(10 * week), None);
+        assert!(contract.get_claimable_lock_bonus(dust_lock_id).is_empty());
+        let short_lock_id = contract.lock_shares(pool_id, U128(shares / 4), near_sdk::json_types::U64(week), None);
+        let long_lock_id = contract.lock_shares(pool_id, U128(shares / 4), near_sdk::json_types::U64(4 * week), None);
+        assert_eq!(contract.get_pool_lock_bonus(pool_id).unwrap().locked_weight.0, shares / 4 * 5);
+
+        deposit_tokens(&mut context, &mut contract, accounts(3), vec![(accounts(1), to_yocto("1"))]);
+        testing_env!(context.predecessor_account_id(accounts(3)).attached_deposit(1).build());
+        swap(&mut contract, pool_id, accounts(1), to_yocto("1"), accounts(2));
+        let short_bonus = contract.get_claimable_lock_bonus(short_lock_id)[0].0;
+        let long_bonus = contract.get_claimable_lock_bonus(long_lock_id)[0].0;
+        assert!(short_bonus > 0);
+        assert!(long_bonus >= short_bonus * 4 && long_bonus <= short_bonus * 4 + 4);
+    }
+
+    #[test]
+    fn test_share_lock_vesting() {
+        let (mut context, mut contract) = setup_contract();
+        let pool_id = create_pool_with_liquidity(
+            &mut context,
+            &mut contract,
+            accounts(3),
+            vec![(accounts(1), to_yocto("5")), (accounts(2), to_yocto("10"))],
+        );
+        let shares = contract.get_pool_shares(pool_id, accounts(3)).0;
+        let day = 86400 * 1_000_000_000u64;
+        testing_env!(context
+            .predecessor_account_id(accounts(3))
+            .block_timestamp(day)
+            .attached_deposit(to_yocto("0.01"))
+            .build());
+        let lock_id = contract.lock_shares(pool_id, U128(shares / 2), near_sdk::json_types::U64(3 * day), Some(near_sdk::json_types::U64(day)));
+        assert_eq!(contract.get_locked_shares(accounts(3), pool_id).0, shares / 2);
+        assert_eq!(contract.get_share_locks(accounts(3), Some(pool_id))[0].lock_id, lock_id);
+
+        // halfway through vesting half of the lock is released
+        testing_env!(context.block_timestamp(2 * day).attached_deposit(1).build());
+        assert_eq!(contract.get_locked_shares(accounts(3), pool_id).0, shares / 4);
+        contract.remove_liquidity(pool_id, U128(shares - shares / 4), vec![1.into(), 1.into()]);
+        assert_eq!(contract.get_pool_shares(pool_id, accounts(3)).0, shares / 4);
+
+        testing_env!(context.block_timestamp(3 * day).attached_deposit(1).build());
+        assert_eq!(contract.get_locked_shares(accounts(3), pool_id).0, 0);
+        contract.remove_liquidity(pool_id, U128(shares / 4), vec![1.into(), 1.into()]);
+    }
+
+    #[test]
+    #[should_panic(expected = "Not enough unlocked shares")]
+    fn test_share_lock_blocks_transfer() {
+        let (mut context, mut contract) = setup_contract();
+        let pool_id = create_pool_with_liquidity(
+            &mut context,
+            &mut contract,
+            accounts(3),
+            vec![(accounts(1), to_yocto("5")), (accounts(2), to_yocto("10"))],
+        );
+        let shares = contract.get_pool_shares(pool_id, accounts(3)).0;
+        testing_env!(context
+            .predecessor_account_id(accounts(3))
+            .attached_deposit(to_yocto("0.01"))
+            .build());
+        contract.lock_shares(pool_id, U128(shares / 2), near_sdk::json_types::U64(86400 * 1_000_000_000), None);
+        testing_env!(context.attached_deposit(1).build());
+        contract.mft_transfer(format!(":{}", pool_id), accounts(4), U128(shares / 2 + 1), None);
+    }
+
+    #[test]
+    fn test_share_lock_nft_transfer() {
+        let (mut context, mut contract) = setup_contract();
+        let pool_id = create_pool_with_liquidity(
+            &mut context,
+            &mut contract,
+            accounts(3),
+            vec![(accounts(1), to_yocto("5")), (accounts(2), to_yocto("10"))],
+        );
+        let shares = contract.get_pool_shares(pool_id, accounts(3)).0;
+        let day = 86400 * 1_000_000_000u64;
+        testing_env!(context
+            .predecessor_account_id(accounts(3))
+            .attached_deposit(to_yocto("0.01"))
+            .build());
+        let lock_id = contract.lock_shares(pool_id, U128(shares / 2), near_sdk::json_types::U64(2 * day), Some(near_sdk::json_types::U64(0)));
+        contract.mft_register(format!(":{}", pool_id), accounts(4));
+
+        // half of the lock has vested, only the still locked half travels with the token
+        testing_env!(context.block_timestamp(day).attached_deposit(1).build());
+        contract.nft_transfer(accounts(4), lock_id.to_string(), None, None);
+        assert_eq!(contract.get_pool_shares(pool_id, accounts(3)).0, shares - shares / 4);
+        assert_eq!(contract.get_pool_shares(pool_id, accounts(4)).0, shares / 4);
+        assert_eq!(contract.nft_token(lock_id.to_string()).unwrap().owner_id, accounts(4).to_string());
+        assert_eq!(contract.get_locked_shares(accounts(3), pool_id).0, 0);
+        assert_eq!(contract.get_locked_shares(accounts(4), pool_id).0, shares / 4);
+        assert_eq!(contract.nft_supply_for_owner(accounts(3)).0, 0);
+        assert_eq!(contract.nft_tokens_for_owner(accounts(4), None, None).len(), 1);
+
+        testing_env!(context.block_timestamp(day + day / 2).build());
+        assert_eq!(contract.get_locked_shares(accounts(4), pool_id).0, shares / 8);
+        assert_eq!(contract.nft_total_supply().0, 1);
+        assert_eq!(contract.nft_tokens(None, None)[0].token_id, lock_id.to_string());
+
+        // a new lock burns the released one
+        testing_env!(context
+            .predecessor_account_id(accounts(4))
+            .block_timestamp(3 * day)
+            .attached_deposit(to_yocto("0.01"))
+            .build());
+        let new_lock_id = contract.lock_shares(pool_id, U128(shares / 8), near_sdk::json_types::U64(4 * day), None);
+        assert_eq!(contract.nft_total_supply().0, 1);
+        assert_eq!(contract.nft_tokens(Some(U128(0)), Some(10))[0].token_id, new_lock_id.to_string());
+        assert!(contract.nft_tokens(Some(U128(1)), None).is_empty());
+    }
+
+    #[test]
+    fn test_share_stream() {
+        let (mut context, mut contract) = setup_contract();
+        let pool_id = create_pool_with_liquidity(
+            &mut context,
+            &mut contract,
+            accounts(3),
+            vec![(accounts(1), to_yocto("5")), (accounts(2), to_yocto("10"))],
+        );
+        let shares = contract.get_pool_shares(pool_id, accounts(3)).0;
+        testing_env!(context
+            .predecessor_account_id(accounts(3))
+            .attached_deposit(to_yocto("0.01"))
+            .build());
+        contract.mft_register(format!(":{}", pool_id), accounts(4));
+        let stream_id = contract.share_stream(pool_id, accounts(4), U128(shares / 2), 100);
+        assert_eq!(contract.get_locked_shares(accounts(3), pool_id).0, shares / 2);
+
+        testing_env!(context.block_timestamp(50 * 10u64.pow(9)).attached_deposit(1).build());
+        assert_eq!(contract.withdraw_share_stream(stream_id).0, shares / 4);
+        assert_eq!(contract.get_pool_shares(pool_id, accounts(4)).0, shares / 4);
+        assert_eq!(contract.get_locked_shares(accounts(3), pool_id).0, shares / 2 - shares / 4);
+
+        testing_env!(context.block_timestamp(75 * 10u64.pow(9)).build());
+        assert_eq!(contract.cancel_share_stream(stream_id).0, shares / 8);
+        assert_eq!(contract.get_pool_shares(pool_id, accounts(4)).0, shares / 4 + shares / 8);
+        assert_eq!(contract.get_locked_shares(accounts(3), pool_id).0, 0);
+        assert!(contract.get_share_stream(stream_id).is_none());
+        assert!(contract.get_share_streams(accounts(4)).is_empty());
+    }
+
+    #[test]
+    fn test_share_stream_receiver_left() {
+        let (mut context, mut contract) = setup_contract();
+        let pool_id = create_pool_with_liquidity(
+            &mut context,
+            &mut contract,
+            accounts(3),
+            vec![(accounts(1), to_yocto("5")), (accounts(2), to_yocto("10"))],
+        );
+        let shares = contract.get_pool_shares(pool_id, accounts(3)).0;
+        testing_env!(context
+            .predecessor_account_id(accounts(3))
+            .attached_deposit(to_yocto("0.01"))
+            .build());
+        contract.mft_register(format!(":{}", pool_id), accounts(4));
+        let stream_id = contract.share_stream(pool_id, accounts(4), U128(shares / 2), 100);
+        testing_env!(context.predecessor_account_id(accounts(4)).attached_deposit(1).build());
+        contract.mft_unregister(format!(":{}", pool_id));
+
+        // the unpaid shares stay with the sender
+        testing_env!(context
+            .predecessor_account_id(accounts(3))
+            .block_timestamp(50 * 10u64.pow(9))
+            .build());
+        assert_eq!(contract.withdraw_share_stream(stream_id).0, 0);
+        assert!(contract.get_share_stream(stream_id).is_none());
+        assert_eq!(contract.get_pool_shares(pool_id, accounts(3)).0, shares);
+        assert_eq!(contract.get_locked_shares(accounts(3), pool_id).0, 0);
+    }
+
+    #[test]
+    fn test_share_stream_incoming_not_capped() {
+        let (mut context, mut contract) = setup_contract();
+        let pool_id = create_pool_with_liquidity(
+            &mut context,
+            &mut contract,
+            accounts(3),
+            vec![(accounts(1), to_yocto("5")), (accounts(2), to_yocto("10"))],
+        );
+        testing_env!(context
+            .predecessor_account_id(accounts(3))
+            .attached_deposit(to_yocto("0.1"))
+            .build());
+        contract.mft_register(format!(":{}", pool_id), accounts(4));
+        let stream_ids: Vec<u64> = (0..MAX_SHARE_STREAMS_PER_ACCOUNT)
+            .map(|_| contract.share_stream(pool_id, accounts(4), U128(1), 100))
+            .collect();
+
+        // streams received don't use up the receiver's own limit
+        testing_env!(context
+            .predecessor_account_id(accounts(4))
+            .block_timestamp(100 * 10u64.pow(9))
+            .attached_deposit(1)
+            .build());
+        contract.withdraw_share_stream(stream_ids[0]);
+        testing_env!(context.attached_deposit(to_yocto("0.01")).build());
+        contract.share_stream(pool_id, accounts(3), U128(1), 100);
+
+        // and the receiver can decline them
+        testing_env!(context.attached_deposit(1).build());
+        contract.cancel_share_stream(stream_ids[1]);
+        assert_eq!(contract.get_share_streams(accounts(4)).len(), MAX_SHARE_STREAMS_PER_ACCOUNT - 1);
+    }
+
+    #[test]
+    fn test_fee_rebate() {
+        let (mut context, mut contract) = setup_contract();
+        let pool_id = create_pool_with_liquidity(
+            &mut context,
+            &mut contract,
+            accounts(3),
+            vec![(accounts(1), to_yocto("5")), (accounts(2), to_yocto("10"))],
+        );
+        deposit_tokens(&mut context, &mut contract, accounts(0), vec![(accounts(2), to_yocto("1"))]);
+        testing_env!(context.predecessor_account_id(accounts(0)).attached_deposit(1).build());
+        let mut token_rates = HashMap::new();
+        token_rates.insert(accounts(1).to_string(), U128(2 * FEE_REBATE_RATE_PRECISION));
+        contract.set_fee_rebate_config(Some(FeeRebateConfig {
+            reward_token: accounts(2).to_string(),
+            rebate_bps: 5000,
+            token_rates,
+        }));
+        contract.fund_fee_rebate_pot(U128(to_yocto("1")));
+        assert_eq!(contract.get_deposit(accounts(0), accounts(2)).0, 0);
+
+        deposit_tokens(&mut context, &mut contract, accounts(3), vec![(accounts(1), to_yocto("1"))]);
+        testing_env!(context.predecessor_account_id(accounts(3)).attached_deposit(1).build());
+        swap(&mut contract, pool_id, accounts(1), to_yocto("1"), accounts(2));
+        // the pending rebate takes the trader's storage
+        let storage_available = contract.storage_balance_of(accounts(3)).unwrap().available.0;
+        // half the fee, valued at 2 reward tokens per swapped in token
+        let rebate = to_yocto("1") * contract.get_pool(pool_id).total_fee as u128 / 10_000;
+        assert_eq!(contract.get_pending_fee_rebates(accounts(3))[&accounts(2).to_string()].0, rebate);
+        assert_eq!(contract.get_fee_rebate_pot().0, to_yocto("1") - rebate);
+
+        let prev_deposit = contract.get_deposit(accounts(3), accounts(2)).0;
+        contract.claim_fee_rebates();
+        assert_eq!(contract.get_deposit(accounts(3), accounts(2)).0, prev_deposit + rebate);
+        assert!(contract.get_pending_fee_rebates(accounts(3)).is_empty());
+        assert!(contract.storage_balance_of(accounts(3)).unwrap().available.0 > storage_available);
+    }
+
+    #[test]
+    fn test_fee_rebate_instant_swap() {
+        let (mut context, mut contract) = setup_contract();
+        let pool_id = create_pool_with_liquidity(
+            &mut context,
+            &mut contract,
+            accounts(3),
+            vec![(accounts(1), to_yocto("5")), (accounts(2), to_yocto("10"))],
+        );
+        deposit_tokens(&mut context, &mut contract, accounts(0), vec![(accounts(2), to_yocto("1"))]);
+        testing_env!(context.predecessor_account_id(accounts(0)).attached_deposit(1).build());
+        let mut token_rates = HashMap::new();
+        token_rates.insert(accounts(1).to_string(), U128(2 * FEE_REBATE_RATE_PRECISION));
+        contract.set_fee_rebate_config(Some(FeeRebateConfig {
+            reward_token: accounts(2).to_string(),
+            rebate_bps: 5000,
+            token_rates,
+        }));
+        contract.fund_fee_rebate_pot(U128(to_yocto("1")));
+
+        // an instant swap has no storage balance to book the rebate with
+        testing_env!(context.predecessor_account_id(accounts(1)).attached_deposit(1).build());
+        contract.ft_on_transfer(
+            accounts(4),
+            U128(to_yocto("1")),
+            format!("{{\"actions\": [{{\"pool_id\": {}, \"token_in\": \"{}\", \"token_out\": \"{}\", \"min_amount_out\": \"1\"}}]}}", pool_id, accounts(1), accounts(2)),
+        );
+        assert!(contract.get_pending_fee_rebates(accounts(4)).is_empty());
+        assert_eq!(contract.get_fee_rebate_pot().0, to_yocto("1"));
+    }
+
+    #[test]
+    fn test_trade_history_ring_buffer() {
+        let (mut context, mut contract) = setup_contract();
+        let pool_id = create_pool_with_liquidity(
+            &mut context,
+            &mut contract,
+            accounts(3),
+            vec![(accounts(1), to_yocto("5")), (accounts(2), to_yocto("10"))],
+        );
+        deposit_tokens(&mut context, &mut contract, accounts(3), vec![(accounts(1), to_yocto("3"))]);
+        testing_env!(context.predecessor_account_id(accounts(3)).attached_deposit(to_yocto("1")).build());
+        assert!(contract.get_trade_history(accounts(3)).is_none());
+        contract.enable_trade_history(2);
+
+        testing_env!(context.attached_deposit(1).build());
+        for i in 1..=3 {
+            testing_env!(context.block_timestamp(i).build());
+            swap(&mut contract, pool_id, accounts(1), to_yocto("1"), accounts(2));
+        }
+        let history = contract.get_trade_history(accounts(3)).unwrap();
+        assert_eq!(history.len(), 2);
+        assert_eq!(history[0].timestamp, 2);
+        assert_eq!(history[1].timestamp, 3);
+        assert_eq!(history[1].amount_in, to_yocto("1"));
+
+        contract.disable_trade_history();
+        assert!(contract.get_trade_history(accounts(3)).is_none());
+    }
+
+    #[test]
+    fn test_admin_fee_token_mode() {
+        let (mut context, mut contract) = setup_contract();
+        let pool_id = create_pool_with_liquidity(
+            &mut context,
+            &mut contract,
+            accounts(3),
+            vec![(accounts(1), to_yocto("5")), (accounts(2), to_yocto("10"))],
+        );
+        deposit_tokens(&mut context, &mut contract, accounts(0), vec![(accounts(1), 1)]);
+        testing_env!(context.predecessor_account_id(accounts(0)).attached_deposit(1).build());
+        contract.set_pool_admin_fee_mode(pool_id, AdminFeeMode::Tokens);
+        assert_eq!(contract.get_pool_admin_fee_mode(pool_id), AdminFeeMode::Tokens);
+        let exchange_shares = contract.get_pool_shares(pool_id, env::current_account_id().try_into().unwrap()).0;
+
+        deposit_tokens(&mut context, &mut contract, accounts(3), vec![(accounts(1), to_yocto("1"))]);
+        testing_env!(context.predecessor_account_id(accounts(3)).attached_deposit(1).build());
+        swap(&mut contract, pool_id, accounts(1), to_yocto("1"), accounts(2));
+        assert_eq!(contract.get_pool_shares(pool_id, env::current_account_id().try_into().unwrap()).0, exchange_shares);
+        let accrued = contract.get_pool_admin_fee_tokens(pool_id);
+        assert!(accrued[0].0 > 0 && accrued[1].0 > 0);
+
+        testing_env!(context.predecessor_account_id(accounts(0)).attached_deposit(1).build());
+        assert_eq!(contract.claim_admin_fee_tokens(pool_id), accrued);
+        assert_eq!(contract.get_deposit(accounts(0), accounts(1)).0, 1 + accrued[0].0);
+        assert_eq!(contract.get_deposit(accounts(0), accounts(2)).0, accrued[1].0);
+        assert_eq!(contract.get_pool_admin_fee_tokens(pool_id), vec![U128(0), U128(0)]);
+
+        contract.set_pool_admin_fee_mode(pool_id, AdminFeeMode::Shares);
+        assert!(contract.get_pool_admin_fee_tokens(pool_id).is_empty());
+    }
+
+    #[test]
+    fn test_token_accounting() {
+        let (mut context, mut contract) = setup_contract();
+        let pool_id = create_pool_with_liquidity(
+            &mut context,
+            &mut contract,
+            accounts(3),
+            vec![(accounts(1), to_yocto("5")), (accounts(2), to_yocto("10"))],
+        );
+        deposit_tokens(&mut context, &mut contract, accounts(3), vec![(accounts(1), to_yocto("2"))]);
+        testing_env!(context.predecessor_account_id(accounts(3)).attached_deposit(1).build());
+        swap(&mut contract, pool_id, accounts(1), to_yocto("1"), accounts(2));
+        contract.withdraw(accounts(2), U128(to_yocto("0.5")), None, None);
+
+        let reports = contract.get_token_accounting(vec![accounts(1), accounts(2)], None, None);
+        for report in reports.iter() {
+            assert_eq!(report.drift.as_ref().map(|drift| drift.0), Some(0));
+        }
+        assert_eq!(reports[1].pending_withdrawals.0, to_yocto("0.5"));
+        assert_eq!(reports[0].pool_reserves.0, contract.get_pool(pool_id).amounts[0].0);
+        assert!(contract.get_token_accounting(vec![accounts(1)], Some(0), Some(0))[0].drift.is_none());
+    }
+
+    #[test]
+    fn test_token_accounting_escrows() {
+        let (mut context, mut contract) = setup_contract();
+        let (_, order_id) = setup_twap_order(&mut context, &mut contract);
+        let report = contract.get_token_accounting(vec![accounts(1)], None, None).remove(0);
+        assert_eq!(report.off_pool_reserves.0, to_yocto("1"));
+        assert_eq!(report.drift.map(|drift| drift.0), Some(0));
+
+        testing_env!(context.predecessor_account_id(accounts(3)).attached_deposit(1).build());
+        contract.cancel_twap_order(order_id);
+        let report = contract.get_token_accounting(vec![accounts(1)], None, None).remove(0);
+        assert_eq!(report.off_pool_reserves.0, 0);
+        assert_eq!(report.drift.map(|drift| drift.0), Some(0));
+    }
+
+    #[test]
+    fn test_audit_log() {
+        let (mut context, mut contract) = setup_contract();
+        testing_env!(context.predecessor_account_id(accounts(0)).attached_deposit(1).build());
+        contract.extend_guardians(vec![accounts(1)]);
+        testing_env!(context.predecessor_account_id(accounts(1)).block_timestamp(7).attached_deposit(1).build());
+        contract.change_state(RunningState::Paused);
+
+        let entries = contract.get_audit_log(None, None);
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].entry.actor_id, accounts(0).to_string());
+        assert_eq!(entries[0].entry.action, "extend_guardians");
+        assert_eq!(entries[1].index, 1);
+        assert_eq!(entries[1].entry.action, "change_state");
+        assert_eq!(entries[1].entry.timestamp, 7);
+        assert_eq!(contract.get_audit_log(Some(1), Some(5)).len(), 1);
+
+        for _ in 0..MAX_AUDIT_LOG_LEN {
+            testing_env!(context.predecessor_account_id(accounts(1)).attached_deposit(1).build());
+            contract.change_state(RunningState::Paused);
+        }
+        assert_eq!(contract.get_audit_log_len(), MAX_AUDIT_LOG_LEN + 2);
+        let entries = contract.get_audit_log(Some(0), Some(1));
+        assert_eq!(entries[0].index, 2);
+        assert_eq!(contract.get_audit_log(None, None).len() as u64, MAX_AUDIT_LOG_LEN);
+    }
+
+    #[test]
+    fn test_dao_execute() {
+        let (mut context, mut contract) = setup_contract();
+        testing_env!(context.predecessor_account_id(accounts(0)).attached_deposit(1).build());
+        contract.set_dao_id(Some(accounts(4)));
+        testing_env!(context.predecessor_account_id(accounts(4)).attached_deposit(1).build());
+        contract.dao_execute(1, DaoAction::ChangeState { state: RunningState::Paused });
+        assert_eq!(contract.metadata().state, RunningState::Paused);
+        contract.dao_execute(2, DaoAction::ModifyAdminFee { admin_fee_bps: 100 });
+        assert_eq!(contract.metadata().admin_fee_bps, 100);
+        assert_eq!(contract.get_audit_log(Some(2), None)[0].entry.action, "dao_execute");
+    }
+
+    #[test]
+    #[should_panic(expected = "E100: no permission to invoke this")]
+    fn test_dao_execute_not_dao() {
+        let (mut context, mut contract) = setup_contract();
+        testing_env!(context.predecessor_account_id(accounts(0)).attached_deposit(1).build());
+        contract.dao_execute(1, DaoAction::ModifyAdminFee { admin_fee_bps: 100 });
+    }
+
+    #[test]
+    fn test_lp_voting() {
+        let (mut context, mut contract) = setup_contract();
+        let pool_id = create_pool_with_liquidity(
+            &mut context,
+            &mut contract,
+            accounts(3),
+            vec![(accounts(1), to_yocto("5")), (accounts(2), to_yocto("10"))],
+        );
+        let shares = contract.get_pool_shares(pool_id, accounts(3)).0;
+        testing_env!(context
+            .predecessor_account_id(accounts(4))
+            .attached_deposit(to_yocto("0.00071"))
+            .build());
+        contract.mft_register(format!(":{}", pool_id), accounts(4));
+        testing_env!(context.predecessor_account_id(accounts(3)).attached_deposit(1).build());
+        contract.mft_transfer(format!(":{}", pool_id), accounts(4), U128(shares * 6 / 10), None);
+
+        testing_env!(context.attached_deposit(to_yocto("0.01")).build());
+        let proposal_id = contract.propose_pool_param_change(pool_id, PoolParamChange::TotalFee { total_fee: 40 });
+        let info = contract.get_lp_proposal(proposal_id).unwrap();
+        assert!(info.is_open);
+        assert_eq!(info.proposal.yes_shares, shares - shares * 6 / 10);
+        assert_eq!(contract.get_locked_shares(accounts(3), pool_id).0, shares - shares * 6 / 10);
+        assert_eq!(contract.get_pool(pool_id).total_fee, 25);
+
+        testing_env!(context.predecessor_account_id(accounts(4)).attached_deposit(to_yocto("0.01")).build());
+        contract.vote_pool_param_change(proposal_id, true);
+        let info = contract.get_lp_proposal(proposal_id).unwrap();
+        assert!(info.is_open);
+        assert!(!info.proposal.executed);
+        assert_eq!(contract.get_pool(pool_id).total_fee, 25);
+
+        testing_env!(context
+            .block_timestamp(info.proposal.end_time)
+            .attached_deposit(0)
+            .build());
+        contract.execute_lp_proposal(proposal_id);
+        assert!(contract.get_lp_proposal(proposal_id).unwrap().proposal.executed);
+        assert_eq!(contract.get_pool(pool_id).total_fee, 40);
+        assert_eq!(contract.get_locked_shares(accounts(3), pool_id).0, 0);
+        assert!(contract.get_lp_votes(accounts(4)).is_empty());
+    }
+
+    #[test]
+    #[should_panic(expected = "No shares to vote with")]
+    fn test_lp_voting_shares_after_proposal() {
+        let (mut context, mut contract) = setup_contract();
+        let pool_id = create_pool_with_liquidity(
+            &mut context,
+            &mut contract,
+            accounts(3),
+            vec![(accounts(1), to_yocto("5")), (accounts(2), to_yocto("10"))],
+        );
+        testing_env!(context.predecessor_account_id(accounts(3)).attached_deposit(to_yocto("0.01")).build());
+        let proposal_id = contract.propose_pool_param_change(pool_id, PoolParamChange::TotalFee { total_fee: 40 });
+
+        // shares added after the proposal don't count
+        deposit_tokens(&mut context, &mut contract, accounts(4), vec![(accounts(1), to_yocto("50")), (accounts(2), to_yocto("100"))]);
+        testing_env!(context.predecessor_account_id(accounts(4)).attached_deposit(to_yocto("0.0007")).build());
+        contract.add_liquidity(pool_id, vec![U128(to_yocto("50")), U128(to_yocto("100"))], None);
+        testing_env!(context.predecessor_account_id(accounts(4)).attached_deposit(to_yocto("0.01")).build());
+        contract.vote_pool_param_change(proposal_id, true);
+    }
+
+    #[test]
+    #[should_panic(expected = "E62: illegal fee")]
+    fn test_lp_voting_fee_out_of_band() {
+        let (mut context, mut contract) = setup_contract();
+        let pool_id = create_pool_with_liquidity(
+            &mut context,
+            &mut contract,
+            accounts(3),
+            vec![(accounts(1), to_yocto("5")), (accounts(2), to_yocto("10"))],
+        );
+        testing_env!(context.predecessor_account_id(accounts(3)).attached_deposit(to_yocto("0.01")).build());
+        contract.propose_pool_param_change(pool_id, PoolParamChange::TotalFee { total_fee: 51 });
+    }
+
+    #[test]
+    fn test_degen_listing() {
+        let (mut context, mut contract) = setup_contract();
+        testing_env!(context.predecessor_account_id(accounts(0)).attached_deposit(1).build());
+        contract.extend_guardians(vec![accounts(1)]);
+        testing_env!(context.predecessor_account_id(accounts(3)).attached_deposit(DEGEN_LISTING_BOND).build());
+        contract.propose_degen_token(accounts(4), DegenType::PriceOracle { decimals: 18 });
+        contract.propose_degen_token(accounts(5), DegenType::PriceOracle { decimals: 24 });
+        assert_eq!(contract.get_degen_listings(None, None).len(), 2);
+        assert_eq!(contract.get_degen_listing(accounts(4)).unwrap().proposer_id, accounts(3).to_string());
+
+        testing_env!(context.predecessor_account_id(accounts(1)).attached_deposit(1).build());
+        contract.approve_degen_listing(accounts(4));
+        contract.reject_degen_listing(accounts(5));
+        assert!(contract.get_degen_listings(None, None).is_empty());
+        let degens = degen_swap::degen::read_degens_from_storage();
+        assert!(degens.contains_key(&accounts(4).to_string()));
+        assert!(!degens.contains_key(&accounts(5).to_string()));
+    }
+
+    #[test]
+    #[should_panic(expected = "Degen listing review expired")]
+    fn test_degen_listing_expired() {
+        let (mut context, mut contract) = setup_contract();
+        testing_env!(context.predecessor_account_id(accounts(3)).attached_deposit(DEGEN_LISTING_BOND).build());
+        contract.propose_degen_token(accounts(4), DegenType::PriceOracle { decimals: 18 });
+        testing_env!(context
+            .predecessor_account_id(accounts(0))
+            .block_timestamp(DEGEN_LISTING_REVIEW_SEC as u64 * 1_000_000_000)
+            .attached_deposit(1)
+            .build());
+        contract.reject_degen_listing(accounts(4));
+    }
+
+    #[test]
+    fn test_convert_admin_fees() {
+        let (mut context, mut contract) = setup_contract();
+        let pool_id = create_pool_with_liquidity(
+            &mut context,
+            &mut contract,
+            accounts(3),
+            vec![(accounts(1), to_yocto("5")), (accounts(2), to_yocto("10"))],
+        );
+        deposit_tokens(&mut context, &mut contract, accounts(3), vec![(accounts(1), to_yocto("2")), (accounts(2), to_yocto("2"))]);
+        testing_env!(context.predecessor_account_id(accounts(3)).attached_deposit(1).build());
+        swap(&mut contract, pool_id, accounts(1), to_yocto("1"), accounts(2));
+        swap(&mut contract, pool_id, accounts(2), to_yocto("1"), accounts(1));
+        let ex_id = env::current_account_id();
+        assert!(contract.get_pool_shares(pool_id, ValidAccountId::try_from(ex_id).unwrap()).0 > 0);
+        deposit_tokens(&mut context, &mut contract, accounts(0), vec![]);
+
+        testing_env!(context.predecessor_account_id(accounts(0)).attached_deposit(1).build());
+        let amount_out = contract.convert_admin_fees(vec![pool_id], accounts(2), U128(1)).0;
+        assert!(amount_out > 0);
+        assert_eq!(contract.get_deposit(accounts(0), accounts(2)).0, amount_out);
+        assert_eq!(contract.get_deposit(accounts(0), accounts(1)).0, 0);
+    }
+
+    #[test]
+    #[should_panic(expected = "E100: no permission to invoke this")]
+    fn test_convert_admin_fees_not_allowed() {
+        let (mut context, mut contract) = setup_contract();
+        let pool_id = create_pool_with_liquidity(
+            &mut context,
+            &mut contract,
+            accounts(3),
+            vec![(accounts(1), to_yocto("5")), (accounts(2), to_yocto("10"))],
+        );
+        testing_env!(context.predecessor_account_id(accounts(4)).attached_deposit(1).build());
+        contract.convert_admin_fees(vec![pool_id], accounts(2), U128(1));
+    }
+
+    #[test]
+    fn test_referral_activation() {
+        let (mut context, mut contract) = setup_contract();
+        testing_env!(context.predecessor_account_id(accounts(0)).attached_deposit(1).build());
+        contract.set_referral_activation_delay(100);
+        contract.insert_referral(accounts(4), 2000);
+        contract.insert_referral(accounts(3), 2000);
+        assert_eq!(contract.get_referral_info(accounts(4)).unwrap().active_at, 100 * 1_000_000_000);
+        assert!(contract.internal_get_referral_info(Some(accounts(4).into()), &accounts(3).into()).is_none());
+
+        testing_env!(context.block_timestamp(100 * 1_000_000_000).build());
+        assert_eq!(
+            contract.internal_get_referral_info(Some(accounts(4).into()), &accounts(3).into()),
+            Some((accounts(4).into(), 2000))
+        );
+        // self referral
+        assert!(contract.internal_get_referral_info(Some(accounts(3).into()), &accounts(3).into()).is_none());
+    }
+
+    #[test]
+    fn test_referral_volume() {
+        let (mut context, mut contract) = setup_contract();
+        let pool_id = create_pool_with_liquidity(
+            &mut context,
+            &mut contract,
+            accounts(3),
+            vec![(accounts(1), to_yocto("5")), (accounts(2), to_yocto("10"))],
+        );
+        testing_env!(context.predecessor_account_id(accounts(0)).attached_deposit(1).build());
+        contract.insert_referral(accounts(4), 2000);
+        deposit_tokens(&mut context, &mut contract, accounts(5), vec![(accounts(1), to_yocto("4")), (accounts(2), to_yocto("1"))]);
+        let referral_swap = |context: &mut VMContextBuilder, contract: &mut Contract, token_in: ValidAccountId, token_out: ValidAccountId| {
+            testing_env!(context.predecessor_account_id(accounts(5)).attached_deposit(1).build());
+            contract.swap(
+                vec![SwapAction {
+                    pool_id,
+                    token_in: token_in.into(),
+                    amount_in: Some(U128(to_yocto("1"))),
+                    token_out: token_out.into(),
+                    min_amount_out: U128(1),
+                }],
+                Some(accounts(4)),
+            );
+        };
+        // An unregistered referral can't pay for its volume record.
+        referral_swap(&mut context, &mut contract, accounts(1), accounts(2));
+        assert!(contract.get_referral_volume(accounts(4)).is_none());
+
+        deposit_tokens(&mut context, &mut contract, accounts(4), vec![]);
+        let prev_available = contract.storage_balance_of(accounts(4)).unwrap().available.0;
+        referral_swap(&mut context, &mut contract, accounts(1), accounts(2));
+        referral_swap(&mut context, &mut contract, accounts(1), accounts(2));
+        referral_swap(&mut context, &mut contract, accounts(2), accounts(1));
+        assert!(contract.storage_balance_of(accounts(4)).unwrap().available.0 < prev_available);
+        // Swaps without a referral are not attributed.
+        testing_env!(context.predecessor_account_id(accounts(5)).attached_deposit(1).build());
+        swap(&mut contract, pool_id, accounts(1), to_yocto("1"), accounts(2));
+
+        let volumes = contract.get_referral_volumes(None, None);
+        assert_eq!(volumes.len(), 1);
+        assert_eq!(volumes[0].referral_id, accounts(4).to_string());
+        assert_eq!(volumes[0].period.0, 0);
+        assert_eq!(volumes[0].volumes[&accounts(1).to_string()].0, to_yocto("2"));
+        assert_eq!(volumes[0].volumes[&accounts(2).to_string()].0, to_yocto("1"));
+        assert!(contract.get_referral_volume(accounts(3)).is_none());
+
+        testing_env!(context.predecessor_account_id(accounts(0)).block_timestamp(0).attached_deposit(1).build());
+        contract.set_referral_volume_period(100);
+        let volume = contract.get_referral_volume(accounts(4)).unwrap();
+        assert_eq!(volume.period.0, 1);
+        assert!(volume.volumes.is_empty());
+        assert_eq!(volume.prev_volumes[&accounts(1).to_string()].0, to_yocto("2"));
+
+        deposit_tokens(&mut context, &mut contract, accounts(5), vec![(accounts(1), to_yocto("1"))]);
+        testing_env!(context.block_timestamp(150 * 1_000_000_000).build());
+        referral_swap(&mut context, &mut contract, accounts(1), accounts(2));
+        let volume = contract.get_referral_volume(accounts(4)).unwrap();
+        assert_eq!(volume.period.0, 2);
+        assert_eq!(volume.volumes[&accounts(1).to_string()].0, to_yocto("1"));
+        assert!(volume.prev_volumes.is_empty());
+
+        // Two periods later nothing is left.
+        testing_env!(context.block_timestamp(400 * 1_000_000_000).build());
+        let volume = contract.get_referral_volume(accounts(4)).unwrap();
+        assert_eq!(volume.period.0, 5);
+        assert!(volume.volumes.is_empty() && volume.prev_volumes.is_empty());
+    }
+
+    #[test]
+    fn test_return_with_breakdown() {
+        let (mut context, mut contract) = setup_contract();
+        let pool_id = create_pool_with_liquidity(
+            &mut context,
+            &mut contract,
+            accounts(3),
+            vec![(accounts(1), to_yocto("5")), (accounts(2), to_yocto("10"))],
+        );
+        testing_env!(context.predecessor_account_id(accounts(0)).attached_deposit(1).build());
+        contract.insert_referral(accounts(3), 1000);
+
+        let breakdown = contract.get_return_with_breakdown(pool_id, accounts(1), U128(to_yocto("1")), accounts(2), Some(accounts(3)));
+        assert_eq!(breakdown.amount_out.0, contract.get_return(pool_id, accounts(1), U128(to_yocto("1")), accounts(2)).0);
+        assert_eq!(
+            breakdown.amount_out_before_fees.0 - breakdown.lp_fee.0 - breakdown.admin_fee.0,
+            breakdown.amount_out.0
+        );
+        assert!(breakdown.lp_fee.0 > 0);
+        assert_eq!(breakdown.referral_fee.0, breakdown.admin_fee.0 / 10);
+        assert_eq!(breakdown.imbalance_fee.0 + breakdown.imbalance_rebate.0, 0);
+        // 1 in on a 5:10 pool loses about 1/6 to price impact.
+        assert!((1600..1700).contains(&breakdown.price_impact_bps.unwrap()));
+        assert!(contract.get_return_with_breakdown(pool_id, accounts(1), U128(1), accounts(2), None).price_impact_bps.is_none());
+    }
+
+    #[test]
+    fn test_deposit_plan() {
+        let (mut context, mut contract) = setup_contract();
+        let pool_id = create_pool_with_liquidity(
+            &mut context,
+            &mut contract,
+            accounts(3),
+            vec![(accounts(1), to_yocto("5")), (accounts(2), to_yocto("10"))],
+        );
+        deposit_tokens(&mut context, &mut contract, accounts(4), vec![]);
+        let shares = contract.get_pool_total_shares(pool_id).0;
+
+        testing_env!(context.predecessor_account_id(accounts(1)).attached_deposit(1).build());
+        let msg = format!(
+            "{{\"deposit_plan\": {{\"deposits\": {{\"{}\": \"{}\"}}, \"add_liquidity_infos\": [{{\"pool_id\": {}, \"amounts\": [\"{}\", \"{}\"], \"min_amounts\": [\"0\", \"0\"]}}]}}}}",
+            accounts(2), to_yocto("2"), pool_id, to_yocto("1"), to_yocto("2")
+        );
+        contract.ft_on_transfer(accounts(4), U128(to_yocto("1")), msg);
+        assert_eq!(contract.get_deposit_plan(accounts(4)).unwrap()[&accounts(2).to_string()].0, to_yocto("2"));
+        assert_eq!(contract.get_deposit(accounts(4), accounts(1)).0, to_yocto("1"));
+
+        testing_env!(context.predecessor_account_id(accounts(2)).attached_deposit(1).build());
+        contract.ft_on_transfer(accounts(4), U128(to_yocto("2")), "{\"continue_deposit_plan\": true}".to_string());
+        assert!(contract.get_deposit_plan(accounts(4)).is_none());
+        assert_eq!(contract.get_pool_total_shares(pool_id).0, shares + shares / 5 - 1);
+        // only rounding dust stays in the inner account
+        assert!(contract.get_deposit(accounts(4), accounts(1)).0 < 10);
+        assert!(contract.get_deposit(accounts(4), accounts(2)).0 < 10);
+    }
+
+    #[test]
+    fn test_swap_screen() {
+        let (mut context, mut contract) = setup_contract();
+        let pool_id = create_pool_with_liquidity(
+            &mut context,
+            &mut contract,
+            accounts(3),
+            vec![(accounts(1), to_yocto("5")), (accounts(2), to_yocto("10"))],
+        );
+        deposit_tokens(&mut context, &mut contract, accounts(3), vec![(accounts(1), to_yocto("2"))]);
+        testing_env!(context.predecessor_account_id(accounts(0)).attached_deposit(1).build());
+        contract.set_pool_swap_screen(pool_id, Some(SwapScreenConfig {
+            mode: SwapScreenMode::Allowlist,
+            screener_id: Some(accounts(5).into()),
+        }));
+        testing_env!(context.predecessor_account_id(accounts(5)).attached_deposit(to_yocto("0.01")).build());
+        contract.extend_pool_swap_screen_list(pool_id, vec![accounts(3)]);
+        assert!(contract.is_pool_swap_screen_listed(pool_id, accounts(3)));
+        testing_env!(context.predecessor_account_id(accounts(3)).attached_deposit(1).build());
+        swap(&mut contract, pool_id, accounts(1), to_yocto("1"), accounts(2));
+    }
+
+    #[test]
+    #[should_panic(expected = "rejected by pool 0 screening")]
+    fn test_swap_screen_denied() {
+        let (mut context, mut contract) = setup_contract();
+        let pool_id = create_pool_with_liquidity(
+            &mut context,
+            &mut contract,
+            accounts(3),
+            vec![(accounts(1), to_yocto("5")), (accounts(2), to_yocto("10"))],
+        );
+        deposit_tokens(&mut context, &mut contract, accounts(3), vec![(accounts(1), to_yocto("2"))]);
+        testing_env!(context.predecessor_account_id(accounts(0)).attached_deposit(1).build());
+        contract.set_pool_swap_screen(pool_id, Some(SwapScreenConfig {
+            mode: SwapScreenMode::Denylist,
+            screener_id: None,
+        }));
+        testing_env!(context.attached_deposit(to_yocto("0.01")).build());
+        contract.extend_pool_swap_screen_list(pool_id, vec![accounts(3)]);
+        testing_env!(context.predecessor_account_id(accounts(3)).attached_deposit(1).build());
+        swap(&mut contract, pool_id, accounts(1), to_yocto("1"), accounts(2));
+    }
+
+    #[test]
+    fn test_permissioned_pool() {
+        let (mut context, mut contract) = setup_contract();
+        let pool_id = create_pool_with_liquidity(
+            &mut context,
+            &mut contract,
+            accounts(3),
+            vec![(accounts(1), to_yocto("5")), (accounts(2), to_yocto("10"))],
+        );
+        testing_env!(context.predecessor_account_id(accounts(3)).attached_deposit(to_yocto("0.01")).build());
+        contract.set_pool_permissioned(pool_id, true);
+        contract.extend_pool_lp_allowlist(pool_id, vec![accounts(4)]);
+        assert!(contract.is_pool_permissioned(pool_id));
+        assert!(contract.is_pool_lp_allowed(pool_id, accounts(3)));
+        assert!(contract.is_pool_lp_allowed(pool_id, accounts(4)));
+        assert!(!contract.is_pool_lp_allowed(pool_id, accounts(5)));
+
+        deposit_tokens(&mut context, &mut contract, accounts(4), vec![(accounts(1), to_yocto("1")), (accounts(2), to_yocto("2"))]);
+        testing_env!(context.predecessor_account_id(accounts(4)).attached_deposit(to_yocto("0.001")).build());
+        contract.add_liquidity(pool_id, vec![U128(to_yocto("1")), U128(to_yocto("2"))], None);
+        assert!(contract.get_pool_shares(pool_id, accounts(4)).0 > 0);
+
+        // Swaps stay open to everyone.
+        deposit_tokens(&mut context, &mut contract, accounts(5), vec![(accounts(1), to_yocto("1"))]);
+        testing_env!(context.predecessor_account_id(accounts(5)).attached_deposit(1).build());
+        swap(&mut contract, pool_id, accounts(1), to_yocto("1"), accounts(2));
+
+        testing_env!(context.predecessor_account_id(accounts(3)).attached_deposit(1).build());
+        contract.remove_pool_lp_allowlist(pool_id, vec![accounts(4)]);
+        assert!(!contract.is_pool_lp_allowed(pool_id, accounts(4)));
+        testing_env!(context.predecessor_account_id(accounts(3)).attached_deposit(1).build());
+        contract.set_pool_permissioned(pool_id, false);
+        assert!(contract.is_pool_lp_allowed(pool_id, accounts(4)));
+    }
+
+    #[test]
+    #[should_panic(expected = "is not allowed to provide liquidity to pool")]
+    fn test_permissioned_pool_not_allowed() {
+        let (mut context, mut contract) = setup_contract();
+        let pool_id = create_pool_with_liquidity(
+            &mut context,
+            &mut contract,
+            accounts(3),
+            vec![(accounts(1), to_yocto("5")), (accounts(2), to_yocto("10"))],
+        );
+        testing_env!(context.predecessor_account_id(accounts(3)).attached_deposit(to_yocto("0.01")).build());
+        contract.set_pool_permissioned(pool_id, true);
+        deposit_tokens(&mut context, &mut contract, accounts(4), vec![(accounts(1), to_yocto("1")), (accounts(2), to_yocto("2"))]);
+        testing_env!(context.predecessor_account_id(accounts(4)).attached_deposit(to_yocto("0.001")).build());
+        contract.add_liquidity(pool_id, vec![U128(to_yocto("1")), U128(to_yocto("2"))], None);
+    }
+
+    #[test]
+    fn test_pool_share_transfer_restricted() {
+        let (mut context, mut contract) = setup_contract();
+        let pool_id = create_pool_with_liquidity(
+            &mut context,
+            &mut contract,
+            accounts(3),
+            vec![(accounts(1), to_yocto("5")), (accounts(2), to_yocto("10"))],
+        );
+        testing_env!(context.predecessor_account_id(accounts(3)).attached_deposit(to_yocto("0.01")).build());
+        contract.set_pool_permissioned(pool_id, true);
+        contract.extend_pool_lp_allowlist(pool_id, vec![accounts(4)]);
+        contract.set_pool_share_transfer_restricted(pool_id, true);
+        assert!(contract.is_pool_share_transfer_restricted(pool_id));
+        assert!(contract.is_pool_share_transfer_allowed(pool_id, accounts(4)));
+        assert!(!contract.is_pool_share_transfer_allowed(pool_id, accounts(0)));
+        assert!(!contract.is_pool_share_transfer_allowed(pool_id, env::current_account_id().try_into().unwrap()));
+
+        testing_env!(context.predecessor_account_id(accounts(4)).attached_deposit(to_yocto("0.01")).build());
+        contract.mft_register(format!(":{}", pool_id), accounts(4));
+        testing_env!(context.predecessor_account_id(accounts(3)).attached_deposit(1).build());
+        contract.mft_transfer(format!(":{}", pool_id), accounts(4), U128(to_yocto("1")), None);
+        assert_eq!(contract.get_pool_shares(pool_id, accounts(4)).0, to_yocto("1"));
+        assert!(near_sdk::test_utils::get_logs().iter().any(|log| log.starts_with("EVENT_JSON:") && log.contains("compliance_share_transfer")));
+
+        testing_env!(context.predecessor_account_id(accounts(3)).attached_deposit(1).build());
+        contract.set_pool_share_transfer_restricted(pool_id, false);
+        assert!(contract.is_pool_share_transfer_allowed(pool_id, env::current_account_id().try_into().unwrap()));
+    }
+
+    #[test]
+    #[should_panic(expected = "is not allowed to receive shares of pool")]
+    fn test_pool_share_transfer_restricted_not_allowed() {
+        let (mut context, mut contract) = setup_contract();
+        let pool_id = create_pool_with_liquidity(
+            &mut context,
+            &mut contract,
+            accounts(3),
+            vec![(accounts(1), to_yocto("5")), (accounts(2), to_yocto("10"))],
+        );
+        testing_env!(context.predecessor_account_id(accounts(3)).attached_deposit(to_yocto("0.01")).build());
+        contract.set_pool_permissioned(pool_id, true);
+        contract.set_pool_share_transfer_restricted(pool_id, true);
+        testing_env!(context.predecessor_account_id(accounts(4)).attached_deposit(to_yocto("0.01")).build());
+        contract.mft_register(format!(":{}", pool_id), accounts(4));
+        testing_env!(context.predecessor_account_id(accounts(3)).attached_deposit(1).build());
+        contract.mft_transfer(format!(":{}", pool_id), accounts(4), U128(to_yocto("1")), None);
+    }
+
+    fn setup_pol(context: &mut VMContextBuilder, contract: &mut Contract) -> u64 {
+        let pool_id = create_pool_with_liquidity(
+            context,
+            contract,
+            accounts(3),
+            vec![(accounts(1), to_yocto("5")), (accounts(2), to_yocto("10"))],
+        );
+        testing_env!(context.predecessor_account_id(accounts(0)).attached_deposit(1).build());
+        contract.set_pol_treasury(Some(accounts(4)));
+        contract.set_pol_pool(pool_id, true);
+        deposit_tokens(context, contract, accounts(4), vec![(accounts(1), to_yocto("1")), (accounts(2), to_yocto("2"))]);
+        testing_env!(context.predecessor_account_id(accounts(4)).attached_deposit(to_yocto("0.001")).build());
+        contract.pol_deploy(pool_id, vec![U128(to_yocto("1")), U128(to_yocto("2"))], None, None);
+        pool_id
+    }
+
+    #[test]
+    fn test_pol_manager() {
+        let (mut context, mut contract) = setup_contract();
+        let pool_id = setup_pol(&mut context, &mut contract);
+        let shares = contract.get_pool_shares(pool_id, accounts(4)).0;
+        let position = contract.get_pol_position(pool_id).unwrap();
+        assert_eq!(position.holder_id, Some(accounts(4).into()));
+        assert_eq!(position.shares.0, shares);
+        // The pool rounds the amounts it takes down by a few yocto.
+        assert!(to_yocto("1") - position.cost_basis[0].0 < 10);
+        assert!(to_yocto("2") - position.cost_basis[1].0 < 10);
+        assert_eq!(contract.get_deposit(accounts(4), accounts(1)).0, to_yocto("1") - position.cost_basis[0].0);
+        assert_eq!(contract.get_locked_shares(accounts(4), pool_id).0, shares);
+
+        // A swap moves value between the pool tokens.
+        deposit_tokens(&mut context, &mut contract, accounts(5), vec![(accounts(1), to_yocto("1"))]);
+        testing_env!(context.predecessor_account_id(accounts(5)).attached_deposit(1).build());
+        swap(&mut contract, pool_id, accounts(1), to_yocto("1"), accounts(2));
+        let position = contract.get_pol_position(pool_id).unwrap();
+        assert!(!position.pnl[0].starts_with('-'));
+        assert!(position.pnl[1].starts_with('-'));
+
+        testing_env!(context.predecessor_account_id(accounts(4)).attached_deposit(1).block_timestamp(0).build());
+        contract.pol_request_withdraw(pool_id, U128(shares));
+        let unlock_time = contract.get_pol_position(pool_id).unwrap().pending_withdrawal.unwrap().unlock_time;
+        assert_eq!(unlock_time, POL_WITHDRAW_TIMELOCK);
+        testing_env!(context.predecessor_account_id(accounts(4)).attached_deposit(1).block_timestamp(unlock_time).build());
+        let amounts = contract.pol_execute_withdraw(pool_id, vec![U128(0), U128(0)]);
+        let position = contract.get_pol_position(pool_id).unwrap();
+        assert_eq!(position.shares.0, 0);
+        assert_eq!(position.withdrawn, amounts);
+        assert_eq!(contract.get_pool_shares(pool_id, accounts(4)).0, 0);
+        assert_eq!(contract.get_deposit(accounts(4), accounts(1)).0, to_yocto("1") - position.cost_basis[0].0 + amounts[0].0);
+
+        testing_env!(context.predecessor_account_id(accounts(0)).attached_deposit(1).build());
+        contract.set_pol_pool(pool_id, false);
+        assert!(contract.get_pol_position(pool_id).is_none());
+    }
+
+    #[test]
+    #[should_panic(expected = "Not enough unlocked shares")]
+    fn test_pol_manager_locked() {
+        let (mut context, mut contract) = setup_contract();
+        let pool_id = setup_pol(&mut context, &mut contract);
+        let shares = contract.get_pool_shares(pool_id, accounts(4));
+        testing_env!(context.predecessor_account_id(accounts(4)).attached_deposit(1).build());
+        contract.remove_liquidity(pool_id, shares, vec![U128(0), U128(0)]);
+    }
+
+    #[test]
+    #[should_panic(expected = "POL withdrawal still timelocked")]
+    fn test_pol_manager_timelocked() {
+        let (mut context, mut contract) = setup_contract();
+        let pool_id = setup_pol(&mut context, &mut contract);
+        let shares = contract.get_pool_shares(pool_id, accounts(4));
+        testing_env!(context.predecessor_account_id(accounts(4)).attached_deposit(1).block_timestamp(0).build());
+        contract.pol_request_withdraw(pool_id, shares);
+        testing_env!(context.predecessor_account_id(accounts(4)).attached_deposit(1).block_timestamp(POL_WITHDRAW_TIMELOCK - 1).build());
+        contract.pol_execute_withdraw(pool_id, vec![U128(0), U128(0)]);
+    }
+
+    #[test]
+    fn test_pool_registry() {
+        let (mut context, mut contract) = setup_contract();
+        let pool_id = create_pool_with_liquidity(
+            &mut context,
+            &mut contract,
+            accounts(3),
+            vec![(accounts(1), to_yocto("5")), (accounts(2), to_yocto("10"))],
+        );
+        // Another kind makes another pool.
+        testing_env!(context.predecessor_account_id(accounts(0)).attached_deposit(env::storage_byte_cost() * 1334).build());
+        let other_kind_pool_id = contract.add_stable_swap_pool(vec![accounts(2), accounts(1)], vec![18, 18], 25, 240);
+        assert!(contract.find_duplicate_pools(None, None).is_empty());
+
+        // Another fee makes another pool too.
+        testing_env!(context.predecessor_account_id(accounts(4)).attached_deposit(env::storage_byte_cost() * 1300).build());
+        let other_fee_pool_id = contract.add_simple_pool(vec![accounts(2), accounts(1)], 30);
+        assert!(contract.find_duplicate_pools(None, None).is_empty());
+
+        // Owner can still duplicate on purpose.
+        testing_env!(context.predecessor_account_id(accounts(0)).attached_deposit(env::storage_byte_cost() * 1300).build());
+        let duplicate_pool_id = contract.add_simple_pool(vec![accounts(2), accounts(1)], 25);
+        assert_eq!(contract.find_duplicate_pools(None, None), vec![vec![pool_id, duplicate_pool_id]]);
+        assert!(contract.find_duplicate_pools(Some(other_kind_pool_id), None).is_empty());
+
+        // A fee change moves the pool to the group of its new fee.
+        testing_env!(context.predecessor_account_id(accounts(0)).attached_deposit(1).build());
+        contract.modify_total_fee(other_fee_pool_id, 25);
+        assert_eq!(contract.find_duplicate_pools(None, None), vec![vec![pool_id, duplicate_pool_id, other_fee_pool_id]]);
+
+        testing_env!(context.predecessor_account_id(accounts(0)).attached_deposit(1).build());
+        contract.set_max_pool_count(Some(3));
+        assert_eq!(contract.get_max_pool_count(), Some(3));
+    }
+
+    #[test]
+    fn test_pool_registry_duplicates_allowed() {
+        let (mut context, mut contract) = setup_contract();
+        let pool_id = create_pool_with_liquidity(
+            &mut context,
+            &mut contract,
+            accounts(3),
+            vec![(accounts(1), to_yocto("5")), (accounts(2), to_yocto("10"))],
+        );
+        testing_env!(context.predecessor_account_id(accounts(0)).attached_deposit(1).build());
+        contract.set_duplicate_pools_allowed(true);
+        assert!(contract.get_duplicate_pools_allowed());
+        testing_env!(context.predecessor_account_id(accounts(4)).attached_deposit(env::storage_byte_cost() * 1300).build());
+        let duplicate_pool_id = contract.add_simple_pool(vec![accounts(2), accounts(1)], 25);
+        assert_eq!(contract.find_duplicate_pools(None, None), vec![vec![pool_id, duplicate_pool_id]]);
+    }
+
+    #[test]
+    #[should_panic(expected = "Duplicate of pools [0]")]
+    fn test_pool_registry_duplicate() {
+        let (mut context, mut contract) = setup_contract();
+        create_pool_with_liquidity(
+            &mut context,
+            &mut contract,
+            accounts(3),
+            vec![(accounts(1), to_yocto("5")), (accounts(2), to_yocto("10"))],
+        );
+        testing_env!(context.predecessor_account_id(accounts(4)).attached_deposit(env::storage_byte_cost() * 1300).build());
+        contract.add_simple_pool(vec![accounts(2), accounts(1)], 25);
+    }
+
+    #[test]
+    #[should_panic(expected = "Pool count cap reached")]
+    fn test_pool_registry_cap() {
+        let (mut context, mut contract) = setup_contract();
+        testing_env!(context.predecessor_account_id(accounts(0)).attached_deposit(1).build());
+        contract.set_max_pool_count(Some(1));
+        create_pool_with_liquidity(
+            &mut context,
+            &mut contract,
+            accounts(3),
+            vec![(accounts(1), to_yocto("5")), (accounts(2), to_yocto("10"))],
+        );
+        testing_env!(context.predecessor_account_id(accounts(3)).attached_deposit(env::storage_byte_cost() * 1300).build());
+        contract.add_simple_pool(vec![accounts(2), accounts(1)], 30);
+    }
+
+    fn round_trip_actions(pool_id: u64, amount_in: Balance) -> Vec<SwapAction> {
+        vec![
+            SwapAction {
+                pool_id,
+                token_in: accounts(1).into(),
+                amount_in: Some(U128(amount_in)),
+                token_out: accounts(2).into(),
+                min_amount_out: U128(1),
+            },
+            SwapAction {
+                pool_id,
+                token_in: accounts(2).into(),
+                amount_in: None,
+                token_out: accounts(1).into(),
+                min_amount_out: U128(1),
+            },
+        ]
+    }
+
+    #[test]
+    fn test_swap_with_quote() {
+        let (mut context, mut contract) = setup_contract();
+        let pool_id = create_pool_with_liquidity(
+            &mut context,
+            &mut contract,
+            accounts(3),
+            vec![(accounts(1), to_yocto("5")), (accounts(2), to_yocto("10"))],
+        );
+        deposit_tokens(&mut context, &mut contract, accounts(3), vec![(accounts(1), to_yocto("1"))]);
+        let quote_1 = contract.get_return(pool_id, accounts(1), U128(to_yocto("1")), accounts(2));
+        let quote_2 = contract.get_return(pool_id, accounts(2), quote_1, accounts(1));
+        testing_env!(context.predecessor_account_id(accounts(3)).attached_deposit(1).build());
+        let amount_out = contract.swap_with_quote(
+            round_trip_actions(pool_id, to_yocto("1")),
+            None,
+            RouteQuote { amounts_out: vec![quote_1, quote_2], tolerance_bps: Some(0), quoted_at: None, deadline: None },
+        );
+        // The second hop trades against the moved pool, above its quote.
+        assert!(amount_out.0 > quote_2.0);
+    }
+
+    #[test]
+    #[should_panic(expected = "Route step 0 in pool 0 (bob -> charlie)")]
+    fn test_swap_with_quote_shortfall() {
+        let (mut context, mut contract) = setup_contract();
+        let pool_id = create_pool_with_liquidity(
+            &mut context,
+            &mut contract,
+            accounts(3),
+            vec![(accounts(1), to_yocto("5")), (accounts(2), to_yocto("10"))],
+        );
+        deposit_tokens(&mut context, &mut contract, accounts(3), vec![(accounts(1), to_yocto("1"))]);
+        let quote_1 = contract.get_return(pool_id, accounts(1), U128(to_yocto("1")), accounts(2));
+        testing_env!(context.predecessor_account_id(accounts(3)).attached_deposit(1).build());
+        contract.swap_with_quote(
+            round_trip_actions(pool_id, to_yocto("1")),
+            None,
+            RouteQuote { amounts_out: vec![U128(quote_1.0 * 102 / 100), U128(1)], tolerance_bps: Some(100), quoted_at: None, deadline: None },
+        );
+    }
+
+    #[test]
+    fn test_degen_fallback_oracle() {
+        let (mut context, mut contract) = setup_contract();
+        testing_env!(context.predecessor_account_id(accounts(0)).attached_deposit(1).build());
+        contract.register_degen_token(accounts(4), DegenType::PriceOracle { decimals: 18 });
+        contract.set_degen_fallback_oracle(accounts(4), Some(DegenType::PythOracle {
+            price_identifier: pyth_oracle::PriceIdentifier([1u8; 32]),
+        }));
+        let fallback = contract.get_degen_fallback_oracle(accounts(4)).unwrap();
+        assert_eq!(fallback.degen_type, "PythOracle");
+        assert!(fallback.degen_price.is_none());
+        assert!(!fallback.is_active);
+
+        // Recovery of the primary source is a no-op while the fallback isn't active.
+        on_degen_primary_price_updated(&accounts(4).into());
+        assert!(!contract.get_degen_fallback_oracle(accounts(4)).unwrap().is_active);
+
+        testing_env!(context.predecessor_account_id(accounts(0)).attached_deposit(1).build());
+        contract.set_degen_fallback_oracle(accounts(4), None);
+        assert!(contract.get_degen_fallback_oracle(accounts(4)).is_none());
+    }
+
+    #[test]
+    #[should_panic(expected = "is not degen token")]
+    fn test_degen_fallback_oracle_not_degen() {
+        let (mut context, mut contract) = setup_contract();
+        testing_env!(context.predecessor_account_id(accounts(0)).attached_deposit(1).build());
+        contract.set_degen_fallback_oracle(accounts(4), Some(DegenType::PriceOracle { decimals: 18 }));
+    }
+
+    #[test]
+    fn test_rate_guard() {
+        let (mut context, mut contract) = setup_contract();
+        testing_env!(context.predecessor_account_id(accounts(0)).attached_deposit(1).build());
+        contract.register_rated_token("STNEAR".to_string(), accounts(4), None);
+        contract.set_rate_guard(accounts(4), Some(3600), Some(100));
+        let guard = contract.get_rate_guards().get(&accounts(4).to_string()).cloned().unwrap();
+
+        testing_env!(context.block_timestamp(crate::utils::to_nano(3600) + 1).build());
+        assert!(guard.is_fresh(1));
+        assert!(!guard.is_fresh(0));
+        assert!(guard.accepts_change(to_yocto("1"), to_yocto("1.01")));
+        assert!(guard.accepts_change(to_yocto("1"), to_yocto("0.99")));
+        assert!(!guard.accepts_change(to_yocto("1"), to_yocto("1.011")));
+
+        testing_env!(context.predecessor_account_id(accounts(0)).attached_deposit(1).build());
+        contract.set_rate_guard(accounts(4), None, None);
+        assert!(contract.get_rate_guards().is_empty());
+    }
+
+    #[test]
+    fn test_static_token_rate() {
+        let (mut context, mut contract) = setup_contract();
+        // Rates are cached across tests, so use a token no other test rates.
+        let token_id: ValidAccountId = "static.near".try_into().unwrap();
+        testing_env!(context.predecessor_account_id(accounts(0)).attached_deposit(1).build());
+        contract.register_rated_token("STATIC".to_string(), token_id.clone(), Some(to_yocto("2").to_string()));
+        let info = contract.list_rated_tokens().remove(&token_id.to_string()).unwrap();
+        assert_eq!(info.rate_type, "STATIC");
+        assert_eq!(info.rate_price.0, to_yocto("2"));
+        assert!(info.is_valid);
+
+        testing_env!(context.block_timestamp(crate::utils::to_nano(365 * 24 * 3600)).build());
+        contract.update_static_token_rate(token_id.clone(), U128(to_yocto("2.1")));
+        let info = contract.list_rated_tokens().remove(&token_id.to_string()).unwrap();
+        assert_eq!(info.rate_price.0, to_yocto("2.1"));
+        assert_eq!(info.last_update_ts.0, crate::utils::to_nano(365 * 24 * 3600));
+    }
+
+    #[test]
+    #[should_panic(expected = "is not static")]
+    fn test_update_static_token_rate_not_static() {
+        let (mut context, mut contract) = setup_contract();
+        let token_id: ValidAccountId = "linear.near".try_into().unwrap();
+        testing_env!(context.predecessor_account_id(accounts(0)).attached_deposit(1).build());
+        contract.register_rated_token("LINEAR".to_string(), token_id.clone(), None);
+        contract.update_static_token_rate(token_id, U128(to_yocto("1")));
+    }
+
+    #[test]
+    fn test_sync_static_token_rate() {
+        let (mut context, mut contract) = setup_contract();
+        // Rates are cached across tests, so use a token no other test rates.
+        let token_id: ValidAccountId = "static-sync.near".try_into().unwrap();
+        testing_env!(context.predecessor_account_id(accounts(0)).attached_deposit(1).build());
+        contract.register_rated_token("STATIC".to_string(), token_id.clone(), Some(to_yocto("2").to_string()));
+        assert!(matches!(contract.update_token_rate(token_id.clone()), PromiseOrValue::Value(true)));
+        assert!(matches!(contract.batch_update_token_rates(vec![token_id]), PromiseOrValue::Value(true)));
+    }
+
+    #[test]
+    #[should_panic(expected = "is neither rated nor degen token")]
+    fn test_batch_update_token_rates_unknown_token() {
+        let (_, contract) = setup_contract();
+        // Rates are cached across tests, so use a token no other test rates.
+        contract.batch_update_token_rates(vec!["unknown.near".try_into().unwrap()]);
+    }
+
+    #[test]
+    #[should_panic(expected = "At most 6 tokens per batch")]
+    fn test_batch_update_token_rates_too_many() {
+        let (_, contract) = setup_contract();
+        contract.batch_update_token_rates(vec![accounts(0); MAX_BATCH_RATE_TOKENS + 1]);
+    }
+
+    #[test]
+    fn test_exchange_stats() {
+        let (mut context, mut contract) = setup_contract();
+        let pool_id = create_pool_with_liquidity(
+            &mut context,
+            &mut contract,
+            accounts(3),
+            vec![(accounts(1), to_yocto("5")), (accounts(2), to_yocto("10"))],
+        );
+        deposit_tokens(&mut context, &mut contract, accounts(3), vec![(accounts(1), to_yocto("2"))]);
+        let prev_deposit = contract.get_user_storage_state(accounts(3)).unwrap().deposit.0;
+        testing_env!(context.predecessor_account_id(accounts(3)).attached_deposit(1).build());
+        let amount_out_1 = swap(&mut contract, pool_id, accounts(1), to_yocto("1"), accounts(2));
+        let amount_out_2 = swap(&mut contract, pool_id, accounts(1), to_yocto("1"), accounts(2));
+
+        let stats = contract.get_exchange_stats(None, None);
+        assert_eq!(stats.total_swaps.0, 2);
+        assert_eq!(stats.unique_accounts.0, 1);
+        // the first-trade flag is paid by the trader
+        assert!(read_exchange_traders_from_storage().contains(accounts(3).as_ref()));
+        assert!(contract.get_user_storage_state(accounts(3)).unwrap().deposit.0 < prev_deposit);
+        let stats_in = &stats.tokens[&accounts(1).to_string()];
+        assert_eq!(stats_in.volume, to_yocto("2"));
+        assert_eq!(stats_in.fees, to_yocto("2") * 25 / 10_000);
+        let stats_out = &stats.tokens[&accounts(2).to_string()];
+        assert_eq!(stats_out.volume, amount_out_1 + amount_out_2);
+        assert_eq!(stats_out.fees, 0);
+        assert_eq!(contract.get_exchange_stats(Some(1), Some(1)).tokens.len(), 1);
+    }
+
+    #[test]
+    fn test_lp_performance() {
+        let (mut context, mut contract) = setup_contract();
+        let pool_id = create_pool_with_liquidity(
+            &mut context,
+            &mut contract,
+            accounts(3),
+            vec![(accounts(1), to_yocto("5")), (accounts(2), to_yocto("10"))],
+        );
+        testing_env!(context.predecessor_account_id(accounts(4)).attached_deposit(to_yocto("0.01")).build());
+        let checkpoint_id = contract.checkpoint_pool_share_price(pool_id);
+        let performance = contract.get_lp_performance(pool_id, accounts(3), checkpoint_id);
+        assert_eq!(performance.share_price_growth_bps, 0);
+        assert_eq!(performance.implied_fee_earnings, vec![U128(0), U128(0)]);
+
+        deposit_tokens(&mut context, &mut contract, accounts(3), vec![(accounts(1), to_yocto("1"))]);
+        testing_env!(context.predecessor_account_id(accounts(3)).attached_deposit(1).build());
+        let amount_out = swap(&mut contract, pool_id, accounts(1), to_yocto("1"), accounts(2));
+        swap(&mut contract, pool_id, accounts(2), amount_out, accounts(1));
+
+        let performance = contract.get_lp_performance(pool_id, accounts(3), checkpoint_id);
+        assert!(performance.share_price.0 > performance.checkpoint.share_price);
+        assert!(performance.share_price_growth_bps > 0);
+        for (earned, amount) in performance.implied_fee_earnings.iter().zip(performance.amounts.iter()) {
+            assert!(earned.0 > 0 && earned.0 < amount.0);
+        }
+
+        testing_env!(context
+            .predecessor_account_id(accounts(4))
+            .block_timestamp(crate::utils::to_nano(LP_CHECKPOINT_INTERVAL_SEC))
+            .attached_deposit(to_yocto("0.01"))
+            .build());
+        assert_eq!(contract.checkpoint_pool_share_price(pool_id), checkpoint_id + 1);
+        assert_eq!(contract.get_lp_checkpoints(pool_id).len(), 2);
+    }
+
+    #[test]
+    fn test_pool_fee_apr() {
+        let (mut context, mut contract) = setup_contract();
+        let pool_id = create_pool_with_liquidity(
+            &mut context,
+            &mut contract,
+            accounts(3),
+            vec![(accounts(1), to_yocto("5")), (accounts(2), to_yocto("10"))],
+        );
+        testing_env!(context.predecessor_account_id(accounts(4)).attached_deposit(to_yocto("0.01")).build());
+        contract.checkpoint_pool_share_price(pool_id);
+        assert!(contract.get_pool_fee_apr(pool_id).is_none());
+
+        deposit_tokens(&mut context, &mut contract, accounts(3), vec![(accounts(1), to_yocto("1"))]);
+        testing_env!(context.predecessor_account_id(accounts(3)).attached_deposit(1).build());
+        let amount_out = swap(&mut contract, pool_id, accounts(1), to_yocto("1"), accounts(2));
+        swap(&mut contract, pool_id, accounts(2), amount_out, accounts(1));
+
+        testing_env!(context
+            .predecessor_account_id(accounts(4))
+            .block_timestamp(crate::utils::to_nano(LP_CHECKPOINT_INTERVAL_SEC))
+            .attached_deposit(to_yocto("0.01"))
+            .build());
+        contract.checkpoint_pool_share_price(pool_id);
+        let apr = contract.get_pool_fee_apr(pool_id).unwrap();
+        assert_eq!(apr.to_checkpoint.id, apr.from_checkpoint.id + 1);
+        let growth_bps = (apr.to_checkpoint.share_price - apr.from_checkpoint.share_price) * 10000 / apr.from_checkpoint.share_price;
+        assert!(apr.fee_apr_bps > 0);
+        assert!(apr.fee_apr_bps as u128 >= growth_bps * 365);
+    }
+
+    #[test]
+    fn test_estimate_lp_round_trip() {
+        let (mut context, mut contract) = setup_contract();
+        let pool_id = create_pool_with_liquidity(
+            &mut context,
+            &mut contract,
+            accounts(3),
+            vec![(accounts(1), to_yocto("5")), (accounts(2), to_yocto("10"))],
+        );
+        let estimate = contract.estimate_lp_round_trip(pool_id, vec![U128(to_yocto("1")), U128(to_yocto("3"))]);
+        assert!(estimate.added_amounts[0].0 <= to_yocto("1"));
+        assert!(estimate.added_amounts[1].0 <= to_yocto("2"));
+        assert_eq!(estimate.add_cost_bps, 0);
+        assert_eq!(estimate.exit_cost_bps, 0);
+        assert!(estimate.fee_apr_bps.is_none());
+        assert!(estimate.break_even_sec.is_none());
+
+        let stable_pool_id = setup_stable_kind_pool(&mut context, &mut contract, "stable", vec![accounts(1), accounts(2)]);
+        let estimate = contract.estimate_lp_round_trip(stable_pool_id, vec![U128(to_yocto("100")), U128(0)]);
+        assert!(estimate.add_cost_bps > 0);
+        assert!(estimate.shares.0 > 0);
+        assert_eq!(estimate.daily_fee_earnings, vec![U128(0), U128(0)]);
+    }
+
+    #[test]
+    #[should_panic(expected = "Checkpoint 0 is too recent")]
+    fn test_lp_checkpoint_too_recent() {
+        let (mut context, mut contract) = setup_contract();
+        let pool_id = create_pool_with_liquidity(
+            &mut context,
+            &mut contract,
+            accounts(3),
+            vec![(accounts(1), to_yocto("5")), (accounts(2), to_yocto("10"))],
+        );
+        testing_env!(context.predecessor_account_id(accounts(4)).attached_deposit(to_yocto("0.01")).build());
+        contract.checkpoint_pool_share_price(pool_id);
+        contract.checkpoint_pool_share_price(pool_id);
+    }
+
+    #[test]
+    fn test_token_aliases() {
+        let (mut context, mut contract) = setup_contract();
+        let pool_id = create_pool_with_liquidity(
+            &mut context,
+            &mut contract,
+            accounts(3),
+            vec![(accounts(1), to_yocto("5")), (accounts(2), to_yocto("10"))],
+        );
+        testing_env!(context.predecessor_account_id(accounts(0)).attached_deposit(1).build());
+        contract.set_token_alias("BOB".to_string(), Some("bob.bridge.near".to_string().try_into().unwrap()));
+        contract.set_token_alias("charlie".to_string(), Some(accounts(2)));
+        assert_eq!(contract.get_token_aliases().get("bob"), Some(&"bob.bridge.near".to_string()));
+        assert_eq!(contract.get_pool_health(pool_id).spoofed_tokens, vec![accounts(1).to_string()]);
+        assert_eq!(contract.get_spoofed_pool_tokens(vec![pool_id]).get(&pool_id), Some(&vec![accounts(1).to_string()]));
+
+        contract.set_token_alias("bob".to_string(), None);
+        assert!(contract.get_pool_health(pool_id).spoofed_tokens.is_empty());
+        assert!(contract.get_spoofed_pool_tokens(vec![pool_id]).is_empty());
+    }
+
+    #[test]
+    fn test_pool_params_batch() {
+        let (mut context, mut contract) = setup_contract();
+        let pool_id = create_pool_with_liquidity(
+            &mut context,
+            &mut contract,
+            accounts(3),
+            vec![(accounts(1), to_yocto("5")), (accounts(2), to_yocto("10"))],
+        );
+        let stable_pool_id = setup_stable_kind_pool(&mut context, &mut contract, "stable", vec![accounts(1), accounts(2)]);
+        testing_env!(context.predecessor_account_id(accounts(0)).attached_deposit(1).build());
+        contract.extend_frozenlist_tokens(vec![accounts(2)]);
+
+        let params = contract.get_pool_params_batch(vec![pool_id, stable_pool_id, 99]);
+        assert_eq!(params.len(), 2);
+        assert_eq!(params[0].pool_kind, "SIMPLE_POOL");
+        assert_eq!(params[0].amp, None);
+        assert!(params[0].rates.is_none());
+        assert_eq!(params[0].frozen_tokens, vec![accounts(2).to_string()]);
+        assert!(!params[0].paused && !params[0].archived && !params[0].deprecated && !params[0].rate_guarded);
+        assert_eq!(params[1].pool_id, stable_pool_id);
+        assert_eq!(params[1].amp, Some(240));
+        assert_eq!(params[1].total_fee, 25);
+        assert_eq!(params[1].tvl_limit, None);
+
+        contract.deprecate_pool(pool_id);
+        let params = contract.get_pool_params_batch(vec![pool_id]);
+        assert!(params[0].deprecated && !params[0].archived);
+    }
+
+    #[test]
+    fn test_rate_divergence_breaker() {
+        let (mut context, mut contract) = setup_contract();
+        let sec = 1_000_000_000u64;
+        let pool_id = setup_stable_kind_pool(&mut context, &mut contract, "rated", vec![accounts(1), accounts(2)]);
+        testing_env!(context.predecessor_account_id(accounts(0)).attached_deposit(1).build());
+        contract.set_rate_divergence_breaker(pool_id, 10, 60);
+        assert!(contract.poke_rate_divergence(pool_id).unwrap() <= 10);
+        assert!(contract.get_rate_divergence_breaker(pool_id).unwrap().diverging_since.is_none());
+
+        testing_env!(context.predecessor_account_id(accounts(3)).block_timestamp(10 * sec).block_index(1).build());
+        swap(&mut contract, pool_id, accounts(1), to_yocto("300"), accounts(2));
+        let breaker = contract.get_rate_divergence_breaker(pool_id).unwrap();
+        assert!(breaker.last_divergence_bps > 10);
+        assert_eq!(breaker.diverging_since, Some(near_sdk::json_types::U64(10 * sec)));
+        assert_eq!(breaker.diverging_samples, 1);
+        assert!(!breaker.guarded);
+
+        testing_env!(context.block_timestamp(40 * sec).block_index(2).build());
+        contract.poke_rate_divergence(pool_id);
+        assert!(!contract.get_rate_divergence_breaker(pool_id).unwrap().guarded);
+        // long enough, but seen in two blocks only
+        testing_env!(context.block_timestamp(70 * sec).build());
+        contract.poke_rate_divergence(pool_id);
+        let breaker = contract.get_rate_divergence_breaker(pool_id).unwrap();
+        assert_eq!(breaker.diverging_samples, 2);
+        assert!(!breaker.guarded);
+        testing_env!(context.block_index(3).build());
+        contract.poke_rate_divergence(pool_id);
+        assert!(contract.get_rate_divergence_breaker(pool_id).unwrap().guarded);
+        assert!(contract.get_pool_params_batch(vec![pool_id])[0].rate_guarded);
+
+        testing_env!(context.predecessor_account_id(accounts(0)).attached_deposit(1).build());
+        contract.reset_rate_divergence_breaker(pool_id);
+        let breaker = contract.get_rate_divergence_breaker(pool_id).unwrap();
+        assert!(!breaker.guarded);
+        assert!(breaker.diverging_since.is_none());
+        testing_env!(context.predecessor_account_id(accounts(3)).build());
+        swap(&mut contract, pool_id, accounts(2), to_yocto("1"), accounts(1));
+        assert_eq!(contract.get_rate_divergence_breaker(pool_id).unwrap().diverging_since, Some(near_sdk::json_types::U64(70 * sec)));
+    }
+
+    #[test]
+    #[should_panic(expected = "Pool 0 is guarded for rate divergence")]
+    fn test_swap_rate_guarded_pool() {
+        let (mut context, mut contract) = setup_contract();
+        let pool_id = setup_stable_kind_pool(&mut context, &mut contract, "rated", vec![accounts(1), accounts(2)]);
+        testing_env!(context.predecessor_account_id(accounts(0)).attached_deposit(1).build());
+        contract.set_rate_divergence_breaker(pool_id, 10, 0);
+        testing_env!(context.predecessor_account_id(accounts(3)).block_index(1).build());
+        swap(&mut contract, pool_id, accounts(1), to_yocto("300"), accounts(2));
+        for height in 2..=MIN_RATE_DIVERGENCE_SAMPLES as u64 {
+            testing_env!(context.block_index(height).build());
+            contract.poke_rate_divergence(pool_id);
+        }
+        assert!(contract.get_rate_divergence_breaker(pool_id).unwrap().guarded);
+        swap(&mut contract, pool_id, accounts(1), to_yocto("1"), accounts(2));
+    }
+
+    #[test]
+    fn test_pool_delegation() {
+        let (mut context, mut contract) = setup_contract();
+        let pool_id = create_pool_with_liquidity(
+            &mut context,
+            &mut contract,
+            accounts(3),
+            vec![(accounts(1), to_yocto("5")), (accounts(2), to_yocto("10"))],
+        );
+        testing_env!(context.predecessor_account_id(accounts(0)).attached_deposit(1).build());
+        contract.set_pool_delegation(pool_id, PoolDelegation {
+            delegate_id: accounts(4).into(),
+            fee_bounds: Some(CreatorFeeBounds { min_fee: 10, max_fee: 50 }),
+            metadata: true,
+            max_tvl_limit: None,
+        });
+
+        testing_env!(context.predecessor_account_id(accounts(4)).build());
+        contract.modify_total_fee_by_delegate(pool_id, 40);
+        assert_eq!(contract.get_pool_fee(pool_id), 40);
+        testing_env!(context.attached_deposit(to_yocto("0.01")).build());
+        contract.set_pool_metadata(pool_id, Some("Project pool".to_string()), None, None);
+        assert_eq!(contract.get_pool_metadata(pool_id).unwrap().name, Some("Project pool".to_string()));
+
+        testing_env!(context.predecessor_account_id(accounts(0)).attached_deposit(1).build());
+        contract.revoke_pool_delegation(pool_id);
+        assert!(contract.get_pool_delegation(pool_id).is_none());
+    }
+
+    #[test]
+    #[should_panic(expected = "E62: illegal fee")]
+    fn test_delegate_fee_out_of_bounds() {
+        let (mut context, mut contract) = setup_contract();
+        let pool_id = create_pool_with_liquidity(
+            &mut context,
+            &mut contract,
+            accounts(3),
+            vec![(accounts(1), to_yocto("5")), (accounts(2), to_yocto("10"))],
+        );
+        testing_env!(context.predecessor_account_id(accounts(0)).attached_deposit(1).build());
+        contract.set_pool_delegation(pool_id, PoolDelegation {
+            delegate_id: accounts(4).into(),
+            fee_bounds: Some(CreatorFeeBounds { min_fee: 10, max_fee: 50 }),
+            metadata: false,
+            max_tvl_limit: None,
+        });
+        testing_env!(context.predecessor_account_id(accounts(4)).build());
+        contract.modify_total_fee_by_delegate(pool_id, 60);
+    }
+
+    #[test]
+    fn test_views_v2_schema() {
+        let (mut context, mut contract) = setup_contract();
+        let pool_id = create_pool_with_liquidity(
+            &mut context,
+            &mut contract,
+            accounts(3),
+            vec![(accounts(1), to_yocto("5")), (accounts(2), to_yocto("10"))],
+        );
+        assert_eq!(contract.get_views_schema_version(), 2);
+        let pool = contract.get_pool(pool_id);
+        assert_eq!(
+            near_sdk::serde_json::to_value(contract.get_pool_v2(pool_id)).unwrap(),
+            near_sdk::serde_json::json!({
+                "pool_id": pool_id,
+                "pool_kind": "SIMPLE_POOL",
+                "token_account_ids": [accounts(1), accounts(2)],
+                "amounts": [U128(to_yocto("5")), U128(to_yocto("10"))],
+                "total_fee": pool.total_fee,
+                "shares_total_supply": pool.shares_total_supply,
+                "amp": 0,
+            })
+        );
+        assert_eq!(contract.get_pools_v2(0, 10).len(), 1);
+
+        let deposits = contract.get_deposits_v2(accounts(3));
+        assert!(deposits.windows(2).all(|pair| pair[0].token_id < pair[1].token_id));
+        assert_eq!(
+            near_sdk::serde_json::to_value(&deposits[0]).unwrap(),
+            near_sdk::serde_json::json!({ "token_id": deposits[0].token_id, "amount": deposits[0].amount })
+        );
+    }
+
+    #[test]
+    fn test_pool_health() {
+        let (mut context, mut contract) = setup_contract();
+        let pool_id = create_pool_with_liquidity(
+            &mut context,
+            &mut contract,
+            accounts(3),
+            vec![(accounts(1), to_yocto("5")), (accounts(2), to_yocto("10"))],
+        );
+        let health = contract.get_pool_health(pool_id);
+        assert_eq!(health.imbalance_bps, None);
+        assert_eq!(health.oracle_staleness_sec, None);
+        assert_eq!(health.tvl_utilization_bps, None);
+        assert_eq!(health.recent_volume_bps, vec![0, 0]);
+        assert_eq!(health.fee_apr_bps, None);
+
+        testing_env!(context.predecessor_account_id(accounts(4)).attached_deposit(to_yocto("0.01")).build());
+        contract.checkpoint_pool_share_price(pool_id);
+        deposit_tokens(&mut context, &mut contract, accounts(3), vec![(accounts(1), to_yocto("1"))]);
+        testing_env!(context.predecessor_account_id(accounts(3)).attached_deposit(1).build());
+        swap(&mut contract, pool_id, accounts(1), to_yocto("1"), accounts(2));
+        // 1 in against a reserve of 6.
+        assert_eq!(contract.get_pool_health(pool_id).recent_volume_bps[0], 1666);
+
+        testing_env!(context.block_timestamp(crate::utils::to_nano(POOL_VOLUME_WINDOW_SEC)).build());
+        let health = contract.get_pool_health(pool_id);
+        assert_eq!(health.recent_volume_bps[0], 1666);
+        assert!(health.fee_apr_bps.unwrap() > 0);
+
+        testing_env!(context.block_timestamp(crate::utils::to_nano(2 * POOL_VOLUME_WINDOW_SEC)).build());
+        assert_eq!(contract.get_pool_health(pool_id).recent_volume_bps, vec![0, 0]);
+    }
+
+    #[test]
+    fn test_batched_supply_and_share_price_views() {
+        let (mut context, mut contract) = setup_contract();
+        let pool_id = create_pool_with_liquidity(
+            &mut context,
+            &mut contract,
+            accounts(3),
+            vec![(accounts(1), to_yocto("5")), (accounts(2), to_yocto("10"))],
+        );
+        let other_pool_id = create_pool_with_liquidity(
+            &mut context,
+            &mut contract,
+            accounts(3),
+            vec![(accounts(1), to_yocto("1")), (accounts(4), to_yocto("1"))],
+        );
+        assert_eq!(
+            contract.mft_total_supply_batch(vec![format!(":{}", other_pool_id), format!(":{}", pool_id)]),
+            vec![contract.get_pool_total_shares(other_pool_id), contract.get_pool_total_shares(pool_id)]
+        );
+        assert_eq!(
+            contract.get_share_prices(vec![pool_id, other_pool_id]),
+            vec![contract.get_pool_share_price(pool_id), contract.get_pool_share_price(other_pool_id)]
+        );
+    }
+
+    #[test]
+    fn test_extra_wnear_ids() {
+        let (mut context, mut contract) = setup_contract();
+        testing_env!(context.predecessor_account_id(accounts(0)).attached_deposit(1).build());
+        assert!(contract.get_wnear_ids().is_empty());
+        contract.modify_wnear_id(accounts(1).to_string());
+        contract.extend_extra_wnear_ids(vec![accounts(2), accounts(4), accounts(2)]);
+        assert_eq!(contract.get_wnear_ids(), vec![accounts(1).to_string(), accounts(2).to_string(), accounts(4).to_string()]);
+        assert!(contract.is_wrapped_near(&accounts(4).to_string()));
+        contract.remove_extra_wnear_ids(vec![accounts(2)]);
+        assert!(!contract.is_wrapped_near(&accounts(2).to_string()));
+        assert_eq!(contract.get_wnear_ids(), vec![accounts(1).to_string(), accounts(4).to_string()]);
+    }
+
+    #[test]
+    fn test_account_preferences() {
+        let (mut context, mut contract) = setup_contract();
+        let pool_id = create_pool_with_liquidity(
+            &mut context,
+            &mut contract,
+            accounts(3),
+            vec![(accounts(1), to_yocto("5")), (accounts(2), to_yocto("10"))],
+        );
+        deposit_tokens(&mut context, &mut contract, accounts(3), vec![(accounts(1), to_yocto("1"))]);
+        let preferences = AccountPreferences { max_slippage_bps: Some(100), deadline_sec: Some(60), auto_withdraw: true };
+        testing_env!(context.predecessor_account_id(accounts(3)).attached_deposit(to_yocto("0.01")).build());
+        contract.set_account_preferences(Some(preferences.clone()));
+        assert_eq!(contract.get_account_preferences(accounts(3)), Some(preferences));
+
+        let quote = contract.get_return(pool_id, accounts(1), U128(to_yocto("1")), accounts(2));
+        testing_env!(context.predecessor_account_id(accounts(3)).block_timestamp(crate::utils::to_nano(60)).attached_deposit(1).build());
+        let amount_out = contract.swap_with_quote(
+            vec![SwapAction {
+                pool_id,
+                token_in: accounts(1).into(),
+                amount_in: Some(U128(to_yocto("1"))),
+                token_out: accounts(2).into(),
+                min_amount_out: U128(0),
+            }],
+            None,
+            RouteQuote { amounts_out: vec![U128(quote.0 * 1005 / 1000)], tolerance_bps: None, quoted_at: Some(near_sdk::json_types::U64(0)), deadline: None },
+        );
+        assert_eq!(amount_out, quote);
+        // The output went on to the wallet.
+        assert_eq!(contract.get_deposit(accounts(3), accounts(2)).0, 0);
+    }
+
+    #[test]
+    fn test_actions_gas_budget() {
+        let (mut context, mut contract) = setup_contract();
+        let pool_id = create_pool_with_liquidity(
+            &mut context,
+            &mut contract,
+            accounts(3),
+            vec![(accounts(1), to_yocto("5")), (accounts(2), to_yocto("10"))],
+        );
+        deposit_tokens(&mut context, &mut contract, accounts(3), vec![(accounts(1), to_yocto("1"))]);
+        let actions = vec![Action::Swap(SwapAction {
+            pool_id,
+            token_in: accounts(1).into(),
+            amount_in: Some(U128(to_yocto("1"))),
+            token_out: accounts(2).into(),
+            min_amount_out: U128(1),
+        })];
+        let hint = near_sdk::json_types::U64(15_000_000_000_000);
+        assert_eq!(
+            contract.get_actions_gas_budget(accounts(3), actions.clone(), vec![None]).0,
+            DEFAULT_ACTION_GAS + crate::utils::GAS_FOR_BASIC_OP
+        );
+        testing_env!(context.predecessor_account_id(accounts(3)).attached_deposit(to_yocto("0.01")).build());
+        contract.set_account_preferences(Some(AccountPreferences { max_slippage_bps: None, deadline_sec: None, auto_withdraw: true }));
+        assert_eq!(
+            contract.get_actions_gas_budget(accounts(3), actions.clone(), vec![Some(hint)]).0,
+            hint.0 + crate::utils::GAS_FOR_BASIC_OP + GAS_FOR_WITHDRAW_WITH_CALLBACK
+        );
+
+        testing_env!(context.predecessor_account_id(accounts(3)).attached_deposit(1).build());
+        contract.execute_actions_with_gas_hints(actions, None, vec![Some(hint)]);
+        assert_eq!(contract.get_deposit(accounts(3), accounts(1)).0, 0);
+    }
+
+    #[test]
+    #[should_panic(expected = "Not enough gas for the actions")]
+    fn test_actions_gas_budget_exceeded() {
+        let (mut context, mut contract) = setup_contract();
+        let pool_id = create_pool_with_liquidity(
+            &mut context,
+            &mut contract,
+            accounts(3),
+            vec![(accounts(1), to_yocto("5")), (accounts(2), to_yocto("10"))],
+        );
+        deposit_tokens(&mut context, &mut contract, accounts(3), vec![(accounts(1), to_yocto("1"))]);
+        testing_env!(context.predecessor_account_id(accounts(3)).attached_deposit(1).build());
+        contract.execute_actions_with_gas_hints(
+            vec![Action::Swap(SwapAction {
+                pool_id,
+                token_in: accounts(1).into(),
+                amount_in: Some(U128(to_yocto("1"))),
+                token_out: accounts(2).into(),
+                min_amount_out: U128(1),
+            })],
+            None,
+            vec![Some(near_sdk::json_types::U64(400_000_000_000_000))],
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Route quote deadline 60000000000 passed")]
+    fn test_account_preferences_deadline() {
+        let (mut context, mut contract) = setup_contract();
+        let pool_id = create_pool_with_liquidity(
+            &mut context,
+            &mut contract,
+            accounts(3),
+            vec![(accounts(1), to_yocto("5")), (accounts(2), to_yocto("10"))],
+        );
+        deposit_tokens(&mut context, &mut contract, accounts(3), vec![(accounts(1), to_yocto("1"))]);
+        testing_env!(context.predecessor_account_id(accounts(3)).attached_deposit(to_yocto("0.01")).build());
+        contract.set_account_preferences(Some(AccountPreferences { max_slippage_bps: Some(100), deadline_sec: Some(60), auto_withdraw: false }));
+        let quote = contract.get_return(pool_id, accounts(1), U128(to_yocto("1")), accounts(2));
+        testing_env!(context.predecessor_account_id(accounts(3)).block_timestamp(crate::utils::to_nano(61)).attached_deposit(1).build());
+        contract.swap_with_quote(
+            round_trip_actions(pool_id, to_yocto("1"))[..1].to_vec(),
+            None,
+            RouteQuote { amounts_out: vec![quote], tolerance_bps: None, quoted_at: Some(near_sdk::json_types::U64(0)), deadline: None },
+        );
+    }
+
+    #[test]
+    fn test_deposit_and_withdraw_with_memo() {
+        let (mut context, mut contract) = setup_contract();
+        deposit_tokens(&mut context, &mut contract, accounts(3), vec![(accounts(1), 1_000)]);
+        testing_env!(context.predecessor_account_id(accounts(1)).attached_deposit(0).build());
+        contract.ft_on_transfer(accounts(3), U128(500), "{\"memo\":\"invoice 42\"}".to_string());
+        assert_eq!(contract.get_deposit(accounts(3), accounts(1)).0, 1_500);
+        assert!(near_sdk::test_utils::get_logs().iter().any(|log| log.contains("invoice 42")));
+
+        testing_env!(context.predecessor_account_id(accounts(3)).attached_deposit(1).build());
+        contract.withdraw_with_memo(accounts(1), U128(600), None, None, "payout 7".to_string());
+        assert_eq!(contract.get_deposit(accounts(3), accounts(1)).0, 900);
+        assert!(near_sdk::test_utils::get_logs().iter().any(|log| log.contains("payout 7")));
+    }
+
+    #[test]
+    #[should_panic(expected = "Memo must be 1 to 256 bytes")]
+    fn test_withdraw_memo_too_long() {
+        let (mut context, mut contract) = setup_contract();
+        deposit_tokens(&mut context, &mut contract, accounts(3), vec![(accounts(1), 1_000)]);
+        testing_env!(context.predecessor_account_id(accounts(3)).attached_deposit(1).build());
+        contract.withdraw_with_memo(accounts(1), U128(600), None, None, "x".repeat(MAX_MEMO_LEN + 1));
+    }
+
+    #[test]
+    fn test_referral_fee_opt_out() {
+        let (mut context, mut contract) = setup_contract();
+        let token_amounts = vec![(accounts(1), to_yocto("5")), (accounts(2), to_yocto("10"))];
+        let pool_id = create_pool_with_liquidity(&mut context, &mut contract, accounts(3), token_amounts.clone());
+        // owner may duplicate the pool
+        testing_env!(context
+            .predecessor_account_id(accounts(0))
+            .attached_deposit(env::storage_byte_cost() * 1400)
+            .build());
+        let opt_out_pool_id = contract.add_simple_pool_without_referral_fee(vec![accounts(1), accounts(2)], 25);
+        assert!(contract.is_referral_fee_opted_out(opt_out_pool_id));
+        assert!(!contract.is_referral_fee_opted_out(pool_id));
+        deposit_tokens(&mut context, &mut contract, accounts(3), token_amounts.clone());
+        testing_env!(context.predecessor_account_id(accounts(3)).attached_deposit(to_yocto("0.0007")).build());
+        contract.add_liquidity(opt_out_pool_id, token_amounts.into_iter().map(|(_, x)| U128(x)).collect(), None);
+        testing_env!(context.predecessor_account_id(accounts(0)).attached_deposit(1).build());
+        contract.insert_referral(accounts(3), 1000);
+
+        let breakdown = contract.get_return_with_breakdown(pool_id, accounts(1), U128(to_yocto("1")), accounts(2), Some(accounts(3)));
+        let opt_out_breakdown = contract.get_return_with_breakdown(opt_out_pool_id, accounts(1), U128(to_yocto("1")), accounts(2), Some(accounts(3)));
+        assert_eq!(opt_out_breakdown.amount_out.0, breakdown.amount_out.0);
+        assert_eq!(opt_out_breakdown.referral_fee.0, 0);
+        assert!(opt_out_breakdown.admin_fee.0.abs_diff(breakdown.admin_fee.0 - breakdown.referral_fee.0) <= 1);
+        assert!(opt_out_breakdown.lp_fee.0.abs_diff(breakdown.lp_fee.0 + breakdown.referral_fee.0) <= 1);
+    }
+
+    #[test]
+    #[should_panic(expected = "Withdrawal exceeds 1000 bps of pool reserves")]
+    fn test_pool_withdrawal_cap() {
+        let (mut context, mut contract) = setup_contract();
+        testing_env!(context
+            .predecessor_account_id(accounts(0))
+            .attached_deposit(env::storage_byte_cost() * 1334)
+            .build());
+        let pool_id = contract.add_stable_swap_pool(vec![accounts(1), accounts(2)], vec![18, 18], 25, 240);
+        deposit_tokens(&mut context, &mut contract, accounts(3), vec![(accounts(1), to_yocto("4")), (accounts(2), to_yocto("4"))]);
+        testing_env!(context.predecessor_account_id(accounts(3)).attached_deposit(to_yocto("0.0007")).build());
+        contract.add_stable_liquidity(pool_id, vec![to_yocto("4").into(), to_yocto("4").into()], U128(1));
+        testing_env!(context.predecessor_account_id(accounts(0)).attached_deposit(1).build());
+        contract.set_pool_withdrawal_cap(pool_id, Some(1000));
+        assert_eq!(contract.get_pool_withdrawal_cap(pool_id), Some(1000));
+
+        testing_env!(context.predecessor_account_id(accounts(3)).attached_deposit(1).build());
+        contract.remove_liquidity(pool_id, to_yocto("0.8").into(), vec![1.into(), 1.into()]);
+        contract.remove_liquidity_by_tokens(pool_id, vec![to_yocto("0.3").into(), to_yocto("0.3").into()], to_yocto("1").into());
+        contract.remove_liquidity(pool_id, to_yocto("1").into(), vec![1.into(), 1.into()]);
+    }
+
+    #[test]
+    #[should_panic(expected = "Swap exceeds pool swap cap")]
+    fn test_pool_swap_cap() {
+        let (mut context, mut contract) = setup_contract();
+        let pool_id = create_pool_with_liquidity(
+            &mut context,
+            &mut contract,
+            accounts(3),
+            vec![(accounts(1), to_yocto("5")), (accounts(2), to_yocto("10"))],
+        );
+        testing_env!(context.predecessor_account_id(accounts(0)).attached_deposit(1).build());
+        contract.set_pool_swap_cap(pool_id, Some(3000));
+        assert_eq!(contract.get_pool_swap_cap(pool_id), Some(3000));
+        deposit_tokens(&mut context, &mut contract, accounts(3), vec![(accounts(1), to_yocto("10"))]);
+        testing_env!(context.predecessor_account_id(accounts(3)).attached_deposit(1).build());
+        // Takes about 1.7 of the 10 reserve.
+        swap(&mut contract, pool_id, accounts(1), to_yocto("1"), accounts(2));
+        // Would take over 3 of the remaining reserve.
+        swap(&mut contract, pool_id, accounts(1), to_yocto("5"), accounts(2));
+    }
+
+    #[test]
+    #[should_panic(expected = "Swap exceeds pool swap cap")]
+    fn test_pool_swap_cap_get_return() {
+        let (mut context, mut contract) = setup_contract();
+        let pool_id = create_pool_with_liquidity(
+            &mut context,
+            &mut contract,
+            accounts(3),
+            vec![(accounts(1), to_yocto("5")), (accounts(2), to_yocto("10"))],
+        );
+        testing_env!(context.predecessor_account_id(accounts(0)).attached_deposit(1).build());
+        contract.set_pool_swap_cap(pool_id, Some(3000));
+        // Quotes under the cap go through, those over it fail as the swap would.
+        contract.get_return(pool_id, accounts(1), to_yocto("1").into(), accounts(2));
+        contract.get_return(pool_id, accounts(1), to_yocto("5").into(), accounts(2));
+    }
+
+    #[test]
+    #[should_panic(expected = "Degen price of charlie too old for a swap this large")]
+    fn test_degen_fresh_price_rule() {
+        let (mut context, _contract) = setup_contract();
+        let rule = DegenFreshPriceRule { min_swap_bps: 100, max_price_age_sec: 10 };
+        let token_ids = vec![accounts(1).to_string(), accounts(2).to_string()];
+        testing_env!(context.block_timestamp(crate::utils::to_nano(100)).build());
+        let updated_at = vec![crate::utils::to_nano(95), crate::utils::to_nano(80)];
+        // small swaps go through on the stale price
+        rule.assert_fresh(&token_ids, &updated_at, 100, 10_000);
+        rule.assert_fresh(&token_ids, &[crate::utils::to_nano(95); 2], 101, 10_000);
+        rule.assert_fresh(&token_ids, &updated_at, 101, 10_000);
+    }
+
+    #[test]
+    fn test_lp_incentive() {
+        let (mut context, mut contract) = setup_contract();
+        let pool_id = create_pool_with_liquidity(
+            &mut context,
+            &mut contract,
+            accounts(3),
+            vec![(accounts(1), to_yocto("5")), (accounts(2), to_yocto("10"))],
+        );
+        deposit_tokens(&mut context, &mut contract, accounts(4), vec![(accounts(1), 1_000)]);
+        testing_env!(context
+            .predecessor_account_id(accounts(4))
+            .block_timestamp(crate::utils::to_nano(1000))
+            .attached_deposit(to_yocto("0.01"))
+            .build());
+        contract.create_lp_incentive(pool_id, accounts(1), U128(1_000), 100);
+        assert_eq!(contract.get_deposit(accounts(4), accounts(1)).0, 0);
+
+        testing_env!(context.block_timestamp(crate::utils::to_nano(1050)).attached_deposit(1).build());
+        assert_eq!(contract.get_claimable_lp_incentive(pool_id, accounts(3)).0, 500);
+        let balance = contract.get_deposit(accounts(3), accounts(1)).0;
+        testing_env!(context.predecessor_account_id(accounts(3)).build());
+        assert_eq!(contract.claim_lp_incentive(pool_id).0, 500);
+        assert_eq!(contract.get_deposit(accounts(3), accounts(1)).0, balance + 500);
+
+        testing_env!(context.block_timestamp(crate::utils::to_nano(1200)).build());
+        assert_eq!(contract.get_claimable_lp_incentive(pool_id, accounts(3)).0, 500);
+        assert_eq!(contract.get_lp_incentive(pool_id).unwrap().claimed, 500);
+        testing_env!(context.predecessor_account_id(accounts(4)).build());
+        assert_eq!(contract.close_lp_incentive(pool_id).0, 0);
+    }
+
+    fn setup_launch_auction(context: &mut VMContextBuilder, contract: &mut Contract) -> u64 {
+        testing_env!(context.predecessor_account_id(accounts(0)).attached_deposit(1).build());
+        contract.extend_whitelisted_tokens(vec![accounts(1), accounts(2)]);
+        deposit_tokens(context, contract, accounts(3), vec![(accounts(1), to_yocto("10")), (accounts(2), 1)]);
+        testing_env!(context
+            .predecessor_account_id(accounts(3))
+            .block_timestamp(crate::utils::to_nano(1000))
+            .attached_deposit(env::storage_byte_cost() * 1600)
+            .build());
+        contract.add_simple_pool_with_launch_auction(vec![accounts(1), accounts(2)], 25, LaunchAuctionParams {
+            sale_token: accounts(1),
+            sale_amount: U128(to_yocto("8")),
+            seed_amount: U128(to_yocto("2")),
+            start_price: U128(2 * LAUNCH_PRICE_PRECISION),
+            end_price: U128(LAUNCH_PRICE_PRECISION),
+            duration_sec: 100,
+        })
+    }
+
+    #[test]
+    fn test_launch_auction() {
+        let (mut context, mut contract) = setup_contract();
+        let pool_id = setup_launch_auction(&mut context, &mut contract);
+        assert_eq!(contract.get_deposit(accounts(3), accounts(1)).0, 0);
+        deposit_tokens(&mut context, &mut contract, accounts(4), vec![(accounts(2), to_yocto("10")), (accounts(1), 1)]);
+
+        testing_env!(context.predecessor_account_id(accounts(4)).block_timestamp(crate::utils::to_nano(1050)).attached_deposit(1).build());
+        assert_eq!(contract.get_launch_auction_price(pool_id).unwrap().0, 3 * LAUNCH_PRICE_PRECISION / 2);
+        assert_eq!(contract.bid_launch_auction(pool_id, U128(to_yocto("3")), U128(to_yocto("2"))).0, to_yocto("2"));
+        assert_eq!(contract.get_deposit(accounts(4), accounts(1)).0, to_yocto("2") + 1);
+        assert_eq!(contract.get_deposit(accounts(4), accounts(2)).0, to_yocto("7"));
+        let detail = contract.get_deposits_detail(accounts(3));
+        assert_eq!(detail.tokens[&accounts(1).to_string()].locked.0, to_yocto("8"));
+        assert_eq!(detail.tokens[&accounts(2).to_string()].locked.0, to_yocto("3"));
+
+        testing_env!(context
+            .predecessor_account_id(accounts(3))
+            .block_timestamp(crate::utils::to_nano(1100))
+            .attached_deposit(to_yocto("0.01"))
+            .build());
+        contract.settle_launch_auction(pool_id);
+        assert!(contract.get_launch_auction(pool_id).is_none());
+        let detail = contract.get_deposits_detail(accounts(3));
+        assert_eq!(detail.tokens[&accounts(1).to_string()].locked.0, 0);
+        assert_eq!(detail.tokens[&accounts(2).to_string()].locked.0, 0);
+        assert_eq!(contract.get_pool(pool_id).amounts, vec![U128(to_yocto("2")), U128(to_yocto("3"))]);
+        assert_eq!(contract.get_deposit(accounts(3), accounts(1)).0, to_yocto("6"));
+        assert_eq!(contract.get_deposit(accounts(3), accounts(2)).0, 1);
+        assert!(contract.get_pool_shares(pool_id, accounts(3)).0 > 0);
+    }
+
+    #[test]
+    fn test_launch_auction_sold_out() {
+        let (mut context, mut contract) = setup_contract();
+        let pool_id = setup_launch_auction(&mut context, &mut contract);
+        deposit_tokens(&mut context, &mut contract, accounts(4), vec![(accounts(2), to_yocto("20")), (accounts(1), 1)]);
+
+        testing_env!(context.predecessor_account_id(accounts(4)).block_timestamp(crate::utils::to_nano(1050)).attached_deposit(1).build());
+        assert_eq!(contract.bid_launch_auction(pool_id, U128(to_yocto("20")), U128(1)).0, to_yocto("8"));
+
+        // anyone settles once the end passed, the seed reserve still opens the pool
+        testing_env!(context
+            .predecessor_account_id(accounts(4))
+            .block_timestamp(crate::utils::to_nano(1100))
+            .attached_deposit(to_yocto("0.01"))
+            .build());
+        contract.settle_launch_auction(pool_id);
+        assert_eq!(contract.get_pool(pool_id).amounts, vec![U128(to_yocto("2")), U128(to_yocto("3"))]);
+        assert_eq!(contract.get_deposit(accounts(3), accounts(1)).0, 0);
+        assert_eq!(contract.get_deposit(accounts(3), accounts(2)).0, to_yocto("9") + 1);
+        assert!(contract.get_pool_shares(pool_id, accounts(3)).0 > 0);
+    }
+
+    #[test]
+    fn test_launch_auction_creator_unregistered() {
+        let (mut context, mut contract) = setup_contract();
+        let pool_id = setup_launch_auction(&mut context, &mut contract);
+        testing_env!(context.predecessor_account_id(accounts(3)).attached_deposit(1).build());
+        contract.unregister_tokens(vec![accounts(1)]);
+        deposit_tokens(&mut context, &mut contract, accounts(4), vec![(accounts(2), to_yocto("10")), (accounts(1), 1)]);
+        testing_env!(context.predecessor_account_id(accounts(4)).block_timestamp(crate::utils::to_nano(1050)).attached_deposit(1).build());
+        contract.bid_launch_auction(pool_id, U128(to_yocto("3")), U128(1));
+
+        testing_env!(context
+            .predecessor_account_id(accounts(4))
+            .block_timestamp(crate::utils::to_nano(1100))
+            .attached_deposit(to_yocto("0.01"))
+            .build());
+        assert_eq!(contract.settle_launch_auction(pool_id).0, 0);
+        assert!(contract.get_launch_auction(pool_id).is_none());
+        assert_eq!(contract.get_pool(pool_id).amounts, vec![U128(0), U128(0)]);
+        assert_eq!(contract.get_unclaimed_withdrawal(accounts(3), accounts(1)).unwrap().amount, to_yocto("8"));
+        assert_eq!(contract.get_unclaimed_withdrawal(accounts(3), accounts(2)).unwrap().amount, to_yocto("3"));
+        // held for reclaim, still the creator's
+        assert_eq!(contract.get_deposits_detail(accounts(3)).tokens[&accounts(1).to_string()].locked.0, to_yocto("8"));
+    }
+
+    #[test]
+    #[should_panic(expected = "Daily withdrawal limit of 2000000000000000000000000 charlie exceeded")]
+    fn test_launch_auction_bid_withdrawal_limit() {
+        let (mut context, mut contract) = setup_contract();
+        let pool_id = setup_launch_auction(&mut context, &mut contract);
+        deposit_tokens(&mut context, &mut contract, accounts(4), vec![(accounts(2), to_yocto("10")), (accounts(1), 1)]);
+        testing_env!(context.predecessor_account_id(accounts(4)).attached_deposit(to_yocto("0.01")).build());
+        contract.set_daily_withdrawal_limit(accounts(2).into(), Some(U128(to_yocto("2"))));
+
+        testing_env!(context.predecessor_account_id(accounts(4)).block_timestamp(crate::utils::to_nano(1050)).attached_deposit(1).build());
+        contract.bid_launch_auction(pool_id, U128(to_yocto("3")), U128(to_yocto("2")));
+    }
+
+    #[test]
+    #[should_panic(expected = "Pool in launch auction")]
+    fn test_launch_auction_blocks_swaps() {
+        let (mut context, mut contract) = setup_contract();
+        let pool_id = setup_launch_auction(&mut context, &mut contract);
+        testing_env!(context.predecessor_account_id(accounts(3)).attached_deposit(1).build());
+        swap(&mut contract, pool_id, accounts(2), 1, accounts(1));
+    }
+
+    #[test]
+    #[should_panic(expected = "Account danny denied")]
+    fn test_denied_account() {
+        let (mut context, mut contract) = setup_contract();
+        let pool_id = create_pool_with_liquidity(
+            &mut context,
+            &mut contract,
+            accounts(3),
+            vec![(accounts(1), to_yocto("5")), (accounts(2), to_yocto("10"))],
+        );
+        deposit_tokens(&mut context, &mut contract, accounts(3), vec![(accounts(1), to_yocto("1"))]);
+        testing_env!(context.predecessor_account_id(accounts(0)).attached_deposit(1).build());
+        contract.extend_denied_accounts(vec![accounts(3)]);
+        assert!(contract.is_account_denied(accounts(3)));
+        assert_eq!(contract.get_denied_accounts(None, None), vec![accounts(3).to_string()]);
+
+        testing_env!(context.predecessor_account_id(accounts(3)).attached_deposit(1).build());
+        contract.withdraw(accounts(1), U128(to_yocto("0.5")), None, None);
+        assert_eq!(contract.get_deposit(accounts(3), accounts(1)).0, to_yocto("0.5"));
+        swap(&mut contract, pool_id, accounts(1), to_yocto("0.5"), accounts(2));
+    }
+
+    #[test]
+    #[should_panic(expected = "denied")]
+    fn test_denied_account_mft_transfer() {
+        let (mut context, mut contract) = setup_contract();
+        let pool_id = create_pool_with_liquidity(
+            &mut context,
+            &mut contract,
+            accounts(3),
+            vec![(accounts(1), to_yocto("5")), (accounts(2), to_yocto("10"))],
+        );
+        testing_env!(context
+            .predecessor_account_id(accounts(4))
+            .attached_deposit(to_yocto("0.00071"))
+            .build());
+        contract.mft_register(format!(":{}", pool_id), accounts(4));
+        testing_env!(context.predecessor_account_id(accounts(0)).attached_deposit(1).build());
+        contract.extend_denied_accounts(vec![accounts(3)]);
+        testing_env!(context.predecessor_account_id(accounts(3)).attached_deposit(1).build());
+        contract.mft_transfer(format!(":{}", pool_id), accounts(4), U128(1), None);
+    }
+
+    fn setup_twap_order(context: &mut VMContextBuilder, contract: &mut Contract) -> (u64, u64) {
+        let pool_id = create_pool_with_liquidity(
+            context,
+            contract,
+            accounts(3),
+            vec![(accounts(1), to_yocto("5")), (accounts(2), to_yocto("10"))],
+        );
+        deposit_tokens(context, contract, accounts(3), vec![(accounts(1), to_yocto("1")), (accounts(2), 1)]);
+        testing_env!(context.predecessor_account_id(accounts(3)).attached_deposit(to_yocto("0.01")).build());
+        let order_id = contract.create_twap_order(TwapOrderParams {
+            pool_id,
+            token_in: accounts(1),
+            token_out: accounts(2),
+            amount_in: U128(to_yocto("1")),
+            tranches: 2,
+            interval_sec: 60,
+            min_price: U128(TWAP_PRICE_PRECISION),
+            max_deviation_bps: 3000,
+        });
+        (pool_id, order_id)
+    }
+
+    #[test]
+    fn test_twap_order() {
+        let (mut context, mut contract) = setup_contract();
+        let (pool_id, order_id) = setup_twap_order(&mut context, &mut contract);
+        assert_eq!(contract.get_deposit(accounts(3), accounts(1)).0, 0);
+
+        testing_env!(context.predecessor_account_id(accounts(4)).attached_deposit(0).build());
+        let expected_out = contract.get_return(pool_id, accounts(1), U128(to_yocto("0.5")), accounts(2)).0;
+        assert_eq!(contract.execute_twap_tranche(order_id).0, expected_out);
+        let order = contract.get_twap_order(order_id).unwrap();
+        assert_eq!((order.remaining_in, order.remaining_tranches), (to_yocto("0.5"), 1));
+
+        testing_env!(context.block_timestamp(crate::utils::to_nano(60)).build());
+        let storage_deposit = contract.get_user_storage_state(accounts(3)).unwrap().deposit.0;
+        let amount_out = expected_out + contract.execute_twap_tranche(order_id).0;
+        assert!(contract.get_twap_order(order_id).is_none());
+        assert_eq!(contract.get_deposit(accounts(3), accounts(2)).0, amount_out + 1);
+        // the owner gets the order's storage back
+        assert!(contract.get_user_storage_state(accounts(3)).unwrap().deposit.0 > storage_deposit);
+    }
+
+    #[test]
+    fn test_cancel_twap_order() {
+        let (mut context, mut contract) = setup_contract();
+        let (_, order_id) = setup_twap_order(&mut context, &mut contract);
+        let storage_deposit = contract.get_user_storage_state(accounts(3)).unwrap().deposit.0;
+        testing_env!(context.predecessor_account_id(accounts(3)).attached_deposit(1).build());
+        assert_eq!(contract.cancel_twap_order(order_id).0, to_yocto("1"));
+        assert!(contract.get_twap_order(order_id).is_none());
+        assert_eq!(contract.get_deposit(accounts(3), accounts(1)).0, to_yocto("1"));
+        assert!(contract.get_user_storage_state(accounts(3)).unwrap().deposit.0 > storage_deposit);
+    }
+
+    #[test]
+    fn test_deposits_detail() {
+        let (mut context, mut contract) = setup_contract();
+        let (_, order_id) = setup_twap_order(&mut context, &mut contract);
+        let detail = contract.get_deposits_detail(accounts(3));
+        assert_eq!(detail.tokens.get(accounts(1).as_ref()), Some(&TokenDepositDetail {
+            available: U128(0),
+            pending_withdrawal: U128(0),
+            locked: U128(to_yocto("1")),
+            total: U128(to_yocto("1")),
+        }));
+        assert!(detail.shares.is_empty());
+
+        let available = contract.get_deposit(accounts(3), accounts(2)).0;
+        testing_env!(context.predecessor_account_id(accounts(3)).attached_deposit(1).build());
+        contract.withdraw(accounts(2), U128(1), None, None);
+        let token_detail = contract.get_deposits_detail(accounts(3)).tokens.remove(accounts(2).as_ref()).unwrap();
+        assert_eq!((token_detail.available.0, token_detail.pending_withdrawal.0), (available - 1, 1));
+        assert_eq!(token_detail.total.0, available);
+
+        testing_env!(
+            context.predecessor_account_id(env::current_account_id().try_into().unwrap()).build(),
+            Default::default(),
+            Default::default(),
+            Default::default(),
+            vec![PromiseResult::Successful(vec![])]
+        );
+        contract.exchange_callback_post_withdraw(accounts(2).into(), accounts(3).into(), U128(1), None, None);
+        testing_env!(context.predecessor_account_id(accounts(3)).attached_deposit(1).build());
+        contract.cancel_twap_order(order_id);
+        let detail = contract.get_deposits_detail(accounts(3));
+        assert_eq!(detail.tokens.get(accounts(2).as_ref()).unwrap().pending_withdrawal.0, 0);
+        assert_eq!(detail.tokens.get(accounts(1).as_ref()).unwrap().locked.0, 0);
+        assert_eq!(detail.tokens.get(accounts(1).as_ref()).unwrap().available.0, to_yocto("1"));
+    }
+
+    #[test]
+    #[should_panic(expected = "TWAP tranche not due")]
+    fn test_twap_order_not_due() {
+        let (mut context, mut contract) = setup_contract();
+        let (_, order_id) = setup_twap_order(&mut context, &mut contract);
+        testing_env!(context.predecessor_account_id(accounts(4)).attached_deposit(0).build());
+        contract.execute_twap_tranche(order_id);
+        contract.execute_twap_tranche(order_id);
+    }
+
+    #[test]
+    #[should_panic(expected = "E68: slippage error")]
+    fn test_twap_tranche_below_average_price() {
+        let (mut context, mut contract) = setup_contract();
+        let (pool_id, order_id) = setup_twap_order(&mut context, &mut contract);
+        testing_env!(context.predecessor_account_id(accounts(4)).attached_deposit(0).build());
+        contract.execute_twap_tranche(order_id);
+
+        // pushing the price down keeps it above min_price, but too far below the first tranche
+        deposit_tokens(&mut context, &mut contract, accounts(4), vec![(accounts(1), to_yocto("1"))]);
+        testing_env!(context.predecessor_account_id(accounts(4)).attached_deposit(1).build());
+        swap(&mut contract, pool_id, accounts(1), to_yocto("1"), accounts(2));
+        testing_env!(context.block_timestamp(crate::utils::to_nano(60)).attached_deposit(0).build());
+        contract.execute_twap_tranche(order_id);
+    }
+
+    #[test]
+    fn test_spot_price_and_last_trade() {
+        let (mut context, mut contract) = setup_contract();
+        let pool_id = create_pool_with_liquidity(
+            &mut context,
+            &mut contract,
+            accounts(3),
+            vec![(accounts(1), to_yocto("5")), (accounts(2), to_yocto("10"))],
+        );
+        let spot_price = contract.get_spot_price(pool_id, accounts(1), accounts(2));
+        assert_eq!(spot_price.price.0, 2 * MARKET_PRICE_PRECISION);
+        assert!(!spot_price.normalized);
+        assert!(contract.get_last_trade(pool_id).is_none());
+
+        deposit_tokens(&mut context, &mut contract, accounts(3), vec![(accounts(1), to_yocto("1"))]);
+        testing_env!(context.predecessor_account_id(accounts(3)).attached_deposit(1).build());
+        let amount_out = swap(&mut contract, pool_id, accounts(1), to_yocto("1"), accounts(2));
+        let last_trade = contract.get_last_trade(pool_id).unwrap();
+        assert_eq!((last_trade.trade.amount_in, last_trade.trade.amount_out), (to_yocto("1"), amount_out));
+        assert_eq!(last_trade.price.price.0, crate::utils::u128_ratio(amount_out, MARKET_PRICE_PRECISION, to_yocto("1")));
+
+        testing_env!(context
+            .predecessor_account_id(accounts(0))
+            .attached_deposit(env::storage_byte_cost() * 1334)
+            .build());
+        let stable_pool_id = contract.add_stable_swap_pool(vec![accounts(1), accounts(2)], vec![18, 6], 25, 240);
+        deposit_tokens(&mut context, &mut contract, accounts(3), vec![(accounts(1), 4 * 10u128.pow(18)), (accounts(2), 4_000_000)]);
+        testing_env!(context.predecessor_account_id(accounts(3)).attached_deposit(to_yocto("0.0007")).build());
+        contract.add_stable_liquidity(stable_pool_id, vec![(4 * 10u128.pow(18)).into(), 4_000_000.into()], U128(1));
+        let spot_price = contract.get_spot_price(stable_pool_id, accounts(2), accounts(1));
+        assert!(spot_price.normalized);
+        // balanced pool, one whole token for about one whole token
+        assert!(spot_price.price.0 > MARKET_PRICE_PRECISION * 999 / 1000 && spot_price.price.0 <= MARKET_PRICE_PRECISION);
+    }
+
+    #[test]
+    fn test_epoch_fee_report() {
+        let (mut context, mut contract) = setup_contract();
+        let pool_id = create_pool_with_liquidity(
+            &mut context,
+            &mut contract,
+            accounts(3),
+            vec![(accounts(1), to_yocto("5")), (accounts(2), to_yocto("10"))],
+        );
+        deposit_tokens(&mut context, &mut contract, accounts(3), vec![(accounts(1), to_yocto("2"))]);
+        testing_env!(context.predecessor_account_id(accounts(3)).epoch_height(7).attached_deposit(1).build());
+        swap(&mut contract, pool_id, accounts(1), to_yocto("1"), accounts(2));
+        swap(&mut contract, pool_id, accounts(1), to_yocto("1"), accounts(2));
+        assert!(contract.get_epoch_fee_report(6).is_empty());
+        let report = contract.get_epoch_fee_report(7);
+        assert_eq!(report.len(), 1);
+        assert_eq!((report[0].pool_id, report[0].swap_count), (pool_id, 2));
+        assert_eq!(report[0].token_account_ids, vec![accounts(1).to_string(), accounts(2).to_string()]);
+        assert_eq!(report[0].shares, contract.get_pool_shares(pool_id, env::current_account_id().try_into().unwrap()).0);
+        assert!(report[0].amounts.iter().all(|amount| amount.0 > 0));
+    }
+
+    #[test]
+    fn test_prune_pool_volume_stats() {
+        let (mut context, mut contract) = setup_contract();
+        let pool_id = create_pool_with_liquidity(
+            &mut context,
+            &mut contract,
+            accounts(3),
+            vec![(accounts(1), to_yocto("5")), (accounts(2), to_yocto("10"))],
+        );
+        deposit_tokens(&mut context, &mut contract, accounts(3), vec![(accounts(1), to_yocto("1"))]);
+        testing_env!(context.predecessor_account_id(accounts(3)).epoch_height(7).attached_deposit(1).build());
+        swap(&mut contract, pool_id, accounts(1), to_yocto("1"), accounts(2));
+
+        testing_env!(context.predecessor_account_id(accounts(0)).attached_deposit(1).build());
+        contract.set_pool_volume_stats_retention(pool_id, Some(2));
+        assert_eq!(contract.get_pool_volume_stats_retention(pool_id), 2);
+        testing_env!(context.epoch_height(9).attached_deposit(0).build());
+        assert_eq!(contract.prune_pool_volume_stats(pool_id, vec![7]), 0);
+        assert_eq!(contract.get_epoch_fee_report(7).len(), 1);
+
+        testing_env!(context.epoch_height(10).block_timestamp(crate::utils::to_nano(2 * POOL_VOLUME_WINDOW_SEC)).build());
+        assert_eq!(contract.prune_pool_volume_stats(pool_id, vec![7]), 2);
+        assert!(contract.get_epoch_fee_report(7).is_empty());
+        assert!(read_pool_volume_windows_from_storage().get(&pool_id).is_none());
+    }
+
+    #[test]
+    fn test_forwarded_intent() {
+        let (mut context, mut contract) = setup_contract();
+        let pool_id = create_pool_with_liquidity(
+            &mut context,
+            &mut contract,
+            accounts(3),
+            vec![(accounts(1), to_yocto("5")), (accounts(2), to_yocto("10"))],
+        );
+        // accounts(4) is a smart wallet forwarding for accounts(3), it holds nothing in the exchange
+        testing_env!(context.predecessor_account_id(accounts(1)).attached_deposit(1).build());
+        contract.ft_on_transfer(
+            accounts(4),
+            U128(to_yocto("1")),
+            format!(
+                "{{\"forwarded_intent\":{{\"beneficiary_id\":\"{}\",\"actions\":[{{\"pool_id\":{},\"token_in\":\"{}\",\"token_out\":\"{}\",\"min_amount_out\":\"1\"}}]}}}}",
+                accounts(3), pool_id, accounts(1), accounts(2)
+            ),
+        );
+        assert!(contract.get_deposit(accounts(3), accounts(2)).0 > 0);
+        assert_eq!(contract.get_deposit(accounts(3), accounts(1)).0, 0);
+        assert!(contract.storage_balance_of(accounts(4)).is_none());
+
+        testing_env!(context.predecessor_account_id(accounts(1)).attached_deposit(1).build());
+        contract.ft_on_transfer(
+            accounts(4),
+            U128(to_yocto("2")),
+            format!("{{\"forwarded_intent\":{{\"beneficiary_id\":\"{}\"}}}}", accounts(3)),
+        );
+        assert_eq!(contract.get_deposit(accounts(3), accounts(1)).0, to_yocto("2"));
+    }
+
+    #[test]
+    fn test_cached_prices() {
+        let (mut context, mut contract) = setup_contract();
+        let pool_id = create_pool_with_liquidity(
+            &mut context,
+            &mut contract,
+            accounts(3),
+            vec![(accounts(1), to_yocto("5")), (accounts(2), to_yocto("10"))],
+        );
+        testing_env!(context.predecessor_account_id(accounts(0)).attached_deposit(1).build());
+        contract.extend_price_keepers(vec![accounts(4)]);
+        testing_env!(context.predecessor_account_id(accounts(4)).attached_deposit(to_yocto("0.01")).build());
+        contract.push_cached_prices(vec![TokenPrice { token_id: accounts(1), multiplier: U128(2), decimals: 24 }]);
+        assert!(contract.get_pool_tvl_by_cached_prices(pool_id, None).is_none());
+        let account_value = contract.get_account_value_by_cached_prices(accounts(3), None);
+        assert_eq!(account_value.unpriced_tokens, vec![accounts(2).to_string()]);
+
+        contract.push_cached_prices(vec![TokenPrice { token_id: accounts(2), multiplier: U128(1), decimals: 24 }]);
+        // 5 tokens at 2 and 10 tokens at 1
+        assert_eq!(contract.get_pool_tvl_by_cached_prices(pool_id, None), Some(U128(20)));
+        assert_eq!(contract.get_cached_prices(None, None).len(), 2);
+
+        testing_env!(context.block_timestamp(crate::utils::to_nano(100)).build());
+        assert!(contract.get_pool_tvl_by_cached_prices(pool_id, Some(60)).is_none());
+        assert_eq!(contract.get_pool_tvl_by_cached_prices(pool_id, Some(100)), Some(U128(20)));
+    }
+
+    #[test]
+    #[should_panic(expected = "E100: no permission to invoke this")]
+    fn test_cached_prices_not_keeper() {
+        let (mut context, mut contract) = setup_contract();
+        testing_env!(context.predecessor_account_id(accounts(4)).attached_deposit(to_yocto("0.01")).build());
+        contract.push_cached_prices(vec![TokenPrice { token_id: accounts(1), multiplier: U128(2), decimals: 24 }]);
+    }
+
+    #[test]
+    fn test_safe_mode_claim() {
+        let (mut context, mut contract) = setup_contract();
+        let leaf = safe_mode_leaf(&accounts(3).to_string(), &accounts(1).to_string(), to_yocto("2"));
+        let sibling = safe_mode_leaf(&accounts(4).to_string(), &accounts(1).to_string(), to_yocto("1"));
+        let root = safe_mode_root(leaf, &[sibling.into()]);
+        deposit_tokens(&mut context, &mut contract, accounts(3), vec![(accounts(1), to_yocto("1"))]);
+        testing_env!(context.predecessor_account_id(accounts(0)).attached_deposit(1).build());
+        contract.change_state(RunningState::Paused);
+        contract.publish_safe_mode_snapshot(root.into());
+
+        testing_env!(context.predecessor_account_id(accounts(3)).attached_deposit(1).build());
+        contract.claim_safe_mode_withdrawal(accounts(1), U128(to_yocto("2")), vec![sibling.into()]);
+        assert_eq!(contract.get_safe_mode_claimed(accounts(3), accounts(1)).0, to_yocto("2"));
+        // the claim is debited from the inner balance, it can't be withdrawn again after resuming
+        assert_eq!(contract.get_deposit(accounts(3), accounts(1)).0, 0);
+
+        // a corrected entitlement only pays out the difference
+        let leaf = safe_mode_leaf(&accounts(3).to_string(), &accounts(1).to_string(), to_yocto("3"));
+        testing_env!(context.predecessor_account_id(accounts(0)).attached_deposit(1).build());
+        contract.publish_safe_mode_snapshot(safe_mode_root(leaf, &[sibling.into()]).into());
+        testing_env!(context.predecessor_account_id(accounts(3)).attached_deposit(1).build());
+        contract.claim_safe_mode_withdrawal(accounts(1), U128(to_yocto("3")), vec![sibling.into()]);
+        assert_eq!(contract.get_safe_mode_claimed(accounts(3), accounts(1)).0, to_yocto("3"));
+    }
+
+    #[test]
+    #[should_panic(expected = "Invalid safe mode proof")]
+    fn test_safe_mode_claim_invalid_proof() {
+        let (mut context, mut contract) = setup_contract();
+        let leaf = safe_mode_leaf(&accounts(3).to_string(), &accounts(1).to_string(), to_yocto("2"));
+        let sibling = safe_mode_leaf(&accounts(4).to_string(), &accounts(1).to_string(), to_yocto("1"));
+        testing_env!(context.predecessor_account_id(accounts(0)).attached_deposit(1).build());
+        contract.change_state(RunningState::Paused);
+        contract.publish_safe_mode_snapshot(safe_mode_root(leaf, &[sibling.into()]).into());
+        testing_env!(context.predecessor_account_id(accounts(3)).attached_deposit(1).build());
+        contract.claim_safe_mode_withdrawal(accounts(1), U128(to_yocto("20")), vec![sibling.into()]);
+    }
+
+    #[test]
+    fn test_action_limits() {
+        let (mut context, mut contract) = setup_contract();
+        let pool_id = create_pool_with_liquidity(
+            &mut context,
+            &mut contract,
+            accounts(3),
+            vec![(accounts(1), to_yocto("5")), (accounts(2), to_yocto("10"))],
+        );
+        let hop = |token_in: ValidAccountId, amount_in: Option<U128>, token_out: ValidAccountId| Action::Swap(SwapAction {
+            pool_id,
+            token_in: token_in.into(),
+            amount_in,
+            token_out: token_out.into(),
+            min_amount_out: U128(0),
+        });
+        let actions = vec![
+            hop(accounts(1), Some(U128(to_yocto("0.1"))), accounts(2)),
+            hop(accounts(2), None, accounts(1)),
+            hop(accounts(1), Some(U128(to_yocto("0.1"))), accounts(2)),
+        ];
+        assert_eq!(get_longest_route_hops(&actions), 2);
+        testing_env!(context.predecessor_account_id(accounts(0)).attached_deposit(1).build());
+        contract.set_action_limits(Some(ActionLimits { max_actions: 3, max_route_hops: 2 }));
+        deposit_tokens(&mut context, &mut contract, accounts(3), vec![(accounts(1), to_yocto("1"))]);
+        testing_env!(context.predecessor_account_id(accounts(3)).attached_deposit(1).build());
+        contract.execute_actions(actions, None);
+        assert_eq!(contract.get_action_limits(), Some(ActionLimits { max_actions: 3, max_route_hops: 2 }));
+
+        testing_env!(context.predecessor_account_id(accounts(0)).attached_deposit(1).build());
+        contract.set_action_limits(None);
+        assert!(contract.get_action_limits().is_none());
+    }
+
+    #[test]
+    #[should_panic(expected = "Route of 3 hops exceeds the limit of 2")]
+    fn test_action_limits_route_hops() {
+        let (mut context, mut contract) = setup_contract();
+        testing_env!(context.predecessor_account_id(accounts(0)).attached_deposit(1).build());
+        contract.set_action_limits(Some(ActionLimits { max_actions: 5, max_route_hops: 2 }));
+        let actions: Vec<Action> = (0..3).map(|step| Action::Swap(SwapAction {
+            pool_id: 0,
+            token_in: accounts(1).into(),
+            amount_in: if step == 0 { Some(U128(1)) } else { None },
+            token_out: accounts(2).into(),
+            min_amount_out: U128(0),
+        })).collect();
+        deposit_tokens(&mut context, &mut contract, accounts(3), vec![(accounts(1), to_yocto("1"))]);
+        testing_env!(context.predecessor_account_id(accounts(3)).attached_deposit(1).build());
+        contract.execute_actions(actions, None);
+    }
+
+    #[test]
+    fn test_execute_actions_partial() {
+        let (mut context, mut contract) = setup_contract();
+        let pool_id = create_pool_with_liquidity(
+            &mut context,
+            &mut contract,
+            accounts(3),
+            vec![(accounts(1), to_yocto("5")), (accounts(2), to_yocto("10"))],
+        );
+        deposit_tokens(&mut context, &mut contract, accounts(3), vec![(accounts(1), to_yocto("1"))]);
+        let balance_2 = contract.get_deposit(accounts(3), accounts(2)).0;
+        testing_env!(context.predecessor_account_id(accounts(3)).attached_deposit(1).build());
+        let result = contract.execute_actions_partial(
+            vec![
+                Action::Swap(SwapAction {
+                    pool_id,
+                    token_in: accounts(1).into(),
+                    amount_in: Some(U128(to_yocto("1"))),
+                    token_out: accounts(2).into(),
+                    min_amount_out: U128(1),
+                }),
+                Action::Swap(SwapAction {
+                    pool_id,
+                    token_in: accounts(2).into(),
+                    amount_in: None,
+                    token_out: accounts(1).into(),
+                    min_amount_out: U128(to_yocto("2")),
+                }),
+            ],
+            None,
+        );
+        assert_eq!(result.completed.len(), 1);
+        assert_eq!(result.failed_action, Some(1));
+        assert!(result.failure.unwrap().starts_with("Quoted"));
+        assert_eq!(contract.get_deposit(accounts(3), accounts(1)).0, 0);
+        assert_eq!(contract.get_deposit(accounts(3), accounts(2)).0, balance_2 + result.completed[0].amount_out.0);
+    }
+
+    #[test]
+    fn test_swap_and_withdraw_to_receiver() {
+        let (mut context, mut contract) = setup_contract();
+        let pool_id = create_pool_with_liquidity(
+            &mut context,
+            &mut contract,
+            accounts(3),
+            vec![(accounts(1), to_yocto("5")), (accounts(2), to_yocto("10"))],
+        );
+        deposit_tokens(&mut context, &mut contract, accounts(3), vec![(accounts(1), to_yocto("2"))]);
+        let balance_2 = contract.get_deposit(accounts(3), accounts(2)).0;
+        testing_env!(context.predecessor_account_id(accounts(3)).attached_deposit(1).build());
+        let amount_out = contract.swap_to(
+            vec![SwapAction {
+                pool_id,
+                token_in: accounts(1).into(),
+                amount_in: Some(U128(to_yocto("1"))),
+                token_out: accounts(2).into(),
+                min_amount_out: U128(1),
+            }],
+            None,
+            accounts(4),
+            None,
+        );
+        assert!(amount_out.0 > 0);
+        // the output left the buyer's account for the receiver's wallet, the buyer is refunded if it fails
+        assert_eq!(contract.get_deposit(accounts(3), accounts(2)).0, balance_2);
+        assert_eq!(contract.get_in_flight_operations(accounts(3)).get(accounts(2).as_ref()), Some(&1));
+        assert!(contract.get_in_flight_operations(accounts(4)).is_empty());
+        testing_env!(
+            context.predecessor_account_id(env::current_account_id().try_into().unwrap()).build(),
+            Default::default(),
+            Default::default(),
+            Default::default(),
+            vec![PromiseResult::Failed]
+        );
+        contract.exchange_callback_post_withdraw(accounts(2).into(), accounts(3).into(), amount_out, None, Some(accounts(4).into()));
+        assert!(contract.get_in_flight_operations(accounts(3)).is_empty());
+        assert_eq!(contract.get_deposit(accounts(3), accounts(2)).0, balance_2 + amount_out.0);
+
+        testing_env!(context.predecessor_account_id(accounts(3)).attached_deposit(1).build(), Default::default(), Default::default(), Default::default(), vec![]);
+        contract.withdraw_to(accounts(1), U128(to_yocto("1")), accounts(4), None);
+        assert_eq!(contract.get_deposit(accounts(3), accounts(1)).0, 0);
+        assert_eq!(contract.get_in_flight_operations(accounts(3)).get(accounts(1).as_ref()), Some(&1));
+    }
+
+    #[test]
+    #[should_panic(expected = "Account danny denied")]
+    fn test_withdraw_to_denied_sender() {
+        let (mut context, mut contract) = setup_contract();
+        deposit_tokens(&mut context, &mut contract, accounts(3), vec![(accounts(1), to_yocto("1"))]);
+        testing_env!(context.predecessor_account_id(accounts(0)).attached_deposit(1).build());
+        contract.extend_denied_accounts(vec![accounts(3)]);
+        testing_env!(context.predecessor_account_id(accounts(3)).attached_deposit(1).build());
+        contract.withdraw_to(accounts(1), U128(to_yocto("1")), accounts(4), None);
+    }
+
+    #[test]
+    fn test_fee_holiday() {
+        let (mut context, mut contract) = setup_contract();
+        let pool_id = create_pool_with_liquidity(
+            &mut context,
+            &mut contract,
+            accounts(3),
+            vec![(accounts(1), to_yocto("5")), (accounts(2), to_yocto("10"))],
+        );
+        let to_nano = crate::utils::to_nano;
+        let full_fee_return = contract.get_return(pool_id, accounts(1), U128(to_yocto("1")), accounts(2));
+        testing_env!(context.predecessor_account_id(accounts(0)).attached_deposit(1).build());
+        contract.schedule_fee_holiday(pool_id, 5, near_sdk::json_types::U64(to_nano(10)), near_sdk::json_types::U64(to_nano(20)));
+        assert_eq!(contract.get_pool(pool_id).total_fee, 25);
+
+        testing_env!(context.block_timestamp(to_nano(15)).build());
+        let holiday_return = contract.get_return(pool_id, accounts(1), U128(to_yocto("1")), accounts(2));
+        assert!(holiday_return.0 > full_fee_return.0);
+        // the holiday only applies to swaps, the pool keeps its own fee
+        deposit_tokens(&mut context, &mut contract, accounts(3), vec![(accounts(1), to_yocto("1"))]);
+        testing_env!(context.predecessor_account_id(accounts(3)).block_timestamp(to_nano(15)).attached_deposit(1).build());
+        swap(&mut contract, pool_id, accounts(1), to_yocto("1"), accounts(2));
+        assert_eq!(contract.get_pool(pool_id).total_fee, 25);
+        testing_env!(context.predecessor_account_id(accounts(0)).attached_deposit(1).build());
+        contract.modify_total_fee(pool_id, 30);
+        assert_eq!(contract.get_pool(pool_id).total_fee, 30);
+
+        // the first swap after the holiday deletes it
+        deposit_tokens(&mut context, &mut contract, accounts(3), vec![(accounts(1), to_yocto("1"))]);
+        testing_env!(context.predecessor_account_id(accounts(3)).block_timestamp(to_nano(20)).attached_deposit(1).build());
+        swap(&mut contract, pool_id, accounts(1), to_yocto("1"), accounts(2));
+        assert!(contract.get_fee_holiday(pool_id).is_none());
+        assert_eq!(contract.get_pool(pool_id).total_fee, 30);
+    }
+
+    #[test]
+    fn test_degen_tvl_alert_thresholds() {
+        let (mut context, mut contract) = setup_contract();
+        testing_env!(context.predecessor_account_id(accounts(0)).attached_deposit(1).build());
+        contract.set_degen_tvl_alert_thresholds(vec![8000, 9500]);
+        assert_eq!(contract.get_degen_tvl_alert_thresholds(), vec![8000, 9500]);
+        assert_eq!(contract.get_degen_tvl_alert_level(0), 0);
+    }
+
+    #[test]
+    #[should_panic(expected = "Thresholds must be ascending within (0, 10000]")]
+    fn test_degen_tvl_alert_thresholds_not_ascending() {
+        let (mut context, mut contract) = setup_contract();
+        testing_env!(context.predecessor_account_id(accounts(0)).attached_deposit(1).build());
+        contract.set_degen_tvl_alert_thresholds(vec![9500, 8000]);
+    }
+
+    #[test]
+    fn test_predict_with_reserves() {
+        let (mut context, mut contract) = setup_contract();
+        let pool_id = create_pool_with_liquidity(
+            &mut context,
+            &mut contract,
+            accounts(3),
+            vec![(accounts(1), to_yocto("5")), (accounts(2), to_yocto("10"))],
+        );
+        let current = vec![U128(to_yocto("5")), U128(to_yocto("10"))];
+        let amount_out = contract.get_return(pool_id, accounts(1), U128(to_yocto("1")), accounts(2));
+        assert_eq!(contract.get_return_with_reserves(pool_id, accounts(1), U128(to_yocto("1")), accounts(2), current, None), amount_out);
+
+        // the pool after that swap, as a router would chain it
+        let next = vec![U128(to_yocto("6")), U128(to_yocto("10") - amount_out.0)];
+        let next_out = contract.get_return_with_reserves(pool_id, accounts(1), U128(to_yocto("1")), accounts(2), next.clone(), None);
+        assert!(next_out.0 < amount_out.0);
+        let mut reserves = HashMap::new();
+        reserves.insert(pool_id, next);
+        let mut token_deposit = HashMap::new();
+        token_deposit.insert(accounts(1).to_string(), U128(to_yocto("1")));
+        let predicted = contract.predict_swap_actions_with_reserves(
+            token_deposit,
+            vec![Action::Swap(SwapAction {
+                pool_id,
+                token_in: accounts(1).into(),
+                amount_in: Some(U128(to_yocto("1"))),
+                token_out: accounts(2).into(),
+                min_amount_out: U128(0),
+            })],
+            reserves,
+        );
+        assert_eq!(predicted.get(accounts(2).as_ref()), Some(&next_out));
+        // on chain state is untouched
+        assert_eq!(contract.get_pool(pool_id).amounts, vec![U128(to_yocto("5")), U128(to_yocto("10"))]);
+    }
+
+    #[test]
+    fn test_storage_auto_top_up() {
+        let (mut context, mut contract) = setup_contract();
+        testing_env!(context.predecessor_account_id(accounts(0)).attached_deposit(1).build());
+        contract.modify_wnear_id(accounts(1).to_string());
+        deposit_tokens(&mut context, &mut contract, accounts(3), vec![(accounts(1), to_yocto("1"))]);
+        testing_env!(context.predecessor_account_id(accounts(3)).attached_deposit(to_yocto("0.01")).build());
+        contract.set_storage_auto_top_up(true);
+        assert!(contract.get_storage_auto_top_up(accounts(3)));
+        testing_env!(context.predecessor_account_id(accounts(3)).attached_deposit(1).build());
+        contract.storage_withdraw(None);
+        let storage_before = contract.storage_balance_of(accounts(3)).unwrap().total.0;
+
+        contract.register_tokens(vec![accounts(2)]);
+        let storage_after = contract.storage_balance_of(accounts(3)).unwrap();
+        assert_eq!(storage_after.available.0, 0);
+        let topped_up = storage_after.total.0 - storage_before;
+        assert!(topped_up > 0);
+        assert_eq!(contract.get_deposit(accounts(3), accounts(1)).0, to_yocto("1") - topped_up);
+        assert_eq!(contract.get_storage_top_up_wnear(accounts(1)).0, topped_up);
+
+        // the NEAR paid with wNEAR stays in the storage deposit
+        contract.unregister_tokens(vec![accounts(2)]);
+        let storage_after = contract.storage_balance_of(accounts(3)).unwrap();
+        assert!(storage_after.total.0 - storage_after.available.0 >= topped_up);
+    }
+
+    #[test]
+    fn test_share_checkpoint() {
+        let (mut context, mut contract) = setup_contract();
+        let pool_id = create_pool_with_liquidity(
+            &mut context,
+            &mut contract,
+            accounts(3),
+            vec![(accounts(1), to_yocto("5")), (accounts(2), to_yocto("10"))],
+        );
+        let shares = contract.get_pool_shares(pool_id, accounts(3));
+        testing_env!(context.predecessor_account_id(accounts(0)).attached_deposit(1).build());
+        let checkpoint_id = contract.create_share_checkpoint(pool_id);
+        assert_eq!(contract.get_share_checkpoints(pool_id)[0].shares_total_supply, contract.get_pool_total_shares(pool_id));
+
+        testing_env!(context.predecessor_account_id(accounts(3)).attached_deposit(1).build());
+        contract.remove_liquidity(pool_id, U128(shares.0 / 2), vec![U128(1), U128(1)]);
+        assert_eq!(
+            contract.get_share_checkpoint_balances(pool_id, checkpoint_id, vec![accounts(3), accounts(4)]),
+            vec![shares, U128(0)]
+        );
+        assert_eq!(contract.get_share_checkpoints(pool_id)[0].recorded_accounts, 1);
+
+        testing_env!(context.predecessor_account_id(accounts(0)).attached_deposit(1).build());
+        let later_checkpoint_id = contract.create_share_checkpoint(pool_id);
+        assert_eq!(
+            contract.get_share_checkpoint_balances(pool_id, later_checkpoint_id, vec![accounts(3)]),
+            vec![U128(shares.0 - shares.0 / 2)]
+        );
+        for _ in 0..MAX_SHARE_CHECKPOINTS {
+            contract.create_share_checkpoint(pool_id);
+        }
+        let checkpoints = contract.get_share_checkpoints(pool_id);
+        assert_eq!(checkpoints.len(), MAX_SHARE_CHECKPOINTS);
+        assert!(checkpoints.iter().all(|checkpoint| checkpoint.checkpoint_id > later_checkpoint_id));
+    }
+
+    fn setup_share_raffle(context: &mut VMContextBuilder, contract: &mut Contract) -> u64 {
+        let pool_id = create_pool_with_liquidity(
+            context,
+            contract,
+            accounts(3),
+            vec![(accounts(1), to_yocto("5")), (accounts(2), to_yocto("10"))],
+        );
+        deposit_tokens(context, contract, accounts(4), vec![(accounts(1), to_yocto("1")), (accounts(2), to_yocto("2"))]);
+        testing_env!(context.predecessor_account_id(accounts(4)).attached_deposit(to_yocto("0.01")).build());
+        contract.add_liquidity(pool_id, vec![U128(to_yocto("1")), U128(to_yocto("2"))], None);
+        testing_env!(context.predecessor_account_id(accounts(0)).attached_deposit(1).build());
+        let checkpoint_id = contract.create_share_checkpoint(pool_id);
+        testing_env!(context.predecessor_account_id(accounts(5)).attached_deposit(to_yocto("0.1")).build());
+        contract.create_share_raffle(pool_id, checkpoint_id, 2)
+    }
+
+    #[test]
+    fn test_share_raffle() {
+        let (mut context, mut contract) = setup_contract();
+        let raffle_id = setup_share_raffle(&mut context, &mut contract);
+        testing_env!(context.predecessor_account_id(accounts(5)).attached_deposit(to_yocto("0.1")).build());
+        contract.add_share_raffle_entrants(raffle_id, vec![accounts(3), accounts(4), accounts(1)]);
+        let raffle = contract.get_share_raffle(raffle_id).unwrap();
+        assert_eq!(raffle.entrant_count, 2);
+        assert_eq!(raffle.entered_shares, raffle.shares_total_supply);
+        testing_env!(context.predecessor_account_id(accounts(5)).block_index(1).attached_deposit(0).build());
+        contract.close_share_raffle_entries(raffle_id);
+        assert!(contract.get_share_raffle(raffle_id).unwrap().entries_closed);
+
+        // anyone draws, in a later block than the one entries closed in
+        testing_env!(context.predecessor_account_id(accounts(2)).block_index(2).random_seed(vec![7; 32]).build());
+        let mut winners = contract.draw_share_raffle(raffle_id);
+        assert_eq!(contract.get_share_raffle(raffle_id).unwrap().winners, Some(winners.clone()));
+        winners.sort();
+        assert_eq!(winners, vec![accounts(3).to_string(), accounts(4).to_string()]);
+
+        let entrants = vec![("a".to_string(), 1), ("b".to_string(), 0), ("c".to_string(), 3)];
+        let reversed: Vec<_> = entrants.iter().rev().cloned().collect();
+        assert_eq!(draw_weighted(entrants.clone(), 1, b"seed"), draw_weighted(reversed, 1, b"seed"));
+        assert!(!draw_weighted(entrants, 2, b"seed").contains(&"b".to_string()));
+    }
+
+    #[test]
+    #[should_panic(expected = "Entrants don't hold all shares of the checkpoint")]
+    fn test_share_raffle_missing_entrants() {
+        let (mut context, mut contract) = setup_contract();
+        let raffle_id = setup_share_raffle(&mut context, &mut contract);
+        testing_env!(context.predecessor_account_id(accounts(5)).attached_deposit(to_yocto("0.1")).build());
+        contract.add_share_raffle_entrants(raffle_id, vec![accounts(3)]);
+        contract.close_share_raffle_entries(raffle_id);
+    }
+
+    #[test]
+    #[should_panic(expected = "Share raffle entries closed in this block")]
+    fn test_share_raffle_draw_in_closing_block() {
+        let (mut context, mut contract) = setup_contract();
+        let raffle_id = setup_share_raffle(&mut context, &mut contract);
+        testing_env!(context.predecessor_account_id(accounts(5)).attached_deposit(to_yocto("0.1")).build());
+        contract.add_share_raffle_entrants(raffle_id, vec![accounts(3), accounts(4)]);
+        contract.close_share_raffle_entries(raffle_id);
+        contract.draw_share_raffle(raffle_id);
+    }
+
+    fn setup_stable_kind_pool(
+        context: &mut VMContextBuilder,
+        contract: &mut Contract,
+        kind: &str,
+        tokens: Vec<ValidAccountId>,
+    ) -> u64 {
+        testing_env!(context.predecessor_account_id(accounts(0)).attached_deposit(1).build());
+        contract.extend_whitelisted_tokens(tokens.clone());
+        testing_env!(context.predecessor_account_id(accounts(0)).attached_deposit(to_yocto("1")).build());
+        let pool_id = match kind {
+            "stable" => contract.add_stable_swap_pool(tokens.clone(), vec![18, 18], 25, 240),
+            "rated" => contract.add_rated_swap_pool(tokens.clone(), vec![18, 18], 25, 240),
+            _ => contract.add_degen_swap_pool(tokens.clone(), vec![18, 18], 25, 240),
+        };
+        deposit_tokens(context, contract, accounts(3), tokens.iter().map(|token| (token.clone(), to_yocto("1000"))).collect());
+        testing_env!(context.predecessor_account_id(accounts(3)).attached_deposit(to_yocto("0.01")).build());
+        contract.add_stable_liquidity(pool_id, vec![U128(to_yocto("500")), U128(to_yocto("500"))], U128(1));
+        pool_id
+    }
+
+    /// Registers the tokens as price oracle degens with the given prices, updated at timestamp 25.
+    /// Degens are cached across tests, so callers pass tokens no other test registers.
+    fn register_degen_tokens(
+        context: &mut VMContextBuilder,
+        contract: &mut Contract,
+        token_prices: Vec<(ValidAccountId, Balance)>,
+    ) {
+        testing_env!(context.predecessor_account_id(accounts(0)).block_timestamp(25).attached_deposit(1).build());
+        contract.register_degen_oracle_config(DegenOracleConfig::PriceOracle(PriceOracleConfig {
+            oracle_id: "oracle_id".to_string(),
+            expire_ts: 60,
+            maximum_recency_duration_sec: 90,
+            maximum_staleness_duration_sec: 90,
+        }));
+        for (token, price) in token_prices {
+            contract.register_degen_token(token.clone(), DegenType::PriceOracle { decimals: 18 });
+            let mut degen = degen_swap::degen::read_degens_from_storage().get(token.as_ref()).cloned().unwrap();
+            degen.set_price_info(PriceInfo { stored_degen: price, degen_updated_at: 25 });
+            global_set_degen(token.as_ref(), &degen);
+        }
+    }
+
+    fn run_stable_kind_differential(context: &mut VMContextBuilder, contract: &mut Contract, pool_id: u64, tokens: &[ValidAccountId]) {
+        let (token_a, token_b): (AccountId, AccountId) = (tokens[0].clone().into(), tokens[1].clone().into());
+        testing_env!(context.predecessor_account_id(accounts(3)).attached_deposit(1).build());
+        contract.diff_execute_actions(vec![Action::Swap(SwapAction {
+            pool_id,
+            token_in: token_a.clone(),
+            amount_in: Some(U128(to_yocto("10"))),
+            token_out: token_b.clone(),
+            min_amount_out: U128(0),
+        })]);
+        contract.diff_execute_actions(vec![Action::Swap(SwapAction {
+            pool_id,
+            token_in: token_b,
+            amount_in: Some(U128(to_yocto("3"))),
+            token_out: token_a,
+            min_amount_out: U128(0),
+        })]);
+        testing_env!(context.predecessor_account_id(accounts(3)).attached_deposit(to_yocto("0.01")).build());
+        contract.diff_add_stable_liquidity(pool_id, vec![U128(to_yocto("20")), U128(to_yocto("1"))]);
+        testing_env!(context.predecessor_account_id(accounts(3)).attached_deposit(1).build());
+        contract.diff_remove_liquidity(pool_id, U128(to_yocto("15")));
+        contract.diff_remove_liquidity_by_tokens(pool_id, vec![U128(to_yocto("7")), U128(0)]);
+    }
+
+    #[test]
+    fn test_differential_simple_pool() {
+        let (mut context, mut contract) = setup_contract();
+        let pool_id = create_pool_with_liquidity(
+            &mut context,
+            &mut contract,
+            accounts(3),
+            vec![(accounts(1), to_yocto("50")), (accounts(2), to_yocto("100"))],
+        );
+        create_pool_with_liquidity(
+            &mut context,
+            &mut contract,
+            accounts(3),
+            vec![(accounts(2), to_yocto("100")), (accounts(4), to_yocto("30"))],
+        );
+        deposit_tokens(&mut context, &mut contract, accounts(3), vec![(accounts(1), to_yocto("100")), (accounts(2), to_yocto("100"))]);
+        testing_env!(context.predecessor_account_id(accounts(3)).attached_deposit(1).build());
+        contract.diff_execute_actions(vec![Action::Swap(SwapAction {
+            pool_id,
+            token_in: accounts(1).into(),
+            amount_in: Some(U128(to_yocto("2"))),
+            token_out: accounts(2).into(),
+            min_amount_out: U128(0),
+        })]);
+        contract.diff_execute_actions(vec![
+            Action::Swap(SwapAction {
+                pool_id,
+                token_in: accounts(1).into(),
+                amount_in: Some(U128(to_yocto("1"))),
+                token_out: accounts(2).into(),
+                min_amount_out: U128(0),
+            }),
+            Action::Swap(SwapAction {
+                pool_id: 1,
+                token_in: accounts(2).into(),
+                amount_in: None,
+                token_out: accounts(4).into(),
+                min_amount_out: U128(0),
+            }),
+        ]);
+        contract.diff_execute_actions(vec![Action::SwapByOutput(SwapByOutputAction {
+            pool_id,
+            token_in: accounts(2).into(),
+            amount_out: Some(U128(to_yocto("1"))),
+            token_out: accounts(1).into(),
+            max_amount_in: None,
+        })]);
+        testing_env!(context.predecessor_account_id(accounts(3)).attached_deposit(to_yocto("0.01")).build());
+        contract.diff_add_liquidity(pool_id, vec![U128(to_yocto("3")), U128(to_yocto("10"))]);
+        testing_env!(context.predecessor_account_id(accounts(3)).attached_deposit(1).build());
+        contract.diff_remove_liquidity(pool_id, U128(to_yocto("1")));
+    }
+
+    #[test]
+    fn test_differential_stable_pool() {
+        let (mut context, mut contract) = setup_contract();
+        let tokens = vec![accounts(1), accounts(2)];
+        let pool_id = setup_stable_kind_pool(&mut context, &mut contract, "stable", tokens.clone());
+        run_stable_kind_differential(&mut context, &mut contract, pool_id, &tokens);
+    }
+
+    #[test]
+    fn test_differential_rated_pool() {
+        let (mut context, mut contract) = setup_contract();
+        // Rates are cached across tests, so use tokens no other test rates.
+        let tokens: Vec<ValidAccountId> = vec!["diff_rated_a.near".try_into().unwrap(), "diff_rated_b.near".try_into().unwrap()];
+        let pool_id = setup_stable_kind_pool(&mut context, &mut contract, "rated", tokens.clone());
+        run_stable_kind_differential(&mut context, &mut contract, pool_id, &tokens);
+    }
+
+    #[test]
+    fn test_differential_degen_pool() {
+        let (mut context, mut contract) = setup_contract();
+        let tokens: Vec<ValidAccountId> = vec!["diff_degen_a.near".try_into().unwrap(), "diff_degen_b.near".try_into().unwrap()];
+        register_degen_tokens(&mut context, &mut contract, vec![(tokens[0].clone(), to_yocto("2")), (tokens[1].clone(), to_yocto("1"))]);
+        let pool_id = setup_stable_kind_pool(&mut context, &mut contract, "degen", tokens.clone());
+        run_stable_kind_differential(&mut context, &mut contract, pool_id, &tokens);
+    }
+
+    #[test]
+    fn test_is_degen_pool_swappable() {
+        let (mut context, mut contract) = setup_contract();
+        let tokens: Vec<ValidAccountId> = vec!["swappable_a.near".try_into().unwrap(), "swappable_b.near".try_into().unwrap()];
+        register_degen_tokens(&mut context, &mut contract, vec![(tokens[0].clone(), to_yocto("1")), (tokens[1].clone(), to_yocto("1"))]);
+        let pool_id = setup_stable_kind_pool(&mut context, &mut contract, "degen", tokens.clone());
+        let status = contract.is_degen_pool_swappable(pool_id);
+        assert!(status.swappable);
+        assert_eq!(status.tvl_exceeds_limit, Some(false));
+
+        testing_env!(context.predecessor_account_id(accounts(0)).attached_deposit(1).build());
+        contract.set_degen_price_band(tokens[0].clone(), U128(to_yocto("2")), U128(to_yocto("3")));
+        let status = contract.is_degen_pool_swappable(pool_id);
+        assert!(!status.swappable);
+        assert_eq!(status.out_of_band_tokens, vec![tokens[0].to_string()]);
+
+        testing_env!(context.block_timestamp(200).build());
+        let status = contract.is_degen_pool_swappable(pool_id);
+        assert_eq!(status.expired_price_tokens.len(), 2);
+        assert_eq!(status.tvl_exceeds_limit, None);
+    }
+
+    #[test]
+    fn test_stable_zap() {
+        let (mut context, mut contract) = setup_contract();
+        let pool_id = setup_stable_kind_pool(&mut context, &mut contract, "stable", vec![accounts(1), accounts(2)]);
+        let shares = contract.get_pool_shares(pool_id, accounts(3)).0;
+        testing_env!(context.predecessor_account_id(accounts(3)).attached_deposit(1).build());
+        let minted = contract.stable_zap(pool_id, accounts(1), U128(to_yocto("10")), U128(to_yocto("9.9"))).0;
+        assert_eq!(contract.get_pool_shares(pool_id, accounts(3)).0, shares + minted);
+        assert_eq!(contract.get_deposit(accounts(3), accounts(1)).0, to_yocto("490"));
+        assert_eq!(contract.get_deposit(accounts(3), accounts(2)).0, to_yocto("500"));
+        let amounts = contract.get_pool(pool_id).amounts;
+        assert_eq!(amounts[0].0 + amounts[1].0, to_yocto("1010"));
+    }
+
+    #[test]
+    #[should_panic(expected = "E68: slippage error")]
+    fn test_stable_zap_slippage() {
+        let (mut context, mut contract) = setup_contract();
+        let pool_id = setup_stable_kind_pool(&mut context, &mut contract, "stable", vec![accounts(1), accounts(2)]);
+        testing_env!(context.predecessor_account_id(accounts(3)).attached_deposit(1).build());
+        contract.stable_zap(pool_id, accounts(1), U128(to_yocto("10")), U128(to_yocto("10")));
+    }
+
+    #[test]
+    #[should_panic(expected = "rejected by pool 0 screening")]
+    fn test_stable_zap_screened() {
+        let (mut context, mut contract) = setup_contract();
+        let pool_id = setup_stable_kind_pool(&mut context, &mut contract, "stable", vec![accounts(1), accounts(2)]);
+        testing_env!(context.predecessor_account_id(accounts(0)).attached_deposit(1).build());
+        contract.set_pool_swap_screen(pool_id, Some(SwapScreenConfig {
+            mode: SwapScreenMode::Allowlist,
+            screener_id: None,
+        }));
+        testing_env!(context.predecessor_account_id(accounts(3)).attached_deposit(1).build());
+        contract.stable_zap(pool_id, accounts(1), U128(to_yocto("10")), U128(1));
+    }
+
+    #[test]
+    fn test_inspect_storage() {
+        let (mut context, mut contract) = setup_contract();
+        let pool_id = create_pool_with_liquidity(
+            &mut context,
+            &mut contract,
+            accounts(3),
+            vec![(accounts(1), to_yocto("5")), (accounts(2), to_yocto("10"))],
+        );
+        testing_env!(context.predecessor_account_id(accounts(0)).attached_deposit(1).build());
+        let inspection = contract.inspect_storage(None, None);
+        assert_eq!(inspection.total_keys, inspection.keys.len() as u64);
+        assert_eq!(inspection.keys[0].name, "STATE");
+        let pool_info = &inspection.keys[inspection.keys.len() - 2];
+        assert_eq!(pool_info.name, format!("pool:{}", pool_id));
+        assert_eq!(pool_info.entries.0, 1);
+        assert!(pool_info.bytes.unwrap().0 > 0);
+        // the exchange and accounts(3).
+        let shares_info = inspection.keys.last().unwrap();
+        assert_eq!(shares_info.name, format!("shares:{}", pool_id));
+        assert_eq!(shares_info.entries.0, 2);
+        assert_eq!(shares_info.bytes, None);
+        let whitelist = inspection.keys.iter().find(|info| info.name == "whitelisted_tokens").unwrap();
+        assert_eq!(whitelist.entries.0, 2);
+        assert_eq!(whitelist.bytes, None);
+        let accounts_info = inspection.keys.iter().find(|info| info.name == "accounts").unwrap();
+        assert_eq!(accounts_info.entries.0, 1);
+
+        deposit_tokens(&mut context, &mut contract, accounts(4), vec![]);
+        testing_env!(context.predecessor_account_id(accounts(0)).attached_deposit(1).build());
+        let page = contract.inspect_storage(Some(inspection.total_keys - 3), Some(5));
+        assert_eq!(page.keys.len(), 3);
+        assert_eq!(page.keys[1], *pool_info);
+        assert_eq!(page.keys[2], *shares_info);
+        let inspection = contract.inspect_storage(None, None);
+        let accounts_info = inspection.keys.iter().find(|info| info.name == "accounts").unwrap();
+        assert_eq!(accounts_info.entries.0, 2);
+    }
+
+    #[test]
+    fn test_inspect_storage_lists_custom_keys() {
+        let source = include_str!("custom_keys.rs");
+        let mut keys = 0;
+        for line in source.lines().filter(|line| line.starts_with("pub const ") && line.contains(": &str = ")) {
+            let key = line.split('"').nth(1).unwrap();
+            assert!(CUSTOM_KEYS.contains(&key), "{} is not in CUSTOM_KEYS", key);
+            keys += 1;
+        }
+        assert_eq!(keys, CUSTOM_KEYS.len());
+    }
+
+    #[test]
+    #[should_panic(expected = "E100: no permission to invoke this")]
+    fn test_inspect_storage_not_owner() {
+        let (mut context, mut contract) = setup_contract();
+        testing_env!(context.predecessor_account_id(accounts(3)).attached_deposit(1).build());
+        contract.inspect_storage(None, None);
+    }
+
+    #[test]
+    fn test_order_nonces() {
+        let (mut context, mut contract) = setup_contract();
+        let nonces = |values: Vec<u64>| values.into_iter().map(near_sdk::json_types::U64).collect::<Vec<_>>();
+        testing_env!(context.predecessor_account_id(accounts(3)).attached_deposit(to_yocto("0.01")).build());
+        contract.cancel_order_nonces(nonces(vec![3, 5]));
+        assert_eq!(contract.check_order_nonces(accounts(3), nonces(vec![2, 3, 4, 5])), vec![true, false, true, false]);
+        assert!(contract.is_order_nonce_valid(accounts(4), near_sdk::json_types::U64(3)));
+
+        contract.internal_use_order_nonce(&accounts(3).into(), 4);
+        assert!(!contract.is_order_nonce_valid(accounts(3), near_sdk::json_types::U64(4)));
+
+        testing_env!(context.predecessor_account_id(accounts(3)).attached_deposit(to_yocto("0.01")).build());
+        contract.cancel_order_nonces_below(near_sdk::json_types::U64(10));
+        assert_eq!(contract.get_order_nonce_floor(accounts(3)).0, 10);
+        assert_eq!(contract.check_order_nonces(accounts(3), nonces(vec![2, 9, 10])), vec![false, false, true]);
+    }
+
+    #[test]
+    #[should_panic(expected = "Order nonce 7 of charlie already used or cancelled")]
+    fn test_order_nonce_replay() {
+        let (_, mut contract) = setup_contract();
+        contract.internal_use_order_nonce(&accounts(2).into(), 7);
+        contract.internal_use_order_nonce(&accounts(2).into(), 7);
+    }
+
+    #[test]
+    fn test_insurance_fund() {
+        let (mut context, mut contract) = setup_contract();
+        let pool_id = create_pool_with_liquidity(
+            &mut context,
+            &mut contract,
+            accounts(3),
+            vec![(accounts(1), to_yocto("5")), (accounts(2), to_yocto("10"))],
+        );
+        testing_env!(context.predecessor_account_id(accounts(0)).attached_deposit(1).build());
+        contract.set_insurance_fee_bps(2000);
+        contract.set_dao_id(Some(accounts(5)));
+        deposit_tokens(&mut context, &mut contract, accounts(3), vec![(accounts(1), to_yocto("1"))]);
+        testing_env!(context.predecessor_account_id(accounts(3)).attached_deposit(1).build());
+        swap(&mut contract, pool_id, accounts(1), to_yocto("1"), accounts(2));
+        let exchange_shares = contract.get_pool_shares(pool_id, env::current_account_id().try_into().unwrap()).0;
+        let fund = contract.get_insurance_fund(pool_id);
+        assert!(fund.shares.0 > 0);
+        assert_eq!(fund.shares, fund.accrued_shares);
+        // 20% of the admin fee shares went to the fund, up to rounding
+        assert!(fund.shares.0 * 4 <= exchange_shares && exchange_shares <= fund.shares.0 * 4 + 4);
+
+        deposit_tokens(&mut context, &mut contract, accounts(4), vec![]);
+        testing_env!(context.predecessor_account_id(accounts(5)).attached_deposit(1).build());
+        contract.request_insurance_withdrawal(pool_id, fund.shares, accounts(4));
+        assert!(contract.get_insurance_fund(pool_id).pending_withdrawal.is_some());
+        testing_env!(context
+            .predecessor_account_id(accounts(5))
+            .block_timestamp(INSURANCE_WITHDRAW_TIMELOCK)
+            .attached_deposit(1)
+            .build());
+        let amounts = contract.execute_insurance_withdrawal(pool_id, vec![U128(0), U128(0)]);
+        assert_eq!(contract.get_deposit(accounts(4), accounts(1)), amounts[0]);
+        assert_eq!(contract.get_deposit(accounts(4), accounts(2)), amounts[1]);
+        let fund = contract.get_insurance_fund(pool_id);
+        assert_eq!(fund.shares.0, 0);
+        assert!(fund.pending_withdrawal.is_none());
+    }
+
+    #[test]
+    #[should_panic(expected = "Insurance withdrawal still timelocked")]
+    fn test_insurance_withdrawal_timelocked() {
+        let (mut context, mut contract) = setup_contract();
+        let pool_id = create_pool_with_liquidity(
+            &mut context,
+            &mut contract,
+            accounts(3),
+            vec![(accounts(1), to_yocto("5")), (accounts(2), to_yocto("10"))],
+        );
+        testing_env!(context.predecessor_account_id(accounts(0)).attached_deposit(1).build());
+        contract.set_insurance_fee_bps(2000);
+        contract.set_dao_id(Some(accounts(5)));
+        deposit_tokens(&mut context, &mut contract, accounts(3), vec![(accounts(1), to_yocto("1"))]);
+        testing_env!(context.predecessor_account_id(accounts(3)).attached_deposit(1).build());
+        swap(&mut contract, pool_id, accounts(1), to_yocto("1"), accounts(2));
+        testing_env!(context.predecessor_account_id(accounts(5)).attached_deposit(1).build());
+        contract.request_insurance_withdrawal(pool_id, U128(1), accounts(5));
+        contract.execute_insurance_withdrawal(pool_id, vec![U128(0), U128(0)]);
+    }
+
+    #[test]
+    fn test_admin_fee_burn() {
+        let (mut context, mut contract) = setup_contract();
+        let tokens = vec![accounts(1), accounts(2)];
+        let pool_id = setup_stable_kind_pool(&mut context, &mut contract, "stable", tokens.clone());
+        let shares_total_supply = contract.get_pool_total_shares(pool_id).0;
+        testing_env!(context.predecessor_account_id(accounts(0)).attached_deposit(1).build());
+        contract.set_pool_admin_fee_burn_bps(pool_id, 10000);
+        testing_env!(context.predecessor_account_id(accounts(3)).attached_deposit(1).build());
+        swap(&mut contract, pool_id, accounts(1), to_yocto("10"), accounts(2));
+        // All admin fee shares are burnt, the LPs keep the whole fee.
+        let exchange_shares = contract.get_pool_shares(pool_id, env::current_account_id().try_into().unwrap()).0;
+        assert_eq!(exchange_shares, 0);
+        assert!(contract.get_pool_admin_fee_burn(pool_id).unwrap().burnt_shares > 0);
+        assert_eq!(contract.get_pool_total_shares(pool_id).0, shares_total_supply);
+
+        testing_env!(context.predecessor_account_id(accounts(0)).attached_deposit(1).build());
+        contract.set_pool_admin_fee_burn_bps(pool_id, 0);
+        let burnt_shares = contract.get_pool_admin_fee_burn(pool_id).unwrap().burnt_shares;
+        testing_env!(context.predecessor_account_id(accounts(3)).attached_deposit(1).build());
+        swap(&mut contract, pool_id, accounts(1), to_yocto("10"), accounts(2));
+        assert!(contract.get_pool_shares(pool_id, env::current_account_id().try_into().unwrap()).0 > 0);
+        assert_eq!(contract.get_pool_admin_fee_burn(pool_id).unwrap().burnt_shares, burnt_shares);
+    }
+
+    #[test]
+    #[should_panic(expected = "Admin fee burning is for stable kind pools")]
+    fn test_admin_fee_burn_simple_pool() {
+        let (mut context, mut contract) = setup_contract();
+        let pool_id = create_pool_with_liquidity(
+            &mut context,
+            &mut contract,
+            accounts(3),
+            vec![(accounts(1), to_yocto("5")), (accounts(2), to_yocto("10"))],
+        );
+        testing_env!(context.predecessor_account_id(accounts(0)).attached_deposit(1).build());
+        contract.set_pool_admin_fee_burn_bps(pool_id, 5000);
+    }
+
+    #[test]
+    fn test_dynamic_tvl_limit() {
+        let (mut context, mut contract) = setup_contract();
+        let tokens: Vec<ValidAccountId> = vec!["dtl_degen_a.near".try_into().unwrap(), "dtl_degen_b.near".try_into().unwrap()];
+        register_degen_tokens(&mut context, &mut contract, vec![(tokens[0].clone(), to_yocto("2")), (tokens[1].clone(), to_yocto("1"))]);
+        let pool_id = setup_stable_kind_pool(&mut context, &mut contract, "degen", tokens.clone());
+
+        testing_env!(context.predecessor_account_id(accounts(0)).block_timestamp(30).attached_deposit(1).build());
+        contract.set_dynamic_tvl_limit(pool_id, tokens[0].clone(), 1000, U128(1000), U128(1_000_000));
+        assert_eq!(contract.get_dynamic_tvl_limit(pool_id).unwrap().updated_at, 0);
+
+        // 10% of 1M tokens at a price of 2.
+        contract.internal_apply_dynamic_tvl_limit(pool_id, 1_000_000 * 10u128.pow(18));
+        assert_eq!(contract.get_pool_limit_by_pool_id(pool_id).unwrap().get_degen_pool_limit().tvl_limit, 200_000);
+        assert_eq!(contract.get_dynamic_tvl_limit(pool_id).unwrap().updated_at, 30);
+
+        // Bounded by the max and the min.
+        contract.internal_apply_dynamic_tvl_limit(pool_id, 100_000_000 * 10u128.pow(18));
+        assert_eq!(contract.get_pool_limit_by_pool_id(pool_id).unwrap().get_degen_pool_limit().tvl_limit, 1_000_000);
+        contract.internal_apply_dynamic_tvl_limit(pool_id, 10u128.pow(18));
+        assert_eq!(contract.get_pool_limit_by_pool_id(pool_id).unwrap().get_degen_pool_limit().tvl_limit, 1000);
+
+        // A stale price keeps the last limit.
+        testing_env!(context.predecessor_account_id(accounts(0)).block_timestamp(100).attached_deposit(1).build());
+        contract.internal_apply_dynamic_tvl_limit(pool_id, 1_000_000 * 10u128.pow(18));
+        assert_eq!(contract.get_pool_limit_by_pool_id(pool_id).unwrap().get_degen_pool_limit().tvl_limit, 1000);
+        assert_eq!(contract.get_dynamic_tvl_limit(pool_id).unwrap().updated_at, 30);
+
+        contract.remove_dynamic_tvl_limit(pool_id);
+        assert!(contract.get_dynamic_tvl_limit(pool_id).is_none());
+        assert_eq!(contract.get_pool_limit_by_pool_id(pool_id).unwrap().get_degen_pool_limit().tvl_limit, 1000);
+    }
+
+    #[test]
+    #[should_panic(expected = "E53: token not in list")]
+    fn test_dynamic_tvl_limit_token_not_in_pool() {
+        let (mut context, mut contract) = setup_contract();
+        let tokens: Vec<ValidAccountId> = vec!["dtl_degen_c.near".try_into().unwrap(), "dtl_degen_d.near".try_into().unwrap()];
+        register_degen_tokens(&mut context, &mut contract, vec![(tokens[0].clone(), to_yocto("1")), (tokens[1].clone(), to_yocto("1"))]);
+        let pool_id = setup_stable_kind_pool(&mut context, &mut contract, "degen", tokens);
+        testing_env!(context.predecessor_account_id(accounts(0)).attached_deposit(1).build());
+        contract.set_dynamic_tvl_limit(pool_id, accounts(1), 1000, U128(0), U128(1_000_000));
+    }
+
+    #[test]
+    fn test_stable_route() {
+        let (mut context, mut contract) = setup_contract();
+        let tokens = vec![accounts(1), accounts(2)];
+        let balanced = setup_stable_kind_pool(&mut context, &mut contract, "stable", tokens.clone());
+        let imbalanced = setup_stable_kind_pool(&mut context, &mut contract, "stable", tokens.clone());
+        assert_eq!(contract.get_stable_pair_pools(accounts(2), accounts(1)), vec![balanced, imbalanced]);
+        testing_env!(context.predecessor_account_id(accounts(3)).attached_deposit(1).build());
+        swap(&mut contract, imbalanced, accounts(1), to_yocto("300"), accounts(2));
+
+        let route = contract.get_stable_route(accounts(1), U128(to_yocto("1")), accounts(2));
+        assert_eq!(route.actions.len(), 1);
+        assert_eq!(route.actions[0].pool_id, balanced);
+        assert_eq!(route.amount_out, contract.get_return(balanced, accounts(1), U128(to_yocto("1")), accounts(2)));
+
+        // Going the other way the imbalanced pool pays more.
+        let route = contract.get_stable_route(accounts(2), U128(to_yocto("1")), accounts(1));
+        assert_eq!(route.actions[0].pool_id, imbalanced);
+
+        let amount_in = U128(to_yocto("400"));
+        let route = contract.get_stable_route(accounts(2), amount_in, accounts(1));
+        assert_eq!(route.actions.len(), 2);
+        assert_eq!(route.actions.iter().map(|action| action.amount_in.unwrap().0).sum::<u128>(), amount_in.0);
+        assert!(route.amount_out.0 > contract.get_return(balanced, accounts(2), amount_in, accounts(1)).0);
+        assert!(route.amount_out.0 > contract.get_return(imbalanced, accounts(2), amount_in, accounts(1)).0);
+
+        deposit_tokens(&mut context, &mut contract, accounts(4), vec![(accounts(1), 0), (accounts(2), amount_in.0)]);
+        testing_env!(context.predecessor_account_id(accounts(4)).attached_deposit(1).build());
+        let amount_out = contract.swap_stable_routed(accounts(2), amount_in, accounts(1), route.amount_out, None);
+        assert_eq!(amount_out, route.amount_out);
+        assert_eq!(contract.get_deposit(accounts(4), accounts(1)), amount_out);
+    }
+
+    #[test]
+    fn test_stable_route_skips_capped_pool() {
+        let (mut context, mut contract) = setup_contract();
+        let tokens = vec![accounts(1), accounts(2)];
+        let capped = setup_stable_kind_pool(&mut context, &mut contract, "stable", tokens.clone());
+        let open = setup_stable_kind_pool(&mut context, &mut contract, "stable", tokens);
+        testing_env!(context.predecessor_account_id(accounts(0)).attached_deposit(1).build());
+        contract.set_pool_swap_cap(capped, Some(1));
+
+        let route = contract.get_stable_route(accounts(1), U128(to_yocto("1")), accounts(2));
+        assert_eq!(route.actions.len(), 1);
+        assert_eq!(route.actions[0].pool_id, open);
+    }
+
+    #[test]
+    #[should_panic(expected = "E68: slippage error")]
+    fn test_stable_route_slippage() {
+        let (mut context, mut contract) = setup_contract();
+        setup_stable_kind_pool(&mut context, &mut contract, "stable", vec![accounts(1), accounts(2)]);
+        deposit_tokens(&mut context, &mut contract, accounts(4), vec![(accounts(1), to_yocto("1"))]);
+        testing_env!(context.predecessor_account_id(accounts(4)).attached_deposit(1).build());
+        contract.swap_stable_routed(accounts(1), U128(to_yocto("1")), accounts(2), U128(to_yocto("1")), None);
+    }
 }
@@ -0,0 +1,316 @@
+use crate::*;
+use crate::utils::{to_nano, u128_ratio, FEE_DIVISOR, U256};
+use near_sdk::Timestamp;
+
+/// Upper bound of the part of a pool's LP fees going to locked shares, in bps.
+pub const MAX_LOCK_BONUS_BPS: u32 = 2000;
+/// Locks stop earning the bonus at the start of the bucket their shares start vesting in.
+pub const LOCK_BONUS_EXPIRY_BUCKET_SEC: u32 = 7 * 86400;
+/// Upper bound of the buckets a pool tracks at once, as every swap in the pool reads them.
+/// Locks falling in a further bucket don't take part in the bonus.
+pub const MAX_LOCK_BONUS_EXPIRIES: usize = 52;
+/// Smallest lock taking part in the bonus, in bps of the pool's shares, so the expiries
+/// can't be filled up with dust locks.
+pub const MIN_LOCK_BONUS_SHARE_BPS: u32 = 10;
+/// Fewest full expiry buckets a lock must earn through to take part in the bonus.
+pub const MIN_LOCK_BONUS_BUCKETS: u64 = 1;
+
+/// Lock bonus tier of a fee claim pool. Part of the LP fee of each swap is shared among the
+/// shares locked at that time instead of among all shares, see `set_pool_lock_bonus`.
+#[derive(BorshSerialize, BorshDeserialize, Clone, Default)]
+pub struct PoolLockBonus {
+    pub bonus_bps: u32,
+    /// Weight of the registered locks not yet vesting.
+    pub locked_weight: Balance,
+    /// Accumulated bonus per unit of lock weight of each token, in FEE_GROWTH_PRECISION.
+    pub bonus_growth: Vec<u128>,
+    /// Weight of the registered locks by the start of their expiry bucket, earliest first.
+    pub expiries: Vec<(Timestamp, Balance)>,
+}
+
+/// Locks of a pool sharing an expiry bucket, they stop earning together.
+#[derive(BorshSerialize, BorshDeserialize, Clone)]
+pub struct LockBonusExpiry {
+    /// Pool bonus growth when the bucket started, set once it's expired.
+    pub final_bonus_growth: Option<Vec<u128>>,
+    /// Positions of the bucket still to be claimed out.
+    pub positions: u32,
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Clone)]
+pub struct LockBonusPosition {
+    pub pool_id: u64,
+    /// Locker, claims go to the current owner of the lock while it exists.
+    pub account_id: AccountId,
+    /// Locked shares times the full expiry buckets left when registered, up to MAX_LOCK_BONUS_EXPIRIES.
+    pub weight: Balance,
+    /// Pool bonus growth at the last claim.
+    pub bonus_growth_checkpoint: Vec<u128>,
+    /// Start of the expiry bucket, the lock earns nothing past it.
+    pub expiry: Timestamp,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+#[cfg_attr(not(target_arch = "wasm32"), derive(Debug))]
+pub struct PoolLockBonusInfo {
+    pub bonus_bps: u32,
+    pub locked_weight: U128,
+}
+
+pub fn read_pool_lock_bonus_from_storage() -> LookupMap<u64, PoolLockBonus> {
+    if let Some(content) = env::storage_read(POOL_LOCK_BONUS.as_bytes()) {
+        LookupMap::try_from_slice(&content).expect("deserialize pool lock bonus failed.")
+    } else {
+        LookupMap::new(StorageKey::PoolLockBonus)
+    }
+}
+
+pub fn write_pool_lock_bonus_to_storage(pool_lock_bonus: LookupMap<u64, PoolLockBonus>) {
+    env::storage_write(
+        POOL_LOCK_BONUS.as_bytes(),
+        &pool_lock_bonus.try_to_vec().unwrap(),
+    );
+}
+
+pub fn read_lock_bonus_positions_from_storage() -> LookupMap<u64, LockBonusPosition> {
+    if let Some(content) = env::storage_read(LOCK_BONUS_POSITIONS.as_bytes()) {
+        LookupMap::try_from_slice(&content).expect("deserialize lock bonus positions failed.")
+    } else {
+        LookupMap::new(StorageKey::LockBonusPositions)
+    }
+}
+
+pub fn write_lock_bonus_positions_to_storage(lock_bonus_positions: LookupMap<u64, LockBonusPosition>) {
+    env::storage_write(
+        LOCK_BONUS_POSITIONS.as_bytes(),
+        &lock_bonus_positions.try_to_vec().unwrap(),
+    );
+}
+
+pub fn read_lock_bonus_expiries_from_storage() -> LookupMap<(u64, Timestamp), LockBonusExpiry> {
+    if let Some(content) = env::storage_read(LOCK_BONUS_EXPIRIES.as_bytes()) {
+        LookupMap::try_from_slice(&content).expect("deserialize lock bonus expiries failed.")
+    } else {
+        LookupMap::new(StorageKey::LockBonusExpiries)
+    }
+}
+
+pub fn write_lock_bonus_expiries_to_storage(lock_bonus_expiries: LookupMap<(u64, Timestamp), LockBonusExpiry>) {
+    env::storage_write(
+        LOCK_BONUS_EXPIRIES.as_bytes(),
+        &lock_bonus_expiries.try_to_vec().unwrap(),
+    );
+}
+
+/// Stops the locks of the buckets that started from earning, at the bonus growth reached so far.
+/// No fee was shared since they started, so that growth is the one at their start.
+fn expire_lock_bonuses(pool_id: u64, lock_bonus: &mut PoolLockBonus) {
+    let current_time = env::block_timestamp();
+    let expired = lock_bonus.expiries.iter().take_while(|(time, _)| *time <= current_time).count();
+    if expired == 0 {
+        return;
+    }
+    let mut lock_bonus_expiries = read_lock_bonus_expiries_from_storage();
+    for (time, weight) in lock_bonus.expiries.drain(..expired) {
+        lock_bonus.locked_weight -= weight;
+        if let Some(mut expiry) = lock_bonus_expiries.get(&(pool_id, time)) {
+            expiry.final_bonus_growth = Some(lock_bonus.bonus_growth.clone());
+            lock_bonus_expiries.insert(&(pool_id, time), &expiry);
+        }
+    }
+    write_lock_bonus_expiries_to_storage(lock_bonus_expiries);
+}
+
+fn earned_lock_bonus(position: &LockBonusPosition, bonus_growth: &[u128]) -> Vec<Balance> {
+    bonus_growth.iter().zip(position.bonus_growth_checkpoint.iter())
+        .map(|(growth, checkpoint)| {
+            (U256::from(growth - checkpoint) * U256::from(position.weight) / U256::from(FEE_GROWTH_PRECISION)).as_u128()
+        })
+        .collect()
+}
+
+impl Contract {
+    /// Takes the bonus part out of the LP fee of a swap and shares it among the locked shares,
+    /// returns what is left for all shares.
+    pub(crate) fn internal_share_lock_bonus(&self, pool_id: u64, token_idx: usize, lp_fee: Balance) -> Balance {
+        let mut pool_lock_bonus = read_pool_lock_bonus_from_storage();
+        let mut lock_bonus = match pool_lock_bonus.get(&pool_id) {
+            Some(lock_bonus) if lock_bonus.bonus_bps > 0 => lock_bonus,
+            _ => return lp_fee,
+        };
+        expire_lock_bonuses(pool_id, &mut lock_bonus);
+        let bonus = if lock_bonus.locked_weight == 0 {
+            0
+        } else {
+            u128_ratio(lp_fee, lock_bonus.bonus_bps as u128, FEE_DIVISOR as u128)
+        };
+        lock_bonus.bonus_growth[token_idx] += (U256::from(bonus) * U256::from(FEE_GROWTH_PRECISION)
+            / U256::from(std::cmp::max(lock_bonus.locked_weight, 1)))
+            .as_u128();
+        pool_lock_bonus.insert(&pool_id, &lock_bonus);
+        write_pool_lock_bonus_to_storage(pool_lock_bonus);
+        lp_fee - bonus
+    }
+
+    /// Registers a new lock for the pool's bonus until the start of its expiry bucket, weighted
+    /// by the full buckets it has left. Locks below MIN_LOCK_BONUS_SHARE_BPS of the pool's shares
+    /// or earning for less than MIN_LOCK_BONUS_BUCKETS take no part.
+    /// The position and a new bucket are paid by the locker's attached deposit.
+    pub(crate) fn internal_register_lock_bonus(&mut self, lock_id: u64, lock: &ShareLock) {
+        let bucket_sec = to_nano(LOCK_BONUS_EXPIRY_BUCKET_SEC);
+        let expiry = lock.start_time / bucket_sec * bucket_sec;
+        let buckets = expiry.saturating_sub(env::block_timestamp()) / bucket_sec;
+        let mut pool_lock_bonus = read_pool_lock_bonus_from_storage();
+        let mut lock_bonus = match pool_lock_bonus.get(&lock.pool_id) {
+            Some(lock_bonus) => lock_bonus,
+            None => return,
+        };
+        let min_amount = u128_ratio(
+            self.internal_get_pool(lock.pool_id).share_total_balance(),
+            MIN_LOCK_BONUS_SHARE_BPS as u128,
+            FEE_DIVISOR as u128,
+        );
+        if buckets < MIN_LOCK_BONUS_BUCKETS || lock.amount < min_amount {
+            log!("Lock {} takes no part in the lock bonus of pool {}, too small or too short", lock_id, lock.pool_id);
+            return;
+        }
+        let weight = lock.amount * std::cmp::min(buckets, MAX_LOCK_BONUS_EXPIRIES as u64) as u128;
+        expire_lock_bonuses(lock.pool_id, &mut lock_bonus);
+        let idx = lock_bonus.expiries.iter().take_while(|(time, _)| *time < expiry).count();
+        if lock_bonus.expiries.get(idx).map(|(time, _)| *time) == Some(expiry) {
+            lock_bonus.expiries[idx].1 += weight;
+        } else if lock_bonus.expiries.len() < MAX_LOCK_BONUS_EXPIRIES {
+            lock_bonus.expiries.insert(idx, (expiry, weight));
+        } else {
+            log!("Lock {} takes no part in the lock bonus of pool {}, too many expiries", lock_id, lock.pool_id);
+            pool_lock_bonus.insert(&lock.pool_id, &lock_bonus);
+            write_pool_lock_bonus_to_storage(pool_lock_bonus);
+            return;
+        }
+        let mut lock_bonus_expiries = read_lock_bonus_expiries_from_storage();
+        let mut bucket = lock_bonus_expiries
+            .get(&(lock.pool_id, expiry))
+            .unwrap_or(LockBonusExpiry { final_bonus_growth: None, positions: 0 });
+        bucket.positions += 1;
+        lock_bonus_expiries.insert(&(lock.pool_id, expiry), &bucket);
+        write_lock_bonus_expiries_to_storage(lock_bonus_expiries);
+
+        let mut positions = read_lock_bonus_positions_from_storage();
+        positions.insert(&lock_id, &LockBonusPosition {
+            pool_id: lock.pool_id,
+            account_id: lock.account_id.clone(),
+            weight,
+            bonus_growth_checkpoint: lock_bonus.bonus_growth.clone(),
+            expiry,
+        });
+        write_lock_bonus_positions_to_storage(positions);
+        lock_bonus.locked_weight += weight;
+        pool_lock_bonus.insert(&lock.pool_id, &lock_bonus);
+        write_pool_lock_bonus_to_storage(pool_lock_bonus);
+    }
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Route `bonus_bps` of the LP fees of a fee claim pool to its locked shares, on top of
+    /// their part of the rest. Only locks created from then on take part, each until the start of
+    /// the LOCK_BONUS_EXPIRY_BUCKET_SEC bucket its shares start vesting in and weighted by the buckets
+    /// left then. 0 stops the bonus, what was accrued stays claimable.
+    #[payable]
+    pub fn set_pool_lock_bonus(&mut self, pool_id: u64, bonus_bps: u32) {
+        assert_one_yocto();
+        self.assert_owner();
+        audit_privileged_action("set_pool_lock_bonus");
+        assert!(bonus_bps <= MAX_LOCK_BONUS_BPS, "Lock bonus must be at most {} bps", MAX_LOCK_BONUS_BPS);
+        assert!(read_pool_fee_growth_from_storage().get(&pool_id).is_some(), "Fee claims not enabled");
+        let mut pool_lock_bonus = read_pool_lock_bonus_from_storage();
+        let mut lock_bonus = pool_lock_bonus.get(&pool_id).unwrap_or_else(|| PoolLockBonus {
+            bonus_growth: vec![0; self.internal_get_pool(pool_id).tokens().len()],
+            ..Default::default()
+        });
+        lock_bonus.bonus_bps = bonus_bps;
+        pool_lock_bonus.insert(&pool_id, &lock_bonus);
+        write_pool_lock_bonus_to_storage(pool_lock_bonus);
+    }
+
+    /// Harvest the bonus earned by the caller's lock into the caller's inner account.
+    #[payable]
+    pub fn claim_lock_bonus(&mut self, lock_id: u64) -> Vec<U128> {
+        assert_one_yocto();
+        self.assert_contract_running();
+        let sender_id = env::predecessor_account_id();
+        let mut positions = read_lock_bonus_positions_from_storage();
+        let mut position = positions.get(&lock_id).expect("Lock has no bonus");
+        let owner_id = read_share_locks_from_storage()
+            .get(&lock_id)
+            .map(|lock| lock.account_id)
+            .unwrap_or_else(|| position.account_id.clone());
+        assert_eq!(owner_id, sender_id, "{}", ERR100_NOT_ALLOWED);
+        let pool_id = position.pool_id;
+        let mut pool_lock_bonus = read_pool_lock_bonus_from_storage();
+        let mut lock_bonus = pool_lock_bonus.get(&pool_id).unwrap();
+        expire_lock_bonuses(pool_id, &mut lock_bonus);
+        let mut lock_bonus_expiries = read_lock_bonus_expiries_from_storage();
+        let mut expiry = lock_bonus_expiries.get(&(pool_id, position.expiry)).unwrap();
+        let claimed = earned_lock_bonus(
+            &position,
+            expiry.final_bonus_growth.as_deref().unwrap_or(&lock_bonus.bonus_growth),
+        );
+
+        let pool = self.internal_get_pool(pool_id);
+        let mut pool_fee_growth = read_pool_fee_growth_from_storage();
+        let mut state = pool_fee_growth.get(&pool_id).unwrap();
+        let mut deposits = self.internal_unwrap_account(&sender_id);
+        for (i, token_id) in pool.tokens().iter().enumerate() {
+            state.unclaimed[i] -= claimed[i];
+            if claimed[i] > 0 {
+                deposits.deposit(token_id, claimed[i]);
+            }
+        }
+        self.internal_save_account(&sender_id, deposits);
+        pool_fee_growth.insert(&pool_id, &state);
+        write_pool_fee_growth_to_storage(pool_fee_growth);
+
+        if expiry.final_bonus_growth.is_some() {
+            positions.remove(&lock_id);
+            expiry.positions -= 1;
+            if expiry.positions == 0 {
+                lock_bonus_expiries.remove(&(pool_id, position.expiry));
+            } else {
+                lock_bonus_expiries.insert(&(pool_id, position.expiry), &expiry);
+            }
+            write_lock_bonus_expiries_to_storage(lock_bonus_expiries);
+        } else {
+            position.bonus_growth_checkpoint = lock_bonus.bonus_growth.clone();
+            positions.insert(&lock_id, &position);
+        }
+        pool_lock_bonus.insert(&pool_id, &lock_bonus);
+        write_pool_lock_bonus_to_storage(pool_lock_bonus);
+        write_lock_bonus_positions_to_storage(positions);
+        log!("{} claimed lock bonus {:?} of lock {}", sender_id, claimed, lock_id);
+        claimed.into_iter().map(|amount| amount.into()).collect()
+    }
+
+    pub fn get_pool_lock_bonus(&self, pool_id: u64) -> Option<PoolLockBonusInfo> {
+        read_pool_lock_bonus_from_storage().get(&pool_id).map(|lock_bonus| PoolLockBonusInfo {
+            bonus_bps: lock_bonus.bonus_bps,
+            locked_weight: lock_bonus.locked_weight.into(),
+        })
+    }
+
+    /// Bonus the lock can claim, empty if it doesn't take part in the pool's lock bonus.
+    pub fn get_claimable_lock_bonus(&self, lock_id: u64) -> Vec<U128> {
+        read_lock_bonus_positions_from_storage()
+            .get(&lock_id)
+            .map(|position| {
+                let lock_bonus = read_pool_lock_bonus_from_storage().get(&position.pool_id).unwrap();
+                let expiry = read_lock_bonus_expiries_from_storage().get(&(position.pool_id, position.expiry)).unwrap();
+                earned_lock_bonus(&position, expiry.final_bonus_growth.as_deref().unwrap_or(&lock_bonus.bonus_growth))
+                    .into_iter()
+                    .map(|amount| amount.into())
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+}
@@ -0,0 +1,266 @@
+use crate::*;
+use crate::utils::{u128_ratio, FEE_DIVISOR, U256};
+
+/// Precision of the per share fee growth.
+pub const FEE_GROWTH_PRECISION: u128 = 1_000_000_000_000_000_000_000_000;
+
+/// Fee claim state of a simple pool. Once enabled, the LP part of swap fees is kept aside
+/// instead of being compounded into the pool, and LPs harvest it with `claim_fees`.
+#[derive(BorshSerialize, BorshDeserialize, Clone, Default)]
+pub struct PoolFeeGrowth {
+    /// Accumulated LP fee per share of each token, in FEE_GROWTH_PRECISION.
+    pub fee_growth: Vec<u128>,
+    /// Fees kept aside and not yet claimed, held by the exchange outside the pool reserves.
+    pub unclaimed: Vec<Balance>,
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Clone, Default)]
+pub struct LpFeePosition {
+    /// Pool fee growth at the last settlement.
+    pub fee_growth_checkpoint: Vec<u128>,
+    /// Fees settled and not yet claimed.
+    pub owed: Vec<Balance>,
+}
+
+pub fn read_pool_fee_growth_from_storage() -> LookupMap<u64, PoolFeeGrowth> {
+    if let Some(content) = env::storage_read(POOL_FEE_GROWTH.as_bytes()) {
+        LookupMap::try_from_slice(&content).expect("deserialize pool fee growth failed.")
+    } else {
+        LookupMap::new(StorageKey::PoolFeeGrowth)
+    }
+}
+
+pub fn write_pool_fee_growth_to_storage(pool_fee_growth: LookupMap<u64, PoolFeeGrowth>) {
+    env::storage_write(
+        POOL_FEE_GROWTH.as_bytes(),
+        &pool_fee_growth.try_to_vec().unwrap(),
+    );
+}
+
+pub fn read_lp_fee_positions_from_storage() -> LookupMap<AccountId, HashMap<u64, LpFeePosition>> {
+    if let Some(content) = env::storage_read(LP_FEE_POSITIONS.as_bytes()) {
+        LookupMap::try_from_slice(&content).expect("deserialize lp fee positions failed.")
+    } else {
+        LookupMap::new(StorageKey::LpFeePositions)
+    }
+}
+
+pub fn write_lp_fee_positions_to_storage(lp_fee_positions: LookupMap<AccountId, HashMap<u64, LpFeePosition>>) {
+    env::storage_write(
+        LP_FEE_POSITIONS.as_bytes(),
+        &lp_fee_positions.try_to_vec().unwrap(),
+    );
+}
+
+/// Returns the position brought up to the current fee growth, given the shares held since the last settlement,
+/// and the fees earned since then.
+/// A missing position has held its shares since fee claims were enabled, i.e. from zero growth.
+fn settled_position(position: Option<LpFeePosition>, fee_growth: &[u128], shares: Balance) -> (LpFeePosition, Vec<Balance>) {
+    let mut position = position.unwrap_or_else(|| LpFeePosition {
+        fee_growth_checkpoint: vec![0; fee_growth.len()],
+        owed: vec![0; fee_growth.len()],
+    });
+    let mut earned = vec![0; fee_growth.len()];
+    for (i, growth) in fee_growth.iter().enumerate() {
+        earned[i] = (U256::from(growth - position.fee_growth_checkpoint[i]) * U256::from(shares)
+            / U256::from(FEE_GROWTH_PRECISION)).as_u128();
+        position.owed[i] += earned[i];
+        position.fee_growth_checkpoint[i] = *growth;
+    }
+    (position, earned)
+}
+
+/// Pays the referrer of the account its cut of the fees the account just earned, at the rate fixed
+/// when the referrer was recorded. The cut comes out of the exchange's own fees of the pool, earned
+/// on its admin fee shares, and is capped by them.
+fn pay_lp_referral_cut(
+    pool_id: u64,
+    pool: &Pool,
+    fee_growth: &[u128],
+    lp_fee_positions: &mut LookupMap<AccountId, HashMap<u64, LpFeePosition>>,
+    account_id: &AccountId,
+    earned: &[Balance],
+) {
+    let (referrer_id, fee_bps) = match lp_referrer_of(pool_id, account_id) {
+        Some(referrer) => referrer,
+        None => return,
+    };
+    let exchange_id = env::current_account_id();
+    let mut exchange_positions = lp_fee_positions.get(&exchange_id).unwrap_or_default();
+    let (mut exchange_position, _) = settled_position(
+        exchange_positions.remove(&pool_id),
+        fee_growth,
+        pool.share_balances(&exchange_id),
+    );
+    let referral_cut: Vec<Balance> = earned
+        .iter()
+        .zip(exchange_position.owed.iter_mut())
+        .map(|(earned, owed)| {
+            let cut = std::cmp::min(u128_ratio(*earned, fee_bps as u128, FEE_DIVISOR as u128), *owed);
+            *owed -= cut;
+            cut
+        })
+        .collect();
+    exchange_positions.insert(pool_id, exchange_position);
+    lp_fee_positions.insert(&exchange_id, &exchange_positions);
+    accrue_lp_referral_rewards(&referrer_id, pool_id, &referral_cut);
+}
+
+impl Contract {
+    /// Settles the fees and incentives earned so far by the given accounts and records them in share checkpoints,
+    /// must run before their shares of the pool change.
+    pub(crate) fn internal_settle_lp_fees(&self, pool_id: u64, pool: &Pool, account_ids: &[&AccountId]) {
+        self.internal_record_share_checkpoints(pool_id, pool, account_ids);
+        if !matches!(pool, Pool::SimplePool(_)) {
+            return;
+        }
+        self.internal_settle_lp_incentives(pool_id, pool, account_ids);
+        let state = match read_pool_fee_growth_from_storage().get(&pool_id) {
+            Some(state) => state,
+            None => return,
+        };
+        let mut lp_fee_positions = read_lp_fee_positions_from_storage();
+        for account_id in account_ids {
+            let mut positions = lp_fee_positions.get(account_id).unwrap_or_default();
+            let (position, earned) = settled_position(
+                positions.remove(&pool_id),
+                &state.fee_growth,
+                pool.share_balances(account_id),
+            );
+            positions.insert(pool_id, position);
+            lp_fee_positions.insert(account_id, &positions);
+            pay_lp_referral_cut(pool_id, pool, &state.fee_growth, &mut lp_fee_positions, account_id, &earned);
+        }
+        write_lp_fee_positions_to_storage(lp_fee_positions);
+    }
+
+    /// Settles the exchange and referral accounts, which may receive admin fee shares from a swap.
+    pub(crate) fn internal_settle_admin_fee_receivers(&self, pool_id: u64, pool: &Pool, referral_info: &Option<(AccountId, u32)>) {
+        let exchange_id = env::current_account_id();
+        match referral_info {
+            Some((referral_id, _)) => self.internal_settle_lp_fees(pool_id, pool, &[&exchange_id, referral_id]),
+            None => self.internal_settle_lp_fees(pool_id, pool, &[&exchange_id]),
+        }
+    }
+
+    /// Moves the LP part of a finished simple pool swap's fee out of the pool reserves, crediting it to share holders.
+    /// `admin_fees` are the ones the swap was charged with, the rest of the fee is the LP part.
+    pub(crate) fn internal_collect_lp_fee(&self, pool_id: u64, pool: &mut Pool, token_in: &AccountId, amount_in: Balance, admin_fees: &AdminFees) {
+        let pool = match pool {
+            Pool::SimplePool(p) => p,
+            _ => return,
+        };
+        let mut pool_fee_growth = read_pool_fee_growth_from_storage();
+        let mut state = match pool_fee_growth.get(&pool_id) {
+            Some(state) => state,
+            None => return,
+        };
+        let in_idx = pool.token_account_ids.iter().position(|id| id == token_in).expect(ERR63_MISSING_TOKEN);
+        let lp_fee = (U256::from(amount_in)
+            * U256::from(pool.total_fee)
+            * U256::from(FEE_DIVISOR - admin_fees.admin_fee_bps)
+            / U256::from(FEE_DIVISOR)
+            / U256::from(FEE_DIVISOR))
+            .as_u128();
+        if lp_fee == 0 || pool.shares_total_supply == 0 {
+            return;
+        }
+        pool.amounts[in_idx] -= lp_fee;
+        let shared_fee = self.internal_share_lock_bonus(pool_id, in_idx, lp_fee);
+        state.fee_growth[in_idx] += (U256::from(shared_fee) * U256::from(FEE_GROWTH_PRECISION)
+            / U256::from(pool.shares_total_supply))
+            .as_u128();
+        state.unclaimed[in_idx] += lp_fee;
+        pool_fee_growth.insert(&pool_id, &state);
+        write_pool_fee_growth_to_storage(pool_fee_growth);
+    }
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Switch a simple pool to claimable LP fees. From then on swap fees no longer compound
+    /// into the pool but accrue to LPs, who harvest them with `claim_fees`. Cannot be undone.
+    #[payable]
+    pub fn enable_pool_fee_claims(&mut self, pool_id: u64) {
+        assert_one_yocto();
+        self.assert_owner();
+        audit_privileged_action("enable_pool_fee_claims");
+        let pool = self.internal_get_pool(pool_id);
+        assert!(matches!(pool, Pool::SimplePool(_)), "Not simple pool");
+        let mut pool_fee_growth = read_pool_fee_growth_from_storage();
+        assert!(pool_fee_growth.get(&pool_id).is_none(), "Fee claims already enabled");
+        let n_tokens = pool.tokens().len();
+        pool_fee_growth.insert(&pool_id, &PoolFeeGrowth {
+            fee_growth: vec![0; n_tokens],
+            unclaimed: vec![0; n_tokens],
+        });
+        write_pool_fee_growth_to_storage(pool_fee_growth);
+    }
+
+    /// Harvest the caller's earned fees of the given pool into the caller's inner account, shares are kept.
+    #[payable]
+    pub fn claim_fees(&mut self, pool_id: u64) -> Vec<U128> {
+        assert_one_yocto();
+        self.assert_contract_running();
+        let sender_id = env::predecessor_account_id();
+        let pool = self.internal_get_pool(pool_id);
+        let mut pool_fee_growth = read_pool_fee_growth_from_storage();
+        let mut state = pool_fee_growth.get(&pool_id).expect("Fee claims not enabled");
+        let mut lp_fee_positions = read_lp_fee_positions_from_storage();
+        let mut positions = lp_fee_positions.get(&sender_id).unwrap_or_default();
+        let (mut position, earned) = settled_position(
+            positions.remove(&pool_id),
+            &state.fee_growth,
+            pool.share_balances(&sender_id),
+        );
+        pay_lp_referral_cut(pool_id, &pool, &state.fee_growth, &mut lp_fee_positions, &sender_id, &earned);
+        let claimed = std::mem::replace(&mut position.owed, vec![0; state.fee_growth.len()]);
+
+        let mut deposits = self.internal_unwrap_account(&sender_id);
+        for (i, token_id) in pool.tokens().iter().enumerate() {
+            state.unclaimed[i] -= claimed[i];
+            if claimed[i] > 0 {
+                deposits.deposit(token_id, claimed[i]);
+            }
+        }
+        self.internal_save_account(&sender_id, deposits);
+        positions.insert(pool_id, position);
+        lp_fee_positions.insert(&sender_id, &positions);
+        write_lp_fee_positions_to_storage(lp_fee_positions);
+        pool_fee_growth.insert(&pool_id, &state);
+        write_pool_fee_growth_to_storage(pool_fee_growth);
+        log!("{} claimed fees {:?} from pool {}", sender_id, claimed, pool_id);
+        claimed.into_iter().map(|amount| amount.into()).collect()
+    }
+
+    pub fn is_pool_fee_claims_enabled(&self, pool_id: u64) -> bool {
+        read_pool_fee_growth_from_storage().get(&pool_id).is_some()
+    }
+
+    /// Returns the fees the account can claim from the given pool right now.
+    pub fn get_claimable_fees(&self, pool_id: u64, account_id: ValidAccountId) -> Vec<U128> {
+        let pool = self.internal_get_pool(pool_id);
+        match read_pool_fee_growth_from_storage().get(&pool_id) {
+            Some(state) => {
+                let position = read_lp_fee_positions_from_storage()
+                    .get(account_id.as_ref())
+                    .and_then(|mut positions| positions.remove(&pool_id));
+                settled_position(position, &state.fee_growth, pool.share_balances(account_id.as_ref()))
+                    .0
+                    .owed
+                    .into_iter()
+                    .map(|amount| amount.into())
+                    .collect()
+            }
+            None => vec![U128(0); pool.tokens().len()],
+        }
+    }
+
+    /// Returns the fees of the pool kept aside and not yet claimed.
+    pub fn get_pool_unclaimed_fees(&self, pool_id: u64) -> Vec<U128> {
+        read_pool_fee_growth_from_storage()
+            .get(&pool_id)
+            .map(|state| state.unclaimed.into_iter().map(|amount| amount.into()).collect())
+            .unwrap_or_default()
+    }
+}
@@ -0,0 +1,255 @@
+use crate::*;
+use crate::utils::{to_nano, u128_dec_format, u128_ratio, u64_dec_format, U256};
+use near_sdk::Timestamp;
+
+/// Precision of the per share incentive growth.
+pub const INCENTIVE_GROWTH_PRECISION: u128 = 1_000_000_000_000_000_000_000_000;
+
+/// Incentive tokens escrowed by a project for the LPs of a simple pool. They unlock linearly
+/// from `start` to `end` and are split between LPs by the shares held while unlocking.
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone)]
+#[serde(crate = "near_sdk::serde")]
+#[cfg_attr(not(target_arch = "wasm32"), derive(Debug))]
+pub struct LpIncentive {
+    pub funder_id: AccountId,
+    pub token_id: AccountId,
+    #[serde(with = "u128_dec_format")]
+    pub total: Balance,
+    #[serde(with = "u64_dec_format")]
+    pub start: Timestamp,
+    #[serde(with = "u64_dec_format")]
+    pub end: Timestamp,
+    #[serde(with = "u64_dec_format")]
+    pub last_update: Timestamp,
+    /// Accumulated incentive per share, in INCENTIVE_GROWTH_PRECISION.
+    #[serde(with = "u128_dec_format")]
+    pub growth: u128,
+    /// Unlocked while the pool had no shares, returned to the funder once the escrow ends.
+    #[serde(with = "u128_dec_format")]
+    pub unallocated: Balance,
+    #[serde(with = "u128_dec_format")]
+    pub claimed: Balance,
+    /// Funded and neither claimed nor refunded yet, carried over from previous escrows of the pool.
+    #[serde(with = "u128_dec_format")]
+    pub escrowed: Balance,
+}
+
+impl LpIncentive {
+    /// Unlocks the incentive up to the current block over the given share supply.
+    fn update(&mut self, shares_total_supply: Balance) {
+        let now = std::cmp::min(env::block_timestamp(), self.end);
+        if now <= self.last_update {
+            return;
+        }
+        let unlocked = u128_ratio(self.total, (now - self.last_update) as u128, (self.end - self.start) as u128);
+        if shares_total_supply == 0 {
+            self.unallocated += unlocked;
+        } else {
+            self.growth += (U256::from(unlocked) * U256::from(INCENTIVE_GROWTH_PRECISION)
+                / U256::from(shares_total_supply))
+                .as_u128();
+        }
+        self.last_update = now;
+    }
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Clone, Default)]
+pub struct LpIncentivePosition {
+    /// Incentive growth at the last settlement.
+    pub growth_checkpoint: u128,
+    /// Incentive settled and not yet claimed.
+    pub owed: Balance,
+}
+
+impl LpIncentivePosition {
+    fn settle(&mut self, growth: u128, shares: Balance) {
+        self.owed += (U256::from(growth - self.growth_checkpoint) * U256::from(shares)
+            / U256::from(INCENTIVE_GROWTH_PRECISION))
+            .as_u128();
+        self.growth_checkpoint = growth;
+    }
+}
+
+pub fn read_lp_incentives_from_storage() -> LookupMap<u64, LpIncentive> {
+    if let Some(content) = env::storage_read(LP_INCENTIVES.as_bytes()) {
+        LookupMap::try_from_slice(&content).expect("deserialize lp incentives failed.")
+    } else {
+        LookupMap::new(StorageKey::LpIncentives)
+    }
+}
+
+pub fn write_lp_incentives_to_storage(lp_incentives: LookupMap<u64, LpIncentive>) {
+    env::storage_write(
+        LP_INCENTIVES.as_bytes(),
+        &lp_incentives.try_to_vec().unwrap(),
+    );
+}
+
+pub fn read_lp_incentive_positions_from_storage() -> LookupMap<AccountId, HashMap<u64, LpIncentivePosition>> {
+    if let Some(content) = env::storage_read(LP_INCENTIVE_POSITIONS.as_bytes()) {
+        LookupMap::try_from_slice(&content).expect("deserialize lp incentive positions failed.")
+    } else {
+        LookupMap::new(StorageKey::LpIncentivePositions)
+    }
+}
+
+pub fn write_lp_incentive_positions_to_storage(lp_incentive_positions: LookupMap<AccountId, HashMap<u64, LpIncentivePosition>>) {
+    env::storage_write(
+        LP_INCENTIVE_POSITIONS.as_bytes(),
+        &lp_incentive_positions.try_to_vec().unwrap(),
+    );
+}
+
+impl Contract {
+    /// Settles the incentive earned so far by the given accounts, must run before their shares of the pool change.
+    pub(crate) fn internal_settle_lp_incentives(&self, pool_id: u64, pool: &Pool, account_ids: &[&AccountId]) {
+        let mut lp_incentives = read_lp_incentives_from_storage();
+        let mut incentive = match lp_incentives.get(&pool_id) {
+            Some(incentive) => incentive,
+            None => return,
+        };
+        incentive.update(pool.share_total_balance());
+        lp_incentives.insert(&pool_id, &incentive);
+        write_lp_incentives_to_storage(lp_incentives);
+
+        let mut lp_incentive_positions = read_lp_incentive_positions_from_storage();
+        for account_id in account_ids {
+            let mut positions = lp_incentive_positions.get(account_id).unwrap_or_default();
+            positions.entry(pool_id).or_default().settle(incentive.growth, pool.share_balances(account_id));
+            lp_incentive_positions.insert(account_id, &positions);
+        }
+        write_lp_incentive_positions_to_storage(lp_incentive_positions);
+    }
+
+    /// Refunds the unallocated part of an ended escrow to its funder, returns the refund.
+    fn internal_close_lp_incentive(&mut self, pool_id: u64, pool: &Pool) -> Balance {
+        let mut lp_incentives = read_lp_incentives_from_storage();
+        let mut incentive = lp_incentives.get(&pool_id).expect("No incentive for the pool");
+        incentive.update(pool.share_total_balance());
+        let refund = std::mem::take(&mut incentive.unallocated);
+        incentive.escrowed -= refund;
+        release_locked_deposit(&incentive.funder_id, &incentive.token_id, refund);
+        if refund > 0 {
+            let mut account = self.internal_unwrap_account(&incentive.funder_id);
+            account.deposit(&incentive.token_id, refund);
+            self.internal_save_account(&incentive.funder_id, account);
+        }
+        lp_incentives.insert(&pool_id, &incentive);
+        write_lp_incentives_to_storage(lp_incentives);
+        refund
+    }
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Escrow `amount` of `token_id` from the caller's inner account for the LPs of a simple pool,
+    /// unlocking over `duration_sec` from now. A pool has one escrow at a time, a new one
+    /// can follow once the previous one ended, in the same token, and closes it.
+    #[payable]
+    pub fn create_lp_incentive(&mut self, pool_id: u64, token_id: ValidAccountId, amount: U128, duration_sec: u32) {
+        self.assert_contract_running();
+        let prev_storage = env::storage_usage();
+        let sender_id = env::predecessor_account_id();
+        let token_id: AccountId = token_id.into();
+        let pool = self.internal_get_pool(pool_id);
+        assert!(matches!(pool, Pool::SimplePool(_)), "Not simple pool");
+        assert!(amount.0 > 0 && duration_sec > 0, "Invalid incentive");
+        let (growth, claimed, escrowed) = match read_lp_incentives_from_storage().get(&pool_id) {
+            Some(previous) => {
+                // positions keep their growth checkpoints, so the growth carries over
+                assert!(env::block_timestamp() >= previous.end, "Pool already has an incentive");
+                assert_eq!(previous.token_id, token_id, "Incentive token must stay {}", previous.token_id);
+                self.internal_close_lp_incentive(pool_id, &pool);
+                let previous = read_lp_incentives_from_storage().get(&pool_id).unwrap();
+                // what is left is owed to LPs, no longer the previous funder's
+                release_locked_deposit(&previous.funder_id, &token_id, previous.escrowed);
+                (previous.growth, previous.claimed, previous.escrowed)
+            }
+            None => (0, 0, 0),
+        };
+
+        let mut account = self.internal_unwrap_account(&sender_id);
+        account.withdraw(&token_id, amount.0);
+        self.internal_save_account(&sender_id, account);
+        add_locked_deposit(&sender_id, &token_id, amount.0);
+        let now = env::block_timestamp();
+        let mut lp_incentives = read_lp_incentives_from_storage();
+        lp_incentives.insert(&pool_id, &LpIncentive {
+            funder_id: sender_id.clone(),
+            token_id,
+            total: amount.0,
+            start: now,
+            end: now + to_nano(duration_sec),
+            last_update: now,
+            growth,
+            unallocated: 0,
+            claimed,
+            escrowed: escrowed + amount.0,
+        });
+        write_lp_incentives_to_storage(lp_incentives);
+        self.internal_check_storage(prev_storage);
+    }
+
+    /// Harvest the caller's unlocked incentive of the given pool into the caller's inner account.
+    #[payable]
+    pub fn claim_lp_incentive(&mut self, pool_id: u64) -> U128 {
+        assert_one_yocto();
+        self.assert_contract_running();
+        let sender_id = env::predecessor_account_id();
+        let pool = self.internal_get_pool(pool_id);
+        self.internal_settle_lp_incentives(pool_id, &pool, &[&sender_id]);
+        let mut lp_incentives = read_lp_incentives_from_storage();
+        let mut incentive = lp_incentives.get(&pool_id).expect("No incentive for the pool");
+        let mut lp_incentive_positions = read_lp_incentive_positions_from_storage();
+        let mut positions = lp_incentive_positions.get(&sender_id).unwrap_or_default();
+        let claimed = positions.get_mut(&pool_id).map(|position| std::mem::take(&mut position.owed)).unwrap_or(0);
+        if claimed > 0 {
+            let mut account = self.internal_unwrap_account(&sender_id);
+            account.deposit(&incentive.token_id, claimed);
+            self.internal_save_account(&sender_id, account);
+            incentive.claimed += claimed;
+            incentive.escrowed -= claimed;
+            release_locked_deposit(&incentive.funder_id, &incentive.token_id, claimed);
+            lp_incentives.insert(&pool_id, &incentive);
+            write_lp_incentives_to_storage(lp_incentives);
+        }
+        lp_incentive_positions.insert(&sender_id, &positions);
+        write_lp_incentive_positions_to_storage(lp_incentive_positions);
+        log!("{} claimed incentive {} {} from pool {}", sender_id, claimed, incentive.token_id, pool_id);
+        claimed.into()
+    }
+
+    /// Close an ended escrow, returning the part unlocked while the pool had no shares to the funder.
+    /// LPs can still claim what they earned from it.
+    #[payable]
+    pub fn close_lp_incentive(&mut self, pool_id: u64) -> U128 {
+        assert_one_yocto();
+        let incentive = read_lp_incentives_from_storage().get(&pool_id).expect("No incentive for the pool");
+        assert_eq!(env::predecessor_account_id(), incentive.funder_id, "{}", ERR100_NOT_ALLOWED);
+        assert!(env::block_timestamp() >= incentive.end, "Incentive not ended");
+        self.internal_close_lp_incentive(pool_id, &self.internal_get_pool(pool_id)).into()
+    }
+
+    pub fn get_lp_incentive(&self, pool_id: u64) -> Option<LpIncentive> {
+        let pool = self.internal_get_pool(pool_id);
+        read_lp_incentives_from_storage().get(&pool_id).map(|mut incentive| {
+            incentive.update(pool.share_total_balance());
+            incentive
+        })
+    }
+
+    /// Returns the incentive the account can claim from the given pool right now.
+    pub fn get_claimable_lp_incentive(&self, pool_id: u64, account_id: ValidAccountId) -> U128 {
+        let incentive = match self.get_lp_incentive(pool_id) {
+            Some(incentive) => incentive,
+            None => return U128(0),
+        };
+        let pool = self.internal_get_pool(pool_id);
+        let mut position = read_lp_incentive_positions_from_storage()
+            .get(account_id.as_ref())
+            .and_then(|mut positions| positions.remove(&pool_id))
+            .unwrap_or_default();
+        position.settle(incentive.growth, pool.share_balances(account_id.as_ref()));
+        position.owed.into()
+    }
+}
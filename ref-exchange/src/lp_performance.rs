@@ -0,0 +1,166 @@
+use crate::*;
+use crate::utils::{to_nano, u128_dec_format, u128_ratio, u64_dec_format, FEE_DIVISOR, U256};
+use near_sdk::Timestamp;
+
+/// Shortest time between two share price checkpoints of a pool.
+pub const LP_CHECKPOINT_INTERVAL_SEC: u32 = 24 * 3600;
+/// Checkpoints kept per pool, the oldest is dropped beyond it.
+pub const MAX_LP_CHECKPOINTS: usize = 90;
+pub const SECONDS_PER_YEAR: u32 = 365 * 24 * 3600;
+
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone)]
+#[serde(crate = "near_sdk::serde")]
+#[cfg_attr(not(target_arch = "wasm32"), derive(Debug))]
+pub struct LpCheckpoint {
+    #[serde(with = "u64_dec_format")]
+    pub id: u64,
+    #[serde(with = "u64_dec_format")]
+    pub timestamp: Timestamp,
+    /// Pool share price with 1e8 precision, see `get_pool_share_price`.
+    #[serde(with = "u128_dec_format")]
+    pub share_price: u128,
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Default)]
+pub struct LpCheckpoints {
+    pub next_id: u64,
+    pub records: Vec<LpCheckpoint>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+#[cfg_attr(not(target_arch = "wasm32"), derive(Debug))]
+pub struct LpPerformance {
+    pub checkpoint: LpCheckpoint,
+    pub share_price: U128,
+    /// Share price growth since the checkpoint in bps, 0 if it went down.
+    pub share_price_growth_bps: u32,
+    pub shares: U128,
+    /// Token amounts the shares are worth now.
+    pub amounts: Vec<U128>,
+    /// Part of `amounts` earned from fees, assuming the shares were held since the checkpoint.
+    pub implied_fee_earnings: Vec<U128>,
+}
+
+/// Fee APR of a pool over its last checkpoint period, the same figure for every frontend.
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+#[cfg_attr(not(target_arch = "wasm32"), derive(Debug))]
+pub struct PoolFeeApr {
+    pub from_checkpoint: LpCheckpoint,
+    pub to_checkpoint: LpCheckpoint,
+    /// Fees compounded into the pool over the period relative to its TVL, annualized, in bps.
+    pub fee_apr_bps: u32,
+}
+
+pub fn read_lp_checkpoints_from_storage() -> LookupMap<u64, LpCheckpoints> {
+    if let Some(content) = env::storage_read(LP_CHECKPOINTS.as_bytes()) {
+        LookupMap::try_from_slice(&content).expect("deserialize lp checkpoints failed.")
+    } else {
+        LookupMap::new(StorageKey::LpCheckpoints)
+    }
+}
+
+pub fn write_lp_checkpoints_to_storage(lp_checkpoints: LookupMap<u64, LpCheckpoints>) {
+    env::storage_write(
+        LP_CHECKPOINTS.as_bytes(),
+        &lp_checkpoints.try_to_vec().unwrap(),
+    );
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Record the current share price of the pool, at most once per LP_CHECKPOINT_INTERVAL_SEC.
+    /// Anyone can call it, the attached deposit covers the new record until the pool is at MAX_LP_CHECKPOINTS.
+    #[payable]
+    pub fn checkpoint_pool_share_price(&mut self, pool_id: u64) -> u64 {
+        let prev_storage = env::storage_usage();
+        let pool = self.internal_get_pool(pool_id);
+        assert!(pool.share_total_balance() > 0, "Pool has no liquidity");
+        let mut lp_checkpoints = read_lp_checkpoints_from_storage();
+        let mut checkpoints = lp_checkpoints.get(&pool_id).unwrap_or_default();
+        if let Some(last) = checkpoints.records.last() {
+            assert!(
+                env::block_timestamp() >= last.timestamp + to_nano(LP_CHECKPOINT_INTERVAL_SEC),
+                "Checkpoint {} is too recent", last.id
+            );
+        }
+        let id = checkpoints.next_id;
+        checkpoints.next_id += 1;
+        checkpoints.records.push(LpCheckpoint {
+            id,
+            timestamp: env::block_timestamp(),
+            share_price: pool.get_share_price(),
+        });
+        if checkpoints.records.len() > MAX_LP_CHECKPOINTS {
+            checkpoints.records.remove(0);
+        }
+        lp_checkpoints.insert(&pool_id, &checkpoints);
+        write_lp_checkpoints_to_storage(lp_checkpoints);
+        self.internal_check_storage(prev_storage);
+        id
+    }
+
+    pub fn get_lp_checkpoints(&self, pool_id: u64) -> Vec<LpCheckpoint> {
+        read_lp_checkpoints_from_storage().get(&pool_id).map(|checkpoints| checkpoints.records).unwrap_or_default()
+    }
+
+    /// Fee APR of the pool between its two latest share price checkpoints, None until it has two.
+    /// Share price growth is the fees earned per share over the time-weighted TVL, so no average
+    /// is taken separately. Fees kept aside for `claim_fees` are not included, and for rated and
+    /// degen pools the growth includes rate moves as well.
+    pub fn get_pool_fee_apr(&self, pool_id: u64) -> Option<PoolFeeApr> {
+        let mut records = read_lp_checkpoints_from_storage().get(&pool_id)?.records;
+        let to_checkpoint = records.pop()?;
+        let from_checkpoint = records.pop()?;
+        let period = to_checkpoint.timestamp - from_checkpoint.timestamp;
+        let growth = to_checkpoint.share_price.saturating_sub(from_checkpoint.share_price);
+        let fee_apr_bps = if from_checkpoint.share_price == 0 || period == 0 {
+            0
+        } else {
+            std::cmp::min(
+                U256::from(growth) * U256::from(FEE_DIVISOR) * U256::from(to_nano(SECONDS_PER_YEAR))
+                    / U256::from(from_checkpoint.share_price)
+                    / U256::from(period),
+                U256::from(u32::MAX),
+            ).as_u32()
+        };
+        Some(PoolFeeApr { from_checkpoint, to_checkpoint, fee_apr_bps })
+    }
+
+    /// Growth of the account's shares in the pool since the given checkpoint, and the fee
+    /// earnings implied by it. Share price grows with fees, and for rated and degen pools
+    /// moves with their rates too, so the earnings are the current amounts scaled by its growth.
+    pub fn get_lp_performance(&self, pool_id: u64, account_id: ValidAccountId, since_checkpoint: u64) -> LpPerformance {
+        let checkpoint = read_lp_checkpoints_from_storage()
+            .get(&pool_id)
+            .and_then(|checkpoints| checkpoints.records.into_iter().find(|checkpoint| checkpoint.id == since_checkpoint))
+            .expect("Checkpoint not found");
+        let pool = self.internal_get_pool(pool_id);
+        let share_price = pool.get_share_price();
+        let shares = pool.share_balances(account_id.as_ref());
+        let total_shares = pool.share_total_balance();
+        let amounts: Vec<Balance> = pool.get_amounts().into_iter().map(|amount| {
+            if total_shares == 0 { 0 } else { u128_ratio(amount, shares, total_shares) }
+        }).collect();
+        let growth = share_price.saturating_sub(checkpoint.share_price);
+        let share_price_growth_bps = if checkpoint.share_price == 0 {
+            0
+        } else {
+            std::cmp::min(
+                U256::from(growth) * U256::from(FEE_DIVISOR) / U256::from(checkpoint.share_price),
+                U256::from(u32::MAX),
+            ).as_u32()
+        };
+        LpPerformance {
+            checkpoint,
+            share_price: share_price.into(),
+            share_price_growth_bps,
+            shares: shares.into(),
+            implied_fee_earnings: amounts.iter().map(|amount| {
+                if share_price == 0 { U128(0) } else { U128(u128_ratio(*amount, growth, share_price)) }
+            }).collect(),
+            amounts: amounts.into_iter().map(|amount| amount.into()).collect(),
+        }
+    }
+}
@@ -0,0 +1,315 @@
+use near_contract_standards::non_fungible_token::metadata::{
+    NFTContractMetadata, TokenMetadata, NFT_METADATA_SPEC,
+};
+use near_contract_standards::non_fungible_token::{Token, TokenId};
+use near_sdk::serde_json::{self, json};
+use near_sdk::PromiseOrValue;
+
+use crate::utils::{GAS_FOR_RESOLVE_TRANSFER, NO_DEPOSIT};
+use crate::*;
+
+pub const GAS_FOR_NFT_ON_TRANSFER: Gas = 30_000_000_000_000;
+
+#[ext_contract(ext_nft_receiver)]
+pub trait NonFungibleTokenReceiver {
+    fn nft_on_transfer(
+        &mut self,
+        sender_id: AccountId,
+        previous_owner_id: AccountId,
+        token_id: TokenId,
+        msg: String,
+    ) -> PromiseOrValue<bool>;
+}
+
+#[ext_contract(ext_nft_resolver)]
+trait NonFungibleTokenResolver {
+    fn nft_resolve_transfer(
+        &mut self,
+        previous_owner_id: AccountId,
+        receiver_id: AccountId,
+        token_id: TokenId,
+    ) -> bool;
+}
+
+/// Logs a NEP-171 event for share lock NFTs.
+pub(crate) fn emit_nft_event(event: &str, data: serde_json::Value) {
+    let event_json = json!({
+        "standard": "nep171",
+        "version": "1.0.0",
+        "event": event,
+        "data": [data]
+    })
+    .to_string();
+    log!("EVENT_JSON:{}", event_json);
+}
+
+fn parse_lock_id(token_id: &TokenId) -> u64 {
+    token_id.parse().expect("Invalid token_id")
+}
+
+fn share_lock_token(lock_id: u64, lock: ShareLock) -> Token {
+    let locked_amount = lock.locked_amount(env::block_timestamp());
+    Token {
+        token_id: lock_id.to_string(),
+        owner_id: lock.account_id.clone(),
+        metadata: Some(TokenMetadata {
+            title: Some(format!("REF-POOL-{} locked shares #{}", lock.pool_id, lock_id)),
+            description: None,
+            media: None,
+            media_hash: None,
+            copies: Some(1),
+            issued_at: None,
+            expires_at: Some(lock.end_time.to_string()),
+            starts_at: None,
+            updated_at: None,
+            extra: Some(serde_json::to_string(&ShareLockInfo {
+                lock_id,
+                locked_amount: locked_amount.into(),
+                lock,
+            }).unwrap()),
+            reference: None,
+            reference_hash: None,
+        }),
+        approved_account_ids: None,
+    }
+}
+
+impl Contract {
+    /// Counts the shares a transferred lock took along towards the sender's daily withdrawal limits.
+    fn internal_record_limited_lock_transfer(&mut self, sender_id: &AccountId, lock_id: u64, locked_amount: Balance) {
+        let pool_id = read_share_locks_from_storage().get(&lock_id).unwrap().pool_id;
+        self.internal_record_limited_withdrawal(sender_id, &format!(":{}", pool_id), locked_amount);
+    }
+
+    /// Hands the lock over to `receiver_id` together with the shares it still holds.
+    /// Shares already vested stay with the sender, the remaining schedule is unchanged.
+    fn internal_transfer_share_lock(
+        &mut self,
+        lock_id: u64,
+        sender_id: &AccountId,
+        receiver_id: &AccountId,
+    ) -> Result<Balance, &'static str> {
+        if sender_id == receiver_id {
+            return Err(ERR33_TRANSFER_TO_SELF);
+        }
+        assert_account_not_denied(sender_id);
+        assert_account_not_denied(receiver_id);
+        let mut share_locks = read_share_locks_from_storage();
+        let mut lock = share_locks.get(&lock_id).ok_or("Share lock not found")?;
+        if &lock.account_id != sender_id {
+            return Err("Not the owner of the share lock");
+        }
+        let current_time = env::block_timestamp();
+        let locked_amount = lock.locked_amount(current_time);
+        if locked_amount == 0 {
+            return Err("Share lock already released");
+        }
+        let mut pool = self.internal_get_pool(lock.pool_id);
+        if !pool.share_has_registered(receiver_id) {
+            return Err(ERR13_LP_NOT_REGISTERED);
+        }
+        if !self.internal_is_lp_allowed(lock.pool_id, receiver_id) {
+            return Err("Receiver not allowed to provide liquidity to the pool");
+        }
+        let total_shares = pool.share_balances(sender_id);
+        let free_shares = self.internal_get_account(sender_id)
+            .and_then(|account| account.get_shadow_record(lock.pool_id))
+            .map(|record| record.free_shares(total_shares))
+            .unwrap_or(total_shares);
+        if locked_amount > free_shares {
+            return Err("Not enough free shares");
+        }
+        let mut account_share_locks = read_account_share_locks_from_storage();
+        let mut receiver_lock_ids = account_share_locks.get(receiver_id).unwrap_or_default();
+        if receiver_lock_ids.len() >= MAX_SHARE_LOCKS_PER_ACCOUNT {
+            return Err("Too many share locks");
+        }
+
+        let prev_storage = env::storage_usage();
+        self.internal_settle_lp_fees(lock.pool_id, &pool, &[sender_id, receiver_id]);
+        self.internal_charge_caller_storage(sender_id, prev_storage);
+        pool.share_transfer(sender_id, receiver_id, locked_amount);
+        self.pools.replace(lock.pool_id, &pool);
+
+        let mut sender_lock_ids = account_share_locks.get(sender_id).unwrap_or_default();
+        sender_lock_ids.retain(|id| *id != lock_id);
+        if sender_lock_ids.is_empty() {
+            account_share_locks.remove(sender_id);
+        } else {
+            account_share_locks.insert(sender_id, &sender_lock_ids);
+        }
+        receiver_lock_ids.push(lock_id);
+        account_share_locks.insert(receiver_id, &receiver_lock_ids);
+        write_account_share_locks_to_storage(account_share_locks);
+
+        lock.account_id = receiver_id.clone();
+        lock.amount = locked_amount;
+        lock.start_time = std::cmp::max(lock.start_time, current_time);
+        share_locks.insert(&lock_id, &lock);
+        write_share_locks_to_storage(share_locks);
+
+        emit_nft_event("nft_transfer", json!({
+            "old_owner_id": sender_id,
+            "new_owner_id": receiver_id,
+            "token_ids": [lock_id.to_string()],
+        }));
+        Ok(locked_amount)
+    }
+}
+
+/// Every share lock is a NEP-171 token, its token_id being the lock id.
+/// Transferring the token moves the still locked shares with it, the receiver must be registered in the pool.
+/// Approvals are not supported.
+#[near_bindgen]
+impl Contract {
+    #[payable]
+    pub fn nft_transfer(
+        &mut self,
+        receiver_id: ValidAccountId,
+        token_id: TokenId,
+        approval_id: Option<u64>,
+        memo: Option<String>,
+    ) {
+        assert_one_yocto();
+        self.assert_contract_running();
+        assert!(approval_id.is_none(), "Approvals not supported");
+        let sender_id = env::predecessor_account_id();
+        let lock_id = parse_lock_id(&token_id);
+        let locked_amount = self.internal_transfer_share_lock(lock_id, &sender_id, receiver_id.as_ref())
+            .unwrap_or_else(|err| env::panic(err.as_bytes()));
+        self.internal_record_limited_lock_transfer(&sender_id, lock_id, locked_amount);
+        if let Some(memo) = memo {
+            log!("Memo: {}", memo);
+        }
+    }
+
+    #[payable]
+    pub fn nft_transfer_call(
+        &mut self,
+        receiver_id: ValidAccountId,
+        token_id: TokenId,
+        approval_id: Option<u64>,
+        memo: Option<String>,
+        msg: String,
+    ) -> PromiseOrValue<bool> {
+        assert_one_yocto();
+        self.assert_contract_running();
+        assert!(approval_id.is_none(), "Approvals not supported");
+        let sender_id = env::predecessor_account_id();
+        let lock_id = parse_lock_id(&token_id);
+        let locked_amount = self.internal_transfer_share_lock(lock_id, &sender_id, receiver_id.as_ref())
+            .unwrap_or_else(|err| env::panic(err.as_bytes()));
+        self.internal_record_limited_lock_transfer(&sender_id, lock_id, locked_amount);
+        if let Some(memo) = memo {
+            log!("Memo: {}", memo);
+        }
+        ext_nft_receiver::nft_on_transfer(
+            sender_id.clone(),
+            sender_id.clone(),
+            token_id.clone(),
+            msg,
+            receiver_id.as_ref(),
+            NO_DEPOSIT,
+            GAS_FOR_NFT_ON_TRANSFER,
+        )
+        .then(ext_nft_resolver::nft_resolve_transfer(
+            sender_id,
+            receiver_id.into(),
+            token_id,
+            &env::current_account_id(),
+            NO_DEPOSIT,
+            GAS_FOR_RESOLVE_TRANSFER,
+        ))
+        .into()
+    }
+
+    /// Returns true if the token stays with the receiver. If the receiver asked to return it
+    /// and still can, the lock and its shares go back to the previous owner.
+    #[private]
+    pub fn nft_resolve_transfer(
+        &mut self,
+        previous_owner_id: AccountId,
+        receiver_id: AccountId,
+        token_id: TokenId,
+    ) -> bool {
+        let return_token = match env::promise_result(0) {
+            PromiseResult::NotReady => unreachable!(),
+            PromiseResult::Successful(value) => {
+                near_sdk::serde_json::from_slice::<bool>(&value).unwrap_or(true)
+            }
+            PromiseResult::Failed => true,
+        };
+        if !return_token {
+            return true;
+        }
+        match self.internal_transfer_share_lock(parse_lock_id(&token_id), &receiver_id, &previous_owner_id) {
+            Ok(_) => false,
+            Err(err) => {
+                log!("Share lock {} not returned: {}", token_id, err);
+                true
+            }
+        }
+    }
+
+    pub fn nft_token(&self, token_id: TokenId) -> Option<Token> {
+        let lock_id = token_id.parse().ok()?;
+        read_share_locks_from_storage()
+            .get(&lock_id)
+            .map(|lock| share_lock_token(lock_id, lock))
+    }
+
+    pub fn nft_metadata(&self) -> NFTContractMetadata {
+        NFTContractMetadata {
+            spec: NFT_METADATA_SPEC.to_string(),
+            name: "Ref locked LP positions".to_string(),
+            symbol: "REF-LOCK".to_string(),
+            icon: None,
+            base_uri: None,
+            reference: None,
+            reference_hash: None,
+        }
+    }
+
+    pub fn nft_total_supply(&self) -> U128 {
+        U128(read_share_lock_ids_from_storage().len() as u128)
+    }
+
+    /// Tokens by position in the lock id index, positions change as locks are released.
+    pub fn nft_tokens(&self, from_index: Option<U128>, limit: Option<u64>) -> Vec<Token> {
+        let share_locks = read_share_locks_from_storage();
+        let lock_ids = read_share_lock_ids_from_storage();
+        let lock_ids = lock_ids.as_vector();
+        let from_index = from_index.map(|v| v.0 as u64).unwrap_or(0);
+        let limit = limit.unwrap_or(lock_ids.len());
+        (from_index..std::cmp::min(from_index.saturating_add(limit), lock_ids.len()))
+            .map(|index| {
+                let lock_id = lock_ids.get(index).unwrap();
+                share_lock_token(lock_id, share_locks.get(&lock_id).unwrap())
+            })
+            .collect()
+    }
+
+    pub fn nft_supply_for_owner(&self, account_id: ValidAccountId) -> U128 {
+        U128(read_account_share_locks_from_storage()
+            .get(account_id.as_ref())
+            .map(|lock_ids| lock_ids.len())
+            .unwrap_or(0) as u128)
+    }
+
+    pub fn nft_tokens_for_owner(
+        &self,
+        account_id: ValidAccountId,
+        from_index: Option<U128>,
+        limit: Option<u64>,
+    ) -> Vec<Token> {
+        let share_locks = read_share_locks_from_storage();
+        read_account_share_locks_from_storage()
+            .get(account_id.as_ref())
+            .unwrap_or_default()
+            .into_iter()
+            .skip(from_index.map(|v| v.0 as usize).unwrap_or(0))
+            .take(limit.map(|v| v as usize).unwrap_or(usize::MAX))
+            .filter_map(|lock_id| share_locks.get(&lock_id).map(|lock| share_lock_token(lock_id, lock)))
+            .collect()
+    }
+}
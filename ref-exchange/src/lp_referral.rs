@@ -0,0 +1,182 @@
+use crate::*;
+
+/// Upper bound of the referrer's cut of an LP's fees, in bps.
+pub const MAX_LP_REFERRAL_FEE_BPS: u32 = 2000;
+
+pub fn read_lp_referral_fee_bps_from_storage() -> u32 {
+    env::storage_read(LP_REFERRAL_FEE_BPS.as_bytes())
+        .map(|content| u32::try_from_slice(&content).expect("deserialize lp referral fee bps failed."))
+        .unwrap_or(0)
+}
+
+pub fn write_lp_referral_fee_bps_to_storage(fee_bps: u32) {
+    env::storage_write(LP_REFERRAL_FEE_BPS.as_bytes(), &fee_bps.try_to_vec().unwrap());
+}
+
+/// Referrer of each LP per pool and the referral fee bps at the time, fixed by the first referred add liquidity.
+pub fn read_lp_referrers_from_storage() -> LookupMap<AccountId, HashMap<u64, (AccountId, u32)>> {
+    if let Some(content) = env::storage_read(LP_REFERRERS.as_bytes()) {
+        LookupMap::try_from_slice(&content).expect("deserialize lp referrers failed.")
+    } else {
+        LookupMap::new(StorageKey::LpReferrers)
+    }
+}
+
+pub fn write_lp_referrers_to_storage(lp_referrers: LookupMap<AccountId, HashMap<u64, (AccountId, u32)>>) {
+    env::storage_write(
+        LP_REFERRERS.as_bytes(),
+        &lp_referrers.try_to_vec().unwrap(),
+    );
+}
+
+/// Fees accrued to each referrer per pool, in pool token order. An entry is created when the referrer
+/// is first recorded in the pool, at the referred LP's expense, and kept after claims.
+pub fn read_lp_referral_rewards_from_storage() -> LookupMap<AccountId, HashMap<u64, Vec<Balance>>> {
+    if let Some(content) = env::storage_read(LP_REFERRAL_REWARDS.as_bytes()) {
+        LookupMap::try_from_slice(&content).expect("deserialize lp referral rewards failed.")
+    } else {
+        LookupMap::new(StorageKey::LpReferralRewards)
+    }
+}
+
+pub fn write_lp_referral_rewards_to_storage(lp_referral_rewards: LookupMap<AccountId, HashMap<u64, Vec<Balance>>>) {
+    env::storage_write(
+        LP_REFERRAL_REWARDS.as_bytes(),
+        &lp_referral_rewards.try_to_vec().unwrap(),
+    );
+}
+
+/// Referrer of the account in the pool and the referral fee bps it gets.
+pub fn lp_referrer_of(pool_id: u64, account_id: &AccountId) -> Option<(AccountId, u32)> {
+    read_lp_referrers_from_storage()
+        .get(account_id)
+        .and_then(|referrers| referrers.get(&pool_id).cloned())
+}
+
+/// Adds the referrer's cut of an LP's settled fees to its existing rewards entry. The amounts stay in the pool's
+/// unclaimed fees until claimed.
+pub fn accrue_lp_referral_rewards(referrer_id: &AccountId, pool_id: u64, amounts: &[Balance]) {
+    if amounts.iter().all(|amount| *amount == 0) {
+        return;
+    }
+    let mut lp_referral_rewards = read_lp_referral_rewards_from_storage();
+    let mut rewards = lp_referral_rewards.get(referrer_id).unwrap_or_default();
+    let accrued = match rewards.get_mut(&pool_id) {
+        Some(accrued) => accrued,
+        None => return,
+    };
+    for (i, amount) in amounts.iter().enumerate() {
+        accrued[i] += amount;
+    }
+    lp_referral_rewards.insert(referrer_id, &rewards);
+    write_lp_referral_rewards_to_storage(lp_referral_rewards);
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Same as `add_liquidity`, and records `referral_id` as the sender's referrer in this pool
+    /// if it's an active referral and the sender has none there yet. The referrer then gets the
+    /// current `get_lp_referral_fee_bps` of the fees the sender earns in the pool from now on, paid out of
+    /// the exchange's own fees of the pool. Attached deposit covers the referrer and rewards records.
+    #[payable]
+    pub fn add_liquidity_with_referral(
+        &mut self,
+        pool_id: u64,
+        amounts: Vec<U128>,
+        min_amounts: Option<Vec<U128>>,
+        referral_id: ValidAccountId,
+    ) -> U128 {
+        self.assert_contract_running();
+        assert!(
+            env::attached_deposit() > 0,
+            "{}", ERR35_AT_LEAST_ONE_YOCTO
+        );
+        self.internal_update_unit_share_cumulative_info(pool_id);
+        let prev_storage = env::storage_usage();
+        let sender_id = env::predecessor_account_id();
+        let shares = self.internal_add_liquidity(pool_id, &sender_id, amounts, min_amounts);
+        if let Some((referral_id, _)) = self.internal_get_referral_info(Some(referral_id.into()), &sender_id) {
+            let mut lp_referrers = read_lp_referrers_from_storage();
+            let mut referrers = lp_referrers.get(&sender_id).unwrap_or_default();
+            if let std::collections::hash_map::Entry::Vacant(entry) = referrers.entry(pool_id) {
+                log!("{} referred {} in pool {}", referral_id, sender_id, pool_id);
+                let mut lp_referral_rewards = read_lp_referral_rewards_from_storage();
+                let mut rewards = lp_referral_rewards.get(&referral_id).unwrap_or_default();
+                if let std::collections::hash_map::Entry::Vacant(rewards_entry) = rewards.entry(pool_id) {
+                    rewards_entry.insert(vec![0; self.internal_get_pool(pool_id).tokens().len()]);
+                    lp_referral_rewards.insert(&referral_id, &rewards);
+                    write_lp_referral_rewards_to_storage(lp_referral_rewards);
+                }
+                entry.insert((referral_id, read_lp_referral_fee_bps_from_storage()));
+                lp_referrers.insert(&sender_id, &referrers);
+                write_lp_referrers_to_storage(lp_referrers);
+            }
+        }
+        self.internal_check_storage(prev_storage);
+        U128(shares)
+    }
+
+    /// Moves the LP fees accrued to the sender as referrer in the pool to its inner account.
+    #[payable]
+    pub fn claim_lp_referral_rewards(&mut self, pool_id: u64) -> Vec<U128> {
+        assert_one_yocto();
+        self.assert_contract_running();
+        let sender_id = env::predecessor_account_id();
+        let pool = self.internal_get_pool(pool_id);
+        let mut lp_referral_rewards = read_lp_referral_rewards_from_storage();
+        let mut rewards = lp_referral_rewards.get(&sender_id).unwrap_or_default();
+        let claimed = rewards.get_mut(&pool_id)
+            .map(|accrued| std::mem::replace(accrued, vec![0; pool.tokens().len()]))
+            .unwrap_or_else(|| vec![0; pool.tokens().len()]);
+
+        let mut pool_fee_growth = read_pool_fee_growth_from_storage();
+        if let Some(mut state) = pool_fee_growth.get(&pool_id) {
+            let mut deposits = self.internal_unwrap_account(&sender_id);
+            for (i, token_id) in pool.tokens().iter().enumerate() {
+                state.unclaimed[i] -= claimed[i];
+                if claimed[i] > 0 {
+                    deposits.deposit(token_id, claimed[i]);
+                }
+            }
+            self.internal_save_account(&sender_id, deposits);
+            pool_fee_growth.insert(&pool_id, &state);
+            write_pool_fee_growth_to_storage(pool_fee_growth);
+        }
+        if claimed.iter().any(|amount| *amount > 0) {
+            lp_referral_rewards.insert(&sender_id, &rewards);
+            write_lp_referral_rewards_to_storage(lp_referral_rewards);
+        }
+        log!("{} claimed lp referral rewards {:?} from pool {}", sender_id, claimed, pool_id);
+        claimed.into_iter().map(|amount| amount.into()).collect()
+    }
+
+    /// Set the cut of referred LPs' fees paid to their referrers out of the exchange's fees, at most MAX_LP_REFERRAL_FEE_BPS.
+    /// Applies to referrers recorded from now on.
+    #[payable]
+    pub fn set_lp_referral_fee_bps(&mut self, fee_bps: u32) {
+        assert_one_yocto();
+        self.assert_owner();
+        audit_privileged_action("set_lp_referral_fee_bps");
+        assert!(fee_bps <= MAX_LP_REFERRAL_FEE_BPS, "LP referral fee must be at most {} bps", MAX_LP_REFERRAL_FEE_BPS);
+        write_lp_referral_fee_bps_to_storage(fee_bps);
+    }
+
+    pub fn get_lp_referral_fee_bps(&self) -> u32 {
+        read_lp_referral_fee_bps_from_storage()
+    }
+
+    pub fn get_lp_referrer(&self, pool_id: u64, account_id: ValidAccountId) -> Option<AccountId> {
+        lp_referrer_of(pool_id, account_id.as_ref()).map(|(referrer_id, _)| referrer_id)
+    }
+
+    /// Returns the rewards accrued to the referrer in the pool, not counting fees its LPs earned since their last settlement.
+    pub fn get_lp_referral_rewards(&self, pool_id: u64, referrer_id: ValidAccountId) -> Vec<U128> {
+        read_lp_referral_rewards_from_storage()
+            .get(referrer_id.as_ref())
+            .and_then(|mut rewards| rewards.remove(&pool_id))
+            .unwrap_or_else(|| vec![0; self.internal_get_pool(pool_id).tokens().len()])
+            .into_iter()
+            .map(|amount| amount.into())
+            .collect()
+    }
+}
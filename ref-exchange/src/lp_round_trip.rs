@@ -0,0 +1,105 @@
+use crate::*;
+use crate::utils::{FEE_DIVISOR, U256};
+use near_sdk::json_types::U64;
+
+/// Weight of each token of a simple pool when valuing amounts against its reserves.
+const SIMPLE_VALUE_PRECISION: u128 = 1_000_000_000_000_000_000_000_000;
+
+/// Estimated cost of adding liquidity, holding it and removing it again, see `estimate_lp_round_trip`.
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+#[cfg_attr(not(target_arch = "wasm32"), derive(Debug))]
+pub struct LpRoundTripEstimate {
+    /// Amounts the add takes, for simple pools the part of the given amounts fitting the pool ratio.
+    pub added_amounts: Vec<U128>,
+    pub shares: U128,
+    /// Amounts the shares are worth right after the add.
+    pub share_amounts: Vec<U128>,
+    /// Value lost to the imbalance fee and rounding when adding, in bps of the added value.
+    pub add_cost_bps: u32,
+    /// Value lost to the imbalance fee when withdrawing the added token mix again, in bps of the added value.
+    pub exit_cost_bps: u32,
+    /// Current fee APR of the pool, see `get_pool_fee_apr`.
+    pub fee_apr_bps: Option<u32>,
+    /// Fees the shares are expected to earn per day at that APR.
+    pub daily_fee_earnings: Vec<U128>,
+    /// Holding time after which the expected fees cover both costs, None without a positive APR.
+    pub break_even_sec: Option<U64>,
+}
+
+/// Value of the amounts relative to the reserves, each token weighted by its part of the pool value.
+fn relative_value(amounts: &[Balance], reserves: &[Balance], weights: &[Balance]) -> U256 {
+    amounts.iter().zip(reserves).zip(weights).fold(U256::zero(), |acc, ((amount, reserve), weight)| {
+        acc + U256::from(*amount) * U256::from(*weight) / U256::from(*reserve)
+    })
+}
+
+fn lost_bps(lost: U256, total: U256) -> u32 {
+    if total.is_zero() {
+        0
+    } else {
+        std::cmp::min(lost * U256::from(FEE_DIVISOR) / total, U256::from(FEE_DIVISOR)).as_u32()
+    }
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Estimate the cost of adding the amounts to the pool, and of withdrawing the same token mix
+    /// afterwards, against the fees the shares would earn at the pool's current fee APR.
+    /// Simple pools add and remove pro rata, so their only cost is in the add.
+    pub fn estimate_lp_round_trip(&self, pool_id: u64, amounts: Vec<U128>) -> LpRoundTripEstimate {
+        let mut pool = self.internal_get_pool(pool_id);
+        let reserves = pool.get_amounts();
+        assert!(reserves.iter().all(|reserve| *reserve > 0), "Pool has no liquidity");
+        let is_simple = matches!(pool, Pool::SimplePool(_) | Pool::RangePool(_));
+        let weights = if is_simple {
+            vec![SIMPLE_VALUE_PRECISION; reserves.len()]
+        } else {
+            valued_reserves(&pool).expect(ERR129_DEGENS_EXPIRED)
+        };
+        let view_id = String::from("@view");
+        let mut added: Vec<Balance> = amounts.into_iter().map(|amount| amount.0).collect();
+        let shares = if is_simple {
+            pool.add_liquidity(&view_id, &mut added, true)
+        } else {
+            pool.add_stable_liquidity(&view_id, &added, 0, AdminFees::new(self.admin_fee_bps), true)
+        };
+        let total_shares = pool.share_total_balance();
+        let share_amounts: Vec<Balance> = pool.get_amounts()
+            .iter()
+            .map(|amount| (U256::from(*amount) * U256::from(shares) / U256::from(total_shares)).as_u128())
+            .collect();
+
+        let added_value = relative_value(&added, &reserves, &weights);
+        let share_value = relative_value(&share_amounts, &reserves, &weights);
+        let add_cost_bps = lost_bps(added_value.saturating_sub(share_value), added_value);
+        let exit_cost_bps = if is_simple {
+            0
+        } else {
+            // shares burnt to get back exactly the added amounts, compared to the minted ones
+            let burn_shares = pool.remove_liquidity_by_tokens(&view_id, added.clone(), u128::MAX, AdminFees::new(self.admin_fee_bps), true);
+            let round_trip_cost_bps = lost_bps(U256::from(burn_shares.saturating_sub(shares)), U256::from(burn_shares));
+            round_trip_cost_bps.saturating_sub(add_cost_bps)
+        };
+
+        let fee_apr_bps = self.get_pool_fee_apr(pool_id).map(|apr| apr.fee_apr_bps);
+        let apr_bps = fee_apr_bps.unwrap_or(0) as u128;
+        let daily_fee_earnings = share_amounts
+            .iter()
+            .map(|amount| U128((U256::from(*amount) * U256::from(apr_bps) / U256::from(FEE_DIVISOR) / U256::from(365)).as_u128()))
+            .collect();
+        let break_even_sec = ((add_cost_bps + exit_cost_bps) as u128 * SECONDS_PER_YEAR as u128)
+            .checked_div(apr_bps)
+            .map(|sec| U64(sec as u64));
+        LpRoundTripEstimate {
+            added_amounts: added.into_iter().map(U128).collect(),
+            shares: shares.into(),
+            share_amounts: share_amounts.into_iter().map(U128).collect(),
+            add_cost_bps,
+            exit_cost_bps,
+            fee_apr_bps,
+            daily_fee_earnings,
+            break_even_sec,
+        }
+    }
+}
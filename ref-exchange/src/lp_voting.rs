@@ -0,0 +1,332 @@
+use crate::*;
+use crate::utils::{to_nano, u128_dec_format, u64_dec_format, FEE_DIVISOR, U256};
+use near_sdk::Timestamp;
+
+pub const LP_VOTE_DURATION_SEC: u32 = 7 * 24 * 3600;
+/// Share of the pool supply at the proposal's checkpoint, in bps, that must approve it.
+pub const LP_VOTE_QUORUM_BPS: u32 = 5_000;
+/// Share of the pool supply, in bps, the proposer must hold.
+pub const LP_PROPOSAL_MIN_SHARES_BPS: u32 = 100;
+/// A fee proposal can at most halve or double the pool fee.
+pub const LP_VOTE_MAX_FEE_FACTOR: u32 = 2;
+/// Amp changes voted by LPs ramp over this long, the pool bounds the target itself.
+pub const LP_VOTE_AMP_RAMP_DURATION_SEC: u32 = 2 * 24 * 3600;
+pub const MAX_LP_VOTES_PER_ACCOUNT: usize = 20;
+
+/// Pool parameter change the pool's LPs can vote on.
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(crate = "near_sdk::serde")]
+#[cfg_attr(not(target_arch = "wasm32"), derive(Debug))]
+pub enum PoolParamChange {
+    TotalFee { total_fee: u32 },
+    AmpFactor { amp_factor: u64 },
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone)]
+#[serde(crate = "near_sdk::serde")]
+#[cfg_attr(not(target_arch = "wasm32"), derive(Debug))]
+pub struct LpProposal {
+    pub pool_id: u64,
+    pub proposer_id: AccountId,
+    pub change: PoolParamChange,
+    /// Share checkpoint taken when the proposal was made, votes are weighted by it.
+    pub checkpoint_id: u64,
+    #[serde(with = "u128_dec_format")]
+    pub shares_total_supply: Balance,
+    #[serde(with = "u128_dec_format")]
+    pub yes_shares: Balance,
+    #[serde(with = "u128_dec_format")]
+    pub no_shares: Balance,
+    #[serde(with = "u64_dec_format")]
+    pub end_time: Timestamp,
+    pub executed: bool,
+}
+
+impl LpProposal {
+    pub fn is_open(&self, current_time: Timestamp) -> bool {
+        !self.executed && current_time < self.end_time
+    }
+
+    pub fn is_passed(&self) -> bool {
+        let quorum = (U256::from(self.shares_total_supply) * U256::from(LP_VOTE_QUORUM_BPS)
+            / U256::from(FEE_DIVISOR)).as_u128();
+        self.yes_shares >= quorum && self.yes_shares > self.no_shares
+    }
+}
+
+/// A cast vote. The voted shares stay reserved in the voter's account while the proposal is open.
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone)]
+#[serde(crate = "near_sdk::serde")]
+#[cfg_attr(not(target_arch = "wasm32"), derive(Debug))]
+pub struct LpVote {
+    pub proposal_id: u64,
+    pub pool_id: u64,
+    #[serde(with = "u128_dec_format")]
+    pub shares: Balance,
+    pub approve: bool,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+#[cfg_attr(not(target_arch = "wasm32"), derive(Debug))]
+pub struct LpProposalInfo {
+    pub proposal_id: u64,
+    pub proposal: LpProposal,
+    pub is_open: bool,
+}
+
+pub fn read_lp_proposals_from_storage() -> LookupMap<u64, LpProposal> {
+    if let Some(content) = env::storage_read(LP_PROPOSALS.as_bytes()) {
+        LookupMap::try_from_slice(&content).expect("deserialize lp proposals failed.")
+    } else {
+        LookupMap::new(StorageKey::LpProposals)
+    }
+}
+
+pub fn write_lp_proposals_to_storage(lp_proposals: LookupMap<u64, LpProposal>) {
+    env::storage_write(
+        LP_PROPOSALS.as_bytes(),
+        &lp_proposals.try_to_vec().unwrap(),
+    );
+}
+
+/// Latest proposal of each pool.
+pub fn read_pool_lp_proposals_from_storage() -> LookupMap<u64, u64> {
+    if let Some(content) = env::storage_read(POOL_LP_PROPOSALS.as_bytes()) {
+        LookupMap::try_from_slice(&content).expect("deserialize pool lp proposals failed.")
+    } else {
+        LookupMap::new(StorageKey::PoolLpProposals)
+    }
+}
+
+pub fn write_pool_lp_proposals_to_storage(pool_lp_proposals: LookupMap<u64, u64>) {
+    env::storage_write(
+        POOL_LP_PROPOSALS.as_bytes(),
+        &pool_lp_proposals.try_to_vec().unwrap(),
+    );
+}
+
+pub fn read_lp_votes_from_storage() -> LookupMap<AccountId, Vec<LpVote>> {
+    if let Some(content) = env::storage_read(LP_VOTES.as_bytes()) {
+        LookupMap::try_from_slice(&content).expect("deserialize lp votes failed.")
+    } else {
+        LookupMap::new(StorageKey::LpVotes)
+    }
+}
+
+pub fn write_lp_votes_to_storage(lp_votes: LookupMap<AccountId, Vec<LpVote>>) {
+    env::storage_write(
+        LP_VOTES.as_bytes(),
+        &lp_votes.try_to_vec().unwrap(),
+    );
+}
+
+pub fn read_next_lp_proposal_id_from_storage() -> u64 {
+    if let Some(content) = env::storage_read(NEXT_LP_PROPOSAL_ID.as_bytes()) {
+        u64::try_from_slice(&content).expect("deserialize next lp proposal id failed.")
+    } else {
+        0
+    }
+}
+
+pub fn write_next_lp_proposal_id_to_storage(next_lp_proposal_id: u64) {
+    env::storage_write(
+        NEXT_LP_PROPOSAL_ID.as_bytes(),
+        &next_lp_proposal_id.try_to_vec().unwrap(),
+    );
+}
+
+impl Contract {
+    /// Shares of the account in the given pool behind its votes on open proposals.
+    pub fn internal_voted_shares(&self, account_id: &AccountId, pool_id: u64) -> Balance {
+        let votes = read_lp_votes_from_storage().get(account_id).unwrap_or_default();
+        if votes.is_empty() {
+            return 0;
+        }
+        let lp_proposals = read_lp_proposals_from_storage();
+        let current_time = env::block_timestamp();
+        votes.iter()
+            .filter(|vote| vote.pool_id == pool_id)
+            .filter(|vote| lp_proposals.get(&vote.proposal_id).map(|p| p.is_open(current_time)).unwrap_or(false))
+            .map(|vote| vote.shares)
+            .max()
+            .unwrap_or(0)
+    }
+
+    fn assert_pool_param_change(&self, pool: &Pool, change: &PoolParamChange) {
+        match change {
+            PoolParamChange::TotalFee { total_fee } => {
+                let fee = pool.get_fee();
+                assert!(
+                    *total_fee != fee
+                        && *total_fee < FEE_DIVISOR
+                        && *total_fee * LP_VOTE_MAX_FEE_FACTOR >= fee
+                        && *total_fee <= fee * LP_VOTE_MAX_FEE_FACTOR,
+                    "{}", ERR62_FEE_ILLEGAL
+                );
+            }
+            PoolParamChange::AmpFactor { .. } => match pool {
+                Pool::StableSwapPool(_) | Pool::RatedSwapPool(_) | Pool::DegenSwapPool(_) => {}
+                _ => env::panic(ERR88_NOT_STABLE_POOL.as_bytes()),
+            },
+        }
+    }
+
+    fn internal_execute_pool_param_change(&mut self, pool_id: u64, change: &PoolParamChange) {
+        match change {
+            PoolParamChange::TotalFee { total_fee } => {
+                self.internal_modify_total_fee(pool_id, *total_fee);
+            }
+            PoolParamChange::AmpFactor { amp_factor } => {
+                let future_amp_time = env::block_timestamp() + to_nano(LP_VOTE_AMP_RAMP_DURATION_SEC);
+                self.internal_ramp_amp(pool_id, *amp_factor, future_amp_time);
+            }
+        }
+    }
+
+    /// Adds the account's shares as of the proposal's checkpoint to the proposal, as far as they are still free.
+    fn internal_cast_lp_vote(&mut self, proposal_id: u64, account_id: &AccountId, approve: bool) {
+        let mut lp_proposals = read_lp_proposals_from_storage();
+        let mut proposal = lp_proposals.get(&proposal_id).expect("LP proposal not found");
+        let current_time = env::block_timestamp();
+        assert!(proposal.is_open(current_time), "LP proposal closed");
+
+        let mut votes = read_lp_votes_from_storage();
+        let mut account_votes = account_votes_pruned(&lp_proposals, votes.get(account_id).unwrap_or_default(), current_time);
+        assert!(account_votes.iter().all(|vote| vote.proposal_id != proposal_id), "Already voted");
+        assert!(account_votes.len() < MAX_LP_VOTES_PER_ACCOUNT, "Too many LP votes");
+
+        let pool = self.internal_get_pool(proposal.pool_id);
+        let total_shares = pool.share_balances(account_id);
+        let shadow_in_burrow = self.internal_get_account(account_id)
+            .and_then(|account| account.get_shadow_record(proposal.pool_id))
+            .map(|record| record.shadow_in_burrow)
+            .unwrap_or(0);
+        let checkpoint_shares = self.internal_share_checkpoint_balance(proposal.pool_id, &pool, proposal.checkpoint_id, account_id)
+            .expect("LP proposal checkpoint dropped");
+        let shares = std::cmp::min(total_shares.saturating_sub(shadow_in_burrow), checkpoint_shares);
+        assert!(shares > 0, "No shares to vote with");
+
+        account_votes.push(LpVote { proposal_id, pool_id: proposal.pool_id, shares, approve });
+        votes.insert(account_id, &account_votes);
+        write_lp_votes_to_storage(votes);
+        if approve {
+            proposal.yes_shares += shares;
+        } else {
+            proposal.no_shares += shares;
+        }
+        log!("{} votes {} on LP proposal {} with {} shares", account_id, approve, proposal_id, shares);
+        lp_proposals.insert(&proposal_id, &proposal);
+        write_lp_proposals_to_storage(lp_proposals);
+    }
+}
+
+/// Drops the votes on proposals that are no longer open, their shares are free again.
+fn account_votes_pruned(lp_proposals: &LookupMap<u64, LpProposal>, votes: Vec<LpVote>, current_time: Timestamp) -> Vec<LpVote> {
+    votes.into_iter()
+        .filter(|vote| lp_proposals.get(&vote.proposal_id).map(|p| p.is_open(current_time)).unwrap_or(false))
+        .collect()
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Open a vote among the pool's LPs on a parameter change, counting the caller's shares as yes.
+    /// Caller must hold LP_PROPOSAL_MIN_SHARES_BPS of the pool supply and the pool can't have another open
+    /// or passed but unexecuted proposal. Votes are weighted by the shares held when the proposal is made.
+    /// Attached deposit covers the storage of the proposal and vote, the rest is refunded.
+    #[payable]
+    pub fn propose_pool_param_change(&mut self, pool_id: u64, change: PoolParamChange) -> u64 {
+        self.assert_contract_running();
+        let prev_storage = env::storage_usage();
+        let proposer_id = env::predecessor_account_id();
+        let pool = self.internal_get_pool(pool_id);
+        self.assert_pool_param_change(&pool, &change);
+        assert!(
+            U256::from(pool.share_balances(&proposer_id)) * U256::from(FEE_DIVISOR)
+                >= U256::from(pool.share_total_balance()) * U256::from(LP_PROPOSAL_MIN_SHARES_BPS),
+            "Not enough shares to propose"
+        );
+
+        let current_time = env::block_timestamp();
+        let mut lp_proposals = read_lp_proposals_from_storage();
+        let mut pool_lp_proposals = read_pool_lp_proposals_from_storage();
+        if let Some(prev_id) = pool_lp_proposals.get(&pool_id) {
+            let prev = lp_proposals.get(&prev_id).unwrap();
+            assert!(!prev.is_open(current_time), "Pool has an open LP proposal");
+            assert!(prev.executed || !prev.is_passed(), "Pool has an unexecuted LP proposal");
+            lp_proposals.remove(&prev_id);
+        }
+        let proposal_id = read_next_lp_proposal_id_from_storage();
+        write_next_lp_proposal_id_to_storage(proposal_id + 1);
+        pool_lp_proposals.insert(&pool_id, &proposal_id);
+        write_pool_lp_proposals_to_storage(pool_lp_proposals);
+        let checkpoint_id = self.internal_create_share_checkpoint(pool_id, &pool);
+        lp_proposals.insert(&proposal_id, &LpProposal {
+            pool_id,
+            proposer_id: proposer_id.clone(),
+            change,
+            checkpoint_id,
+            shares_total_supply: pool.share_total_balance(),
+            yes_shares: 0,
+            no_shares: 0,
+            end_time: current_time + to_nano(LP_VOTE_DURATION_SEC),
+            executed: false,
+        });
+        write_lp_proposals_to_storage(lp_proposals);
+        log!("{} opens LP proposal {} on pool {}", proposer_id, proposal_id, pool_id);
+        self.internal_cast_lp_vote(proposal_id, &proposer_id, true);
+        self.internal_check_storage(prev_storage);
+        proposal_id
+    }
+
+    /// Vote with all the caller's shares in the pool, minus those in burrow.
+    /// They can't leave the account until the proposal closes.
+    #[payable]
+    pub fn vote_pool_param_change(&mut self, proposal_id: u64, approve: bool) {
+        self.assert_contract_running();
+        let prev_storage = env::storage_usage();
+        self.internal_cast_lp_vote(proposal_id, &env::predecessor_account_id(), approve);
+        self.internal_check_storage(prev_storage);
+    }
+
+    /// Execute a proposal whose vote has ended with the yes side at quorum and ahead of the no side.
+    pub fn execute_lp_proposal(&mut self, proposal_id: u64) {
+        self.assert_contract_running();
+        let mut lp_proposals = read_lp_proposals_from_storage();
+        let mut proposal = lp_proposals.get(&proposal_id).expect("LP proposal not found");
+        assert!(!proposal.executed, "LP proposal already executed");
+        assert!(env::block_timestamp() >= proposal.end_time, "LP proposal still open");
+        assert!(proposal.is_passed(), "LP proposal not passed");
+        let pool = self.internal_get_pool(proposal.pool_id);
+        self.assert_pool_param_change(&pool, &proposal.change);
+        proposal.executed = true;
+        lp_proposals.insert(&proposal_id, &proposal);
+        write_lp_proposals_to_storage(lp_proposals);
+        self.internal_execute_pool_param_change(proposal.pool_id, &proposal.change);
+        log!("LP proposal {} executed: {:?}", proposal_id, proposal.change);
+    }
+
+    pub fn get_lp_proposal(&self, proposal_id: u64) -> Option<LpProposalInfo> {
+        read_lp_proposals_from_storage().get(&proposal_id).map(|proposal| LpProposalInfo {
+            proposal_id,
+            is_open: proposal.is_open(env::block_timestamp()),
+            proposal,
+        })
+    }
+
+    /// Returns the latest proposal of the pool.
+    pub fn get_pool_lp_proposal(&self, pool_id: u64) -> Option<LpProposalInfo> {
+        read_pool_lp_proposals_from_storage()
+            .get(&pool_id)
+            .and_then(|proposal_id| self.get_lp_proposal(proposal_id))
+    }
+
+    /// Returns the account's votes on open proposals.
+    pub fn get_lp_votes(&self, account_id: ValidAccountId) -> Vec<LpVote> {
+        account_votes_pruned(
+            &read_lp_proposals_from_storage(),
+            read_lp_votes_from_storage().get(account_id.as_ref()).unwrap_or_default(),
+            env::block_timestamp(),
+        )
+    }
+}
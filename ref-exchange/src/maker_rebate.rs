@@ -0,0 +1,191 @@
+use crate::*;
+use crate::utils::{u128_ratio, FEE_DIVISOR};
+
+/// Upper bound for both the surcharge and the rebate, 1%.
+pub const MAX_MAKER_REBATE_BPS: u32 = 100;
+
+/// Per stable pool swap incentive: swaps that push the pool further out of balance
+/// leave `surcharge_bps` of their output in the pool's rebate escrow, swaps that
+/// bring it back toward balance get `rebate_bps` of their output on top, out of that escrow.
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone)]
+#[serde(crate = "near_sdk::serde")]
+#[cfg_attr(not(target_arch = "wasm32"), derive(Debug))]
+pub struct MakerRebateConfig {
+    pub surcharge_bps: u32,
+    pub rebate_bps: u32,
+}
+
+pub fn read_maker_rebate_configs_from_storage() -> LookupMap<u64, MakerRebateConfig> {
+    if let Some(content) = env::storage_read(MAKER_REBATE_CONFIGS.as_bytes()) {
+        LookupMap::try_from_slice(&content).expect("deserialize maker rebate configs failed.")
+    } else {
+        LookupMap::new(StorageKey::MakerRebateConfigs)
+    }
+}
+
+pub fn write_maker_rebate_configs_to_storage(maker_rebate_configs: LookupMap<u64, MakerRebateConfig>) {
+    env::storage_write(
+        MAKER_REBATE_CONFIGS.as_bytes(),
+        &maker_rebate_configs.try_to_vec().unwrap(),
+    );
+}
+
+/// Tokens set aside for each pool's rebates, per pool token. They are kept outside the pool
+/// reserves, filled by surcharges and by `fund_pool_maker_rebate`.
+pub fn read_maker_rebate_escrows_from_storage() -> LookupMap<u64, Vec<Balance>> {
+    if let Some(content) = env::storage_read(MAKER_REBATE_ESCROWS.as_bytes()) {
+        LookupMap::try_from_slice(&content).expect("deserialize maker rebate escrows failed.")
+    } else {
+        LookupMap::new(StorageKey::MakerRebateEscrows)
+    }
+}
+
+pub fn write_maker_rebate_escrows_to_storage(maker_rebate_escrows: LookupMap<u64, Vec<Balance>>) {
+    env::storage_write(
+        MAKER_REBATE_ESCROWS.as_bytes(),
+        &maker_rebate_escrows.try_to_vec().unwrap(),
+    );
+}
+
+/// Spread between the largest and smallest comparable reserve, None for non stable pools.
+pub fn stable_pool_imbalance(pool: &Pool) -> Option<Balance> {
+    match pool {
+        Pool::StableSwapPool(p) => {
+            let max = p.c_amounts.iter().max()?;
+            let min = p.c_amounts.iter().min()?;
+            Some(max - min)
+        }
+        _ => None,
+    }
+}
+
+impl Contract {
+    /// Applies the pool's maker rebate config to a finished swap and returns the adjusted amount out,
+    /// which the caller has to check against its slippage limit again.
+    /// `prev_imbalance` is the pool imbalance before the swap, `pool` the pool after it.
+    /// Surcharges go to the pool's escrow, rebates are paid from it and skipped when it is short.
+    pub(crate) fn internal_apply_maker_rebate(
+        &self,
+        pool_id: u64,
+        prev_imbalance: Option<Balance>,
+        pool: &Pool,
+        token_out: &AccountId,
+        amount_out: Balance,
+        is_view: bool,
+    ) -> Balance {
+        let (prev_imbalance, imbalance) = match (prev_imbalance, stable_pool_imbalance(pool)) {
+            (Some(prev), Some(current)) if prev != current => (prev, current),
+            _ => return amount_out,
+        };
+        let config = match read_maker_rebate_configs_from_storage().get(&pool_id) {
+            Some(config) => config,
+            None => return amount_out,
+        };
+        let out_idx = pool.tokens().iter().position(|id| id == token_out).expect(ERR63_MISSING_TOKEN);
+        let mut maker_rebate_escrows = read_maker_rebate_escrows_from_storage();
+        let mut escrow = maker_rebate_escrows.get(&pool_id).unwrap_or_else(|| vec![0; pool.tokens().len()]);
+        let amount_out = if imbalance > prev_imbalance {
+            let surcharge = u128_ratio(amount_out, config.surcharge_bps as u128, FEE_DIVISOR as u128);
+            if surcharge == 0 {
+                return amount_out;
+            }
+            escrow[out_idx] += surcharge;
+            if !is_view {
+                log!("Imbalance surcharge {} {} kept for pool {}", surcharge, token_out, pool_id);
+            }
+            amount_out - surcharge
+        } else {
+            let rebate = u128_ratio(amount_out, config.rebate_bps as u128, FEE_DIVISOR as u128);
+            if rebate == 0 || rebate > escrow[out_idx] {
+                return amount_out;
+            }
+            escrow[out_idx] -= rebate;
+            if !is_view {
+                log!("Maker rebate {} {} paid for pool {}", rebate, token_out, pool_id);
+            }
+            amount_out + rebate
+        };
+        if !is_view {
+            maker_rebate_escrows.insert(&pool_id, &escrow);
+            write_maker_rebate_escrows_to_storage(maker_rebate_escrows);
+        }
+        amount_out
+    }
+
+    /// Quotes a swap in the pool with its maker rebate config applied, as the swap would pay out.
+    pub(crate) fn internal_quote_with_maker_rebate(
+        &self,
+        pool_id: u64,
+        pool: &mut Pool,
+        token_in: &AccountId,
+        amount_in: Balance,
+        token_out: &AccountId,
+        admin_fees: AdminFees,
+    ) -> Balance {
+        let prev_imbalance = stable_pool_imbalance(pool);
+        let amount_out = pool.swap(token_in, amount_in, token_out, 0, admin_fees, true);
+        self.internal_apply_maker_rebate(pool_id, prev_imbalance, pool, token_out, amount_out, true)
+    }
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Set or remove (None) the maker rebate config of a stable pool.
+    /// Removing the config keeps the escrow, which is used again once a config is set.
+    #[payable]
+    pub fn set_pool_maker_rebate(&mut self, pool_id: u64, config: Option<MakerRebateConfig>) {
+        assert_one_yocto();
+        self.assert_owner();
+        audit_privileged_action("set_pool_maker_rebate");
+        let pool = self.internal_get_pool(pool_id);
+        assert!(matches!(pool, Pool::StableSwapPool(_)), "{}", ERR88_NOT_STABLE_POOL);
+        let mut maker_rebate_configs = read_maker_rebate_configs_from_storage();
+        if let Some(config) = config {
+            assert!(
+                config.surcharge_bps <= MAX_MAKER_REBATE_BPS && config.rebate_bps <= MAX_MAKER_REBATE_BPS,
+                "{}", ERR101_ILLEGAL_FEE
+            );
+            maker_rebate_configs.insert(&pool_id, &config);
+            let mut maker_rebate_escrows = read_maker_rebate_escrows_from_storage();
+            if maker_rebate_escrows.get(&pool_id).is_none() {
+                maker_rebate_escrows.insert(&pool_id, &vec![0; pool.tokens().len()]);
+                write_maker_rebate_escrows_to_storage(maker_rebate_escrows);
+            }
+        } else {
+            maker_rebate_configs.remove(&pool_id);
+        }
+        write_maker_rebate_configs_to_storage(maker_rebate_configs);
+    }
+
+    pub fn get_pool_maker_rebate(&self, pool_id: u64) -> Option<MakerRebateConfig> {
+        read_maker_rebate_configs_from_storage().get(&pool_id)
+    }
+
+    /// Move tokens from the caller's inner balance into the pool's rebate escrow.
+    #[payable]
+    pub fn fund_pool_maker_rebate(&mut self, pool_id: u64, token_id: ValidAccountId, amount: U128) {
+        assert_one_yocto();
+        self.assert_contract_running();
+        let sender_id = env::predecessor_account_id();
+        let mut maker_rebate_escrows = read_maker_rebate_escrows_from_storage();
+        let mut escrow = maker_rebate_escrows.get(&pool_id).expect("No maker rebate escrow");
+        let pool = self.internal_get_pool(pool_id);
+        let idx = pool.tokens().iter().position(|id| id == token_id.as_ref()).expect(ERR63_MISSING_TOKEN);
+        let mut account = self.internal_unwrap_account(&sender_id);
+        account.withdraw(token_id.as_ref(), amount.0);
+        self.internal_save_account(&sender_id, account);
+        escrow[idx] += amount.0;
+        maker_rebate_escrows.insert(&pool_id, &escrow);
+        write_maker_rebate_escrows_to_storage(maker_rebate_escrows);
+    }
+
+    /// Returns the rebate escrow of the pool, per pool token.
+    pub fn get_pool_maker_rebate_escrow(&self, pool_id: u64) -> Vec<U128> {
+        read_maker_rebate_escrows_from_storage()
+            .get(&pool_id)
+            .unwrap_or_default()
+            .into_iter()
+            .map(U128)
+            .collect()
+    }
+}
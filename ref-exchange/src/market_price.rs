@@ -0,0 +1,129 @@
+use crate::*;
+use crate::admin_fee::AdminFees;
+use crate::utils::{u128_dec_format, u64_dec_format, U256};
+use near_sdk::Timestamp;
+
+/// Precision of spot and last trade prices.
+pub const MARKET_PRICE_PRECISION: u128 = 1_000_000_000_000_000_000;
+/// Stable-like pools are quoted with this fraction of the token_in reserve.
+pub const SPOT_PRICE_PROBE_DIVISOR: u128 = 1_000_000;
+
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone)]
+#[serde(crate = "near_sdk::serde")]
+#[cfg_attr(not(target_arch = "wasm32"), derive(Debug))]
+pub struct LastTrade {
+    pub token_in: AccountId,
+    pub token_out: AccountId,
+    #[serde(with = "u128_dec_format")]
+    pub amount_in: Balance,
+    #[serde(with = "u128_dec_format")]
+    pub amount_out: Balance,
+    #[serde(with = "u64_dec_format")]
+    pub timestamp: Timestamp,
+}
+
+/// token_out per token_in in MARKET_PRICE_PRECISION. Per whole token when `normalized`,
+/// per smallest unit for simple pools, which don't record token decimals.
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+#[cfg_attr(not(target_arch = "wasm32"), derive(Debug))]
+pub struct MarketPrice {
+    pub price: U128,
+    pub normalized: bool,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+#[cfg_attr(not(target_arch = "wasm32"), derive(Debug))]
+pub struct LastTradeInfo {
+    pub trade: LastTrade,
+    pub price: MarketPrice,
+}
+
+pub fn read_last_trades_from_storage() -> LookupMap<u64, LastTrade> {
+    if let Some(content) = env::storage_read(LAST_TRADES.as_bytes()) {
+        LookupMap::try_from_slice(&content).expect("deserialize last trades failed.")
+    } else {
+        LookupMap::new(StorageKey::LastTrades)
+    }
+}
+
+pub fn write_last_trades_to_storage(last_trades: LookupMap<u64, LastTrade>) {
+    env::storage_write(
+        LAST_TRADES.as_bytes(),
+        &last_trades.try_to_vec().unwrap(),
+    );
+}
+
+/// Price of `amount_in` for `amount_out`, scaled to whole tokens when the pool records decimals.
+fn market_price(pool: &Pool, token_in: &AccountId, amount_in: Balance, token_out: &AccountId, amount_out: Balance) -> MarketPrice {
+    if amount_in == 0 {
+        return MarketPrice { price: U128(0), normalized: false };
+    }
+    let mut numerator = U256::from(amount_out) * U256::from(MARKET_PRICE_PRECISION);
+    let mut denominator = U256::from(amount_in);
+    let normalized = match pool.get_token_decimals() {
+        Some(decimals) => {
+            let tokens = pool.tokens();
+            let in_idx = tokens.iter().position(|id| id == token_in).expect(ERR63_MISSING_TOKEN);
+            let out_idx = tokens.iter().position(|id| id == token_out).expect(ERR63_MISSING_TOKEN);
+            numerator *= U256::from(10).pow(U256::from(decimals[in_idx]));
+            denominator *= U256::from(10).pow(U256::from(decimals[out_idx]));
+            true
+        }
+        None => false,
+    };
+    MarketPrice { price: U128((numerator / denominator).as_u128()), normalized }
+}
+
+impl Contract {
+    /// Keeps the latest swap through the pool.
+    pub(crate) fn internal_record_last_trade(
+        &mut self,
+        pool_id: u64,
+        token_in: &AccountId,
+        amount_in: Balance,
+        token_out: &AccountId,
+        amount_out: Balance,
+    ) {
+        let mut last_trades = read_last_trades_from_storage();
+        last_trades.insert(&pool_id, &LastTrade {
+            token_in: token_in.clone(),
+            token_out: token_out.clone(),
+            amount_in,
+            amount_out,
+            timestamp: env::block_timestamp(),
+        });
+        write_last_trades_to_storage(last_trades);
+    }
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Marginal price of token_in in token_out before fees. Rates of rated and degen pools
+    /// are applied the same way as in swaps.
+    pub fn get_spot_price(&self, pool_id: u64, token_in: ValidAccountId, token_out: ValidAccountId) -> MarketPrice {
+        let mut pool = self.internal_get_pool(pool_id);
+        let tokens = pool.tokens();
+        let in_idx = tokens.iter().position(|id| id == token_in.as_ref()).expect(ERR63_MISSING_TOKEN);
+        let out_idx = tokens.iter().position(|id| id == token_out.as_ref()).expect(ERR63_MISSING_TOKEN);
+        let amounts = pool.get_amounts();
+        let (amount_in, amount_out) = if matches!(pool, Pool::SimplePool(_)) {
+            (amounts[in_idx], amounts[out_idx])
+        } else {
+            let probe_in = std::cmp::max(amounts[in_idx] / SPOT_PRICE_PROBE_DIVISOR, 1);
+            pool.modify_total_fee(0);
+            (probe_in, pool.swap(token_in.as_ref(), probe_in, token_out.as_ref(), 0, AdminFees::zero(), true))
+        };
+        market_price(&pool, token_in.as_ref(), amount_in, token_out.as_ref(), amount_out)
+    }
+
+    /// The latest swap through the pool, with its average price after fees.
+    pub fn get_last_trade(&self, pool_id: u64) -> Option<LastTradeInfo> {
+        let pool = self.internal_get_pool(pool_id);
+        read_last_trades_from_storage().get(&pool_id).map(|trade| {
+            let price = market_price(&pool, &trade.token_in, trade.amount_in, &trade.token_out, trade.amount_out);
+            LastTradeInfo { trade, price }
+        })
+    }
+}
@@ -0,0 +1,161 @@
+use crate::*;
+use crate::utils::GAS_FOR_FT_TRANSFER_CALL;
+use near_sdk::json_types::U64;
+
+/// Gas a `mft_transfer_call` receiver gets at least, unless configured otherwise.
+pub const DEFAULT_MFT_RECEIVER_MIN_GAS: Gas = 10_000_000_000_000;
+
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(crate = "near_sdk::serde")]
+#[cfg_attr(not(target_arch = "wasm32"), derive(Debug))]
+pub enum MftReceiverListMode {
+    /// Every receiver except the listed ones is accepted.
+    Denylist,
+    /// Only the listed receivers are accepted.
+    Allowlist,
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone)]
+#[serde(crate = "near_sdk::serde")]
+#[cfg_attr(not(target_arch = "wasm32"), derive(Debug))]
+pub struct MftReceiverPolicy {
+    pub mode: MftReceiverListMode,
+    pub default_min_gas: U64,
+}
+
+impl Default for MftReceiverPolicy {
+    fn default() -> Self {
+        Self {
+            mode: MftReceiverListMode::Denylist,
+            default_min_gas: U64(DEFAULT_MFT_RECEIVER_MIN_GAS),
+        }
+    }
+}
+
+pub fn read_mft_receiver_policy_from_storage() -> MftReceiverPolicy {
+    if let Some(content) = env::storage_read(MFT_RECEIVER_POLICY.as_bytes()) {
+        MftReceiverPolicy::try_from_slice(&content).expect("deserialize mft receiver policy failed.")
+    } else {
+        MftReceiverPolicy::default()
+    }
+}
+
+pub fn write_mft_receiver_policy_to_storage(policy: MftReceiverPolicy) {
+    env::storage_write(
+        MFT_RECEIVER_POLICY.as_bytes(),
+        &policy.try_to_vec().unwrap(),
+    );
+}
+
+pub fn read_mft_receiver_list_from_storage() -> UnorderedSet<AccountId> {
+    if let Some(content) = env::storage_read(MFT_RECEIVER_LIST.as_bytes()) {
+        UnorderedSet::try_from_slice(&content).expect("deserialize mft receiver list failed.")
+    } else {
+        UnorderedSet::new(StorageKey::MftReceiverList)
+    }
+}
+
+pub fn write_mft_receiver_list_to_storage(receiver_list: UnorderedSet<AccountId>) {
+    env::storage_write(
+        MFT_RECEIVER_LIST.as_bytes(),
+        &receiver_list.try_to_vec().unwrap(),
+    );
+}
+
+pub fn read_mft_receiver_min_gas_from_storage() -> UnorderedMap<AccountId, u64> {
+    if let Some(content) = env::storage_read(MFT_RECEIVER_MIN_GAS.as_bytes()) {
+        UnorderedMap::try_from_slice(&content).expect("deserialize mft receiver min gas failed.")
+    } else {
+        UnorderedMap::new(StorageKey::MftReceiverMinGas)
+    }
+}
+
+pub fn write_mft_receiver_min_gas_to_storage(receiver_min_gas: UnorderedMap<AccountId, u64>) {
+    env::storage_write(
+        MFT_RECEIVER_MIN_GAS.as_bytes(),
+        &receiver_min_gas.try_to_vec().unwrap(),
+    );
+}
+
+/// Checks the receiver against the allow/deny list and returns the gas to forward to it,
+/// which must cover the receiver's minimum gas.
+pub fn internal_mft_receiver_gas(receiver_id: &AccountId) -> Gas {
+    let policy = read_mft_receiver_policy_from_storage();
+    let listed = read_mft_receiver_list_from_storage().contains(receiver_id);
+    match policy.mode {
+        MftReceiverListMode::Denylist => assert!(!listed, "Receiver not allowed"),
+        MftReceiverListMode::Allowlist => assert!(listed, "Receiver not allowed"),
+    }
+    let min_gas = read_mft_receiver_min_gas_from_storage()
+        .get(receiver_id)
+        .unwrap_or(policy.default_min_gas.0);
+    let receiver_gas = env::prepaid_gas().saturating_sub(GAS_FOR_FT_TRANSFER_CALL);
+    assert!(receiver_gas >= min_gas, "Not enough gas for receiver, need {} more", min_gas - receiver_gas);
+    receiver_gas
+}
+
+#[near_bindgen]
+impl Contract {
+    #[payable]
+    pub fn set_mft_receiver_policy(&mut self, mode: MftReceiverListMode, default_min_gas: U64) {
+        assert_one_yocto();
+        self.assert_owner();
+        audit_privileged_action("set_mft_receiver_policy");
+        write_mft_receiver_policy_to_storage(MftReceiverPolicy { mode, default_min_gas });
+    }
+
+    #[payable]
+    pub fn extend_mft_receiver_list(&mut self, receiver_ids: Vec<ValidAccountId>) {
+        assert_one_yocto();
+        assert!(self.is_owner_or_guardians(), "{}", ERR100_NOT_ALLOWED);
+        audit_privileged_action("extend_mft_receiver_list");
+        let mut receiver_list = read_mft_receiver_list_from_storage();
+        for receiver_id in receiver_ids {
+            let is_success = receiver_list.insert(receiver_id.as_ref());
+            assert!(is_success, "Receiver already exist");
+        }
+        write_mft_receiver_list_to_storage(receiver_list);
+    }
+
+    #[payable]
+    pub fn remove_mft_receiver_list(&mut self, receiver_ids: Vec<ValidAccountId>) {
+        assert_one_yocto();
+        self.assert_owner();
+        audit_privileged_action("remove_mft_receiver_list");
+        let mut receiver_list = read_mft_receiver_list_from_storage();
+        for receiver_id in receiver_ids {
+            let is_success = receiver_list.remove(receiver_id.as_ref());
+            assert!(is_success, "Invalid receiver");
+        }
+        write_mft_receiver_list_to_storage(receiver_list);
+    }
+
+    /// Set minimum gas forwarded to the given receiver, None falls back to the policy default.
+    #[payable]
+    pub fn set_mft_receiver_min_gas(&mut self, receiver_id: ValidAccountId, min_gas: Option<U64>) {
+        assert_one_yocto();
+        assert!(self.is_owner_or_guardians(), "{}", ERR100_NOT_ALLOWED);
+        audit_privileged_action("set_mft_receiver_min_gas");
+        let mut receiver_min_gas = read_mft_receiver_min_gas_from_storage();
+        if let Some(min_gas) = min_gas {
+            receiver_min_gas.insert(receiver_id.as_ref(), &min_gas.0);
+        } else {
+            receiver_min_gas.remove(receiver_id.as_ref());
+        }
+        write_mft_receiver_min_gas_to_storage(receiver_min_gas);
+    }
+
+    pub fn get_mft_receiver_policy(&self) -> MftReceiverPolicy {
+        read_mft_receiver_policy_from_storage()
+    }
+
+    pub fn get_mft_receiver_list(&self) -> Vec<AccountId> {
+        read_mft_receiver_list_from_storage().to_vec()
+    }
+
+    pub fn get_mft_receiver_min_gas(&self, receiver_id: ValidAccountId) -> U64 {
+        U64(read_mft_receiver_min_gas_from_storage()
+            .get(receiver_id.as_ref())
+            .unwrap_or(read_mft_receiver_policy_from_storage().default_min_gas.0))
+    }
+}
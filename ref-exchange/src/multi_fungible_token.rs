@@ -2,7 +2,7 @@ use near_contract_standards::fungible_token::metadata::FungibleTokenMetadata;
 use near_sdk::json_types::{ValidAccountId, U128};
 use near_sdk::{ext_contract, near_bindgen, Balance, PromiseOrValue};
 
-use crate::utils::{GAS_FOR_FT_TRANSFER_CALL, GAS_FOR_RESOLVE_TRANSFER, NO_DEPOSIT};
+use crate::utils::{GAS_FOR_RESOLVE_TRANSFER, NO_DEPOSIT};
 use crate::*;
 
 #[ext_contract(ext_self)]
@@ -56,6 +56,14 @@ fn parse_token_id(token_id: String) -> TokenOrPool {
     }
 }
 
+/// Token id with the pool id of shares in its plain form, e.g. ":3" for ":03".
+pub(crate) fn canonical_mft_token_id(token_id: String) -> String {
+    match parse_token_id(token_id) {
+        TokenOrPool::Pool(pool_id) => format!(":{}", pool_id),
+        TokenOrPool::Token(token_id) => token_id,
+    }
+}
+
 impl Contract {
     pub fn internal_mft_transfer(
         &mut self,
@@ -67,9 +75,11 @@ impl Contract {
     ) -> Balance {
         // [AUDIT_07]
         assert_ne!(sender_id, receiver_id, "{}", ERR33_TRANSFER_TO_SELF);
+        assert_account_not_denied(sender_id);
+        assert_account_not_denied(receiver_id);
         let transfer_amount = match parse_token_id(token_id) {
             TokenOrPool::Pool(pool_id) => {
-                let mut pool = self.pools.get(pool_id).expect(ERR85_NO_POOL);
+                let mut pool = self.internal_get_pool(pool_id);
 
                 let total_shares = pool.share_balances(sender_id);
                 let available_shares = if let Some(sender_account) = self.internal_get_account(sender_id) {
@@ -84,7 +94,12 @@ impl Contract {
                 let amount = amount.unwrap_or(available_shares);
                 assert!(amount > 0, "transfer_amount must be greater than zero");
                 assert!(amount <= available_shares, "Not enough free shares");
+                self.assert_shares_unlocked(sender_id, pool_id, total_shares, amount);
+                self.assert_share_transfer_allowed(pool_id, receiver_id);
                 
+                let prev_storage = env::storage_usage();
+                self.internal_settle_lp_fees(pool_id, &pool, &[sender_id, receiver_id]);
+                self.internal_charge_caller_storage(sender_id, prev_storage);
                 pool.share_transfer(sender_id, receiver_id, amount);
                 self.pools.replace(pool_id, &pool);
                 log!(
@@ -94,6 +109,9 @@ impl Contract {
                     sender_id,
                     receiver_id
                 );
+                if read_permissioned_pools_from_storage().contains(&pool_id) {
+                    event::Event::ComplianceShareTransfer { pool_id, sender_id, receiver_id, amount: U128(amount) }.emit();
+                }
                 amount
             }
             TokenOrPool::Token(token_id) => {
@@ -126,7 +144,7 @@ impl Contract {
     fn internal_mft_balance(&self, token_id: String, account_id: &AccountId) -> Balance {
         match parse_token_id(token_id) {
             TokenOrPool::Pool(pool_id) => {
-                let pool = self.pools.get(pool_id).expect(ERR85_NO_POOL);
+                let pool = self.internal_get_pool(pool_id);
                 pool.share_balances(account_id)
             }
             TokenOrPool::Token(token_id) => self.internal_get_deposit(account_id, &token_id),
@@ -148,13 +166,20 @@ impl Contract {
     pub fn mft_total_supply(&self, token_id: String) -> U128 {
         match parse_token_id(token_id) {
             TokenOrPool::Pool(pool_id) => {
-                let pool = self.pools.get(pool_id).expect(ERR85_NO_POOL);
+                let pool = self.internal_get_pool(pool_id);
                 U128(pool.share_total_balance())
             }
             TokenOrPool::Token(_token_id) => unimplemented!(),
         }
     }
 
+    /// Returns the total supplies of the given pool tokens, in the given order.
+    pub fn mft_total_supply_batch(&self, token_ids: Vec<String>) -> Vec<U128> {
+        token_ids.into_iter()
+            .map(|token_id| self.mft_total_supply(token_id))
+            .collect()
+    }
+
     pub fn mft_has_registered(&self, token_id: String, account_id: ValidAccountId) -> bool {
         match parse_token_id(token_id) {
             TokenOrPool::Token(_) => false,
@@ -177,7 +202,7 @@ impl Contract {
         match parse_token_id(token_id) {
             TokenOrPool::Token(_) => env::panic(ERR110_INVALID_REGISTER.as_bytes()),
             TokenOrPool::Pool(pool_id) => {
-                let mut pool = self.pools.get(pool_id).expect(ERR85_NO_POOL);
+                let mut pool = self.internal_get_pool(pool_id);
                 pool.share_register(account_id.as_ref());
                 self.pools.replace(pool_id, &pool);
                 self.internal_check_storage(prev_storage);
@@ -195,7 +220,7 @@ impl Contract {
         match parse_token_id(token_id) {
             TokenOrPool::Token(_) => env::panic(ERR111_INVALID_UNREGISTER.as_bytes()),
             TokenOrPool::Pool(pool_id) => {
-                let mut pool = self.pools.get(pool_id).expect(ERR85_NO_POOL);
+                let mut pool = self.internal_get_pool(pool_id);
                 pool.share_unregister(&account_id);
                 self.pools.replace(pool_id, &pool);
                 if prev_storage > env::storage_usage() {
@@ -218,9 +243,11 @@ impl Contract {
     ) {
         assert_one_yocto();
         self.assert_contract_running();
+        let sender_id = env::predecessor_account_id();
+        self.internal_record_limited_withdrawal(&sender_id, &canonical_mft_token_id(token_id.clone()), amount.0);
         self.internal_mft_transfer(
             token_id,
-            &env::predecessor_account_id(),
+            &sender_id,
             receiver_id.as_ref(),
             Some(amount.0),
             memo,
@@ -238,7 +265,9 @@ impl Contract {
     ) -> PromiseOrValue<U128> {
         assert_one_yocto();
         self.assert_contract_running();
+        let receiver_gas = internal_mft_receiver_gas(receiver_id.as_ref());
         let sender_id = env::predecessor_account_id();
+        self.internal_record_limited_withdrawal(&sender_id, &canonical_mft_token_id(token_id.clone()), amount.0);
         self.internal_mft_transfer(
             token_id.clone(),
             &sender_id,
@@ -253,7 +282,7 @@ impl Contract {
             msg,
             receiver_id.as_ref(),
             NO_DEPOSIT,
-            env::prepaid_gas() - GAS_FOR_FT_TRANSFER_CALL,
+            receiver_gas,
         )
         .then(ext_self::mft_resolve_transfer(
             token_id,
@@ -277,6 +306,7 @@ impl Contract {
     ) -> PromiseOrValue<U128> {
         assert_one_yocto();
         self.assert_contract_running();
+        let receiver_gas = internal_mft_receiver_gas(receiver_id.as_ref());
         let sender_id = env::predecessor_account_id();
         let transfer_amount = self.internal_mft_transfer(
             token_id.clone(),
@@ -285,6 +315,7 @@ impl Contract {
             None,
             memo,
         );
+        self.internal_record_limited_withdrawal(&sender_id, &canonical_mft_token_id(token_id.clone()), transfer_amount);
         ext_share_token_receiver::mft_on_transfer(
             token_id.clone(),
             sender_id.clone(),
@@ -292,7 +323,7 @@ impl Contract {
             msg,
             receiver_id.as_ref(),
             NO_DEPOSIT,
-            env::prepaid_gas() - GAS_FOR_FT_TRANSFER_CALL,
+            receiver_gas,
         )
         .then(ext_self::mft_resolve_transfer(
             token_id,
@@ -349,7 +380,7 @@ impl Contract {
     pub fn mft_metadata(&self, token_id: String) -> FungibleTokenMetadata {
         match parse_token_id(token_id) {
             TokenOrPool::Pool(pool_id) => {
-                let pool = self.pools.get(pool_id).expect(ERR85_NO_POOL);
+                let pool = self.internal_get_pool(pool_id);
                 let decimals = pool.get_share_decimal();
                 FungibleTokenMetadata {
                     // [AUDIT_08]
@@ -0,0 +1,98 @@
+use crate::*;
+use crate::token_ledger::update_token_ledger;
+use crate::utils::{ext_self, ext_wrap_near, GAS_FOR_NEAR_DEPOSIT, GAS_FOR_RESOLVE_TRANSFER};
+use near_sdk::is_promise_success;
+
+#[near_bindgen]
+impl Contract {
+    /// Onboards the caller in one transaction. Part of the attached NEAR tops up the caller's
+    /// storage, by default just enough to register it with wNEAR, the rest is wrapped into wNEAR
+    /// deposited to its inner account, then the actions run on the inner account.
+    /// NEAR of a failed wrap is refunded; failing actions leave the wNEAR deposited.
+    #[payable]
+    pub fn near_deposit_and_execute(
+        &mut self,
+        storage_amount: Option<U128>,
+        actions: Vec<Action>,
+        referral_id: Option<ValidAccountId>,
+    ) -> Promise {
+        self.assert_contract_running();
+        let sender_id = env::predecessor_account_id();
+        assert_account_not_denied(&sender_id);
+        let wnear_id = self.wnear_id.clone().expect("wNEAR not set");
+        let mut account = self.internal_unwrap_or_default_account(&sender_id);
+        // Registering wNEAR up front keeps the deposit in the callback from failing on storage.
+        account.register(&vec![wnear_id.clone().try_into().unwrap()]);
+        let storage_amount = storage_amount
+            .map(|amount| amount.0)
+            .unwrap_or_else(|| {
+                let registration_fee = if self.accounts.contains_key(&sender_id) { 0 } else { Account::registration_fee() };
+                (account.storage_usage() + registration_fee).saturating_sub(account.near_amount)
+            });
+        let amount = env::attached_deposit().checked_sub(storage_amount).expect(ERR11_INSUFFICIENT_STORAGE);
+        assert!(amount > 0, "Nothing left to wrap");
+        account.near_amount += storage_amount;
+        self.internal_save_account(&sender_id, account);
+        ext_wrap_near::near_deposit(
+            &wnear_id,
+            amount,
+            GAS_FOR_NEAR_DEPOSIT,
+        )
+        .then(ext_self::exchange_callback_post_near_deposit(
+            sender_id,
+            U128(amount),
+            actions,
+            referral_id.map(|rid| rid.into()),
+            &env::current_account_id(),
+            0,
+            env::prepaid_gas() - env::used_gas() - GAS_FOR_NEAR_DEPOSIT - GAS_FOR_RESOLVE_TRANSFER,
+        ))
+    }
+
+    /// Credits the wrapped NEAR and hands the actions to a separate call, so a failing action
+    /// can't undo the deposit. Refunds the NEAR if the wrap failed.
+    #[private]
+    pub fn exchange_callback_post_near_deposit(
+        &mut self,
+        sender_id: AccountId,
+        amount: U128,
+        actions: Vec<Action>,
+        referral_id: Option<AccountId>,
+    ) {
+        if !is_promise_success() {
+            log!("Wrap of {} NEAR for {} failed, refunded", amount.0, sender_id);
+            Promise::new(sender_id).transfer(amount.0);
+            return;
+        }
+        let wnear_id = self.wnear_id.clone().expect("wNEAR not set");
+        update_token_ledger(&wnear_id, |ledger| ledger.total += amount.0 as i128);
+        self.internal_deposit(&sender_id, &wnear_id, amount.0);
+        if actions.is_empty() {
+            return;
+        }
+        ext_self::exchange_callback_near_deposit_actions(
+            sender_id,
+            actions,
+            referral_id,
+            &env::current_account_id(),
+            0,
+            env::prepaid_gas() - env::used_gas() - GAS_FOR_RESOLVE_TRANSFER,
+        );
+    }
+
+    /// Runs the actions of `near_deposit_and_execute` on the sender's inner account.
+    #[private]
+    pub fn exchange_callback_near_deposit_actions(
+        &mut self,
+        sender_id: AccountId,
+        actions: Vec<Action>,
+        referral_id: Option<AccountId>,
+    ) -> ActionResult {
+        self.assert_contract_running();
+        let referral_info = self.internal_get_referral_info(referral_id, &sender_id);
+        let mut account = self.internal_unwrap_account(&sender_id);
+        let result = self.internal_execute_actions(&sender_id, &mut account, &referral_info, &actions, ActionResult::None, None);
+        self.internal_save_account(&sender_id, account);
+        result
+    }
+}
@@ -0,0 +1,97 @@
+use crate::*;
+use near_sdk::collections::LookupSet;
+use near_sdk::json_types::U64;
+
+/// Lowest nonce each account still accepts orders with.
+pub fn read_order_nonce_floors_from_storage() -> LookupMap<AccountId, u64> {
+    if let Some(content) = env::storage_read(ORDER_NONCE_FLOORS.as_bytes()) {
+        LookupMap::try_from_slice(&content).expect("deserialize order nonce floors failed.")
+    } else {
+        LookupMap::new(StorageKey::OrderNonceFloors)
+    }
+}
+
+pub fn write_order_nonce_floors_to_storage(floors: LookupMap<AccountId, u64>) {
+    env::storage_write(
+        ORDER_NONCE_FLOORS.as_bytes(),
+        &floors.try_to_vec().unwrap(),
+    );
+}
+
+/// Nonces at or above the floor that were settled or cancelled.
+pub fn read_used_order_nonces_from_storage() -> LookupSet<(AccountId, u64)> {
+    if let Some(content) = env::storage_read(USED_ORDER_NONCES.as_bytes()) {
+        LookupSet::try_from_slice(&content).expect("deserialize used order nonces failed.")
+    } else {
+        LookupSet::new(StorageKey::UsedOrderNonces)
+    }
+}
+
+pub fn write_used_order_nonces_to_storage(used_nonces: LookupSet<(AccountId, u64)>) {
+    env::storage_write(
+        USED_ORDER_NONCES.as_bytes(),
+        &used_nonces.try_to_vec().unwrap(),
+    );
+}
+
+pub fn is_order_nonce_usable(account_id: &AccountId, nonce: u64) -> bool {
+    nonce >= read_order_nonce_floors_from_storage().get(account_id).unwrap_or(0)
+        && !read_used_order_nonces_from_storage().contains(&(account_id.clone(), nonce))
+}
+
+impl Contract {
+    /// Consumes the nonce of an order signed by the account, failing if it was already
+    /// settled or cancelled. Storage of the record is left to the calling method's check.
+    pub(crate) fn internal_use_order_nonce(&mut self, account_id: &AccountId, nonce: u64) {
+        assert!(is_order_nonce_usable(account_id, nonce), "Order nonce {} of {} already used or cancelled", nonce, account_id);
+        let mut used_nonces = read_used_order_nonces_from_storage();
+        used_nonces.insert(&(account_id.clone(), nonce));
+        write_used_order_nonces_to_storage(used_nonces);
+    }
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Cancel the caller's signed orders with these nonces.
+    /// Attached deposit covers the records, the rest is refunded.
+    #[payable]
+    pub fn cancel_order_nonces(&mut self, nonces: Vec<U64>) {
+        let prev_storage = env::storage_usage();
+        let account_id = env::predecessor_account_id();
+        for nonce in nonces {
+            if is_order_nonce_usable(&account_id, nonce.0) {
+                self.internal_use_order_nonce(&account_id, nonce.0);
+                log!("Order nonce {} of {} cancelled", nonce.0, account_id);
+            }
+        }
+        self.internal_check_storage(prev_storage);
+    }
+
+    /// Cancel all the caller's signed orders with nonces below `floor`, which can only go up.
+    /// Attached deposit covers the record, the rest is refunded.
+    #[payable]
+    pub fn cancel_order_nonces_below(&mut self, floor: U64) {
+        let prev_storage = env::storage_usage();
+        let account_id = env::predecessor_account_id();
+        let mut floors = read_order_nonce_floors_from_storage();
+        assert!(floor.0 > floors.get(&account_id).unwrap_or(0), "Order nonce floor can only go up");
+        floors.insert(&account_id, &floor.0);
+        write_order_nonce_floors_to_storage(floors);
+        log!("Order nonces of {} below {} cancelled", account_id, floor.0);
+        self.internal_check_storage(prev_storage);
+    }
+
+    pub fn get_order_nonce_floor(&self, account_id: ValidAccountId) -> U64 {
+        U64(read_order_nonce_floors_from_storage().get(account_id.as_ref()).unwrap_or(0))
+    }
+
+    /// Whether an order of the account signed with this nonce can still settle.
+    pub fn is_order_nonce_valid(&self, account_id: ValidAccountId, nonce: U64) -> bool {
+        is_order_nonce_usable(account_id.as_ref(), nonce.0)
+    }
+
+    /// Validity of several nonces of the account, in the order given.
+    pub fn check_order_nonces(&self, account_id: ValidAccountId, nonces: Vec<U64>) -> Vec<bool> {
+        nonces.into_iter().map(|nonce| is_order_nonce_usable(account_id.as_ref(), nonce.0)).collect()
+    }
+}
@@ -2,10 +2,11 @@
 
 use degen_swap::degen::{global_register_degen, global_register_degen_oracle_config, global_unregister_degen, global_unregister_degen_oracle_config, DegenOracleConfig, DegenType};
 use near_sdk::json_types::WrappedTimestamp;
+use near_sdk::Timestamp;
 use near_contract_standards::fungible_token::core_impl::ext_fungible_token;
 
 use crate::*;
-use crate::rated_swap::rate::{global_register_rate, global_unregister_rate, global_update_rated_token_extra_info};
+use crate::rated_swap::rate::{global_register_rate, global_unregister_rate, global_update_rated_token_extra_info, global_update_static_rate};
 use crate::utils::{FEE_DIVISOR, MAX_ADMIN_FEE_BPS, GAS_FOR_BASIC_OP};
 
 #[near_bindgen]
@@ -15,7 +16,8 @@ impl Contract {
     pub fn set_owner(&mut self, owner_id: ValidAccountId) {
         assert_one_yocto();
         self.assert_owner();
-        self.owner_id = owner_id.as_ref().clone();
+        audit_privileged_action("set_owner");
+        self.internal_set_owner(owner_id);
     }
 
     /// Get the owner of this account.
@@ -29,6 +31,7 @@ impl Contract {
     #[payable]
     pub fn retrieve_unmanaged_token(&mut self, token_id: ValidAccountId, amount: U128) -> Promise {
         self.assert_owner();
+        audit_privileged_action("retrieve_unmanaged_token");
         assert_one_yocto();
         let token_id: AccountId = token_id.into();
         let amount: u128 = amount.into();
@@ -55,9 +58,8 @@ impl Contract {
     pub fn extend_guardians(&mut self, guardians: Vec<ValidAccountId>) {
         assert_one_yocto();
         self.assert_owner();
-        for guardian in guardians {
-            self.guardians.insert(guardian.as_ref());
-        }
+        audit_privileged_action("extend_guardians");
+        self.internal_extend_guardians(guardians);
     }
 
     /// Remove guardians. Only can be called by owner.
@@ -65,17 +67,15 @@ impl Contract {
     pub fn remove_guardians(&mut self, guardians: Vec<ValidAccountId>) {
         assert_one_yocto();
         self.assert_owner();
-        for guardian in guardians {
-            let exist = self.guardians.remove(guardian.as_ref());
-            // [AUDITION_AMENDMENT] 2.3.1 Lack of Check on Guardians’ Removal
-            assert!(exist, "{}", ERR104_GUARDIAN_NOT_IN_LIST);
-        }
+        audit_privileged_action("remove_guardians");
+        self.internal_remove_guardians(guardians);
     }
 
     #[payable]
     pub fn extend_auto_whitelisted_postfix(&mut self, postfixes: Vec<String>) {
         assert_one_yocto();
         assert!(self.is_owner_or_guardians(), "{}", ERR100_NOT_ALLOWED);
+        audit_privileged_action("extend_auto_whitelisted_postfix");
         for postfix in postfixes {
             self.auto_whitelisted_postfix.insert(postfix.clone());
         }
@@ -85,6 +85,7 @@ impl Contract {
     pub fn remove_auto_whitelisted_postfix(&mut self, postfixes: Vec<String>) {
         assert_one_yocto();
         assert!(self.is_owner_or_guardians(), "{}", ERR100_NOT_ALLOWED);
+        audit_privileged_action("remove_auto_whitelisted_postfix");
         for postfix in postfixes {
             let exist = self.auto_whitelisted_postfix.remove(&postfix);
             assert!(exist, "{}", ERR105_WHITELISTED_POSTFIX_NOT_IN_LIST);
@@ -95,6 +96,7 @@ impl Contract {
     pub fn modify_boost_farm_id(&mut self, boost_farm_id: AccountId) {
         assert_one_yocto();
         assert!(self.is_owner_or_guardians(), "{}", ERR100_NOT_ALLOWED);
+        audit_privileged_action("modify_boost_farm_id");
         log!("Modify boost_farm_id from {} to {}", self.boost_farm_id, boost_farm_id);  
         self.boost_farm_id = boost_farm_id;
     }
@@ -103,6 +105,7 @@ impl Contract {
     pub fn modify_burrowland_id(&mut self, burrowland_id: AccountId) {
         assert_one_yocto();
         assert!(self.is_owner_or_guardians(), "{}", ERR100_NOT_ALLOWED);
+        audit_privileged_action("modify_burrowland_id");
         log!("Modify burrowland_id from {} to {}", self.burrowland_id, burrowland_id);  
         self.burrowland_id = burrowland_id;
     }
@@ -111,6 +114,7 @@ impl Contract {
     pub fn modify_wnear_id(&mut self, wnear_id: AccountId) {
         assert_one_yocto();
         self.assert_owner();
+        audit_privileged_action("modify_wnear_id");
         log!("Modify wnear_id from {:?} to {}", self.wnear_id, wnear_id);  
         self.wnear_id = Some(wnear_id);
     }
@@ -120,21 +124,12 @@ impl Contract {
     pub fn change_state(&mut self, state: RunningState) {
         assert_one_yocto();
         assert!(self.is_owner_or_guardians(), "{}", ERR100_NOT_ALLOWED);
-
-        if self.state != state {
-            if state == RunningState::Running {
-                // only owner can resume the contract
-                self.assert_owner();
-            }
-            env::log(
-                format!(
-                    "Contract state changed from {} to {} by {}",
-                    self.state, state, env::predecessor_account_id()
-                )
-                .as_bytes(),
-            );       
-            self.state = state;
+        audit_privileged_action("change_state");
+        if self.state != state && state == RunningState::Running {
+            // only owner can resume the contract
+            self.assert_owner();
         }
+        self.internal_change_state(state);
     }
 
     /// Extend whitelisted tokens with new tokens. Only can be called by owner.
@@ -142,9 +137,8 @@ impl Contract {
     pub fn extend_whitelisted_tokens(&mut self, tokens: Vec<ValidAccountId>) {
         assert_one_yocto();
         assert!(self.is_owner_or_guardians(), "{}", ERR100_NOT_ALLOWED);
-        for token in tokens {
-            self.whitelisted_tokens.insert(token.as_ref());
-        }
+        audit_privileged_action("extend_whitelisted_tokens");
+        self.internal_extend_whitelisted_tokens(tokens);
     }
 
     /// Remove whitelisted token. Only can be called by owner.
@@ -152,10 +146,8 @@ impl Contract {
     pub fn remove_whitelisted_tokens(&mut self, tokens: Vec<ValidAccountId>) {
         assert_one_yocto();
         assert!(self.is_owner_or_guardians(), "{}", ERR100_NOT_ALLOWED);
-        for token in tokens {
-            let exist = self.whitelisted_tokens.remove(token.as_ref());
-            assert!(exist, "{}", ERR53_TOKEN_NOT_IN_LIST);
-        }
+        audit_privileged_action("remove_whitelisted_tokens");
+        self.internal_remove_whitelisted_tokens(tokens);
     }
 
     /// Extend frozenlist tokens with new tokens.
@@ -163,6 +155,7 @@ impl Contract {
     pub fn extend_frozenlist_tokens(&mut self, tokens: Vec<ValidAccountId>) {
         assert_one_yocto();
         assert!(self.is_owner_or_guardians(), "{}", ERR100_NOT_ALLOWED);
+        audit_privileged_action("extend_frozenlist_tokens");
         for token in tokens {
             self.frozen_tokens.insert(token.as_ref());
         }
@@ -173,6 +166,7 @@ impl Contract {
     pub fn remove_frozenlist_tokens(&mut self, tokens: Vec<ValidAccountId>) {
         assert_one_yocto();
         assert!(self.is_owner_or_guardians(), "{}", ERR100_NOT_ALLOWED);
+        audit_privileged_action("remove_frozenlist_tokens");
         for token in tokens {
             let exist = self.frozen_tokens.remove(token.as_ref());
             assert!(exist, "{}", ERR53_TOKEN_NOT_IN_LIST);
@@ -184,10 +178,12 @@ impl Contract {
     pub fn insert_referral(&mut self, referral_id: ValidAccountId, fee_bps: u32) {
         assert_one_yocto();
         assert!(self.is_owner_or_guardians(), "{}", ERR100_NOT_ALLOWED);
+        audit_privileged_action("insert_referral");
         let referral_id: AccountId = referral_id.into();
         assert!(fee_bps > 0 && fee_bps < FEE_DIVISOR, "{}", ERR132_ILLEGAL_REFERRAL_FEE);
         let old_fee_bps = self.referrals.insert(&referral_id, &fee_bps);
         assert!(old_fee_bps.is_none(), "{}", ERR130_REFERRAL_EXIST);
+        self.internal_schedule_referral_activation(&referral_id);
         env::log(
             format!(
                 "Insert referral {} with fee_bps {}",
@@ -202,6 +198,7 @@ impl Contract {
     pub fn update_referral(&mut self, referral_id: ValidAccountId, fee_bps: u32) {
         assert_one_yocto();
         assert!(self.is_owner_or_guardians(), "{}", ERR100_NOT_ALLOWED);
+        audit_privileged_action("update_referral");
         let referral_id: AccountId = referral_id.into();
         assert!(fee_bps > 0 && fee_bps < FEE_DIVISOR, "{}", ERR132_ILLEGAL_REFERRAL_FEE);
         let old_fee_bps = self.referrals.insert(&referral_id, &fee_bps);
@@ -220,9 +217,11 @@ impl Contract {
     pub fn remove_referral(&mut self, referral_id: ValidAccountId) {
         assert_one_yocto();
         assert!(self.is_owner_or_guardians(), "{}", ERR100_NOT_ALLOWED);
+        audit_privileged_action("remove_referral");
         let referral_id: AccountId = referral_id.into();
         let old_fee_bps = self.referrals.remove(&referral_id);
         assert!(old_fee_bps.is_some(), "{}", ERR131_REFERRAL_NOT_EXIST);
+        self.internal_clear_referral_activation(&referral_id);
         env::log(
             format!(
                 "Remove referral {} where fee_bps {}",
@@ -238,21 +237,16 @@ impl Contract {
     pub fn modify_admin_fee(&mut self, admin_fee_bps: u32) {
         assert_one_yocto();
         self.assert_owner();
-        assert!(admin_fee_bps <= MAX_ADMIN_FEE_BPS, "{}", ERR101_ILLEGAL_FEE);
-        self.admin_fee_bps = admin_fee_bps;
+        audit_privileged_action("modify_admin_fee");
+        self.internal_modify_admin_fee(admin_fee_bps);
     }
 
     #[payable]
     pub fn modify_total_fee(&mut self, pool_id: u64, total_fee: u32) {
         assert_one_yocto();
         assert!(self.is_owner_or_guardians(), "{}", ERR100_NOT_ALLOWED);
-        assert!(total_fee < FEE_DIVISOR, "{}", ERR62_FEE_ILLEGAL);
-        let mut pool = self.pools.get(pool_id).expect(ERR85_NO_POOL);
-        env::log(
-            format!("Modify total_fee pool_id {} from {} to {}", pool_id, pool.get_fee(), total_fee).as_bytes()
-        );
-        pool.modify_total_fee(total_fee);
-        self.pools.replace(pool_id, &pool);
+        audit_privileged_action("modify_total_fee");
+        self.internal_modify_total_fee(pool_id, total_fee);
     }
 
     /// Remove exchange fee liquidity to owner's inner account.
@@ -261,10 +255,12 @@ impl Contract {
     pub fn remove_exchange_fee_liquidity(&mut self, pool_id: u64, shares: U128, min_amounts: Vec<U128>) {
         assert_one_yocto();
         assert!(self.is_owner_or_guardians(), "{}", ERR100_NOT_ALLOWED);
+        audit_privileged_action("remove_exchange_fee_liquidity");
         self.assert_contract_running();
         let ex_id = env::current_account_id();
         let owner_id = self.owner_id.clone();
-        let mut pool = self.pools.get(pool_id).expect(ERR85_NO_POOL);
+        let mut pool = self.internal_get_pool(pool_id);
+        self.internal_settle_lp_fees(pool_id, &pool, &[&ex_id]);
         let amounts = pool.remove_liquidity(
             &ex_id,
             shares.into(),
@@ -294,6 +290,7 @@ impl Contract {
     ) -> Promise {
         assert_one_yocto();
         assert!(self.is_owner_or_guardians(), "{}", ERR100_NOT_ALLOWED);
+        audit_privileged_action("withdraw_owner_token");
         self.assert_contract_running();
         let token_id: AccountId = token_id.into();
         let amount: u128 = amount.into();
@@ -318,34 +315,21 @@ impl Contract {
     ) {
         assert_one_yocto();
         assert!(self.is_owner_or_guardians(), "{}", ERR100_NOT_ALLOWED);
-        let mut pool = self.pools.get(pool_id).expect(ERR85_NO_POOL);
-        match &mut pool {
-            Pool::StableSwapPool(pool) => {
-                pool.ramp_amplification(future_amp_factor as u128, future_amp_time.0)
-            }
-            Pool::RatedSwapPool(pool) => {
-                pool.ramp_amplification(future_amp_factor as u128, future_amp_time.0)
-            }
-            Pool::DegenSwapPool(pool) => {
-                pool.ramp_amplification(future_amp_factor as u128, future_amp_time.0)
-            }
-            _ => env::panic(ERR88_NOT_STABLE_POOL.as_bytes()),
-        }
-        self.pools.replace(pool_id, &pool);
+        audit_privileged_action("stable_swap_ramp_amp");
+        self.internal_ramp_amp(pool_id, future_amp_factor, future_amp_time.0);
     }
 
+    /// Stops the ramp at the current interpolated amp and drops any amp schedule. With `freeze`,
+    /// an emergency stop of a destabilizing ramp, new ramps are rejected until the owner calls `resume_ramp_amp`.
     #[payable]
-    pub fn stable_swap_stop_ramp_amp(&mut self, pool_id: u64) {
+    pub fn stable_swap_stop_ramp_amp(&mut self, pool_id: u64, freeze: Option<bool>) {
         assert_one_yocto();
         assert!(self.is_owner_or_guardians(), "{}", ERR100_NOT_ALLOWED);
-        let mut pool = self.pools.get(pool_id).expect(ERR85_NO_POOL);
-        match &mut pool {
-            Pool::StableSwapPool(pool) => pool.stop_ramp_amplification(),
-            Pool::RatedSwapPool(pool) => pool.stop_ramp_amplification(),
-            Pool::DegenSwapPool(pool) => pool.stop_ramp_amplification(),
-            _ => env::panic(ERR88_NOT_STABLE_POOL.as_bytes()),
+        audit_privileged_action("stable_swap_stop_ramp_amp");
+        self.internal_stop_ramp_amp(pool_id);
+        if freeze.unwrap_or(false) {
+            freeze_amp(pool_id);
         }
-        self.pools.replace(pool_id, &pool);
     }
 
     /// Register new rated token.
@@ -353,6 +337,7 @@ impl Contract {
     pub fn register_rated_token(&mut self, rate_type: String, token_id: ValidAccountId, extra_info: Option<String>) {
         assert_one_yocto();
         assert!(self.is_owner_or_guardians(), "{}", ERR100_NOT_ALLOWED);
+        audit_privileged_action("register_rated_token");
         let token_id: AccountId = token_id.into();
         if global_register_rate(&rate_type, &token_id, extra_info) {
             log!("New {} typed rated token {} registered by {}", rate_type, token_id, env::predecessor_account_id());
@@ -366,6 +351,7 @@ impl Contract {
     pub fn unregister_rated_token(&mut self, token_id: ValidAccountId) {
         assert_one_yocto();
         self.assert_owner();
+        audit_privileged_action("unregister_rated_token");
         let token_id: AccountId = token_id.into();
         if global_unregister_rate(&token_id) {
             log!("Rated token {} removed.", token_id);
@@ -378,16 +364,30 @@ impl Contract {
     pub fn update_rated_token_extra_info(&mut self, token_id: ValidAccountId, extra_info: String) {
         assert_one_yocto();
         assert!(self.is_owner_or_guardians(), "{}", ERR100_NOT_ALLOWED);
+        audit_privileged_action("update_rated_token_extra_info");
         let token_id: AccountId = token_id.into();
         global_update_rated_token_extra_info(&token_id, extra_info.clone());
         log!("Update rated token {} extra info: {}", token_id, extra_info);
     }
 
+    /// Set the rate of a STATIC typed rated token, whose rate is never synced.
+    /// Pools holding it reprice on their next swap, so change it in small steps.
+    #[payable]
+    pub fn update_static_token_rate(&mut self, token_id: ValidAccountId, rate: U128) {
+        assert_one_yocto();
+        self.assert_owner();
+        audit_privileged_action("update_static_token_rate");
+        let token_id: AccountId = token_id.into();
+        global_update_static_rate(&token_id, rate.0);
+        log!("Update static rate of token {} to {}", token_id, rate.0);
+    }
+
     /// Register new degen token.
     #[payable]
     pub fn register_degen_token(&mut self, token_id: ValidAccountId, degen_type: DegenType) {
         assert_one_yocto();
         assert!(self.is_owner_or_guardians(), "{}", ERR100_NOT_ALLOWED);
+        audit_privileged_action("register_degen_token");
         let token_id: AccountId = token_id.into();
         if global_register_degen(&token_id, degen_type.clone()) {
             log!("New {:?} typed degen token {} registered by {}", degen_type, token_id, env::predecessor_account_id());
@@ -401,6 +401,7 @@ impl Contract {
     pub fn unregister_degen_token(&mut self, token_id: ValidAccountId) {
         assert_one_yocto();
         self.assert_owner();
+        audit_privileged_action("unregister_degen_token");
         let token_id: AccountId = token_id.into();
         if global_unregister_degen(&token_id) {
             log!("Degen token {} removed.", token_id);
@@ -414,6 +415,7 @@ impl Contract {
     pub fn register_degen_oracle_config(&mut self, degen_oracle_config: DegenOracleConfig) {
         assert_one_yocto();
         assert!(self.is_owner_or_guardians(), "{}", ERR100_NOT_ALLOWED);
+        audit_privileged_action("register_degen_oracle_config");
         if global_register_degen_oracle_config(degen_oracle_config.clone()) {
             log!("New degen oracle config {} registered by {}", degen_oracle_config.get_key(), env::predecessor_account_id());
         } else {
@@ -426,6 +428,7 @@ impl Contract {
     pub fn unregister_degen_oracle_config(&mut self, degen_oracle_config_key: String) {
         assert_one_yocto();
         self.assert_owner();
+        audit_privileged_action("unregister_degen_oracle_config");
         if global_unregister_degen_oracle_config(&degen_oracle_config_key) {
             log!("Degen oracle config {} removed.", degen_oracle_config_key);
         } else {
@@ -438,6 +441,7 @@ impl Contract {
     pub fn update_degen_oracle_config(&mut self, degen_oracle_config: DegenOracleConfig) {
         assert_one_yocto();
         self.assert_owner();
+        audit_privileged_action("update_degen_oracle_config");
         if global_update_degen_oracle_config(degen_oracle_config.clone()) {
             log!("Update oracle degen config {} registered by {}", degen_oracle_config.get_key(), env::predecessor_account_id());
         } else {
@@ -449,6 +453,7 @@ impl Contract {
     pub fn add_degen_pool_limit(&mut self, pool_id: u64, degen_pool_limit_info: DegenPoolLimitInfo) {
         assert_one_yocto();
         assert!(self.is_owner_or_guardians(), "{}", ERR100_NOT_ALLOWED);
+        audit_privileged_action("add_degen_pool_limit");
         assert!(self.get_pool(pool_id).pool_kind == "DEGEN_SWAP");
         let mut pool_limit = read_pool_limit_from_storage();
         assert!(pool_limit.get(&pool_id).is_none(), "degen pool limit already exist");
@@ -460,6 +465,7 @@ impl Contract {
     pub fn update_degen_pool_limit(&mut self, pool_id: u64, degen_pool_limit_info: DegenPoolLimitInfo) {
         assert_one_yocto();
         self.assert_owner();
+        audit_privileged_action("update_degen_pool_limit");
         assert!(self.get_pool(pool_id).pool_kind == "DEGEN_SWAP");
         let mut pool_limit = read_pool_limit_from_storage();
         assert!(pool_limit.get(&pool_id).is_some(), "degen pool limit not exist");
@@ -471,6 +477,7 @@ impl Contract {
     pub fn remove_pool_limit(&mut self, pool_id: u64) {
         assert_one_yocto();
         self.assert_owner();
+        audit_privileged_action("remove_pool_limit");
         let mut pool_limit = read_pool_limit_from_storage();
         assert!(pool_limit.remove(&pool_id).is_some(), "Invalid pool_id");
         write_pool_limit_to_storage(pool_limit);
@@ -489,6 +496,98 @@ impl Contract {
             || self.guardians.contains(&env::predecessor_account_id())
     }
 
+    pub(crate) fn internal_set_owner(&mut self, owner_id: ValidAccountId) {
+        self.owner_id = owner_id.as_ref().clone();
+    }
+
+    pub(crate) fn internal_extend_guardians(&mut self, guardians: Vec<ValidAccountId>) {
+        for guardian in guardians {
+            self.guardians.insert(guardian.as_ref());
+        }
+    }
+
+    pub(crate) fn internal_remove_guardians(&mut self, guardians: Vec<ValidAccountId>) {
+        for guardian in guardians {
+            let exist = self.guardians.remove(guardian.as_ref());
+            // [AUDITION_AMENDMENT] 2.3.1 Lack of Check on Guardians’ Removal
+            assert!(exist, "{}", ERR104_GUARDIAN_NOT_IN_LIST);
+        }
+    }
+
+    pub(crate) fn internal_change_state(&mut self, state: RunningState) {
+        if self.state != state {
+            env::log(
+                format!(
+                    "Contract state changed from {} to {} by {}",
+                    self.state, state, env::predecessor_account_id()
+                )
+                .as_bytes(),
+            );       
+            self.state = state;
+        }
+    }
+
+    pub(crate) fn internal_extend_whitelisted_tokens(&mut self, tokens: Vec<ValidAccountId>) {
+        for token in tokens {
+            self.whitelisted_tokens.insert(token.as_ref());
+        }
+    }
+
+    pub(crate) fn internal_remove_whitelisted_tokens(&mut self, tokens: Vec<ValidAccountId>) {
+        for token in tokens {
+            let exist = self.whitelisted_tokens.remove(token.as_ref());
+            assert!(exist, "{}", ERR53_TOKEN_NOT_IN_LIST);
+        }
+    }
+
+    pub(crate) fn internal_modify_admin_fee(&mut self, admin_fee_bps: u32) {
+        assert!(admin_fee_bps <= MAX_ADMIN_FEE_BPS, "{}", ERR101_ILLEGAL_FEE);
+        self.admin_fee_bps = admin_fee_bps;
+    }
+
+    pub(crate) fn internal_ramp_amp(&mut self, pool_id: u64, future_amp_factor: u64, future_amp_time: Timestamp) {
+        assert_amp_not_frozen(pool_id);
+        let mut pool = self.internal_get_pool(pool_id);
+        match &mut pool {
+            Pool::StableSwapPool(pool) => {
+                pool.ramp_amplification(future_amp_factor as u128, future_amp_time)
+            }
+            Pool::RatedSwapPool(pool) => {
+                pool.ramp_amplification(future_amp_factor as u128, future_amp_time)
+            }
+            Pool::DegenSwapPool(pool) => {
+                pool.ramp_amplification(future_amp_factor as u128, future_amp_time)
+            }
+            _ => env::panic(ERR88_NOT_STABLE_POOL.as_bytes()),
+        }
+        self.pools.replace(pool_id, &pool);
+        self.internal_clear_amp_schedule(pool_id);
+    }
+
+    pub(crate) fn internal_stop_ramp_amp(&mut self, pool_id: u64) {
+        let mut pool = self.internal_get_pool(pool_id);
+        match &mut pool {
+            Pool::StableSwapPool(pool) => pool.stop_ramp_amplification(),
+            Pool::RatedSwapPool(pool) => pool.stop_ramp_amplification(),
+            Pool::DegenSwapPool(pool) => pool.stop_ramp_amplification(),
+            _ => env::panic(ERR88_NOT_STABLE_POOL.as_bytes()),
+        }
+        self.pools.replace(pool_id, &pool);
+        self.internal_clear_amp_schedule(pool_id);
+    }
+
+    pub(crate) fn internal_modify_total_fee(&mut self, pool_id: u64, total_fee: u32) {
+        assert!(total_fee < FEE_DIVISOR, "{}", ERR62_FEE_ILLEGAL);
+        let mut pool = self.internal_get_pool(pool_id);
+        env::log(
+            format!("Modify total_fee pool_id {} from {} to {}", pool_id, pool.get_fee(), total_fee).as_bytes()
+        );
+        let prev_key = pool_registry_key(&pool);
+        pool.modify_total_fee(total_fee);
+        self.pools.replace(pool_id, &pool);
+        self.internal_rekey_pool(pool_id, &prev_key, &pool);
+    }
+
     /// Migration function from v1.6.x to v1.7.0.
     /// For next version upgrades, change this function.
     #[init(ignore_state)]
@@ -0,0 +1,131 @@
+use crate::*;
+
+/// Amounts of a swap action that went through.
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+#[cfg_attr(not(target_arch = "wasm32"), derive(Debug))]
+pub struct ActionOutcome {
+    pub amount_in: U128,
+    pub amount_out: U128,
+}
+
+/// Result of `execute_actions_partial`. Completed actions stay executed, nothing after
+/// the failed one was attempted.
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+#[cfg_attr(not(target_arch = "wasm32"), derive(Debug))]
+pub struct PartialActionsResult {
+    pub completed: Vec<ActionOutcome>,
+    /// Index of the action execution stopped at, None if all completed.
+    pub failed_action: Option<u32>,
+    pub failure: Option<String>,
+}
+
+impl Contract {
+    /// Checks a swap can go through as given, returning its quoted amount out.
+    fn internal_check_partial_swap(
+        &self,
+        account: &Account,
+        referral_info: &Option<(AccountId, u32)>,
+        swap_action: &SwapAction,
+        amount_in: Balance,
+    ) -> Result<Balance, String> {
+        let pool_id = swap_action.pool_id;
+        if pool_id >= self.pools.len() {
+            return Err(format!("Pool {} not found", pool_id));
+        }
+        if self.is_pool_archived(pool_id) {
+            return Err(format!("Pool {} archived", pool_id));
+        }
+        if read_launch_auctions_from_storage().get(&pool_id).is_some() {
+            return Err(format!("Pool {} in launch auction", pool_id));
+        }
+        let tokens = self.internal_get_pool(pool_id).tokens().to_vec();
+        if !tokens.contains(&swap_action.token_in) || !tokens.contains(&swap_action.token_out) {
+            return Err(format!("Pool {} doesn't hold {} and {}", pool_id, swap_action.token_in, swap_action.token_out));
+        }
+        if account.get_balance(&swap_action.token_in).unwrap_or(0) < amount_in {
+            return Err(format!("Insufficient {} balance for {}", swap_action.token_in, amount_in));
+        }
+        let quoted = self.internal_pool_swap_by_cache(
+            &mut HashMap::new(),
+            pool_id,
+            &swap_action.token_in,
+            amount_in,
+            &swap_action.token_out,
+            0,
+            referral_info,
+        );
+        if quoted < swap_action.min_amount_out.0 {
+            return Err(format!("Quoted {} below min_amount_out {}", quoted, swap_action.min_amount_out.0));
+        }
+        Ok(quoted)
+    }
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Execute swap actions on the predecessor's inner account one by one, stopping before
+    /// the first one that would fail on missing pool, balance or min_amount_out, and keeping
+    /// the completed ones. Checks that apply to the whole batch still fail the call.
+    #[payable]
+    pub fn execute_actions_partial(&mut self, actions: Vec<Action>, referral_id: Option<ValidAccountId>) -> PartialActionsResult {
+        self.assert_contract_running();
+        assert_ne!(actions.len(), 0, "{}", ERR72_AT_LEAST_ONE_SWAP);
+        assert!(matches!(actions[0], Action::Swap(_)), "Partial execution only applies to swap actions");
+        let sender_id = env::predecessor_account_id();
+        let mut account = self.internal_unwrap_account(&sender_id);
+        if env::attached_deposit() == 0 {
+            for token in get_tokens_in_actions(&actions) {
+                assert!(
+                    account.get_balance(&token).is_some() || self.is_whitelisted_token(&token),
+                    "{}",
+                    ERR27_DEPOSIT_NEEDED
+                );
+            }
+        }
+        assert_account_not_denied(&sender_id);
+        assert_all_same_action_type(&actions);
+        assert_within_action_limits(&actions);
+        self.assert_no_frozen_tokens(&get_tokens_in_actions(&actions).into_iter().collect::<Vec<AccountId>>());
+        let referral_info = self.internal_get_referral_info(referral_id.map(|rid| rid.into()), &sender_id);
+
+        let mut completed = vec![];
+        let mut failure = None;
+        let mut prev_amount_out = None;
+        for (index, action) in actions.iter().enumerate() {
+            let swap_action = match action {
+                Action::Swap(swap_action) => swap_action,
+                Action::SwapByOutput(_) => unreachable!(),
+            };
+            let amount_in = match swap_action.amount_in.map(|amount_in| amount_in.0).or(prev_amount_out) {
+                Some(amount_in) => amount_in,
+                None => {
+                    failure = Some((index, "No amount_in on the first step".to_string()));
+                    break;
+                }
+            };
+            if let Err(reason) = self.internal_check_partial_swap(&account, &referral_info, swap_action, amount_in) {
+                failure = Some((index, reason));
+                break;
+            }
+            let amount_out = self
+                .internal_execute_action(&sender_id, &mut account, &referral_info, action, ActionResult::Amount(U128(amount_in)))
+                .to_amount();
+            completed.push(ActionOutcome { amount_in: U128(amount_in), amount_out: U128(amount_out) });
+            prev_amount_out = Some(amount_out);
+        }
+        self.internal_save_account(&sender_id, account);
+        for token_id in self.get_degen_tokens_in_actions(&actions[..completed.len()]) {
+            sync_degen_prices(&token_id);
+        }
+        if let Some((index, reason)) = failure.as_ref() {
+            log!("Action {} of {} not executed: {}", index, sender_id, reason);
+        }
+        PartialActionsResult {
+            completed,
+            failed_action: failure.as_ref().map(|(index, _)| *index as u32),
+            failure: failure.map(|(_, reason)| reason),
+        }
+    }
+}
@@ -0,0 +1,167 @@
+use crate::*;
+use near_sdk::collections::LookupSet;
+
+pub fn read_permissioned_pools_from_storage() -> LookupSet<u64> {
+    if let Some(content) = env::storage_read(PERMISSIONED_POOLS.as_bytes()) {
+        LookupSet::try_from_slice(&content).expect("deserialize permissioned pools failed.")
+    } else {
+        LookupSet::new(StorageKey::PermissionedPools)
+    }
+}
+
+pub fn write_permissioned_pools_to_storage(permissioned_pools: LookupSet<u64>) {
+    env::storage_write(
+        PERMISSIONED_POOLS.as_bytes(),
+        &permissioned_pools.try_to_vec().unwrap(),
+    );
+}
+
+pub fn read_pool_lp_allowlist_from_storage() -> LookupSet<(u64, AccountId)> {
+    if let Some(content) = env::storage_read(POOL_LP_ALLOWLIST.as_bytes()) {
+        LookupSet::try_from_slice(&content).expect("deserialize pool lp allowlist failed.")
+    } else {
+        LookupSet::new(StorageKey::PoolLpAllowlist)
+    }
+}
+
+pub fn write_pool_lp_allowlist_to_storage(pool_lp_allowlist: LookupSet<(u64, AccountId)>) {
+    env::storage_write(
+        POOL_LP_ALLOWLIST.as_bytes(),
+        &pool_lp_allowlist.try_to_vec().unwrap(),
+    );
+}
+
+pub fn read_transfer_restricted_pools_from_storage() -> LookupSet<u64> {
+    if let Some(content) = env::storage_read(TRANSFER_RESTRICTED_POOLS.as_bytes()) {
+        LookupSet::try_from_slice(&content).expect("deserialize transfer restricted pools failed.")
+    } else {
+        LookupSet::new(StorageKey::TransferRestrictedPools)
+    }
+}
+
+pub fn write_transfer_restricted_pools_to_storage(transfer_restricted_pools: LookupSet<u64>) {
+    env::storage_write(
+        TRANSFER_RESTRICTED_POOLS.as_bytes(),
+        &transfer_restricted_pools.try_to_vec().unwrap(),
+    );
+}
+
+impl Contract {
+    /// Whether the account may get new shares of the pool. In a permissioned pool
+    /// only the creator, the allow-listed accounts and the exchange itself can.
+    pub fn internal_is_lp_allowed(&self, pool_id: u64, account_id: &AccountId) -> bool {
+        !read_permissioned_pools_from_storage().contains(&pool_id)
+            || account_id == &env::current_account_id()
+            || self.internal_get_pool_creator(pool_id).as_ref() == Some(account_id)
+            || read_pool_lp_allowlist_from_storage().contains(&(pool_id, account_id.clone()))
+    }
+
+    pub(crate) fn assert_lp_allowed(&self, pool_id: u64, account_id: &AccountId) {
+        assert!(
+            self.internal_is_lp_allowed(pool_id, account_id),
+            "{} is not allowed to provide liquidity to pool {}", account_id, pool_id
+        );
+    }
+
+    /// Whether shares of the pool can be transferred to the account. A transfer restricted
+    /// permissioned pool only lets them go to the creator and the allow-listed accounts.
+    pub fn internal_is_share_transfer_allowed(&self, pool_id: u64, receiver_id: &AccountId) -> bool {
+        if !read_permissioned_pools_from_storage().contains(&pool_id)
+            || !read_transfer_restricted_pools_from_storage().contains(&pool_id) {
+            return self.internal_is_lp_allowed(pool_id, receiver_id);
+        }
+        self.internal_get_pool_creator(pool_id).as_ref() == Some(receiver_id)
+            || read_pool_lp_allowlist_from_storage().contains(&(pool_id, receiver_id.clone()))
+    }
+
+    pub(crate) fn assert_share_transfer_allowed(&self, pool_id: u64, receiver_id: &AccountId) {
+        assert!(
+            self.internal_is_share_transfer_allowed(pool_id, receiver_id),
+            "{} is not allowed to receive shares of pool {}", receiver_id, pool_id
+        );
+    }
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Turn the LP allow-list of a pool on or off. Swaps stay open either way.
+    /// Callable by the pool creator or the owner.
+    #[payable]
+    pub fn set_pool_permissioned(&mut self, pool_id: u64, permissioned: bool) {
+        assert!(env::attached_deposit() > 0, "{}", ERR35_AT_LEAST_ONE_YOCTO);
+        assert!(pool_id < self.pools.len(), "{}", ERR85_NO_POOL);
+        self.assert_pool_creator_or_owner(pool_id);
+        let prev_storage = env::storage_usage();
+        let mut permissioned_pools = read_permissioned_pools_from_storage();
+        if permissioned {
+            permissioned_pools.insert(&pool_id);
+        } else {
+            permissioned_pools.remove(&pool_id);
+        }
+        write_permissioned_pools_to_storage(permissioned_pools);
+        self.internal_check_storage(prev_storage);
+    }
+
+    /// Allow accounts to provide liquidity to a permissioned pool, callable by the pool creator or the owner.
+    /// Attached deposit covers the storage of the new entries, the rest is refunded.
+    #[payable]
+    pub fn extend_pool_lp_allowlist(&mut self, pool_id: u64, account_ids: Vec<ValidAccountId>) {
+        assert!(env::attached_deposit() > 0, "{}", ERR35_AT_LEAST_ONE_YOCTO);
+        assert!(pool_id < self.pools.len(), "{}", ERR85_NO_POOL);
+        self.assert_pool_creator_or_owner(pool_id);
+        let prev_storage = env::storage_usage();
+        let mut pool_lp_allowlist = read_pool_lp_allowlist_from_storage();
+        for account_id in account_ids {
+            pool_lp_allowlist.insert(&(pool_id, account_id.into()));
+        }
+        write_pool_lp_allowlist_to_storage(pool_lp_allowlist);
+        self.internal_check_storage(prev_storage);
+    }
+
+    /// Remove accounts from the allow-list of a pool, their shares stay but they can't get new ones.
+    #[payable]
+    pub fn remove_pool_lp_allowlist(&mut self, pool_id: u64, account_ids: Vec<ValidAccountId>) {
+        assert_one_yocto();
+        self.assert_pool_creator_or_owner(pool_id);
+        let mut pool_lp_allowlist = read_pool_lp_allowlist_from_storage();
+        for account_id in account_ids {
+            pool_lp_allowlist.remove(&(pool_id, account_id.into()));
+        }
+        write_pool_lp_allowlist_to_storage(pool_lp_allowlist);
+    }
+
+    /// Restrict share transfers of a permissioned pool to the creator and the allow-listed
+    /// accounts, on top of the LP allow-list. Callable by the pool creator or the owner.
+    #[payable]
+    pub fn set_pool_share_transfer_restricted(&mut self, pool_id: u64, restricted: bool) {
+        assert!(env::attached_deposit() > 0, "{}", ERR35_AT_LEAST_ONE_YOCTO);
+        assert!(pool_id < self.pools.len(), "{}", ERR85_NO_POOL);
+        self.assert_pool_creator_or_owner(pool_id);
+        let prev_storage = env::storage_usage();
+        let mut transfer_restricted_pools = read_transfer_restricted_pools_from_storage();
+        if restricted {
+            assert!(read_permissioned_pools_from_storage().contains(&pool_id), "Pool {} is not permissioned", pool_id);
+            transfer_restricted_pools.insert(&pool_id);
+        } else {
+            transfer_restricted_pools.remove(&pool_id);
+        }
+        write_transfer_restricted_pools_to_storage(transfer_restricted_pools);
+        self.internal_check_storage(prev_storage);
+    }
+
+    pub fn is_pool_permissioned(&self, pool_id: u64) -> bool {
+        read_permissioned_pools_from_storage().contains(&pool_id)
+    }
+
+    pub fn is_pool_lp_allowed(&self, pool_id: u64, account_id: ValidAccountId) -> bool {
+        self.internal_is_lp_allowed(pool_id, account_id.as_ref())
+    }
+
+    pub fn is_pool_share_transfer_restricted(&self, pool_id: u64) -> bool {
+        read_transfer_restricted_pools_from_storage().contains(&pool_id)
+    }
+
+    pub fn is_pool_share_transfer_allowed(&self, pool_id: u64, receiver_id: ValidAccountId) -> bool {
+        self.internal_is_share_transfer_allowed(pool_id, receiver_id.as_ref())
+    }
+}
@@ -0,0 +1,273 @@
+use crate::*;
+use crate::utils::{u128_dec_format, u64_dec_format, u128_ratio};
+use near_sdk::Timestamp;
+
+/// Delay between requesting a withdrawal of protocol-owned liquidity and executing it.
+pub const POL_WITHDRAW_TIMELOCK: Timestamp = 3 * 24 * 3600 * 1_000_000_000;
+
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone)]
+#[serde(crate = "near_sdk::serde")]
+#[cfg_attr(not(target_arch = "wasm32"), derive(Debug))]
+pub struct PolWithdrawal {
+    #[serde(with = "u128_dec_format")]
+    pub shares: Balance,
+    #[serde(with = "u64_dec_format")]
+    pub unlock_time: Timestamp,
+}
+
+/// Protocol-owned liquidity of a designated pool. The shares sit in the holder's account
+/// and are locked there, they only leave through a timelocked withdrawal.
+#[derive(BorshSerialize, BorshDeserialize)]
+pub struct PolPosition {
+    /// Treasury that deployed the current shares.
+    pub holder_id: Option<AccountId>,
+    pub shares: Balance,
+    /// Token amounts deployed so far, in pool token order.
+    pub cost_basis: Vec<Balance>,
+    /// Token amounts withdrawn so far, in pool token order.
+    pub withdrawn: Vec<Balance>,
+    pub pending_withdrawal: Option<PolWithdrawal>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+#[cfg_attr(not(target_arch = "wasm32"), derive(Debug))]
+pub struct PolPositionInfo {
+    pub holder_id: Option<AccountId>,
+    pub shares: U128,
+    pub cost_basis: Vec<U128>,
+    pub withdrawn: Vec<U128>,
+    /// Token amounts the shares are worth now.
+    pub current_amounts: Vec<U128>,
+    /// Signed current + withdrawn - cost basis per token, as decimal strings.
+    pub pnl: Vec<String>,
+    pub pending_withdrawal: Option<PolWithdrawal>,
+}
+
+pub fn read_pol_treasury_from_storage() -> Option<AccountId> {
+    if let Some(content) = env::storage_read(POL_TREASURY.as_bytes()) {
+        Option::<AccountId>::try_from_slice(&content).expect("deserialize pol treasury failed.")
+    } else {
+        None
+    }
+}
+
+pub fn write_pol_treasury_to_storage(treasury_id: Option<AccountId>) {
+    env::storage_write(
+        POL_TREASURY.as_bytes(),
+        &treasury_id.try_to_vec().unwrap(),
+    );
+}
+
+pub fn read_pol_positions_from_storage() -> LookupMap<u64, PolPosition> {
+    if let Some(content) = env::storage_read(POL_POSITIONS.as_bytes()) {
+        LookupMap::try_from_slice(&content).expect("deserialize pol positions failed.")
+    } else {
+        LookupMap::new(StorageKey::PolPositions)
+    }
+}
+
+pub fn write_pol_positions_to_storage(pol_positions: LookupMap<u64, PolPosition>) {
+    env::storage_write(
+        POL_POSITIONS.as_bytes(),
+        &pol_positions.try_to_vec().unwrap(),
+    );
+}
+
+fn signed_diff(gain: Balance, cost: Balance) -> String {
+    if gain >= cost {
+        (gain - cost).to_string()
+    } else {
+        format!("-{}", cost - gain)
+    }
+}
+
+impl Contract {
+    /// Protocol-owned shares the account holds in the pool.
+    pub fn internal_pol_shares(&self, account_id: &AccountId, pool_id: u64) -> Balance {
+        read_pol_positions_from_storage()
+            .get(&pool_id)
+            .filter(|position| position.holder_id.as_ref() == Some(account_id))
+            .map(|position| position.shares)
+            .unwrap_or(0)
+    }
+
+    fn assert_pol_treasury(&self) -> AccountId {
+        let treasury_id = read_pol_treasury_from_storage().expect("No POL treasury");
+        assert_eq!(env::predecessor_account_id(), treasury_id, "{}", ERR100_NOT_ALLOWED);
+        treasury_id
+    }
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Set the account allowed to deploy protocol-owned liquidity.
+    #[payable]
+    pub fn set_pol_treasury(&mut self, treasury_id: Option<ValidAccountId>) {
+        assert_one_yocto();
+        self.assert_owner();
+        audit_privileged_action("set_pol_treasury");
+        write_pol_treasury_to_storage(treasury_id.map(|id| id.into()));
+    }
+
+    /// Designate a pool for protocol-owned liquidity, or drop an emptied one.
+    #[payable]
+    pub fn set_pol_pool(&mut self, pool_id: u64, designated: bool) {
+        assert_one_yocto();
+        self.assert_owner();
+        audit_privileged_action("set_pol_pool");
+        let mut pol_positions = read_pol_positions_from_storage();
+        if designated {
+            let token_count = self.internal_get_pool(pool_id).tokens().len();
+            assert!(pol_positions.get(&pool_id).is_none(), "Pool already designated");
+            pol_positions.insert(&pool_id, &PolPosition {
+                holder_id: None,
+                shares: 0,
+                cost_basis: vec![0; token_count],
+                withdrawn: vec![0; token_count],
+                pending_withdrawal: None,
+            });
+        } else {
+            let position = pol_positions.remove(&pool_id).expect("Pool not designated");
+            assert_eq!(position.shares, 0, "POL still deployed in pool");
+        }
+        write_pol_positions_to_storage(pol_positions);
+    }
+
+    /// Add liquidity from the treasury's inner account into a designated pool.
+    /// min_amounts applies to simple pools and min_shares to the other kinds, as in
+    /// `add_liquidity` and `add_stable_liquidity`.
+    #[payable]
+    pub fn pol_deploy(
+        &mut self,
+        pool_id: u64,
+        amounts: Vec<U128>,
+        min_amounts: Option<Vec<U128>>,
+        min_shares: Option<U128>,
+    ) -> U128 {
+        let treasury_id = self.assert_pol_treasury();
+        audit_privileged_action("pol_deploy");
+        let mut pol_positions = read_pol_positions_from_storage();
+        let mut position = pol_positions.get(&pool_id).expect("Pool not designated");
+        if position.shares > 0 {
+            assert_eq!(position.holder_id.as_ref(), Some(&treasury_id), "POL held by another treasury");
+        }
+        let tokens = self.internal_get_pool(pool_id).tokens().to_vec();
+        let account = self.internal_unwrap_account(&treasury_id);
+        let prev_balances: Vec<Balance> = tokens.iter().map(|token_id| account.get_balance(token_id).unwrap_or(0)).collect();
+        let shares = match self.internal_get_pool(pool_id) {
+            Pool::SimplePool(_) | Pool::RangePool(_) => self.add_liquidity(pool_id, amounts, min_amounts),
+            _ => self.add_stable_liquidity(pool_id, amounts, min_shares.expect("Need input min_shares")),
+        };
+        let account = self.internal_unwrap_account(&treasury_id);
+        let mut deployed = vec![];
+        for (i, token_id) in tokens.iter().enumerate() {
+            let amount = prev_balances[i] - account.get_balance(token_id).unwrap_or(0);
+            position.cost_basis[i] += amount;
+            deployed.push(U128(amount));
+        }
+        position.holder_id = Some(treasury_id.clone());
+        position.shares += shares.0;
+        pol_positions.insert(&pool_id, &position);
+        write_pol_positions_to_storage(pol_positions);
+        event::Event::PolDeploy {
+            account_id: &treasury_id,
+            pool_id,
+            shares,
+            amounts: deployed,
+        }.emit();
+        shares
+    }
+
+    /// Start the timelock for withdrawing `shares` of protocol-owned liquidity, replacing any pending request.
+    #[payable]
+    pub fn pol_request_withdraw(&mut self, pool_id: u64, shares: U128) {
+        assert_one_yocto();
+        let treasury_id = self.assert_pol_treasury();
+        audit_privileged_action("pol_request_withdraw");
+        let mut pol_positions = read_pol_positions_from_storage();
+        let mut position = pol_positions.get(&pool_id).expect("Pool not designated");
+        assert_eq!(position.holder_id.as_ref(), Some(&treasury_id), "POL held by another treasury");
+        assert!(shares.0 > 0 && shares.0 <= position.shares, "Invalid shares");
+        position.pending_withdrawal = Some(PolWithdrawal {
+            shares: shares.0,
+            unlock_time: env::block_timestamp() + POL_WITHDRAW_TIMELOCK,
+        });
+        pol_positions.insert(&pool_id, &position);
+        write_pol_positions_to_storage(pol_positions);
+    }
+
+    /// Drop a pending withdrawal, callable by the treasury, the owner or guardians.
+    #[payable]
+    pub fn pol_cancel_withdraw(&mut self, pool_id: u64) {
+        assert_one_yocto();
+        assert!(
+            self.is_owner_or_guardians() || read_pol_treasury_from_storage() == Some(env::predecessor_account_id()),
+            "{}", ERR100_NOT_ALLOWED
+        );
+        audit_privileged_action("pol_cancel_withdraw");
+        let mut pol_positions = read_pol_positions_from_storage();
+        let mut position = pol_positions.get(&pool_id).expect("Pool not designated");
+        position.pending_withdrawal.take().expect("No pending POL withdrawal");
+        pol_positions.insert(&pool_id, &position);
+        write_pol_positions_to_storage(pol_positions);
+    }
+
+    /// Remove the liquidity of an unlocked withdrawal into the treasury's inner account.
+    #[payable]
+    pub fn pol_execute_withdraw(&mut self, pool_id: u64, min_amounts: Vec<U128>) -> Vec<U128> {
+        assert_one_yocto();
+        let treasury_id = self.assert_pol_treasury();
+        audit_privileged_action("pol_execute_withdraw");
+        let mut pol_positions = read_pol_positions_from_storage();
+        let mut position = pol_positions.get(&pool_id).expect("Pool not designated");
+        assert_eq!(position.holder_id.as_ref(), Some(&treasury_id), "POL held by another treasury");
+        let withdrawal = position.pending_withdrawal.take().expect("No pending POL withdrawal");
+        assert!(env::block_timestamp() >= withdrawal.unlock_time, "POL withdrawal still timelocked");
+        position.shares -= withdrawal.shares;
+        // Unlock the shares before removing them.
+        pol_positions.insert(&pool_id, &position);
+        write_pol_positions_to_storage(pol_positions);
+        let amounts = self.remove_liquidity(pool_id, U128(withdrawal.shares), min_amounts);
+        let mut pol_positions = read_pol_positions_from_storage();
+        for (i, amount) in amounts.iter().enumerate() {
+            position.withdrawn[i] += amount.0;
+        }
+        pol_positions.insert(&pool_id, &position);
+        write_pol_positions_to_storage(pol_positions);
+        event::Event::PolWithdraw {
+            account_id: &treasury_id,
+            pool_id,
+            shares: U128(withdrawal.shares),
+            amounts: amounts.clone(),
+        }.emit();
+        amounts
+    }
+
+    pub fn get_pol_treasury(&self) -> Option<AccountId> {
+        read_pol_treasury_from_storage()
+    }
+
+    /// Returns the protocol-owned liquidity of a designated pool with its cost basis and PnL.
+    pub fn get_pol_position(&self, pool_id: u64) -> Option<PolPositionInfo> {
+        read_pol_positions_from_storage().get(&pool_id).map(|position| {
+            let pool = self.internal_get_pool(pool_id);
+            let total_shares = pool.share_total_balance();
+            let current_amounts: Vec<Balance> = pool.get_amounts().into_iter().map(|amount| {
+                if total_shares == 0 { 0 } else { u128_ratio(amount, position.shares, total_shares) }
+            }).collect();
+            let pnl = current_amounts.iter().zip(position.withdrawn.iter()).zip(position.cost_basis.iter())
+                .map(|((current, withdrawn), cost)| signed_diff(current + withdrawn, *cost))
+                .collect();
+            PolPositionInfo {
+                holder_id: position.holder_id,
+                shares: position.shares.into(),
+                cost_basis: position.cost_basis.into_iter().map(|amount| amount.into()).collect(),
+                withdrawn: position.withdrawn.into_iter().map(|amount| amount.into()).collect(),
+                current_amounts: current_amounts.into_iter().map(|amount| amount.into()).collect(),
+                pnl,
+                pending_withdrawal: position.pending_withdrawal,
+            }
+        })
+    }
+}
@@ -1,11 +1,15 @@
 use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
+use near_sdk::collections::LookupMap;
 use near_sdk::{AccountId, Balance};
 
 use crate::admin_fee::AdminFees;
+use crate::errors::{ERR13_LP_NOT_REGISTERED, ERR34_INSUFFICIENT_LP_SHARES, ERR63_MISSING_TOKEN, ERR64_TOKENS_COUNT_ILLEGAL};
 use crate::degen_swap::DegenSwapPool;
+use crate::range_pool::RangePool;
 use crate::simple_pool::SimplePool;
 use crate::stable_swap::StableSwapPool;
 use crate::rated_swap::RatedSwapPool;
+use crate::storage_inspector::update_collection_len;
 use crate::utils::SwapVolume;
 
 /// Generic Pool, providing wrapper around different implementations of swap pools.
@@ -16,6 +20,7 @@ pub enum Pool {
     StableSwapPool(StableSwapPool),
     RatedSwapPool(RatedSwapPool),
     DegenSwapPool(DegenSwapPool),
+    RangePool(RangePool),
 }
 
 impl Pool {
@@ -26,6 +31,7 @@ impl Pool {
             Pool::StableSwapPool(_) => "STABLE_SWAP".to_string(),
             Pool::RatedSwapPool(_) => "RATED_SWAP".to_string(),
             Pool::DegenSwapPool(_) => "DEGEN_SWAP".to_string(),
+            Pool::RangePool(_) => "RANGE_POOL".to_string(),
         }
     }
 
@@ -36,6 +42,20 @@ impl Pool {
             Pool::StableSwapPool(pool) => pool.tokens(),
             Pool::RatedSwapPool(pool) => pool.tokens(),
             Pool::DegenSwapPool(pool) => pool.tokens(),
+            Pool::RangePool(pool) => pool.pool.tokens(),
+        }
+    }
+
+    /// Replaces the token reserves, only meant for predictions on a hypothetical pool state.
+    pub fn override_amounts(&mut self, amounts: &[Balance]) {
+        assert_eq!(amounts.len(), self.tokens().len(), "{}", ERR64_TOKENS_COUNT_ILLEGAL);
+        let amounts = amounts.to_vec();
+        match self {
+            Pool::SimplePool(pool) => pool.amounts = amounts,
+            Pool::StableSwapPool(pool) => pool.c_amounts = pool.amounts_to_c_amounts(&amounts),
+            Pool::RatedSwapPool(pool) => pool.c_amounts = pool.amounts_to_c_amounts(&amounts),
+            Pool::DegenSwapPool(pool) => pool.c_amounts = pool.amounts_to_c_amounts(&amounts),
+            Pool::RangePool(pool) => pool.pool.amounts = amounts,
         }
     }
 
@@ -45,6 +65,7 @@ impl Pool {
             Pool::StableSwapPool(pool) => pool.modify_total_fee(total_fee),
             Pool::RatedSwapPool(pool) => pool.modify_total_fee(total_fee),
             Pool::DegenSwapPool(pool) => pool.modify_total_fee(total_fee),
+            Pool::RangePool(pool) => pool.pool.modify_total_fee(total_fee),
         }
     }
 
@@ -61,6 +82,7 @@ impl Pool {
             Pool::StableSwapPool(_) => unimplemented!(),
             Pool::RatedSwapPool(_) => unimplemented!(),
             Pool::DegenSwapPool(_) => unimplemented!(),
+            Pool::RangePool(pool) => pool.pool.add_liquidity(sender_id, amounts, is_view),
         }
     }
 
@@ -77,6 +99,7 @@ impl Pool {
             Pool::StableSwapPool(pool) => pool.add_liquidity(sender_id, amounts, min_shares, &admin_fee, is_view),
             Pool::RatedSwapPool(pool) => pool.add_liquidity(sender_id, amounts, min_shares, &admin_fee, is_view),
             Pool::DegenSwapPool(pool) => pool.add_liquidity(sender_id, amounts, min_shares, &admin_fee, is_view),
+            Pool::RangePool(_) => unimplemented!(),
         }
     }
 
@@ -99,6 +122,7 @@ impl Pool {
             Pool::DegenSwapPool(pool) => {
                 pool.remove_liquidity_by_shares(sender_id, shares, min_amounts, is_view)
             }
+            Pool::RangePool(pool) => pool.pool.remove_liquidity(sender_id, shares, min_amounts, is_view),
         }
     }
 
@@ -122,6 +146,7 @@ impl Pool {
             Pool::DegenSwapPool(pool) => {
                 pool.remove_liquidity_by_tokens(sender_id, amounts, max_burn_shares, &admin_fee, is_view)
             }
+            Pool::RangePool(_) => unimplemented!(),
         }
     }
 
@@ -132,6 +157,7 @@ impl Pool {
             Pool::StableSwapPool(_) => 18,
             Pool::RatedSwapPool(_) => 24,
             Pool::DegenSwapPool(_) => 24,
+            Pool::RangePool(_) => 24,
         }
     }
 
@@ -142,6 +168,29 @@ impl Pool {
             Pool::StableSwapPool(pool) => pool.get_fee(),
             Pool::RatedSwapPool(pool) => pool.get_fee(),
             Pool::DegenSwapPool(pool) => pool.get_fee(),
+            Pool::RangePool(pool) => pool.pool.get_fee(),
+        }
+    }
+
+    /// Returns the token reserves of the pool.
+    pub fn get_amounts(&self) -> Vec<Balance> {
+        match self {
+            Pool::SimplePool(pool) => pool.amounts.clone(),
+            Pool::StableSwapPool(pool) => pool.get_amounts(),
+            Pool::RatedSwapPool(pool) => pool.get_amounts(),
+            Pool::DegenSwapPool(pool) => pool.get_amounts(),
+            Pool::RangePool(pool) => pool.pool.amounts.clone(),
+        }
+    }
+
+    /// Returns the token decimals of the pool, None for simple pools which don't record them.
+    pub fn get_token_decimals(&self) -> Option<Vec<u8>> {
+        match self {
+            Pool::SimplePool(_) => None,
+            Pool::StableSwapPool(pool) => Some(pool.token_decimals.clone()),
+            Pool::RatedSwapPool(pool) => Some(pool.token_decimals.clone()),
+            Pool::DegenSwapPool(pool) => Some(pool.token_decimals.clone()),
+            Pool::RangePool(_) => None,
         }
     }
 
@@ -152,16 +201,38 @@ impl Pool {
             Pool::StableSwapPool(pool) => pool.get_volumes(),
             Pool::RatedSwapPool(pool) => pool.get_volumes(),
             Pool::DegenSwapPool(pool) => pool.get_volumes(),
+            Pool::RangePool(pool) => pool.pool.get_volumes(),
         }
     }
 
+    /// Takes a swap of `amount_in` token_in for `amount_out` token_out back out of the volume statistics.
+    pub fn net_volumes(&mut self, token_in: &AccountId, amount_in: Balance, token_out: &AccountId, amount_out: Balance) {
+        // Simple and range pools book both sides of a swap under the input token.
+        let (tokens, volumes, by_input) = match self {
+            Pool::SimplePool(pool) => (&pool.token_account_ids, &mut pool.volumes, true),
+            Pool::StableSwapPool(pool) => (&pool.token_account_ids, &mut pool.volumes, false),
+            Pool::RatedSwapPool(pool) => (&pool.token_account_ids, &mut pool.volumes, false),
+            Pool::DegenSwapPool(pool) => (&pool.token_account_ids, &mut pool.volumes, false),
+            Pool::RangePool(pool) => (&pool.pool.token_account_ids, &mut pool.pool.volumes, true),
+        };
+        let in_idx = tokens.iter().position(|id| id == token_in).expect(ERR63_MISSING_TOKEN);
+        let out_idx = if by_input {
+            in_idx
+        } else {
+            tokens.iter().position(|id| id == token_out).expect(ERR63_MISSING_TOKEN)
+        };
+        volumes[in_idx].input.0 = volumes[in_idx].input.0.saturating_sub(amount_in);
+        volumes[out_idx].output.0 = volumes[out_idx].output.0.saturating_sub(amount_out);
+    }
+
     /// Returns given pool's share price in precision 1e8.
     pub fn get_share_price(&self) -> u128 {
         match self {
-            Pool::SimplePool(_) => unimplemented!(),
+            Pool::SimplePool(pool) => pool.get_share_price(),
             Pool::StableSwapPool(pool) => pool.get_share_price(),
             Pool::RatedSwapPool(pool) => pool.get_share_price(),
             Pool::DegenSwapPool(pool) => pool.get_share_price(),
+            Pool::RangePool(pool) => pool.get_share_price(),
         }
     }
 
@@ -174,6 +245,17 @@ impl Pool {
                 pool.assert_degens_valid();
                 pool.get_tvl()
             },
+            Pool::RangePool(_) => unimplemented!(),
+        }
+    }
+
+    /// Quote of a stable, rated or degen pool swap, None where the swap would fail.
+    pub fn try_quote_swap(&self, token_in: &AccountId, amount_in: Balance, token_out: &AccountId, admin_fee: &AdminFees) -> Option<Balance> {
+        match self {
+            Pool::StableSwapPool(pool) => pool.try_quote_swap(token_in, amount_in, token_out, admin_fee),
+            Pool::RatedSwapPool(pool) => pool.try_quote_swap(token_in, amount_in, token_out, admin_fee),
+            Pool::DegenSwapPool(pool) => pool.try_quote_swap(token_in, amount_in, token_out, admin_fee),
+            Pool::SimplePool(_) | Pool::RangePool(_) => None,
         }
     }
 
@@ -200,6 +282,9 @@ impl Pool {
             Pool::DegenSwapPool(pool) => {
                 pool.swap(token_in, amount_in, token_out, min_amount_out, &admin_fee, is_view)
             }
+            Pool::RangePool(pool) => {
+                pool.swap(token_in, amount_in, token_out, min_amount_out, &admin_fee, is_view)
+            }
         }
     }
 
@@ -226,6 +311,9 @@ impl Pool {
             Pool::DegenSwapPool(_) => {
                 unimplemented!()
             }
+            Pool::RangePool(pool) => {
+                pool.swap_by_output(token_in, amount_out, token_out, max_amount_in, &admin_fee, is_view)
+            }
         }
     }
     
@@ -235,6 +323,7 @@ impl Pool {
             Pool::StableSwapPool(pool) => pool.share_total_balance(),
             Pool::RatedSwapPool(pool) => pool.share_total_balance(),
             Pool::DegenSwapPool(pool) => pool.share_total_balance(),
+            Pool::RangePool(pool) => pool.pool.share_total_balance(),
         }
     }
 
@@ -244,6 +333,7 @@ impl Pool {
             Pool::StableSwapPool(pool) => pool.share_balance_of(account_id),
             Pool::RatedSwapPool(pool) => pool.share_balance_of(account_id),
             Pool::DegenSwapPool(pool) => pool.share_balance_of(account_id),
+            Pool::RangePool(pool) => pool.pool.share_balance_of(account_id),
         }
     }
 
@@ -253,9 +343,24 @@ impl Pool {
             Pool::StableSwapPool(pool) => pool.share_transfer(sender_id, receiver_id, amount),
             Pool::RatedSwapPool(pool) => pool.share_transfer(sender_id, receiver_id, amount),
             Pool::DegenSwapPool(pool) => pool.share_transfer(sender_id, receiver_id, amount),
+            Pool::RangePool(pool) => pool.pool.share_transfer(sender_id, receiver_id, amount),
         }
     }
 
+    /// Burns `shares` of the account, raising the share price of the other LPs. Stable kind pools only.
+    pub fn share_burn(&mut self, account_id: &AccountId, shares: Balance) {
+        let (pool_shares, shares_total_supply) = match self {
+            Pool::StableSwapPool(pool) => (&mut pool.shares, &mut pool.shares_total_supply),
+            Pool::RatedSwapPool(pool) => (&mut pool.shares, &mut pool.shares_total_supply),
+            Pool::DegenSwapPool(pool) => (&mut pool.shares, &mut pool.shares_total_supply),
+            Pool::SimplePool(_) | Pool::RangePool(_) => unimplemented!(),
+        };
+        let prev_shares = pool_shares.get(account_id).expect(ERR13_LP_NOT_REGISTERED);
+        assert!(prev_shares >= shares, "{}", ERR34_INSUFFICIENT_LP_SHARES);
+        pool_shares.insert(account_id, &(prev_shares - shares));
+        *shares_total_supply -= shares;
+    }
+
     /// See if the given account has been registered as a LP
     pub fn share_has_registered(&self, account_id: &AccountId) -> bool {
         match self {
@@ -263,6 +368,7 @@ impl Pool {
             Pool::StableSwapPool(pool) => pool.share_has_registered(account_id),
             Pool::RatedSwapPool(pool) => pool.share_has_registered(account_id),
             Pool::DegenSwapPool(pool) => pool.share_has_registered(account_id),
+            Pool::RangePool(pool) => pool.pool.share_has_registered(account_id),
         }
     }
 
@@ -272,7 +378,9 @@ impl Pool {
             Pool::StableSwapPool(pool) => pool.share_register(account_id),
             Pool::RatedSwapPool(pool) => pool.share_register(account_id),
             Pool::DegenSwapPool(pool) => pool.share_register(account_id),
+            Pool::RangePool(pool) => pool.pool.share_register(account_id),
         }
+        update_collection_len(self.shares(), true);
     }
 
     pub fn share_unregister(&mut self, account_id: &AccountId) {
@@ -281,6 +389,19 @@ impl Pool {
             Pool::StableSwapPool(pool) => pool.share_unregister(account_id),
             Pool::RatedSwapPool(pool) => pool.share_unregister(account_id),
             Pool::DegenSwapPool(pool) => pool.share_unregister(account_id),
+            Pool::RangePool(pool) => pool.pool.share_unregister(account_id),
+        }
+        update_collection_len(self.shares(), false);
+    }
+
+    /// Share balances of the pool's LPs.
+    pub(crate) fn shares(&self) -> &LookupMap<AccountId, Balance> {
+        match self {
+            Pool::SimplePool(pool) => &pool.shares,
+            Pool::StableSwapPool(pool) => &pool.shares,
+            Pool::RatedSwapPool(pool) => &pool.shares,
+            Pool::DegenSwapPool(pool) => &pool.shares,
+            Pool::RangePool(pool) => &pool.pool.shares,
         }
     }
 
@@ -295,6 +416,7 @@ impl Pool {
             Pool::StableSwapPool(_) => unimplemented!(),
             Pool::RatedSwapPool(pool) => pool.predict_add_rated_liquidity(amounts, rates, fees),
             Pool::DegenSwapPool(_) => unimplemented!(),
+            Pool::RangePool(_) => unimplemented!(),
         }
     }
 
@@ -309,6 +431,7 @@ impl Pool {
             Pool::StableSwapPool(_) => unimplemented!(),
             Pool::RatedSwapPool(_) => unimplemented!(),
             Pool::DegenSwapPool(pool) => pool.predict_add_degen_liquidity(amounts, degens, fees),
+            Pool::RangePool(_) => unimplemented!(),
         }
     }
 
@@ -323,6 +446,7 @@ impl Pool {
             Pool::StableSwapPool(_) => unimplemented!(),
             Pool::RatedSwapPool(pool) => pool.predict_remove_rated_liquidity_by_tokens(amounts, rates, fees),
             Pool::DegenSwapPool(_) => unimplemented!(),
+            Pool::RangePool(_) => unimplemented!(),
         }
     }
 
@@ -337,6 +461,7 @@ impl Pool {
             Pool::StableSwapPool(_) => unimplemented!(),
             Pool::RatedSwapPool(_) => unimplemented!(),
             Pool::DegenSwapPool(pool) => pool.predict_remove_degen_liquidity_by_tokens(amounts, degens, fees),
+            Pool::RangePool(_) => unimplemented!(),
         }
     }
 
@@ -353,6 +478,7 @@ impl Pool {
             Pool::StableSwapPool(_) => unimplemented!(),
             Pool::RatedSwapPool(pool) => pool.get_rated_return(token_in, amount_in, token_out, rates, fees),
             Pool::DegenSwapPool(_) => unimplemented!(),
+            Pool::RangePool(_) => unimplemented!(),
         }
     }
 
@@ -369,6 +495,7 @@ impl Pool {
             Pool::StableSwapPool(_) => unimplemented!(),
             Pool::RatedSwapPool(_) => unimplemented!(),
             Pool::DegenSwapPool(pool) => pool.get_degen_return(token_in, amount_in, token_out, degens, fees),
+            Pool::RangePool(_) => unimplemented!(),
         }
     }
 }
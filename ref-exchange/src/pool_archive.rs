@@ -0,0 +1,169 @@
+use crate::*;
+use near_sdk::collections::TreeMap;
+use near_sdk::json_types::U64;
+use std::ops::Bound;
+
+/// Archived pool ids, mapped to the block timestamp they were archived at.
+/// Pool ids are never reused or compacted, as LP token ids (":N") and farms refer to them.
+pub fn read_archived_pools_from_storage() -> UnorderedMap<u64, u64> {
+    if let Some(content) = env::storage_read(ARCHIVED_POOLS.as_bytes()) {
+        UnorderedMap::try_from_slice(&content).expect("deserialize archived pools failed.")
+    } else {
+        UnorderedMap::new(StorageKey::ArchivedPools)
+    }
+}
+
+pub fn write_archived_pools_to_storage(archived_pools: UnorderedMap<u64, u64>) {
+    env::storage_write(
+        ARCHIVED_POOLS.as_bytes(),
+        &archived_pools.try_to_vec().unwrap(),
+    );
+}
+
+/// Ids of pools that aren't archived, so listing them doesn't walk archived ones. Ordered by id,
+/// so pages stay stable while pools are archived. Pools created before the index existed are
+/// added by `backfill_active_pool_index`.
+pub fn read_active_pool_ids_from_storage() -> TreeMap<u64, ()> {
+    if let Some(content) = env::storage_read(ACTIVE_POOL_IDS.as_bytes()) {
+        TreeMap::try_from_slice(&content).expect("deserialize active pool ids failed.")
+    } else {
+        TreeMap::new(StorageKey::ActivePoolIds)
+    }
+}
+
+pub fn write_active_pool_ids_to_storage(active_pool_ids: TreeMap<u64, ()>) {
+    env::storage_write(
+        ACTIVE_POOL_IDS.as_bytes(),
+        &active_pool_ids.try_to_vec().unwrap(),
+    );
+}
+
+/// Pool ids below this have been considered for the active index.
+pub fn read_active_pool_index_len_from_storage() -> u64 {
+    env::storage_read(ACTIVE_POOL_INDEX_LEN.as_bytes())
+        .map(|content| u64::try_from_slice(&content).expect("deserialize active pool index len failed."))
+        .unwrap_or(0)
+}
+
+pub fn write_active_pool_index_len_to_storage(index_len: u64) {
+    env::storage_write(ACTIVE_POOL_INDEX_LEN.as_bytes(), &index_len.try_to_vec().unwrap());
+}
+
+impl Contract {
+    pub(crate) fn assert_pool_not_archived(&self, pool_id: u64) {
+        assert!(read_archived_pools_from_storage().get(&pool_id).is_none(), "Pool archived");
+    }
+
+    /// Adds the next pool to the active index, once the index has caught up with older pools.
+    pub(crate) fn internal_index_active_pool(&mut self, pool_id: u64) {
+        if read_active_pool_index_len_from_storage() != pool_id {
+            return;
+        }
+        if !self.is_pool_archived(pool_id) {
+            let mut active_pool_ids = read_active_pool_ids_from_storage();
+            active_pool_ids.insert(&pool_id, &());
+            write_active_pool_ids_to_storage(active_pool_ids);
+        }
+        write_active_pool_index_len_to_storage(pool_id + 1);
+    }
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Mark a pool as deprecated, the step before archiving it. Creators of simple and range
+    /// pools can ask for it with `request_pool_deprecation`.
+    #[payable]
+    pub fn deprecate_pool(&mut self, pool_id: u64) {
+        assert_one_yocto();
+        self.assert_owner();
+        audit_privileged_action("deprecate_pool");
+        assert!(pool_id < self.pools.len(), "{}", ERR85_NO_POOL);
+        self.assert_pool_not_archived(pool_id);
+        let mut deprecation_requests = read_pool_deprecation_requests_from_storage();
+        if deprecation_requests.get(&pool_id).is_none() {
+            deprecation_requests.insert(&pool_id, &env::block_timestamp());
+            write_pool_deprecation_requests_to_storage(deprecation_requests);
+        }
+    }
+
+    /// Moves a deprecated and fully drained pool into the archive.
+    /// Archived pools stay viewable but can no longer be swapped through or receive liquidity.
+    #[payable]
+    pub fn archive_pool(&mut self, pool_id: u64) {
+        assert_one_yocto();
+        self.assert_owner();
+        audit_privileged_action("archive_pool");
+        let pool = self.pools.get(pool_id).expect(ERR85_NO_POOL);
+        assert_eq!(pool.share_total_balance(), 0, "Pool not drained");
+        let mut deprecation_requests = read_pool_deprecation_requests_from_storage();
+        assert!(deprecation_requests.remove(&pool_id).is_some(), "Pool not deprecated");
+        write_pool_deprecation_requests_to_storage(deprecation_requests);
+        let mut archived_pools = read_archived_pools_from_storage();
+        assert!(archived_pools.insert(&pool_id, &env::block_timestamp()).is_none(), "Pool archived");
+        write_archived_pools_to_storage(archived_pools);
+        let mut active_pool_ids = read_active_pool_ids_from_storage();
+        if active_pool_ids.remove(&pool_id).is_some() {
+            write_active_pool_ids_to_storage(active_pool_ids);
+        }
+        self.unit_share_cumulative_infos.remove(&pool_id);
+        log!("Pool {} archived", pool_id);
+    }
+
+    pub fn is_pool_archived(&self, pool_id: u64) -> bool {
+        read_archived_pools_from_storage().get(&pool_id).is_some()
+    }
+
+    /// Returns archived pool ids with their archive timestamp.
+    pub fn get_archived_pools(&self, from_index: Option<u64>, limit: Option<u64>) -> HashMap<u64, U64> {
+        let archived_pools = read_archived_pools_from_storage();
+        let keys = archived_pools.keys_as_vector();
+        let from_index = from_index.unwrap_or(0);
+        let limit = limit.unwrap_or(keys.len());
+        (from_index..std::cmp::min(from_index + limit, keys.len()))
+            .map(|index| {
+                let pool_id = keys.get(index).unwrap();
+                (pool_id, U64(archived_pools.get(&pool_id).unwrap()))
+            })
+            .collect()
+    }
+
+    /// Add up to `limit` pools created before the active index existed, returns how many ids
+    /// are left to consider.
+    #[payable]
+    pub fn backfill_active_pool_index(&mut self, limit: u64) -> u64 {
+        assert_one_yocto();
+        assert!(self.is_owner_or_guardians(), "{}", ERR100_NOT_ALLOWED);
+        audit_privileged_action("backfill_active_pool_index");
+        let archived_pools = read_archived_pools_from_storage();
+        let mut active_pool_ids = read_active_pool_ids_from_storage();
+        let from_index = read_active_pool_index_len_from_storage();
+        let end = std::cmp::min(from_index + limit, self.pools.len());
+        for pool_id in from_index..end {
+            if archived_pools.get(&pool_id).is_none() {
+                active_pool_ids.insert(&pool_id, &());
+            }
+        }
+        write_active_pool_ids_to_storage(active_pool_ids);
+        write_active_pool_index_len_to_storage(end);
+        self.pools.len() - end
+    }
+
+    /// Returns up to `limit` non-archived pools with ids from `from_pool_id` up, the next page
+    /// starts after the highest id returned. Until the index is backfilled, pools it doesn't
+    /// cover yet follow the indexed ones.
+    pub fn get_active_pools(&self, from_pool_id: u64, limit: u64) -> HashMap<u64, PoolInfo> {
+        let active_pool_ids = read_active_pool_ids_from_storage();
+        let index_len = read_active_pool_index_len_from_storage();
+        let archived_pools = read_archived_pools_from_storage();
+        active_pool_ids
+            .range((Bound::Included(from_pool_id), Bound::Unbounded))
+            .map(|(pool_id, _)| pool_id)
+            .chain(
+                (std::cmp::max(from_pool_id, index_len)..self.pools.len())
+                    .filter(|pool_id| archived_pools.get(pool_id).is_none()),
+            )
+            .take(limit as usize)
+            .map(|pool_id| (pool_id, self.get_pool(pool_id)))
+            .collect()
+    }
+}
@@ -0,0 +1,150 @@
+use crate::*;
+use crate::utils::FEE_DIVISOR;
+use near_sdk::json_types::U64;
+
+/// Range a creator may set its pool's total fee in.
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone)]
+#[serde(crate = "near_sdk::serde")]
+#[cfg_attr(not(target_arch = "wasm32"), derive(Debug, PartialEq))]
+pub struct CreatorFeeBounds {
+    pub min_fee: u32,
+    pub max_fee: u32,
+}
+
+pub fn read_creator_fee_bounds_from_storage() -> Option<CreatorFeeBounds> {
+    env::storage_read(CREATOR_FEE_BOUNDS.as_bytes())
+        .map(|content| CreatorFeeBounds::try_from_slice(&content).expect("deserialize creator fee bounds failed."))
+}
+
+pub fn write_creator_fee_bounds_to_storage(creator_fee_bounds: Option<CreatorFeeBounds>) {
+    match creator_fee_bounds {
+        Some(creator_fee_bounds) => {
+            env::storage_write(CREATOR_FEE_BOUNDS.as_bytes(), &creator_fee_bounds.try_to_vec().unwrap());
+        }
+        None => {
+            env::storage_remove(CREATOR_FEE_BOUNDS.as_bytes());
+        }
+    }
+}
+
+/// Pools their creators asked to deprecate, mapped to the block timestamp of the request.
+pub fn read_pool_deprecation_requests_from_storage() -> UnorderedMap<u64, u64> {
+    if let Some(content) = env::storage_read(POOL_DEPRECATION_REQUESTS.as_bytes()) {
+        UnorderedMap::try_from_slice(&content).expect("deserialize pool deprecation requests failed.")
+    } else {
+        UnorderedMap::new(StorageKey::PoolDeprecationRequests)
+    }
+}
+
+pub fn write_pool_deprecation_requests_to_storage(requests: UnorderedMap<u64, u64>) {
+    env::storage_write(
+        POOL_DEPRECATION_REQUESTS.as_bytes(),
+        &requests.try_to_vec().unwrap(),
+    );
+}
+
+impl Contract {
+    /// Panics unless the pool is one anyone can create and the caller is its creator.
+    fn assert_permissionless_pool_creator(&self, pool_id: u64) {
+        let pool = self.pools.get(pool_id).expect(ERR85_NO_POOL);
+        assert!(matches!(pool, Pool::SimplePool(_) | Pool::RangePool(_)), "Not a permissionless pool");
+        assert_eq!(
+            Some(env::predecessor_account_id()),
+            self.internal_get_pool_creator(pool_id),
+            "{}", ERR100_NOT_ALLOWED
+        );
+    }
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Hand the creator rights of a simple or range pool to another account, callable by
+    /// its creator or the owner, e.g. for a project taking over a community-created pool.
+    /// Attached deposit covers any extra storage, the rest is refunded.
+    #[payable]
+    pub fn transfer_pool_creator(&mut self, pool_id: u64, new_creator: ValidAccountId) {
+        assert!(env::attached_deposit() > 0, "{}", ERR35_AT_LEAST_ONE_YOCTO);
+        let pool = self.pools.get(pool_id).expect(ERR85_NO_POOL);
+        assert!(matches!(pool, Pool::SimplePool(_) | Pool::RangePool(_)), "Not a permissionless pool");
+        self.assert_pool_creator_or_owner(pool_id);
+        let prev_storage = env::storage_usage();
+        let mut metadata = self.internal_get_pool_metadata(pool_id).unwrap_or(PoolMetadata {
+            creator: None,
+            name: None,
+            project_url_hash: None,
+            category: None,
+        });
+        log!("Creator of pool {} moved from {:?} to {}", pool_id, metadata.creator, new_creator);
+        metadata.creator = Some(new_creator.into());
+        self.internal_set_pool_metadata(pool_id, metadata);
+        self.internal_check_storage(prev_storage);
+    }
+
+    /// Set the total fee of a simple or range pool within the creator fee bounds, creator only.
+    #[payable]
+    pub fn modify_total_fee_by_creator(&mut self, pool_id: u64, total_fee: u32) {
+        assert_one_yocto();
+        self.assert_permissionless_pool_creator(pool_id);
+        let bounds = read_creator_fee_bounds_from_storage().expect("Creators can't set fees");
+        assert!(
+            total_fee >= bounds.min_fee && total_fee <= bounds.max_fee,
+            "{}", ERR62_FEE_ILLEGAL
+        );
+        self.internal_modify_total_fee(pool_id, total_fee);
+    }
+
+    /// Ask the owner to deprecate a simple or range pool, creator only.
+    /// Attached deposit covers the request, the rest is refunded.
+    #[payable]
+    pub fn request_pool_deprecation(&mut self, pool_id: u64) {
+        assert!(env::attached_deposit() > 0, "{}", ERR35_AT_LEAST_ONE_YOCTO);
+        self.assert_permissionless_pool_creator(pool_id);
+        self.assert_pool_not_archived(pool_id);
+        let prev_storage = env::storage_usage();
+        let mut requests = read_pool_deprecation_requests_from_storage();
+        assert!(requests.insert(&pool_id, &env::block_timestamp()).is_none(), "Deprecation already requested");
+        write_pool_deprecation_requests_to_storage(requests);
+        self.internal_check_storage(prev_storage);
+    }
+
+    /// Drop a deprecation request, callable by the pool's creator or the owner.
+    #[payable]
+    pub fn cancel_pool_deprecation(&mut self, pool_id: u64) {
+        assert_one_yocto();
+        self.assert_pool_creator_or_owner(pool_id);
+        let mut requests = read_pool_deprecation_requests_from_storage();
+        requests.remove(&pool_id).expect("No deprecation requested");
+        write_pool_deprecation_requests_to_storage(requests);
+    }
+
+    /// Let creators of simple and range pools set their pool's fee within the bounds,
+    /// None takes the right away.
+    #[payable]
+    pub fn set_creator_fee_bounds(&mut self, creator_fee_bounds: Option<CreatorFeeBounds>) {
+        assert_one_yocto();
+        self.assert_owner();
+        audit_privileged_action("set_creator_fee_bounds");
+        if let Some(bounds) = creator_fee_bounds.as_ref() {
+            assert!(bounds.min_fee <= bounds.max_fee && bounds.max_fee < FEE_DIVISOR, "{}", ERR62_FEE_ILLEGAL);
+        }
+        write_creator_fee_bounds_to_storage(creator_fee_bounds);
+    }
+
+    pub fn get_creator_fee_bounds(&self) -> Option<CreatorFeeBounds> {
+        read_creator_fee_bounds_from_storage()
+    }
+
+    /// Returns pools with a pending deprecation request and the request timestamp.
+    pub fn get_pool_deprecation_requests(&self, from_index: Option<u64>, limit: Option<u64>) -> HashMap<u64, U64> {
+        let requests = read_pool_deprecation_requests_from_storage();
+        let keys = requests.keys_as_vector();
+        let from_index = from_index.unwrap_or(0);
+        let limit = limit.unwrap_or(keys.len());
+        (from_index..std::cmp::min(from_index + limit, keys.len()))
+            .map(|index| {
+                let pool_id = keys.get(index).unwrap();
+                (pool_id, U64(requests.get(&pool_id).unwrap()))
+            })
+            .collect()
+    }
+}
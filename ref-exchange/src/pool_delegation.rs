@@ -0,0 +1,107 @@
+use crate::*;
+use crate::utils::FEE_DIVISOR;
+
+/// Limited admin rights of a pool the owner handed to a project's account.
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone)]
+#[serde(crate = "near_sdk::serde")]
+#[cfg_attr(not(target_arch = "wasm32"), derive(Debug))]
+pub struct PoolDelegation {
+    pub delegate_id: AccountId,
+    /// Range the delegate may set the pool's total fee in, None if it may not.
+    pub fee_bounds: Option<CreatorFeeBounds>,
+    /// Whether the delegate may set the pool's name, project url hash and category.
+    pub metadata: bool,
+    /// Highest TVL limit the delegate may give a degen pool, None if it may not.
+    pub max_tvl_limit: Option<U128>,
+}
+
+pub fn read_pool_delegations_from_storage() -> LookupMap<u64, PoolDelegation> {
+    if let Some(content) = env::storage_read(POOL_DELEGATIONS.as_bytes()) {
+        LookupMap::try_from_slice(&content).expect("deserialize pool delegations failed.")
+    } else {
+        LookupMap::new(StorageKey::PoolDelegations)
+    }
+}
+
+pub fn write_pool_delegations_to_storage(pool_delegations: LookupMap<u64, PoolDelegation>) {
+    env::storage_write(
+        POOL_DELEGATIONS.as_bytes(),
+        &pool_delegations.try_to_vec().unwrap(),
+    );
+}
+
+impl Contract {
+    /// Returns the delegation of the pool if the caller is its delegate.
+    pub(crate) fn internal_caller_pool_delegation(&self, pool_id: u64) -> Option<PoolDelegation> {
+        read_pool_delegations_from_storage()
+            .get(&pool_id)
+            .filter(|delegation| delegation.delegate_id == env::predecessor_account_id())
+    }
+
+    fn assert_caller_pool_delegation(&self, pool_id: u64) -> PoolDelegation {
+        self.internal_caller_pool_delegation(pool_id).expect(ERR100_NOT_ALLOWED)
+    }
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Delegate limited admin of a pool to a project's account, replacing any previous delegation.
+    #[payable]
+    pub fn set_pool_delegation(&mut self, pool_id: u64, delegation: PoolDelegation) {
+        assert_one_yocto();
+        self.assert_owner();
+        audit_privileged_action("set_pool_delegation");
+        assert!(pool_id < self.pools.len(), "{}", ERR85_NO_POOL);
+        if let Some(bounds) = delegation.fee_bounds.as_ref() {
+            assert!(bounds.min_fee <= bounds.max_fee && bounds.max_fee < FEE_DIVISOR, "{}", ERR62_FEE_ILLEGAL);
+        }
+        if delegation.max_tvl_limit.is_some() {
+            assert!(matches!(self.internal_get_pool(pool_id), Pool::DegenSwapPool(_)), "Deposit cap is only for degen pools");
+        }
+        let mut pool_delegations = read_pool_delegations_from_storage();
+        pool_delegations.insert(&pool_id, &delegation);
+        write_pool_delegations_to_storage(pool_delegations);
+        log!("Admin of pool {} delegated to {}", pool_id, delegation.delegate_id);
+    }
+
+    /// Take back the delegated admin of a pool, callable by the owner or guardians.
+    #[payable]
+    pub fn revoke_pool_delegation(&mut self, pool_id: u64) {
+        assert_one_yocto();
+        assert!(self.is_owner_or_guardians(), "{}", ERR100_NOT_ALLOWED);
+        audit_privileged_action("revoke_pool_delegation");
+        let mut pool_delegations = read_pool_delegations_from_storage();
+        let delegation = pool_delegations.remove(&pool_id).expect("No delegation for the pool");
+        write_pool_delegations_to_storage(pool_delegations);
+        log!("Admin of pool {} revoked from {}", pool_id, delegation.delegate_id);
+    }
+
+    /// Set the total fee of a pool within the delegated fee bounds, delegate only.
+    #[payable]
+    pub fn modify_total_fee_by_delegate(&mut self, pool_id: u64, total_fee: u32) {
+        assert_one_yocto();
+        let bounds = self.assert_caller_pool_delegation(pool_id).fee_bounds.expect("Fee is not delegated");
+        audit_privileged_action("modify_total_fee_by_delegate");
+        assert!(
+            total_fee >= bounds.min_fee && total_fee <= bounds.max_fee,
+            "{}", ERR62_FEE_ILLEGAL
+        );
+        self.internal_modify_total_fee(pool_id, total_fee);
+    }
+
+    /// Set the TVL limit of a degen pool up to the delegated maximum, delegate only.
+    #[payable]
+    pub fn set_degen_pool_limit_by_delegate(&mut self, pool_id: u64, degen_pool_limit_info: DegenPoolLimitInfo) {
+        assert_one_yocto();
+        let max_tvl_limit = self.assert_caller_pool_delegation(pool_id).max_tvl_limit.expect("Deposit cap is not delegated");
+        audit_privileged_action("set_degen_pool_limit_by_delegate");
+        assert!(degen_pool_limit_info.tvl_limit <= max_tvl_limit.0, "TVL limit above the delegated maximum");
+        let mut pool_limit = read_pool_limit_from_storage();
+        pool_limit.insert(&pool_id, &VPoolLimitInfo::DegenPoolLimit(degen_pool_limit_info.into()));
+        write_pool_limit_to_storage(pool_limit);
+    }
+
+    pub fn get_pool_delegation(&self, pool_id: u64) -> Option<PoolDelegation> {
+        read_pool_delegations_from_storage().get(&pool_id)
+    }
+}
@@ -0,0 +1,271 @@
+use crate::*;
+use crate::degen_swap::degen::{is_global_degen_price_valid, read_degens_from_storage};
+use crate::utils::{nano_to_sec, to_nano, u128_ratio, FEE_DIVISOR, U256};
+use near_sdk::Timestamp;
+
+/// Length of the window recent pool volume is counted over.
+pub const POOL_VOLUME_WINDOW_SEC: u32 = 24 * 3600;
+const YEAR_SEC: u64 = 365 * 24 * 3600;
+
+/// Swap volume of a pool in the current window and the one before it, per token in pool order.
+#[derive(BorshSerialize, BorshDeserialize)]
+pub struct PoolVolumeWindow {
+    pub start: Timestamp,
+    pub current: Vec<Balance>,
+    pub previous: Vec<Balance>,
+}
+
+impl PoolVolumeWindow {
+    fn new(token_count: usize) -> Self {
+        Self {
+            start: env::block_timestamp(),
+            current: vec![0; token_count],
+            previous: vec![0; token_count],
+        }
+    }
+
+    /// Moves the window forward to the current block.
+    fn roll(&mut self) {
+        let window = to_nano(POOL_VOLUME_WINDOW_SEC);
+        let elapsed = env::block_timestamp().saturating_sub(self.start);
+        if elapsed < window {
+            return;
+        }
+        let token_count = self.current.len();
+        self.previous = if elapsed < 2 * window {
+            std::mem::replace(&mut self.current, vec![0; token_count])
+        } else {
+            self.current = vec![0; token_count];
+            vec![0; token_count]
+        };
+        self.start = env::block_timestamp() - elapsed % window;
+    }
+
+    fn recent(&self) -> Vec<Balance> {
+        self.current.iter().zip(self.previous.iter()).map(|(current, previous)| current + previous).collect()
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+#[cfg_attr(not(target_arch = "wasm32"), derive(Debug))]
+pub struct PoolHealth {
+    pub pool_id: u64,
+    pub pool_kind: String,
+    /// Spread between the largest and smallest reserve, valued at rates or prices,
+    /// over their sum in bps. None for simple pools and degen pools without valid prices.
+    pub imbalance_bps: Option<u32>,
+    /// Age of the oldest rate or price among the pool tokens, None for simple and stable pools.
+    pub oracle_staleness_sec: Option<u32>,
+    /// TVL against the degen pool TVL limit in bps, None without a limit or valid prices.
+    pub tvl_utilization_bps: Option<u32>,
+    /// Volume swapped in and out over the current and previous POOL_VOLUME_WINDOW_SEC,
+    /// against the reserve, in bps per token.
+    pub recent_volume_bps: Vec<u32>,
+    /// Share price growth since the oldest LP checkpoint, annualized in bps.
+    pub fee_apr_bps: Option<u32>,
+    /// Pool tokens impersonating a canonical token, see `get_token_aliases`.
+    pub spoofed_tokens: Vec<AccountId>,
+}
+
+/// Why swaps in a degen pool would fail right now, if they would.
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+#[cfg_attr(not(target_arch = "wasm32"), derive(Debug))]
+pub struct DegenPoolSwappability {
+    pub swappable: bool,
+    /// Tokens whose price expired, swaps fail with E129 until it is updated.
+    pub expired_price_tokens: Vec<AccountId>,
+    /// Tokens whose price is outside their price band.
+    pub out_of_band_tokens: Vec<AccountId>,
+    /// Whether the TVL is above the pool's TVL limit, None while a price is expired.
+    /// Adding liquidity fails then, swaps don't.
+    pub tvl_exceeds_limit: Option<bool>,
+}
+
+pub fn read_pool_volume_windows_from_storage() -> LookupMap<u64, PoolVolumeWindow> {
+    if let Some(content) = env::storage_read(POOL_VOLUME_WINDOWS.as_bytes()) {
+        LookupMap::try_from_slice(&content).expect("deserialize pool volume windows failed.")
+    } else {
+        LookupMap::new(StorageKey::PoolVolumeWindows)
+    }
+}
+
+pub fn write_pool_volume_windows_to_storage(pool_volume_windows: LookupMap<u64, PoolVolumeWindow>) {
+    env::storage_write(
+        POOL_VOLUME_WINDOWS.as_bytes(),
+        &pool_volume_windows.try_to_vec().unwrap(),
+    );
+}
+
+/// Drops the pool's volume window once no swap falls in it anymore.
+pub(crate) fn prune_pool_volume_window(pool_id: u64) -> bool {
+    let mut pool_volume_windows = read_pool_volume_windows_from_storage();
+    let stale = pool_volume_windows.get(&pool_id).map(|mut volume_window| {
+        volume_window.roll();
+        volume_window.recent().iter().all(|volume| *volume == 0)
+    });
+    if stale != Some(true) {
+        return false;
+    }
+    pool_volume_windows.remove(&pool_id);
+    write_pool_volume_windows_to_storage(pool_volume_windows);
+    true
+}
+
+fn ratio_bps(numerator: Balance, denominator: Balance) -> u32 {
+    if denominator == 0 {
+        return 0;
+    }
+    std::cmp::min(
+        U256::from(numerator) * U256::from(FEE_DIVISOR) / U256::from(denominator),
+        U256::from(u32::MAX),
+    ).as_u32()
+}
+
+/// Comparable reserves of stable-like pools valued at their rates or prices.
+pub(crate) fn valued_reserves(pool: &Pool) -> Option<Vec<Balance>> {
+    match pool {
+        Pool::SimplePool(_) | Pool::RangePool(_) => None,
+        Pool::StableSwapPool(p) => Some(p.c_amounts.clone()),
+        Pool::RatedSwapPool(p) => Some(
+            p.c_amounts.iter().zip(p.get_rates())
+                .map(|(amount, rate)| u128_ratio(*amount, rate, rated_swap::PRECISION))
+                .collect()
+        ),
+        Pool::DegenSwapPool(p) => {
+            if !p.token_account_ids.iter().all(is_global_degen_price_valid) {
+                return None;
+            }
+            Some(
+                p.c_amounts.iter().zip(p.get_degens())
+                    .map(|(amount, degen)| u128_ratio(*amount, degen, degen_swap::PRECISION))
+                    .collect()
+            )
+        }
+    }
+}
+
+impl Contract {
+    /// Adds a swap to the pool's recent volume.
+    pub(crate) fn internal_record_pool_volume(
+        &mut self,
+        pool_id: u64,
+        pool: &Pool,
+        token_in: &AccountId,
+        amount_in: Balance,
+        token_out: &AccountId,
+        amount_out: Balance,
+    ) {
+        let mut pool_volume_windows = read_pool_volume_windows_from_storage();
+        let mut volume_window = pool_volume_windows
+            .get(&pool_id)
+            .unwrap_or_else(|| PoolVolumeWindow::new(pool.tokens().len()));
+        volume_window.roll();
+        let tokens = pool.tokens();
+        let in_idx = tokens.iter().position(|id| id == token_in).expect(ERR63_MISSING_TOKEN);
+        let out_idx = tokens.iter().position(|id| id == token_out).expect(ERR63_MISSING_TOKEN);
+        volume_window.current[in_idx] += amount_in;
+        volume_window.current[out_idx] += amount_out;
+        pool_volume_windows.insert(&pool_id, &volume_window);
+        write_pool_volume_windows_to_storage(pool_volume_windows);
+    }
+
+    fn internal_oracle_staleness_sec(&self, pool: &Pool) -> Option<u32> {
+        let degens = match pool {
+            Pool::SimplePool(_) | Pool::StableSwapPool(_) | Pool::RangePool(_) => return None,
+            Pool::RatedSwapPool(_) => HashMap::new(),
+            Pool::DegenSwapPool(_) => read_degens_from_storage(),
+        };
+        pool.tokens()
+            .iter()
+            .filter_map(|token_id| match global_get_rate(token_id) {
+                Some(rate) => Some(rate.last_update_ts()),
+                None => degens.get(token_id).map(|degen| degen.price_info().map(|price_info| price_info.degen_updated_at).unwrap_or(0)),
+            })
+            .min()
+            .map(|oldest| nano_to_sec(env::block_timestamp().saturating_sub(oldest)))
+    }
+
+    fn internal_fee_apr_bps(&self, pool_id: u64, pool: &Pool) -> Option<u32> {
+        let checkpoint = self.get_lp_checkpoints(pool_id).into_iter().next()?;
+        let elapsed_sec = nano_to_sec(env::block_timestamp().saturating_sub(checkpoint.timestamp)) as u64;
+        if elapsed_sec == 0 || checkpoint.share_price == 0 {
+            return None;
+        }
+        let growth = pool.get_share_price().saturating_sub(checkpoint.share_price);
+        Some(std::cmp::min(
+            U256::from(growth) * U256::from(FEE_DIVISOR) * U256::from(YEAR_SEC)
+                / (U256::from(checkpoint.share_price) * U256::from(elapsed_sec)),
+            U256::from(u32::MAX),
+        ).as_u32())
+    }
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Health indicators of the given pool in one call.
+    pub fn get_pool_health(&self, pool_id: u64) -> PoolHealth {
+        let pool = self.internal_get_pool(pool_id);
+        let imbalance_bps = valued_reserves(&pool).map(|reserves| {
+            let max = reserves.iter().max().cloned().unwrap_or(0);
+            let min = reserves.iter().min().cloned().unwrap_or(0);
+            ratio_bps(max - min, reserves.iter().sum())
+        });
+        let tvl_utilization_bps = match &pool {
+            Pool::DegenSwapPool(p) if p.token_account_ids.iter().all(is_global_degen_price_valid) => {
+                read_pool_limit_from_storage()
+                    .get(&pool_id)
+                    .map(|pool_limit| ratio_bps(p.get_tvl(), pool_limit.get_degen_pool_limit().tvl_limit))
+            }
+            _ => None,
+        };
+        let recent_volumes = read_pool_volume_windows_from_storage()
+            .get(&pool_id)
+            .map(|mut volume_window| {
+                volume_window.roll();
+                volume_window.recent()
+            })
+            .unwrap_or_else(|| vec![0; pool.tokens().len()]);
+        PoolHealth {
+            pool_id,
+            pool_kind: pool.kind(),
+            imbalance_bps,
+            oracle_staleness_sec: self.internal_oracle_staleness_sec(&pool),
+            tvl_utilization_bps,
+            recent_volume_bps: recent_volumes.into_iter().zip(pool.get_amounts())
+                .map(|(volume, reserve)| ratio_bps(volume, reserve))
+                .collect(),
+            fee_apr_bps: self.internal_fee_apr_bps(pool_id, &pool),
+            spoofed_tokens: spoofed_tokens(pool.tokens()),
+        }
+    }
+
+    /// Runs the degen price checks of a swap in a degen pool and the TVL limit check of adding
+    /// liquidity without panicking, so frontends can tell beforehand whether they would go through.
+    pub fn is_degen_pool_swappable(&self, pool_id: u64) -> DegenPoolSwappability {
+        let pool = match self.internal_get_pool(pool_id) {
+            Pool::DegenSwapPool(pool) => pool,
+            _ => env::panic(format!("Pool {} is not degen pool", pool_id).as_bytes()),
+        };
+        let expired_price_tokens: Vec<AccountId> = pool.token_account_ids.iter()
+            .filter(|token_id| !is_global_degen_price_valid(token_id))
+            .cloned()
+            .collect();
+        let out_of_band_tokens = degen_tokens_out_of_band(&pool.token_account_ids, &pool.get_degens());
+        let tvl_exceeds_limit = if expired_price_tokens.is_empty() {
+            Some(read_pool_limit_from_storage()
+                .get(&pool_id)
+                .map(|pool_limit| pool.get_tvl() > pool_limit.get_degen_pool_limit().tvl_limit)
+                .unwrap_or(false))
+        } else {
+            None
+        };
+        DegenPoolSwappability {
+            swappable: expired_price_tokens.is_empty() && out_of_band_tokens.is_empty(),
+            expired_price_tokens,
+            out_of_band_tokens,
+            tvl_exceeds_limit,
+        }
+    }
+}
@@ -0,0 +1,129 @@
+use crate::*;
+
+pub const MAX_POOL_NAME_LEN: usize = 64;
+pub const MAX_POOL_CATEGORY_LEN: usize = 32;
+/// Hex encoded sha256 of the project url.
+pub const PROJECT_URL_HASH_LEN: usize = 64;
+
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone)]
+#[serde(crate = "near_sdk::serde")]
+#[cfg_attr(not(target_arch = "wasm32"), derive(Debug))]
+pub struct PoolMetadata {
+    /// Account that created the pool, None for pools created before metadata existed.
+    pub creator: Option<AccountId>,
+    pub name: Option<String>,
+    pub project_url_hash: Option<String>,
+    pub category: Option<String>,
+}
+
+#[derive(BorshSerialize, BorshDeserialize)]
+pub enum VPoolMetadata {
+    Current(PoolMetadata),
+}
+
+impl From<VPoolMetadata> for PoolMetadata {
+    fn from(v: VPoolMetadata) -> Self {
+        match v {
+            VPoolMetadata::Current(c) => c,
+        }
+    }
+}
+
+impl From<PoolMetadata> for VPoolMetadata {
+    fn from(c: PoolMetadata) -> Self {
+        VPoolMetadata::Current(c)
+    }
+}
+
+pub fn read_pool_metadata_from_storage() -> LookupMap<u64, VPoolMetadata> {
+    if let Some(content) = env::storage_read(POOL_METADATA.as_bytes()) {
+        LookupMap::try_from_slice(&content).expect("deserialize pool metadata failed.")
+    } else {
+        LookupMap::new(StorageKey::PoolMetadata)
+    }
+}
+
+pub fn write_pool_metadata_to_storage(pool_metadata: LookupMap<u64, VPoolMetadata>) {
+    env::storage_write(
+        POOL_METADATA.as_bytes(),
+        &pool_metadata.try_to_vec().unwrap(),
+    );
+}
+
+impl Contract {
+    pub fn internal_get_pool_metadata(&self, pool_id: u64) -> Option<PoolMetadata> {
+        read_pool_metadata_from_storage().get(&pool_id).map(|v| v.into())
+    }
+
+    pub fn internal_set_pool_metadata(&mut self, pool_id: u64, metadata: PoolMetadata) {
+        let mut pool_metadata = read_pool_metadata_from_storage();
+        pool_metadata.insert(&pool_id, &metadata.into());
+        write_pool_metadata_to_storage(pool_metadata);
+    }
+
+    /// Returns the recorded creator of the given pool.
+    pub fn internal_get_pool_creator(&self, pool_id: u64) -> Option<AccountId> {
+        self.internal_get_pool_metadata(pool_id).and_then(|m| m.creator)
+    }
+
+    pub(crate) fn assert_pool_creator_or_owner(&self, pool_id: u64) {
+        let predecessor_id = env::predecessor_account_id();
+        assert!(
+            predecessor_id == self.owner_id || Some(predecessor_id) == self.internal_get_pool_creator(pool_id),
+            "{}", ERR100_NOT_ALLOWED
+        );
+    }
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Set the descriptive metadata of a pool, callable by its creator, its metadata delegate
+    /// or the owner. Attached deposit covers any extra storage, the rest is refunded.
+    #[payable]
+    pub fn set_pool_metadata(
+        &mut self,
+        pool_id: u64,
+        name: Option<String>,
+        project_url_hash: Option<String>,
+        category: Option<String>,
+    ) {
+        assert!(env::attached_deposit() > 0, "{}", ERR35_AT_LEAST_ONE_YOCTO);
+        assert!(pool_id < self.pools.len(), "{}", ERR85_NO_POOL);
+        if self.internal_caller_pool_delegation(pool_id).map(|delegation| delegation.metadata).unwrap_or(false) {
+            audit_privileged_action("set_pool_metadata_by_delegate");
+        } else {
+            self.assert_pool_creator_or_owner(pool_id);
+        }
+        if let Some(name) = name.as_ref() {
+            assert!(name.len() <= MAX_POOL_NAME_LEN, "Invalid name");
+        }
+        if let Some(project_url_hash) = project_url_hash.as_ref() {
+            assert!(
+                project_url_hash.len() == PROJECT_URL_HASH_LEN && project_url_hash.chars().all(|c| c.is_ascii_hexdigit()),
+                "Invalid project_url_hash"
+            );
+        }
+        if let Some(category) = category.as_ref() {
+            assert!(category.len() <= MAX_POOL_CATEGORY_LEN, "Invalid category");
+        }
+        let prev_storage = env::storage_usage();
+        let creator = self.internal_get_pool_creator(pool_id);
+        self.internal_set_pool_metadata(pool_id, PoolMetadata {
+            creator,
+            name,
+            project_url_hash,
+            category,
+        });
+        self.internal_check_storage(prev_storage);
+    }
+
+    pub fn get_pool_metadata(&self, pool_id: u64) -> Option<PoolMetadata> {
+        self.internal_get_pool_metadata(pool_id)
+    }
+
+    pub fn get_pool_metadata_batch(&self, pool_ids: Vec<u64>) -> HashMap<u64, PoolMetadata> {
+        pool_ids.into_iter()
+            .filter_map(|pool_id| self.internal_get_pool_metadata(pool_id).map(|m| (pool_id, m)))
+            .collect()
+    }
+}
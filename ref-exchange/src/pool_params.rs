@@ -0,0 +1,102 @@
+use crate::*;
+use crate::degen_swap::degen::{global_get_degen, DegenTrait};
+
+/// Rate or price a rated or degen pool applies to one of its tokens.
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+#[cfg_attr(not(target_arch = "wasm32"), derive(Debug))]
+pub struct PoolTokenRate {
+    pub rate_type: String,
+    pub rate: U128,
+    pub is_valid: bool,
+}
+
+/// Parameters needed to render a pool, see `get_pool_params_batch`.
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+#[cfg_attr(not(target_arch = "wasm32"), derive(Debug))]
+pub struct PoolParams {
+    pub pool_id: u64,
+    pub pool_kind: String,
+    pub token_account_ids: Vec<AccountId>,
+    pub total_fee: u32,
+    /// None for simple and range pools.
+    pub amp: Option<u64>,
+    /// Per pool token for rated and degen pools, None for other kinds.
+    pub rates: Option<Vec<PoolTokenRate>>,
+    /// Whether the whole contract is paused.
+    pub paused: bool,
+    pub archived: bool,
+    /// Deprecated or asked to be by its creator, pending archive.
+    pub deprecated: bool,
+    /// Swaps are rejected until the owner resets the pool's rate divergence breaker.
+    pub rate_guarded: bool,
+    /// Pool tokens on the frozenlist, nothing involving them can be traded.
+    pub frozen_tokens: Vec<AccountId>,
+    /// TVL limit of degen pools, if set.
+    pub tvl_limit: Option<U128>,
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Parameters of the given pools in one call, unknown pool ids are left out.
+    pub fn get_pool_params_batch(&self, pool_ids: Vec<u64>) -> Vec<PoolParams> {
+        let archived_pools = read_archived_pools_from_storage();
+        let deprecation_requests = read_pool_deprecation_requests_from_storage();
+        let rate_divergence_breakers = read_rate_divergence_breakers_from_storage();
+        let pool_limit = read_pool_limit_from_storage();
+        pool_ids
+            .into_iter()
+            .filter(|pool_id| *pool_id < self.pools.len())
+            .map(|pool_id| {
+                let pool = self.internal_get_pool(pool_id);
+                let tokens = pool.tokens();
+                let (amp, rates) = match &pool {
+                    Pool::SimplePool(_) | Pool::RangePool(_) => (None, None),
+                    Pool::StableSwapPool(p) => (Some(p.get_amp()), None),
+                    Pool::RatedSwapPool(p) => (
+                        Some(p.get_amp()),
+                        Some(tokens.iter().map(|token_id| match global_get_rate(token_id) {
+                            Some(rate) => PoolTokenRate {
+                                rate_type: rate.get_type(),
+                                rate: rate.get().into(),
+                                is_valid: rate.are_actual() && is_rate_fresh(token_id, rate.last_update_ts()),
+                            },
+                            // tokens without a registered rate count at par
+                            None => PoolTokenRate {
+                                rate_type: "NONE".to_string(),
+                                rate: U128(rated_swap::PRECISION),
+                                is_valid: true,
+                            },
+                        }).collect()),
+                    ),
+                    Pool::DegenSwapPool(p) => (
+                        Some(p.get_amp()),
+                        Some(tokens.iter().map(|token_id| {
+                            let degen = global_get_degen(token_id);
+                            PoolTokenRate {
+                                rate_type: degen.get_type(),
+                                rate: degen.get_price_info().stored_degen.into(),
+                                is_valid: degen.is_price_valid(),
+                            }
+                        }).collect()),
+                    ),
+                };
+                PoolParams {
+                    pool_id,
+                    pool_kind: pool.kind(),
+                    token_account_ids: tokens.to_vec(),
+                    total_fee: pool.get_fee(),
+                    amp,
+                    rates,
+                    paused: self.state != RunningState::Running,
+                    archived: archived_pools.get(&pool_id).is_some(),
+                    deprecated: deprecation_requests.get(&pool_id).is_some(),
+                    rate_guarded: rate_divergence_breakers.get(&pool_id).map(|breaker| breaker.guarded).unwrap_or(false),
+                    frozen_tokens: tokens.iter().filter(|token_id| self.frozen_tokens.contains(*token_id)).cloned().collect(),
+                    tvl_limit: pool_limit.get(&pool_id).map(|limit| limit.get_degen_pool_limit().tvl_limit.into()),
+                }
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,179 @@
+use crate::*;
+
+pub fn read_pool_registry_from_storage() -> LookupMap<String, Vec<u64>> {
+    if let Some(content) = env::storage_read(POOL_REGISTRY.as_bytes()) {
+        LookupMap::try_from_slice(&content).expect("deserialize pool registry failed.")
+    } else {
+        LookupMap::new(StorageKey::PoolRegistry)
+    }
+}
+
+pub fn write_pool_registry_to_storage(pool_registry: LookupMap<String, Vec<u64>>) {
+    env::storage_write(
+        POOL_REGISTRY.as_bytes(),
+        &pool_registry.try_to_vec().unwrap(),
+    );
+}
+
+pub fn read_max_pool_count_from_storage() -> Option<u64> {
+    if let Some(content) = env::storage_read(MAX_POOL_COUNT.as_bytes()) {
+        Option::<u64>::try_from_slice(&content).expect("deserialize max pool count failed.")
+    } else {
+        None
+    }
+}
+
+pub fn write_max_pool_count_to_storage(max_pool_count: Option<u64>) {
+    env::storage_write(
+        MAX_POOL_COUNT.as_bytes(),
+        &max_pool_count.try_to_vec().unwrap(),
+    );
+}
+
+pub fn read_duplicate_pools_allowed_from_storage() -> bool {
+    if let Some(content) = env::storage_read(DUPLICATE_POOLS_ALLOWED.as_bytes()) {
+        bool::try_from_slice(&content).expect("deserialize duplicate pools allowed failed.")
+    } else {
+        false
+    }
+}
+
+pub fn write_duplicate_pools_allowed_to_storage(allowed: bool) {
+    env::storage_write(
+        DUPLICATE_POOLS_ALLOWED.as_bytes(),
+        &allowed.try_to_vec().unwrap(),
+    );
+}
+
+/// Pools of the same kind and fee over the same token set share this key.
+/// A fee change moves the pool to the group of its new fee, see `internal_rekey_pool`.
+pub(crate) fn pool_registry_key(pool: &Pool) -> String {
+    let mut tokens = pool.tokens().to_vec();
+    tokens.sort();
+    format!("{}:{}:{}", pool.kind(), pool.get_fee(), tokens.join(","))
+}
+
+impl Contract {
+    /// Active pools with the same kind and token set as the given one.
+    fn internal_same_pools(&self, pool: &Pool) -> Vec<u64> {
+        read_pool_registry_from_storage()
+            .get(&pool_registry_key(pool))
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|pool_id| !self.is_pool_archived(*pool_id))
+            .collect()
+    }
+
+    /// Panics if the pool cap is reached or an active pool like this one exists.
+    /// Owner and guardians may still create duplicates on purpose, anyone may once the owner allows it.
+    pub(crate) fn assert_pool_creatable(&self, pool: &Pool) {
+        if let Some(max_pool_count) = read_max_pool_count_from_storage() {
+            assert!(self.pools.len() < max_pool_count, "Pool count cap reached");
+        }
+        let same_pools = self.internal_same_pools(pool);
+        if same_pools.is_empty() {
+            return;
+        }
+        if self.is_owner_or_guardians() || read_duplicate_pools_allowed_from_storage() {
+            log!("Duplicate of pools {:?}", same_pools);
+        } else {
+            env::panic(format!("Duplicate of pools {:?}", same_pools).as_bytes());
+        }
+    }
+
+    /// Registers a new pool under its kind, fee and tokens, and indexes it for listing and routing.
+    pub(crate) fn internal_register_pool(&mut self, pool_id: u64) {
+        let pool = self.internal_get_pool(pool_id);
+        self.internal_index_active_pool(pool_id);
+        self.internal_index_stable_pair_pool(pool_id, &pool);
+        let key = pool_registry_key(&pool);
+        let mut pool_registry = read_pool_registry_from_storage();
+        let mut pool_ids = pool_registry.get(&key).unwrap_or_default();
+        if !pool_ids.contains(&pool_id) {
+            pool_ids.push(pool_id);
+            pool_registry.insert(&key, &pool_ids);
+            write_pool_registry_to_storage(pool_registry);
+        }
+    }
+
+    /// Moves a registered pool from the group under `prev_key` to the one of its current kind, fee and tokens.
+    pub(crate) fn internal_rekey_pool(&mut self, pool_id: u64, prev_key: &str, pool: &Pool) {
+        let key = pool_registry_key(pool);
+        if key == prev_key {
+            return;
+        }
+        let mut pool_registry = read_pool_registry_from_storage();
+        let mut prev_pool_ids = pool_registry.get(&prev_key.to_string()).unwrap_or_default();
+        if !prev_pool_ids.contains(&pool_id) {
+            return;
+        }
+        prev_pool_ids.retain(|id| *id != pool_id);
+        if prev_pool_ids.is_empty() {
+            pool_registry.remove(&prev_key.to_string());
+        } else {
+            pool_registry.insert(&prev_key.to_string(), &prev_pool_ids);
+        }
+        let mut pool_ids = pool_registry.get(&key).unwrap_or_default();
+        pool_ids.push(pool_id);
+        pool_registry.insert(&key, &pool_ids);
+        write_pool_registry_to_storage(pool_registry);
+    }
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Set the most pools the exchange may hold, None for no cap.
+    #[payable]
+    pub fn set_max_pool_count(&mut self, max_pool_count: Option<u64>) {
+        assert_one_yocto();
+        self.assert_owner();
+        audit_privileged_action("set_max_pool_count");
+        write_max_pool_count_to_storage(max_pool_count);
+    }
+
+    /// Register pools created before the registry existed, so that they are deduplicated
+    /// and stable ones routed too.
+    #[payable]
+    pub fn register_existing_pools(&mut self, from_index: u64, limit: u64) {
+        assert_one_yocto();
+        assert!(self.is_owner_or_guardians(), "{}", ERR100_NOT_ALLOWED);
+        audit_privileged_action("register_existing_pools");
+        for pool_id in from_index..std::cmp::min(from_index + limit, self.pools.len()) {
+            self.internal_register_pool(pool_id);
+        }
+    }
+
+    /// Let anyone create pools of the same kind, fee and tokens as an active one.
+    #[payable]
+    pub fn set_duplicate_pools_allowed(&mut self, allowed: bool) {
+        assert_one_yocto();
+        self.assert_owner();
+        audit_privileged_action("set_duplicate_pools_allowed");
+        write_duplicate_pools_allowed_to_storage(allowed);
+    }
+
+    pub fn get_duplicate_pools_allowed(&self) -> bool {
+        read_duplicate_pools_allowed_from_storage()
+    }
+
+    pub fn get_max_pool_count(&self) -> Option<u64> {
+        read_max_pool_count_from_storage()
+    }
+
+    /// Returns the groups of active pools sharing kind, fee and token set, each listed once
+    /// under its lowest pool id within the given range.
+    pub fn find_duplicate_pools(&self, from_index: Option<u64>, limit: Option<u64>) -> Vec<Vec<u64>> {
+        let from_index = from_index.unwrap_or(0);
+        let limit = limit.unwrap_or(self.pools.len());
+        (from_index..std::cmp::min(from_index + limit, self.pools.len()))
+            .filter_map(|pool_id| {
+                let same_pools = self.internal_same_pools(&self.internal_get_pool(pool_id));
+                if same_pools.len() > 1 && same_pools.iter().min() == Some(&pool_id) {
+                    Some(same_pools)
+                } else {
+                    None
+                }
+            })
+            .collect()
+    }
+}
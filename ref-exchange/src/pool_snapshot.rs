@@ -0,0 +1,198 @@
+use crate::*;
+use crate::utils::SwapVolume;
+use near_sdk::json_types::U64;
+
+/// Default number of bytes returned by a single `get_pool_snapshot` call.
+pub const DEFAULT_SNAPSHOT_CHUNK_SIZE: u64 = 16 * 1024;
+
+/// Portable description of a pool's state.
+/// Reserves of stable-like pools are kept in comparable decimal (c_amounts).
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct PoolSnapshot {
+    pub pool_kind: String,
+    pub token_account_ids: Vec<AccountId>,
+    /// Empty for simple pools.
+    pub token_decimals: Vec<u8>,
+    pub amounts: Vec<U128>,
+    pub volumes: Vec<SwapVolume>,
+    pub total_fee: u32,
+    pub shares_total_supply: U128,
+    pub init_amp_factor: U128,
+    pub target_amp_factor: U128,
+    pub init_amp_time: U64,
+    pub stop_amp_time: U64,
+    /// Lower and upper price of range pools.
+    #[serde(default)]
+    pub price_range: Option<(U128, U128)>,
+}
+
+/// Whether the contract was initialized for a test network, where snapshots can be imported.
+/// Contracts initialized before the flag existed count as mainnet.
+pub fn read_test_network_from_storage() -> bool {
+    env::storage_read(TEST_NETWORK.as_bytes())
+        .map(|content| bool::try_from_slice(&content).expect("deserialize test network failed."))
+        .unwrap_or(false)
+}
+
+pub fn write_test_network_to_storage(test_network: bool) {
+    env::storage_write(TEST_NETWORK.as_bytes(), &test_network.try_to_vec().unwrap());
+}
+
+/// A slice of the JSON encoded `PoolSnapshot`.
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+#[cfg_attr(not(target_arch = "wasm32"), derive(Debug))]
+pub struct PoolSnapshotChunk {
+    pub total_len: U64,
+    pub data: String,
+}
+
+macro_rules! stable_like_snapshot {
+    ($kind: expr, $pool: expr) => {
+        PoolSnapshot {
+            pool_kind: $kind,
+            token_account_ids: $pool.token_account_ids.clone(),
+            token_decimals: $pool.token_decimals.clone(),
+            amounts: $pool.c_amounts.iter().map(|v| U128(*v)).collect(),
+            volumes: $pool.volumes.clone(),
+            total_fee: $pool.total_fee,
+            shares_total_supply: U128($pool.shares_total_supply),
+            init_amp_factor: U128($pool.init_amp_factor),
+            target_amp_factor: U128($pool.target_amp_factor),
+            init_amp_time: U64($pool.init_amp_time),
+            stop_amp_time: U64($pool.stop_amp_time),
+            price_range: None,
+        }
+    };
+}
+
+fn simple_snapshot(kind: String, pool: &SimplePool, price_range: Option<(U128, U128)>) -> PoolSnapshot {
+    PoolSnapshot {
+        pool_kind: kind,
+        token_account_ids: pool.token_account_ids.clone(),
+        token_decimals: vec![],
+        amounts: pool.amounts.iter().map(|v| U128(*v)).collect(),
+        volumes: pool.volumes.clone(),
+        total_fee: pool.total_fee,
+        shares_total_supply: U128(pool.shares_total_supply),
+        init_amp_factor: U128(0),
+        target_amp_factor: U128(0),
+        init_amp_time: U64(0),
+        stop_amp_time: U64(0),
+        price_range,
+    }
+}
+
+macro_rules! restore_stable_like {
+    ($pool_type: ident, $snapshot: expr, $id: expr) => {{
+        let mut pool = $pool_type::new(
+            $id,
+            $snapshot.valid_token_ids(),
+            $snapshot.token_decimals.clone(),
+            $snapshot.init_amp_factor.0,
+            $snapshot.total_fee,
+        );
+        pool.c_amounts = $snapshot.amounts.iter().map(|v| v.0).collect();
+        pool.volumes = $snapshot.volumes.clone();
+        pool.shares_total_supply = $snapshot.shares_total_supply.0;
+        pool.target_amp_factor = $snapshot.target_amp_factor.0;
+        pool.init_amp_time = $snapshot.init_amp_time.0;
+        pool.stop_amp_time = $snapshot.stop_amp_time.0;
+        pool
+    }};
+}
+
+impl From<&Pool> for PoolSnapshot {
+    fn from(pool: &Pool) -> Self {
+        match pool {
+            Pool::SimplePool(p) => simple_snapshot(pool.kind(), p, None),
+            Pool::StableSwapPool(p) => stable_like_snapshot!(pool.kind(), p),
+            Pool::RatedSwapPool(p) => stable_like_snapshot!(pool.kind(), p),
+            Pool::DegenSwapPool(p) => stable_like_snapshot!(pool.kind(), p),
+            Pool::RangePool(p) => simple_snapshot(pool.kind(), &p.pool, Some((U128(p.price_lower), U128(p.price_upper)))),
+        }
+    }
+}
+
+impl PoolSnapshot {
+    fn valid_token_ids(&self) -> Vec<ValidAccountId> {
+        self.token_account_ids.iter().map(|v| v.clone().try_into().expect("Invalid token id")).collect()
+    }
+
+    fn restore_simple(&self, mut pool: SimplePool) -> SimplePool {
+        pool.amounts = self.amounts.iter().map(|v| v.0).collect();
+        pool.volumes = self.volumes.clone();
+        pool.shares_total_supply = self.shares_total_supply.0;
+        pool
+    }
+
+    /// Rebuilds the pool under the given id, all shares are credited to `shares_holder`.
+    pub fn into_pool(self, id: u32, shares_holder: &AccountId) -> Pool {
+        let token_count = self.token_account_ids.len();
+        assert_eq!(self.amounts.len(), token_count, "Invalid amounts");
+        assert_eq!(self.volumes.len(), token_count, "Invalid volumes");
+        let mut pool = match self.pool_kind.as_str() {
+            "SIMPLE_POOL" => Pool::SimplePool(self.restore_simple(SimplePool::new(id, self.valid_token_ids(), self.total_fee))),
+            "RANGE_POOL" => {
+                let (price_lower, price_upper) = self.price_range.expect("Invalid price_range");
+                let mut pool = RangePool::new(id, self.valid_token_ids(), self.total_fee, price_lower.0, price_upper.0);
+                pool.pool = self.restore_simple(pool.pool);
+                Pool::RangePool(pool)
+            },
+            "STABLE_SWAP" => Pool::StableSwapPool(restore_stable_like!(StableSwapPool, self, id)),
+            "RATED_SWAP" => Pool::RatedSwapPool(restore_stable_like!(RatedSwapPool, self, id)),
+            "DEGEN_SWAP" => Pool::DegenSwapPool(restore_stable_like!(DegenSwapPool, self, id)),
+            _ => env::panic(b"Invalid pool_kind"),
+        };
+        let prev_shares = match &mut pool {
+            Pool::SimplePool(p) => p.shares.insert(shares_holder, &self.shares_total_supply.0),
+            Pool::StableSwapPool(p) => p.shares.insert(shares_holder, &self.shares_total_supply.0),
+            Pool::RatedSwapPool(p) => p.shares.insert(shares_holder, &self.shares_total_supply.0),
+            Pool::DegenSwapPool(p) => p.shares.insert(shares_holder, &self.shares_total_supply.0),
+            Pool::RangePool(p) => p.pool.shares.insert(shares_holder, &self.shares_total_supply.0),
+        };
+        if prev_shares.is_none() {
+            update_collection_len(pool.shares(), true);
+        }
+        pool
+    }
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Returns a chunk of the JSON encoded snapshot of the given pool.
+    /// Callers page through `from_index` until `total_len` bytes have been read.
+    pub fn get_pool_snapshot(&self, pool_id: u64, from_index: Option<u64>, limit: Option<u64>) -> PoolSnapshotChunk {
+        let pool = self.internal_get_pool(pool_id);
+        let json = near_sdk::serde_json::to_string(&PoolSnapshot::from(&pool)).unwrap();
+        let total_len = json.len() as u64;
+        let from_index = std::cmp::min(from_index.unwrap_or(0), total_len);
+        let end = std::cmp::min(from_index + limit.unwrap_or(DEFAULT_SNAPSHOT_CHUNK_SIZE), total_len);
+        PoolSnapshotChunk {
+            total_len: U64(total_len),
+            data: json[from_index as usize..end as usize].to_string(),
+        }
+    }
+
+    /// Creates a new pool from a snapshot exported by `get_pool_snapshot`, all shares go to the owner.
+    /// Only meant for test environments, so it refuses to run unless the contract was initialized
+    /// with `test_network`.
+    #[payable]
+    pub fn import_pool_snapshot(&mut self, snapshot: PoolSnapshot) -> u64 {
+        self.assert_owner();
+        audit_privileged_action("import_pool_snapshot");
+        assert!(read_test_network_from_storage(), "Snapshot import is disabled on mainnet");
+        let prev_storage = env::storage_usage();
+        let id = self.pools.len();
+        let mut pool = snapshot.into_pool(id as u32, &self.owner_id);
+        if !pool.share_has_registered(&env::current_account_id()) {
+            pool.share_register(&env::current_account_id());
+        }
+        self.pools.push(&pool);
+        self.internal_index_active_pool(id);
+        self.internal_check_storage(prev_storage);
+        log!("Imported pool {} from snapshot", id);
+        id
+    }
+}
@@ -0,0 +1,192 @@
+use crate::*;
+use crate::utils::{nano_to_sec, u128_dec_format, u64_dec_format, U256};
+use near_sdk::Timestamp;
+
+/// Token price kept for views, which can't query oracles themselves, and as the reference
+/// reward compounding checks the pool price against.
+/// `amount` of the token is worth amount * multiplier / 10^decimals, as with the price oracle.
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone)]
+#[serde(crate = "near_sdk::serde")]
+#[cfg_attr(not(target_arch = "wasm32"), derive(Debug))]
+pub struct CachedPrice {
+    #[serde(with = "u128_dec_format")]
+    pub multiplier: Balance,
+    pub decimals: u8,
+    #[serde(with = "u64_dec_format")]
+    pub updated_at: Timestamp,
+}
+
+impl CachedPrice {
+    pub fn value_of(&self, amount: Balance) -> U256 {
+        U256::from(amount) * U256::from(self.multiplier) / U256::from(10).pow(U256::from(self.decimals))
+    }
+
+    pub fn is_fresh(&self, max_age_sec: Option<u32>) -> bool {
+        max_age_sec
+            .map(|max_age_sec| nano_to_sec(env::block_timestamp().saturating_sub(self.updated_at)) <= max_age_sec)
+            .unwrap_or(true)
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct TokenPrice {
+    pub token_id: ValidAccountId,
+    pub multiplier: U128,
+    pub decimals: u8,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+#[cfg_attr(not(target_arch = "wasm32"), derive(Debug))]
+pub struct AccountValue {
+    /// Value of the priced deposits.
+    pub value: U128,
+    /// Deposited tokens without a cached price, or with one older than asked.
+    pub unpriced_tokens: Vec<AccountId>,
+}
+
+pub fn read_cached_prices_from_storage() -> UnorderedMap<AccountId, CachedPrice> {
+    if let Some(content) = env::storage_read(CACHED_PRICES.as_bytes()) {
+        UnorderedMap::try_from_slice(&content).expect("deserialize cached prices failed.")
+    } else {
+        UnorderedMap::new(StorageKey::CachedPrices)
+    }
+}
+
+pub fn write_cached_prices_to_storage(cached_prices: UnorderedMap<AccountId, CachedPrice>) {
+    env::storage_write(
+        CACHED_PRICES.as_bytes(),
+        &cached_prices.try_to_vec().unwrap(),
+    );
+}
+
+pub fn read_price_keepers_from_storage() -> UnorderedSet<AccountId> {
+    if let Some(content) = env::storage_read(PRICE_KEEPERS.as_bytes()) {
+        UnorderedSet::try_from_slice(&content).expect("deserialize price keepers failed.")
+    } else {
+        UnorderedSet::new(StorageKey::PriceKeepers)
+    }
+}
+
+pub fn write_price_keepers_to_storage(price_keepers: UnorderedSet<AccountId>) {
+    env::storage_write(
+        PRICE_KEEPERS.as_bytes(),
+        &price_keepers.try_to_vec().unwrap(),
+    );
+}
+
+/// Total value of the amounts, None if a token has no fresh enough cached price.
+fn cached_value(cached_prices: &UnorderedMap<AccountId, CachedPrice>, tokens: &[AccountId], amounts: &[Balance], max_age_sec: Option<u32>) -> Option<U256> {
+    let mut value = U256::zero();
+    for (token_id, amount) in tokens.iter().zip(amounts.iter()) {
+        let price = cached_prices.get(token_id).filter(|price| price.is_fresh(max_age_sec))?;
+        value += price.value_of(*amount);
+    }
+    Some(value)
+}
+
+#[near_bindgen]
+impl Contract {
+    #[payable]
+    pub fn extend_price_keepers(&mut self, account_ids: Vec<ValidAccountId>) {
+        assert_one_yocto();
+        self.assert_owner();
+        audit_privileged_action("extend_price_keepers");
+        let mut price_keepers = read_price_keepers_from_storage();
+        for account_id in account_ids {
+            price_keepers.insert(account_id.as_ref());
+        }
+        write_price_keepers_to_storage(price_keepers);
+    }
+
+    #[payable]
+    pub fn remove_price_keepers(&mut self, account_ids: Vec<ValidAccountId>) {
+        assert_one_yocto();
+        self.assert_owner();
+        audit_privileged_action("remove_price_keepers");
+        let mut price_keepers = read_price_keepers_from_storage();
+        for account_id in account_ids {
+            assert!(price_keepers.remove(account_id.as_ref()), "Account {} not a price keeper", account_id);
+        }
+        write_price_keepers_to_storage(price_keepers);
+    }
+
+    /// Push token prices into the view cache, by the owner or a price keeper.
+    /// Attached deposit covers newly cached tokens, the rest is refunded.
+    #[payable]
+    pub fn push_cached_prices(&mut self, prices: Vec<TokenPrice>) {
+        let prev_storage = env::storage_usage();
+        let caller_id = env::predecessor_account_id();
+        assert!(
+            caller_id == self.owner_id || read_price_keepers_from_storage().contains(&caller_id),
+            "{}", ERR100_NOT_ALLOWED
+        );
+        let mut cached_prices = read_cached_prices_from_storage();
+        for price in prices {
+            assert!(price.decimals <= 38, "Invalid decimals of {}", price.token_id);
+            cached_prices.insert(price.token_id.as_ref(), &CachedPrice {
+                multiplier: price.multiplier.0,
+                decimals: price.decimals,
+                updated_at: env::block_timestamp(),
+            });
+        }
+        write_cached_prices_to_storage(cached_prices);
+        self.internal_check_storage(prev_storage);
+    }
+
+    #[payable]
+    pub fn remove_cached_prices(&mut self, token_ids: Vec<ValidAccountId>) {
+        assert_one_yocto();
+        self.assert_owner();
+        audit_privileged_action("remove_cached_prices");
+        let mut cached_prices = read_cached_prices_from_storage();
+        for token_id in token_ids {
+            cached_prices.remove(token_id.as_ref()).expect("Price not cached");
+        }
+        write_cached_prices_to_storage(cached_prices);
+    }
+
+    pub fn get_price_keepers(&self) -> Vec<AccountId> {
+        read_price_keepers_from_storage().to_vec()
+    }
+
+    pub fn get_cached_price(&self, token_id: ValidAccountId) -> Option<CachedPrice> {
+        read_cached_prices_from_storage().get(token_id.as_ref())
+    }
+
+    pub fn get_cached_prices(&self, from_index: Option<u64>, limit: Option<u64>) -> HashMap<AccountId, CachedPrice> {
+        let cached_prices = read_cached_prices_from_storage();
+        let keys = cached_prices.keys_as_vector();
+        let from_index = from_index.unwrap_or(0);
+        let limit = limit.unwrap_or(keys.len());
+        (from_index..std::cmp::min(keys.len(), from_index + limit))
+            .map(|index| {
+                let key = keys.get(index).unwrap();
+                (key.clone(), cached_prices.get(&key).unwrap())
+            })
+            .collect()
+    }
+
+    /// Pool reserves valued at cached prices, None if a token has no price or, with
+    /// max_age_sec, one pushed longer ago.
+    pub fn get_pool_tvl_by_cached_prices(&self, pool_id: u64, max_age_sec: Option<u32>) -> Option<U128> {
+        let pool = self.internal_get_pool(pool_id);
+        cached_value(&read_cached_prices_from_storage(), pool.tokens(), &pool.get_amounts(), max_age_sec)
+            .map(|value| U128(value.as_u128()))
+    }
+
+    /// Inner account deposits valued at cached prices.
+    pub fn get_account_value_by_cached_prices(&self, account_id: ValidAccountId, max_age_sec: Option<u32>) -> AccountValue {
+        let cached_prices = read_cached_prices_from_storage();
+        let mut value = U256::zero();
+        let mut unpriced_tokens = vec![];
+        for (token_id, amount) in self.get_deposits(account_id) {
+            match cached_value(&cached_prices, std::slice::from_ref(&token_id), &[amount.0], max_age_sec) {
+                Some(token_value) => value += token_value,
+                None => unpriced_tokens.push(token_id),
+            }
+        }
+        AccountValue { value: U128(value.as_u128()), unpriced_tokens }
+    }
+}
@@ -0,0 +1,229 @@
+use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
+use near_sdk::json_types::ValidAccountId;
+use near_sdk::{env, AccountId, Balance};
+use ref_exchange_math::range;
+
+use crate::admin_fee::AdminFees;
+use crate::errors::*;
+use crate::simple_pool::SimplePool;
+use crate::utils::{FEE_DIVISOR, U256};
+
+/// Minimum width of the range, upper price over lower price, in bps above 1. Liquidity grows as
+/// the range narrows, past this it would overflow on reserves of ordinary size.
+pub const MIN_PRICE_RANGE_BPS: u32 = 100;
+
+/// Simple pool whose liquidity is concentrated between a lower and an upper price, set by the
+/// creator, for pairs trading around a slowly moving price like liquid staking tokens against
+/// their underlying. Within the range it trades as a constant product pool over virtual reserves,
+/// deeper than a simple pool of the same reserves; a swap pushing the price past a bound fails.
+/// Prices are raw token1 per raw token0, in precision 1e24.
+#[derive(BorshSerialize, BorshDeserialize)]
+pub struct RangePool {
+    /// Real reserves, shares and volumes, kept as by a simple pool.
+    pub pool: SimplePool,
+    pub price_lower: u128,
+    pub price_upper: u128,
+}
+
+impl RangePool {
+    pub fn new(
+        id: u32,
+        token_account_ids: Vec<ValidAccountId>,
+        total_fee: u32,
+        price_lower: u128,
+        price_upper: u128,
+    ) -> Self {
+        assert!(
+            price_lower > 0
+                && U256::from(price_upper) * U256::from(FEE_DIVISOR)
+                    >= U256::from(price_lower) * U256::from(FEE_DIVISOR + MIN_PRICE_RANGE_BPS),
+            "{}", ERR93_INVALID_PRICE_RANGE
+        );
+        Self {
+            pool: SimplePool::new(id, token_account_ids, total_fee),
+            price_lower,
+            price_upper,
+        }
+    }
+
+    fn sqrt_prices(&self) -> (u128, u128) {
+        (range::sqrt_price(self.price_lower), range::sqrt_price(self.price_upper))
+    }
+
+    /// Liquidity of the real reserves within the range.
+    pub fn get_liquidity(&self) -> Balance {
+        let (sqrt_lower, sqrt_upper) = self.sqrt_prices();
+        range::get_liquidity(&self.pool.amounts, sqrt_lower, sqrt_upper)
+    }
+
+    /// Current price of token1 per token0, in precision 1e24.
+    pub fn get_price(&self) -> u128 {
+        let (sqrt_lower, sqrt_upper) = self.sqrt_prices();
+        let virtuals = range::virtual_amounts(&self.pool.amounts, self.get_liquidity(), sqrt_lower, sqrt_upper);
+        (U256::from(virtuals[1]) * U256::from(ref_exchange_math::PRECISION))
+            .checked_div(virtuals[0].into())
+            .unwrap_or_else(|| self.price_lower.into())
+            .as_u128()
+    }
+
+    /// Get per lp token liquidity, with 1e8 precision.
+    pub fn get_share_price(&self) -> u128 {
+        (U256::from(self.get_liquidity()) * U256::from(100000000))
+            .checked_div(self.pool.shares_total_supply.into())
+            .unwrap_or(100000000.into())
+            .as_u128()
+    }
+
+    fn assert_swappable(&self, in_idx: usize, out_idx: usize, amount: Balance) {
+        assert!(
+            self.pool.amounts[in_idx] > 0
+                && self.pool.amounts[out_idx] > 0
+                && in_idx != out_idx
+                && amount > 0,
+            "{}", ERR76_INVALID_PARAMS
+        );
+    }
+
+    /// Swap `amount_in` of `token_in` into `token_out` and return how much was received.
+    pub fn swap(
+        &mut self,
+        token_in: &AccountId,
+        amount_in: Balance,
+        token_out: &AccountId,
+        min_amount_out: Balance,
+        admin_fee: &AdminFees,
+        is_view: bool
+    ) -> Balance {
+        assert_ne!(token_in, token_out, "{}", ERR73_SAME_TOKEN);
+        let in_idx = self.pool.token_index(token_in);
+        let out_idx = self.pool.token_index(token_out);
+        self.assert_swappable(in_idx, out_idx, amount_in);
+        let (sqrt_lower, sqrt_upper) = self.sqrt_prices();
+        let amount_out = range::get_amount_out(&self.pool.amounts, in_idx, amount_in, sqrt_lower, sqrt_upper, self.pool.total_fee)
+            .expect(ERR94_OUT_OF_PRICE_RANGE);
+        assert!(amount_out >= min_amount_out, "{}", ERR68_SLIPPAGE);
+        if !is_view {
+            env::log(
+                format!(
+                    "Swapped {} {} for {} {}",
+                    amount_in, token_in, amount_out, token_out
+                )
+                .as_bytes(),
+            );
+        }
+        self.update_pool_and_distribute_fee(in_idx, amount_in, out_idx, amount_out, admin_fee, is_view);
+        amount_out
+    }
+
+    /// Swap `token_in` to receive `amount_out` of `token_out` and return the amount of `token_in` spent.
+    pub fn swap_by_output(
+        &mut self,
+        token_in: &AccountId,
+        amount_out: Balance,
+        token_out: &AccountId,
+        max_amount_in: Option<u128>,
+        admin_fee: &AdminFees,
+        is_view: bool
+    ) -> Balance {
+        assert_ne!(token_in, token_out, "{}", ERR73_SAME_TOKEN);
+        let in_idx = self.pool.token_index(token_in);
+        let out_idx = self.pool.token_index(token_out);
+        self.assert_swappable(in_idx, out_idx, amount_out);
+        let (sqrt_lower, sqrt_upper) = self.sqrt_prices();
+        let amount_in = range::get_amount_in(&self.pool.amounts, in_idx, amount_out, sqrt_lower, sqrt_upper, self.pool.total_fee)
+            .expect(ERR94_OUT_OF_PRICE_RANGE);
+        assert!(max_amount_in.is_none() || amount_in <= max_amount_in.unwrap(), "{}", ERR68_SLIPPAGE);
+        if !is_view {
+            env::log(
+                format!(
+                    "Swap_by_output {} {} for {} {}",
+                    amount_in, token_in, amount_out, token_out
+                )
+                .as_bytes(),
+            );
+        }
+        self.update_pool_and_distribute_fee(in_idx, amount_in, out_idx, amount_out, admin_fee, is_view);
+        amount_in
+    }
+
+    /// Same as for simple pools, with the liquidity as invariant.
+    fn update_pool_and_distribute_fee(
+        &mut self,
+        in_idx: usize,
+        amount_in: Balance,
+        out_idx: usize,
+        amount_out: Balance,
+        admin_fee: &AdminFees,
+        is_view: bool
+    ) {
+        let prev_liquidity = self.get_liquidity();
+        self.pool.amounts[in_idx] += amount_in;
+        self.pool.amounts[out_idx] -= amount_out;
+        let new_liquidity = self.get_liquidity();
+        assert!(new_liquidity >= prev_liquidity, "{}", ERR75_INVARIANT_REDUCE);
+
+        self.pool.mint_admin_fee_shares(prev_liquidity.into(), new_liquidity.into(), admin_fee, is_view);
+
+        self.pool.volumes[in_idx].input.0 += amount_in;
+        self.pool.volumes[in_idx].output.0 += amount_out;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use near_sdk::test_utils::{accounts, VMContextBuilder};
+    use near_sdk::{testing_env, MockedBlockchain};
+    use near_sdk_sim::to_yocto;
+
+    use super::*;
+
+    fn no_admin_fee() -> AdminFees {
+        AdminFees {
+            admin_fee_bps: 0,
+            exchange_id: accounts(3).as_ref().clone(),
+            referral_info: None,
+        }
+    }
+
+    #[test]
+    fn test_range_pool_deeper_than_simple() {
+        let mut context = VMContextBuilder::new();
+        context.predecessor_account_id(accounts(0));
+        testing_env!(context.build());
+        let one = 10u128.pow(24);
+        let mut simple = SimplePool::new(0, vec![accounts(1), accounts(2)], 30);
+        let mut ranged = RangePool::new(1, vec![accounts(1), accounts(2)], 30, one * 95 / 100, one * 105 / 100);
+        for pool in [&mut simple, &mut ranged.pool] {
+            pool.add_liquidity(accounts(0).as_ref(), &mut vec![to_yocto("100"), to_yocto("100")], false);
+        }
+        assert_eq!(ranged.get_price(), one);
+        let simple_out = simple.swap(accounts(1).as_ref(), to_yocto("10"), accounts(2).as_ref(), 1, &no_admin_fee(), false);
+        let range_out = ranged.swap(accounts(1).as_ref(), to_yocto("10"), accounts(2).as_ref(), 1, &no_admin_fee(), false);
+        assert!(range_out > simple_out);
+        assert!(range_out < to_yocto("10"));
+        assert!(ranged.get_price() >= ranged.price_lower && ranged.get_price() < one);
+        assert!(ranged.get_share_price() > 0);
+    }
+
+    #[test]
+    #[should_panic(expected = "E94: swap moves price out of range")]
+    fn test_range_pool_swap_out_of_range() {
+        let mut context = VMContextBuilder::new();
+        context.predecessor_account_id(accounts(0));
+        testing_env!(context.build());
+        let one = 10u128.pow(24);
+        let mut ranged = RangePool::new(0, vec![accounts(1), accounts(2)], 30, one * 95 / 100, one * 105 / 100);
+        ranged.pool.add_liquidity(accounts(0).as_ref(), &mut vec![to_yocto("100"), to_yocto("100")], false);
+        ranged.swap(accounts(1).as_ref(), to_yocto("200"), accounts(2).as_ref(), 1, &no_admin_fee(), false);
+    }
+
+    #[test]
+    #[should_panic(expected = "E93: invalid price range")]
+    fn test_range_pool_too_narrow() {
+        let mut context = VMContextBuilder::new();
+        context.predecessor_account_id(accounts(0));
+        testing_env!(context.build());
+        let one = 10u128.pow(24);
+        RangePool::new(0, vec![accounts(1), accounts(2)], 30, one, one + one / 1000);
+    }
+}
@@ -0,0 +1,131 @@
+use crate::*;
+use crate::rated_swap::rate::Rate;
+
+/// Most tokens a single `batch_update_token_rates` call may sync.
+pub const MAX_BATCH_RATE_TOKENS: usize = 6;
+
+impl Contract {
+    /// Stores a rate fetched for a rated token, unless its rate guard rejects the change.
+    pub(crate) fn internal_apply_token_rate(&mut self, token_id: &AccountId, cross_call_result: &Vec<u8>) {
+        if let Some(mut rate) = global_get_rate(token_id) {
+            let prev_rate = rate.clone();
+            let new_rate = rate.set(cross_call_result);
+            if !is_rate_change_accepted(token_id, &prev_rate, new_rate) {
+                log!(
+                    "Token {} rate {} from cross-contract call rejected, stored rate {} kept.",
+                    token_id, new_rate, prev_rate.get()
+                );
+                return;
+            }
+            global_set_rate(token_id, &rate);
+            log!(
+                "Token {} got new rate {} from cross-contract call.",
+                token_id, new_rate
+            );
+        }
+    }
+
+    /// Stores a price fetched from the primary source of a degen token.
+    pub(crate) fn internal_apply_degen_price(&mut self, token_id: &AccountId, cross_call_result: &Vec<u8>) {
+        let mut degen = global_get_degen(token_id);
+        let new_degen = degen.set_price(cross_call_result);
+        global_set_degen(token_id, &degen);
+        log!(
+            "Token {} got new degen {} from cross-contract call.",
+            token_id, new_degen
+        );
+        on_degen_primary_price_updated(token_id);
+        if read_degen_price_bands_from_storage().get(token_id).map(|band| !band.contains(new_degen)).unwrap_or(false) {
+            log!("Token {} degen {} is out of band, pools holding it are suspended.", token_id, new_degen);
+        }
+    }
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Sync the rates of several rated or degen tokens in one transaction, all results are
+    /// applied by a single callback. Degen fallback sources are synced as in `update_degen_token_price`.
+    /// Tokens with a static rate have nothing to sync and are skipped.
+    pub fn batch_update_token_rates(&self, token_ids: Vec<ValidAccountId>) -> PromiseOrValue<bool> {
+        assert!(!token_ids.is_empty(), "No token to update");
+        assert!(token_ids.len() <= MAX_BATCH_RATE_TOKENS, "At most {} tokens per batch", MAX_BATCH_RATE_TOKENS);
+        let token_ids: Vec<AccountId> = token_ids.into_iter().map(|token_id| token_id.into()).collect();
+        for (i, token_id) in token_ids.iter().enumerate() {
+            assert!(!token_ids[..i].contains(token_id), "Duplicate token {}", token_id);
+        }
+        let (static_token_ids, token_ids): (Vec<AccountId>, Vec<AccountId>) = token_ids
+            .into_iter()
+            .partition(|token_id| global_get_rate(token_id).map(|rate| rate.is_static()).unwrap_or(false));
+        if !static_token_ids.is_empty() {
+            log!("Tokens {:?} have static rates, skipped.", static_token_ids);
+        }
+        if token_ids.is_empty() {
+            return PromiseOrValue::Value(true);
+        }
+        let degens = read_degens_from_storage();
+        let degen_fallbacks = read_degen_fallbacks_from_storage();
+        let mut promise: Option<Promise> = None;
+        for token_id in token_ids.iter() {
+            let update = if let Some(rate) = global_get_rate(token_id) {
+                rate.async_update()
+            } else if let Some(degen) = degens.get(token_id) {
+                if let Some(fallback) = degen_fallbacks.get(token_id) {
+                    fallback.degen.async_update().then(ext_self::update_degen_fallback_price_callback(
+                        token_id.clone(),
+                        &env::current_account_id(),
+                        NO_DEPOSIT,
+                        GAS_FOR_BASIC_OP,
+                    ));
+                }
+                degen.async_update()
+            } else {
+                env::panic(format!("{} is neither rated nor degen token", token_id).as_bytes())
+            };
+            promise = Some(match promise {
+                Some(promise) => promise.and(update),
+                None => update,
+            });
+        }
+        log!("Caller {} invokes async-update of tokens {:?}.", env::predecessor_account_id(), token_ids);
+        let gas = GAS_FOR_BASIC_OP * token_ids.len() as u64;
+        promise.unwrap().then(ext_self::batch_update_token_rates_callback(
+            token_ids,
+            &env::current_account_id(),
+            NO_DEPOSIT,
+            gas,
+        )).into()
+    }
+
+    /// The async return of batch_update_token_rates. Results come in token order, a token
+    /// whose fetch failed is skipped and keeps its stored rate.
+    #[private]
+    pub fn batch_update_token_rates_callback(&mut self, token_ids: Vec<AccountId>) {
+        let mut index = 0;
+        for token_id in token_ids {
+            let rate = global_get_rate(&token_id);
+            let count = rate.as_ref().map(Rate::promise_results_count).unwrap_or(1);
+            let results: Vec<Vec<u8>> = (index..index + count)
+                .filter_map(|i| match env::promise_result(i) {
+                    PromiseResult::Successful(result) => Some(result),
+                    _ => None,
+                })
+                .collect();
+            index += count;
+            if results.len() as u64 != count {
+                log!("Token {} async-update failed, {}", token_id, ERR124_CROSS_CALL_FAILED);
+                continue;
+            }
+            let mut results = results.into_iter();
+            let cross_call_result = if count == 2 {
+                pair_rated_price_to_vec_u8(results.next().unwrap(), results.next().unwrap())
+            } else {
+                results.next().unwrap()
+            };
+            if rate.is_some() {
+                self.internal_apply_token_rate(&token_id, &cross_call_result);
+            } else {
+                self.internal_apply_degen_price(&token_id, &cross_call_result);
+            }
+        }
+    }
+}
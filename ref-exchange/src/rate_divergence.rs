@@ -0,0 +1,208 @@
+use crate::*;
+use crate::admin_fee::AdminFees;
+use crate::market_price::SPOT_PRICE_PROBE_DIVISOR;
+use crate::rated_swap::RatedSwapPool;
+use crate::rated_swap::rate::is_global_rate_valid;
+use crate::utils::{to_nano, FEE_DIVISOR, U256};
+use near_sdk::json_types::U64;
+use near_sdk::{BlockHeight, Timestamp};
+
+/// Fewest observations above the limit, in distinct blocks, before a breaker trips, so reserves
+/// pushed off for a moment can't guard the pool.
+pub const MIN_RATE_DIVERGENCE_SAMPLES: u32 = 3;
+
+/// Watches how far a rated pool's own exchange rate drifts from its rate providers and
+/// guards the pool once the drift outlasts `max_duration_sec`.
+#[derive(BorshSerialize, BorshDeserialize, Clone)]
+pub struct RateDivergenceBreaker {
+    pub max_divergence_bps: u32,
+    pub max_duration_sec: u32,
+    pub last_divergence_bps: u32,
+    /// When the divergence last went above `max_divergence_bps`, None while it's within.
+    pub diverging_since: Option<Timestamp>,
+    /// Blocks the divergence was seen above the limit in since `diverging_since`.
+    pub diverging_samples: u32,
+    pub last_sample_height: BlockHeight,
+    /// Swaps in the pool fail until the owner or guardians reset the breaker.
+    pub guarded: bool,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+#[cfg_attr(not(target_arch = "wasm32"), derive(Debug))]
+pub struct RateDivergenceBreakerInfo {
+    pub max_divergence_bps: u32,
+    pub max_duration_sec: u32,
+    pub last_divergence_bps: u32,
+    pub diverging_since: Option<U64>,
+    pub diverging_samples: u32,
+    pub guarded: bool,
+}
+
+impl From<RateDivergenceBreaker> for RateDivergenceBreakerInfo {
+    fn from(breaker: RateDivergenceBreaker) -> Self {
+        Self {
+            max_divergence_bps: breaker.max_divergence_bps,
+            max_duration_sec: breaker.max_duration_sec,
+            last_divergence_bps: breaker.last_divergence_bps,
+            diverging_since: breaker.diverging_since.map(U64),
+            diverging_samples: breaker.diverging_samples,
+            guarded: breaker.guarded,
+        }
+    }
+}
+
+pub fn read_rate_divergence_breakers_from_storage() -> HashMap<u64, RateDivergenceBreaker> {
+    if let Some(content) = env::storage_read(RATE_DIVERGENCE_BREAKERS.as_bytes()) {
+        HashMap::try_from_slice(&content).expect("deserialize rate divergence breakers failed.")
+    } else {
+        HashMap::new()
+    }
+}
+
+pub fn write_rate_divergence_breakers_to_storage(breakers: HashMap<u64, RateDivergenceBreaker>) {
+    env::storage_write(
+        RATE_DIVERGENCE_BREAKERS.as_bytes(),
+        &breakers.try_to_vec().unwrap(),
+    );
+}
+
+/// Largest divergence, in bps, between the pool's marginal rate of each token into the first
+/// one and the ratio of their provider rates. The marginal rate is probed the same way as
+/// `get_spot_price` and taken before the swap fee. None while any rate is expired.
+pub fn rated_pool_divergence_bps(pool: &RatedSwapPool) -> Option<u32> {
+    if pool.token_account_ids.iter().any(|token_id| !is_global_rate_valid(token_id)) {
+        return None;
+    }
+    let rates = pool.get_rates();
+    let amounts = pool.get_amounts();
+    let c_factor = |idx: usize| U256::from(10).pow(U256::from(rated_swap::TARGET_DECIMAL - pool.token_decimals[idx]));
+    let token_out = &pool.token_account_ids[0];
+    let mut divergence_bps = 0;
+    for (idx, token_in) in pool.token_account_ids.iter().enumerate().skip(1) {
+        let probe_in = amounts[idx] / SPOT_PRICE_PROBE_DIVISOR;
+        if probe_in == 0 || amounts[0] == 0 {
+            continue;
+        }
+        let probe_out = pool.get_rated_return(token_in, probe_in, token_out, &None, &AdminFees::zero());
+        // c_out / c_in against rate_in / rate_out, with the fee added back to c_out
+        let implied = U256::from(probe_out) * c_factor(0) * U256::from(FEE_DIVISOR) / U256::from(FEE_DIVISOR - pool.total_fee)
+            * U256::from(rates[0]);
+        let provided = U256::from(probe_in) * c_factor(idx) * U256::from(rates[idx]);
+        let diff = if implied > provided { implied - provided } else { provided - implied };
+        let bps = (diff * U256::from(FEE_DIVISOR) / provided).min(U256::from(u32::MAX)).as_u32();
+        divergence_bps = std::cmp::max(divergence_bps, bps);
+    }
+    Some(divergence_bps)
+}
+
+impl Contract {
+    pub(crate) fn assert_pool_not_rate_guarded(&self, pool_id: u64) {
+        assert!(
+            !read_rate_divergence_breakers_from_storage().get(&pool_id).map(|breaker| breaker.guarded).unwrap_or(false),
+            "Pool {} is guarded for rate divergence", pool_id
+        );
+    }
+
+    /// Updates the divergence breaker of the pool, if any, from its current reserves and
+    /// trips it once the divergence has stayed above the limit for long enough, seen in at
+    /// least MIN_RATE_DIVERGENCE_SAMPLES blocks. Called after swaps and liquidity changes.
+    /// Returns the divergence, None without a breaker or while rates are expired.
+    pub(crate) fn internal_track_rate_divergence(&mut self, pool_id: u64, pool: &Pool) -> Option<u32> {
+        let rated_pool = match pool {
+            Pool::RatedSwapPool(rated_pool) => rated_pool,
+            _ => return None,
+        };
+        let mut breakers = read_rate_divergence_breakers_from_storage();
+        let breaker = breakers.get_mut(&pool_id)?;
+        let divergence_bps = rated_pool_divergence_bps(rated_pool)?;
+        let now = env::block_timestamp();
+        breaker.last_divergence_bps = divergence_bps;
+        if divergence_bps <= breaker.max_divergence_bps {
+            breaker.diverging_since = None;
+            breaker.diverging_samples = 0;
+        } else {
+            let since = *breaker.diverging_since.get_or_insert(now);
+            let height = env::block_index();
+            if breaker.diverging_samples == 0 || height > breaker.last_sample_height {
+                breaker.diverging_samples += 1;
+                breaker.last_sample_height = height;
+            }
+            if !breaker.guarded
+                && now >= since + to_nano(breaker.max_duration_sec)
+                && breaker.diverging_samples >= MIN_RATE_DIVERGENCE_SAMPLES
+            {
+                breaker.guarded = true;
+                log!("Pool {} guarded, rates diverging by {} bps since {}", pool_id, divergence_bps, since);
+            }
+        }
+        write_rate_divergence_breakers_to_storage(breakers);
+        Some(divergence_bps)
+    }
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Set the divergence limit of a rated pool and how long it may be exceeded before
+    /// the pool is guarded. Tracking state of an existing breaker is kept.
+    #[payable]
+    pub fn set_rate_divergence_breaker(&mut self, pool_id: u64, max_divergence_bps: u32, max_duration_sec: u32) {
+        assert_one_yocto();
+        assert!(self.is_owner_or_guardians(), "{}", ERR100_NOT_ALLOWED);
+        audit_privileged_action("set_rate_divergence_breaker");
+        assert!(
+            matches!(self.internal_get_pool(pool_id), Pool::RatedSwapPool(_)),
+            "Pool {} is not a rated pool", pool_id
+        );
+        assert!(max_divergence_bps > 0 && max_divergence_bps <= FEE_DIVISOR, "Invalid max_divergence_bps");
+        let mut breakers = read_rate_divergence_breakers_from_storage();
+        let breaker = breakers.entry(pool_id).or_insert(RateDivergenceBreaker {
+            max_divergence_bps,
+            max_duration_sec,
+            last_divergence_bps: 0,
+            diverging_since: None,
+            diverging_samples: 0,
+            last_sample_height: 0,
+            guarded: false,
+        });
+        breaker.max_divergence_bps = max_divergence_bps;
+        breaker.max_duration_sec = max_duration_sec;
+        write_rate_divergence_breakers_to_storage(breakers);
+        log!("Rate divergence breaker of pool {} set to {} bps for {} sec", pool_id, max_divergence_bps, max_duration_sec);
+    }
+
+    #[payable]
+    pub fn remove_rate_divergence_breaker(&mut self, pool_id: u64) {
+        assert_one_yocto();
+        assert!(self.is_owner_or_guardians(), "{}", ERR100_NOT_ALLOWED);
+        audit_privileged_action("remove_rate_divergence_breaker");
+        let mut breakers = read_rate_divergence_breakers_from_storage();
+        assert!(breakers.remove(&pool_id).is_some(), "No rate divergence breaker for pool {}", pool_id);
+        write_rate_divergence_breakers_to_storage(breakers);
+    }
+
+    /// Lift the guard of a pool and restart its divergence tracking.
+    #[payable]
+    pub fn reset_rate_divergence_breaker(&mut self, pool_id: u64) {
+        assert_one_yocto();
+        assert!(self.is_owner_or_guardians(), "{}", ERR100_NOT_ALLOWED);
+        audit_privileged_action("reset_rate_divergence_breaker");
+        let mut breakers = read_rate_divergence_breakers_from_storage();
+        let breaker = breakers.get_mut(&pool_id).expect("No rate divergence breaker for the pool");
+        breaker.guarded = false;
+        breaker.diverging_since = None;
+        breaker.diverging_samples = 0;
+        write_rate_divergence_breakers_to_storage(breakers);
+    }
+
+    /// Track the divergence of a pool without swapping, so a drift shows up in quiet pools too.
+    /// Anyone can call it. Returns the current divergence in bps.
+    pub fn poke_rate_divergence(&mut self, pool_id: u64) -> Option<u32> {
+        let pool = self.internal_get_pool(pool_id);
+        self.internal_track_rate_divergence(pool_id, &pool)
+    }
+
+    pub fn get_rate_divergence_breaker(&self, pool_id: u64) -> Option<RateDivergenceBreakerInfo> {
+        read_rate_divergence_breakers_from_storage().remove(&pool_id).map(Into::into)
+    }
+}
@@ -0,0 +1,106 @@
+use crate::*;
+use crate::rated_swap::rate::Rate;
+use crate::utils::{to_nano, FEE_DIVISOR, U256};
+
+/// Per rated token limits on top of the built-in rate expiry.
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone)]
+#[serde(crate = "near_sdk::serde")]
+#[cfg_attr(not(target_arch = "wasm32"), derive(Debug))]
+pub struct RateGuard {
+    /// Rate is treated as expired this long after its last sync.
+    pub max_rate_staleness_sec: Option<u32>,
+    /// A synced rate moving further than this from the stored one, in bps, is rejected.
+    pub max_rate_change_per_sync_bps: Option<u32>,
+}
+
+impl RateGuard {
+    pub fn is_fresh(&self, last_update_ts: u64) -> bool {
+        self.max_rate_staleness_sec
+            .map(|staleness| env::block_timestamp() <= last_update_ts + to_nano(staleness))
+            .unwrap_or(true)
+    }
+
+    pub fn accepts_change(&self, prev_rate: Balance, new_rate: Balance) -> bool {
+        self.max_rate_change_per_sync_bps
+            .map(|max_change| {
+                let change = new_rate.abs_diff(prev_rate);
+                U256::from(change) * U256::from(FEE_DIVISOR) <= U256::from(prev_rate) * U256::from(max_change)
+            })
+            .unwrap_or(true)
+    }
+}
+
+pub fn read_rate_guards_from_storage() -> HashMap<AccountId, RateGuard> {
+    if let Some(content) = env::storage_read(RATE_GUARDS.as_bytes()) {
+        HashMap::try_from_slice(&content).expect("deserialize rate guards failed.")
+    } else {
+        HashMap::new()
+    }
+}
+
+pub fn write_rate_guards_to_storage(rate_guards: HashMap<AccountId, RateGuard>) {
+    env::storage_write(
+        RATE_GUARDS.as_bytes(),
+        &rate_guards.try_to_vec().unwrap(),
+    );
+}
+
+/// Whether the rate last synced at `last_update_ts` is within the token's staleness limit, if any.
+pub fn is_rate_fresh(token_id: &AccountId, last_update_ts: u64) -> bool {
+    read_rate_guards_from_storage()
+        .get(token_id)
+        .map(|guard| guard.is_fresh(last_update_ts))
+        .unwrap_or(true)
+}
+
+/// Whether a synced rate may replace the stored one. The first sync is always accepted.
+pub fn is_rate_change_accepted(token_id: &AccountId, rate: &Rate, new_rate: Balance) -> bool {
+    rate.last_update_ts() == 0
+        || read_rate_guards_from_storage()
+            .get(token_id)
+            .map(|guard| guard.accepts_change(rate.get(), new_rate))
+            .unwrap_or(true)
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Set the staleness and per sync change limits of a rated token, both None to remove them.
+    #[payable]
+    pub fn set_rate_guard(
+        &mut self,
+        token_id: ValidAccountId,
+        max_rate_staleness_sec: Option<u32>,
+        max_rate_change_per_sync_bps: Option<u32>,
+    ) {
+        assert_one_yocto();
+        assert!(self.is_owner_or_guardians(), "{}", ERR100_NOT_ALLOWED);
+        audit_privileged_action("set_rate_guard");
+        let token_id: AccountId = token_id.into();
+        let rates: HashMap<AccountId, Rate> = if let Some(content) = env::storage_read(RATE_STORAGE_KEY.as_bytes()) {
+            HashMap::try_from_slice(&content).expect("deserialize failed.")
+        } else {
+            HashMap::new()
+        };
+        assert!(rates.contains_key(&token_id), "{} is not rated token", token_id);
+        assert!(max_rate_staleness_sec.map(|staleness| staleness > 0).unwrap_or(true), "Invalid max_rate_staleness_sec");
+        assert!(
+            max_rate_change_per_sync_bps.map(|max_change| max_change > 0 && max_change <= FEE_DIVISOR).unwrap_or(true),
+            "Invalid max_rate_change_per_sync_bps"
+        );
+        let mut rate_guards = read_rate_guards_from_storage();
+        if max_rate_staleness_sec.is_none() && max_rate_change_per_sync_bps.is_none() {
+            rate_guards.remove(&token_id);
+        } else {
+            rate_guards.insert(token_id.clone(), RateGuard {
+                max_rate_staleness_sec,
+                max_rate_change_per_sync_bps,
+            });
+        }
+        write_rate_guards_to_storage(rate_guards);
+        log!("Rate guard of {} set to {:?} sec, {:?} bps", token_id, max_rate_staleness_sec, max_rate_change_per_sync_bps);
+    }
+
+    pub fn get_rate_guards(&self) -> HashMap<AccountId, RateGuard> {
+        read_rate_guards_from_storage()
+    }
+}
@@ -5,7 +5,7 @@ use near_sdk::{env, AccountId, Balance, Timestamp};
 
 use crate::admin_fee::AdminFees;
 use crate::errors::*;
-use crate::rated_swap::math::{
+use ref_exchange_math::rated::{
     Fees, RatedSwap, SwapResult, MAX_AMP, MAX_AMP_CHANGE, MIN_AMP, MIN_RAMP_DURATION,
 };
 use crate::utils::{add_to_collection, SwapVolume, FEE_DIVISOR, U256, u128_ratio};
@@ -13,12 +13,12 @@ use crate::StorageKey;
 
 use self::rate::*;
 
-mod math;
 pub mod rate;
 mod stnear_rate;
 mod linear_rate;
 mod nearx_rate;
 mod sfrax_rate;
+mod static_rate;
 
 pub const TARGET_DECIMAL: u8 = 24;
 pub const MIN_DECIMAL: u8 = 1;
@@ -121,7 +121,7 @@ impl RatedSwapPool {
         amounts
     }
 
-    fn amounts_to_c_amounts(&self, amounts: &Vec<u128>) ->Vec<u128> {
+    pub fn amounts_to_c_amounts(&self, amounts: &Vec<u128>) ->Vec<u128> {
         let mut c_amounts = amounts.clone();
         for (index, value) in self.token_decimals.iter().enumerate() {
             let factor = 10_u128
@@ -240,7 +240,7 @@ impl RatedSwapPool {
                     &c_amounts,
                     &self.c_amounts,
                     self.shares_total_supply,
-                    &Fees::new(self.total_fee, &fees),
+                    &Fees::new(self.total_fee, fees.admin_fee_bps),
                 )
                 .expect(ERR67_LPSHARE_CALC_ERR)
         }
@@ -394,7 +394,7 @@ impl RatedSwapPool {
         }
 
         let invariant = self.get_invariant_with_rates(rates.as_ref().unwrap_or(&self.get_rates()));
-        let trade_fee = Fees::new(self.total_fee, &fees);
+        let trade_fee = Fees::new(self.total_fee, fees.admin_fee_bps);
 
         let (burn_shares, _) = invariant
             .compute_lp_amount_for_withdraw(
@@ -432,7 +432,7 @@ impl RatedSwapPool {
         self.assert_rates_valid();
 
         let invariant = self.get_invariant_with_rates(&self.get_rates());
-        let trade_fee = Fees::new(self.total_fee, &fees);
+        let trade_fee = Fees::new(self.total_fee, fees.admin_fee_bps);
 
         let (burn_shares, fee_part) = invariant
             .compute_lp_amount_for_withdraw(
@@ -502,6 +502,27 @@ impl RatedSwapPool {
         burn_shares
     }
 
+    /// Quote of a swap, None where rates expired, the swap fails to compute or would leave the out reserve below MIN_RESERVE.
+    pub fn try_quote_swap(&self, token_in: &AccountId, amount_in: Balance, token_out: &AccountId, fees: &AdminFees) -> Option<Balance> {
+        if !self.token_account_ids.iter().all(is_global_rate_valid) {
+            return None;
+        }
+        let in_idx = self.token_index(token_in);
+        let out_idx = self.token_index(token_out);
+        let result = self.get_invariant_with_rates(&self.get_rates())
+            .swap_to(
+                in_idx,
+                self.amount_to_c_amount(amount_in, in_idx),
+                out_idx,
+                &self.c_amounts,
+                &Fees::new(self.total_fee, fees.admin_fee_bps),
+            )?;
+        if result.new_destination_amount < MIN_RESERVE {
+            return None;
+        }
+        Some(self.c_amount_to_amount(result.amount_swapped, out_idx))
+    }
+
     /// Returns number of tokens in outcome, given amount.
     /// Tokens are provided as indexes into token list for given pool.
     /// All tokens are comparable tokens
@@ -533,7 +554,7 @@ impl RatedSwapPool {
                 c_amount_in,
                 token_out,
                 &self.c_amounts,
-                &Fees::new(self.total_fee, &fees),
+                &Fees::new(self.total_fee, fees.admin_fee_bps),
             )
             .expect(ERR70_SWAP_OUT_CALC_ERR)
 
@@ -1,7 +1,8 @@
-use super::sfrax_rate::SfraxRate;
+use super::sfrax_rate::{SfraxExtraInfo, SfraxRate};
 use super::stnear_rate::StnearRate;
 use super::linear_rate::LinearRate;
 use super::nearx_rate::NearxRate;
+use super::static_rate::StaticRate;
 use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
 use near_sdk::{env, AccountId, Balance, Promise};
 use crate::ERR127_INVALID_RATE_TYPE;
@@ -10,7 +11,7 @@ use once_cell::sync::Lazy;
 use std::collections::HashMap;
 use std::sync::Mutex;
 
-use crate::RATE_STORAGE_KEY;
+use crate::{is_rate_fresh, RATE_STORAGE_KEY};
 
 pub static RATES: Lazy<Mutex<HashMap<AccountId, Rate>>> = Lazy::new(|| Mutex::new(HashMap::new()));
 
@@ -21,7 +22,8 @@ pub enum Rate {
     Stnear(StnearRate),
     Linear(LinearRate),
     Nearx(NearxRate),
-    Sfrax(SfraxRate)
+    Sfrax(SfraxRate),
+    Static(StaticRate),
 }
 
 pub trait RateTrait {
@@ -39,6 +41,7 @@ impl RateTrait for Rate {
             Rate::Linear(rates) => rates.are_actual(),
             Rate::Nearx(rates) => rates.are_actual(),
             Rate::Sfrax(rates) => rates.are_actual(),
+            Rate::Static(rates) => rates.are_actual(),
         }
     }
     fn get(&self) -> Balance {
@@ -47,6 +50,7 @@ impl RateTrait for Rate {
             Rate::Linear(rates) => rates.get(),
             Rate::Nearx(rates) => rates.get(),
             Rate::Sfrax(rates) => rates.get(),
+            Rate::Static(rates) => rates.get(),
         }
     }
     fn last_update_ts(&self) -> u64 {
@@ -55,6 +59,7 @@ impl RateTrait for Rate {
             Rate::Linear(rates) => rates.last_update_ts(),
             Rate::Nearx(rates) => rates.last_update_ts(),
             Rate::Sfrax(rates) => rates.last_update_ts(),
+            Rate::Static(rates) => rates.last_update_ts(),
         }
     }
     fn async_update(&self) -> Promise {
@@ -63,6 +68,7 @@ impl RateTrait for Rate {
             Rate::Linear(rates) => rates.async_update(),
             Rate::Nearx(rates) => rates.async_update(),
             Rate::Sfrax(rates) => rates.async_update(),
+            Rate::Static(rates) => rates.async_update(),
         }
     }
     fn set(&mut self, cross_call_result: &Vec<u8>) -> u128 {
@@ -71,6 +77,7 @@ impl RateTrait for Rate {
             Rate::Linear(rates) => rates.set(cross_call_result),
             Rate::Nearx(rates) => rates.set(cross_call_result),
             Rate::Sfrax(rates) => rates.set(cross_call_result),
+            Rate::Static(rates) => rates.set(cross_call_result),
         }
     }
 }
@@ -82,6 +89,7 @@ impl Rate {
             "LINEAR" => Rate::Linear(LinearRate::new(contract_id)),
             "NEARX" => Rate::Nearx(NearxRate::new(contract_id)),
             "SFRAX" => Rate::Sfrax(SfraxRate::new(contract_id, extra_info.expect("Missing extra_info"))),
+            "STATIC" => Rate::Static(StaticRate::new(contract_id, extra_info.expect("Missing extra_info"))),
             _ => unimplemented!(),
         }
     }
@@ -92,6 +100,20 @@ impl Rate {
             Rate::Linear(_) => "LINEAR".to_string(),
             Rate::Nearx(_) => "NEARX".to_string(),
             Rate::Sfrax(_) => "SFRAX".to_string(),
+            Rate::Static(_) => "STATIC".to_string(),
+        }
+    }
+
+    /// Static rates are set by governance and have nothing to sync.
+    pub fn is_static(&self) -> bool {
+        matches!(self, Rate::Static(_))
+    }
+
+    /// Number of promise results the promise of `async_update` resolves to.
+    pub fn promise_results_count(&self) -> u64 {
+        match self {
+            Rate::Sfrax(r) if matches!(r.extra_info, SfraxExtraInfo::PythOracle(_)) => 2,
+            _ => 1,
         }
     }
 
@@ -101,6 +123,7 @@ impl Rate {
             "LINEAR" => true,
             "NEARX" => true,
             "SFRAX" => true,
+            "STATIC" => true,
             _ => false,
         }
     }
@@ -170,6 +193,16 @@ pub fn global_update_rated_token_extra_info(token_id: &AccountId, extra_info: St
     );
 }
 
+/// Set the rate of a token with static rate.
+pub fn global_update_static_rate(token_id: &AccountId, rate: Balance) {
+    let mut static_rate = global_get_rate(token_id).expect("Invalid token_id");
+    match &mut static_rate {
+        Rate::Static(r) => r.update(rate),
+        _ => env::panic(format!("Rate of {} is not static", token_id).as_bytes()),
+    }
+    global_set_rate(token_id, &static_rate);
+}
+
 pub fn global_get_rate(token_id: &AccountId) -> Option<Rate> {
     if RATES.lock().unwrap().is_empty() {
         let rates: HashMap<AccountId, Rate> =
@@ -224,7 +257,7 @@ pub fn is_global_rate_valid(token_id: &AccountId) -> bool {
         }
     }
     if let Some(rate) = RATES.lock().unwrap().get(token_id) {
-        rate.are_actual()
+        rate.are_actual() && is_rate_fresh(token_id, rate.last_update_ts())
     } else {
         // non-rated token always has valid rate
         true
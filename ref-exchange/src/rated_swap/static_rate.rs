@@ -0,0 +1,58 @@
+use super::rate::RateTrait;
+use crate::errors::{ERR126_FAILED_TO_PARSE_RESULT, ERR128_INVALID_EXTRA_INFO_MSG_FORMAT};
+use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
+use near_sdk::{env, json_types::U128, serde_json::from_slice, AccountId, Balance, Promise};
+
+/// Rate set by governance instead of synced from a contract, for pairs whose ratio rarely changes.
+/// It never expires, a rate guard's staleness limit still applies.
+#[derive(BorshSerialize, BorshDeserialize, Clone)]
+pub struct StaticRate {
+    /// *
+    pub stored_rates: Balance,
+    /// *
+    pub rates_updated_at: u64,
+    /// *
+    pub contract_id: AccountId,
+}
+
+impl RateTrait for StaticRate {
+    fn are_actual(&self) -> bool {
+        true
+    }
+    fn get(&self) -> Balance {
+        self.stored_rates
+    }
+    fn last_update_ts(&self) -> u64 {
+        self.rates_updated_at
+    }
+    fn async_update(&self) -> Promise {
+        env::panic(format!("Rate of {} is static", self.contract_id).as_bytes());
+    }
+    fn set(&mut self, cross_call_result: &Vec<u8>) -> u128 {
+        if let Ok(U128(price)) = from_slice::<U128>(cross_call_result) {
+            self.update(price);
+            price
+        } else {
+            env::panic(ERR126_FAILED_TO_PARSE_RESULT.as_bytes());
+        }
+    }
+}
+
+impl StaticRate {
+    /// `extra_info` is the initial rate as a decimal string.
+    pub fn new(contract_id: AccountId, extra_info: String) -> Self {
+        let stored_rates: Balance = extra_info.parse().expect(ERR128_INVALID_EXTRA_INFO_MSG_FORMAT);
+        assert!(stored_rates > 0, "{}", ERR128_INVALID_EXTRA_INFO_MSG_FORMAT);
+        Self {
+            stored_rates,
+            rates_updated_at: env::block_timestamp(),
+            contract_id,
+        }
+    }
+
+    pub fn update(&mut self, rate: Balance) {
+        assert!(rate > 0, "Rate must be positive");
+        self.stored_rates = rate;
+        self.rates_updated_at = env::block_timestamp();
+    }
+}
@@ -0,0 +1,66 @@
+use crate::*;
+use crate::admin_fee::AdminFees;
+use crate::utils::FEE_DIVISOR;
+use near_sdk::collections::LookupSet;
+
+pub fn read_referral_opt_out_pools_from_storage() -> LookupSet<u64> {
+    if let Some(content) = env::storage_read(REFERRAL_OPT_OUT_POOLS.as_bytes()) {
+        LookupSet::try_from_slice(&content).expect("deserialize referral opt out pools failed.")
+    } else {
+        LookupSet::new(StorageKey::ReferralOptOutPools)
+    }
+}
+
+pub fn write_referral_opt_out_pools_to_storage(referral_opt_out_pools: LookupSet<u64>) {
+    env::storage_write(
+        REFERRAL_OPT_OUT_POOLS.as_bytes(),
+        &referral_opt_out_pools.try_to_vec().unwrap(),
+    );
+}
+
+impl Contract {
+    /// Admin fees of a swap through the pool. In pools opted out of the referral fee,
+    /// the referral part of the admin fee is not charged and stays with LPs.
+    pub(crate) fn internal_admin_fees(&self, pool_id: u64, referral_info: &Option<(AccountId, u32)>) -> AdminFees {
+        match referral_info {
+            Some((_, referral_fee)) if read_referral_opt_out_pools_from_storage().contains(&pool_id) => AdminFees {
+                admin_fee_bps: self.admin_fee_bps * (FEE_DIVISOR - referral_fee) / FEE_DIVISOR,
+                exchange_id: env::current_account_id(),
+                referral_info: None,
+            },
+            _ => AdminFees {
+                admin_fee_bps: self.admin_fee_bps,
+                exchange_id: env::current_account_id(),
+                referral_info: referral_info.clone(),
+            },
+        }
+    }
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Same as `add_simple_pool`, but swaps through the pool pay no referral fee,
+    /// that part of the admin fee is left to LPs instead.
+    #[payable]
+    pub fn add_simple_pool_without_referral_fee(&mut self, tokens: Vec<ValidAccountId>, fee: u32) -> u64 {
+        self.assert_contract_running();
+        check_token_duplicates(&tokens);
+        let prev_storage = env::storage_usage();
+        let pool_id = self.internal_push_pool(Pool::SimplePool(SimplePool::new(
+            self.pools.len() as u32,
+            tokens,
+            fee,
+        )));
+        let mut referral_opt_out_pools = read_referral_opt_out_pools_from_storage();
+        referral_opt_out_pools.insert(&pool_id);
+        write_referral_opt_out_pools_to_storage(referral_opt_out_pools);
+        self.internal_record_pool_creator(pool_id);
+        self.internal_register_pool(pool_id);
+        self.internal_check_storage(prev_storage);
+        pool_id
+    }
+
+    pub fn is_referral_fee_opted_out(&self, pool_id: u64) -> bool {
+        read_referral_opt_out_pools_from_storage().contains(&pool_id)
+    }
+}
@@ -0,0 +1,100 @@
+use crate::*;
+use crate::utils::{to_nano, u64_dec_format};
+use near_sdk::Timestamp;
+
+pub const MAX_REFERRAL_ACTIVATION_DELAY_SEC: u32 = 30 * 24 * 3600;
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+#[cfg_attr(not(target_arch = "wasm32"), derive(Debug))]
+pub struct ReferralInfo {
+    pub fee_bps: u32,
+    #[serde(with = "u64_dec_format")]
+    pub active_at: Timestamp,
+}
+
+/// Newly inserted referrals earn fees only after this long, giving time to revoke bad listings.
+pub fn read_referral_activation_delay_from_storage() -> u32 {
+    if let Some(content) = env::storage_read(REFERRAL_ACTIVATION_DELAY.as_bytes()) {
+        u32::try_from_slice(&content).expect("deserialize referral activation delay failed.")
+    } else {
+        0
+    }
+}
+
+pub fn write_referral_activation_delay_to_storage(delay_sec: u32) {
+    env::storage_write(
+        REFERRAL_ACTIVATION_DELAY.as_bytes(),
+        &delay_sec.try_to_vec().unwrap(),
+    );
+}
+
+/// Activation time of referrals inserted with a delay, others are active right away.
+pub fn read_referral_activations_from_storage() -> LookupMap<AccountId, Timestamp> {
+    if let Some(content) = env::storage_read(REFERRAL_ACTIVATIONS.as_bytes()) {
+        LookupMap::try_from_slice(&content).expect("deserialize referral activations failed.")
+    } else {
+        LookupMap::new(StorageKey::ReferralActivations)
+    }
+}
+
+pub fn write_referral_activations_to_storage(referral_activations: LookupMap<AccountId, Timestamp>) {
+    env::storage_write(
+        REFERRAL_ACTIVATIONS.as_bytes(),
+        &referral_activations.try_to_vec().unwrap(),
+    );
+}
+
+impl Contract {
+    /// Resolves the referral passed along a trade into the fee receiver and its fee bps.
+    /// Unknown, not yet active and self referrals get nothing.
+    pub(crate) fn internal_get_referral_info(&self, referral_id: Option<AccountId>, trader_id: &AccountId) -> Option<(AccountId, u32)> {
+        let referral_id = referral_id.filter(|rid| rid != trader_id)?;
+        let fee_bps = self.referrals.get(&referral_id)?;
+        let active_at = read_referral_activations_from_storage().get(&referral_id).unwrap_or(0);
+        if env::block_timestamp() < active_at {
+            return None;
+        }
+        Some((referral_id, fee_bps))
+    }
+
+    pub(crate) fn internal_schedule_referral_activation(&mut self, referral_id: &AccountId) {
+        let delay_sec = read_referral_activation_delay_from_storage();
+        if delay_sec == 0 {
+            return;
+        }
+        let mut referral_activations = read_referral_activations_from_storage();
+        referral_activations.insert(referral_id, &(env::block_timestamp() + to_nano(delay_sec)));
+        write_referral_activations_to_storage(referral_activations);
+    }
+
+    pub(crate) fn internal_clear_referral_activation(&mut self, referral_id: &AccountId) {
+        let mut referral_activations = read_referral_activations_from_storage();
+        referral_activations.remove(referral_id);
+        write_referral_activations_to_storage(referral_activations);
+    }
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Set how long referrals inserted from now on wait before earning fees.
+    #[payable]
+    pub fn set_referral_activation_delay(&mut self, delay_sec: u32) {
+        assert_one_yocto();
+        self.assert_owner();
+        audit_privileged_action("set_referral_activation_delay");
+        assert!(delay_sec <= MAX_REFERRAL_ACTIVATION_DELAY_SEC, "Invalid referral activation delay");
+        write_referral_activation_delay_to_storage(delay_sec);
+    }
+
+    pub fn get_referral_activation_delay(&self) -> u32 {
+        read_referral_activation_delay_from_storage()
+    }
+
+    pub fn get_referral_info(&self, referral_id: ValidAccountId) -> Option<ReferralInfo> {
+        self.referrals.get(referral_id.as_ref()).map(|fee_bps| ReferralInfo {
+            fee_bps,
+            active_at: read_referral_activations_from_storage().get(referral_id.as_ref()).unwrap_or(0),
+        })
+    }
+}
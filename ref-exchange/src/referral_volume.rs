@@ -0,0 +1,210 @@
+use crate::*;
+use crate::utils::{to_nano, u64_dec_format};
+use near_sdk::json_types::U64;
+use near_sdk::{StorageUsage, Timestamp};
+
+/// Most tokens a referral's volume is kept in per period.
+pub const MAX_REFERRAL_VOLUME_TOKENS: usize = 10;
+
+/// How attributed volume is split into periods. Periods are numbered on from `first_period`,
+/// counted from `started_at`, a zero `duration_sec` keeps a single period.
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone, Default)]
+#[serde(crate = "near_sdk::serde")]
+#[cfg_attr(not(target_arch = "wasm32"), derive(Debug))]
+pub struct ReferralVolumePeriod {
+    pub duration_sec: u32,
+    #[serde(with = "u64_dec_format")]
+    pub started_at: Timestamp,
+    #[serde(with = "u64_dec_format")]
+    pub first_period: u64,
+}
+
+impl ReferralVolumePeriod {
+    pub fn current(&self) -> u64 {
+        if self.duration_sec == 0 {
+            return self.first_period;
+        }
+        self.first_period + (env::block_timestamp() - self.started_at) / to_nano(self.duration_sec)
+    }
+}
+
+/// Amounts swapped in per token through trades paying fees to a referral.
+/// Storage for new tokens is paid out of the referral's storage balance.
+#[derive(BorshSerialize, BorshDeserialize, Clone, Default)]
+pub struct ReferralVolume {
+    pub period: u64,
+    pub volumes: HashMap<AccountId, Balance>,
+    /// Volumes of the period right before `period`, if any.
+    pub prev_volumes: HashMap<AccountId, Balance>,
+}
+
+impl ReferralVolume {
+    /// Moves the volumes to the given period, keeping them as previous if it directly follows.
+    fn roll_to(&mut self, period: u64) {
+        if self.period == period {
+            return;
+        }
+        self.prev_volumes = if self.period + 1 == period {
+            std::mem::take(&mut self.volumes)
+        } else {
+            HashMap::new()
+        };
+        self.volumes.clear();
+        self.period = period;
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+#[cfg_attr(not(target_arch = "wasm32"), derive(Debug))]
+pub struct ReferralVolumeInfo {
+    pub referral_id: AccountId,
+    pub period: U64,
+    pub volumes: HashMap<AccountId, U128>,
+    pub prev_volumes: HashMap<AccountId, U128>,
+}
+
+pub fn read_referral_volume_period_from_storage() -> ReferralVolumePeriod {
+    if let Some(content) = env::storage_read(REFERRAL_VOLUME_PERIOD.as_bytes()) {
+        ReferralVolumePeriod::try_from_slice(&content).expect("deserialize referral volume period failed.")
+    } else {
+        ReferralVolumePeriod::default()
+    }
+}
+
+pub fn write_referral_volume_period_to_storage(referral_volume_period: ReferralVolumePeriod) {
+    env::storage_write(
+        REFERRAL_VOLUME_PERIOD.as_bytes(),
+        &referral_volume_period.try_to_vec().unwrap(),
+    );
+}
+
+pub fn read_referral_volumes_from_storage() -> UnorderedMap<AccountId, ReferralVolume> {
+    if let Some(content) = env::storage_read(REFERRAL_VOLUMES.as_bytes()) {
+        UnorderedMap::try_from_slice(&content).expect("deserialize referral volumes failed.")
+    } else {
+        UnorderedMap::new(StorageKey::ReferralVolumes)
+    }
+}
+
+pub fn write_referral_volumes_to_storage(referral_volumes: UnorderedMap<AccountId, ReferralVolume>) {
+    env::storage_write(
+        REFERRAL_VOLUMES.as_bytes(),
+        &referral_volumes.try_to_vec().unwrap(),
+    );
+}
+
+fn to_u128_map(volumes: HashMap<AccountId, Balance>) -> HashMap<AccountId, U128> {
+    volumes.into_iter().map(|(token_id, volume)| (token_id, U128(volume))).collect()
+}
+
+impl Contract {
+    /// Charges the storage used since `prev_storage` to the referral's available storage balance.
+    /// Returns false, charging nothing, if the referral is not registered or can't cover it.
+    fn internal_charge_referral_storage(&mut self, referral_id: &AccountId, prev_storage: StorageUsage) -> bool {
+        if env::storage_usage() <= prev_storage {
+            return true;
+        }
+        let storage_cost = (env::storage_usage() - prev_storage) as Balance * env::storage_byte_cost();
+        match self.internal_get_account(referral_id) {
+            Some(mut account) if account.storage_available() >= storage_cost => {
+                account.near_amount -= storage_cost;
+                self.internal_save_account(referral_id, account);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Attributes the amount swapped in to the referral the swap paid fees to.
+    /// A token beyond MAX_REFERRAL_VOLUME_TOKENS in the period, or one the referral can't pay
+    /// the storage for, is not attributed.
+    pub(crate) fn internal_record_referral_volume(
+        &mut self,
+        referral_info: &Option<(AccountId, u32)>,
+        token_in: &AccountId,
+        amount_in: Balance,
+    ) {
+        let referral_id = match referral_info {
+            Some((referral_id, _)) => referral_id,
+            None => return,
+        };
+        let period = read_referral_volume_period_from_storage().current();
+        let prev_storage = env::storage_usage();
+        let mut referral_volumes = read_referral_volumes_from_storage();
+        let prev_referral_volume = referral_volumes.get(referral_id);
+        let mut referral_volume = prev_referral_volume.clone().unwrap_or_default();
+        referral_volume.roll_to(period);
+        if !referral_volume.volumes.contains_key(token_in) && referral_volume.volumes.len() >= MAX_REFERRAL_VOLUME_TOKENS {
+            log!("Volume of {} not attributed to {}, too many tokens", token_in, referral_id);
+            return;
+        }
+        *referral_volume.volumes.entry(token_in.clone()).or_insert(0) += amount_in;
+        referral_volumes.insert(referral_id, &referral_volume);
+        if !self.internal_charge_referral_storage(referral_id, prev_storage) {
+            log!("Volume of {} not attributed to {}, not enough storage", token_in, referral_id);
+            match prev_referral_volume {
+                Some(prev_referral_volume) => referral_volumes.insert(referral_id, &prev_referral_volume),
+                None => referral_volumes.remove(referral_id),
+            };
+        }
+        write_referral_volumes_to_storage(referral_volumes);
+    }
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Start attributing volume in periods of the given length from now on, 0 for no reset.
+    /// Volumes recorded so far become the previous period's.
+    #[payable]
+    pub fn set_referral_volume_period(&mut self, duration_sec: u32) {
+        assert_one_yocto();
+        self.assert_owner();
+        audit_privileged_action("set_referral_volume_period");
+        let referral_volume_period = read_referral_volume_period_from_storage();
+        write_referral_volume_period_to_storage(ReferralVolumePeriod {
+            duration_sec,
+            started_at: env::block_timestamp(),
+            first_period: referral_volume_period.current() + 1,
+        });
+    }
+
+    pub fn get_referral_volume_period(&self) -> ReferralVolumePeriod {
+        read_referral_volume_period_from_storage()
+    }
+
+    /// Volume attributed to referrals in the current and the previous period.
+    pub fn get_referral_volumes(&self, from_index: Option<u64>, limit: Option<u64>) -> Vec<ReferralVolumeInfo> {
+        let period = read_referral_volume_period_from_storage().current();
+        let referral_volumes = read_referral_volumes_from_storage();
+        let keys = referral_volumes.keys_as_vector();
+        let from_index = from_index.unwrap_or(0);
+        let limit = limit.unwrap_or(keys.len());
+        (from_index..std::cmp::min(keys.len(), from_index + limit))
+            .map(|index| {
+                let referral_id = keys.get(index).unwrap();
+                let mut referral_volume = referral_volumes.get(&referral_id).unwrap();
+                referral_volume.roll_to(period);
+                ReferralVolumeInfo {
+                    referral_id,
+                    period: period.into(),
+                    volumes: to_u128_map(referral_volume.volumes),
+                    prev_volumes: to_u128_map(referral_volume.prev_volumes),
+                }
+            })
+            .collect()
+    }
+
+    pub fn get_referral_volume(&self, referral_id: ValidAccountId) -> Option<ReferralVolumeInfo> {
+        let period = read_referral_volume_period_from_storage().current();
+        read_referral_volumes_from_storage().get(referral_id.as_ref()).map(|mut referral_volume| {
+            referral_volume.roll_to(period);
+            ReferralVolumeInfo {
+                referral_id: referral_id.into(),
+                period: period.into(),
+                volumes: to_u128_map(referral_volume.volumes),
+                prev_volumes: to_u128_map(referral_volume.prev_volumes),
+            }
+        })
+    }
+}
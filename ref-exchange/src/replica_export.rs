@@ -0,0 +1,183 @@
+use crate::*;
+use near_sdk::collections::Vector;
+use near_sdk::json_types::U64;
+
+pub const MAX_REPLICA_PAGE_SIZE: u64 = 100;
+
+/// A page of a full export, `next_cursor` is None once the end is reached.
+/// A scan is consistent if all its pages carry the same `state_version`.
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+#[cfg_attr(not(target_arch = "wasm32"), derive(Debug))]
+pub struct ReplicaPage<T> {
+    pub items: Vec<T>,
+    pub next_cursor: Option<U64>,
+    pub state_version: U64,
+    pub block_height: U64,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+#[cfg_attr(not(target_arch = "wasm32"), derive(Debug))]
+pub struct ReplicaAccount {
+    pub account_id: AccountId,
+    pub storage_deposit: U128,
+    pub deposits: HashMap<AccountId, U128>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+#[cfg_attr(not(target_arch = "wasm32"), derive(Debug))]
+pub struct ReplicaPool {
+    pub pool_id: u64,
+    #[serde(flatten)]
+    pub pool: PoolInfo,
+}
+
+/// Bumped when an account is registered or unregistered and on pool creation. Balances and
+/// pool parameters changed in between don't bump it, replicas pick those up by block height.
+pub fn read_state_version_from_storage() -> u64 {
+    env::storage_read(STATE_VERSION.as_bytes())
+        .map(|content| u64::try_from_slice(&content).expect("deserialize state version failed."))
+        .unwrap_or(0)
+}
+
+pub fn bump_state_version() {
+    let state_version = read_state_version_from_storage() + 1;
+    env::storage_write(STATE_VERSION.as_bytes(), &state_version.try_to_vec().unwrap());
+}
+
+/// Accounts in the export index, i.e. registered ones unless registered before the index and not backfilled.
+pub fn read_replica_account_count_from_storage() -> u64 {
+    env::storage_read(REPLICA_ACCOUNT_COUNT.as_bytes())
+        .map(|content| u64::try_from_slice(&content).expect("deserialize replica account count failed."))
+        .unwrap_or(0)
+}
+
+fn write_replica_account_count_to_storage(count: u64) {
+    env::storage_write(REPLICA_ACCOUNT_COUNT.as_bytes(), &count.try_to_vec().unwrap());
+}
+
+/// Every account registered since the index exists, in registration order. Unregistered
+/// accounts leave an empty slot behind, so cursors stay valid.
+pub fn read_replica_account_ids_from_storage() -> Vector<AccountId> {
+    if let Some(content) = env::storage_read(REPLICA_ACCOUNT_IDS.as_bytes()) {
+        Vector::try_from_slice(&content).expect("deserialize replica account ids failed.")
+    } else {
+        Vector::new(StorageKey::ReplicaAccountIds)
+    }
+}
+
+pub fn write_replica_account_ids_to_storage(account_ids: Vector<AccountId>) {
+    env::storage_write(
+        REPLICA_ACCOUNT_IDS.as_bytes(),
+        &account_ids.try_to_vec().unwrap(),
+    );
+}
+
+pub fn read_replica_account_positions_from_storage() -> LookupMap<AccountId, u64> {
+    if let Some(content) = env::storage_read(REPLICA_ACCOUNT_POSITIONS.as_bytes()) {
+        LookupMap::try_from_slice(&content).expect("deserialize replica account positions failed.")
+    } else {
+        LookupMap::new(StorageKey::ReplicaAccountPositions)
+    }
+}
+
+pub fn write_replica_account_positions_to_storage(positions: LookupMap<AccountId, u64>) {
+    env::storage_write(
+        REPLICA_ACCOUNT_POSITIONS.as_bytes(),
+        &positions.try_to_vec().unwrap(),
+    );
+}
+
+/// Appends the account to the export index unless it's there already, returns whether it was added.
+/// New accounts pay for the entries with their registration fee, see REPLICA_INDEX_STORAGE.
+pub fn index_replica_account(account_id: &AccountId) -> bool {
+    let mut positions = read_replica_account_positions_from_storage();
+    if positions.contains_key(account_id) {
+        return false;
+    }
+    let mut account_ids = read_replica_account_ids_from_storage();
+    positions.insert(account_id, &account_ids.len());
+    account_ids.push(account_id);
+    write_replica_account_ids_to_storage(account_ids);
+    write_replica_account_positions_to_storage(positions);
+    write_replica_account_count_to_storage(read_replica_account_count_from_storage() + 1);
+    true
+}
+
+/// Drops the account from the export index, its slot is emptied in place.
+pub fn unindex_replica_account(account_id: &AccountId) {
+    let mut positions = read_replica_account_positions_from_storage();
+    if let Some(position) = positions.remove(account_id) {
+        let mut account_ids = read_replica_account_ids_from_storage();
+        account_ids.replace(position, &AccountId::new());
+        write_replica_account_ids_to_storage(account_ids);
+        write_replica_account_positions_to_storage(positions);
+        write_replica_account_count_to_storage(read_replica_account_count_from_storage() - 1);
+    }
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Index accounts registered before the export index existed, skipping unknown
+    /// and already indexed ones. Returns how many were added.
+    #[payable]
+    pub fn backfill_replica_account_index(&mut self, account_ids: Vec<ValidAccountId>) -> u32 {
+        assert_one_yocto();
+        assert!(self.is_owner_or_guardians(), "{}", ERR100_NOT_ALLOWED);
+        audit_privileged_action("backfill_replica_account_index");
+        let added = account_ids
+            .iter()
+            .filter(|account_id| self.accounts.contains_key(account_id.as_ref()) && index_replica_account(account_id.as_ref()))
+            .count() as u32;
+        if added > 0 {
+            bump_state_version();
+        }
+        added
+    }
+
+    pub fn get_state_version(&self) -> U64 {
+        read_state_version_from_storage().into()
+    }
+
+    /// Returns registered accounts of the export index from `cursor` on, at most `limit` slots.
+    pub fn get_replica_accounts(&self, cursor: Option<U64>, limit: u64) -> ReplicaPage<ReplicaAccount> {
+        let account_ids = read_replica_account_ids_from_storage();
+        let from_index = cursor.map(|cursor| cursor.0).unwrap_or(0);
+        let end = std::cmp::min(from_index + std::cmp::min(limit, MAX_REPLICA_PAGE_SIZE), account_ids.len());
+        let items = (from_index..end)
+            .filter_map(|index| {
+                let account_id = account_ids.get(index).unwrap();
+                self.internal_get_account(&account_id).map(|account| ReplicaAccount {
+                    storage_deposit: U128(account.near_amount),
+                    deposits: account.get_tokens()
+                        .iter()
+                        .map(|token| (token.clone(), U128(account.get_balance(token).unwrap())))
+                        .collect(),
+                    account_id,
+                })
+            })
+            .collect();
+        ReplicaPage {
+            items,
+            next_cursor: if end < account_ids.len() { Some(U64(end)) } else { None },
+            state_version: read_state_version_from_storage().into(),
+            block_height: env::block_index().into(),
+        }
+    }
+
+    /// Returns pools from `cursor` on, at most `limit` of them.
+    pub fn get_replica_pools(&self, cursor: Option<U64>, limit: u64) -> ReplicaPage<ReplicaPool> {
+        let from_index = cursor.map(|cursor| cursor.0).unwrap_or(0);
+        let end = std::cmp::min(from_index + std::cmp::min(limit, MAX_REPLICA_PAGE_SIZE), self.pools.len());
+        ReplicaPage {
+            items: (from_index..end)
+                .map(|pool_id| ReplicaPool { pool_id, pool: self.get_pool(pool_id) })
+                .collect(),
+            next_cursor: if end < self.pools.len() { Some(U64(end)) } else { None },
+            state_version: read_state_version_from_storage().into(),
+            block_height: env::block_index().into(),
+        }
+    }
+}
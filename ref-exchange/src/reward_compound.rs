@@ -0,0 +1,212 @@
+use crate::*;
+use crate::utils::{u128_ratio, FEE_DIVISOR, U256};
+use ref_exchange_math::simple;
+
+/// Slippage, in bps, compounding accepts for accounts that didn't set their own.
+pub const DEFAULT_COMPOUND_MAX_SLIPPAGE_BPS: u32 = 100;
+/// Cached prices older than this aren't used to check compounding against.
+pub const COMPOUND_PRICE_MAX_AGE_SEC: u32 = 3600;
+
+/// Accounts whose rewards anyone may compound into pool shares for them,
+/// with the slippage from cached prices, in bps, each accepts.
+pub fn read_auto_compound_accounts_from_storage() -> LookupMap<AccountId, u32> {
+    if let Some(content) = env::storage_read(AUTO_COMPOUND_ACCOUNTS.as_bytes()) {
+        LookupMap::try_from_slice(&content).expect("deserialize auto compound accounts failed.")
+    } else {
+        LookupMap::new(StorageKey::AutoCompoundAccounts)
+    }
+}
+
+pub fn write_auto_compound_accounts_to_storage(accounts: LookupMap<AccountId, u32>) {
+    env::storage_write(
+        AUTO_COMPOUND_ACCOUNTS.as_bytes(),
+        &accounts.try_to_vec().unwrap(),
+    );
+}
+
+/// Amount of `token_out` worth `amount_in` of `token_in` by fresh cached prices.
+fn cached_price_out(token_in: &AccountId, amount_in: Balance, token_out: &AccountId) -> Option<Balance> {
+    let cached_prices = read_cached_prices_from_storage();
+    let price_in = cached_prices.get(token_in).filter(|price| price.is_fresh(Some(COMPOUND_PRICE_MAX_AGE_SEC)))?;
+    let price_out = cached_prices.get(token_out).filter(|price| price.is_fresh(Some(COMPOUND_PRICE_MAX_AGE_SEC)))?;
+    if price_out.multiplier == 0 {
+        return None;
+    }
+    Some((U256::from(amount_in) * U256::from(price_in.multiplier) * U256::from(10).pow(U256::from(price_out.decimals))
+        / U256::from(10).pow(U256::from(price_in.decimals)) / U256::from(price_out.multiplier)).as_u128())
+}
+
+/// Whether the pool ratio is within `max_slippage_bps` of the cached prices of its tokens.
+fn pool_price_within(tokens: &[AccountId], reserves: &[Balance], max_slippage_bps: u32) -> bool {
+    match cached_price_out(&tokens[0], reserves[0], &tokens[1]) {
+        Some(fair) if fair > 0 => {
+            let deviation = std::cmp::max(fair, reserves[1]) - std::cmp::min(fair, reserves[1]);
+            U256::from(deviation) * U256::from(FEE_DIVISOR) <= U256::from(fair) * U256::from(max_slippage_bps)
+        }
+        _ => false,
+    }
+}
+
+impl Contract {
+    /// Takes the account's unclaimed LP incentive of the pool and its pending fee rebates,
+    /// as far as they are in the pool's tokens, into its inner account. Returns them per pool token.
+    fn internal_take_compoundable_rewards(&mut self, account_id: &AccountId, pool_id: u64, pool: &Pool) -> Vec<Balance> {
+        let tokens = pool.tokens();
+        let mut amounts = vec![0; tokens.len()];
+        let mut account = self.internal_unwrap_account(account_id);
+
+        self.internal_settle_lp_incentives(pool_id, pool, &[account_id]);
+        let mut lp_incentives = read_lp_incentives_from_storage();
+        if let Some(mut incentive) = lp_incentives.get(&pool_id) {
+            if let Some(idx) = tokens.iter().position(|id| id == &incentive.token_id) {
+                let mut lp_incentive_positions = read_lp_incentive_positions_from_storage();
+                let mut positions = lp_incentive_positions.get(account_id).unwrap_or_default();
+                let owed = positions.get_mut(&pool_id).map(|position| std::mem::take(&mut position.owed)).unwrap_or(0);
+                if owed > 0 {
+                    account.deposit(&incentive.token_id, owed);
+                    amounts[idx] += owed;
+                    incentive.claimed += owed;
+                    incentive.escrowed -= owed;
+                    release_locked_deposit(&incentive.funder_id, &incentive.token_id, owed);
+                    lp_incentives.insert(&pool_id, &incentive);
+                    write_lp_incentives_to_storage(lp_incentives);
+                }
+                lp_incentive_positions.insert(account_id, &positions);
+                write_lp_incentive_positions_to_storage(lp_incentive_positions);
+            }
+        }
+
+        let mut pending_fee_rebates = read_pending_fee_rebates_from_storage();
+        if let Some(mut pending) = pending_fee_rebates.get(account_id) {
+            let mut pending_totals = read_pending_fee_rebate_totals_from_storage();
+            for (idx, token_id) in tokens.iter().enumerate() {
+                if let Some(rebate) = pending.remove(token_id) {
+                    *pending_totals.get_mut(token_id).unwrap() -= rebate;
+                    account.deposit(token_id, rebate);
+                    amounts[idx] += rebate;
+                }
+            }
+            if pending.is_empty() {
+                pending_fee_rebates.remove(account_id);
+            } else {
+                pending_fee_rebates.insert(account_id, &pending);
+            }
+            write_pending_fee_rebates_to_storage(pending_fee_rebates);
+            write_pending_fee_rebate_totals_to_storage(pending_totals);
+        }
+
+        self.internal_save_account(account_id, account);
+        amounts
+    }
+
+    /// Swaps the excess of the rewards over the pool ratio on the account's inner balances,
+    /// so the rest can be added as liquidity in full. Returns the amounts to add, or None if the
+    /// pool price or the swap is off the cached prices by more than `max_slippage_bps`.
+    fn internal_balance_rewards(&mut self, account_id: &AccountId, pool_id: u64, mut amounts: Vec<Balance>, max_slippage_bps: u32) -> Option<Vec<Balance>> {
+        let pool = self.internal_get_pool(pool_id);
+        let (tokens, reserves) = (pool.tokens().to_vec(), pool.get_amounts());
+        if reserves.contains(&0) || !pool_price_within(&tokens, &reserves, max_slippage_bps) {
+            return None;
+        }
+        // Value of the other token's rewards in the token the pool holds too much of.
+        let (in_idx, excess) = if u128_ratio(amounts[0], reserves[1], reserves[0]) > amounts[1] {
+            (0, amounts[0] - u128_ratio(amounts[1], reserves[0], reserves[1]))
+        } else {
+            (1, amounts[1] - u128_ratio(amounts[0], reserves[1], reserves[0]))
+        };
+        let swap_amount = simple::get_zap_swap_amount(reserves[in_idx], excess, pool.get_fee());
+        if swap_amount == 0 {
+            return Some(amounts);
+        }
+        assert_account_not_denied(account_id);
+        self.assert_swap_screen_passed(pool_id, account_id);
+        let out_idx = 1 - in_idx;
+        let fair_out = cached_price_out(&tokens[in_idx], swap_amount, &tokens[out_idx])?;
+        let min_amount_out = u128_ratio(fair_out, (FEE_DIVISOR - max_slippage_bps) as u128, FEE_DIVISOR as u128);
+        let quote = self.get_return(
+            pool_id,
+            tokens[in_idx].clone().try_into().unwrap(),
+            U128(swap_amount),
+            tokens[out_idx].clone().try_into().unwrap(),
+        ).0;
+        if quote < min_amount_out {
+            return None;
+        }
+        let amount_out = self.internal_pool_swap(pool_id, &tokens[in_idx], swap_amount, &tokens[out_idx], min_amount_out, &None);
+        let mut account = self.internal_unwrap_account(account_id);
+        account.withdraw(&tokens[in_idx], swap_amount);
+        account.deposit(&tokens[out_idx], amount_out);
+        self.internal_save_account(account_id, account);
+        amounts[in_idx] -= swap_amount;
+        amounts[out_idx] += amount_out;
+        Some(amounts)
+    }
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Let anyone, e.g. a keeper, compound the caller's rewards into pool shares for it,
+    /// as long as the pool price and the swap stay within `max_slippage_bps` of the cached prices,
+    /// DEFAULT_COMPOUND_MAX_SLIPPAGE_BPS if not given. The bound applies to the caller's own compounding too.
+    /// Attached deposit covers the record, the rest is refunded.
+    #[payable]
+    pub fn set_reward_auto_compound(&mut self, enabled: bool, max_slippage_bps: Option<u32>) {
+        let prev_storage = env::storage_usage();
+        let account_id = env::predecessor_account_id();
+        assert!(self.accounts.get(&account_id).is_some(), "{}", ERR10_ACC_NOT_REGISTERED);
+        let mut accounts = read_auto_compound_accounts_from_storage();
+        if enabled {
+            let max_slippage_bps = max_slippage_bps.unwrap_or(DEFAULT_COMPOUND_MAX_SLIPPAGE_BPS);
+            assert!(max_slippage_bps < FEE_DIVISOR, "Invalid max slippage");
+            accounts.insert(&account_id, &max_slippage_bps);
+        } else {
+            accounts.remove(&account_id);
+        }
+        write_auto_compound_accounts_to_storage(accounts);
+        self.internal_check_storage(prev_storage);
+    }
+
+    /// Returns the slippage bound the account compounds with if it opted in.
+    pub fn get_reward_auto_compound(&self, account_id: ValidAccountId) -> Option<u32> {
+        read_auto_compound_accounts_from_storage().get(account_id.as_ref())
+    }
+
+    /// Turn the account's unclaimed LP incentive of a simple pool and its pending fee rebates
+    /// in the pool's tokens into more shares of the pool, swapping part of them to match
+    /// the pool ratio. Callable by the account, or by anyone if it opted in to auto compounding.
+    /// Nothing is swapped or added when the pool is off the cached prices of its tokens, or has none fresh.
+    /// What can't be added stays in the inner account; storage of new records is paid from
+    /// the account's storage deposit. Returns the minted shares.
+    pub fn compound_rewards(&mut self, account_id: ValidAccountId, pool_id: u64) -> U128 {
+        self.assert_contract_running();
+        let account_id: AccountId = account_id.into();
+        let max_slippage_bps = read_auto_compound_accounts_from_storage().get(&account_id);
+        assert!(
+            env::predecessor_account_id() == account_id || max_slippage_bps.is_some(),
+            "{}", ERR100_NOT_ALLOWED
+        );
+        let max_slippage_bps = max_slippage_bps.unwrap_or(DEFAULT_COMPOUND_MAX_SLIPPAGE_BPS);
+        let pool = self.internal_get_pool(pool_id);
+        assert!(matches!(pool, Pool::SimplePool(_)), "Not simple pool");
+        let prev_storage = env::storage_usage();
+        let amounts = self.internal_take_compoundable_rewards(&account_id, pool_id, &pool);
+        let shares = if amounts.iter().all(|amount| *amount == 0) {
+            0
+        } else if let Some(amounts) = self.internal_balance_rewards(&account_id, pool_id, amounts, max_slippage_bps) {
+            self.internal_update_unit_share_cumulative_info(pool_id);
+            self.internal_add_liquidity(pool_id, &account_id, amounts.into_iter().map(U128).collect(), None)
+        } else {
+            log!("Rewards of {} left in the inner account, pool {} is off its cached prices", account_id, pool_id);
+            0
+        };
+        if env::storage_usage() > prev_storage {
+            let storage_cost = (env::storage_usage() - prev_storage) as Balance * env::storage_byte_cost();
+            let mut account = self.internal_unwrap_account(&account_id);
+            self.internal_top_up_storage(&account_id, &mut account, storage_cost);
+            account.near_amount = account.near_amount.checked_sub(storage_cost).expect(ERR11_INSUFFICIENT_STORAGE);
+            self.internal_save_account(&account_id, account);
+        }
+        log!("Compounded rewards of {} into {} shares of pool {}", account_id, shares, pool_id);
+        U128(shares)
+    }
+}
@@ -0,0 +1,172 @@
+use crate::*;
+use crate::utils::{ext_self, u64_dec_format, GAS_FOR_FT_TRANSFER, GAS_FOR_RESOLVE_TRANSFER};
+use near_contract_standards::fungible_token::core_impl::ext_fungible_token;
+use near_sdk::json_types::Base58CryptoHash;
+use near_sdk::{is_promise_success, CryptoHash, Timestamp};
+use std::convert::TryInto;
+
+/// Merkle root of the balances users are entitled to after an incident, claimable while the
+/// contract is paused. Leaves are sha256 of "{account_id}:{token_id}:{amount}" with the total
+/// entitlement in decimal, parents sha256 of their two children in ascending byte order.
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone)]
+#[serde(crate = "near_sdk::serde")]
+#[cfg_attr(not(target_arch = "wasm32"), derive(Debug))]
+pub struct SafeModeSnapshot {
+    pub root: Base58CryptoHash,
+    #[serde(with = "u64_dec_format")]
+    pub published_at: Timestamp,
+}
+
+pub fn read_safe_mode_snapshot_from_storage() -> Option<SafeModeSnapshot> {
+    env::storage_read(SAFE_MODE_SNAPSHOT.as_bytes())
+        .map(|content| SafeModeSnapshot::try_from_slice(&content).expect("deserialize safe mode snapshot failed."))
+}
+
+pub fn write_safe_mode_snapshot_to_storage(snapshot: Option<SafeModeSnapshot>) {
+    match snapshot {
+        Some(snapshot) => {
+            env::storage_write(SAFE_MODE_SNAPSHOT.as_bytes(), &snapshot.try_to_vec().unwrap());
+        }
+        None => {
+            env::storage_remove(SAFE_MODE_SNAPSHOT.as_bytes());
+        }
+    }
+}
+
+/// Amounts claimed so far per account and token, kept across republished snapshots
+/// so a corrected entitlement only pays out the difference.
+pub fn read_safe_mode_claims_from_storage() -> LookupMap<(AccountId, AccountId), Balance> {
+    if let Some(content) = env::storage_read(SAFE_MODE_CLAIMS.as_bytes()) {
+        LookupMap::try_from_slice(&content).expect("deserialize safe mode claims failed.")
+    } else {
+        LookupMap::new(StorageKey::SafeModeClaims)
+    }
+}
+
+pub fn write_safe_mode_claims_to_storage(safe_mode_claims: LookupMap<(AccountId, AccountId), Balance>) {
+    env::storage_write(
+        SAFE_MODE_CLAIMS.as_bytes(),
+        &safe_mode_claims.try_to_vec().unwrap(),
+    );
+}
+
+pub fn safe_mode_leaf(account_id: &AccountId, token_id: &AccountId, amount: Balance) -> CryptoHash {
+    env::sha256(format!("{}:{}:{}", account_id, token_id, amount).as_bytes()).try_into().unwrap()
+}
+
+pub fn safe_mode_root(leaf: CryptoHash, proof: &[Base58CryptoHash]) -> CryptoHash {
+    proof.iter().fold(leaf, |node, sibling| {
+        let sibling: CryptoHash = (*sibling).into();
+        let (left, right) = if node <= sibling { (node, sibling) } else { (sibling, node) };
+        env::sha256(&[left, right].concat()).try_into().unwrap()
+    })
+}
+
+fn update_safe_mode_claim(account_id: &AccountId, token_id: &AccountId, update: impl FnOnce(Balance) -> Balance) {
+    let mut safe_mode_claims = read_safe_mode_claims_from_storage();
+    let key = (account_id.clone(), token_id.clone());
+    let claimed = update(safe_mode_claims.get(&key).unwrap_or(0));
+    safe_mode_claims.insert(&key, &claimed);
+    write_safe_mode_claims_to_storage(safe_mode_claims);
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Publish the root of entitled balances while the contract is paused. Republishing
+    /// replaces the root, claims already paid count against the new entitlements.
+    #[payable]
+    pub fn publish_safe_mode_snapshot(&mut self, root: Base58CryptoHash) {
+        assert_one_yocto();
+        self.assert_owner();
+        audit_privileged_action("publish_safe_mode_snapshot");
+        assert!(self.state == RunningState::Paused, "Safe mode needs the contract paused");
+        write_safe_mode_snapshot_to_storage(Some(SafeModeSnapshot {
+            root,
+            published_at: env::block_timestamp(),
+        }));
+    }
+
+    /// Stop claims, e.g. once accounts are reconciled and before resuming the contract.
+    #[payable]
+    pub fn clear_safe_mode_snapshot(&mut self) {
+        assert_one_yocto();
+        self.assert_owner();
+        audit_privileged_action("clear_safe_mode_snapshot");
+        assert!(read_safe_mode_snapshot_from_storage().is_some(), "No safe mode snapshot");
+        write_safe_mode_snapshot_to_storage(None);
+    }
+
+    /// Withdraw the part of the caller's entitlement not claimed yet, proven against the
+    /// published root. The claim is debited from the caller's inner balance of the token, as far
+    /// as it goes, so the same funds can't be withdrawn again once the contract resumes.
+    #[payable]
+    pub fn claim_safe_mode_withdrawal(&mut self, token_id: ValidAccountId, amount: U128, proof: Vec<Base58CryptoHash>) -> Promise {
+        assert_one_yocto();
+        assert!(self.state == RunningState::Paused, "Safe mode needs the contract paused");
+        let snapshot = read_safe_mode_snapshot_from_storage().expect("No safe mode snapshot");
+        let account_id = env::predecessor_account_id();
+        let token_id: AccountId = token_id.into();
+        let root = safe_mode_root(safe_mode_leaf(&account_id, &token_id, amount.0), &proof);
+        assert!(root == CryptoHash::from(snapshot.root), "Invalid safe mode proof");
+        let claimed = read_safe_mode_claims_from_storage().get(&(account_id.clone(), token_id.clone())).unwrap_or(0);
+        let claimable = amount.0.checked_sub(claimed).filter(|claimable| *claimable > 0).expect("Nothing to claim");
+        update_safe_mode_claim(&account_id, &token_id, |_| amount.0);
+        let debited = match self.internal_get_account(&account_id) {
+            Some(mut account) => {
+                let debited = std::cmp::min(account.get_balance(&token_id).unwrap_or(0), claimable);
+                if debited > 0 {
+                    account.withdraw(&token_id, debited);
+                    self.internal_save_account(&account_id, account);
+                }
+                debited
+            }
+            None => 0,
+        };
+        log!("Safe mode claim of {} {} by {}", claimable, token_id, account_id);
+        ext_fungible_token::ft_transfer(
+            account_id.clone(),
+            U128(claimable),
+            None,
+            &token_id,
+            1,
+            GAS_FOR_FT_TRANSFER,
+        )
+        .then(ext_self::exchange_callback_post_safe_mode_claim(
+            token_id.clone(),
+            account_id,
+            U128(claimable),
+            U128(debited),
+            &env::current_account_id(),
+            0,
+            GAS_FOR_RESOLVE_TRANSFER,
+        ))
+    }
+
+    /// Reopens the claim and credits back what was debited if the transfer failed.
+    #[private]
+    pub fn exchange_callback_post_safe_mode_claim(&mut self, token_id: AccountId, account_id: AccountId, amount: U128, debited: U128) {
+        if is_promise_success() {
+            update_token_ledger(&token_id, |ledger| ledger.total -= amount.0 as i128);
+        } else {
+            update_safe_mode_claim(&account_id, &token_id, |claimed| claimed - amount.0);
+            if debited.0 > 0 {
+                match self.internal_get_account(&account_id) {
+                    Some(mut account) => {
+                        account.deposit(&token_id, debited.0);
+                        self.internal_save_account(&account_id, account);
+                    }
+                    None => self.internal_hold_unclaimed_withdrawal(&account_id, &token_id, debited.0),
+                }
+            }
+            log!("Safe mode claim of {} {} by {} failed", amount.0, token_id, account_id);
+        }
+    }
+
+    pub fn get_safe_mode_snapshot(&self) -> Option<SafeModeSnapshot> {
+        read_safe_mode_snapshot_from_storage()
+    }
+
+    pub fn get_safe_mode_claimed(&self, account_id: ValidAccountId, token_id: ValidAccountId) -> U128 {
+        U128(read_safe_mode_claims_from_storage().get(&(account_id.into(), token_id.into())).unwrap_or(0))
+    }
+}
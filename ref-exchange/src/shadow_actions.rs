@@ -47,7 +47,7 @@ impl Contract {
         for pool_id in pool_ids {
             let shadow_id = pool_id_to_shadow_id(pool_id);
             if let Some(amounts) = self.get_unit_share_twap_token_amounts(pool_id) {
-                let pool = self.pools.get(pool_id).expect(ERR85_NO_POOL);
+                let pool = self.internal_get_pool(pool_id);
                 let share_decimals = pool.get_share_decimal();
                 let tokens = pool.tokens().iter().zip(amounts.into_iter()).map(|(token_id, amount)| TokenAmount { token_id: token_id.clone(), amount }).collect();
                 result.insert(shadow_id, UnitShareTokens{
@@ -66,8 +66,9 @@ impl Contract {
         let shadow_id = pool_id_to_shadow_id(pool_id);
         let prev_storage = env::storage_usage();
         let sender_id = env::predecessor_account_id();
+        assert_no_in_flight(&sender_id, Some(&shadow_in_flight_key(pool_id)));
         let mut account = self.internal_unwrap_account(&sender_id);
-        let pool = self.pools.get(pool_id).expect(ERR85_NO_POOL);
+        let pool = self.internal_get_pool(pool_id);
         let total_shares = pool.share_balances(&sender_id);
         let (amount, max_amount) = match action {
             ShadowActions::ToFarming => {
@@ -79,10 +80,12 @@ impl Contract {
                 (amount.unwrap_or(U128(available_amount)).0, available_amount)
             }
             ShadowActions::ToBurrowland => {
+                // Collateral can be liquidated, so locked shares stay out of burrowland.
+                let unlocked_shares = total_shares.saturating_sub(self.internal_locked_shares(&sender_id, pool_id));
                 let available_amount = if let Some(record) = account.get_shadow_record(pool_id) {
-                    record.available_burrowland_shares(total_shares)
+                    unlocked_shares.saturating_sub(record.shadow_in_burrow)
                 } else {
-                    total_shares
+                    unlocked_shares
                 };
                 (amount.unwrap_or(U128(available_amount)).0, available_amount)
             }
@@ -120,6 +123,7 @@ impl Contract {
                 account.update_shadow_record(pool_id, &action, amount);
                 self.internal_save_account(&sender_id, account);
                 let storage_fee = self.internal_check_storage(prev_storage);
+                acquire_in_flight(&sender_id, &shadow_in_flight_key(pool_id));
                 ext_shadow_receiver::on_cast_shadow(
                         sender_id.clone(),
                         shadow_id,
@@ -143,6 +147,7 @@ impl Contract {
                     .into()
             }
             ShadowActions::FromFarming | ShadowActions::FromBurrowland => {
+                acquire_in_flight(&sender_id, &shadow_in_flight_key(pool_id));
                 ext_shadow_receiver::on_remove_shadow(
                         sender_id.clone(),
                         shadow_id,
@@ -172,7 +177,7 @@ impl Contract {
         assert!(self.burrowland_id == env::predecessor_account_id());
         let pool_id = shadow_id_to_pool_id(&shadow_id);
         
-        let mut pool = self.pools.get(pool_id).expect(ERR85_NO_POOL);
+        let mut pool = self.internal_get_pool(pool_id);
         self.assert_no_frozen_tokens(pool.tokens());
         let total_shares = pool.share_balances(&liquidation_account_id);
 
@@ -199,6 +204,7 @@ impl Contract {
         self.internal_save_account(&liquidation_account_id, liquidation_account);
         
         let mut liquidator_account = self.internal_unwrap_account(&liquidator_account_id);
+        self.internal_settle_lp_fees(pool_id, &pool, &[&liquidation_account_id]);
         let amounts = pool.remove_liquidity(
             &liquidation_account_id,
             liquidate_share_amount.0,
@@ -249,6 +255,7 @@ impl Contract {
         amount: U128,
         storage_fee: U128
     ) -> bool {
+        release_in_flight(&sender_id, &shadow_in_flight_key(pool_id));
         if !is_promise_success() {
             let mut account = self.internal_unwrap_account(&sender_id); 
             match action {
@@ -0,0 +1,166 @@
+use crate::*;
+use crate::utils::u64_dec_format;
+use near_sdk::Timestamp;
+
+/// Checkpoints kept per pool, creating another one drops the oldest.
+pub const MAX_SHARE_CHECKPOINTS: usize = 8;
+
+/// Shares of a pool as of a checkpoint. Accounts are recorded right before their shares
+/// first change after the checkpoint, an account not recorded still holds its shares from then.
+#[derive(BorshSerialize, BorshDeserialize)]
+pub struct ShareCheckpoint {
+    pub checkpoint_id: u64,
+    pub created_at: Timestamp,
+    pub shares_total_supply: Balance,
+    pub recorded: UnorderedMap<AccountId, Balance>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+#[cfg_attr(not(target_arch = "wasm32"), derive(Debug))]
+pub struct ShareCheckpointInfo {
+    pub checkpoint_id: u64,
+    #[serde(with = "u64_dec_format")]
+    pub created_at: Timestamp,
+    pub shares_total_supply: U128,
+    pub recorded_accounts: u64,
+}
+
+impl From<&ShareCheckpoint> for ShareCheckpointInfo {
+    fn from(checkpoint: &ShareCheckpoint) -> Self {
+        ShareCheckpointInfo {
+            checkpoint_id: checkpoint.checkpoint_id,
+            created_at: checkpoint.created_at,
+            shares_total_supply: U128(checkpoint.shares_total_supply),
+            recorded_accounts: checkpoint.recorded.len(),
+        }
+    }
+}
+
+pub fn read_share_checkpoints_from_storage() -> LookupMap<u64, Vec<ShareCheckpoint>> {
+    if let Some(content) = env::storage_read(SHARE_CHECKPOINTS.as_bytes()) {
+        LookupMap::try_from_slice(&content).expect("deserialize share checkpoints failed.")
+    } else {
+        LookupMap::new(StorageKey::ShareCheckpoints)
+    }
+}
+
+pub fn write_share_checkpoints_to_storage(share_checkpoints: LookupMap<u64, Vec<ShareCheckpoint>>) {
+    env::storage_write(
+        SHARE_CHECKPOINTS.as_bytes(),
+        &share_checkpoints.try_to_vec().unwrap(),
+    );
+}
+
+pub fn read_next_share_checkpoint_id_from_storage() -> u64 {
+    if let Some(content) = env::storage_read(NEXT_SHARE_CHECKPOINT_ID.as_bytes()) {
+        u64::try_from_slice(&content).expect("deserialize next share checkpoint id failed.")
+    } else {
+        0
+    }
+}
+
+pub fn write_next_share_checkpoint_id_to_storage(next_share_checkpoint_id: u64) {
+    env::storage_write(
+        NEXT_SHARE_CHECKPOINT_ID.as_bytes(),
+        &next_share_checkpoint_id.try_to_vec().unwrap(),
+    );
+}
+
+impl Contract {
+    /// Records the current shares of the given accounts in the pool's checkpoints they are
+    /// missing from, must run before their shares change.
+    pub(crate) fn internal_record_share_checkpoints(&self, pool_id: u64, pool: &Pool, account_ids: &[&AccountId]) {
+        let mut share_checkpoints = read_share_checkpoints_from_storage();
+        let mut checkpoints = match share_checkpoints.get(&pool_id) {
+            Some(checkpoints) => checkpoints,
+            None => return,
+        };
+        for account_id in account_ids {
+            // an account recorded in a checkpoint is recorded in all older ones as well
+            for checkpoint in checkpoints.iter_mut().rev() {
+                if checkpoint.recorded.get(account_id).is_some() {
+                    break;
+                }
+                checkpoint.recorded.insert(account_id, &pool.share_balances(account_id));
+            }
+        }
+        share_checkpoints.insert(&pool_id, &checkpoints);
+        write_share_checkpoints_to_storage(share_checkpoints);
+    }
+
+    /// Takes a checkpoint of the pool's current shares and returns its id.
+    pub(crate) fn internal_create_share_checkpoint(&self, pool_id: u64, pool: &Pool) -> u64 {
+        let checkpoint_id = read_next_share_checkpoint_id_from_storage();
+        write_next_share_checkpoint_id_to_storage(checkpoint_id + 1);
+        let mut share_checkpoints = read_share_checkpoints_from_storage();
+        let mut checkpoints = share_checkpoints.get(&pool_id).unwrap_or_default();
+        if checkpoints.len() >= MAX_SHARE_CHECKPOINTS {
+            let mut oldest = checkpoints.remove(0);
+            oldest.recorded.clear();
+            log!("Share checkpoint {} of pool {} dropped", oldest.checkpoint_id, pool_id);
+        }
+        checkpoints.push(ShareCheckpoint {
+            checkpoint_id,
+            created_at: env::block_timestamp(),
+            shares_total_supply: pool.share_total_balance(),
+            recorded: UnorderedMap::new(StorageKey::ShareCheckpointRecords { checkpoint_id }),
+        });
+        share_checkpoints.insert(&pool_id, &checkpoints);
+        write_share_checkpoints_to_storage(share_checkpoints);
+        checkpoint_id
+    }
+
+    /// Shares the account held in the pool at the checkpoint, None if the checkpoint was dropped.
+    pub(crate) fn internal_share_checkpoint_balance(&self, pool_id: u64, pool: &Pool, checkpoint_id: u64, account_id: &AccountId) -> Option<Balance> {
+        read_share_checkpoints_from_storage()
+            .get(&pool_id)
+            .unwrap_or_default()
+            .iter()
+            .find(|checkpoint| checkpoint.checkpoint_id == checkpoint_id)
+            .map(|checkpoint| checkpoint.recorded.get(account_id).unwrap_or_else(|| pool.share_balances(account_id)))
+    }
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Take a checkpoint of the pool's shares, e.g. for an airdrop to its LPs, returns its id.
+    #[payable]
+    pub fn create_share_checkpoint(&mut self, pool_id: u64) -> u64 {
+        assert_one_yocto();
+        self.assert_owner();
+        audit_privileged_action("create_share_checkpoint");
+        let pool = self.internal_get_pool(pool_id);
+        self.internal_create_share_checkpoint(pool_id, &pool)
+    }
+
+    pub fn get_share_checkpoints(&self, pool_id: u64) -> Vec<ShareCheckpointInfo> {
+        read_share_checkpoints_from_storage()
+            .get(&pool_id)
+            .unwrap_or_default()
+            .iter()
+            .map(|checkpoint| checkpoint.into())
+            .collect()
+    }
+
+    /// Shares the given accounts held in the pool at the checkpoint.
+    pub fn get_share_checkpoint_balances(
+        &self,
+        pool_id: u64,
+        checkpoint_id: u64,
+        account_ids: Vec<ValidAccountId>,
+    ) -> Vec<U128> {
+        let checkpoints = read_share_checkpoints_from_storage().get(&pool_id).unwrap_or_default();
+        let checkpoint = checkpoints
+            .iter()
+            .find(|checkpoint| checkpoint.checkpoint_id == checkpoint_id)
+            .expect("No share checkpoint");
+        let pool = self.internal_get_pool(pool_id);
+        account_ids
+            .iter()
+            .map(|account_id| {
+                U128(checkpoint.recorded.get(account_id.as_ref()).unwrap_or_else(|| pool.share_balances(account_id.as_ref())))
+            })
+            .collect()
+    }
+}
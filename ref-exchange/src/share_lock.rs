@@ -0,0 +1,250 @@
+use crate::*;
+use crate::utils::{u128_dec_format, u64_dec_format, U256};
+use near_sdk::json_types::U64;
+use near_sdk::serde_json::json;
+use near_sdk::Timestamp;
+
+/// Bounds the locks an account can hold, every share movement sums them up.
+pub const MAX_SHARE_LOCKS_PER_ACCOUNT: usize = 20;
+
+/// Shares of a pool an account has locked itself out of moving, also exposed as an NFT.
+/// Fully locked until `start_time`, then released linearly until `end_time`;
+/// a plain lock has `start_time == end_time`.
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone)]
+#[serde(crate = "near_sdk::serde")]
+#[cfg_attr(not(target_arch = "wasm32"), derive(Debug))]
+pub struct ShareLock {
+    pub account_id: AccountId,
+    pub pool_id: u64,
+    #[serde(with = "u128_dec_format")]
+    pub amount: Balance,
+    #[serde(with = "u64_dec_format")]
+    pub start_time: Timestamp,
+    #[serde(with = "u64_dec_format")]
+    pub end_time: Timestamp,
+}
+
+impl ShareLock {
+    pub fn locked_amount(&self, current_time: Timestamp) -> Balance {
+        if current_time >= self.end_time {
+            0
+        } else if current_time <= self.start_time {
+            self.amount
+        } else {
+            (U256::from(self.amount) * U256::from(self.end_time - current_time)
+                / U256::from(self.end_time - self.start_time))
+                .as_u128()
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+#[cfg_attr(not(target_arch = "wasm32"), derive(Debug))]
+pub struct ShareLockInfo {
+    pub lock_id: u64,
+    pub lock: ShareLock,
+    pub locked_amount: U128,
+}
+
+pub fn read_share_locks_from_storage() -> LookupMap<u64, ShareLock> {
+    if let Some(content) = env::storage_read(SHARE_LOCKS.as_bytes()) {
+        LookupMap::try_from_slice(&content).expect("deserialize share locks failed.")
+    } else {
+        LookupMap::new(StorageKey::ShareLocks)
+    }
+}
+
+pub fn write_share_locks_to_storage(share_locks: LookupMap<u64, ShareLock>) {
+    env::storage_write(
+        SHARE_LOCKS.as_bytes(),
+        &share_locks.try_to_vec().unwrap(),
+    );
+}
+
+pub fn read_account_share_locks_from_storage() -> LookupMap<AccountId, Vec<u64>> {
+    if let Some(content) = env::storage_read(ACCOUNT_SHARE_LOCKS.as_bytes()) {
+        LookupMap::try_from_slice(&content).expect("deserialize account share locks failed.")
+    } else {
+        LookupMap::new(StorageKey::AccountShareLocks)
+    }
+}
+
+pub fn write_account_share_locks_to_storage(account_share_locks: LookupMap<AccountId, Vec<u64>>) {
+    env::storage_write(
+        ACCOUNT_SHARE_LOCKS.as_bytes(),
+        &account_share_locks.try_to_vec().unwrap(),
+    );
+}
+
+/// Ids of the share locks that exist, so their NFTs can be counted and listed without
+/// walking every lock id ever issued.
+pub fn read_share_lock_ids_from_storage() -> UnorderedSet<u64> {
+    if let Some(content) = env::storage_read(SHARE_LOCK_IDS.as_bytes()) {
+        UnorderedSet::try_from_slice(&content).expect("deserialize share lock ids failed.")
+    } else {
+        UnorderedSet::new(StorageKey::ShareLockIds)
+    }
+}
+
+pub fn write_share_lock_ids_to_storage(share_lock_ids: UnorderedSet<u64>) {
+    env::storage_write(
+        SHARE_LOCK_IDS.as_bytes(),
+        &share_lock_ids.try_to_vec().unwrap(),
+    );
+}
+
+pub fn read_next_share_lock_id_from_storage() -> u64 {
+    if let Some(content) = env::storage_read(NEXT_SHARE_LOCK_ID.as_bytes()) {
+        u64::try_from_slice(&content).expect("deserialize next share lock id failed.")
+    } else {
+        0
+    }
+}
+
+pub fn write_next_share_lock_id_to_storage(next_share_lock_id: u64) {
+    env::storage_write(
+        NEXT_SHARE_LOCK_ID.as_bytes(),
+        &next_share_lock_id.try_to_vec().unwrap(),
+    );
+}
+
+impl Contract {
+    /// Shares of the account in the given pool still held by its locks, owed to its outgoing streams
+    /// or deployed as protocol-owned liquidity.
+    pub fn internal_locked_shares(&self, account_id: &AccountId, pool_id: u64) -> Balance {
+        let streaming_shares = self.internal_streaming_shares(account_id, pool_id)
+            + self.internal_pol_shares(account_id, pool_id);
+        // Voted shares only have to stay in the account, locked ones can back a vote.
+        let voted_shares = self.internal_voted_shares(account_id, pool_id);
+        let lock_ids = read_account_share_locks_from_storage().get(account_id).unwrap_or_default();
+        if lock_ids.is_empty() {
+            return std::cmp::max(streaming_shares, voted_shares);
+        }
+        let share_locks = read_share_locks_from_storage();
+        let current_time = env::block_timestamp();
+        let locked_shares = streaming_shares + lock_ids.iter()
+            .filter_map(|lock_id| share_locks.get(lock_id))
+            .filter(|lock| lock.pool_id == pool_id)
+            .map(|lock| lock.locked_amount(current_time))
+            .sum::<Balance>();
+        std::cmp::max(locked_shares, voted_shares)
+    }
+
+    /// Panics if moving `amount` out of the account's `total_shares` would touch locked shares.
+    pub(crate) fn assert_shares_unlocked(&self, account_id: &AccountId, pool_id: u64, total_shares: Balance, amount: Balance) {
+        let locked_shares = self.internal_locked_shares(account_id, pool_id);
+        assert!(
+            total_shares.saturating_sub(amount) >= locked_shares,
+            "Not enough unlocked shares"
+        );
+    }
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Lock `amount` of the caller's shares in the pool until `end_time`. With `vesting_start_time`
+    /// the shares are released linearly from that time on instead of all at once at `end_time`.
+    /// Locked shares can't be transferred, removed or used as collateral, the lock can't be undone.
+    /// Attached deposit covers the storage of the lock, the rest is refunded.
+    #[payable]
+    pub fn lock_shares(
+        &mut self,
+        pool_id: u64,
+        amount: U128,
+        end_time: U64,
+        vesting_start_time: Option<U64>,
+    ) -> u64 {
+        self.assert_contract_running();
+        let prev_storage = env::storage_usage();
+        let sender_id = env::predecessor_account_id();
+        let pool = self.internal_get_pool(pool_id);
+        let current_time = env::block_timestamp();
+        let start_time = vesting_start_time.map(|v| v.0).unwrap_or(end_time.0);
+        assert!(amount.0 > 0, "Invalid amount");
+        assert!(end_time.0 > current_time && start_time <= end_time.0, "Invalid lock time");
+
+        let total_shares = pool.share_balances(&sender_id);
+        let shadow_in_burrow = self.internal_get_account(&sender_id)
+            .and_then(|account| account.get_shadow_record(pool_id))
+            .map(|record| record.shadow_in_burrow)
+            .unwrap_or(0);
+        // Shares backing a burrowland loan can be liquidated, so they can't be locked.
+        assert!(
+            self.internal_locked_shares(&sender_id, pool_id) + shadow_in_burrow + amount.0 <= total_shares,
+            "Not enough unlocked shares"
+        );
+
+        let mut account_share_locks = read_account_share_locks_from_storage();
+        let mut share_locks = read_share_locks_from_storage();
+        let mut share_lock_ids = read_share_lock_ids_from_storage();
+        let mut lock_ids = account_share_locks.get(&sender_id).unwrap_or_default();
+        // Drop finished locks before counting.
+        let mut released_ids = vec![];
+        lock_ids.retain(|lock_id| {
+            let finished = share_locks.get(lock_id).map(|lock| lock.locked_amount(current_time) == 0).unwrap_or(true);
+            if finished {
+                share_locks.remove(lock_id);
+                share_lock_ids.remove(lock_id);
+                released_ids.push(lock_id.to_string());
+            }
+            !finished
+        });
+        if !released_ids.is_empty() {
+            emit_nft_event("nft_burn", json!({ "owner_id": sender_id, "token_ids": released_ids }));
+        }
+        assert!(lock_ids.len() < MAX_SHARE_LOCKS_PER_ACCOUNT, "Too many share locks");
+
+        let lock_id = read_next_share_lock_id_from_storage();
+        write_next_share_lock_id_to_storage(lock_id + 1);
+        let lock = ShareLock {
+            account_id: sender_id.clone(),
+            pool_id,
+            amount: amount.0,
+            start_time,
+            end_time: end_time.0,
+        };
+        share_locks.insert(&lock_id, &lock);
+        share_lock_ids.insert(&lock_id);
+        lock_ids.push(lock_id);
+        account_share_locks.insert(&sender_id, &lock_ids);
+        write_share_locks_to_storage(share_locks);
+        write_share_lock_ids_to_storage(share_lock_ids);
+        write_account_share_locks_to_storage(account_share_locks);
+        self.internal_register_lock_bonus(lock_id, &lock);
+        self.internal_check_storage(prev_storage);
+        log!("{} locked {} shares of pool {} until {}, lock {}", sender_id, amount.0, pool_id, end_time.0, lock_id);
+        emit_nft_event("nft_mint", json!({ "owner_id": sender_id, "token_ids": [lock_id.to_string()] }));
+        lock_id
+    }
+
+    pub fn get_share_lock(&self, lock_id: u64) -> Option<ShareLockInfo> {
+        read_share_locks_from_storage().get(&lock_id).map(|lock| ShareLockInfo {
+            lock_id,
+            locked_amount: lock.locked_amount(env::block_timestamp()).into(),
+            lock,
+        })
+    }
+
+    /// Returns the locks of the account, optionally only those on the given pool.
+    pub fn get_share_locks(&self, account_id: ValidAccountId, pool_id: Option<u64>) -> Vec<ShareLockInfo> {
+        let share_locks = read_share_locks_from_storage();
+        let current_time = env::block_timestamp();
+        read_account_share_locks_from_storage()
+            .get(account_id.as_ref())
+            .unwrap_or_default()
+            .into_iter()
+            .filter_map(|lock_id| share_locks.get(&lock_id).map(|lock| (lock_id, lock)))
+            .filter(|(_, lock)| pool_id.map(|pool_id| lock.pool_id == pool_id).unwrap_or(true))
+            .map(|(lock_id, lock)| ShareLockInfo {
+                lock_id,
+                locked_amount: lock.locked_amount(current_time).into(),
+                lock,
+            })
+            .collect()
+    }
+
+    pub fn get_locked_shares(&self, account_id: ValidAccountId, pool_id: u64) -> U128 {
+        self.internal_locked_shares(account_id.as_ref(), pool_id).into()
+    }
+}
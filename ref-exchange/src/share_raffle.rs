@@ -0,0 +1,204 @@
+use crate::*;
+use near_sdk::BlockHeight;
+use std::convert::TryInto;
+
+pub const MAX_RAFFLE_WINNERS: u32 = 50;
+
+/// Selection of share holders of a pool, weighted by their shares at a share checkpoint.
+/// The draw takes the random seed of its own block, which is unknown when entries close, so the
+/// creator can't pick entrants for a known outcome. The seed is still up to the block producer.
+#[derive(BorshSerialize, BorshDeserialize)]
+pub struct ShareRaffle {
+    pub creator_id: AccountId,
+    pub pool_id: u64,
+    pub checkpoint_id: u64,
+    pub shares_total_supply: Balance,
+    pub winner_count: u32,
+    /// Block entries were closed in, the draw has to come in a later one.
+    pub entries_closed_at: Option<BlockHeight>,
+    pub entrants: UnorderedMap<AccountId, Balance>,
+    pub entered_shares: Balance,
+    pub winners: Option<Vec<AccountId>>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+#[cfg_attr(not(target_arch = "wasm32"), derive(Debug))]
+pub struct ShareRaffleInfo {
+    pub raffle_id: u64,
+    pub creator_id: AccountId,
+    pub pool_id: u64,
+    pub checkpoint_id: u64,
+    pub shares_total_supply: U128,
+    pub winner_count: u32,
+    pub entries_closed: bool,
+    pub entrant_count: u64,
+    pub entered_shares: U128,
+    pub winners: Option<Vec<AccountId>>,
+}
+
+impl From<(u64, &ShareRaffle)> for ShareRaffleInfo {
+    fn from((raffle_id, raffle): (u64, &ShareRaffle)) -> Self {
+        ShareRaffleInfo {
+            raffle_id,
+            creator_id: raffle.creator_id.clone(),
+            pool_id: raffle.pool_id,
+            checkpoint_id: raffle.checkpoint_id,
+            shares_total_supply: U128(raffle.shares_total_supply),
+            winner_count: raffle.winner_count,
+            entries_closed: raffle.entries_closed_at.is_some(),
+            entrant_count: raffle.entrants.len(),
+            entered_shares: U128(raffle.entered_shares),
+            winners: raffle.winners.clone(),
+        }
+    }
+}
+
+pub fn read_share_raffles_from_storage() -> LookupMap<u64, ShareRaffle> {
+    if let Some(content) = env::storage_read(SHARE_RAFFLES.as_bytes()) {
+        LookupMap::try_from_slice(&content).expect("deserialize share raffles failed.")
+    } else {
+        LookupMap::new(StorageKey::ShareRaffles)
+    }
+}
+
+pub fn write_share_raffles_to_storage(share_raffles: LookupMap<u64, ShareRaffle>) {
+    env::storage_write(
+        SHARE_RAFFLES.as_bytes(),
+        &share_raffles.try_to_vec().unwrap(),
+    );
+}
+
+pub fn read_next_share_raffle_id_from_storage() -> u64 {
+    env::storage_read(NEXT_SHARE_RAFFLE_ID.as_bytes())
+        .map(|content| u64::try_from_slice(&content).expect("deserialize next share raffle id failed."))
+        .unwrap_or(0)
+}
+
+pub fn write_next_share_raffle_id_to_storage(next_share_raffle_id: u64) {
+    env::storage_write(NEXT_SHARE_RAFFLE_ID.as_bytes(), &next_share_raffle_id.try_to_vec().unwrap());
+}
+
+/// Draws up to `winner_count` distinct entrants, each draw weighted by the shares of those left.
+/// Entrants are walked in account id order, so the outcome doesn't depend on how they were added.
+/// Entrants without shares are never drawn.
+pub fn draw_weighted(entrants: Vec<(AccountId, Balance)>, winner_count: u32, seed: &[u8]) -> Vec<AccountId> {
+    let mut entrants = entrants;
+    entrants.sort();
+    let mut remaining: Balance = entrants.iter().map(|(_, shares)| shares).sum();
+    let mut winners = vec![];
+    for round in 0..winner_count {
+        if remaining == 0 {
+            break;
+        }
+        let hash = env::sha256(&[seed, &round.to_le_bytes()].concat());
+        let mut target = u128::from_le_bytes(hash[..16].try_into().unwrap()) % remaining;
+        let idx = entrants
+            .iter()
+            .position(|(_, shares)| {
+                if target < *shares {
+                    true
+                } else {
+                    target -= shares;
+                    false
+                }
+            })
+            .unwrap();
+        let (winner, shares) = entrants.remove(idx);
+        remaining -= shares;
+        winners.push(winner);
+    }
+    winners
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Start a raffle among the share holders of the pool at the given share checkpoint.
+    /// Attached deposit covers the raffle and the entrants added later, the rest is refunded.
+    #[payable]
+    pub fn create_share_raffle(&mut self, pool_id: u64, checkpoint_id: u64, winner_count: u32) -> u64 {
+        self.assert_contract_running();
+        let prev_storage = env::storage_usage();
+        assert!(winner_count > 0 && winner_count <= MAX_RAFFLE_WINNERS, "Invalid winner count");
+        let shares_total_supply = read_share_checkpoints_from_storage()
+            .get(&pool_id)
+            .unwrap_or_default()
+            .iter()
+            .find(|checkpoint| checkpoint.checkpoint_id == checkpoint_id)
+            .map(|checkpoint| checkpoint.shares_total_supply)
+            .expect("No share checkpoint");
+        let raffle_id = read_next_share_raffle_id_from_storage();
+        write_next_share_raffle_id_to_storage(raffle_id + 1);
+        let mut share_raffles = read_share_raffles_from_storage();
+        share_raffles.insert(&raffle_id, &ShareRaffle {
+            creator_id: env::predecessor_account_id(),
+            pool_id,
+            checkpoint_id,
+            shares_total_supply,
+            winner_count,
+            entries_closed_at: None,
+            entrants: UnorderedMap::new(StorageKey::ShareRaffleEntrants { raffle_id }),
+            entered_shares: 0,
+            winners: None,
+        });
+        write_share_raffles_to_storage(share_raffles);
+        self.internal_check_storage(prev_storage);
+        raffle_id
+    }
+
+    /// Enter share holders with their shares at the raffle's checkpoint, entries only close once
+    /// all of them are in. Holders already entered or without shares are skipped.
+    #[payable]
+    pub fn add_share_raffle_entrants(&mut self, raffle_id: u64, account_ids: Vec<ValidAccountId>) {
+        let prev_storage = env::storage_usage();
+        let mut share_raffles = read_share_raffles_from_storage();
+        let mut raffle = share_raffles.get(&raffle_id).expect("No share raffle");
+        assert_eq!(raffle.creator_id, env::predecessor_account_id(), "{}", ERR100_NOT_ALLOWED);
+        assert!(raffle.entries_closed_at.is_none(), "Share raffle entries closed");
+        let balances = self.get_share_checkpoint_balances(raffle.pool_id, raffle.checkpoint_id, account_ids.clone());
+        for (account_id, shares) in account_ids.iter().zip(balances) {
+            if shares.0 == 0 || raffle.entrants.get(account_id.as_ref()).is_some() {
+                continue;
+            }
+            raffle.entrants.insert(account_id.as_ref(), &shares.0);
+            raffle.entered_shares += shares.0;
+        }
+        share_raffles.insert(&raffle_id, &raffle);
+        write_share_raffles_to_storage(share_raffles);
+        self.internal_check_storage(prev_storage);
+    }
+
+    /// Close the entries once they hold all shares of the checkpoint, so no holder can have been
+    /// left out. From the next block on anyone can draw, the creator can no longer back out.
+    pub fn close_share_raffle_entries(&mut self, raffle_id: u64) {
+        let mut share_raffles = read_share_raffles_from_storage();
+        let mut raffle = share_raffles.get(&raffle_id).expect("No share raffle");
+        assert_eq!(raffle.creator_id, env::predecessor_account_id(), "{}", ERR100_NOT_ALLOWED);
+        assert!(raffle.entries_closed_at.is_none(), "Share raffle entries closed");
+        assert_eq!(raffle.entered_shares, raffle.shares_total_supply, "Entrants don't hold all shares of the checkpoint");
+        raffle.entries_closed_at = Some(env::block_index());
+        share_raffles.insert(&raffle_id, &raffle);
+        write_share_raffles_to_storage(share_raffles);
+    }
+
+    /// Draw the winners with the random seed of the current block, callable by anyone after the
+    /// block entries were closed in. The seed is logged so anyone can replay the draw off-chain.
+    pub fn draw_share_raffle(&mut self, raffle_id: u64) -> Vec<AccountId> {
+        let mut share_raffles = read_share_raffles_from_storage();
+        let mut raffle = share_raffles.get(&raffle_id).expect("No share raffle");
+        assert!(raffle.winners.is_none(), "Share raffle already drawn");
+        let entries_closed_at = raffle.entries_closed_at.expect("Share raffle entries not closed");
+        assert!(env::block_index() > entries_closed_at, "Share raffle entries closed in this block");
+        let seed = env::random_seed();
+        let winners = draw_weighted(raffle.entrants.to_vec(), raffle.winner_count, &seed);
+        log!("Share raffle {} drawn with seed {:?}: {:?}", raffle_id, seed, winners);
+        raffle.winners = Some(winners.clone());
+        share_raffles.insert(&raffle_id, &raffle);
+        write_share_raffles_to_storage(share_raffles);
+        winners
+    }
+
+    pub fn get_share_raffle(&self, raffle_id: u64) -> Option<ShareRaffleInfo> {
+        read_share_raffles_from_storage().get(&raffle_id).map(|raffle| (raffle_id, &raffle).into())
+    }
+}
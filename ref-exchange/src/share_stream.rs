@@ -0,0 +1,289 @@
+use crate::*;
+use crate::utils::{to_nano, u128_dec_format, u64_dec_format, U256};
+use near_sdk::Timestamp;
+
+/// Bounds the streams an account sends. Incoming streams don't count, so others can't use
+/// up the limit of an account, which can decline them with `cancel_share_stream`.
+pub const MAX_SHARE_STREAMS_PER_ACCOUNT: usize = 20;
+
+/// Pool shares flowing linearly from `sender_id` to `receiver_id` between `start_time` and `end_time`.
+/// Shares not yet withdrawn stay with the sender, reserved like locked shares.
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone)]
+#[serde(crate = "near_sdk::serde")]
+#[cfg_attr(not(target_arch = "wasm32"), derive(Debug))]
+pub struct ShareStream {
+    pub sender_id: AccountId,
+    pub receiver_id: AccountId,
+    pub pool_id: u64,
+    #[serde(with = "u128_dec_format")]
+    pub amount: Balance,
+    #[serde(with = "u128_dec_format")]
+    pub withdrawn: Balance,
+    #[serde(with = "u64_dec_format")]
+    pub start_time: Timestamp,
+    #[serde(with = "u64_dec_format")]
+    pub end_time: Timestamp,
+}
+
+impl ShareStream {
+    pub fn streamed_amount(&self, current_time: Timestamp) -> Balance {
+        if current_time >= self.end_time {
+            self.amount
+        } else if current_time <= self.start_time {
+            0
+        } else {
+            (U256::from(self.amount) * U256::from(current_time - self.start_time)
+                / U256::from(self.end_time - self.start_time))
+                .as_u128()
+        }
+    }
+
+    pub fn withdrawable_amount(&self, current_time: Timestamp) -> Balance {
+        self.streamed_amount(current_time) - self.withdrawn
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+#[cfg_attr(not(target_arch = "wasm32"), derive(Debug))]
+pub struct ShareStreamInfo {
+    pub stream_id: u64,
+    pub stream: ShareStream,
+    pub withdrawable_amount: U128,
+}
+
+pub fn read_share_streams_from_storage() -> LookupMap<u64, ShareStream> {
+    if let Some(content) = env::storage_read(SHARE_STREAMS.as_bytes()) {
+        LookupMap::try_from_slice(&content).expect("deserialize share streams failed.")
+    } else {
+        LookupMap::new(StorageKey::ShareStreams)
+    }
+}
+
+pub fn write_share_streams_to_storage(share_streams: LookupMap<u64, ShareStream>) {
+    env::storage_write(
+        SHARE_STREAMS.as_bytes(),
+        &share_streams.try_to_vec().unwrap(),
+    );
+}
+
+pub fn read_account_share_streams_from_storage() -> LookupMap<AccountId, Vec<u64>> {
+    if let Some(content) = env::storage_read(ACCOUNT_SHARE_STREAMS.as_bytes()) {
+        LookupMap::try_from_slice(&content).expect("deserialize account share streams failed.")
+    } else {
+        LookupMap::new(StorageKey::AccountShareStreams)
+    }
+}
+
+pub fn write_account_share_streams_to_storage(account_share_streams: LookupMap<AccountId, Vec<u64>>) {
+    env::storage_write(
+        ACCOUNT_SHARE_STREAMS.as_bytes(),
+        &account_share_streams.try_to_vec().unwrap(),
+    );
+}
+
+pub fn read_next_share_stream_id_from_storage() -> u64 {
+    if let Some(content) = env::storage_read(NEXT_SHARE_STREAM_ID.as_bytes()) {
+        u64::try_from_slice(&content).expect("deserialize next share stream id failed.")
+    } else {
+        0
+    }
+}
+
+pub fn write_next_share_stream_id_to_storage(next_share_stream_id: u64) {
+    env::storage_write(
+        NEXT_SHARE_STREAM_ID.as_bytes(),
+        &next_share_stream_id.try_to_vec().unwrap(),
+    );
+}
+
+impl Contract {
+    /// Shares of the account in the given pool still owed to the receivers of its streams.
+    pub fn internal_streaming_shares(&self, account_id: &AccountId, pool_id: u64) -> Balance {
+        let stream_ids = read_account_share_streams_from_storage().get(account_id).unwrap_or_default();
+        if stream_ids.is_empty() {
+            return 0;
+        }
+        let share_streams = read_share_streams_from_storage();
+        stream_ids.iter()
+            .filter_map(|stream_id| share_streams.get(stream_id))
+            .filter(|stream| &stream.sender_id == account_id && stream.pool_id == pool_id)
+            .map(|stream| stream.amount - stream.withdrawn)
+            .sum()
+    }
+
+    /// Pays the receiver what has streamed so far, then drops the stream if `close` or once fully paid.
+    /// If the receiver left the pool, the stream is dropped and the unpaid shares stay with the sender.
+    fn internal_settle_share_stream(&mut self, stream_id: u64, close: bool) -> Balance {
+        let mut share_streams = read_share_streams_from_storage();
+        let mut stream = share_streams.get(&stream_id).expect("Share stream not found");
+        let prev_storage = env::storage_usage();
+        let mut pool = self.internal_get_pool(stream.pool_id);
+        let receiver_left = !pool.share_has_registered(&stream.receiver_id);
+        let amount = if receiver_left {
+            log!("Receiver {} of share stream {} left the pool", stream.receiver_id, stream_id);
+            0
+        } else {
+            stream.withdrawable_amount(env::block_timestamp())
+        };
+        if amount > 0 {
+            assert_account_not_denied(&stream.sender_id);
+            assert_account_not_denied(&stream.receiver_id);
+            let total_shares = pool.share_balances(&stream.sender_id);
+            let free_shares = self.internal_get_account(&stream.sender_id)
+                .and_then(|account| account.get_shadow_record(stream.pool_id))
+                .map(|record| record.free_shares(total_shares))
+                .unwrap_or(total_shares);
+            assert!(amount <= free_shares, "Not enough free shares");
+            self.internal_settle_lp_fees(stream.pool_id, &pool, &[&stream.sender_id, &stream.receiver_id]);
+            pool.share_transfer(&stream.sender_id, &stream.receiver_id, amount);
+            self.pools.replace(stream.pool_id, &pool);
+            stream.withdrawn += amount;
+        }
+        if close || receiver_left || stream.withdrawn == stream.amount {
+            share_streams.remove(&stream_id);
+            let mut account_share_streams = read_account_share_streams_from_storage();
+            for account_id in &[&stream.sender_id, &stream.receiver_id] {
+                let mut stream_ids = account_share_streams.get(account_id).unwrap_or_default();
+                stream_ids.retain(|id| *id != stream_id);
+                if stream_ids.is_empty() {
+                    account_share_streams.remove(account_id);
+                } else {
+                    account_share_streams.insert(account_id, &stream_ids);
+                }
+            }
+            write_account_share_streams_to_storage(account_share_streams);
+            log!("Share stream {} closed, {} of {} shares streamed", stream_id, stream.withdrawn, stream.amount);
+        } else {
+            share_streams.insert(&stream_id, &stream);
+        }
+        write_share_streams_to_storage(share_streams);
+        // The sender paid for the stream's storage.
+        if prev_storage > env::storage_usage() {
+            let refund = (prev_storage - env::storage_usage()) as Balance * env::storage_byte_cost();
+            if let Some(mut account) = self.internal_get_account(&stream.sender_id) {
+                account.near_amount += refund;
+                self.internal_save_account(&stream.sender_id, account);
+            } else {
+                Promise::new(stream.sender_id.clone()).transfer(refund);
+            }
+        }
+        amount
+    }
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Stream `amount` of the caller's shares in the pool to `receiver_id`, linearly over `duration_sec`.
+    /// The receiver must be registered in the pool and withdraws with `withdraw_share_stream`,
+    /// the caller can stop the stream any time with `cancel_share_stream`.
+    /// Attached deposit covers the storage of the stream, the rest is refunded.
+    #[payable]
+    pub fn share_stream(
+        &mut self,
+        pool_id: u64,
+        receiver_id: ValidAccountId,
+        amount: U128,
+        duration_sec: u32,
+    ) -> u64 {
+        self.assert_contract_running();
+        let prev_storage = env::storage_usage();
+        let sender_id = env::predecessor_account_id();
+        let receiver_id: AccountId = receiver_id.into();
+        assert_ne!(sender_id, receiver_id, "{}", ERR33_TRANSFER_TO_SELF);
+        assert_account_not_denied(&sender_id);
+        assert_account_not_denied(&receiver_id);
+        assert!(amount.0 > 0, "Invalid amount");
+        assert!(duration_sec > 0, "Invalid duration");
+        let pool = self.internal_get_pool(pool_id);
+        assert!(pool.share_has_registered(&receiver_id), "{}", ERR13_LP_NOT_REGISTERED);
+        self.assert_lp_allowed(pool_id, &receiver_id);
+        let total_shares = pool.share_balances(&sender_id);
+        let shadow_in_burrow = self.internal_get_account(&sender_id)
+            .and_then(|account| account.get_shadow_record(pool_id))
+            .map(|record| record.shadow_in_burrow)
+            .unwrap_or(0);
+        assert!(
+            self.internal_locked_shares(&sender_id, pool_id) + shadow_in_burrow + amount.0 <= total_shares,
+            "Not enough unlocked shares"
+        );
+        self.internal_record_limited_withdrawal(&sender_id, &format!(":{}", pool_id), amount.0);
+
+        let stream_id = read_next_share_stream_id_from_storage();
+        write_next_share_stream_id_to_storage(stream_id + 1);
+        let mut share_streams = read_share_streams_from_storage();
+        let mut account_share_streams = read_account_share_streams_from_storage();
+        let outgoing_streams = account_share_streams.get(&sender_id).unwrap_or_default()
+            .iter()
+            .filter(|id| share_streams.get(id).map(|stream| stream.sender_id == sender_id).unwrap_or(false))
+            .count();
+        assert!(outgoing_streams < MAX_SHARE_STREAMS_PER_ACCOUNT, "Too many share streams");
+        for account_id in &[&sender_id, &receiver_id] {
+            let mut stream_ids = account_share_streams.get(account_id).unwrap_or_default();
+            stream_ids.push(stream_id);
+            account_share_streams.insert(account_id, &stream_ids);
+        }
+        write_account_share_streams_to_storage(account_share_streams);
+        let start_time = env::block_timestamp();
+        share_streams.insert(&stream_id, &ShareStream {
+            sender_id: sender_id.clone(),
+            receiver_id: receiver_id.clone(),
+            pool_id,
+            amount: amount.0,
+            withdrawn: 0,
+            start_time,
+            end_time: start_time + to_nano(duration_sec),
+        });
+        write_share_streams_to_storage(share_streams);
+        self.internal_check_storage(prev_storage);
+        log!("{} streams {} shares of pool {} to {} over {} seconds, stream {}", sender_id, amount.0, pool_id, receiver_id, duration_sec, stream_id);
+        stream_id
+    }
+
+    /// Move the shares streamed so far to the receiver. Callable by either party, returns the amount moved.
+    #[payable]
+    pub fn withdraw_share_stream(&mut self, stream_id: u64) -> U128 {
+        assert_one_yocto();
+        self.assert_contract_running();
+        let stream = read_share_streams_from_storage().get(&stream_id).expect("Share stream not found");
+        let caller = env::predecessor_account_id();
+        assert!(caller == stream.sender_id || caller == stream.receiver_id, "{}", ERR100_NOT_ALLOWED);
+        self.internal_settle_share_stream(stream_id, false).into()
+    }
+
+    /// Stop the stream: the receiver gets what has streamed so far, the rest stays with the sender.
+    /// Callable by the sender, or by the receiver to decline the stream.
+    #[payable]
+    pub fn cancel_share_stream(&mut self, stream_id: u64) -> U128 {
+        assert_one_yocto();
+        self.assert_contract_running();
+        let stream = read_share_streams_from_storage().get(&stream_id).expect("Share stream not found");
+        let caller = env::predecessor_account_id();
+        assert!(caller == stream.sender_id || caller == stream.receiver_id, "{}", ERR100_NOT_ALLOWED);
+        self.internal_settle_share_stream(stream_id, true).into()
+    }
+
+    pub fn get_share_stream(&self, stream_id: u64) -> Option<ShareStreamInfo> {
+        read_share_streams_from_storage().get(&stream_id).map(|stream| ShareStreamInfo {
+            stream_id,
+            withdrawable_amount: stream.withdrawable_amount(env::block_timestamp()).into(),
+            stream,
+        })
+    }
+
+    /// Returns the streams the account sends or receives.
+    pub fn get_share_streams(&self, account_id: ValidAccountId) -> Vec<ShareStreamInfo> {
+        let share_streams = read_share_streams_from_storage();
+        let current_time = env::block_timestamp();
+        read_account_share_streams_from_storage()
+            .get(account_id.as_ref())
+            .unwrap_or_default()
+            .into_iter()
+            .filter_map(|stream_id| share_streams.get(&stream_id).map(|stream| ShareStreamInfo {
+                stream_id,
+                withdrawable_amount: stream.withdrawable_amount(current_time).into(),
+                stream,
+            }))
+            .collect()
+    }
+}
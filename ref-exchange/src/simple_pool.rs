@@ -4,6 +4,8 @@ use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
 use near_sdk::collections::LookupMap;
 use near_sdk::json_types::ValidAccountId;
 use near_sdk::{env, AccountId, Balance};
+use ref_exchange_math::simple;
+
 use crate::StorageKey;
 use crate::admin_fee::AdminFees;
 
@@ -172,7 +174,7 @@ impl SimplePool {
     }
 
     /// Mint new shares for given user.
-    fn mint_shares(&mut self, account_id: &AccountId, shares: Balance, is_view: bool) {
+    pub(crate) fn mint_shares(&mut self, account_id: &AccountId, shares: Balance, is_view: bool) {
         if shares == 0 {
             return;
         }
@@ -234,7 +236,7 @@ impl SimplePool {
     }
 
     /// Returns token index for given pool.
-    fn token_index(&self, token_id: &AccountId) -> usize {
+    pub(crate) fn token_index(&self, token_id: &AccountId) -> usize {
         self.token_account_ids
             .iter()
             .position(|id| id == token_id)
@@ -249,18 +251,14 @@ impl SimplePool {
         amount_in: Balance,
         token_out: usize,
     ) -> Balance {
-        let in_balance = U256::from(self.amounts[token_in]);
-        let out_balance = U256::from(self.amounts[token_out]);
         assert!(
-            in_balance > U256::zero()
-                && out_balance > U256::zero()
+            self.amounts[token_in] > 0
+                && self.amounts[token_out] > 0
                 && token_in != token_out
                 && amount_in > 0,
             "{}", ERR76_INVALID_PARAMS
         );
-        let amount_with_fee = U256::from(amount_in) * U256::from(FEE_DIVISOR - self.total_fee);
-        (amount_with_fee * out_balance / (U256::from(FEE_DIVISOR) * in_balance + amount_with_fee))
-            .as_u128()
+        simple::get_amount_out(self.amounts[token_in], self.amounts[token_out], amount_in, self.total_fee)
     }
 
     /// Returns amount of input tokens required to obtain the given amount of output tokens.
@@ -271,18 +269,14 @@ impl SimplePool {
         amount_out: Balance,
         token_out: usize,
     ) -> Balance {
-        let in_balance = U256::from(self.amounts[token_in]);
-        let out_balance = U256::from(self.amounts[token_out]);
         assert!(
-            in_balance > U256::zero()
-                && out_balance > U256::zero()
+            self.amounts[token_in] > 0
+                && self.amounts[token_out] > 0
                 && token_in != token_out
                 && amount_out > 0,
             "{}", ERR76_INVALID_PARAMS
         );
-        let numerator = in_balance * U256::from(amount_out) * U256::from(FEE_DIVISOR);
-        let denominator = (out_balance - U256::from(amount_out)) * U256::from(FEE_DIVISOR - self.total_fee);
-        (numerator / denominator + U256::one()).as_u128()
+        simple::get_amount_in(self.amounts[token_in], self.amounts[token_out], amount_out, self.total_fee)
     }
 
     /// Returns given pool's total fee.
@@ -295,6 +289,15 @@ impl SimplePool {
         self.volumes.clone()
     }
 
+    /// Get per lp token invariant sqrt(x * y), with 1e8 precision.
+    /// It only grows from fees, unlike the token amounts behind a share.
+    pub fn get_share_price(&self) -> u128 {
+        (integer_sqrt(U256::from(self.amounts[0]) * U256::from(self.amounts[1])) * U256::from(100000000))
+            .checked_div(self.shares_total_supply.into())
+            .unwrap_or(100000000.into())
+            .as_u128()
+    }
+
     /// Swap `token_amount_in` of `token_in` token into `token_out` and return how much was received.
     /// Assuming that `token_amount_in` was already received from `sender_id`.
     pub fn swap(
@@ -375,7 +378,23 @@ impl SimplePool {
         // Invariant can not reduce (otherwise loosing balance of the pool and something it broken).
         assert!(new_invariant >= prev_invariant, "{}", ERR75_INVARIANT_REDUCE);
 
-        // Allocate admin fee as fraction of total fee by issuing LP shares proportionally.
+        self.mint_admin_fee_shares(prev_invariant, new_invariant, admin_fee, is_view);
+
+        // Keeping track of volume per each input traded separately.
+        // Reported volume with fees will be sum of `input`, without fees will be sum of `output`.
+        self.volumes[in_idx].input.0 += amount_in;
+        self.volumes[in_idx].output.0 += amount_out;
+    }
+
+    /// Allocate admin fee as fraction of total fee by issuing LP shares proportionally
+    /// to the growth of the invariant by a swap.
+    pub(crate) fn mint_admin_fee_shares(
+        &mut self,
+        prev_invariant: U256,
+        new_invariant: U256,
+        admin_fee: &AdminFees,
+        is_view: bool
+    ) {
         let numerator = (new_invariant - prev_invariant) * U256::from(self.shares_total_supply);
         if admin_fee.admin_fee_bps > 0 && numerator > U256::zero() {
             // First we convert all admin fee into shares, as invariant increase is caused by total fee, so:
@@ -423,11 +442,6 @@ impl SimplePool {
             // Finally, remaining admin shares belong to the exchange
             self.mint_shares(&admin_fee.exchange_id, admin_shares - referral_share, is_view);
         }
-
-        // Keeping track of volume per each input traded separately.
-        // Reported volume with fees will be sum of `input`, without fees will be sum of `output`.
-        self.volumes[in_idx].input.0 += amount_in;
-        self.volumes[in_idx].output.0 += amount_out;
     }
 }
 
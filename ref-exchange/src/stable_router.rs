@@ -0,0 +1,188 @@
+use crate::*;
+
+/// Parts the amount is split into when spreading a routed swap over pools.
+pub const STABLE_ROUTE_PARTS: u128 = 10;
+
+/// Swap actions over the stable pools of a pair giving the most out for the amount in.
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct StableRoute {
+    /// One action per pool used, with min_amount_out left at 0.
+    pub actions: Vec<SwapAction>,
+    pub amount_out: U128,
+}
+
+pub fn read_stable_pair_pools_from_storage() -> LookupMap<String, Vec<u64>> {
+    if let Some(content) = env::storage_read(STABLE_PAIR_POOLS.as_bytes()) {
+        LookupMap::try_from_slice(&content).expect("deserialize stable pair pools failed.")
+    } else {
+        LookupMap::new(StorageKey::StablePairPools)
+    }
+}
+
+pub fn write_stable_pair_pools_to_storage(stable_pair_pools: LookupMap<String, Vec<u64>>) {
+    env::storage_write(
+        STABLE_PAIR_POOLS.as_bytes(),
+        &stable_pair_pools.try_to_vec().unwrap(),
+    );
+}
+
+fn stable_pair_key(token_a: &AccountId, token_b: &AccountId) -> String {
+    if token_a < token_b {
+        format!("{}:{}", token_a, token_b)
+    } else {
+        format!("{}:{}", token_b, token_a)
+    }
+}
+
+impl Contract {
+    /// Indexes a stable, rated or degen pool under each pair of its tokens.
+    pub(crate) fn internal_index_stable_pair_pool(&mut self, pool_id: u64, pool: &Pool) {
+        if matches!(pool, Pool::SimplePool(_) | Pool::RangePool(_)) {
+            return;
+        }
+        let tokens = pool.tokens();
+        let mut stable_pair_pools = read_stable_pair_pools_from_storage();
+        for (i, token_a) in tokens.iter().enumerate() {
+            for token_b in tokens[i + 1..].iter() {
+                let key = stable_pair_key(token_a, token_b);
+                let mut pool_ids = stable_pair_pools.get(&key).unwrap_or_default();
+                if !pool_ids.contains(&pool_id) {
+                    pool_ids.push(pool_id);
+                    stable_pair_pools.insert(&key, &pool_ids);
+                }
+            }
+        }
+        write_stable_pair_pools_to_storage(stable_pair_pools);
+    }
+
+    /// Whether swaps can currently go through the pool at all.
+    fn is_stable_pool_routable(&self, pool_id: u64) -> bool {
+        !self.is_pool_archived(pool_id)
+            && read_launch_auctions_from_storage().get(&pool_id).is_none()
+            && !read_rate_divergence_breakers_from_storage().get(&pool_id).map(|breaker| breaker.guarded).unwrap_or(false)
+            && !self.internal_get_pool(pool_id).tokens().iter().any(|token_id| self.frozen_tokens.contains(token_id))
+    }
+
+    /// Amount out of the pool for the amount in, None if the swap would fail in the pool
+    /// or take more than the pool's swap cap.
+    fn internal_stable_quote(&self, pool_id: u64, token_in: &AccountId, amount_in: Balance, token_out: &AccountId) -> Option<Balance> {
+        if amount_in == 0 {
+            return Some(0);
+        }
+        let mut pool = self.internal_get_swap_pool(pool_id);
+        let admin_fees = AdminFees::new(self.admin_fee_bps);
+        pool.try_quote_swap(token_in, amount_in, token_out, &admin_fees)?;
+        if !self.is_degen_swap_price_fresh(pool_id, &pool, token_in, amount_in) {
+            return None;
+        }
+        let max_amount_out = self.internal_max_swap_out(pool_id, &pool, token_out);
+        let amount_out = self.internal_quote_with_maker_rebate(pool_id, &mut pool, token_in, amount_in, token_out, admin_fees);
+        match max_amount_out {
+            Some(max_amount_out) if amount_out > max_amount_out => None,
+            _ => Some(amount_out),
+        }
+    }
+
+    /// Splits the amount in parts, each going to the pool adding the most out on top of
+    /// the parts it already got, unless a single pool does better with all of it.
+    /// Pools that can't take a part are passed over for it.
+    /// Returns the pools used with their amounts in and out.
+    pub(crate) fn internal_stable_route(&self, token_in: &AccountId, amount_in: Balance, token_out: &AccountId) -> Vec<(u64, Balance, Balance)> {
+        let pool_ids: Vec<u64> = read_stable_pair_pools_from_storage()
+            .get(&stable_pair_key(token_in, token_out))
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|pool_id| self.is_stable_pool_routable(*pool_id))
+            .collect();
+        assert!(self.state == RunningState::Running, "{}", ERR51_CONTRACT_PAUSED);
+        assert!(!pool_ids.is_empty(), "No stable pool for {} and {}", token_in, token_out);
+        let part = amount_in / STABLE_ROUTE_PARTS;
+        let mut amounts_in = vec![0; pool_ids.len()];
+        let mut amounts_out = vec![0; pool_ids.len()];
+        for index in 0..STABLE_ROUTE_PARTS {
+            let part = if index + 1 == STABLE_ROUTE_PARTS { amount_in - part * index } else { part };
+            if part == 0 {
+                continue;
+            }
+            let (best, best_out) = pool_ids
+                .iter()
+                .enumerate()
+                .filter_map(|(i, pool_id)| {
+                    self.internal_stable_quote(*pool_id, token_in, amounts_in[i] + part, token_out)
+                        .map(|amount_out| (i, amount_out))
+                })
+                .max_by_key(|(i, amount_out)| amount_out - amounts_out[*i])
+                .expect("No stable pool can take the swap");
+            amounts_in[best] += part;
+            amounts_out[best] = best_out;
+        }
+        let single = pool_ids
+            .iter()
+            .filter_map(|pool_id| {
+                self.internal_stable_quote(*pool_id, token_in, amount_in, token_out)
+                    .map(|amount_out| (*pool_id, amount_out))
+            })
+            .max_by_key(|(_, amount_out)| *amount_out);
+        if let Some((single, single_out)) = single {
+            if single_out >= amounts_out.iter().sum() {
+                return vec![(single, amount_in, single_out)];
+            }
+        }
+        pool_ids
+            .into_iter()
+            .zip(amounts_in.into_iter().zip(amounts_out))
+            .filter(|(_, (amount_in, _))| *amount_in > 0)
+            .map(|(pool_id, (amount_in, amount_out))| (pool_id, amount_in, amount_out))
+            .collect()
+    }
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Stable, rated and degen pools holding both tokens, archived ones included.
+    pub fn get_stable_pair_pools(&self, token_a: ValidAccountId, token_b: ValidAccountId) -> Vec<u64> {
+        read_stable_pair_pools_from_storage()
+            .get(&stable_pair_key(token_a.as_ref(), token_b.as_ref()))
+            .unwrap_or_default()
+    }
+
+    /// Best execution of the amount over the active stable pools of the pair, in a single pool
+    /// or split over several.
+    pub fn get_stable_route(&self, token_in: ValidAccountId, amount_in: U128, token_out: ValidAccountId) -> StableRoute {
+        let route = self.internal_stable_route(token_in.as_ref(), amount_in.0, token_out.as_ref());
+        StableRoute {
+            amount_out: U128(route.iter().map(|(_, _, amount_out)| amount_out).sum()),
+            actions: route
+                .into_iter()
+                .map(|(pool_id, amount_in, _)| SwapAction {
+                    pool_id,
+                    token_in: token_in.clone().into(),
+                    amount_in: Some(U128(amount_in)),
+                    token_out: token_out.clone().into(),
+                    min_amount_out: U128(0),
+                })
+                .collect(),
+        }
+    }
+
+    /// Swap over the route of `get_stable_route`, failing if less than min_amount_out comes out in total.
+    /// If referrer provided, pays referral_fee to it.
+    #[payable]
+    pub fn swap_stable_routed(
+        &mut self,
+        token_in: ValidAccountId,
+        amount_in: U128,
+        token_out: ValidAccountId,
+        min_amount_out: U128,
+        referral_id: Option<ValidAccountId>,
+    ) -> U128 {
+        let sender_id = env::predecessor_account_id();
+        let route = self.get_stable_route(token_in, amount_in, token_out.clone());
+        let prev_amount = self.internal_get_deposit(&sender_id, token_out.as_ref());
+        self.swap(route.actions, referral_id);
+        let amount_out = self.internal_get_deposit(&sender_id, token_out.as_ref()) - prev_amount;
+        assert!(amount_out >= min_amount_out.0, "{}", ERR68_SLIPPAGE);
+        U128(amount_out)
+    }
+}
@@ -5,19 +5,39 @@ use near_sdk::{env, AccountId, Balance, Timestamp};
 
 use crate::admin_fee::AdminFees;
 use crate::errors::*;
-use crate::stable_swap::math::{
+use ref_exchange_math::stable::{
     Fees, StableSwap, SwapResult, MAX_AMP, MAX_AMP_CHANGE, MIN_AMP, MIN_RAMP_DURATION,
 };
 use crate::utils::{add_to_collection, SwapVolume, FEE_DIVISOR, U256, u128_ratio};
 use crate::StorageKey;
 
-mod math;
 
 pub const MIN_DECIMAL: u8 = 1;
 pub const MAX_DECIMAL: u8 = 24;
 pub const TARGET_DECIMAL: u8 = 18;
 pub const MIN_RESERVE: u128 = 1_000_000_000_000_000;
 
+/// Checks a ramp from `from_amp` at `from_time` to `to_amp` at `to_time` follows the same
+/// duration and change limits as `ramp_amplification`.
+pub fn assert_amp_ramp_step(from_amp: u128, from_time: Timestamp, to_amp: u128, to_time: Timestamp) {
+    assert!(
+        to_time >= from_time + MIN_RAMP_DURATION,
+        "{}",
+        ERR82_INSUFFICIENT_RAMP_TIME
+    );
+    assert!(
+        to_amp > 0 && to_amp < MAX_AMP,
+        "{}",
+        ERR83_INVALID_AMP_FACTOR
+    );
+    assert!(
+        (to_amp >= from_amp && to_amp <= from_amp * MAX_AMP_CHANGE)
+            || (to_amp < from_amp && to_amp * MAX_AMP_CHANGE >= from_amp),
+        "{}",
+        ERR84_AMP_LARGE_CHANGE
+    );
+}
+
 #[derive(BorshSerialize, BorshDeserialize)]
 pub struct StableSwapPool {
     /// List of tokens in the pool.
@@ -99,7 +119,7 @@ impl StableSwapPool {
         amounts
     }
 
-    fn amounts_to_c_amounts(&self, amounts: &Vec<u128>) ->Vec<u128> {
+    pub fn amounts_to_c_amounts(&self, amounts: &Vec<u128>) ->Vec<u128> {
         let mut c_amounts = amounts.clone();
         for (index, value) in self.token_decimals.iter().enumerate() {
             if *value <= TARGET_DECIMAL {
@@ -117,7 +137,7 @@ impl StableSwapPool {
         c_amounts
     }
 
-    fn amount_to_c_amount(&self, amount: u128, index: usize) -> u128 {
+    pub fn amount_to_c_amount(&self, amount: u128, index: usize) -> u128 {
         let value = self.token_decimals.get(index).unwrap();
         if *value <= TARGET_DECIMAL {
             let factor = 10_u128
@@ -236,7 +256,7 @@ impl StableSwapPool {
                     &c_amounts,
                     &self.c_amounts,
                     self.shares_total_supply,
-                    &Fees::new(self.total_fee, &fees),
+                    &Fees::new(self.total_fee, fees.admin_fee_bps),
                 )
                 .expect(ERR67_LPSHARE_CALC_ERR)
         }
@@ -376,7 +396,7 @@ impl StableSwapPool {
         }
 
         let invariant = self.get_invariant();
-        let trade_fee = Fees::new(self.total_fee, &fees);
+        let trade_fee = Fees::new(self.total_fee, fees.admin_fee_bps);
 
         let (burn_shares, fee_part) = invariant
             .compute_lp_amount_for_withdraw(
@@ -446,6 +466,24 @@ impl StableSwapPool {
         burn_shares
     }
 
+    /// Quote of a swap, None where the swap fails to compute or would leave the out reserve below MIN_RESERVE.
+    pub fn try_quote_swap(&self, token_in: &AccountId, amount_in: Balance, token_out: &AccountId, fees: &AdminFees) -> Option<Balance> {
+        let in_idx = self.token_index(token_in);
+        let out_idx = self.token_index(token_out);
+        let result = self.get_invariant()
+            .swap_to(
+                in_idx,
+                self.amount_to_c_amount(amount_in, in_idx),
+                out_idx,
+                &self.c_amounts,
+                &Fees::new(self.total_fee, fees.admin_fee_bps),
+            )?;
+        if result.new_destination_amount < MIN_RESERVE {
+            return None;
+        }
+        Some(self.c_amount_to_amount(result.amount_swapped, out_idx))
+    }
+
     /// Returns number of tokens in outcome, given amount.
     /// Tokens are provided as indexes into token list for given pool.
     /// All tokens are comparable tokens
@@ -465,7 +503,7 @@ impl StableSwapPool {
                 c_amount_in,
                 token_out,
                 &self.c_amounts,
-                &Fees::new(self.total_fee, &fees),
+                &Fees::new(self.total_fee, fees.admin_fee_bps),
             )
             .expect(ERR70_SWAP_OUT_CALC_ERR)
 
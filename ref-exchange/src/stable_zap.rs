@@ -0,0 +1,72 @@
+use crate::*;
+use crate::utils::u128_ratio;
+
+/// Zap of the transferred token into a stable, rated or degen pool, see `stable_zap`.
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct StableZapInfo {
+    pub pool_id: u64,
+    pub min_shares: U128,
+}
+
+impl Contract {
+    /// Swaps parts of `amount_in` on the sender's inner balance into the other tokens of the pool,
+    /// in proportion to the pool's reserves valued at their rates or prices, then adds all of
+    /// it as liquidity. Returns the minted shares. Storage is not checked here.
+    pub(crate) fn internal_stable_zap(
+        &mut self,
+        sender_id: &AccountId,
+        pool_id: u64,
+        token_in: &AccountId,
+        amount_in: Balance,
+        min_shares: U128,
+    ) -> Balance {
+        assert!(amount_in > 0, "Nothing to zap");
+        assert_account_not_denied(sender_id);
+        self.assert_swap_screen_passed(pool_id, sender_id);
+        let pool = self.internal_get_pool(pool_id);
+        assert!(!matches!(pool, Pool::SimplePool(_) | Pool::RangePool(_)), "{}", ERR88_NOT_STABLE_POOL);
+        let tokens = pool.tokens().to_vec();
+        let in_idx = tokens.iter().position(|id| id == token_in).expect(ERR63_MISSING_TOKEN);
+        let reserves = valued_reserves(&pool).expect(ERR129_DEGENS_EXPIRED);
+        let total: Balance = reserves.iter().sum();
+
+        let mut amounts = vec![0; tokens.len()];
+        amounts[in_idx] = amount_in;
+        for (idx, token_out) in tokens.iter().enumerate() {
+            let part = if idx == in_idx || total == 0 { 0 } else { u128_ratio(amount_in, reserves[idx], total) };
+            if part == 0 {
+                continue;
+            }
+            let amount_out = self.internal_pool_swap(pool_id, token_in, part, token_out, 0, &None);
+            let mut account = self.internal_unwrap_account(sender_id);
+            account.withdraw(token_in, part);
+            account.deposit(token_out, amount_out);
+            self.internal_save_account(sender_id, account);
+            amounts[in_idx] -= part;
+            amounts[idx] = amount_out;
+        }
+        self.internal_update_unit_share_cumulative_info(pool_id);
+        let shares = self.internal_add_stable_liquidity(pool_id, sender_id, amounts.into_iter().map(U128).collect(), min_shares);
+        log!("{} zapped {} {} into {} shares of pool {}", sender_id, amount_in, token_in, shares, pool_id);
+        shares
+    }
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Add liquidity to a stable, rated or degen pool from a single token of the sender's inner
+    /// account, part of which is swapped in the same pool into the other tokens first.
+    /// Fails with ERR68_SLIPPAGE if less than `min_shares` are minted.
+    /// Attached deposit covers LP registration, the rest is refunded.
+    #[payable]
+    pub fn stable_zap(&mut self, pool_id: u64, token_in: ValidAccountId, amount_in: U128, min_shares: U128) -> U128 {
+        self.assert_contract_running();
+        assert!(env::attached_deposit() > 0, "{}", ERR35_AT_LEAST_ONE_YOCTO);
+        let prev_storage = env::storage_usage();
+        let sender_id = env::predecessor_account_id();
+        let shares = self.internal_stable_zap(&sender_id, pool_id, token_in.as_ref(), amount_in.0, min_shares);
+        self.internal_check_storage(prev_storage);
+        shares.into()
+    }
+}
@@ -28,6 +28,7 @@ impl StorageManagement for Contract {
                     Promise::new(env::predecessor_account_id()).transfer(amount);
                 }
             } else {
+                // the registration fee is taken out of it on save
                 self.internal_register_account(&account_id, min_balance);
                 let refund = amount - min_balance;
                 if refund > 0 {
@@ -46,6 +47,7 @@ impl StorageManagement for Contract {
         assert_one_yocto();
         self.assert_contract_running();
         let account_id = env::predecessor_account_id();
+        assert_no_in_flight(&account_id, None);
         let amount = amount.unwrap_or(U128(0)).0;
         let withdraw_amount = self.internal_storage_withdraw(&account_id, amount);
         Promise::new(account_id.clone()).transfer(withdraw_amount);
@@ -60,6 +62,7 @@ impl StorageManagement for Contract {
         assert_one_yocto();
         self.assert_contract_running();
         let account_id = env::predecessor_account_id();
+        assert_no_in_flight(&account_id, None);
         if let Some(account_deposit) = self.internal_get_account(&account_id) {
             // [AUDITION_AMENDMENT] 2.1.1 Improper Account Unregistration
             assert!(
@@ -67,7 +70,10 @@ impl StorageManagement for Contract {
                 "{}", ERR18_TOKENS_NOT_EMPTY
             );
             self.accounts.remove(&account_id);
-            Promise::new(account_id.clone()).transfer(account_deposit.near_amount);
+            unindex_replica_account(&account_id);
+            bump_state_version();
+            let in_wnear = self.internal_settle_storage_top_up(&account_id);
+            Promise::new(account_id.clone()).transfer(account_deposit.near_amount - in_wnear);
             true
         } else {
             false
@@ -76,7 +82,7 @@ impl StorageManagement for Contract {
 
     fn storage_balance_bounds(&self) -> StorageBalanceBounds {
         StorageBalanceBounds {
-            min: Account::min_storage_usage().into(),
+            min: (Account::min_storage_usage() + Account::registration_fee()).into(),
             max: None,
         }
     }
@@ -87,7 +93,7 @@ impl StorageManagement for Contract {
                 { 
                     StorageBalance {
                         total: U128(account.near_amount),
-                        available: U128(account.storage_available()),
+                        available: U128(self.internal_storage_available(account_id.as_ref(), &account)),
                     } 
                 })
     }
@@ -0,0 +1,131 @@
+use crate::*;
+use near_sdk::json_types::U64;
+
+/// Collections of the contract state that keep their length, or are counted through an index.
+const INSPECTED_COLLECTIONS: &[&str] = &[
+    "whitelisted_tokens",
+    "guardians",
+    "frozen_tokens",
+    "referrals",
+    "unit_share_cumulative_infos",
+    "accounts",
+];
+
+/// Entry counts of lookup collections, keyed by the collection serialized, i.e. its prefix.
+pub fn read_collection_lens_from_storage() -> LookupMap<Vec<u8>, u64> {
+    if let Some(content) = env::storage_read(COLLECTION_LENS.as_bytes()) {
+        LookupMap::try_from_slice(&content).expect("deserialize collection lens failed.")
+    } else {
+        LookupMap::new(StorageKey::CollectionLens)
+    }
+}
+
+pub fn write_collection_lens_to_storage(collection_lens: LookupMap<Vec<u8>, u64>) {
+    env::storage_write(
+        COLLECTION_LENS.as_bytes(),
+        &collection_lens.try_to_vec().unwrap(),
+    );
+}
+
+/// Counts an entry added to or removed from the lookup collection.
+/// Entries from before the count existed are missing, so removal saturates.
+pub fn update_collection_len<C: BorshSerialize>(collection: &C, increase: bool) {
+    let key = collection.try_to_vec().unwrap();
+    let mut collection_lens = read_collection_lens_from_storage();
+    let len = collection_lens.get(&key).unwrap_or(0);
+    collection_lens.insert(&key, &if increase { len + 1 } else { len.saturating_sub(1) });
+    write_collection_lens_to_storage(collection_lens);
+}
+
+/// Storage taken under a key or key prefix.
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+#[cfg_attr(not(target_arch = "wasm32"), derive(Debug, PartialEq))]
+pub struct StorageKeyInfo {
+    /// Custom key, field of the contract state, `pool:<id>` or `shares:<id>` of a pool.
+    pub name: String,
+    /// Entries under the key, 0 for a key that isn't written yet.
+    pub entries: U64,
+    /// Bytes of the keys and values, None where the entries can't be read one by one.
+    pub bytes: Option<U64>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+#[cfg_attr(not(target_arch = "wasm32"), derive(Debug))]
+pub struct StorageInspection {
+    pub storage_usage: U64,
+    /// Number of keys inspected over all pages.
+    pub total_keys: u64,
+    pub keys: Vec<StorageKeyInfo>,
+}
+
+fn raw_key_info(name: String, key: &[u8]) -> StorageKeyInfo {
+    let value_len = env::storage_read(key).map(|value| value.len() as u64);
+    StorageKeyInfo {
+        name,
+        entries: U64(value_len.is_some() as u64),
+        bytes: Some(U64(value_len.map(|value_len| key.len() as u64 + value_len).unwrap_or(0))),
+    }
+}
+
+impl Contract {
+    fn internal_storage_key_info(&self, index: u64) -> StorageKeyInfo {
+        let custom_keys = CUSTOM_KEYS.len() as u64;
+        let collections = INSPECTED_COLLECTIONS.len() as u64;
+        if index == 0 {
+            return raw_key_info("STATE".to_string(), b"STATE");
+        }
+        let index = index - 1;
+        if index < custom_keys {
+            let key = CUSTOM_KEYS[index as usize];
+            return raw_key_info(key.to_string(), key.as_bytes());
+        }
+        let index = index - custom_keys;
+        if index < collections {
+            let name = INSPECTED_COLLECTIONS[index as usize];
+            let entries = match name {
+                "whitelisted_tokens" => self.whitelisted_tokens.len(),
+                "guardians" => self.guardians.len(),
+                "frozen_tokens" => self.frozen_tokens.len(),
+                "referrals" => self.referrals.len(),
+                "unit_share_cumulative_infos" => self.unit_share_cumulative_infos.len(),
+                _ => read_replica_account_count_from_storage(),
+            };
+            return StorageKeyInfo { name: name.to_string(), entries: U64(entries), bytes: None };
+        }
+        let index = index - collections;
+        let pool_id = index / 2;
+        if index % 2 == 1 {
+            let shares = self.pools.get(pool_id).expect(ERR85_NO_POOL).shares().try_to_vec().unwrap();
+            let entries = read_collection_lens_from_storage().get(&shares).unwrap_or(0);
+            return StorageKeyInfo { name: format!("shares:{}", pool_id), entries: U64(entries), bytes: None };
+        }
+        let mut key = StorageKey::Pools.try_to_vec().unwrap();
+        key.extend_from_slice(&pool_id.to_le_bytes());
+        raw_key_info(format!("pool:{}", pool_id), &key)
+    }
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Sizes of the contract state by key, custom keys and collections first, then every pool
+    /// followed by its share holders. Accounts and share holders are counted since their counts exist,
+    /// other entries of lookup collections are only covered by storage_usage.
+    /// Owner only, the result is read from the transaction outcome.
+    #[payable]
+    pub fn inspect_storage(&mut self, from_index: Option<u64>, limit: Option<u64>) -> StorageInspection {
+        assert_one_yocto();
+        self.assert_owner();
+        let total_keys = 1 + CUSTOM_KEYS.len() as u64 + INSPECTED_COLLECTIONS.len() as u64 + 2 * self.pools.len();
+        let from_index = from_index.unwrap_or(0);
+        let limit = limit.unwrap_or(total_keys);
+        StorageInspection {
+            storage_usage: U64(env::storage_usage()),
+            total_keys,
+            keys: (from_index..std::cmp::min(from_index.saturating_add(limit), total_keys))
+                .map(|index| self.internal_storage_key_info(index))
+                .collect(),
+        }
+    }
+}
@@ -0,0 +1,197 @@
+use crate::*;
+use crate::utils::{ext_self, ext_wrap_near, GAS_FOR_NEAR_WITHDRAW, GAS_FOR_RESOLVE_TRANSFER};
+use near_sdk::is_promise_success;
+use near_sdk::collections::LookupSet;
+
+/// Accounts that let their inner wNEAR cover storage they are short of.
+pub fn read_storage_top_up_accounts_from_storage() -> LookupSet<AccountId> {
+    if let Some(content) = env::storage_read(STORAGE_TOP_UP_ACCOUNTS.as_bytes()) {
+        LookupSet::try_from_slice(&content).expect("deserialize storage top up accounts failed.")
+    } else {
+        LookupSet::new(StorageKey::StorageTopUpAccounts)
+    }
+}
+
+pub fn write_storage_top_up_accounts_to_storage(accounts: LookupSet<AccountId>) {
+    env::storage_write(
+        STORAGE_TOP_UP_ACCOUNTS.as_bytes(),
+        &accounts.try_to_vec().unwrap(),
+    );
+}
+
+/// wNEAR moved out of inner accounts into storage deposits, per wNEAR token, held by
+/// the exchange until the owner unwraps it.
+pub fn read_storage_top_up_wnear_from_storage() -> LookupMap<AccountId, Balance> {
+    if let Some(content) = env::storage_read(STORAGE_TOP_UP_WNEAR.as_bytes()) {
+        LookupMap::try_from_slice(&content).expect("deserialize storage top up wnear failed.")
+    } else {
+        LookupMap::new(StorageKey::StorageTopUpWnear)
+    }
+}
+
+pub fn write_storage_top_up_wnear_to_storage(storage_wnear: LookupMap<AccountId, Balance>) {
+    env::storage_write(
+        STORAGE_TOP_UP_WNEAR.as_bytes(),
+        &storage_wnear.try_to_vec().unwrap(),
+    );
+}
+
+/// Part of each account's storage deposit paid with its inner wNEAR. It is settled in wNEAR,
+/// so it can't be taken out with `storage_withdraw` and goes back unwrapped on unregister.
+pub fn read_storage_top_up_deposits_from_storage() -> LookupMap<AccountId, Balance> {
+    if let Some(content) = env::storage_read(STORAGE_TOP_UP_DEPOSITS.as_bytes()) {
+        LookupMap::try_from_slice(&content).expect("deserialize storage top up deposits failed.")
+    } else {
+        LookupMap::new(StorageKey::StorageTopUpDeposits)
+    }
+}
+
+pub fn write_storage_top_up_deposits_to_storage(deposits: LookupMap<AccountId, Balance>) {
+    env::storage_write(
+        STORAGE_TOP_UP_DEPOSITS.as_bytes(),
+        &deposits.try_to_vec().unwrap(),
+    );
+}
+
+pub fn storage_top_up_deposit_of(account_id: &AccountId) -> Balance {
+    read_storage_top_up_deposits_from_storage().get(account_id).unwrap_or(0)
+}
+
+fn update_storage_top_up_wnear(token_id: &AccountId, update: impl FnOnce(Balance) -> Balance) {
+    let mut storage_wnear = read_storage_top_up_wnear_from_storage();
+    let amount = update(storage_wnear.get(token_id).unwrap_or(0));
+    if amount == 0 {
+        storage_wnear.remove(token_id);
+    } else {
+        storage_wnear.insert(token_id, &amount);
+    }
+    write_storage_top_up_wnear_to_storage(storage_wnear);
+}
+
+impl Contract {
+    /// Raises the account's storage deposit to `required` with its inner wNEAR if it opted in
+    /// and holds enough, otherwise leaves it to the storage check to fail.
+    /// Storage deposit the account can withdraw, it never drops below the part paid with wNEAR.
+    pub(crate) fn internal_storage_available(&self, account_id: &AccountId, account: &Account) -> Balance {
+        let locked = std::cmp::max(account.storage_usage(), storage_top_up_deposit_of(account_id));
+        account.near_amount.saturating_sub(locked)
+    }
+
+    /// Sends the part of an unregistered account's storage deposit paid with wNEAR back,
+    /// unwrapped, returns how much of it is sent that way. The rest the owner already unwrapped.
+    /// What is sent counts towards the daily wNEAR withdrawal limit of the account, if it set any.
+    pub(crate) fn internal_settle_storage_top_up(&mut self, account_id: &AccountId) -> Balance {
+        let mut deposits = read_storage_top_up_deposits_from_storage();
+        let deposit = deposits.remove(account_id).unwrap_or(0);
+        write_storage_top_up_deposits_to_storage(deposits);
+        let wnear_id = match self.wnear_id.clone() {
+            Some(wnear_id) if deposit > 0 => wnear_id,
+            _ => return 0,
+        };
+        // the owner may have unwrapped some of it already
+        let held = read_storage_top_up_wnear_from_storage().get(&wnear_id).unwrap_or(0);
+        let in_wnear = std::cmp::min(deposit, held);
+        if in_wnear > 0 {
+            self.internal_record_limited_withdrawal(account_id, &wnear_id, in_wnear);
+            update_storage_top_up_wnear(&wnear_id, |amount| amount - in_wnear);
+            self.internal_send_tokens(account_id, &wnear_id, in_wnear, Some(false));
+        }
+        in_wnear
+    }
+
+    pub(crate) fn internal_top_up_storage(&mut self, account_id: &AccountId, account: &mut Account, required: Balance) {
+        if account.near_amount >= required {
+            return;
+        }
+        let wnear_id = match self.wnear_id.clone() {
+            Some(wnear_id) => wnear_id,
+            None => return,
+        };
+        let shortfall = required - account.near_amount;
+        if account.get_balance(&wnear_id).unwrap_or(0) < shortfall
+            || !read_storage_top_up_accounts_from_storage().contains(account_id) {
+            return;
+        }
+        account.withdraw(&wnear_id, shortfall);
+        account.near_amount += shortfall;
+        update_storage_top_up_wnear(&wnear_id, |amount| amount + shortfall);
+        // the record was created when the account opted in
+        let mut deposits = read_storage_top_up_deposits_from_storage();
+        deposits.insert(account_id, &(deposits.get(account_id).unwrap_or(0) + shortfall));
+        write_storage_top_up_deposits_to_storage(deposits);
+        log!("Storage of {} topped up with {} {}", account_id, shortfall, wnear_id);
+    }
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Let the caller's inner wNEAR cover storage it runs short of, e.g. when registering
+    /// tokens or joining pools, instead of failing on insufficient storage.
+    /// NEAR covered this way can't be withdrawn with `storage_withdraw`, it is returned on unregister.
+    /// Attached deposit covers the record, the rest is refunded.
+    #[payable]
+    pub fn set_storage_auto_top_up(&mut self, enabled: bool) {
+        let prev_storage = env::storage_usage();
+        let account_id = env::predecessor_account_id();
+        assert!(self.accounts.get(&account_id).is_some(), "{}", ERR10_ACC_NOT_REGISTERED);
+        let mut accounts = read_storage_top_up_accounts_from_storage();
+        let mut deposits = read_storage_top_up_deposits_from_storage();
+        if enabled {
+            accounts.insert(&account_id);
+            if deposits.get(&account_id).is_none() {
+                deposits.insert(&account_id, &0);
+            }
+        } else {
+            accounts.remove(&account_id);
+            if deposits.get(&account_id) == Some(0) {
+                deposits.remove(&account_id);
+            }
+        }
+        write_storage_top_up_accounts_to_storage(accounts);
+        write_storage_top_up_deposits_to_storage(deposits);
+        self.internal_check_storage(prev_storage);
+    }
+
+    pub fn get_storage_auto_top_up(&self, account_id: ValidAccountId) -> bool {
+        read_storage_top_up_accounts_from_storage().contains(account_id.as_ref())
+    }
+
+    pub fn get_storage_top_up_wnear(&self, token_id: ValidAccountId) -> U128 {
+        U128(read_storage_top_up_wnear_from_storage().get(token_id.as_ref()).unwrap_or(0))
+    }
+
+    /// Unwrap the wNEAR taken for storage deposits back into NEAR held by the exchange.
+    #[payable]
+    pub fn unwrap_storage_top_up_wnear(&mut self, token_id: ValidAccountId) -> Promise {
+        assert_one_yocto();
+        self.assert_owner();
+        audit_privileged_action("unwrap_storage_top_up_wnear");
+        let token_id: AccountId = token_id.into();
+        let amount = read_storage_top_up_wnear_from_storage().get(&token_id).expect("No wNEAR to unwrap");
+        update_storage_top_up_wnear(&token_id, |_| 0);
+        ext_wrap_near::near_withdraw(
+            U128(amount),
+            &token_id,
+            1,
+            GAS_FOR_NEAR_WITHDRAW,
+        )
+        .then(ext_self::exchange_callback_post_unwrap_storage_top_up_wnear(
+            token_id.clone(),
+            U128(amount),
+            &env::current_account_id(),
+            0,
+            GAS_FOR_RESOLVE_TRANSFER,
+        ))
+    }
+
+    /// Keeps the wNEAR for a later unwrap if it failed.
+    #[private]
+    pub fn exchange_callback_post_unwrap_storage_top_up_wnear(&mut self, token_id: AccountId, amount: U128) {
+        if is_promise_success() {
+            update_token_ledger(&token_id, |ledger| ledger.total -= amount.0 as i128);
+        } else {
+            update_storage_top_up_wnear(&token_id, |unwrapped| unwrapped + amount.0);
+            log!("Unwrap of {} {} taken for storage failed", amount.0, token_id);
+        }
+    }
+}
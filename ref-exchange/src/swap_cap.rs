@@ -0,0 +1,59 @@
+use crate::*;
+use crate::utils::{u128_ratio, FEE_DIVISOR};
+
+pub fn read_swap_caps_from_storage() -> LookupMap<u64, u32> {
+    if let Some(content) = env::storage_read(SWAP_CAPS.as_bytes()) {
+        LookupMap::try_from_slice(&content).expect("deserialize swap caps failed.")
+    } else {
+        LookupMap::new(StorageKey::SwapCaps)
+    }
+}
+
+pub fn write_swap_caps_to_storage(swap_caps: LookupMap<u64, u32>) {
+    env::storage_write(
+        SWAP_CAPS.as_bytes(),
+        &swap_caps.try_to_vec().unwrap(),
+    );
+}
+
+/// Panics if a swap paid out more than the most allowed by its pool's swap cap.
+pub fn assert_within_swap_cap(max_amount_out: Option<Balance>, amount_out: Balance) {
+    if let Some(max_amount_out) = max_amount_out {
+        assert!(amount_out <= max_amount_out, "Swap exceeds pool swap cap of {}", max_amount_out);
+    }
+}
+
+impl Contract {
+    /// Most of `token_out` a single swap may take from the pool as it is now, None if uncapped.
+    pub(crate) fn internal_max_swap_out(&self, pool_id: u64, pool: &Pool, token_out: &AccountId) -> Option<Balance> {
+        let max_swap_out_bps = read_swap_caps_from_storage().get(&pool_id)?;
+        let out_idx = pool.tokens().iter().position(|id| id == token_out).expect(ERR63_MISSING_TOKEN);
+        Some(u128_ratio(pool.get_amounts()[out_idx], max_swap_out_bps as u128, FEE_DIVISOR as u128))
+    }
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Limit the part of the output reserve a single swap can take from the pool,
+    /// so large swaps against thin pools fail instead of paying out at absurd slippage.
+    /// None removes the cap.
+    #[payable]
+    pub fn set_pool_swap_cap(&mut self, pool_id: u64, max_swap_out_bps: Option<u32>) {
+        assert_one_yocto();
+        self.assert_owner();
+        audit_privileged_action("set_pool_swap_cap");
+        self.internal_get_pool(pool_id);
+        let mut swap_caps = read_swap_caps_from_storage();
+        if let Some(max_swap_out_bps) = max_swap_out_bps {
+            assert!(max_swap_out_bps > 0 && max_swap_out_bps <= FEE_DIVISOR, "Invalid max_swap_out_bps");
+            swap_caps.insert(&pool_id, &max_swap_out_bps);
+        } else {
+            swap_caps.remove(&pool_id);
+        }
+        write_swap_caps_to_storage(swap_caps);
+    }
+
+    pub fn get_pool_swap_cap(&self, pool_id: u64) -> Option<u32> {
+        read_swap_caps_from_storage().get(&pool_id)
+    }
+}
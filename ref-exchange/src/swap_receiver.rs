@@ -0,0 +1,72 @@
+use crate::*;
+
+impl Contract {
+    /// Withdraws the output of a finished swap chain from the trader's inner account
+    /// and sends it to the receiver.
+    pub(crate) fn internal_send_swap_output(
+        &mut self,
+        trader_id: &AccountId,
+        receiver_id: &AccountId,
+        actions: &[Action],
+        result: &ActionResult,
+        skip_unwrap_near: Option<bool>,
+    ) {
+        let token_out = match actions.last() {
+            Some(Action::Swap(swap_action)) => swap_action.token_out.clone(),
+            _ => return,
+        };
+        let amount = result.to_amount();
+        if amount == 0 {
+            return;
+        }
+        self.internal_record_limited_withdrawal(trader_id, &token_out, amount);
+        let mut account = self.internal_unwrap_account(trader_id);
+        account.withdraw(&token_out, amount);
+        self.internal_save_account(trader_id, account);
+        self.internal_send_tokens_to(trader_id, receiver_id, &token_out, amount, skip_unwrap_near);
+    }
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Same as `swap`, with the final output sent straight to the receiver's wallet, e.g. to pay a merchant.
+    /// If the transfer fails, the output goes back to the trader's inner account, or is held for the
+    /// trader to reclaim, as for a failed withdraw.
+    #[payable]
+    pub fn swap_to(
+        &mut self,
+        actions: Vec<SwapAction>,
+        referral_id: Option<ValidAccountId>,
+        receiver_id: ValidAccountId,
+        skip_unwrap_near: Option<bool>,
+    ) -> U128 {
+        assert_one_yocto();
+        assert_account_not_denied(receiver_id.as_ref());
+        U128(
+            self.internal_execute_sender_actions(
+                actions.into_iter().map(Action::Swap).collect(),
+                referral_id,
+                None,
+                Some(receiver_id.as_ref()),
+                skip_unwrap_near,
+            )
+            .to_amount(),
+        )
+    }
+
+    /// Same as `withdraw` to another wallet, failed transfers are handled as in `swap_to`.
+    #[payable]
+    pub fn withdraw_to(
+        &mut self,
+        token_id: ValidAccountId,
+        amount: U128,
+        receiver_id: ValidAccountId,
+        skip_unwrap_near: Option<bool>,
+    ) -> Promise {
+        assert_account_not_denied(receiver_id.as_ref());
+        if receiver_id.as_ref() != &env::predecessor_account_id() {
+            assert_account_not_denied(&env::predecessor_account_id());
+        }
+        self.internal_withdraw(token_id, amount, None, skip_unwrap_near, Some(receiver_id.as_ref())).1
+    }
+}
@@ -0,0 +1,138 @@
+use crate::*;
+use near_sdk::collections::LookupSet;
+
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(crate = "near_sdk::serde")]
+#[cfg_attr(not(target_arch = "wasm32"), derive(Debug))]
+pub enum SwapScreenMode {
+    /// Everyone except the listed accounts can swap.
+    Denylist,
+    /// Only the listed accounts can swap.
+    Allowlist,
+}
+
+/// Pre-swap screening of a permissioned pool against its account list.
+/// `screener_id`, typically a compliance provider, can keep the list up to date
+/// alongside the owner and guardians.
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone)]
+#[serde(crate = "near_sdk::serde")]
+#[cfg_attr(not(target_arch = "wasm32"), derive(Debug))]
+pub struct SwapScreenConfig {
+    pub mode: SwapScreenMode,
+    pub screener_id: Option<AccountId>,
+}
+
+pub fn read_swap_screen_configs_from_storage() -> LookupMap<u64, SwapScreenConfig> {
+    if let Some(content) = env::storage_read(SWAP_SCREEN_CONFIGS.as_bytes()) {
+        LookupMap::try_from_slice(&content).expect("deserialize swap screen configs failed.")
+    } else {
+        LookupMap::new(StorageKey::SwapScreenConfigs)
+    }
+}
+
+pub fn write_swap_screen_configs_to_storage(swap_screen_configs: LookupMap<u64, SwapScreenConfig>) {
+    env::storage_write(
+        SWAP_SCREEN_CONFIGS.as_bytes(),
+        &swap_screen_configs.try_to_vec().unwrap(),
+    );
+}
+
+pub fn read_swap_screen_list_from_storage() -> LookupSet<(u64, AccountId)> {
+    if let Some(content) = env::storage_read(SWAP_SCREEN_LIST.as_bytes()) {
+        LookupSet::try_from_slice(&content).expect("deserialize swap screen list failed.")
+    } else {
+        LookupSet::new(StorageKey::SwapScreenList)
+    }
+}
+
+pub fn write_swap_screen_list_to_storage(swap_screen_list: LookupSet<(u64, AccountId)>) {
+    env::storage_write(
+        SWAP_SCREEN_LIST.as_bytes(),
+        &swap_screen_list.try_to_vec().unwrap(),
+    );
+}
+
+impl Contract {
+    /// Panics if the pool screens swaps and `trader_id` doesn't pass.
+    pub(crate) fn assert_swap_screen_passed(&self, pool_id: u64, trader_id: &AccountId) {
+        let config = match read_swap_screen_configs_from_storage().get(&pool_id) {
+            Some(config) => config,
+            None => return,
+        };
+        let listed = read_swap_screen_list_from_storage().contains(&(pool_id, trader_id.clone()));
+        let passed = match config.mode {
+            SwapScreenMode::Denylist => !listed,
+            SwapScreenMode::Allowlist => listed,
+        };
+        assert!(passed, "Swap from {} rejected by pool {} screening", trader_id, pool_id);
+    }
+
+    fn assert_swap_screen_manager(&self, pool_id: u64) {
+        let screener_id = read_swap_screen_configs_from_storage()
+            .get(&pool_id)
+            .and_then(|config| config.screener_id);
+        assert!(
+            self.is_owner_or_guardians() || screener_id == Some(env::predecessor_account_id()),
+            "{}", ERR100_NOT_ALLOWED
+        );
+    }
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Set or remove (None) the swap screening of a pool. The account list is kept when removed.
+    #[payable]
+    pub fn set_pool_swap_screen(&mut self, pool_id: u64, config: Option<SwapScreenConfig>) {
+        assert_one_yocto();
+        self.assert_owner();
+        audit_privileged_action("set_pool_swap_screen");
+        self.internal_get_pool(pool_id);
+        let mut swap_screen_configs = read_swap_screen_configs_from_storage();
+        match config {
+            Some(config) => {
+                swap_screen_configs.insert(&pool_id, &config);
+            }
+            None => {
+                swap_screen_configs.remove(&pool_id);
+            }
+        }
+        write_swap_screen_configs_to_storage(swap_screen_configs);
+    }
+
+    /// Add accounts to the pool's screening list. Callable by owner, guardians or the pool's screener.
+    /// Attached deposit covers the new entries, the rest is refunded.
+    #[payable]
+    pub fn extend_pool_swap_screen_list(&mut self, pool_id: u64, account_ids: Vec<ValidAccountId>) {
+        assert!(env::attached_deposit() > 0, "{}", ERR35_AT_LEAST_ONE_YOCTO);
+        self.assert_swap_screen_manager(pool_id);
+        audit_privileged_action("extend_pool_swap_screen_list");
+        let prev_storage = env::storage_usage();
+        let mut swap_screen_list = read_swap_screen_list_from_storage();
+        for account_id in account_ids {
+            swap_screen_list.insert(&(pool_id, account_id.into()));
+        }
+        write_swap_screen_list_to_storage(swap_screen_list);
+        self.internal_check_storage(prev_storage);
+    }
+
+    /// Remove accounts from the pool's screening list. Callable by owner, guardians or the pool's screener.
+    #[payable]
+    pub fn remove_pool_swap_screen_list(&mut self, pool_id: u64, account_ids: Vec<ValidAccountId>) {
+        assert_one_yocto();
+        self.assert_swap_screen_manager(pool_id);
+        audit_privileged_action("remove_pool_swap_screen_list");
+        let mut swap_screen_list = read_swap_screen_list_from_storage();
+        for account_id in account_ids {
+            swap_screen_list.remove(&(pool_id, account_id.into()));
+        }
+        write_swap_screen_list_to_storage(swap_screen_list);
+    }
+
+    pub fn get_pool_swap_screen(&self, pool_id: u64) -> Option<SwapScreenConfig> {
+        read_swap_screen_configs_from_storage().get(&pool_id)
+    }
+
+    pub fn is_pool_swap_screen_listed(&self, pool_id: u64, account_id: ValidAccountId) -> bool {
+        read_swap_screen_list_from_storage().contains(&(pool_id, account_id.into()))
+    }
+}
@@ -0,0 +1,71 @@
+use crate::*;
+
+pub const MAX_TOKEN_SYMBOL_LEN: usize = 16;
+
+/// Canonical token of each symbol, symbols are kept lowercase.
+pub fn read_token_aliases_from_storage() -> HashMap<String, AccountId> {
+    if let Some(content) = env::storage_read(TOKEN_ALIASES.as_bytes()) {
+        HashMap::try_from_slice(&content).expect("deserialize token aliases failed.")
+    } else {
+        HashMap::new()
+    }
+}
+
+pub fn write_token_aliases_to_storage(token_aliases: HashMap<String, AccountId>) {
+    env::storage_write(
+        TOKEN_ALIASES.as_bytes(),
+        &token_aliases.try_to_vec().unwrap(),
+    );
+}
+
+/// Tokens whose account id starts with the label of a canonical symbol, e.g. `usdc.fake.near`,
+/// without being the canonical token of that symbol.
+pub fn spoofed_tokens(token_ids: &[AccountId]) -> Vec<AccountId> {
+    let token_aliases = read_token_aliases_from_storage();
+    token_ids
+        .iter()
+        .filter(|token_id| {
+            let label = token_id.split('.').next().unwrap_or_default().to_lowercase();
+            token_aliases.get(&label).map(|canonical_id| canonical_id != *token_id).unwrap_or(false)
+        })
+        .cloned()
+        .collect()
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Set the canonical token of a symbol, None removes the symbol.
+    #[payable]
+    pub fn set_token_alias(&mut self, symbol: String, token_id: Option<ValidAccountId>) {
+        assert_one_yocto();
+        assert!(self.is_owner_or_guardians(), "{}", ERR100_NOT_ALLOWED);
+        audit_privileged_action("set_token_alias");
+        let symbol = symbol.to_lowercase();
+        assert!(
+            !symbol.is_empty() && symbol.len() <= MAX_TOKEN_SYMBOL_LEN && !symbol.contains('.'),
+            "Invalid token symbol"
+        );
+        let mut token_aliases = read_token_aliases_from_storage();
+        if let Some(token_id) = token_id {
+            token_aliases.insert(symbol, token_id.into());
+        } else {
+            token_aliases.remove(&symbol);
+        }
+        write_token_aliases_to_storage(token_aliases);
+    }
+
+    pub fn get_token_aliases(&self) -> HashMap<String, AccountId> {
+        read_token_aliases_from_storage()
+    }
+
+    /// Tokens of each given pool impersonating a canonical token, pools without any are left out.
+    pub fn get_spoofed_pool_tokens(&self, pool_ids: Vec<u64>) -> HashMap<u64, Vec<AccountId>> {
+        pool_ids
+            .into_iter()
+            .filter_map(|pool_id| {
+                let spoofed = spoofed_tokens(self.internal_get_pool(pool_id).tokens());
+                if spoofed.is_empty() { None } else { Some((pool_id, spoofed)) }
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,145 @@
+use crate::*;
+use near_sdk::json_types::I128;
+
+/// Running per token flows of the exchange. `total` moves with tokens entering and leaving the contract,
+/// `inner_balances` with every change of an account's inner balance. Both track flows since the ledger
+/// was introduced, older balances are brought in once by the owner with `set_token_ledger`.
+#[derive(BorshSerialize, BorshDeserialize, Default)]
+pub struct TokenLedger {
+    pub total: i128,
+    pub inner_balances: i128,
+    /// Sent out and waiting for the transfer result.
+    pub pending_withdrawals: Balance,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+#[cfg_attr(not(target_arch = "wasm32"), derive(Debug))]
+pub struct TokenAccountingReport {
+    pub token_id: AccountId,
+    pub total: I128,
+    pub inner_balances: I128,
+    pub pending_withdrawals: U128,
+    pub pool_reserves: U128,
+    /// Tokens kept aside outside the pool reserves: unclaimed LP fees, admin fee tokens, maker rebate escrows, fee rebates,
+    /// wNEAR taken for storage deposits, failed withdrawals held for reclaim, and the LP incentive, launch auction
+    /// and TWAP order escrows.
+    pub off_pool_reserves: U128,
+    /// total - (inner_balances + pending_withdrawals + pool_reserves + off_pool_reserves),
+    /// only given when all pools were summed. Stable like pools may show dust from decimal normalization.
+    pub drift: Option<I128>,
+}
+
+pub fn read_token_ledgers_from_storage() -> LookupMap<AccountId, TokenLedger> {
+    if let Some(content) = env::storage_read(TOKEN_LEDGERS.as_bytes()) {
+        LookupMap::try_from_slice(&content).expect("deserialize token ledgers failed.")
+    } else {
+        LookupMap::new(StorageKey::TokenLedgers)
+    }
+}
+
+pub fn write_token_ledgers_to_storage(token_ledgers: LookupMap<AccountId, TokenLedger>) {
+    env::storage_write(
+        TOKEN_LEDGERS.as_bytes(),
+        &token_ledgers.try_to_vec().unwrap(),
+    );
+}
+
+pub(crate) fn update_token_ledger<F: FnOnce(&mut TokenLedger)>(token_id: &AccountId, f: F) {
+    let mut token_ledgers = read_token_ledgers_from_storage();
+    let mut ledger = token_ledgers.get(token_id).unwrap_or_default();
+    f(&mut ledger);
+    token_ledgers.insert(token_id, &ledger);
+    write_token_ledgers_to_storage(token_ledgers);
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Compare the running totals of the given tokens with what the exchange accounts for.
+    /// Pools from `from_index` to `from_index + limit` are summed, by default all of them.
+    pub fn get_token_accounting(
+        &self,
+        token_ids: Vec<ValidAccountId>,
+        from_index: Option<u64>,
+        limit: Option<u64>,
+    ) -> Vec<TokenAccountingReport> {
+        let from_index = from_index.unwrap_or(0);
+        let to_index = std::cmp::min(from_index + limit.unwrap_or(self.pools.len()), self.pools.len());
+        let token_ids: Vec<AccountId> = token_ids.into_iter().map(|token_id| token_id.into()).collect();
+        let mut pool_reserves = vec![0; token_ids.len()];
+        let mut off_pool_reserves = vec![0; token_ids.len()];
+        let pool_fee_growth = read_pool_fee_growth_from_storage();
+        let admin_fee_tokens = read_admin_fee_tokens_from_storage();
+        let maker_rebate_escrows = read_maker_rebate_escrows_from_storage();
+        let lp_incentives = read_lp_incentives_from_storage();
+        let launch_auctions = read_launch_auctions_from_storage();
+        for pool_id in from_index..to_index {
+            let pool = self.pools.get(pool_id).expect(ERR85_NO_POOL);
+            let unclaimed_fees = pool_fee_growth.get(&pool_id).map(|state| state.unclaimed);
+            let accrued_admin_fees = admin_fee_tokens.get(&pool_id);
+            let maker_rebate_escrow = maker_rebate_escrows.get(&pool_id);
+            for (i, (token_id, amount)) in pool.tokens().iter().zip(pool.get_amounts()).enumerate() {
+                if let Some(index) = token_ids.iter().position(|id| id == token_id) {
+                    pool_reserves[index] += amount;
+                    off_pool_reserves[index] += unclaimed_fees.as_ref().map(|v| v[i]).unwrap_or(0)
+                        + accrued_admin_fees.as_ref().map(|v| v[i]).unwrap_or(0)
+                        + maker_rebate_escrow.as_ref().map(|v| v[i]).unwrap_or(0);
+                }
+            }
+            if let Some(incentive) = lp_incentives.get(&pool_id) {
+                if let Some(index) = token_ids.iter().position(|id| *id == incentive.token_id) {
+                    off_pool_reserves[index] += incentive.escrowed;
+                }
+            }
+            if let Some(auction) = launch_auctions.get(&pool_id) {
+                if let Some(index) = token_ids.iter().position(|id| *id == auction.sale_token) {
+                    off_pool_reserves[index] += auction.unsold + auction.seed_reserve;
+                }
+                if let Some(index) = token_ids.iter().position(|id| *id == auction.quote_token) {
+                    off_pool_reserves[index] += auction.raised;
+                }
+            }
+        }
+        let fee_rebate_token = read_fee_rebate_config_from_storage().map(|config| config.reward_token);
+        let pending_fee_rebates = read_pending_fee_rebate_totals_from_storage();
+        let storage_top_up_wnear = read_storage_top_up_wnear_from_storage();
+        let unclaimed_withdrawal_totals = read_unclaimed_withdrawal_totals_from_storage();
+        let twap_escrow_totals = read_twap_escrow_totals_from_storage();
+        let token_ledgers = read_token_ledgers_from_storage();
+        let complete = from_index == 0 && to_index == self.pools.len();
+        token_ids.into_iter().enumerate().map(|(index, token_id)| {
+            let ledger = token_ledgers.get(&token_id).unwrap_or_default();
+            if fee_rebate_token.as_ref() == Some(&token_id) {
+                off_pool_reserves[index] += read_fee_rebate_pot_from_storage();
+            }
+            off_pool_reserves[index] += pending_fee_rebates.get(&token_id).cloned().unwrap_or(0);
+            off_pool_reserves[index] += storage_top_up_wnear.get(&token_id).unwrap_or(0);
+            off_pool_reserves[index] += unclaimed_withdrawal_totals.get(&token_id).unwrap_or(0);
+            off_pool_reserves[index] += twap_escrow_totals.get(&token_id).unwrap_or(0);
+            let accounted = ledger.inner_balances
+                + (ledger.pending_withdrawals + pool_reserves[index] + off_pool_reserves[index]) as i128;
+            TokenAccountingReport {
+                total: I128(ledger.total),
+                inner_balances: I128(ledger.inner_balances),
+                pending_withdrawals: U128(ledger.pending_withdrawals),
+                pool_reserves: U128(pool_reserves[index]),
+                off_pool_reserves: U128(off_pool_reserves[index]),
+                drift: if complete { Some(I128(ledger.total - accounted)) } else { None },
+                token_id,
+            }
+        }).collect()
+    }
+
+    /// Set the ledger of a token to the exchange's holdings and inner balance sum measured off chain,
+    /// pending withdrawals are kept.
+    #[payable]
+    pub fn set_token_ledger(&mut self, token_id: ValidAccountId, total: I128, inner_balances: I128) {
+        assert_one_yocto();
+        self.assert_owner();
+        audit_privileged_action("set_token_ledger");
+        update_token_ledger(token_id.as_ref(), |ledger| {
+            ledger.total = total.0;
+            ledger.inner_balances = inner_balances.0;
+        });
+    }
+}
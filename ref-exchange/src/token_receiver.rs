@@ -30,35 +30,58 @@ enum TokenReceiverMessage {
         /// to send token_out back to predecessor with this msg.
         client_echo: Option<String>,
         skip_unwrap_near: Option<bool>,
-        swap_out_recipient: Option<ValidAccountId>
+        swap_out_recipient: Option<ValidAccountId>,
+        /// If not None, each swap step is checked against the quoted amounts.
+        route_quote: Option<RouteQuote>,
     },
     HotZap {
         referral_id: Option<ValidAccountId>,
         hot_zap_actions: Vec<Action>,
         add_liquidity_infos: Vec<AddLiquidityInfo>
     },
+    /// First deposit of a multi token deposit, see `DepositPlanInfo`.
+    DepositPlan {
+        deposit_plan: DepositPlanInfo,
+    },
+    /// Further deposit of the sender's plan in progress.
+    ContinueDepositPlan {
+        continue_deposit_plan: bool,
+    },
+    /// Simple deposit, the memo is echoed in a deposit event for reconciliation.
+    Deposit {
+        memo: String,
+    },
+    /// Intent forwarded by a smart-contract wallet for its user, see `ForwardedIntent`.
+    ForwardedIntent {
+        forwarded_intent: ForwardedIntent,
+    },
+    /// Deposit then zap into a stable kind pool, see `StableZapInfo`.
+    StableZap {
+        stable_zap: StableZapInfo,
+    },
 }
 
 impl Contract {
     /// Executes set of actions on virtual account.
     /// Returns amounts to send to the sender directly.
-    fn internal_direct_actions(
+    pub(crate) fn internal_direct_actions(
         &mut self,
+        sender_id: &AccountId,
         token_in: AccountId,
         amount_in: Balance,
         referral_id: Option<AccountId>,
         actions: &[Action],
+        route_quote: Option<&RouteQuote>,
     ) -> Vec<(AccountId, Balance)> {
 
         // let @ be the virtual account
         let mut account: Account = Account::new(&String::from(VIRTUAL_ACC));
 
-        let referral_info :Option<(AccountId, u32)> = referral_id
-            .as_ref().and_then(|rid| self.referrals.get(&rid))
-            .map(|fee| (referral_id.unwrap().into(), fee));
+        let referral_info = self.internal_get_referral_info(referral_id, sender_id);
 
         account.deposit(&token_in, amount_in);
         let _ = self.internal_execute_actions(
+            sender_id,
             &mut account,
             &referral_info,
             &actions,
@@ -66,11 +89,14 @@ impl Contract {
                 Action::Swap(_) => ActionResult::Amount(U128(amount_in)),
                 Action::SwapByOutput(_) => ActionResult::None,
             },
+            route_quote,
         );
 
         let mut result = vec![];
         for (token, amount) in account.tokens.to_vec() {
             if amount > 0 {
+                // leaves the virtual account to be sent out or reused
+                account.withdraw(&token, amount);
                 result.push((token.clone(), amount));
             }
         }
@@ -93,7 +119,9 @@ impl FungibleTokenReceiver for Contract {
         msg: String,
     ) -> PromiseOrValue<U128> {
         self.assert_contract_running();
+        assert_account_not_denied(sender_id.as_ref());
         let token_in = env::predecessor_account_id();
+        update_token_ledger(&token_in, |ledger| ledger.total += amount.0 as i128);
         if msg.is_empty() {
             // Simple deposit.
             self.assert_no_frozen_tokens(&[token_in.clone()]);
@@ -110,6 +138,7 @@ impl FungibleTokenReceiver for Contract {
                     client_echo,
                     skip_unwrap_near,
                     swap_out_recipient,
+                    route_quote,
                 } => {
                     assert!(!(swap_out_recipient.is_some() && client_echo.is_some()), "client_echo and swap_out_recipient cannot have value at the same time");
                     assert_ne!(actions.len(), 0, "{}", ERR72_AT_LEAST_ONE_SWAP);
@@ -118,10 +147,12 @@ impl FungibleTokenReceiver for Contract {
                     }
                     let referral_id = referral_id.map(|x| x.to_string());
                     let out_amounts = self.internal_direct_actions(
+                        sender_id.as_ref(),
                         token_in,
                         amount.0,
                         referral_id,
                         &actions,
+                        route_quote.as_ref(),
                     );
                     if client_echo.is_some() && sender_id.to_string() == self.burrowland_id {
                         assert!(out_amounts.len() == 1, "Invalid actions, only one out token is allowed");
@@ -146,10 +177,12 @@ impl FungibleTokenReceiver for Contract {
                     let mut account = self.internal_unwrap_account(&sender_id);                    
                     let referral_id = referral_id.map(|x| x.to_string());
                     let out_amounts = self.internal_direct_actions(
+                        &sender_id,
                         token_in,
                         amount.0,
                         referral_id,
                         &hot_zap_actions,
+                        None,
                     );
 
                     let mut token_cache = TokenCache::new();
@@ -159,18 +192,23 @@ impl FungibleTokenReceiver for Contract {
 
                     let prev_storage = env::storage_usage();
                     for add_liquidity_info in add_liquidity_infos {
-                        let mut pool = self.pools.get(add_liquidity_info.pool_id).expect(ERR85_NO_POOL);
+                        self.assert_pool_not_archived(add_liquidity_info.pool_id);
+                        self.assert_pool_launched(add_liquidity_info.pool_id);
+                        self.assert_lp_allowed(add_liquidity_info.pool_id, &sender_id);
+                        let mut pool = self.internal_get_pool(add_liquidity_info.pool_id);
                         let tokens_in_pool = match &pool {
                             Pool::SimplePool(p) => p.token_account_ids.clone(),
                             Pool::RatedSwapPool(p) => p.token_account_ids.clone(),
                             Pool::StableSwapPool(p) => p.token_account_ids.clone(),
                             Pool::DegenSwapPool(p) => p.token_account_ids.clone(),
+                            Pool::RangePool(p) => p.pool.token_account_ids.clone(),
                         };
                         
                         let mut add_liquidity_amounts = add_liquidity_info.amounts.iter().map(|v| v.0).collect();
 
                         match pool {
-                            Pool::SimplePool(_) => {
+                            Pool::SimplePool(_) | Pool::RangePool(_) => {
+                                self.internal_settle_lp_fees(add_liquidity_info.pool_id, &pool, &[&sender_id]);
                                 pool.add_liquidity(
                                     &sender_id,
                                     &mut add_liquidity_amounts,
@@ -184,6 +222,7 @@ impl FungibleTokenReceiver for Contract {
                             },
                             Pool::StableSwapPool(_) | Pool::RatedSwapPool(_) | Pool::DegenSwapPool(_) => {
                                 let min_shares = add_liquidity_info.min_shares.expect("Need input min_shares");
+                                self.internal_record_share_checkpoints(add_liquidity_info.pool_id, &pool, &[&sender_id, &env::current_account_id()]);
                                 pool.add_stable_liquidity(
                                     &sender_id,
                                     &add_liquidity_amounts,
@@ -194,6 +233,7 @@ impl FungibleTokenReceiver for Contract {
                             }
                         };
                         pool.assert_tvl_not_exceed_limit(add_liquidity_info.pool_id);
+                        self.internal_check_degen_tvl_utilization(add_liquidity_info.pool_id, &pool);
 
                         for (cost_token_id, cost_amount) in tokens_in_pool.iter().zip(add_liquidity_amounts.into_iter()) {
                             token_cache.sub(cost_token_id, cost_amount);
@@ -202,10 +242,7 @@ impl FungibleTokenReceiver for Contract {
                         self.pools.replace(add_liquidity_info.pool_id, &pool);
                     }
 
-                    if env::storage_usage() > prev_storage {
-                        let storage_cost = (env::storage_usage() - prev_storage) as Balance * env::storage_byte_cost();
-                        account.near_amount = account.near_amount.checked_sub(storage_cost).expect(ERR11_INSUFFICIENT_STORAGE);
-                    }
+                    self.internal_charge_account_storage(&sender_id, &mut account, prev_storage);
 
                     for (remain_token_id, remain_amount) in token_cache.0.iter() {
                         account.deposit(remain_token_id, *remain_amount);
@@ -223,6 +260,44 @@ impl FungibleTokenReceiver for Contract {
 
                     PromiseOrValue::Value(U128(0))
                 }
+                TokenReceiverMessage::DepositPlan { deposit_plan } => {
+                    self.internal_start_deposit_plan(sender_id.as_ref(), &token_in, amount.0, deposit_plan);
+                    PromiseOrValue::Value(U128(0))
+                }
+                TokenReceiverMessage::ContinueDepositPlan { continue_deposit_plan } => {
+                    assert!(continue_deposit_plan, "{}", ERR28_WRONG_MSG_FORMAT);
+                    self.internal_continue_deposit_plan(sender_id.as_ref(), &token_in, amount.0);
+                    PromiseOrValue::Value(U128(0))
+                }
+                TokenReceiverMessage::Deposit { memo } => {
+                    assert_memo_valid(&memo);
+                    self.assert_no_frozen_tokens(&[token_in.clone()]);
+                    self.internal_deposit(sender_id.as_ref(), &token_in, amount.into());
+                    event::Event::Deposit {
+                        account_id: sender_id.as_ref(),
+                        token_id: &token_in,
+                        amount,
+                        memo: &memo,
+                    }.emit();
+                    PromiseOrValue::Value(U128(0))
+                }
+                TokenReceiverMessage::ForwardedIntent { forwarded_intent } => {
+                    self.internal_execute_forwarded_intent(sender_id.as_ref(), token_in, amount.0, forwarded_intent);
+                    PromiseOrValue::Value(U128(0))
+                }
+                TokenReceiverMessage::StableZap { stable_zap } => {
+                    let sender_id: AccountId = sender_id.into();
+                    self.assert_no_frozen_tokens(&[token_in.clone()]);
+                    self.internal_deposit(&sender_id, &token_in, amount.into());
+                    let prev_storage = env::storage_usage();
+                    self.internal_stable_zap(&sender_id, stable_zap.pool_id, &token_in, amount.0, stable_zap.min_shares);
+                    if env::storage_usage() > prev_storage {
+                        let mut account = self.internal_unwrap_account(&sender_id);
+                        self.internal_charge_account_storage(&sender_id, &mut account, prev_storage);
+                        self.internal_save_account(&sender_id, account);
+                    }
+                    PromiseOrValue::Value(U128(0))
+                }
             }
         }
     }
@@ -0,0 +1,140 @@
+use crate::*;
+use crate::utils::{u128_dec_format, u64_dec_format};
+use near_sdk::Timestamp;
+
+pub const MAX_TRADE_HISTORY_CAPACITY: u32 = 100;
+/// Storage reserved per history slot, covers a record with two max length token ids.
+pub const TRADE_RECORD_STORAGE_BYTES: StorageUsage = 200;
+
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone)]
+#[serde(crate = "near_sdk::serde")]
+#[cfg_attr(not(target_arch = "wasm32"), derive(Debug))]
+pub struct TradeRecord {
+    pub pool_id: u64,
+    pub token_in: AccountId,
+    pub token_out: AccountId,
+    #[serde(with = "u128_dec_format")]
+    pub amount_in: Balance,
+    #[serde(with = "u128_dec_format")]
+    pub amount_out: Balance,
+    #[serde(with = "u64_dec_format")]
+    pub timestamp: Timestamp,
+}
+
+/// Last `capacity` swaps of an account that opted in, oldest overwritten first.
+#[derive(BorshSerialize, BorshDeserialize)]
+pub struct TradeHistory {
+    pub capacity: u32,
+    /// Slot the next record goes to once the buffer is full.
+    pub head: u32,
+    pub records: Vec<TradeRecord>,
+    /// Paid on opt-in for the full buffer, refunded on opt-out.
+    pub storage_deposit: Balance,
+}
+
+impl TradeHistory {
+    fn push(&mut self, record: TradeRecord) {
+        if self.records.len() < self.capacity as usize {
+            self.records.push(record);
+        } else {
+            self.records[self.head as usize] = record;
+            self.head = (self.head + 1) % self.capacity;
+        }
+    }
+
+    /// Records from the oldest to the newest.
+    fn ordered_records(self) -> Vec<TradeRecord> {
+        let mut records = self.records;
+        records.rotate_left(self.head as usize);
+        records
+    }
+}
+
+pub fn read_trade_histories_from_storage() -> LookupMap<AccountId, TradeHistory> {
+    if let Some(content) = env::storage_read(TRADE_HISTORIES.as_bytes()) {
+        LookupMap::try_from_slice(&content).expect("deserialize trade histories failed.")
+    } else {
+        LookupMap::new(StorageKey::TradeHistories)
+    }
+}
+
+pub fn write_trade_histories_to_storage(trade_histories: LookupMap<AccountId, TradeHistory>) {
+    env::storage_write(
+        TRADE_HISTORIES.as_bytes(),
+        &trade_histories.try_to_vec().unwrap(),
+    );
+}
+
+impl Contract {
+    /// Appends a swap to the trader's history if the trader opted in.
+    pub(crate) fn internal_record_trade(
+        &mut self,
+        trader_id: &AccountId,
+        pool_id: u64,
+        token_in: &AccountId,
+        amount_in: Balance,
+        token_out: &AccountId,
+        amount_out: Balance,
+    ) {
+        let mut trade_histories = read_trade_histories_from_storage();
+        if let Some(mut history) = trade_histories.get(trader_id) {
+            history.push(TradeRecord {
+                pool_id,
+                token_in: token_in.clone(),
+                token_out: token_out.clone(),
+                amount_in,
+                amount_out,
+                timestamp: env::block_timestamp(),
+            });
+            trade_histories.insert(trader_id, &history);
+        }
+    }
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Start keeping the caller's last `capacity` swaps on chain.
+    /// Attached deposit must cover TRADE_RECORD_STORAGE_BYTES per slot, the rest is refunded.
+    #[payable]
+    pub fn enable_trade_history(&mut self, capacity: u32) {
+        self.assert_contract_running();
+        assert!(capacity > 0 && capacity <= MAX_TRADE_HISTORY_CAPACITY, "Invalid capacity");
+        let sender_id = env::predecessor_account_id();
+        let mut trade_histories = read_trade_histories_from_storage();
+        assert!(trade_histories.get(&sender_id).is_none(), "Trade history already enabled");
+        let storage_deposit = (capacity as StorageUsage * TRADE_RECORD_STORAGE_BYTES) as Balance * env::storage_byte_cost();
+        let attached_deposit = env::attached_deposit();
+        assert!(
+            attached_deposit >= storage_deposit,
+            "{}: {}", ERR11_INSUFFICIENT_STORAGE, storage_deposit
+        );
+        trade_histories.insert(&sender_id, &TradeHistory {
+            capacity,
+            head: 0,
+            records: vec![],
+            storage_deposit,
+        });
+        write_trade_histories_to_storage(trade_histories);
+        if attached_deposit > storage_deposit {
+            Promise::new(sender_id).transfer(attached_deposit - storage_deposit);
+        }
+    }
+
+    /// Drop the caller's trade history and refund its storage deposit.
+    #[payable]
+    pub fn disable_trade_history(&mut self) {
+        assert_one_yocto();
+        let sender_id = env::predecessor_account_id();
+        let mut trade_histories = read_trade_histories_from_storage();
+        let history = trade_histories.remove(&sender_id).expect("Trade history not enabled");
+        write_trade_histories_to_storage(trade_histories);
+        Promise::new(sender_id).transfer(history.storage_deposit);
+    }
+
+    /// Returns the recorded swaps of the account, oldest first, or None if it didn't opt in.
+    pub fn get_trade_history(&self, account_id: ValidAccountId) -> Option<Vec<TradeRecord>> {
+        read_trade_histories_from_storage()
+            .get(account_id.as_ref())
+            .map(|history| history.ordered_records())
+    }
+}
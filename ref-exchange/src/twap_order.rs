@@ -0,0 +1,256 @@
+use crate::*;
+use crate::utils::{to_nano, u128_dec_format, u128_ratio, u64_dec_format, FEE_DIVISOR};
+use near_sdk::Timestamp;
+
+/// Precision of TWAP order limit prices, token_out units per token_in unit.
+pub const TWAP_PRICE_PRECISION: u128 = 1_000_000_000_000_000_000_000_000;
+pub const MAX_TWAP_TRANCHES: u32 = 100;
+
+/// A large swap split into `tranches` equal swaps through one pool, at least `interval_sec` apart.
+/// Anyone can execute the next tranche once it is due, it fails while the pool is below `min_price`
+/// or more than `max_deviation_bps` below the average price of the tranches swapped so far.
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone)]
+#[serde(crate = "near_sdk::serde")]
+#[cfg_attr(not(target_arch = "wasm32"), derive(Debug))]
+pub struct TwapOrder {
+    pub owner_id: AccountId,
+    pub pool_id: u64,
+    pub token_in: AccountId,
+    pub token_out: AccountId,
+    /// token_in escrowed and not swapped yet.
+    #[serde(with = "u128_dec_format")]
+    pub remaining_in: Balance,
+    /// token_in swapped so far.
+    #[serde(with = "u128_dec_format")]
+    pub swapped_in: Balance,
+    #[serde(with = "u128_dec_format")]
+    pub amount_out: Balance,
+    pub remaining_tranches: u32,
+    pub interval_sec: u32,
+    #[serde(with = "u64_dec_format")]
+    pub next_execution: Timestamp,
+    /// In TWAP_PRICE_PRECISION.
+    #[serde(with = "u128_dec_format")]
+    pub min_price: u128,
+    pub max_deviation_bps: u32,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+#[cfg_attr(not(target_arch = "wasm32"), derive(Debug))]
+pub struct TwapOrderParams {
+    pub pool_id: u64,
+    pub token_in: ValidAccountId,
+    pub token_out: ValidAccountId,
+    pub amount_in: U128,
+    pub tranches: u32,
+    pub interval_sec: u32,
+    /// In TWAP_PRICE_PRECISION, must be set.
+    pub min_price: U128,
+    /// How far below the average price of the previous tranches a tranche may fill, in bps.
+    pub max_deviation_bps: u32,
+}
+
+pub fn read_twap_orders_from_storage() -> LookupMap<u64, TwapOrder> {
+    if let Some(content) = env::storage_read(TWAP_ORDERS.as_bytes()) {
+        LookupMap::try_from_slice(&content).expect("deserialize twap orders failed.")
+    } else {
+        LookupMap::new(StorageKey::TwapOrders)
+    }
+}
+
+pub fn write_twap_orders_to_storage(twap_orders: LookupMap<u64, TwapOrder>) {
+    env::storage_write(
+        TWAP_ORDERS.as_bytes(),
+        &twap_orders.try_to_vec().unwrap(),
+    );
+}
+
+pub fn read_next_twap_order_id_from_storage() -> u64 {
+    if let Some(content) = env::storage_read(NEXT_TWAP_ORDER_ID.as_bytes()) {
+        u64::try_from_slice(&content).expect("deserialize next twap order id failed.")
+    } else {
+        0
+    }
+}
+
+pub fn write_next_twap_order_id_to_storage(next_twap_order_id: u64) {
+    env::storage_write(
+        NEXT_TWAP_ORDER_ID.as_bytes(),
+        &next_twap_order_id.try_to_vec().unwrap(),
+    );
+}
+
+/// token_in escrowed by open orders per token, accounted as off pool reserves.
+pub fn read_twap_escrow_totals_from_storage() -> LookupMap<AccountId, Balance> {
+    if let Some(content) = env::storage_read(TWAP_ESCROW_TOTALS.as_bytes()) {
+        LookupMap::try_from_slice(&content).expect("deserialize twap escrow totals failed.")
+    } else {
+        LookupMap::new(StorageKey::TwapEscrowTotals)
+    }
+}
+
+pub fn write_twap_escrow_totals_to_storage(twap_escrow_totals: LookupMap<AccountId, Balance>) {
+    env::storage_write(
+        TWAP_ESCROW_TOTALS.as_bytes(),
+        &twap_escrow_totals.try_to_vec().unwrap(),
+    );
+}
+
+fn update_twap_escrow_total(token_id: &AccountId, update: impl FnOnce(Balance) -> Balance) {
+    let mut twap_escrow_totals = read_twap_escrow_totals_from_storage();
+    let amount = update(twap_escrow_totals.get(token_id).unwrap_or(0));
+    if amount == 0 {
+        twap_escrow_totals.remove(token_id);
+    } else {
+        twap_escrow_totals.insert(token_id, &amount);
+    }
+    write_twap_escrow_totals_to_storage(twap_escrow_totals);
+}
+
+impl TwapOrder {
+    /// Least amount out of the next tranche swapping `amount_in`.
+    fn min_tranche_amount_out(&self, amount_in: Balance) -> Balance {
+        let min_amount_out = u128_ratio(amount_in, self.min_price, TWAP_PRICE_PRECISION);
+        if self.swapped_in == 0 {
+            return min_amount_out;
+        }
+        let average_amount_out = u128_ratio(amount_in, self.amount_out, self.swapped_in);
+        std::cmp::max(
+            min_amount_out,
+            u128_ratio(average_amount_out, (FEE_DIVISOR - self.max_deviation_bps) as u128, FEE_DIVISOR as u128),
+        )
+    }
+}
+
+impl Contract {
+    /// Credits the storage freed since `prev_storage` back to the order's owner, who paid for it.
+    fn internal_refund_twap_order_storage(&mut self, owner_id: &AccountId, prev_storage: StorageUsage) {
+        if prev_storage > env::storage_usage() {
+            let refund = (prev_storage - env::storage_usage()) as Balance * env::storage_byte_cost();
+            if let Some(mut account) = self.internal_get_account(owner_id) {
+                account.near_amount += refund;
+                self.internal_save_account(owner_id, account);
+            } else {
+                Promise::new(owner_id.clone()).transfer(refund);
+            }
+        }
+    }
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Escrow `amount_in` of the caller's inner balance for a swap in `tranches` parts,
+    /// the first one due right away. The caller must have token_out registered.
+    /// Attached deposit covers the order, the rest is refunded.
+    #[payable]
+    pub fn create_twap_order(&mut self, order: TwapOrderParams) -> u64 {
+        self.assert_contract_running();
+        let TwapOrderParams { pool_id, token_in, token_out, amount_in, tranches, interval_sec, min_price, max_deviation_bps } = order;
+        let prev_storage = env::storage_usage();
+        let owner_id = env::predecessor_account_id();
+        assert_account_not_denied(&owner_id);
+        assert!(tranches > 0 && tranches <= MAX_TWAP_TRANCHES, "Invalid tranches");
+        assert!(interval_sec > 0, "Invalid interval_sec");
+        assert!(min_price.0 > 0, "Invalid min_price");
+        assert!(max_deviation_bps < FEE_DIVISOR, "Invalid max_deviation_bps");
+        assert!(amount_in.0 >= tranches as u128, "{}", ERR31_ZERO_AMOUNT);
+        let token_in: AccountId = token_in.into();
+        let token_out: AccountId = token_out.into();
+        let pool = self.internal_get_pool(pool_id);
+        assert!(
+            pool.tokens().contains(&token_in) && pool.tokens().contains(&token_out) && token_in != token_out,
+            "{}", ERR63_MISSING_TOKEN
+        );
+
+        let mut account = self.internal_unwrap_account(&owner_id);
+        account.get_balance(&token_out).expect(ERR21_TOKEN_NOT_REG);
+        account.withdraw(&token_in, amount_in.0);
+        self.internal_save_account(&owner_id, account);
+        add_locked_deposit(&owner_id, &token_in, amount_in.0);
+        update_twap_escrow_total(&token_in, |total| total + amount_in.0);
+        let order_id = read_next_twap_order_id_from_storage();
+        write_next_twap_order_id_to_storage(order_id + 1);
+        let mut twap_orders = read_twap_orders_from_storage();
+        twap_orders.insert(&order_id, &TwapOrder {
+            owner_id,
+            pool_id,
+            token_in,
+            token_out,
+            remaining_in: amount_in.0,
+            swapped_in: 0,
+            amount_out: 0,
+            remaining_tranches: tranches,
+            interval_sec,
+            next_execution: env::block_timestamp(),
+            min_price: min_price.0,
+            max_deviation_bps,
+        });
+        write_twap_orders_to_storage(twap_orders);
+        self.internal_check_storage(prev_storage);
+        order_id
+    }
+
+    /// Swap the next due tranche of the order into its owner's inner account, returns the amount out.
+    /// The order is dropped after its last tranche and its storage credited back to the owner.
+    pub fn execute_twap_tranche(&mut self, order_id: u64) -> U128 {
+        self.assert_contract_running();
+        let mut twap_orders = read_twap_orders_from_storage();
+        let mut order = twap_orders.get(&order_id).expect("TWAP order not found");
+        assert_account_not_denied(&order.owner_id);
+        self.assert_swap_screen_passed(order.pool_id, &order.owner_id);
+        assert!(env::block_timestamp() >= order.next_execution, "TWAP tranche not due");
+        let amount_in = if order.remaining_tranches == 1 {
+            order.remaining_in
+        } else {
+            order.remaining_in / order.remaining_tranches as u128
+        };
+        let min_amount_out = order.min_tranche_amount_out(amount_in);
+        let amount_out = self.internal_pool_swap(order.pool_id, &order.token_in, amount_in, &order.token_out, min_amount_out, &None);
+
+        let mut account = self.internal_unwrap_account(&order.owner_id);
+        account.deposit(&order.token_out, amount_out);
+        self.internal_save_account(&order.owner_id, account);
+        release_locked_deposit(&order.owner_id, &order.token_in, amount_in);
+        update_twap_escrow_total(&order.token_in, |total| total - amount_in);
+        order.remaining_in -= amount_in;
+        order.swapped_in += amount_in;
+        order.amount_out += amount_out;
+        order.remaining_tranches -= 1;
+        order.next_execution = env::block_timestamp() + to_nano(order.interval_sec);
+        if order.remaining_tranches == 0 {
+            let prev_storage = env::storage_usage();
+            twap_orders.remove(&order_id);
+            write_twap_orders_to_storage(twap_orders);
+            self.internal_refund_twap_order_storage(&order.owner_id, prev_storage);
+        } else {
+            twap_orders.insert(&order_id, &order);
+            write_twap_orders_to_storage(twap_orders);
+        }
+        log!("TWAP order {} swapped {} {} for {} {}", order_id, amount_in, order.token_in, amount_out, order.token_out);
+        amount_out.into()
+    }
+
+    /// Cancel the caller's order, returning the token_in not swapped yet and the order's storage
+    /// to its inner account.
+    #[payable]
+    pub fn cancel_twap_order(&mut self, order_id: u64) -> U128 {
+        assert_one_yocto();
+        let prev_storage = env::storage_usage();
+        let mut twap_orders = read_twap_orders_from_storage();
+        let order = twap_orders.remove(&order_id).expect("TWAP order not found");
+        assert_eq!(env::predecessor_account_id(), order.owner_id, "{}", ERR100_NOT_ALLOWED);
+        write_twap_orders_to_storage(twap_orders);
+        let mut account = self.internal_unwrap_account(&order.owner_id);
+        account.deposit(&order.token_in, order.remaining_in);
+        self.internal_save_account(&order.owner_id, account);
+        release_locked_deposit(&order.owner_id, &order.token_in, order.remaining_in);
+        update_twap_escrow_total(&order.token_in, |total| total - order.remaining_in);
+        self.internal_refund_twap_order_storage(&order.owner_id, prev_storage);
+        order.remaining_in.into()
+    }
+
+    pub fn get_twap_order(&self, order_id: u64) -> Option<TwapOrder> {
+        read_twap_orders_from_storage().get(&order_id)
+    }
+}
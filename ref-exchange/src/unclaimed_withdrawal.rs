@@ -0,0 +1,180 @@
+use crate::*;
+use crate::utils::{to_nano, u128_dec_format, u64_dec_format};
+use near_sdk::json_types::U64;
+use near_sdk::Timestamp;
+
+/// How long a held withdrawal stays with its account before the owner may sweep it.
+pub const UNCLAIMED_WITHDRAWAL_GRACE_SEC: u32 = 180 * 24 * 3600;
+/// Most held withdrawals at a time. Their records are paid by the contract, as the account
+/// is gone or short of storage, so failed withdrawals past this go to lostfound.
+pub const MAX_UNCLAIMED_WITHDRAWALS: u64 = 1000;
+
+/// A failed withdrawal that could not be credited back to the account's inner balance,
+/// because the account was gone or short of storage.
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone)]
+#[serde(crate = "near_sdk::serde")]
+#[cfg_attr(not(target_arch = "wasm32"), derive(Debug))]
+pub struct UnclaimedWithdrawal {
+    #[serde(with = "u128_dec_format")]
+    pub amount: Balance,
+    /// Last time a failed withdrawal was added, the grace period runs from here.
+    #[serde(with = "u64_dec_format")]
+    pub held_at: Timestamp,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+#[cfg_attr(not(target_arch = "wasm32"), derive(Debug))]
+pub struct UnclaimedWithdrawalInfo {
+    pub account_id: AccountId,
+    pub token_id: AccountId,
+    pub amount: U128,
+    /// Timestamp from which the owner may sweep it.
+    pub sweepable_at: U64,
+}
+
+pub fn read_unclaimed_withdrawals_from_storage() -> UnorderedMap<(AccountId, AccountId), UnclaimedWithdrawal> {
+    if let Some(content) = env::storage_read(UNCLAIMED_WITHDRAWALS.as_bytes()) {
+        UnorderedMap::try_from_slice(&content).expect("deserialize unclaimed withdrawals failed.")
+    } else {
+        UnorderedMap::new(StorageKey::UnclaimedWithdrawals)
+    }
+}
+
+pub fn write_unclaimed_withdrawals_to_storage(unclaimed_withdrawals: UnorderedMap<(AccountId, AccountId), UnclaimedWithdrawal>) {
+    env::storage_write(
+        UNCLAIMED_WITHDRAWALS.as_bytes(),
+        &unclaimed_withdrawals.try_to_vec().unwrap(),
+    );
+}
+
+/// Held amounts per token, accounted as off pool reserves.
+pub fn read_unclaimed_withdrawal_totals_from_storage() -> LookupMap<AccountId, Balance> {
+    if let Some(content) = env::storage_read(UNCLAIMED_WITHDRAWAL_TOTALS.as_bytes()) {
+        LookupMap::try_from_slice(&content).expect("deserialize unclaimed withdrawal totals failed.")
+    } else {
+        LookupMap::new(StorageKey::UnclaimedWithdrawalTotals)
+    }
+}
+
+pub fn write_unclaimed_withdrawal_totals_to_storage(unclaimed_withdrawal_totals: LookupMap<AccountId, Balance>) {
+    env::storage_write(
+        UNCLAIMED_WITHDRAWAL_TOTALS.as_bytes(),
+        &unclaimed_withdrawal_totals.try_to_vec().unwrap(),
+    );
+}
+
+fn update_unclaimed_withdrawal_total(token_id: &AccountId, update: impl FnOnce(Balance) -> Balance) {
+    let mut unclaimed_withdrawal_totals = read_unclaimed_withdrawal_totals_from_storage();
+    let amount = update(unclaimed_withdrawal_totals.get(token_id).unwrap_or(0));
+    if amount == 0 {
+        unclaimed_withdrawal_totals.remove(token_id);
+    } else {
+        unclaimed_withdrawal_totals.insert(token_id, &amount);
+    }
+    write_unclaimed_withdrawal_totals_to_storage(unclaimed_withdrawal_totals);
+}
+
+fn take_unclaimed_withdrawal(account_id: &AccountId, token_id: &AccountId) -> UnclaimedWithdrawal {
+    let mut unclaimed_withdrawals = read_unclaimed_withdrawals_from_storage();
+    let unclaimed_withdrawal = unclaimed_withdrawals
+        .remove(&(account_id.clone(), token_id.clone()))
+        .expect("No unclaimed withdrawal");
+    write_unclaimed_withdrawals_to_storage(unclaimed_withdrawals);
+    update_unclaimed_withdrawal_total(token_id, |amount| amount - unclaimed_withdrawal.amount);
+    release_locked_deposit(account_id, token_id, unclaimed_withdrawal.amount);
+    unclaimed_withdrawal
+}
+
+impl Contract {
+    /// Keeps a failed withdrawal for the account to reclaim, adding to a held one of the same token if any.
+    /// Once MAX_UNCLAIMED_WITHDRAWALS are held, a new one goes to lostfound instead.
+    pub(crate) fn internal_hold_unclaimed_withdrawal(&mut self, account_id: &AccountId, token_id: &AccountId, amount: Balance) {
+        let key = (account_id.clone(), token_id.clone());
+        let mut unclaimed_withdrawals = read_unclaimed_withdrawals_from_storage();
+        let held = match unclaimed_withdrawals.get(&key) {
+            Some(unclaimed_withdrawal) => unclaimed_withdrawal.amount,
+            None if unclaimed_withdrawals.len() >= MAX_UNCLAIMED_WITHDRAWALS => {
+                log!("Too many unclaimed withdrawals, {} {} of {} goes to lostfound", amount, token_id, account_id);
+                self.internal_lostfound(token_id, amount);
+                return;
+            }
+            None => 0,
+        };
+        unclaimed_withdrawals.insert(&key, &UnclaimedWithdrawal {
+            amount: held + amount,
+            held_at: env::block_timestamp(),
+        });
+        write_unclaimed_withdrawals_to_storage(unclaimed_withdrawals);
+        update_unclaimed_withdrawal_total(token_id, |total| total + amount);
+        add_locked_deposit(account_id, token_id, amount);
+        event::Event::UnclaimedWithdrawalHeld { account_id, token_id, amount: U128(amount) }.emit();
+    }
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Move the caller's held failed withdrawal of the token into its inner account.
+    /// The caller needs storage for the token, e.g. by registering it first.
+    #[payable]
+    pub fn claim_unclaimed_withdrawal(&mut self, token_id: ValidAccountId) -> U128 {
+        assert_one_yocto();
+        let account_id = env::predecessor_account_id();
+        let token_id: AccountId = token_id.into();
+        let mut account = self.internal_unwrap_account(&account_id);
+        let unclaimed_withdrawal = take_unclaimed_withdrawal(&account_id, &token_id);
+        account.deposit(&token_id, unclaimed_withdrawal.amount);
+        self.internal_save_account(&account_id, account);
+        event::Event::UnclaimedWithdrawalClaimed {
+            account_id: &account_id,
+            token_id: &token_id,
+            amount: U128(unclaimed_withdrawal.amount),
+        }.emit();
+        U128(unclaimed_withdrawal.amount)
+    }
+
+    /// Move held failed withdrawals left unclaimed for the whole grace period into the owner's account.
+    #[payable]
+    pub fn sweep_unclaimed_withdrawals(&mut self, withdrawals: Vec<(ValidAccountId, ValidAccountId)>) {
+        assert_one_yocto();
+        self.assert_owner();
+        audit_privileged_action("sweep_unclaimed_withdrawals");
+        for (account_id, token_id) in withdrawals {
+            let (account_id, token_id): (AccountId, AccountId) = (account_id.into(), token_id.into());
+            let unclaimed_withdrawal = take_unclaimed_withdrawal(&account_id, &token_id);
+            assert!(
+                env::block_timestamp() >= unclaimed_withdrawal.held_at + to_nano(UNCLAIMED_WITHDRAWAL_GRACE_SEC),
+                "Unclaimed withdrawal of {} {} still in grace period", account_id, token_id
+            );
+            self.internal_lostfound(&token_id, unclaimed_withdrawal.amount);
+            event::Event::UnclaimedWithdrawalSwept {
+                account_id: &account_id,
+                token_id: &token_id,
+                amount: U128(unclaimed_withdrawal.amount),
+            }.emit();
+        }
+    }
+
+    pub fn get_unclaimed_withdrawal(&self, account_id: ValidAccountId, token_id: ValidAccountId) -> Option<UnclaimedWithdrawal> {
+        read_unclaimed_withdrawals_from_storage().get(&(account_id.into(), token_id.into()))
+    }
+
+    pub fn get_unclaimed_withdrawals(&self, from_index: Option<u64>, limit: Option<u64>) -> Vec<UnclaimedWithdrawalInfo> {
+        let unclaimed_withdrawals = read_unclaimed_withdrawals_from_storage();
+        let keys = unclaimed_withdrawals.keys_as_vector();
+        let from_index = from_index.unwrap_or(0);
+        let limit = limit.unwrap_or(keys.len());
+        (from_index..std::cmp::min(keys.len(), from_index + limit))
+            .map(|index| {
+                let (account_id, token_id) = keys.get(index).unwrap();
+                let unclaimed_withdrawal = unclaimed_withdrawals.get(&(account_id.clone(), token_id.clone())).unwrap();
+                UnclaimedWithdrawalInfo {
+                    account_id,
+                    token_id,
+                    amount: U128(unclaimed_withdrawal.amount),
+                    sweepable_at: U64(unclaimed_withdrawal.held_at + to_nano(UNCLAIMED_WITHDRAWAL_GRACE_SEC)),
+                }
+            })
+            .collect()
+    }
+}
@@ -5,6 +5,8 @@ use crate::utils::*;
 use uint::construct_uint;
 
 pub const RECORD_COUNT_LIMIT: usize = 60;
+/// Epochs a pool's volume stats are kept by default, see `prune_pool_volume_stats`.
+pub const DEFAULT_VOLUME_STATS_RETENTION_EPOCHS: u32 = 60;
 
 construct_uint! {
     #[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize)]
@@ -121,6 +123,13 @@ impl UnitShareCumulativeInfo {
         }
     }
 
+    /// Drops the oldest records so at most `record_limit` remain, returns the number removed.
+    pub fn prune(&mut self, record_limit: usize) -> usize {
+        let removed = self.records.len().saturating_sub(record_limit);
+        self.records.drain(..removed);
+        removed
+    }
+
     pub fn twap_token_amounts(&self) -> Vec<u128> {
         let earliest_record = &self.records[0];
         let numerators = self.cumulative_token_amounts.iter().zip(earliest_record.cumulative_token_amounts.iter()).map(|(x, y)| {
@@ -133,9 +142,49 @@ impl UnitShareCumulativeInfo {
     }
 }
 
+pub fn read_twap_record_limit_from_storage() -> UnorderedMap<u64, u32> {
+    if let Some(content) = env::storage_read(TWAP_RECORD_LIMIT.as_bytes()) {
+        UnorderedMap::try_from_slice(&content).expect("deserialize twap record limit failed.")
+    } else {
+        UnorderedMap::new(StorageKey::TwapRecordLimit)
+    }
+}
+
+pub fn write_twap_record_limit_to_storage(twap_record_limit: UnorderedMap<u64, u32>) {
+    env::storage_write(
+        TWAP_RECORD_LIMIT.as_bytes(), 
+        &twap_record_limit.try_to_vec().unwrap(),
+    );
+}
+
+pub fn read_volume_stats_retention_from_storage() -> UnorderedMap<u64, u32> {
+    if let Some(content) = env::storage_read(VOLUME_STATS_RETENTION.as_bytes()) {
+        UnorderedMap::try_from_slice(&content).expect("deserialize volume stats retention failed.")
+    } else {
+        UnorderedMap::new(StorageKey::VolumeStatsRetention)
+    }
+}
+
+pub fn write_volume_stats_retention_to_storage(volume_stats_retention: UnorderedMap<u64, u32>) {
+    env::storage_write(
+        VOLUME_STATS_RETENTION.as_bytes(),
+        &volume_stats_retention.try_to_vec().unwrap(),
+    );
+}
+
 impl Contract {
+    /// Number of epochs the pool's volume stats are kept, defaults to DEFAULT_VOLUME_STATS_RETENTION_EPOCHS.
+    pub fn internal_volume_stats_retention(&self, pool_id: u64) -> u64 {
+        read_volume_stats_retention_from_storage().get(&pool_id).unwrap_or(DEFAULT_VOLUME_STATS_RETENTION_EPOCHS) as u64
+    }
+
+    /// Number of records kept for the given pool, defaults to RECORD_COUNT_LIMIT.
+    pub fn internal_twap_record_limit(&self, pool_id: u64) -> usize {
+        read_twap_record_limit_from_storage().get(&pool_id).map(|v| v as usize).unwrap_or(RECORD_COUNT_LIMIT)
+    }
+
     pub fn internal_unit_share_token_amounts(&self, pool_id: u64) -> Option<Vec<u128>> {
-        let mut pool = self.pools.get(pool_id).expect(ERR85_NO_POOL);
+        let mut pool = self.internal_get_pool(pool_id);
         let share_decimals = pool.get_share_decimal();
         if pool.share_total_balance() > 10u128.pow(share_decimals as u32) {
             Some(pool.remove_liquidity(&String::from("@view"), 10u128.pow(share_decimals as u32), vec![0; pool.tokens().len()], true))
@@ -148,6 +197,7 @@ impl Contract {
         if let Some(mut unit_share_cumulative_info) =  self.internal_get_unit_share_cumulative_infos(pool_id) {
             if let Some(tokens) = self.internal_unit_share_token_amounts(pool_id) {
                 unit_share_cumulative_info.update(nano_to_sec(env::block_timestamp()), tokens, self.cumulative_info_record_interval_sec);
+                unit_share_cumulative_info.prune(self.internal_twap_record_limit(pool_id));
                 self.internal_set_unit_share_cumulative_infos(pool_id, unit_share_cumulative_info);
             }
         }
@@ -160,6 +210,7 @@ impl Contract {
     pub fn register_pool_twap_record(&mut self, pool_id: u64) {
         assert_one_yocto();
         assert!(self.is_owner_or_guardians(), "{}", ERR100_NOT_ALLOWED);
+        audit_privileged_action("register_pool_twap_record");
         assert!(self.unit_share_cumulative_infos.get(&pool_id).is_none(), "Already register");
         let amounts = self.internal_unit_share_token_amounts(pool_id).expect("Too few shares in the pool");
         self.internal_set_unit_share_cumulative_infos(pool_id, UnitShareCumulativeInfo::new(nano_to_sec(env::block_timestamp()), amounts));
@@ -169,6 +220,7 @@ impl Contract {
     pub fn unregister_pool_twap_record(&mut self, pool_id: u64) {
         assert_one_yocto();
         assert!(self.is_owner_or_guardians(), "{}", ERR100_NOT_ALLOWED);
+        audit_privileged_action("unregister_pool_twap_record");
         self.unit_share_cumulative_infos.remove(&pool_id).expect(ERR85_NO_POOL);
     }
 
@@ -176,6 +228,7 @@ impl Contract {
     pub fn modify_cumulative_info_record_interval_sec(&mut self, record_interval_sec: u32) {
         assert_one_yocto();
         assert!(self.is_owner_or_guardians(), "{}", ERR100_NOT_ALLOWED);
+        audit_privileged_action("modify_cumulative_info_record_interval_sec");
         self.cumulative_info_record_interval_sec = record_interval_sec;
     }
 
@@ -183,9 +236,78 @@ impl Contract {
         let mut unit_share_cumulative_info =  self.internal_unwrap_unit_share_cumulative_infos(pool_id);
         let amounts = self.internal_unit_share_token_amounts(pool_id).expect("Too few shares in the pool");
         unit_share_cumulative_info.update(nano_to_sec(env::block_timestamp()), amounts, self.cumulative_info_record_interval_sec);
+        unit_share_cumulative_info.prune(self.internal_twap_record_limit(pool_id));
         self.internal_set_unit_share_cumulative_infos(pool_id, unit_share_cumulative_info);
     }
 
+    /// Set how many twap records are kept for the given pool, None restores RECORD_COUNT_LIMIT.
+    /// Existing records beyond the new limit are dropped on the next update or prune.
+    #[payable]
+    pub fn set_pool_twap_record_limit(&mut self, pool_id: u64, record_limit: Option<u32>) {
+        assert_one_yocto();
+        self.assert_owner();
+        audit_privileged_action("set_pool_twap_record_limit");
+        let mut twap_record_limit = read_twap_record_limit_from_storage();
+        if let Some(record_limit) = record_limit {
+            assert!(record_limit >= 2 && record_limit as usize <= RECORD_COUNT_LIMIT, "Invalid record_limit");
+            twap_record_limit.insert(&pool_id, &record_limit);
+        } else {
+            twap_record_limit.remove(&pool_id);
+        }
+        write_twap_record_limit_to_storage(twap_record_limit);
+    }
+
+    /// Drops twap records of the given pool beyond its retention limit.
+    pub fn prune_pool_twap_records(&mut self, pool_id: u64) -> u32 {
+        let mut unit_share_cumulative_info =  self.internal_unwrap_unit_share_cumulative_infos(pool_id);
+        let removed = unit_share_cumulative_info.prune(self.internal_twap_record_limit(pool_id));
+        if removed > 0 {
+            self.internal_set_unit_share_cumulative_infos(pool_id, unit_share_cumulative_info);
+        }
+        removed as u32
+    }
+
+    /// Set how many epochs the volume stats of the given pool are kept,
+    /// None restores DEFAULT_VOLUME_STATS_RETENTION_EPOCHS.
+    #[payable]
+    pub fn set_pool_volume_stats_retention(&mut self, pool_id: u64, retention_epochs: Option<u32>) {
+        assert_one_yocto();
+        self.assert_owner();
+        audit_privileged_action("set_pool_volume_stats_retention");
+        let mut volume_stats_retention = read_volume_stats_retention_from_storage();
+        if let Some(retention_epochs) = retention_epochs {
+            assert!(retention_epochs > 0, "Invalid retention_epochs");
+            volume_stats_retention.insert(&pool_id, &retention_epochs);
+        } else {
+            volume_stats_retention.remove(&pool_id);
+        }
+        write_volume_stats_retention_to_storage(volume_stats_retention);
+    }
+
+    /// Drops the pool's entries in the epoch fee reports of the given epochs once they are
+    /// beyond its retention, and its recent volume window once no swap falls in it.
+    /// Returns the number of records removed.
+    pub fn prune_pool_volume_stats(&mut self, pool_id: u64, epochs: Vec<u64>) -> u32 {
+        let retention = self.internal_volume_stats_retention(pool_id);
+        let current_epoch = env::epoch_height();
+        let mut removed = epochs.into_iter()
+            .filter(|epoch| epoch + retention < current_epoch)
+            .filter(|epoch| remove_epoch_pool_fees(*epoch, pool_id))
+            .count() as u32;
+        if prune_pool_volume_window(pool_id) {
+            removed += 1;
+        }
+        removed
+    }
+
+    pub fn get_pool_volume_stats_retention(&self, pool_id: u64) -> u32 {
+        self.internal_volume_stats_retention(pool_id) as u32
+    }
+
+    pub fn get_pool_twap_record_limit(&self, pool_id: u64) -> u32 {
+        self.internal_twap_record_limit(pool_id) as u32
+    }
+
     pub fn get_pool_twap_info_view(&self, pool_id: u64) -> Option<UnitShareCumulativeInfoView> {
         if let Some(v) = self.internal_get_unit_share_cumulative_infos(pool_id) {
             Some(v.into())
@@ -213,7 +335,8 @@ impl Contract {
         let mut unit_share_cumulative_info =  self.internal_unwrap_unit_share_cumulative_infos(pool_id);
         if let Some(tokens) = self.internal_unit_share_token_amounts(pool_id) {
             unit_share_cumulative_info.update(nano_to_sec(env::block_timestamp()), tokens, self.cumulative_info_record_interval_sec);
-            if unit_share_cumulative_info.records.len() == RECORD_COUNT_LIMIT {
+            unit_share_cumulative_info.prune(self.internal_twap_record_limit(pool_id));
+            if unit_share_cumulative_info.records.len() == self.internal_twap_record_limit(pool_id) {
                 Some(unit_share_cumulative_info.twap_token_amounts().into_iter().map(|v| U128(v)).collect())
             } else {
                 None
@@ -296,4 +419,18 @@ mod twap {
             assert!(usci.twap_token_amounts().into_iter().zip(vec![100u128, 100, 100, 10000]).all(|(x, y)| x == y));
         }
     }
+
+    #[test]
+    fn test_prune() {
+        let mut usci = UnitShareCumulativeInfo::new(1000, vec![100u128, 100]);
+        for i in 1..10u32 {
+            usci.update(1000 + i * TEST_RECORD_INTERVAL_SEC, vec![100u128, 100], TEST_RECORD_INTERVAL_SEC);
+        }
+        assert_eq!(usci.records.len(), 10);
+        assert_eq!(usci.prune(20), 0);
+        assert_eq!(usci.prune(4), 6);
+        assert_eq!(usci.records.len(), 4);
+        assert_eq!(usci.records[0].time_sec, 1000 + 6 * TEST_RECORD_INTERVAL_SEC);
+        assert!(usci.twap_token_amounts().into_iter().all(|x| x == 100));
+    }
 }
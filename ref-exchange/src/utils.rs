@@ -6,8 +6,9 @@ use near_sdk::collections::LookupMap;
 use near_sdk::json_types::{ValidAccountId, U128};
 use near_sdk::serde::{Deserialize, Serialize};
 use near_sdk::{ext_contract, AccountId, Balance, Gas, Timestamp};
-use uint::construct_uint;
+pub use ref_exchange_math::{u128_ratio, FEE_DIVISOR, U256};
 use crate::errors::*;
+use crate::storage_inspector::update_collection_len;
 
 /// Attach no deposit.
 pub const NO_DEPOSIT: u128 = 0;
@@ -23,23 +24,13 @@ pub const GAS_FOR_FT_TRANSFER_CALL: Gas = 25_000_000_000_000 + GAS_FOR_RESOLVE_T
 /// Amount of gas for fungible token transfers, increased to 20T to support AS token contracts.
 pub const GAS_FOR_FT_TRANSFER: Gas = 20_000_000_000_000;
 pub const GAS_FOR_NEAR_WITHDRAW: Gas = 20_000_000_000_000;
+pub const GAS_FOR_NEAR_DEPOSIT: Gas = 10_000_000_000_000;
 
-/// Fee divisor, allowing to provide fee in bps.
-pub const FEE_DIVISOR: u32 = 10_000;
 pub const MAX_ADMIN_FEE_BPS: u32 = 8_000;
 
 /// Initial shares supply on deposit of liquidity.
 pub const INIT_SHARES_SUPPLY: u128 = 1_000_000_000_000_000_000_000_000;
 
-construct_uint! {
-    /// 256-bit unsigned integer.
-    pub struct U256(4);
-}
-
-construct_uint! {
-    /// 384-bit unsigned integer.
-    pub struct U384(6);
-}
 
 /// Volume of swap on the given token.
 #[derive(Clone, BorshSerialize, BorshDeserialize, Serialize, Deserialize)]
@@ -61,6 +52,7 @@ impl Default for SwapVolume {
 #[ext_contract(ext_wrap_near)]
 pub trait WrapNear {
     fn near_withdraw(&mut self, amount: U128);
+    fn near_deposit(&mut self);
 }
 
 #[ext_contract(ext_self)]
@@ -69,12 +61,17 @@ pub trait RefExchange {
         &mut self,
         sender_id: AccountId,
         amount: U128,
+        token_id: Option<AccountId>,
+        fee: Option<U128>,
+        receiver_id: Option<AccountId>,
     ) -> U128 ;
     fn exchange_callback_post_withdraw(
         &mut self,
         token_id: AccountId,
         sender_id: AccountId,
         amount: U128,
+        fee: Option<U128>,
+        receiver_id: Option<AccountId>,
     );
     fn callback_on_shadow(
         &mut self,
@@ -90,12 +87,40 @@ pub trait RefExchange {
         pool_id: u64,
         amount: U128,
     );
+    fn exchange_callback_post_safe_mode_claim(
+        &mut self,
+        token_id: AccountId,
+        account_id: AccountId,
+        amount: U128,
+        debited: U128,
+    );
+    fn exchange_callback_post_unwrap_storage_top_up_wnear(
+        &mut self,
+        token_id: AccountId,
+        amount: U128,
+    );
+    fn exchange_callback_post_near_deposit(
+        &mut self,
+        sender_id: AccountId,
+        amount: U128,
+        actions: Vec<crate::action::Action>,
+        referral_id: Option<AccountId>,
+    );
+    fn exchange_callback_near_deposit_actions(
+        &mut self,
+        sender_id: AccountId,
+        actions: Vec<crate::action::Action>,
+        referral_id: Option<AccountId>,
+    );
 }
 
 /// Adds given value to item stored in the given key in the LookupMap collection.
 pub fn add_to_collection(c: &mut LookupMap<AccountId, Balance>, key: &String, value: Balance) {
-    let prev_value = c.get(key).unwrap_or(0);
-    c.insert(key, &(prev_value + value));
+    let prev_value = c.get(key);
+    if prev_value.is_none() {
+        update_collection_len(c, true);
+    }
+    c.insert(key, &(prev_value.unwrap_or(0) + value));
 }
 
 /// Checks if there are any duplicates in the given list of tokens.
@@ -115,10 +140,6 @@ pub fn integer_sqrt(value: U256) -> U256 {
     res
 }
 
-pub fn u128_ratio(a: u128, num: u128, denom: u128) -> u128 {
-    (U256::from(a) * U256::from(num) / U256::from(denom)).as_u128()
-}
-
 pub struct TokenCache(pub HashMap<AccountId, u128>);
 
 impl TokenCache {
@@ -118,6 +118,14 @@ impl From<Pool> for PoolInfo {
                 total_fee: pool.total_fee,
                 shares_total_supply: U128(pool.shares_total_supply),
             },
+            Pool::RangePool(RangePool { pool, .. }) => Self {
+                pool_kind,
+                amp: 0,
+                token_account_ids: pool.token_account_ids,
+                amounts: pool.amounts.into_iter().map(|a| U128(a)).collect(),
+                total_fee: pool.total_fee,
+                shares_total_supply: U128(pool.shares_total_supply),
+            },
         }
     }
 }
@@ -130,6 +138,7 @@ pub enum PoolDetailInfo {
     StablePoolInfo(StablePoolInfo),
     RatedPoolInfo(RatedPoolInfo),
     DegenPoolInfo(DegenPoolInfo),
+    RangePoolInfo(RangePoolInfo),
 }
 
 impl From<SimplePoolInfo> for PoolDetailInfo {
@@ -156,6 +165,12 @@ impl From<DegenPoolInfo> for PoolDetailInfo {
     }
 }
 
+impl From<RangePoolInfo> for PoolDetailInfo {
+    fn from(pool: RangePoolInfo) -> Self {
+        PoolDetailInfo::RangePoolInfo(pool)
+    }
+}
+
 #[derive(Serialize, Deserialize)]
 #[serde(crate = "near_sdk::serde")]
 #[cfg_attr(not(target_arch = "wasm32"), derive(Debug, PartialEq))]
@@ -182,6 +197,7 @@ impl From<Pool> for SimplePoolInfo {
             Pool::StableSwapPool(_) => unimplemented!(),
             Pool::RatedSwapPool(_) => unimplemented!(),
             Pool::DegenSwapPool(_) => unimplemented!(),
+            Pool::RangePool(_) => unimplemented!(),
         }
     }
 }
@@ -219,6 +235,7 @@ impl From<Pool> for StablePoolInfo {
             },
             Pool::RatedSwapPool(_) => unimplemented!(),
             Pool::DegenSwapPool(_) => unimplemented!(),
+            Pool::RangePool(_) => unimplemented!(),
         }
     }
 }
@@ -259,6 +276,7 @@ impl From<Pool> for RatedPoolInfo {
                 
             },
             Pool::DegenSwapPool(_) => unimplemented!(),
+            Pool::RangePool(_) => unimplemented!(),
         }
     }
 }
@@ -299,6 +317,46 @@ impl From<Pool> for DegenPoolInfo {
                 shares_total_supply: U128(pool.shares_total_supply),
                 
             },
+            Pool::RangePool(_) => unimplemented!(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+#[cfg_attr(not(target_arch = "wasm32"), derive(Debug, PartialEq))]
+pub struct RangePoolInfo {
+    /// List of tokens in the pool.
+    pub token_account_ids: Vec<AccountId>,
+    /// Real reserves of the tokens.
+    pub amounts: Vec<U128>,
+    /// Fee charged for swap.
+    pub total_fee: u32,
+    /// Total number of shares.
+    pub shares_total_supply: U128,
+    /// Price bounds of token1 per token0 in raw amounts, in precision 1e24.
+    pub price_lower: U128,
+    pub price_upper: U128,
+    /// Current price, in precision 1e24.
+    pub price: U128,
+    /// Liquidity concentrated in the range.
+    pub liquidity: U128,
+}
+
+impl From<Pool> for RangePoolInfo {
+    fn from(pool: Pool) -> Self {
+        match pool {
+            Pool::RangePool(pool) => Self {
+                price: U128(pool.get_price()),
+                liquidity: U128(pool.get_liquidity()),
+                price_lower: U128(pool.price_lower),
+                price_upper: U128(pool.price_upper),
+                token_account_ids: pool.pool.token_account_ids,
+                amounts: pool.pool.amounts.into_iter().map(|a| U128(a)).collect(),
+                total_fee: pool.pool.total_fee,
+                shares_total_supply: U128(pool.pool.shares_total_supply),
+            },
+            _ => unimplemented!(),
         }
     }
 }
@@ -380,7 +438,7 @@ impl Contract {
 
     /// Returns information about specified pool.
     pub fn get_pool(&self, pool_id: u64) -> PoolInfo {
-        self.pools.get(pool_id).expect(ERR85_NO_POOL).into()
+        self.internal_get_pool(pool_id).into()
     }
 
     /// Returns list of pools of given pool ids.
@@ -399,12 +457,13 @@ impl Contract {
 
     /// Returns pool detail info about specified pool.
     pub fn get_pool_detail_info(&self, pool_id: u64) -> PoolDetailInfo {
-        let pool = self.pools.get(pool_id).expect(ERR85_NO_POOL);
+        let pool = self.internal_get_pool(pool_id);
         match &pool {
             Pool::SimplePool(_) => <Pool as Into<SimplePoolInfo>>::into(pool).into(),
             Pool::StableSwapPool(_) => <Pool as Into<StablePoolInfo>>::into(pool).into(),
             Pool::RatedSwapPool(_) => <Pool as Into<RatedPoolInfo>>::into(pool).into(),
             Pool::DegenSwapPool(_) => <Pool as Into<DegenPoolInfo>>::into(pool).into(),
+            Pool::RangePool(_) => <Pool as Into<RangePoolInfo>>::into(pool).into(),
         }
     }
 
@@ -417,43 +476,50 @@ impl Contract {
 
     /// Returns stable pool information about specified pool.
     pub fn get_stable_pool(&self, pool_id: u64) -> StablePoolInfo {
-        self.pools.get(pool_id).expect(ERR85_NO_POOL).into()
+        self.internal_get_pool(pool_id).into()
     }
 
     /// Returns rated pool information about specified pool.
     pub fn get_rated_pool(&self, pool_id: u64) -> RatedPoolInfo {
-        self.pools.get(pool_id).expect(ERR85_NO_POOL).into()
+        self.internal_get_pool(pool_id).into()
     }
 
     /// Returns degen pool information about specified pool.
     pub fn get_degen_pool(&self, pool_id: u64) -> DegenPoolInfo {
-        self.pools.get(pool_id).expect(ERR85_NO_POOL).into()
+        self.internal_get_pool(pool_id).into()
     }
 
     /// Return total fee of the given pool.
     pub fn get_pool_fee(&self, pool_id: u64) -> u32 {
-        self.pools.get(pool_id).expect(ERR85_NO_POOL).get_fee()
+        self.internal_get_pool(pool_id).get_fee()
     }
 
     /// Return volumes of the given pool.
     pub fn get_pool_volumes(&self, pool_id: u64) -> Vec<SwapVolume> {
-        self.pools.get(pool_id).expect(ERR85_NO_POOL).get_volumes()
+        self.internal_get_pool(pool_id).get_volumes()
     }
 
     pub fn get_pool_volumes_by_ids(&self, pool_ids: Vec<u64>) -> Vec<Vec<SwapVolume>> {
         pool_ids.iter()
-            .map(|index| self.pools.get(*index).expect(ERR85_NO_POOL).get_volumes())
+            .map(|index| self.internal_get_pool(*index).get_volumes())
             .collect()
     }
 
     pub fn list_pool_volumes(&self, from_index: u64, limit: u64) -> Vec<Vec<SwapVolume>> {
         (from_index..std::cmp::min(from_index + limit, self.pools.len()))
-            .map(|index| self.pools.get(index).expect(ERR85_NO_POOL).get_volumes())
+            .map(|index| self.internal_get_pool(index).get_volumes())
             .collect()
     }
 
     pub fn get_pool_share_price(&self, pool_id: u64) -> U128 {
-        self.pools.get(pool_id).expect(ERR85_NO_POOL).get_share_price().into()
+        self.internal_get_pool(pool_id).get_share_price().into()
+    }
+
+    /// Returns share prices of given pool ids, in the given order.
+    pub fn get_share_prices(&self, pool_ids: Vec<u64>) -> Vec<U128> {
+        pool_ids.iter()
+            .map(|index| self.get_pool_share_price(*index))
+            .collect()
     }
 
     /// Returns number of shares given account has in given pool.
@@ -542,8 +608,11 @@ impl Contract {
         amount_in: U128,
         token_out: ValidAccountId,
     ) -> U128 {
-        let mut pool = self.pools.get(pool_id).expect(ERR85_NO_POOL);
-        pool.swap(token_in.as_ref(), amount_in.into(), token_out.as_ref(), 0, AdminFees::new(self.admin_fee_bps), true).into()
+        let mut pool = self.internal_get_swap_pool(pool_id);
+        let max_amount_out = self.internal_max_swap_out(pool_id, &pool, token_out.as_ref());
+        let amount_out = self.internal_quote_with_maker_rebate(pool_id, &mut pool, token_in.as_ref(), amount_in.into(), token_out.as_ref(), AdminFees::new(self.admin_fee_bps));
+        assert_within_swap_cap(max_amount_out, amount_out);
+        amount_out.into()
     }
 
     /// Given a specific pool, returns the amount of token_in required to receive amount_out of token_out.
@@ -554,7 +623,8 @@ impl Contract {
         amount_out: U128,
         token_out: ValidAccountId,
     ) -> U128 {
-        let mut pool = self.pools.get(pool_id).expect(ERR85_NO_POOL);
+        let mut pool = self.internal_get_swap_pool(pool_id);
+        assert_within_swap_cap(self.internal_max_swap_out(pool_id, &pool, token_out.as_ref()), amount_out.into());
         pool.swap_by_output(token_in.as_ref(), amount_out.into(), token_out.as_ref(), None, AdminFees::new(self.admin_fee_bps), true).into()
     }
 
@@ -612,7 +682,7 @@ impl Contract {
         pool_id: u64,
         amounts: &Vec<U128>,
     ) -> AddLiquidityPrediction {
-        let mut pool = self.pools.get(pool_id).expect(ERR85_NO_POOL);
+        let mut pool = self.internal_get_pool(pool_id);
         let mut amounts = amounts.iter().map(|v| v.0).collect();
         let mint_shares = pool.add_liquidity(&String::from("@view"), &mut amounts, true);
         AddLiquidityPrediction {
@@ -626,7 +696,7 @@ impl Contract {
         pool_id: u64,
         amounts: &Vec<U128>,
     ) -> U128 {
-        let mut pool = self.pools.get(pool_id).expect(ERR85_NO_POOL);
+        let mut pool = self.internal_get_pool(pool_id);
         let amounts = amounts.iter().map(|v| v.0).collect();
         pool.add_stable_liquidity(&String::from("@view"), &amounts, 0, AdminFees::new(self.admin_fee_bps), true).into()
     }
@@ -636,7 +706,7 @@ impl Contract {
         pool_id: u64,
         shares: U128,
     ) -> Vec<U128> {
-        let mut pool = self.pools.get(pool_id).expect(ERR85_NO_POOL);
+        let mut pool = self.internal_get_pool(pool_id);
         pool.remove_liquidity(&String::from("@view"), shares.into(), vec![0; pool.tokens().len()], true).into_iter().map(|x| U128(x)).collect()
     }
 
@@ -645,7 +715,7 @@ impl Contract {
         pool_id: u64,
         amounts: &Vec<U128>,
     ) -> U128 {
-        let mut pool = self.pools.get(pool_id).expect(ERR85_NO_POOL);
+        let mut pool = self.internal_get_pool(pool_id);
         let amounts = amounts.iter().map(|v| v.0).collect();
         pool.remove_liquidity_by_tokens(&String::from("@view"), amounts, u128::MAX, AdminFees::new(self.admin_fee_bps), true).into()
     }
@@ -666,7 +736,7 @@ impl Contract {
                         rate_type: v.get_type(),
                         rate_price: v.get().into(),
                         last_update_ts: v.last_update_ts().into(),
-                        is_valid: v.are_actual(),
+                        is_valid: v.are_actual() && is_rate_fresh(k, v.last_update_ts()),
                         extra_info: Some(near_sdk::serde_json::to_string(&r.extra_info).unwrap())
                     }),
                 _ => (k.clone(), 
@@ -674,7 +744,7 @@ impl Contract {
                         rate_type: v.get_type(),
                         rate_price: v.get().into(),
                         last_update_ts: v.last_update_ts().into(),
-                        is_valid: v.are_actual(),
+                        is_valid: v.are_actual() && is_rate_fresh(k, v.last_update_ts()),
                         extra_info: None
                     })
             }
@@ -722,7 +792,7 @@ impl Contract {
         amounts: &Vec<U128>,
         rates: &Option<Vec<U128>>,
     ) -> U128 {
-        let pool = self.pools.get(pool_id).expect(ERR85_NO_POOL);
+        let pool = self.internal_get_pool(pool_id);
         let rates = match rates {
             Some(rates) => Some(rates.into_iter().map(|x| x.0).collect()),
             _ => None
@@ -741,7 +811,7 @@ impl Contract {
         amounts: &Vec<U128>,
         degens: &Option<Vec<U128>>,
     ) -> U128 {
-        let pool = self.pools.get(pool_id).expect(ERR85_NO_POOL);
+        let pool = self.internal_get_pool(pool_id);
         let degens = match degens {
             Some(degens) => Some(degens.into_iter().map(|x| x.0).collect()),
             _ => None
@@ -760,7 +830,7 @@ impl Contract {
         amounts: &Vec<U128>,
         rates: &Option<Vec<U128>>,
     ) -> U128 {
-        let pool = self.pools.get(pool_id).expect(ERR85_NO_POOL);
+        let pool = self.internal_get_pool(pool_id);
         let rates = match rates {
             Some(rates) => Some(rates.into_iter().map(|x| x.0).collect()),
             _ => None
@@ -776,7 +846,7 @@ impl Contract {
         amounts: &Vec<U128>,
         degens: &Option<Vec<U128>>,
     ) -> U128 {
-        let pool = self.pools.get(pool_id).expect(ERR85_NO_POOL);
+        let pool = self.internal_get_pool(pool_id);
         let degens = match degens {
             Some(degens) => Some(degens.into_iter().map(|x| x.0).collect()),
             _ => None
@@ -794,7 +864,7 @@ impl Contract {
         token_out: ValidAccountId,
         rates: &Option<Vec<U128>>,
     ) -> U128 {
-        let pool = self.pools.get(pool_id).expect(ERR85_NO_POOL);
+        let pool = self.internal_get_pool(pool_id);
         let rates = match rates {
             Some(rates) => Some(rates.into_iter().map(|x| x.0).collect()),
             _ => None
@@ -812,7 +882,7 @@ impl Contract {
         token_out: ValidAccountId,
         degens: &Option<Vec<U128>>,
     ) -> U128 {
-        let pool = self.pools.get(pool_id).expect(ERR85_NO_POOL);
+        let pool = self.internal_get_pool(pool_id);
         let degens = match degens {
             Some(degens) => Some(degens.into_iter().map(|x| x.0).collect()),
             _ => None
@@ -839,6 +909,60 @@ impl Contract {
         token_cache.0.into_iter().map(|(k, v)| (k, v.into())).collect()
     }
 
+    /// Same as `predict_swap_actions` with the reserves of the given pools replaced, so routers
+    /// can chain predictions on pool states that are not on chain yet.
+    pub fn predict_swap_actions_with_reserves(
+        &self,
+        token_deposit: HashMap<AccountId, U128>,
+        actions: Vec<Action>,
+        reserves: HashMap<u64, Vec<U128>>,
+    ) -> HashMap<AccountId, U128> {
+        let mut pool_cache: HashMap<u64, Pool> = reserves
+            .into_iter()
+            .map(|(pool_id, amounts)| {
+                let mut pool = self.internal_get_swap_pool(pool_id);
+                pool.override_amounts(&amounts.into_iter().map(|amount| amount.0).collect::<Vec<_>>());
+                (pool_id, pool)
+            })
+            .collect();
+        let mut token_cache = TokenCache(token_deposit.into_iter().map(|(k, v)| (k, v.into())).collect());
+
+        self.internal_execute_actions_by_cache(
+            &mut pool_cache,
+            &mut token_cache,
+            &None,
+            &actions,
+            ActionResult::None,
+        );
+        token_cache.0.into_iter().map(|(k, v)| (k, v.into())).collect()
+    }
+
+    /// Same as `get_return` on the given reserves, and for rated and degen pools on the
+    /// given rates or prices if any.
+    pub fn get_return_with_reserves(
+        &self,
+        pool_id: u64,
+        token_in: ValidAccountId,
+        amount_in: U128,
+        token_out: ValidAccountId,
+        reserves: Vec<U128>,
+        rates: Option<Vec<U128>>,
+    ) -> U128 {
+        let mut pool = self.internal_get_swap_pool(pool_id);
+        pool.override_amounts(&reserves.into_iter().map(|amount| amount.0).collect::<Vec<_>>());
+        let admin_fees = AdminFees::new(self.admin_fee_bps);
+        let rates: Option<Vec<Balance>> = rates.map(|rates| rates.into_iter().map(|rate| rate.0).collect());
+        let max_amount_out = self.internal_max_swap_out(pool_id, &pool, token_out.as_ref());
+        let amount_out = match (&pool, rates) {
+            (_, None) => self.internal_quote_with_maker_rebate(pool_id, &mut pool, token_in.as_ref(), amount_in.into(), token_out.as_ref(), admin_fees),
+            (Pool::RatedSwapPool(_), rates) => pool.get_rated_return(token_in.as_ref(), amount_in.into(), token_out.as_ref(), &rates, &admin_fees),
+            (Pool::DegenSwapPool(_), degens) => pool.get_degen_return(token_in.as_ref(), amount_in.into(), token_out.as_ref(), &degens, &admin_fees),
+            _ => env::panic(b"Rates only apply to rated and degen pools"),
+        };
+        assert_within_swap_cap(max_amount_out, amount_out);
+        amount_out.into()
+    }
+
     pub fn batch_predict_swap_actions(
         &self, 
         batch_token_deposit: Vec<HashMap<AccountId, U128>>,
@@ -885,19 +1009,20 @@ impl Contract {
         );
 
         for add_liquidity_info in add_liquidity_infos {
-            let mut pool = pool_cache.remove(&add_liquidity_info.pool_id).unwrap_or(self.pools.get(add_liquidity_info.pool_id).expect(ERR85_NO_POOL));
+            let mut pool = pool_cache.remove(&add_liquidity_info.pool_id).unwrap_or_else(|| self.internal_get_pool(add_liquidity_info.pool_id));
             
             let tokens_in_pool = match &pool {
                 Pool::SimplePool(p) => p.token_account_ids.clone(),
                 Pool::RatedSwapPool(p) => p.token_account_ids.clone(),
                 Pool::StableSwapPool(p) => p.token_account_ids.clone(),
                 Pool::DegenSwapPool(p) => p.token_account_ids.clone(),
+                Pool::RangePool(p) => p.pool.token_account_ids.clone(),
             };
             
             let mut add_liquidity_amounts = add_liquidity_info.amounts.iter().map(|v| v.0).collect();
             
             let shares = match pool {
-                Pool::SimplePool(_) => {
+                Pool::SimplePool(_) | Pool::RangePool(_) => {
                     let shares = pool.add_liquidity(
                         &view_account_id,
                         &mut add_liquidity_amounts,
@@ -936,7 +1061,7 @@ impl Contract {
     }
 
     pub fn get_degen_pool_tvl(&self, pool_id: u64) -> U128 {
-        self.pools.get(pool_id).expect(ERR85_NO_POOL).get_tvl().into()
+        self.internal_get_pool(pool_id).get_tvl().into()
     }
 
     pub fn get_pool_limit_by_pool_id(&self, pool_id: u64) -> Option<VPoolLimitInfo> {
@@ -0,0 +1,91 @@
+//! Versioned views with a frozen JSON schema, kept next to the original views.
+//!
+//! Fields of the `*V2` structs carry explicit serde renames so refactoring the Rust names
+//! never changes the JSON. Fields are never renamed, removed or retyped; new ones are only
+//! appended, as `Option`s so older serializations still parse. A breaking change means a
+//! `views_v3` module, with VIEWS_SCHEMA_VERSION bumped.
+
+use crate::*;
+
+pub const VIEWS_SCHEMA_VERSION: u32 = 2;
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+#[cfg_attr(not(target_arch = "wasm32"), derive(Debug, PartialEq))]
+pub struct PoolInfoV2 {
+    #[serde(rename = "pool_id")]
+    pub pool_id: u64,
+    #[serde(rename = "pool_kind")]
+    pub pool_kind: String,
+    #[serde(rename = "token_account_ids")]
+    pub token_account_ids: Vec<AccountId>,
+    #[serde(rename = "amounts")]
+    pub amounts: Vec<U128>,
+    #[serde(rename = "total_fee")]
+    pub total_fee: u32,
+    #[serde(rename = "shares_total_supply")]
+    pub shares_total_supply: U128,
+    /// 0 for simple and range pools.
+    #[serde(rename = "amp")]
+    pub amp: u64,
+}
+
+impl PoolInfoV2 {
+    fn new(pool_id: u64, pool: PoolInfo) -> Self {
+        Self {
+            pool_id,
+            pool_kind: pool.pool_kind,
+            token_account_ids: pool.token_account_ids,
+            amounts: pool.amounts,
+            total_fee: pool.total_fee,
+            shares_total_supply: pool.shares_total_supply,
+            amp: pool.amp,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+#[cfg_attr(not(target_arch = "wasm32"), derive(Debug, PartialEq))]
+pub struct TokenDepositV2 {
+    #[serde(rename = "token_id")]
+    pub token_id: AccountId,
+    #[serde(rename = "amount")]
+    pub amount: U128,
+}
+
+#[near_bindgen]
+impl Contract {
+    pub fn get_views_schema_version(&self) -> u32 {
+        VIEWS_SCHEMA_VERSION
+    }
+
+    /// Same as `get_pool`, with the pool id included.
+    pub fn get_pool_v2(&self, pool_id: u64) -> PoolInfoV2 {
+        PoolInfoV2::new(pool_id, self.get_pool(pool_id))
+    }
+
+    /// Same as `get_pools`.
+    pub fn get_pools_v2(&self, from_index: u64, limit: u64) -> Vec<PoolInfoV2> {
+        (from_index..std::cmp::min(from_index + limit, self.pools.len()))
+            .map(|pool_id| self.get_pool_v2(pool_id))
+            .collect()
+    }
+
+    /// Same as `get_pool_by_ids`.
+    pub fn get_pool_by_ids_v2(&self, pool_ids: Vec<u64>) -> Vec<PoolInfoV2> {
+        pool_ids.into_iter()
+            .map(|pool_id| self.get_pool_v2(pool_id))
+            .collect()
+    }
+
+    /// Same as `get_deposits`, as a list ordered by token id.
+    pub fn get_deposits_v2(&self, account_id: ValidAccountId) -> Vec<TokenDepositV2> {
+        let mut deposits: Vec<TokenDepositV2> = self.get_deposits(account_id)
+            .into_iter()
+            .map(|(token_id, amount)| TokenDepositV2 { token_id, amount })
+            .collect();
+        deposits.sort_by(|a, b| a.token_id.cmp(&b.token_id));
+        deposits
+    }
+}
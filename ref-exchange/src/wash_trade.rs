@@ -0,0 +1,154 @@
+use crate::*;
+use crate::utils::{to_nano, u128_ratio};
+
+/// A reverse swap by the same account in the same pool within the wash trade window is a round trip.
+/// The filter is off until the owner sets a window.
+pub const DEFAULT_WASH_TRADE_WINDOW_SEC: u32 = 0;
+
+/// Last swap of an account in a pool that can still be matched by a reverse swap.
+#[derive(BorshSerialize, BorshDeserialize, Clone)]
+pub struct RecentSwap {
+    pub token_in: AccountId,
+    pub token_out: AccountId,
+    pub amount_in: Balance,
+    pub amount_out: Balance,
+    pub timestamp: u64,
+}
+
+pub fn read_wash_trade_window_from_storage() -> u32 {
+    if let Some(content) = env::storage_read(WASH_TRADE_WINDOW.as_bytes()) {
+        u32::try_from_slice(&content).expect("deserialize wash trade window failed.")
+    } else {
+        DEFAULT_WASH_TRADE_WINDOW_SEC
+    }
+}
+
+pub fn write_wash_trade_window_to_storage(window_sec: u32) {
+    env::storage_write(
+        WASH_TRADE_WINDOW.as_bytes(),
+        &window_sec.try_to_vec().unwrap(),
+    );
+}
+
+pub fn read_recent_swaps_from_storage() -> LookupMap<(AccountId, u64), RecentSwap> {
+    if let Some(content) = env::storage_read(RECENT_SWAPS.as_bytes()) {
+        LookupMap::try_from_slice(&content).expect("deserialize recent swaps failed.")
+    } else {
+        LookupMap::new(StorageKey::RecentSwaps)
+    }
+}
+
+pub fn write_recent_swaps_to_storage(recent_swaps: LookupMap<(AccountId, u64), RecentSwap>) {
+    env::storage_write(
+        RECENT_SWAPS.as_bytes(),
+        &recent_swaps.try_to_vec().unwrap(),
+    );
+}
+
+impl Contract {
+    /// Records a finished swap of `trader_id`, and if it reverses the trader's previous swap in the same pool
+    /// within the wash trade window, takes the matched round trip out of the pool volumes.
+    /// Fees were already charged by the swaps themselves and are left untouched.
+    /// The record is kept per account and pool, until it's matched or pruned with `prune_recent_swaps`.
+    pub(crate) fn internal_net_wash_trade(
+        &mut self,
+        trader_id: &AccountId,
+        pool_id: u64,
+        token_in: &AccountId,
+        amount_in: Balance,
+        token_out: &AccountId,
+        amount_out: Balance,
+    ) {
+        let window_sec = read_wash_trade_window_from_storage();
+        if window_sec == 0 || amount_in == 0 {
+            return;
+        }
+        let now = env::block_timestamp();
+        let mut recent_swaps = read_recent_swaps_from_storage();
+        let key = (trader_id.clone(), pool_id);
+        let remainder = match recent_swaps.get(&key) {
+            Some(prev) if &prev.token_in == token_out
+                && &prev.token_out == token_in
+                && now <= prev.timestamp + to_nano(window_sec) => {
+                // The part of this swap that hands back the previous output is the round trip.
+                let (cur_in, cur_out, prev_in, prev_out, remainder) = if amount_in <= prev.amount_out {
+                    let prev_in = u128_ratio(prev.amount_in, amount_in, prev.amount_out);
+                    let remainder = RecentSwap {
+                        amount_in: prev.amount_in - prev_in,
+                        amount_out: prev.amount_out - amount_in,
+                        ..prev.clone()
+                    };
+                    (amount_in, amount_out, prev_in, amount_in, remainder)
+                } else {
+                    let cur_out = u128_ratio(amount_out, prev.amount_out, amount_in);
+                    let remainder = RecentSwap {
+                        token_in: token_in.clone(),
+                        token_out: token_out.clone(),
+                        amount_in: amount_in - prev.amount_out,
+                        amount_out: amount_out - cur_out,
+                        timestamp: now,
+                    };
+                    (prev.amount_out, cur_out, prev.amount_in, prev.amount_out, remainder)
+                };
+                let mut pool = self.internal_get_pool(pool_id);
+                pool.net_volumes(token_in, cur_in, token_out, cur_out);
+                pool.net_volumes(token_out, prev_in, token_in, prev_out);
+                self.internal_save_pool(pool_id, &pool);
+                log!("Round trip of {} in pool {} netted out of volumes", trader_id, pool_id);
+                remainder
+            }
+            _ => RecentSwap {
+                token_in: token_in.clone(),
+                token_out: token_out.clone(),
+                amount_in,
+                amount_out,
+                timestamp: now,
+            },
+        };
+        if remainder.amount_in == 0 || remainder.amount_out == 0 {
+            recent_swaps.remove(&key);
+        } else {
+            recent_swaps.insert(&key, &remainder);
+        }
+        write_recent_swaps_to_storage(recent_swaps);
+    }
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Set the round trip window in seconds, 0 turns the wash trade filter off.
+    #[payable]
+    pub fn set_wash_trade_window(&mut self, window_sec: u32) {
+        assert_one_yocto();
+        self.assert_owner();
+        audit_privileged_action("set_wash_trade_window");
+        write_wash_trade_window_to_storage(window_sec);
+    }
+
+    /// Drops the recent swaps of the given accounts in the given pools that fell out of the window
+    /// and can no longer be matched. Anyone can call it. Returns the number removed.
+    pub fn prune_recent_swaps(&mut self, swaps: Vec<(ValidAccountId, u64)>) -> u32 {
+        let window = to_nano(read_wash_trade_window_from_storage());
+        let now = env::block_timestamp();
+        let mut recent_swaps = read_recent_swaps_from_storage();
+        let mut removed = 0;
+        for (account_id, pool_id) in swaps {
+            let key = (account_id.into(), pool_id);
+            let expired = recent_swaps.get(&key)
+                .map(|recent_swap| now > recent_swap.timestamp + window)
+                .unwrap_or(false);
+            if expired {
+                recent_swaps.remove(&key);
+                removed += 1;
+            }
+        }
+        if removed > 0 {
+            write_recent_swaps_to_storage(recent_swaps);
+        }
+        removed
+    }
+
+    pub fn get_wash_trade_window(&self) -> u32 {
+        read_wash_trade_window_from_storage()
+    }
+}
@@ -0,0 +1,93 @@
+use crate::*;
+use crate::utils::{u128_ratio, FEE_DIVISOR};
+
+/// Upper bound of a token's withdraw fee, in bps.
+pub const MAX_WITHDRAW_FEE_BPS: u32 = 100;
+
+/// Exit fee some bridged token issuers require, taken from withdrawals of the token.
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone)]
+#[serde(crate = "near_sdk::serde")]
+#[cfg_attr(not(target_arch = "wasm32"), derive(Debug, PartialEq))]
+pub struct WithdrawFee {
+    pub fee_bps: u32,
+    /// Inner account the fee is credited to, e.g. the treasury or the token issuer.
+    pub recipient_id: AccountId,
+}
+
+pub fn read_withdraw_fees_from_storage() -> HashMap<AccountId, WithdrawFee> {
+    if let Some(content) = env::storage_read(WITHDRAW_FEES.as_bytes()) {
+        HashMap::try_from_slice(&content).expect("deserialize withdraw fees failed.")
+    } else {
+        HashMap::new()
+    }
+}
+
+pub fn write_withdraw_fees_to_storage(withdraw_fees: HashMap<AccountId, WithdrawFee>) {
+    env::storage_write(
+        WITHDRAW_FEES.as_bytes(),
+        &withdraw_fees.try_to_vec().unwrap(),
+    );
+}
+
+/// Withdraw fee of the token out of `amount` leaving the exchange, 0 if the token has none.
+pub fn withdraw_fee_of(token_id: &AccountId, amount: Balance) -> Balance {
+    read_withdraw_fees_from_storage()
+        .get(token_id)
+        .map(|withdraw_fee| u128_ratio(amount, withdraw_fee.fee_bps as u128, FEE_DIVISOR as u128))
+        .unwrap_or(0)
+}
+
+impl Contract {
+    /// Credits a withdraw fee held during its transfer to the token's fee recipient once the
+    /// transfer succeeded, or to lostfound if the recipient is gone or can't hold it.
+    pub(crate) fn internal_credit_withdraw_fee(&mut self, token_id: &AccountId, fee: Balance) {
+        if fee == 0 {
+            return;
+        }
+        let recipient_id = read_withdraw_fees_from_storage()
+            .remove(token_id)
+            .map(|withdraw_fee| withdraw_fee.recipient_id);
+        let credited = match recipient_id.as_ref().and_then(|recipient_id| self.internal_get_account(recipient_id)) {
+            Some(mut account) => {
+                let deposited = account.deposit_with_storage_check(token_id, fee);
+                if deposited {
+                    self.accounts.insert(recipient_id.as_ref().unwrap(), &account.into());
+                }
+                deposited
+            }
+            None => false,
+        };
+        if credited {
+            log!("Withdraw fee {} {} credited to {}", fee, token_id, recipient_id.unwrap());
+        } else {
+            self.internal_lostfound(token_id, fee);
+        }
+    }
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Set the fee taken from withdrawals of a token, at most MAX_WITHDRAW_FEE_BPS. None removes it.
+    #[payable]
+    pub fn set_token_withdraw_fee(&mut self, token_id: ValidAccountId, withdraw_fee: Option<WithdrawFee>) {
+        assert_one_yocto();
+        self.assert_owner();
+        audit_privileged_action("set_token_withdraw_fee");
+        let mut withdraw_fees = read_withdraw_fees_from_storage();
+        if let Some(withdraw_fee) = withdraw_fee {
+            assert!(
+                withdraw_fee.fee_bps > 0 && withdraw_fee.fee_bps <= MAX_WITHDRAW_FEE_BPS,
+                "Withdraw fee must be within (0, {}] bps", MAX_WITHDRAW_FEE_BPS
+            );
+            assert!(self.accounts.contains_key(&withdraw_fee.recipient_id), "{}", ERR10_ACC_NOT_REGISTERED);
+            withdraw_fees.insert(token_id.into(), withdraw_fee);
+        } else {
+            withdraw_fees.remove(token_id.as_ref());
+        }
+        write_withdraw_fees_to_storage(withdraw_fees);
+    }
+
+    pub fn get_token_withdraw_fees(&self) -> HashMap<AccountId, WithdrawFee> {
+        read_withdraw_fees_from_storage()
+    }
+}
@@ -0,0 +1,58 @@
+use crate::*;
+use crate::utils::{u128_ratio, FEE_DIVISOR};
+
+pub fn read_withdrawal_caps_from_storage() -> LookupMap<u64, u32> {
+    if let Some(content) = env::storage_read(WITHDRAWAL_CAPS.as_bytes()) {
+        LookupMap::try_from_slice(&content).expect("deserialize withdrawal caps failed.")
+    } else {
+        LookupMap::new(StorageKey::WithdrawalCaps)
+    }
+}
+
+pub fn write_withdrawal_caps_to_storage(withdrawal_caps: LookupMap<u64, u32>) {
+    env::storage_write(
+        WITHDRAWAL_CAPS.as_bytes(),
+        &withdrawal_caps.try_to_vec().unwrap(),
+    );
+}
+
+impl Contract {
+    /// Panics if a single removal takes more of any reserve than the pool's withdrawal cap.
+    /// `reserves` are the pool amounts before the removal.
+    pub(crate) fn assert_within_withdrawal_cap(&self, pool_id: u64, reserves: &[Balance], amounts: &[Balance]) {
+        if let Some(max_withdrawal_bps) = read_withdrawal_caps_from_storage().get(&pool_id) {
+            for (reserve, amount) in reserves.iter().zip(amounts.iter()) {
+                assert!(
+                    *amount <= u128_ratio(*reserve, max_withdrawal_bps as u128, FEE_DIVISOR as u128),
+                    "Withdrawal exceeds {} bps of pool reserves", max_withdrawal_bps
+                );
+            }
+        }
+    }
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Limit the part of each reserve a single liquidity removal can take from a stable-like pool,
+    /// larger exits have to be split. None removes the cap.
+    #[payable]
+    pub fn set_pool_withdrawal_cap(&mut self, pool_id: u64, max_withdrawal_bps: Option<u32>) {
+        assert_one_yocto();
+        assert!(self.is_owner_or_guardians(), "{}", ERR100_NOT_ALLOWED);
+        audit_privileged_action("set_pool_withdrawal_cap");
+        let pool = self.internal_get_pool(pool_id);
+        assert!(!matches!(pool, Pool::SimplePool(_)), "Simple pools have no withdrawal cap");
+        let mut withdrawal_caps = read_withdrawal_caps_from_storage();
+        if let Some(max_withdrawal_bps) = max_withdrawal_bps {
+            assert!(max_withdrawal_bps > 0 && max_withdrawal_bps <= FEE_DIVISOR, "Invalid max_withdrawal_bps");
+            withdrawal_caps.insert(&pool_id, &max_withdrawal_bps);
+        } else {
+            withdrawal_caps.remove(&pool_id);
+        }
+        write_withdrawal_caps_to_storage(withdrawal_caps);
+    }
+
+    pub fn get_pool_withdrawal_cap(&self, pool_id: u64) -> Option<u32> {
+        read_withdrawal_caps_from_storage().get(&pool_id)
+    }
+}
@@ -0,0 +1,201 @@
+use crate::*;
+use crate::multi_fungible_token::canonical_mft_token_id;
+use crate::utils::to_nano;
+use near_sdk::json_types::U64;
+use near_sdk::Timestamp;
+
+pub const WITHDRAWAL_LIMIT_DAY_SEC: u32 = 86400;
+/// Raising or removing a daily withdrawal limit only takes effect after this delay,
+/// so a compromised key can't lift it and drain the account at once.
+pub const WITHDRAWAL_LIMIT_CHANGE_DELAY_SEC: u32 = 2 * 86400;
+
+#[derive(BorshSerialize, BorshDeserialize, Clone)]
+pub struct WithdrawalLimitChange {
+    /// None removes the limit.
+    pub daily_limit: Option<Balance>,
+    pub effective_at: Timestamp,
+}
+
+/// Cap an account set on its own withdrawals of a token or pool shares, by mft token id, per day
+/// since the unix epoch. Once an account has any, tokens and shares without one can't leave it.
+#[derive(BorshSerialize, BorshDeserialize, Clone)]
+pub struct DailyWithdrawalLimit {
+    pub daily_limit: Balance,
+    pub day: u64,
+    /// Withdrawn during `day`, failed transfers credited back still count.
+    pub withdrawn: Balance,
+    pub pending_change: Option<WithdrawalLimitChange>,
+}
+
+impl DailyWithdrawalLimit {
+    /// The limit with a due pending change applied, None once a removal is due.
+    fn current(mut self, now: Timestamp) -> Option<Self> {
+        match self.pending_change.clone() {
+            Some(change) if change.effective_at <= now => {
+                self.pending_change = None;
+                change.daily_limit.map(|daily_limit| Self { daily_limit, ..self })
+            }
+            _ => Some(self),
+        }
+    }
+
+    fn withdrawn_on(&self, day: u64) -> Balance {
+        if self.day == day { self.withdrawn } else { 0 }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+#[cfg_attr(not(target_arch = "wasm32"), derive(Debug))]
+pub struct WithdrawalLimitChangeInfo {
+    pub daily_limit: Option<U128>,
+    pub effective_at: U64,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+#[cfg_attr(not(target_arch = "wasm32"), derive(Debug))]
+pub struct DailyWithdrawalLimitInfo {
+    pub daily_limit: U128,
+    pub withdrawn_today: U128,
+    pub pending_change: Option<WithdrawalLimitChangeInfo>,
+}
+
+pub fn read_withdrawal_limits_from_storage() -> LookupMap<AccountId, HashMap<AccountId, DailyWithdrawalLimit>> {
+    if let Some(content) = env::storage_read(WITHDRAWAL_LIMITS.as_bytes()) {
+        LookupMap::try_from_slice(&content).expect("deserialize withdrawal limits failed.")
+    } else {
+        LookupMap::new(StorageKey::WithdrawalLimits)
+    }
+}
+
+pub fn write_withdrawal_limits_to_storage(withdrawal_limits: LookupMap<AccountId, HashMap<AccountId, DailyWithdrawalLimit>>) {
+    env::storage_write(
+        WITHDRAWAL_LIMITS.as_bytes(),
+        &withdrawal_limits.try_to_vec().unwrap(),
+    );
+}
+
+fn current_withdrawal_day() -> u64 {
+    env::block_timestamp() / to_nano(WITHDRAWAL_LIMIT_DAY_SEC)
+}
+
+impl Contract {
+    /// Counts `amount` of the token or shares, by mft token id, leaving the account towards its
+    /// daily limit, if it set any, and panics once the limit would be exceeded.
+    pub(crate) fn internal_record_limited_withdrawal(&mut self, account_id: &AccountId, token_id: &AccountId, amount: Balance) {
+        let mut withdrawal_limits = read_withdrawal_limits_from_storage();
+        let now = env::block_timestamp();
+        let mut limits: HashMap<AccountId, DailyWithdrawalLimit> = match withdrawal_limits.get(account_id) {
+            Some(limits) => limits
+                .into_iter()
+                .filter_map(|(limited_token_id, limit)| {
+                    let limit = limit.current(now);
+                    if limit.is_none() {
+                        log!("Daily withdrawal limit of {} for {} removed", limited_token_id, account_id);
+                    }
+                    limit.map(|limit| (limited_token_id, limit))
+                })
+                .collect(),
+            None => return,
+        };
+        if !limits.is_empty() {
+            let day = current_withdrawal_day();
+            let limit = limits.get_mut(token_id).unwrap_or_else(|| env::panic(
+                format!("No daily withdrawal limit of {}, needed while others are set", token_id).as_bytes()
+            ));
+            let withdrawn = limit.withdrawn_on(day) + amount;
+            assert!(withdrawn <= limit.daily_limit, "Daily withdrawal limit of {} {} exceeded", limit.daily_limit, token_id);
+            limit.day = day;
+            limit.withdrawn = withdrawn;
+        }
+        if limits.is_empty() {
+            withdrawal_limits.remove(account_id);
+        } else {
+            withdrawal_limits.insert(account_id, &limits);
+        }
+        write_withdrawal_limits_to_storage(withdrawal_limits);
+    }
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Cap the caller's withdrawals of a token, or of pool shares as ":<pool_id>", per day, None to
+    /// remove the cap. While the caller has any cap, tokens and shares without one can't leave the
+    /// account, so a first cap of the account or a lower one applies at once and drops any pending
+    /// change, while raising, removing or adding one next to others applies
+    /// WITHDRAWAL_LIMIT_CHANGE_DELAY_SEC later. Withdrawals, swap outputs sent to wallets, mft
+    /// transfers, share streams, share lock transfers, launch auction bids and wNEAR paid for
+    /// storage and sent back on unregister count.
+    /// Attached deposit covers the record, the rest is refunded.
+    #[payable]
+    pub fn set_daily_withdrawal_limit(&mut self, token_id: String, daily_limit: Option<U128>) {
+        assert!(env::attached_deposit() > 0, "{}", ERR35_AT_LEAST_ONE_YOCTO);
+        let prev_storage = env::storage_usage();
+        let account_id = env::predecessor_account_id();
+        let token_id = canonical_mft_token_id(token_id);
+        assert!(
+            env::is_valid_account_id(token_id.as_bytes())
+                || token_id.strip_prefix(':').map(|pool_id| pool_id.parse::<u64>().is_ok()).unwrap_or(false),
+            "Invalid token id"
+        );
+        let now = env::block_timestamp();
+        let mut withdrawal_limits = read_withdrawal_limits_from_storage();
+        let mut limits: HashMap<AccountId, DailyWithdrawalLimit> = withdrawal_limits.get(&account_id)
+            .unwrap_or_default()
+            .into_iter()
+            .filter_map(|(limited_token_id, limit)| limit.current(now).map(|limit| (limited_token_id, limit)))
+            .collect();
+        let current = limits.remove(&token_id).or_else(|| {
+            // a token without a cap is locked while others are capped
+            if !limits.is_empty() && daily_limit.is_some() {
+                Some(DailyWithdrawalLimit { daily_limit: 0, day: 0, withdrawn: 0, pending_change: None })
+            } else {
+                None
+            }
+        });
+        let limit = match (current, daily_limit.map(|daily_limit| daily_limit.0)) {
+            (None, Some(daily_limit)) => Some(DailyWithdrawalLimit { daily_limit, day: 0, withdrawn: 0, pending_change: None }),
+            (Some(limit), Some(daily_limit)) if daily_limit <= limit.daily_limit => {
+                Some(DailyWithdrawalLimit { daily_limit, pending_change: None, ..limit })
+            }
+            (Some(limit), daily_limit) => {
+                let effective_at = now + to_nano(WITHDRAWAL_LIMIT_CHANGE_DELAY_SEC);
+                log!("Daily withdrawal limit of {} changes to {:?} at {}", token_id, daily_limit, effective_at);
+                Some(DailyWithdrawalLimit { pending_change: Some(WithdrawalLimitChange { daily_limit, effective_at }), ..limit })
+            }
+            (None, None) => None,
+        };
+        if let Some(limit) = limit {
+            limits.insert(token_id, limit);
+        }
+        if limits.is_empty() {
+            withdrawal_limits.remove(&account_id);
+        } else {
+            withdrawal_limits.insert(&account_id, &limits);
+        }
+        write_withdrawal_limits_to_storage(withdrawal_limits);
+        self.internal_check_storage(prev_storage);
+    }
+
+    pub fn get_daily_withdrawal_limits(&self, account_id: ValidAccountId) -> HashMap<AccountId, DailyWithdrawalLimitInfo> {
+        let now = env::block_timestamp();
+        let day = current_withdrawal_day();
+        read_withdrawal_limits_from_storage()
+            .get(account_id.as_ref())
+            .unwrap_or_default()
+            .into_iter()
+            .filter_map(|(token_id, limit)| {
+                let limit = limit.current(now)?;
+                Some((token_id, DailyWithdrawalLimitInfo {
+                    daily_limit: limit.daily_limit.into(),
+                    withdrawn_today: limit.withdrawn_on(day).into(),
+                    pending_change: limit.pending_change.map(|change| WithdrawalLimitChangeInfo {
+                        daily_limit: change.daily_limit.map(U128),
+                        effective_at: change.effective_at.into(),
+                    }),
+                }))
+            })
+            .collect()
+    }
+}
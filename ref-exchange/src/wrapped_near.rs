@@ -0,0 +1,60 @@
+use crate::*;
+
+pub fn read_extra_wnear_ids_from_storage() -> Vec<AccountId> {
+    if let Some(content) = env::storage_read(EXTRA_WNEAR_IDS.as_bytes()) {
+        Vec::try_from_slice(&content).expect("deserialize extra wnear ids failed.")
+    } else {
+        vec![]
+    }
+}
+
+pub fn write_extra_wnear_ids_to_storage(extra_wnear_ids: Vec<AccountId>) {
+    env::storage_write(
+        EXTRA_WNEAR_IDS.as_bytes(),
+        &extra_wnear_ids.try_to_vec().unwrap(),
+    );
+}
+
+impl Contract {
+    /// Whether the token wraps NEAR and can be unwrapped with `near_withdraw` on withdrawal.
+    pub(crate) fn is_wrapped_near(&self, token_id: &AccountId) -> bool {
+        self.wnear_id.as_ref() == Some(token_id) || read_extra_wnear_ids_from_storage().contains(token_id)
+    }
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Add wrapped NEAR tokens unwrapped like `wnear_id`, e.g. for a migrating or forked deployment.
+    #[payable]
+    pub fn extend_extra_wnear_ids(&mut self, token_ids: Vec<ValidAccountId>) {
+        assert_one_yocto();
+        self.assert_owner();
+        audit_privileged_action("extend_extra_wnear_ids");
+        let mut extra_wnear_ids = read_extra_wnear_ids_from_storage();
+        for token_id in token_ids {
+            let token_id: AccountId = token_id.into();
+            if !extra_wnear_ids.contains(&token_id) {
+                extra_wnear_ids.push(token_id);
+            }
+        }
+        write_extra_wnear_ids_to_storage(extra_wnear_ids);
+    }
+
+    #[payable]
+    pub fn remove_extra_wnear_ids(&mut self, token_ids: Vec<ValidAccountId>) {
+        assert_one_yocto();
+        self.assert_owner();
+        audit_privileged_action("remove_extra_wnear_ids");
+        let mut extra_wnear_ids = read_extra_wnear_ids_from_storage();
+        for token_id in token_ids {
+            let index = extra_wnear_ids.iter().position(|id| id == token_id.as_ref()).expect("Not an extra wnear id");
+            extra_wnear_ids.remove(index);
+        }
+        write_extra_wnear_ids_to_storage(extra_wnear_ids);
+    }
+
+    /// Returns all tokens unwrapped to NEAR on withdrawal, `wnear_id` first.
+    pub fn get_wnear_ids(&self) -> Vec<AccountId> {
+        self.wnear_id.clone().into_iter().chain(read_extra_wnear_ids_from_storage()).collect()
+    }
+}
@@ -186,6 +186,17 @@ pub fn get_deposits(
     view!(pool.get_deposits(account_id)).unwrap_json::<HashMap<String, U128>>()
 }
 
+pub fn get_unclaimed_withdrawal(
+    pool: &ContractAccount<Exchange>,
+    account_id: ValidAccountId,
+    token_id: ValidAccountId,
+) -> u128 {
+    view!(pool.get_unclaimed_withdrawal(account_id, token_id))
+        .unwrap_json::<Option<ref_exchange::UnclaimedWithdrawal>>()
+        .map(|unclaimed_withdrawal| unclaimed_withdrawal.amount)
+        .unwrap_or(0)
+}
+
 pub fn list_referrals(pool: &ContractAccount<Exchange>) -> HashMap<String, u32> {
     view!(pool.list_referrals(None, None)).unwrap_json::<HashMap<String, u32>>()
 }
@@ -346,7 +357,7 @@ pub fn setup_pool_with_liquidity() -> (
         contract_id: swap(),
         bytes: &EXCHANGE_WASM_BYTES,
         signer_account: root,
-        init_method: new(to_va("owner".to_string()), to_va("boost_farm".to_string()), to_va("burrowland".to_string()), 5, 0)
+        init_method: new(to_va("owner".to_string()), to_va("boost_farm".to_string()), to_va("burrowland".to_string()), 5, 0, None)
     );
     let token1 = test_token(&root, dai(), vec![swap()]);
     let token2 = test_token(&root, eth(), vec![swap()]);
@@ -447,7 +458,7 @@ pub fn setup_stable_pool_with_liquidity(
         contract_id: swap(),
         bytes: &EXCHANGE_WASM_BYTES,
         signer_account: root,
-        init_method: new(owner.valid_account_id(), to_va("boost_farm".to_string()), to_va("burrowland".to_string()), 2000, 0)
+        init_method: new(owner.valid_account_id(), to_va("boost_farm".to_string()), to_va("burrowland".to_string()), 2000, 0, None)
     );
 
     let mut token_contracts: Vec<ContractAccount<TestToken>> = vec![];
@@ -538,7 +549,7 @@ pub fn setup_rated_pool_with_liquidity(
         contract_id: swap(),
         bytes: &EXCHANGE_WASM_BYTES,
         signer_account: root,
-        init_method: new(owner.valid_account_id(), to_va("boost_farm".to_string()), to_va("burrowland".to_string()), 2000, 0)
+        init_method: new(owner.valid_account_id(), to_va("boost_farm".to_string()), to_va("burrowland".to_string()), 2000, 0, None)
     );
 
     let mut pool_tokens = vec![];
@@ -656,7 +667,7 @@ pub fn setup_degen_pool(
         contract_id: swap(),
         bytes: &EXCHANGE_WASM_BYTES,
         signer_account: root,
-        init_method: new(owner.valid_account_id(), to_va("boost_farm".to_string()), to_va("burrowland".to_string()), 2000, 0)
+        init_method: new(owner.valid_account_id(), to_va("boost_farm".to_string()), to_va("burrowland".to_string()), 2000, 0, None)
     );
 
     let mut token_contracts: Vec<ContractAccount<TestToken>> = vec![];
@@ -739,7 +750,7 @@ pub fn setup_rated_pool(
         contract_id: swap(),
         bytes: &EXCHANGE_WASM_BYTES,
         signer_account: root,
-        init_method: new(owner.valid_account_id(), to_va("boost_farm".to_string()), to_va("burrowland".to_string()), 2000, 0)
+        init_method: new(owner.valid_account_id(), to_va("boost_farm".to_string()), to_va("burrowland".to_string()), 2000, 0, None)
     );
 
     let mut pool_tokens = vec![];
@@ -818,6 +829,34 @@ pub fn setup_pyth_oracle(root: &UserAccount) -> ContractAccount<PythOracle>{
     )
 }
 
+pub fn set_price_oracle_behavior(
+    root: &UserAccount,
+    oracle: &ContractAccount<PriceOracle>,
+    delay_sec: u32,
+    failure_mode: Option<mock_price_oracle::FailureMode>,
+) {
+    call!(root, oracle.set_delay_sec(delay_sec)).assert_success();
+    call!(root, oracle.set_failure_mode(failure_mode)).assert_success();
+}
+
+pub fn set_pyth_oracle_behavior(
+    root: &UserAccount,
+    oracle: &ContractAccount<PythOracle>,
+    delay_sec: u32,
+    failure_mode: Option<mock_pyth::FailureMode>,
+) {
+    call!(root, oracle.set_delay_sec(delay_sec)).assert_success();
+    call!(root, oracle.set_failure_mode(failure_mode)).assert_success();
+}
+
+pub fn set_rated_token_fail_price_calls(
+    root: &UserAccount,
+    token: &ContractAccount<TestRatedToken>,
+    fail: bool,
+) {
+    call!(root, token.set_fail_price_calls(fail)).assert_success();
+}
+
 pub fn mint_and_deposit_rated_token(
     user: &UserAccount,
     token: &ContractAccount<TestRatedToken>,
@@ -894,7 +933,7 @@ pub fn setup_exchange(root: &UserAccount, admin_fee_bps: u32) -> (
         contract_id: swap(),
         bytes: &EXCHANGE_WASM_BYTES,
         signer_account: root,
-        init_method: new(to_va("owner".to_string()), to_va("boost_farm".to_string()), to_va("burrowland".to_string()), admin_fee_bps, 0)
+        init_method: new(to_va("owner".to_string()), to_va("boost_farm".to_string()), to_va("burrowland".to_string()), admin_fee_bps, 0, None)
     );
     (owner, pool)
 }
@@ -446,3 +446,72 @@ fn degen_limit() {
     out_come.assert_success();
     println!("{:#?}", get_logs(&out_come));
 }
+
+#[test]
+fn degen_price_oracle_failure() {
+    let (root, owner, pool, _) = 
+        setup_degen_pool(
+            vec![eth(), near()],
+            vec![100000*ONE_ETH, 100000*ONE_NEAR],
+            vec![18, 24],
+            25,
+            10000,
+        );
+    let price_oracle_contract = setup_price_oracle(&root);
+    call!(
+        root,
+        price_oracle_contract.set_price_data(eth(), Price {
+            multiplier: 10000,
+            decimals: 22,
+        })
+    ).assert_success();
+    call!(
+        owner, 
+        pool.register_degen_oracle_config(DegenOracleConfig::PriceOracle(PriceOracleConfig { 
+            oracle_id: price_oracle(), 
+            expire_ts: 3600 * 10u64.pow(9), 
+            maximum_recency_duration_sec: 90, 
+            maximum_staleness_duration_sec: 90
+        })),
+        deposit = 1
+    )
+    .assert_success();
+    call!(
+        owner, 
+        pool.register_degen_token(to_va(eth()), DegenType::PriceOracle { decimals: 18 }),
+        deposit = 1
+    )
+    .assert_success();
+    call!(root, pool.update_degen_token_price(to_va(eth()))).assert_success();
+    let degen_price = |pool: &near_sdk_sim::ContractAccount<ref_exchange::ContractContract>| {
+        view!(pool.list_degen_tokens()).unwrap_json::<HashMap<String, DegenTokenInfo>>()[&eth()].degen_price.0
+    };
+    let initial_price = degen_price(&pool);
+    assert!(initial_price > 0);
+
+    call!(
+        root,
+        price_oracle_contract.set_price_data(eth(), Price {
+            multiplier: 20000,
+            decimals: 22,
+        })
+    ).assert_success();
+
+    // failed or empty oracle calls keep the last price
+    set_price_oracle_behavior(&root, &price_oracle_contract, 0, Some(mock_price_oracle::FailureMode::Panic));
+    call!(root, pool.update_degen_token_price(to_va(eth())));
+    assert_eq!(degen_price(&pool), initial_price);
+
+    set_price_oracle_behavior(&root, &price_oracle_contract, 0, Some(mock_price_oracle::FailureMode::MissingPrice));
+    call!(root, pool.update_degen_token_price(to_va(eth())));
+    assert_eq!(degen_price(&pool), initial_price);
+
+    // stale prices are not taken either
+    set_price_oracle_behavior(&root, &price_oracle_contract, 3600, None);
+    call!(root, pool.update_degen_token_price(to_va(eth())));
+    assert_eq!(degen_price(&pool), initial_price);
+
+    set_price_oracle_behavior(&root, &price_oracle_contract, 0, None);
+    call!(root, pool.update_degen_token_price(to_va(eth()))).assert_success();
+    assert_eq!(degen_price(&pool), initial_price * 2);
+}
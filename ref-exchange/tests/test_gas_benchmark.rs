@@ -0,0 +1,212 @@
+use std::fs::OpenOptions;
+use std::io::Write;
+
+use near_sdk::json_types::U128;
+use near_sdk_sim::{call, to_yocto, view, ContractAccount, ExecutionResult, UserAccount};
+
+use ref_exchange::{ContractContract as Exchange, SwapAction};
+use crate::common::utils::*;
+pub mod common;
+
+const ONE_LPT: u128 = 1000000000000000000;
+const ONE_DAI: u128 = 1000000000000000000;
+const ONE_USDT: u128 = 1000000;
+const ONE_USDC: u128 = 1000000;
+const ONE_FRAX: u128 = 1000000000000000000;
+const TGAS: u64 = 1_000_000_000_000;
+
+/// LP accounts spread over the stable pool, overridable with GAS_BENCH_LPS.
+const DEFAULT_LPS: u64 = 1000;
+
+/// Most gas each benchmarked operation may burn over all its receipts, in Tgas.
+/// Raise a limit only together with the change that needs it.
+const GAS_LIMITS: &[(&str, u64)] = &[
+    ("stable_swap", 40),
+    ("stable_add_liquidity", 50),
+    ("stable_remove_liquidity", 50),
+    ("stable_remove_liquidity_by_tokens", 50),
+    ("share_transfer", 30),
+    ("simple_swap", 30),
+    ("simple_add_liquidity", 40),
+    ("simple_remove_liquidity", 40),
+    ("multi_hop_swap", 60),
+    ("withdraw", 50),
+];
+
+fn total_gas_burnt(outcome: &ExecutionResult) -> u64 {
+    outcome
+        .promise_results()
+        .iter()
+        .map(|result| result.as_ref().map(|result| result.gas_burnt()).unwrap_or(0))
+        .sum()
+}
+
+struct GasReport {
+    version: String,
+    lps: u64,
+    entries: Vec<(&'static str, u64)>,
+}
+
+impl GasReport {
+    fn record(&mut self, operation: &'static str, outcome: ExecutionResult) {
+        outcome.assert_success();
+        self.entries.push((operation, total_gas_burnt(&outcome)));
+    }
+
+    /// Appends the gas table as csv to GAS_BENCH_REPORT if set to compare versions,
+    /// and fails on any operation over its limit.
+    fn finish(self) {
+        if let Ok(path) = std::env::var("GAS_BENCH_REPORT") {
+            let csv: String = self.entries.iter()
+                .map(|(operation, gas)| format!("{},{},{},{}\n", self.version, self.lps, operation, gas))
+                .collect();
+            let mut file = OpenOptions::new().create(true).append(true).open(path).unwrap();
+            file.write_all(csv.as_bytes()).unwrap();
+        }
+        let exceeded: Vec<String> = self.entries.iter().filter_map(|(operation, gas)| {
+            let (_, limit) = GAS_LIMITS.iter().find(|(name, _)| name == operation).expect("No gas limit");
+            if *gas > limit * TGAS {
+                Some(format!("{} burnt {} over {} Tgas", operation, gas, limit))
+            } else {
+                None
+            }
+        }).collect();
+        assert!(exceeded.is_empty(), "Gas limits exceeded: {:?}", exceeded);
+    }
+}
+
+fn spread_shares(root: &UserAccount, pool: &ContractAccount<Exchange>, pool_id: u64, lps: u64) {
+    let token_id = format!(":{}", pool_id);
+    for i in 0..lps {
+        let lp = to_va(format!("lp{}", i));
+        call!(
+            root,
+            pool.mft_register(token_id.clone(), lp.clone()),
+            deposit = to_yocto("0.01")
+        )
+        .assert_success();
+        call!(
+            root,
+            pool.mft_transfer(token_id.clone(), lp, U128(ONE_LPT), None),
+            deposit = 1
+        )
+        .assert_success();
+    }
+}
+
+#[test]
+fn gas_benchmark() {
+    let lps = std::env::var("GAS_BENCH_LPS").map(|lps| lps.parse().unwrap()).unwrap_or(DEFAULT_LPS);
+    let (root, _owner, pool, tokens) = setup_stable_pool_with_liquidity(
+        vec![dai(), usdt(), usdc(), frax()],
+        vec![1000000 * ONE_DAI, 1000000 * ONE_USDT, 1000000 * ONE_USDC, 1000000 * ONE_FRAX],
+        vec![18, 6, 6, 18],
+        5,
+        240,
+    );
+    call!(
+        root,
+        pool.add_simple_pool(vec![to_va(dai()), to_va(usdt())], 25),
+        deposit = to_yocto("1")
+    )
+    .assert_success();
+    deposit_token(
+        &root,
+        &pool,
+        tokens.iter().collect(),
+        vec![100000 * ONE_DAI, 100000 * ONE_USDT, 100000 * ONE_USDC, 100000 * ONE_FRAX],
+    );
+    call!(
+        root,
+        pool.add_liquidity(1, vec![U128(10000 * ONE_DAI), U128(10000 * ONE_USDT)], None),
+        deposit = to_yocto("0.01")
+    )
+    .assert_success();
+    spread_shares(&root, &pool, 0, lps);
+    spread_shares(&root, &pool, 1, lps);
+
+    let mut report = GasReport {
+        version: view!(pool.version()).unwrap_json::<String>(),
+        lps,
+        entries: vec![],
+    };
+
+    report.record("stable_swap", call!(
+        root,
+        pool.swap(vec![SwapAction {
+            pool_id: 0,
+            token_in: dai(),
+            amount_in: Some(U128(100 * ONE_DAI)),
+            token_out: usdc(),
+            min_amount_out: U128(1),
+        }], None),
+        deposit = 1
+    ));
+    report.record("stable_add_liquidity", call!(
+        root,
+        pool.add_stable_liquidity(0, vec![U128(100 * ONE_DAI), U128(100 * ONE_USDT), U128(100 * ONE_USDC), U128(100 * ONE_FRAX)], U128(1)),
+        deposit = to_yocto("0.01")
+    ));
+    report.record("stable_remove_liquidity", call!(
+        root,
+        pool.remove_liquidity(0, U128(100 * ONE_LPT), vec![U128(1); 4]),
+        deposit = 1
+    ));
+    report.record("stable_remove_liquidity_by_tokens", call!(
+        root,
+        pool.remove_liquidity_by_tokens(0, vec![U128(10 * ONE_DAI), U128(0), U128(10 * ONE_USDC), U128(0)], U128(100 * ONE_LPT)),
+        deposit = 1
+    ));
+    report.record("share_transfer", call!(
+        root,
+        pool.mft_transfer(":0".to_string(), to_va("lp0".to_string()), U128(ONE_LPT), None),
+        deposit = 1
+    ));
+    report.record("simple_swap", call!(
+        root,
+        pool.swap(vec![SwapAction {
+            pool_id: 1,
+            token_in: dai(),
+            amount_in: Some(U128(10 * ONE_DAI)),
+            token_out: usdt(),
+            min_amount_out: U128(1),
+        }], None),
+        deposit = 1
+    ));
+    report.record("simple_add_liquidity", call!(
+        root,
+        pool.add_liquidity(1, vec![U128(100 * ONE_DAI), U128(100 * ONE_USDT)], None),
+        deposit = to_yocto("0.01")
+    ));
+    report.record("simple_remove_liquidity", call!(
+        root,
+        pool.remove_liquidity(1, U128(ONE_LPT), vec![U128(1); 2]),
+        deposit = 1
+    ));
+    report.record("multi_hop_swap", call!(
+        root,
+        pool.swap(vec![
+            SwapAction {
+                pool_id: 1,
+                token_in: dai(),
+                amount_in: Some(U128(10 * ONE_DAI)),
+                token_out: usdt(),
+                min_amount_out: U128(1),
+            },
+            SwapAction {
+                pool_id: 0,
+                token_in: usdt(),
+                amount_in: None,
+                token_out: frax(),
+                min_amount_out: U128(1),
+            },
+        ], None),
+        deposit = 1
+    ));
+    report.record("withdraw", call!(
+        root,
+        pool.withdraw(to_va(dai()), U128(ONE_DAI), None, None),
+        deposit = 1
+    ));
+    report.finish();
+}
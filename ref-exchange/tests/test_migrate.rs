@@ -24,7 +24,7 @@ fn test_upgrade() {
         signer_account: root,
         init_method: new(ValidAccountId::try_from(root.account_id.clone()).unwrap(),
                 ValidAccountId::try_from("boost_farm".to_string()).unwrap(),
-                ValidAccountId::try_from("burrowland".to_string()).unwrap(), 4, 1)
+                ValidAccountId::try_from("burrowland".to_string()).unwrap(), 4, 1, None)
     );
     let metadata = get_metadata(&pool);
     assert_eq!(metadata.version, "1.9.2".to_string());
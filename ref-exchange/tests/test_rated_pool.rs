@@ -1313,7 +1313,7 @@ fn sim_rated_swap_lp_storage() {
     call!(
         user,
         pool.storage_deposit(None, None),
-        deposit = to_yocto("0.0025")
+        deposit = to_yocto("0.00404")
     )
     .assert_success();
 
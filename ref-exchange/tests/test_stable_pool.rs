@@ -408,7 +408,7 @@ fn sim_stable_lp_storage() {
     call!(
         user,
         pool.storage_deposit(None, None),
-        deposit = to_yocto("0.0025")
+        deposit = to_yocto("0.00404")
     )
     .assert_success();
 
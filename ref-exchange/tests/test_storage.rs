@@ -1,6 +1,7 @@
 /// The storage in REF consists of inner-account storage (A storage) and LP-token storage (T storage).
 /// For A storage:
 ///   Basic cost is 0.00102 Near (102 bytes),
+///   Registration takes a one-off 0.00154 Near (154 bytes) for the replica export index on top,
 ///   Each token cost is 0.00148 Near (148 bytes),
 ///   Following actions will examine A storage:
 ///     ft::ft_transfer_call to deposit token into,
@@ -38,7 +39,7 @@ fn storage_scenario_01() {
     )
     .assert_success();
     let sb = get_storage_balance(&pool, new_user.valid_account_id()).unwrap();
-    assert_eq!(sb.total.0, to_yocto("1"));
+    assert_eq!(sb.total.0, to_yocto("0.99846"));
     assert_eq!(sb.total.0 - sb.available.0, to_yocto("0.00102"));
     let orig_user_balance = new_user.account().unwrap().amount;
 
@@ -56,7 +57,7 @@ fn storage_scenario_01() {
     // println!("{}", new_user.account().unwrap().amount - orig_user_balance);
     assert!(
         new_user.account().unwrap().amount - orig_user_balance > 
-        to_yocto("0.998")
+        to_yocto("0.997")
     );
 
     println!("Storage Case 0102: deposit token would fail with insufficient storage deposit");
@@ -309,7 +310,7 @@ fn storage_scenario_02() {
     call!(
         user3,
         pool.storage_deposit(None, None),
-        deposit = to_yocto("0.00546")
+        deposit = to_yocto("0.00700")
     )
     .assert_success();
 
@@ -148,7 +148,7 @@ fn setup_pool_with_liquidity() -> (
         contract_id: swap(),
         bytes: &EXCHANGE_WASM_BYTES,
         signer_account: root,
-        init_method: new(to_va("owner".to_string()), to_va("boost_farm".to_string()), to_va("burrowland".to_string()), 5, 0)
+        init_method: new(to_va("owner".to_string()), to_va("boost_farm".to_string()), to_va("burrowland".to_string()), 5, 0, None)
     );
     let token1 = test_token(&root, dai(), vec![swap()]);
     let token2 = test_token(&root, eth(), vec![swap()]);
@@ -285,7 +285,7 @@ fn test_withdraw_failure() {
         contract_id: swap(),
         bytes: &EXCHANGE_WASM_BYTES,
         signer_account: root,
-        init_method: new(to_va("owner".to_string()), to_va("boost_farm".to_string()), to_va("burrowland".to_string()), 5, 0)
+        init_method: new(to_va("owner".to_string()), to_va("boost_farm".to_string()), to_va("burrowland".to_string()), 5, 0, None)
     );
     // Deploy DAI and wETH fungible tokens
     let dai_contract = test_token(&root, dai(), vec![swap()]);
@@ -458,7 +458,7 @@ fn test_direct_swap_wnear() {
         contract_id: swap(),
         bytes: &EXCHANGE_WASM_BYTES,
         signer_account: root,
-        init_method: new(to_va("owner".to_string()), to_va("boost_farm".to_string()), to_va("burrowland".to_string()), 5, 0)
+        init_method: new(to_va("owner".to_string()), to_va("boost_farm".to_string()), to_va("burrowland".to_string()), 5, 0, None)
     );
     call!(
         owner,
@@ -568,7 +568,7 @@ fn test_direct_swap_wnear_by_output() {
         contract_id: swap(),
         bytes: &EXCHANGE_WASM_BYTES,
         signer_account: root,
-        init_method: new(to_va("owner".to_string()), to_va("boost_farm".to_string()), to_va("burrowland".to_string()), 5, 0)
+        init_method: new(to_va("owner".to_string()), to_va("boost_farm".to_string()), to_va("burrowland".to_string()), 5, 0, None)
     );
     call!(
         owner,
@@ -798,7 +798,7 @@ fn test_execute_actions_in_va() {
         contract_id: swap(),
         bytes: &EXCHANGE_WASM_BYTES,
         signer_account: root,
-        init_method: new(to_va("owner".to_string()), to_va("boost_farm".to_string()), to_va("burrowland".to_string()), 30, 0)
+        init_method: new(to_va("owner".to_string()), to_va("boost_farm".to_string()), to_va("burrowland".to_string()), 30, 0, None)
     );
     call!(
         owner,
@@ -914,3 +914,103 @@ fn test_execute_actions_in_va() {
     println!("{:#?}", get_logs(&out_come));
     println!("{:#?}", out_come.unwrap_json::<HashMap<AccountId, U128>>());
 }
+
+#[test]
+fn test_near_deposit_and_execute() {
+    let root = init_simulator(None);
+    let owner = root.create_user("owner".to_string(), to_yocto("100"));
+    let pool = deploy!(
+        contract: Exchange,
+        contract_id: swap(),
+        bytes: &EXCHANGE_WASM_BYTES,
+        signer_account: root,
+        init_method: new(to_va("owner".to_string()), to_va("boost_farm".to_string()), to_va("burrowland".to_string()), 5, 0, None)
+    );
+    call!(
+        owner,
+        pool.modify_wnear_id(wnear()),
+        deposit = 1
+    )
+    .assert_success();
+    let token1 = test_token(&root, dai(), vec![swap()]);
+    let token2 = test_wnear(&root, vec![swap()]);
+    call!(
+        owner,
+        pool.extend_whitelisted_tokens(vec![to_va(dai()), to_va(wnear())]),
+        deposit=1
+    );
+    call!(
+        root,
+        pool.add_simple_pool(vec![to_va(dai()), to_va(wnear())], 25),
+        deposit = to_yocto("1")
+    )
+    .assert_success();
+    call!(
+        root,
+        pool.storage_deposit(None, None),
+        deposit = to_yocto("1")
+    )
+    .assert_success();
+    call!(
+        root,
+        token1.ft_transfer_call(to_va(swap()), to_yocto("105").into(), None, "".to_string()),
+        deposit = 1
+    )
+    .assert_success();
+    call!(
+        root,
+        token2.ft_transfer_call(to_va(swap()), to_yocto("110").into(), None, "".to_string()),
+        deposit = 1
+    )
+    .assert_success();
+    call!(
+        root,
+        pool.add_liquidity(0, vec![U128(to_yocto("5")), U128(to_yocto("10"))], None),
+        deposit = to_yocto("0.0007")
+    )
+    .assert_success();
+
+    // Unregistered user wraps 2 NEAR and swaps 1 wNEAR of it in a single call.
+    let new_user = root.create_user("new_user".to_string(), to_yocto("100"));
+    call!(
+        new_user,
+        pool.near_deposit_and_execute(
+            Some(U128(to_yocto("0.01"))),
+            vec![Action::Swap(SwapAction {
+                pool_id: 0,
+                token_in: wnear(),
+                amount_in: Some(U128(to_yocto("1"))),
+                token_out: dai(),
+                min_amount_out: U128(1)
+            })],
+            None
+        ),
+        deposit = to_yocto("2.01"),
+        gas = 300000000000000
+    )
+    .assert_success();
+    let deposits = get_deposits(&pool, to_va(new_user.account_id.clone()));
+    assert_eq!(deposits.get(&wnear()).unwrap().0, to_yocto("1"));
+    assert!(deposits.get(&dai()).unwrap().0 > 0);
+    assert_eq!(wnear_balance_of(&token2, &swap()), to_yocto("112"));
+
+    // A failing action leaves the wrapped NEAR deposited.
+    call!(
+        new_user,
+        pool.near_deposit_and_execute(
+            None,
+            vec![Action::Swap(SwapAction {
+                pool_id: 0,
+                token_in: wnear(),
+                amount_in: Some(U128(to_yocto("1"))),
+                token_out: dai(),
+                min_amount_out: U128(to_yocto("100"))
+            })],
+            None
+        ),
+        deposit = to_yocto("1"),
+        gas = 300000000000000
+    );
+    let deposits = get_deposits(&pool, to_va(new_user.account_id.clone()));
+    assert_eq!(deposits.get(&wnear()).unwrap().0, to_yocto("2"));
+}
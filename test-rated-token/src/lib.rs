@@ -19,6 +19,8 @@ pub struct Contract {
     symbol: String,
     icon: Option<String>,
     decimals: u8,
+    /// Price calls panic, to test the failure paths of rate updates.
+    fail_price_calls: bool,
 }
 
 #[near_bindgen]
@@ -31,7 +33,8 @@ impl Contract {
             symbol,
             icon: None,
             decimals,
-            price: price.0
+            price: price.0,
+            fail_price_calls: false,
         }
     }
 
@@ -40,16 +43,20 @@ impl Contract {
         log!("{} set price to {}", env::predecessor_account_id(), price.0);
     }
 
+    pub fn set_fail_price_calls(&mut self, fail_price_calls: bool) {
+        self.fail_price_calls = fail_price_calls;
+    }
+
     pub fn get_st_near_price(&self) -> U128 {
-        U128(self.price)
+        self.internal_price()
     }
 
     pub fn ft_price(&self) -> U128 {
-        U128(self.price)
+        self.internal_price()
     }
 
     pub fn get_nearx_price(&self) -> U128 {
-        U128(self.price)
+        self.internal_price()
     }
 
     pub fn mint(&mut self, account_id: ValidAccountId, amount: U128) {
@@ -63,6 +70,15 @@ impl Contract {
     }
 }
 
+impl Contract {
+    fn internal_price(&self) -> U128 {
+        if self.fail_price_calls {
+            env::panic(b"Price call failure");
+        }
+        U128(self.price)
+    }
+}
+
 near_contract_standards::impl_fungible_token_core!(Contract, token);
 near_contract_standards::impl_fungible_token_storage!(Contract, token);
 
@@ -129,4 +145,14 @@ mod tests {
         assert_eq!(contract.get_st_near_price().0, 2 * 10u128.pow(24 as u32));
         assert_eq!(contract.get_nearx_price().0, 2 * 10u128.pow(24 as u32));
     }
+
+    #[test]
+    #[should_panic(expected = "Price call failure")]
+    fn test_fail_price_calls() {
+        let context = VMContextBuilder::new();
+        testing_env!(context.build());
+        let mut contract = Contract::new(String::from("TBD"), String::from("TBD"), 24, U128(10u128.pow(24 as u32)));
+        contract.set_fail_price_calls(true);
+        contract.ft_price();
+    }
 }
\ No newline at end of file